@@ -0,0 +1,41 @@
+// Times the recursive `get_numbers` sample with and without method caches, on both the tree
+// walker and the VM. Run with `cargo bench`.
+use std::time::{Duration, Instant};
+
+use jasper::interpreter::interpreter::Interpreter;
+
+// The sample from data/my_program.lang, with its recursion fixed so that it terminates: every
+// subset of a 16 item list, which takes about 130,000 calls.
+static PROGRAM: &str = "def get_numbers(list, index = 0, taken = [])
+  return [taken] if index == list.size
+  get_numbers(list, index + 1, taken) +
+  get_numbers(list, index + 1, taken + [list[index]])
+end
+get_numbers([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]).size
+";
+
+const RUNS: usize = 5;
+
+fn best_of(vm: bool, caches: bool) -> Duration {
+    (0..RUNS).map(|_| {
+        let mut interp = Interpreter::create();
+        interp.set_vm(vm);
+        interp.set_method_caches(caches);
+        let start = Instant::now();
+        let result = interp.eval_source("get_numbers", PROGRAM).unwrap_or_else(|_| panic!("get_numbers raised"));
+        let elapsed = start.elapsed();
+        assert_eq!(interp.inspect(result).unwrap(), "65536");
+        elapsed
+    }).min().unwrap()
+}
+
+fn main() {
+    for (label, vm) in [("tree", false), ("vm", true)] {
+        let uncached = best_of(vm, false);
+        let cached = best_of(vm, true);
+        println!(
+            "get_numbers/{:<4}  no caches {:>8.1?}  caches {:>8.1?}  speedup {:.2}x",
+            label, uncached, cached, uncached.as_secs_f64() / cached.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,475 @@
+use std::cmp::Ordering;
+
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::runtime::heap::ObjectKind;
+use crate::interpreter::runtime::value::Value;
+use super::{array_size, compare, conversion_error, enumerable, expect_integer, expect_string, sort_values};
+
+pub fn install(interp: &mut Interpreter) {
+    let array = interp.core.array;
+    interp.define_builtin(array, "initialize", initialize);
+    interp.define_builtin(array, "[]", index);
+    interp.define_builtin(array, "slice", index);
+    interp.define_builtin(array, "[]=", index_assign);
+    interp.define_builtin(array, "at", |interp, recv, args, _| {
+        interp.check_args(args, 1, Some(1))?;
+        index(interp, recv, args, None)
+    });
+    interp.define_builtin(array, "length", length);
+    interp.define_builtin(array, "size", length);
+    interp.define_builtin(array, "empty?", |interp, recv, _, _| Ok(Value::from_bool(items_of(interp, recv)?.is_empty())));
+    interp.define_builtin(array, "push", push);
+    interp.define_builtin(array, "append", push);
+    interp.define_builtin(array, "<<", push);
+    interp.define_builtin(array, "pop", pop);
+    interp.define_builtin(array, "shift", shift);
+    interp.define_builtin(array, "unshift", unshift);
+    interp.define_builtin(array, "prepend", unshift);
+    interp.define_builtin(array, "insert", insert);
+    interp.define_builtin(array, "concat", concat);
+    interp.define_builtin(array, "delete", delete);
+    interp.define_builtin(array, "delete_at", delete_at);
+    interp.define_builtin(array, "clear", |interp, recv, _, _| {
+        update(interp, recv, |items| items.clear())?;
+        Ok(recv)
+    });
+    interp.define_builtin(array, "replace", |interp, recv, args, _| {
+        interp.check_args(args, 1, Some(1))?;
+        let other = items_of(interp, args[0])?;
+        update(interp, recv, |items| *items = other)?;
+        Ok(recv)
+    });
+    interp.define_builtin(array, "first", first);
+    interp.define_builtin(array, "last", last);
+    interp.define_builtin(array, "+", add);
+    interp.define_builtin(array, "-", difference);
+    interp.define_builtin(array, "*", repeat);
+    interp.define_builtin(array, "==", equal);
+    interp.define_builtin(array, "<=>", cmp);
+    interp.define_builtin(array, "include?", include);
+    interp.define_builtin(array, "index", find_index);
+    interp.define_builtin(array, "join", join);
+    interp.define_builtin(array, "reverse", |interp, recv, _, _| {
+        let mut items = items_of(interp, recv)?;
+        items.reverse();
+        Ok(interp.new_array(items))
+    });
+    interp.define_builtin(array, "sort", sort);
+    interp.define_builtin(array, "sort!", |interp, recv, args, block| {
+        let sorted = sort(interp, recv, args, block)?;
+        let sorted = items_of(interp, sorted)?;
+        update(interp, recv, |items| *items = sorted)?;
+        Ok(recv)
+    });
+    interp.define_builtin(array, "uniq", uniq);
+    interp.define_builtin(array, "compact", |interp, recv, _, _| {
+        let items = items_of(interp, recv)?.into_iter().filter(|v| !v.is_nil()).collect();
+        Ok(interp.new_array(items))
+    });
+    interp.define_builtin(array, "flatten", flatten);
+    interp.define_builtin(array, "each", each);
+    interp.define_builtin(array, "to_a", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(array, "to_ary", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(array, "inspect", inspect);
+    interp.define_builtin(array, "to_s", inspect);
+}
+
+// The items are rooted: the caller may run code that takes them out of the array.
+pub fn items_of(interp: &mut Interpreter, v: Value) -> Result<Vec<Value>, Unwind> {
+    match interp.array_items(v).cloned() {
+        Some(items) => {
+            items.iter().for_each(|item| interp.root(*item));
+            Ok(items)
+        },
+        None => Err(conversion_error(interp, v, "Array"))
+    }
+}
+
+// Mutates an array in place after checking it isn't frozen.
+pub fn update<F, R>(interp: &mut Interpreter, v: Value, f: F) -> Result<R, Unwind>
+where
+    F: FnOnce(&mut Vec<Value>) -> R
+{
+    interp.check_frozen(v)?;
+    match v.as_object().map(|r| &mut interp.heap.get_mut(r).kind) {
+        Some(ObjectKind::Array(items)) => Ok(f(items)),
+        _ => Err(conversion_error(interp, v, "Array"))
+    }
+}
+
+// Resolves a possibly negative index against a length.
+fn normalize(i: i64, len: usize) -> Option<usize> {
+    let i = if i < 0 { i + len as i64 } else { i };
+    if i < 0 { None } else { Some(i as usize) }
+}
+
+// The `(start, count)` a range selects from a sequence of `len` elements; `None` when it starts
+// before the beginning.
+pub fn range_span(interp: &mut Interpreter, start: Value, end: Value, exclusive: bool, len: usize) -> Result<Option<(usize, usize)>, Unwind> {
+    let start = expect_integer(interp, start)?;
+    // An endless range runs to the last element whether or not it excludes its end.
+    let (end, exclusive) = if end.is_nil() { (-1, false) } else { (expect_integer(interp, end)?, exclusive) };
+    Ok(match (normalize(start, len), normalize(end, len)) {
+        (Some(start), Some(end)) => Some((start, (end + 1).saturating_sub(start + exclusive as usize))),
+        (Some(start), None) => Some((start, 0)),
+        _ => None
+    })
+}
+
+fn initialize(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(2))?;
+    let size = match args.first() {
+        Some(v) => array_size(interp, *v)?,
+        None => 0
+    };
+    let mut items = Vec::with_capacity(size);
+    for i in 0..size {
+        items.push(match block {
+            Some(block) => interp.call_proc(block, &[Value::Integer(i as i64)], None)?,
+            None => args.get(1).copied().unwrap_or(Value::Nil)
+        });
+    }
+    update(interp, recv, |existing| *existing = items)?;
+    Ok(Value::Nil)
+}
+
+// `a[i]`, `a[start, length]` and `a[range]`.
+fn index(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let items = items_of(interp, recv)?;
+    let range = match args[0].as_object().map(|r| &interp.heap.get(r).kind) {
+        Some(ObjectKind::Range { start, end, exclusive }) => Some((*start, *end, *exclusive)),
+        _ => None
+    };
+    let (start, count) = match (range, args.get(1)) {
+        (Some((start, end, exclusive)), _) => match range_span(interp, start, end, exclusive, items.len())? {
+            Some(span) => span,
+            None => return Ok(Value::Nil)
+        },
+        (None, Some(count)) => {
+            let count = expect_integer(interp, *count)?;
+            match normalize(expect_integer(interp, args[0])?, items.len()) {
+                Some(start) if count >= 0 => (start, count as usize),
+                _ => return Ok(Value::Nil)
+            }
+        },
+        (None, None) => {
+            let i = expect_integer(interp, args[0])?;
+            return Ok(normalize(i, items.len()).and_then(|i| items.get(i).copied()).unwrap_or(Value::Nil));
+        }
+    };
+    if start > items.len() {
+        return Ok(Value::Nil);
+    }
+    let end = (start + count).min(items.len());
+    Ok(interp.new_array(items[start..end].to_vec()))
+}
+
+fn index_assign(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 2, Some(2))?;
+    let len = items_of(interp, recv)?.len();
+    let i = expect_integer(interp, args[0])?;
+    let i = match normalize(i, len) {
+        Some(i) => i,
+        None => return Err(interp.error(interp.core.index_error, format!("index {} too small for array; minimum: -{}", i, len)))
+    };
+    let v = args[1];
+    update(interp, recv, |items| {
+        if i >= items.len() {
+            items.resize(i + 1, Value::Nil);
+        }
+        items[i] = v;
+    })?;
+    Ok(v)
+}
+
+fn length(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    Ok(Value::Integer(items_of(interp, recv)?.len() as i64))
+}
+
+fn push(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    update(interp, recv, |items| items.extend_from_slice(args))?;
+    Ok(recv)
+}
+
+fn pop(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    Ok(update(interp, recv, |items| items.pop())?.unwrap_or(Value::Nil))
+}
+
+fn shift(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    Ok(update(interp, recv, |items| if items.is_empty() { None } else { Some(items.remove(0)) })?.unwrap_or(Value::Nil))
+}
+
+fn unshift(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    update(interp, recv, |items| {
+        items.splice(0..0, args.iter().copied());
+    })?;
+    Ok(recv)
+}
+
+fn insert(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, None)?;
+    let len = items_of(interp, recv)?.len();
+    let i = expect_integer(interp, args[0])?;
+    // Negative positions count from after the last element.
+    let at = if i < 0 { normalize(i + 1, len) } else { Some(i as usize) };
+    let at = match at {
+        Some(at) => at,
+        None => return Err(interp.error(interp.core.index_error, format!("index {} too small for array", i)))
+    };
+    update(interp, recv, |items| {
+        if at > items.len() {
+            items.resize(at, Value::Nil);
+        }
+        items.splice(at..at, args[1..].iter().copied());
+    })?;
+    Ok(recv)
+}
+
+fn concat(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    let mut added = Vec::new();
+    for arg in args.iter() {
+        added.extend(items_of(interp, *arg)?);
+    }
+    update(interp, recv, |items| items.extend(added))?;
+    Ok(recv)
+}
+
+fn delete(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let mut kept = Vec::new();
+    let mut found = Value::Nil;
+    for item in items_of(interp, recv)? {
+        if interp.values_equal(item, args[0])? {
+            found = item;
+        } else {
+            kept.push(item);
+        }
+    }
+    update(interp, recv, |items| *items = kept)?;
+    Ok(found)
+}
+
+fn delete_at(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let len = items_of(interp, recv)?.len();
+    let i = expect_integer(interp, args[0])?;
+    match normalize(i, len).filter(|i| *i < len) {
+        Some(i) => update(interp, recv, |items| items.remove(i)),
+        None => Ok(Value::Nil)
+    }
+}
+
+fn first(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let items = items_of(interp, recv)?;
+    match args.first() {
+        Some(n) => {
+            let n = array_size(interp, *n)?;
+            Ok(interp.new_array(items.into_iter().take(n).collect()))
+        },
+        None => Ok(items.first().copied().unwrap_or(Value::Nil))
+    }
+}
+
+fn last(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let items = items_of(interp, recv)?;
+    match args.first() {
+        Some(n) => {
+            let n = array_size(interp, *n)?;
+            let skip = items.len().saturating_sub(n);
+            Ok(interp.new_array(items[skip..].to_vec()))
+        },
+        None => Ok(items.last().copied().unwrap_or(Value::Nil))
+    }
+}
+
+fn add(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let mut items = items_of(interp, recv)?;
+    items.extend(items_of(interp, args[0])?);
+    Ok(interp.new_array(items))
+}
+
+fn difference(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let removed: Vec<_> = items_of(interp, args[0])?.into_iter().map(|v| interp.hash_key(v)).collect();
+    let items = items_of(interp, recv)?.into_iter().filter(|v| !removed.contains(&interp.hash_key(*v))).collect();
+    Ok(interp.new_array(items))
+}
+
+fn repeat(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let items = items_of(interp, recv)?;
+    if interp.str_ref(args[0]).is_some() {
+        return join(interp, recv, args, None);
+    }
+    let n = expect_integer(interp, args[0])?;
+    if n < 0 {
+        return Err(interp.argument_error(String::from("negative argument")));
+    }
+    Ok(interp.new_array(items.repeat(n as usize)))
+}
+
+fn equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let a = items_of(interp, recv)?;
+    let b = match interp.array_items(args[0]) {
+        Some(b) => b.clone(),
+        None => return Ok(Value::False)
+    };
+    if a.len() != b.len() {
+        return Ok(Value::False);
+    }
+    for (x, y) in a.into_iter().zip(b) {
+        if !interp.values_equal(x, y)? {
+            return Ok(Value::False);
+        }
+    }
+    Ok(Value::True)
+}
+
+fn cmp(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let a = items_of(interp, recv)?;
+    let b = match interp.array_items(args[0]) {
+        Some(b) => b.clone(),
+        None => return Ok(Value::Nil)
+    };
+    for (x, y) in a.iter().zip(b.iter()) {
+        match compare(interp, *x, *y)? {
+            Ordering::Equal => continue,
+            o => return Ok(Value::Integer(o as i64))
+        }
+    }
+    Ok(Value::Integer(a.len().cmp(&b.len()) as i64))
+}
+
+fn include(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    for item in items_of(interp, recv)? {
+        if interp.values_equal(item, args[0])? {
+            return Ok(Value::True);
+        }
+    }
+    Ok(Value::False)
+}
+
+fn find_index(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    for (i, item) in items_of(interp, recv)?.into_iter().enumerate() {
+        let found = match (args.first(), block) {
+            (Some(target), _) => interp.values_equal(item, *target)?,
+            (None, Some(block)) => interp.call_proc(block, &[item], None)?.is_truthy(),
+            (None, None) => false
+        };
+        if found {
+            return Ok(Value::Integer(i as i64));
+        }
+    }
+    Ok(Value::Nil)
+}
+
+fn join_into(interp: &mut Interpreter, items: Vec<Value>, separator: &str, out: &mut String, first: &mut bool) -> Result<(), Unwind> {
+    for item in items {
+        if let Some(nested) = interp.array_items(item).cloned() {
+            join_into(interp, nested, separator, out, first)?;
+            continue;
+        }
+        if !*first {
+            out.push_str(separator);
+        }
+        *first = false;
+        out.push_str(&interp.to_s(item)?);
+    }
+    Ok(())
+}
+
+fn join(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let separator = match args.first() {
+        Some(Value::Nil) | None => String::new(),
+        Some(v) => expect_string(interp, *v)?
+    };
+    let items = items_of(interp, recv)?;
+    let mut out = String::new();
+    join_into(interp, items, &separator, &mut out, &mut true)?;
+    Ok(interp.new_string(out))
+}
+
+pub fn sort(interp: &mut Interpreter, recv: Value, _args: &[Value], block: Option<Value>) -> EvalResult {
+    let items = items_of(interp, recv)?;
+    let sorted = match block {
+        Some(block) => sort_values(interp, items, &mut |interp, a, b| {
+            let result = interp.call_proc(block, &[a, b], None)?;
+            match result {
+                Value::Integer(n) => Ok(n.cmp(&0)),
+                _ => compare(interp, a, b)
+            }
+        })?,
+        None => sort_values(interp, items, &mut compare)?
+    };
+    Ok(interp.new_array(sorted))
+}
+
+fn uniq(interp: &mut Interpreter, recv: Value, _args: &[Value], block: Option<Value>) -> EvalResult {
+    let mut seen = Vec::new();
+    let mut kept = Vec::new();
+    for item in items_of(interp, recv)? {
+        let key = match block {
+            Some(block) => interp.call_proc(block, &[item], None)?,
+            None => item
+        };
+        let key = interp.hash_key(key);
+        if !seen.contains(&key) {
+            seen.push(key);
+            kept.push(item);
+        }
+    }
+    Ok(interp.new_array(kept))
+}
+
+fn flatten_into(interp: &Interpreter, items: &[Value], depth: Option<i64>, out: &mut Vec<Value>) {
+    for item in items.iter() {
+        match interp.array_items(*item) {
+            Some(nested) if depth.is_none_or(|d| d > 0) => flatten_into(interp, nested, depth.map(|d| d - 1), out),
+            _ => out.push(*item)
+        }
+    }
+}
+
+fn flatten(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let depth = match args.first() {
+        Some(v) => Some(expect_integer(interp, *v)?),
+        None => None
+    };
+    let items = items_of(interp, recv)?;
+    let mut out = Vec::new();
+    flatten_into(interp, &items, depth, &mut out);
+    Ok(interp.new_array(out))
+}
+
+fn each(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerable::enumerator(interp, recv, "each", args))
+    };
+    // Re-reads the array each step so elements pushed by the block are visited too.
+    let mut i = 0;
+    while let Some(item) = interp.array_items(recv).and_then(|items| items.get(i).copied()) {
+        interp.call_proc_for_effect(block, &[item])?;
+        i += 1;
+    }
+    Ok(recv)
+}
+
+fn inspect(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let items = items_of(interp, recv)?;
+    let mut parts = Vec::with_capacity(items.len());
+    for item in items {
+        parts.push(if item == recv { String::from("[...]") } else { interp.inspect(item)? });
+    }
+    Ok(interp.new_string(format!("[{}]", parts.join(", "))))
+}
@@ -0,0 +1,63 @@
+use std::cmp::Ordering;
+
+use crate::interpreter::interpreter::{EvalResult, Interpreter};
+use crate::interpreter::runtime::value::Value;
+use super::compare;
+
+// Comparison operators for any class that defines `<=>` and includes `Comparable`.
+pub fn install(interp: &mut Interpreter) {
+    let comparable = interp.core.comparable;
+    interp.define_builtin(comparable, "==", equal);
+    interp.define_builtin(comparable, "<", |interp, recv, args, _| ordered(interp, recv, args, |o| o == Ordering::Less));
+    interp.define_builtin(comparable, "<=", |interp, recv, args, _| ordered(interp, recv, args, |o| o != Ordering::Greater));
+    interp.define_builtin(comparable, ">", |interp, recv, args, _| ordered(interp, recv, args, |o| o == Ordering::Greater));
+    interp.define_builtin(comparable, ">=", |interp, recv, args, _| ordered(interp, recv, args, |o| o != Ordering::Less));
+    interp.define_builtin(comparable, "between?", between);
+    interp.define_builtin(comparable, "clamp", clamp);
+}
+
+// Unlike the ordering operators, `==` is false rather than an error when `<=>` gives nil.
+fn equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    if recv.is_identical(args[0]) {
+        return Ok(Value::True);
+    }
+    let result = interp.call(recv, "<=>", &[args[0]])?;
+    Ok(Value::from_bool(matches!(result, Value::Integer(0))))
+}
+
+fn ordered(interp: &mut Interpreter, recv: Value, args: &[Value], test: fn(Ordering) -> bool) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    Ok(Value::from_bool(test(compare(interp, recv, args[0])?)))
+}
+
+fn between(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 2, Some(2))?;
+    let above = compare(interp, recv, args[0])? != Ordering::Less;
+    Ok(Value::from_bool(above && compare(interp, recv, args[1])? != Ordering::Greater))
+}
+
+// `clamp(min, max)`, or `clamp(min..max)`.
+fn clamp(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let (min, max) = match args {
+        [range] => {
+            let (start, end, exclusive) = super::range::bounds(interp, *range)?;
+            if exclusive && !end.is_nil() {
+                return Err(interp.argument_error(String::from("cannot clamp with an exclusive range")));
+            }
+            (start, end)
+        },
+        _ => (args[0], args[1])
+    };
+    if !min.is_nil() && !max.is_nil() && compare(interp, min, max)? == Ordering::Greater {
+        return Err(interp.argument_error(String::from("min argument must be less than or equal to max argument")));
+    }
+    if !min.is_nil() && compare(interp, recv, min)? == Ordering::Less {
+        return Ok(min);
+    }
+    if !max.is_nil() && compare(interp, recv, max)? == Ordering::Greater {
+        return Ok(max);
+    }
+    Ok(recv)
+}
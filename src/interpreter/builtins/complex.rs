@@ -0,0 +1,298 @@
+use std::f64::consts::PI;
+
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::runtime::heap::MethodEntry;
+use crate::interpreter::runtime::value::Value;
+use super::numeric::{self, coerce_error, to_f64};
+use super::type_name;
+
+pub fn install(interp: &mut Interpreter) {
+    let complex = interp.core.complex;
+    let singleton = interp.singleton_class(Value::Object(complex)).expect("classes have singleton classes");
+    interp.define_builtin(singleton, "rectangular", |interp, _, args, _| kernel_complex(interp, Value::Nil, args, None));
+    interp.define_builtin(singleton, "rect", |interp, _, args, _| kernel_complex(interp, Value::Nil, args, None));
+    interp.define_builtin(singleton, "polar", polar_new);
+    interp.define_builtin(complex, "+", |interp, recv, args, _| linear(interp, "+", recv, args));
+    interp.define_builtin(complex, "-", |interp, recv, args, _| linear(interp, "-", recv, args));
+    interp.define_builtin(complex, "*", mul);
+    interp.define_builtin(complex, "/", div);
+    interp.define_builtin(complex, "quo", div);
+    interp.define_builtin(complex, "**", pow);
+    interp.define_builtin(complex, "-@", |interp, recv, _, _| {
+        let (a, b) = parts_of(interp, recv)?;
+        let (a, b) = (interp.call(a, "-@", &[])?, interp.call(b, "-@", &[])?);
+        Ok(interp.new_complex(a, b))
+    });
+    interp.define_builtin(complex, "==", equal);
+    interp.define_builtin(complex, "coerce", coerce);
+    interp.define_builtin(complex, "real", |interp, recv, _, _| Ok(parts_of(interp, recv)?.0));
+    interp.define_builtin(complex, "imaginary", |interp, recv, _, _| Ok(parts_of(interp, recv)?.1));
+    interp.define_builtin(complex, "imag", |interp, recv, _, _| Ok(parts_of(interp, recv)?.1));
+    interp.define_builtin(complex, "rectangular", |interp, recv, _, _| {
+        let (a, b) = parts_of(interp, recv)?;
+        Ok(interp.new_array(vec![a, b]))
+    });
+    interp.define_builtin(complex, "rect", |interp, recv, _, _| {
+        let (a, b) = parts_of(interp, recv)?;
+        Ok(interp.new_array(vec![a, b]))
+    });
+    interp.define_builtin(complex, "abs", |interp, recv, _, _| {
+        let (a, b) = float_parts(interp, recv)?;
+        Ok(Value::Float(a.hypot(b)))
+    });
+    interp.define_builtin(complex, "magnitude", |interp, recv, _, _| interp.call(recv, "abs", &[]));
+    interp.define_builtin(complex, "abs2", |interp, recv, _, _| {
+        let (a, b) = parts_of(interp, recv)?;
+        let (aa, bb) = (interp.call(a, "*", &[a])?, interp.call(b, "*", &[b])?);
+        interp.call(aa, "+", &[bb])
+    });
+    interp.define_builtin(complex, "arg", arg);
+    interp.define_builtin(complex, "angle", arg);
+    interp.define_builtin(complex, "phase", arg);
+    interp.define_builtin(complex, "polar", |interp, recv, _, _| {
+        let magnitude = interp.call(recv, "abs", &[])?;
+        let angle = arg(interp, recv, &[], None)?;
+        Ok(interp.new_array(vec![magnitude, angle]))
+    });
+    interp.define_builtin(complex, "conjugate", conjugate);
+    interp.define_builtin(complex, "conj", conjugate);
+    interp.define_builtin(complex, "real?", |_, _, _, _| Ok(Value::False));
+    interp.define_builtin(complex, "finite?", |interp, recv, _, _| {
+        let (a, b) = float_parts(interp, recv)?;
+        Ok(Value::from_bool(a.is_finite() && b.is_finite()))
+    });
+    interp.define_builtin(complex, "to_c", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(complex, "to_f", |interp, recv, _, _| to_real(interp, recv, "to_f", "Float"));
+    interp.define_builtin(complex, "to_i", |interp, recv, _, _| to_real(interp, recv, "to_i", "Integer"));
+    interp.define_builtin(complex, "to_r", |interp, recv, _, _| to_real(interp, recv, "to_r", "Rational"));
+    interp.define_builtin(complex, "to_s", |interp, recv, _, _| {
+        let s = describe(interp, recv, "to_s")?;
+        Ok(interp.new_string(s))
+    });
+    interp.define_builtin(complex, "inspect", |interp, recv, _, _| {
+        let s = format!("({})", describe(interp, recv, "inspect")?);
+        Ok(interp.new_string(s))
+    });
+    // Complex numbers aren't ordered.
+    for name in ["<", "<=", ">", ">=", "between?", "positive?", "negative?", "%", "modulo", "divmod", "floor", "ceil", "round", "truncate", "step"] {
+        let name = interp.sym(name);
+        interp.define_method(complex, name, MethodEntry::Undefined);
+    }
+
+    let numeric = interp.core.numeric;
+    interp.define_builtin(numeric, "real", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(numeric, "imaginary", |_, _, _, _| Ok(Value::Integer(0)));
+    interp.define_builtin(numeric, "imag", |_, _, _, _| Ok(Value::Integer(0)));
+    interp.define_builtin(numeric, "real?", |_, _, _, _| Ok(Value::True));
+    interp.define_builtin(numeric, "conjugate", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(numeric, "conj", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(numeric, "to_c", |interp, recv, _, _| Ok(interp.new_complex(recv, Value::Integer(0))));
+    interp.define_builtin(numeric, "i", |interp, recv, _, _| Ok(interp.new_complex(Value::Integer(0), recv)));
+    interp.define_builtin(numeric, "arg", |interp, recv, _, _| {
+        let f = to_f64(interp, recv)?;
+        Ok(if f.is_sign_negative() { Value::Float(PI) } else { Value::Integer(0) })
+    });
+    interp.define_builtin(numeric, "angle", |interp, recv, _, _| interp.call(recv, "arg", &[]));
+    interp.define_builtin(numeric, "rectangular", |interp, recv, _, _| Ok(interp.new_array(vec![recv, Value::Integer(0)])));
+
+    let kernel = interp.core.kernel;
+    interp.define_builtin(kernel, "Complex", kernel_complex);
+}
+
+fn is_real(interp: &Interpreter, v: Value) -> bool {
+    matches!(v, Value::Integer(_) | Value::Float(_)) || interp.big_int(v).is_some() || interp.rational(v).is_some()
+}
+
+fn parts_of(interp: &mut Interpreter, v: Value) -> Result<(Value, Value), Unwind> {
+    match interp.complex_parts(v) {
+        Some(parts) => Ok(parts),
+        None => Err(super::conversion_error(interp, v, "Complex"))
+    }
+}
+
+fn float_parts(interp: &mut Interpreter, v: Value) -> Result<(f64, f64), Unwind> {
+    let (a, b) = parts_of(interp, v)?;
+    Ok((to_f64(interp, a)?, to_f64(interp, b)?))
+}
+
+// Another operand as real and imaginary parts; a real number has no imaginary part.
+fn operand(interp: &Interpreter, v: Value) -> Option<(Value, Value)> {
+    match interp.complex_parts(v) {
+        Some(parts) => Some(parts),
+        None if is_real(interp, v) => Some((v, Value::Integer(0))),
+        None => None
+    }
+}
+
+// `+` and `-`, which work part by part.
+fn linear(interp: &mut Interpreter, name: &str, recv: Value, args: &[Value]) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let (a, b) = parts_of(interp, recv)?;
+    let (c, d) = match operand(interp, args[0]) {
+        Some(parts) => parts,
+        None => return numeric::coerced(interp, name, recv, args[0])
+    };
+    let (real, imaginary) = (interp.call(a, name, &[c])?, interp.call(b, name, &[d])?);
+    Ok(interp.new_complex(real, imaginary))
+}
+
+fn product(interp: &mut Interpreter, (a, b): (Value, Value), (c, d): (Value, Value)) -> Result<(Value, Value), Unwind> {
+    let (ac, bd) = (interp.call(a, "*", &[c])?, interp.call(b, "*", &[d])?);
+    let (ad, bc) = (interp.call(a, "*", &[d])?, interp.call(b, "*", &[c])?);
+    Ok((interp.call(ac, "-", &[bd])?, interp.call(ad, "+", &[bc])?))
+}
+
+// Divides with `quo`, so integer parts give exact rationals.
+fn quotient(interp: &mut Interpreter, (a, b): (Value, Value), (c, d): (Value, Value)) -> Result<(Value, Value), Unwind> {
+    let (cc, dd) = (interp.call(c, "*", &[c])?, interp.call(d, "*", &[d])?);
+    let divisor = interp.call(cc, "+", &[dd])?;
+    let (ac, bd) = (interp.call(a, "*", &[c])?, interp.call(b, "*", &[d])?);
+    let (bc, ad) = (interp.call(b, "*", &[c])?, interp.call(a, "*", &[d])?);
+    let (real, imaginary) = (interp.call(ac, "+", &[bd])?, interp.call(bc, "-", &[ad])?);
+    Ok((interp.call(real, "quo", &[divisor])?, interp.call(imaginary, "quo", &[divisor])?))
+}
+
+fn mul(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let z = parts_of(interp, recv)?;
+    match operand(interp, args[0]) {
+        Some(w) => {
+            let (real, imaginary) = product(interp, z, w)?;
+            Ok(interp.new_complex(real, imaginary))
+        },
+        None => numeric::coerced(interp, "*", recv, args[0])
+    }
+}
+
+fn div(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let z = parts_of(interp, recv)?;
+    match operand(interp, args[0]) {
+        Some(w) => {
+            let (real, imaginary) = quotient(interp, z, w)?;
+            Ok(interp.new_complex(real, imaginary))
+        },
+        None => numeric::coerced(interp, "/", recv, args[0])
+    }
+}
+
+// Integer powers multiply out exactly; any other exponent goes through the polar form in floats.
+fn pow(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let z = parts_of(interp, recv)?;
+    if let Value::Integer(n) = args[0] {
+        let mut result = (Value::Integer(1), Value::Integer(0));
+        let mut base = z;
+        let mut e = n.unsigned_abs();
+        while e > 0 {
+            if e & 1 == 1 {
+                result = product(interp, result, base)?;
+            }
+            e >>= 1;
+            if e > 0 {
+                base = product(interp, base, base)?;
+            }
+        }
+        if n < 0 {
+            result = quotient(interp, (Value::Integer(1), Value::Integer(0)), result)?;
+        }
+        return Ok(interp.new_complex(result.0, result.1));
+    }
+    let (c, d) = match operand(interp, args[0]) {
+        Some((c, d)) => (to_f64(interp, c)?, to_f64(interp, d)?),
+        None => return numeric::coerced(interp, "**", recv, args[0])
+    };
+    let (a, b) = float_parts(interp, recv)?;
+    if a == 0.0 && b == 0.0 {
+        return Ok(interp.new_complex(Value::Float(0.0), Value::Float(0.0)));
+    }
+    let (log_r, theta) = (a.hypot(b).ln(), b.atan2(a));
+    let (magnitude, angle) = ((c * log_r - d * theta).exp(), d * log_r + c * theta);
+    Ok(interp.new_complex(Value::Float(magnitude * angle.cos()), Value::Float(magnitude * angle.sin())))
+}
+
+fn equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let (a, b) = parts_of(interp, recv)?;
+    match operand(interp, args[0]) {
+        Some((c, d)) => Ok(Value::from_bool(interp.values_equal(a, c)? && interp.values_equal(b, d)?)),
+        None => Ok(Value::False)
+    }
+}
+
+fn coerce(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    match operand(interp, args[0]) {
+        Some((c, d)) => {
+            let other = if interp.complex_parts(args[0]).is_some() { args[0] } else { interp.new_complex(c, d) };
+            Ok(interp.new_array(vec![other, recv]))
+        },
+        None => Err(coerce_error(interp, recv, args[0]))
+    }
+}
+
+fn arg(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let (a, b) = float_parts(interp, recv)?;
+    Ok(Value::Float(b.atan2(a)))
+}
+
+fn conjugate(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let (a, b) = parts_of(interp, recv)?;
+    let b = interp.call(b, "-@", &[])?;
+    Ok(interp.new_complex(a, b))
+}
+
+// Only a complex number with an exact zero imaginary part converts to a real one.
+fn to_real(interp: &mut Interpreter, recv: Value, name: &str, target: &str) -> EvalResult {
+    let (a, b) = parts_of(interp, recv)?;
+    if !matches!(b, Value::Integer(0)) && interp.rational(b).is_none_or(|q| !q.is_zero()) {
+        let shown = describe(interp, recv, "to_s")?;
+        return Err(interp.error(interp.core.range_error, format!("can't convert {} into {}", shown, target)));
+    }
+    interp.call(a, name, &[])
+}
+
+// `1+2i` for `to_s` and `1+(2/3)*i` for `inspect`: the imaginary part gets a `*` when it
+// doesn't end in a digit.
+fn describe(interp: &mut Interpreter, recv: Value, method: &str) -> Result<String, Unwind> {
+    let (a, b) = parts_of(interp, recv)?;
+    let real = interp.call(a, method, &[])?;
+    let real = interp.to_s(real)?;
+    let negative = match b {
+        Value::Float(f) => f.is_sign_negative(),
+        b => interp.call(b, "negative?", &[])?.is_truthy()
+    };
+    let magnitude = interp.call(b, "abs", &[])?;
+    let magnitude = interp.call(magnitude, method, &[])?;
+    let magnitude = interp.to_s(magnitude)?;
+    let star = if magnitude.ends_with(|c: char| c.is_ascii_digit()) { "" } else { "*" };
+    Ok(format!("{}{}{}{}i", real, if negative { '-' } else { '+' }, magnitude, star))
+}
+
+// `Complex(1, 2)`, `Complex(3)`, or a complex number as it is.
+fn kernel_complex(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    for v in args.iter() {
+        let whole = args.len() == 1 && interp.complex_parts(*v).is_some();
+        if !whole && !is_real(interp, *v) {
+            let message = format!("can't convert {} into Complex", type_name(interp, *v));
+            return Err(interp.type_error(message));
+        }
+    }
+    match args {
+        [z] if interp.complex_parts(*z).is_some() => Ok(*z),
+        [a] => Ok(interp.new_complex(*a, Value::Integer(0))),
+        _ => Ok(interp.new_complex(args[0], args[1]))
+    }
+}
+
+// `Complex.polar(magnitude, angle)`, in floats.
+fn polar_new(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let magnitude = to_f64(interp, args[0])?;
+    let angle = match args.get(1) {
+        Some(v) => to_f64(interp, *v)?,
+        None => return Ok(interp.new_complex(args[0], Value::Integer(0)))
+    };
+    Ok(interp.new_complex(Value::Float(magnitude * angle.cos()), Value::Float(magnitude * angle.sin())))
+}
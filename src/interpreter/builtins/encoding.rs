@@ -0,0 +1,37 @@
+use crate::interpreter::interpreter::{EvalResult, Interpreter};
+use crate::interpreter::runtime::heap::{MethodEntry, ObjectKind};
+use crate::interpreter::runtime::value::Value;
+
+// Source and strings are always UTF-8, so `Encoding::UTF_8` is the only encoding there is.
+pub fn install(interp: &mut Interpreter) {
+    let encoding = interp.core.encoding;
+    let singleton = interp.singleton_class(Value::Object(encoding)).expect("classes have singleton classes");
+    let new = interp.sym("new");
+    interp.define_method(singleton, new, MethodEntry::Undefined);
+    interp.define_builtin(encoding, "name", name);
+    interp.define_builtin(encoding, "to_s", name);
+    interp.define_builtin(encoding, "inspect", |interp, recv, _, _| {
+        let name = name(interp, recv, &[], None)?;
+        let name = interp.to_s(name)?;
+        Ok(interp.new_string(format!("#<Encoding:{}>", name)))
+    });
+
+    let utf8 = Value::Object(interp.alloc(encoding, ObjectKind::Plain));
+    let name = interp.new_string(String::from("UTF-8"));
+    let ivar = interp.sym("@name");
+    interp.set_ivar(utf8, ivar, name).expect("fresh objects aren't frozen");
+    let constant = interp.sym("UTF_8");
+    interp.set_constant(encoding, constant, utf8);
+    let string = interp.core.string;
+    interp.define_builtin(string, "encoding", |interp, _, _, _| Ok(utf_8(interp)));
+}
+
+pub fn utf_8(interp: &mut Interpreter) -> Value {
+    let constant = interp.sym("UTF_8");
+    interp.lookup_constant_in(interp.core.encoding, constant).unwrap_or(Value::Nil)
+}
+
+fn name(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let name = interp.sym("@name");
+    Ok(interp.get_ivar(recv, name))
+}
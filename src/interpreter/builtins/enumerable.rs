@@ -0,0 +1,1049 @@
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::runtime::heap::{Cursor, EnumeratorData, HashTable, LazyStage, MethodEntry, ObjectKind, ProcBody};
+use crate::interpreter::runtime::symbols::Sym;
+use crate::interpreter::runtime::value::{ObjRef, Value};
+use super::{array, compare, conversion_error, expect_integer, expect_name, hash, type_name};
+
+// `Enumerable` builds everything on `each`, so Array, Hash, Range, Enumerator and any class that
+// defines `each` and includes the module share these methods.
+pub fn install(interp: &mut Interpreter) {
+    let enumerable = interp.core.enumerable;
+    interp.define_builtin(enumerable, "to_a", |interp, recv, _, _| to_a(interp, recv));
+    interp.define_builtin(enumerable, "entries", |interp, recv, _, _| to_a(interp, recv));
+    interp.define_builtin(enumerable, "map", map);
+    interp.define_builtin(enumerable, "collect", map);
+    interp.define_builtin(enumerable, "flat_map", flat_map);
+    interp.define_builtin(enumerable, "collect_concat", flat_map);
+    interp.define_builtin(enumerable, "select", |interp, recv, args, block| filter(interp, recv, args, block, "select", true));
+    interp.define_builtin(enumerable, "filter", |interp, recv, args, block| filter(interp, recv, args, block, "filter", true));
+    interp.define_builtin(enumerable, "reject", |interp, recv, args, block| filter(interp, recv, args, block, "reject", false));
+    interp.define_builtin(enumerable, "filter_map", filter_map);
+    interp.define_builtin(enumerable, "find", find);
+    interp.define_builtin(enumerable, "detect", find);
+    interp.define_builtin(enumerable, "find_index", find_index);
+    interp.define_builtin(enumerable, "each_with_index", each_with_index);
+    interp.define_builtin(enumerable, "each_with_object", each_with_object);
+    interp.define_builtin(enumerable, "each_slice", each_slice);
+    interp.define_builtin(enumerable, "each_cons", each_cons);
+    interp.define_builtin(enumerable, "reverse_each", reverse_each);
+    interp.define_builtin(enumerable, "reduce", reduce);
+    interp.define_builtin(enumerable, "inject", reduce);
+    interp.define_builtin(enumerable, "sum", sum);
+    interp.define_builtin(enumerable, "count", count);
+    interp.define_builtin(enumerable, "first", first);
+    interp.define_builtin(enumerable, "take", |interp, recv, args, _| {
+        interp.check_args(args, 1, Some(1))?;
+        first(interp, recv, args, None)
+    });
+    interp.define_builtin(enumerable, "drop", drop);
+    interp.define_builtin(enumerable, "take_while", take_while);
+    interp.define_builtin(enumerable, "drop_while", drop_while);
+    interp.define_builtin(enumerable, "include?", include);
+    interp.define_builtin(enumerable, "member?", include);
+    interp.define_builtin(enumerable, "all?", |interp, recv, args, block| quantify(interp, recv, args, block, Quantifier::All));
+    interp.define_builtin(enumerable, "any?", |interp, recv, args, block| quantify(interp, recv, args, block, Quantifier::Any));
+    interp.define_builtin(enumerable, "none?", |interp, recv, args, block| quantify(interp, recv, args, block, Quantifier::None));
+    interp.define_builtin(enumerable, "one?", |interp, recv, args, block| quantify(interp, recv, args, block, Quantifier::One));
+    interp.define_builtin(enumerable, "min", |interp, recv, _, block| extreme(interp, recv, block, Ordering::Less));
+    interp.define_builtin(enumerable, "max", |interp, recv, _, block| extreme(interp, recv, block, Ordering::Greater));
+    interp.define_builtin(enumerable, "min_by", |interp, recv, args, block| extreme_by(interp, recv, args, block, "min_by", Ordering::Less));
+    interp.define_builtin(enumerable, "max_by", |interp, recv, args, block| extreme_by(interp, recv, args, block, "max_by", Ordering::Greater));
+    interp.define_builtin(enumerable, "sort", |interp, recv, args, block| {
+        let items = to_a(interp, recv)?;
+        array::sort(interp, items, args, block)
+    });
+    interp.define_builtin(enumerable, "sort_by", sort_by);
+    interp.define_builtin(enumerable, "group_by", group_by);
+    interp.define_builtin(enumerable, "partition", partition);
+    interp.define_builtin(enumerable, "tally", tally);
+    interp.define_builtin(enumerable, "uniq", uniq);
+    interp.define_builtin(enumerable, "to_h", to_h);
+    interp.define_builtin(enumerable, "zip", zip);
+    interp.define_builtin(enumerable, "lazy", |interp, recv, _, _| {
+        let each = interp.sym("each");
+        Ok(new_enumerator(interp, interp.core.lazy, EnumeratorData { receiver: recv, method: each, args: Vec::new(), stages: Vec::new(), cursor: Cursor::default() }))
+    });
+
+    let enumerator_class = interp.core.enumerator;
+    let singleton = interp.singleton_class(Value::Object(enumerator_class)).expect("classes have singleton classes");
+    let new = interp.sym("new");
+    interp.define_method(singleton, new, MethodEntry::Undefined);
+    interp.define_builtin(enumerator_class, "each", each);
+    interp.define_builtin(enumerator_class, "with_index", with_index);
+    interp.define_builtin(enumerator_class, "each_with_index", with_index);
+    interp.define_builtin(enumerator_class, "with_object", with_object);
+    interp.define_builtin(enumerator_class, "size", size);
+    interp.define_builtin(enumerator_class, "next", |interp, recv, args, _| {
+        interp.check_args(args, 0, Some(0))?;
+        external_value(interp, recv, true)
+    });
+    interp.define_builtin(enumerator_class, "peek", |interp, recv, args, _| {
+        interp.check_args(args, 0, Some(0))?;
+        external_value(interp, recv, false)
+    });
+    interp.define_builtin(enumerator_class, "rewind", |interp, recv, args, _| {
+        interp.check_args(args, 0, Some(0))?;
+        set_cursor(interp, recv, Cursor::default());
+        Ok(recv)
+    });
+    interp.define_builtin(enumerator_class, "inspect", inspect);
+    interp.define_builtin(enumerator_class, "to_s", inspect);
+
+    let lazy = interp.core.lazy;
+    interp.define_builtin(lazy, "each", lazy_each);
+    interp.define_builtin(lazy, "map", |interp, recv, args, block| add_stage(interp, recv, args, block, LazyStage::Map));
+    interp.define_builtin(lazy, "collect", |interp, recv, args, block| add_stage(interp, recv, args, block, LazyStage::Map));
+    interp.define_builtin(lazy, "select", |interp, recv, args, block| add_stage(interp, recv, args, block, LazyStage::Select));
+    interp.define_builtin(lazy, "filter", |interp, recv, args, block| add_stage(interp, recv, args, block, LazyStage::Select));
+    interp.define_builtin(lazy, "reject", |interp, recv, args, block| add_stage(interp, recv, args, block, LazyStage::Reject));
+    interp.define_builtin(lazy, "filter_map", |interp, recv, args, block| add_stage(interp, recv, args, block, LazyStage::FilterMap));
+    interp.define_builtin(lazy, "flat_map", |interp, recv, args, block| add_stage(interp, recv, args, block, LazyStage::FlatMap));
+    interp.define_builtin(lazy, "take_while", |interp, recv, args, block| add_stage(interp, recv, args, block, LazyStage::TakeWhile));
+    interp.define_builtin(lazy, "drop_while", |interp, recv, args, block| add_stage(interp, recv, args, block, LazyStage::DropWhile));
+    interp.define_builtin(lazy, "take", |interp, recv, args, block| add_stage(interp, recv, args, block, LazyStage::Take));
+    interp.define_builtin(lazy, "drop", |interp, recv, args, block| add_stage(interp, recv, args, block, LazyStage::Drop));
+    interp.define_builtin(lazy, "force", |interp, recv, _, _| to_a(interp, recv));
+    interp.define_builtin(lazy, "eager", |interp, recv, _, _| Ok(enumerator(interp, recv, "each", &[])));
+    interp.define_builtin(lazy, "lazy", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(lazy, "size", |_, _, _, _| Ok(Value::Nil));
+}
+
+// -- iteration --
+
+// Several values yielded at once reach a one-parameter block as an array.
+fn packed(interp: &mut Interpreter, args: &[Value]) -> Value {
+    match args {
+        [] => Value::Nil,
+        [v] => *v,
+        _ => interp.new_array(args.to_vec())
+    }
+}
+
+fn native_block<F>(interp: &mut Interpreter, f: F) -> Value
+where
+    F: Fn(&mut Interpreter, usize, &[Value]) -> EvalResult + 'static
+{
+    interp.new_proc(ProcBody::Native(Rc::new(f)), false)
+}
+
+// Calls `recv.each` and runs `f` on each value it yields until `f` returns false.
+pub fn each_value<F>(interp: &mut Interpreter, recv: Value, f: F) -> Result<(), Unwind>
+where
+    F: Fn(&mut Interpreter, Value) -> Result<bool, Unwind> + 'static
+{
+    let block = native_block(interp, move |interp, tag, args| {
+        let v = packed(interp, args);
+        if f(interp, v)? { Ok(Value::Nil) } else { Err(Unwind::Break(Value::Nil, tag)) }
+    });
+    let each = interp.sym("each");
+    let result = interp.send(recv, each, &[], Some(block));
+    interp.catch_break(Some(block), result).map(|_| ())
+}
+
+// What an iterating method returns without a block: an Enumerator for `recv.name(*args)`.
+pub fn enumerator(interp: &mut Interpreter, recv: Value, name: &str, args: &[Value]) -> Value {
+    let method = interp.sym(name);
+    new_enumerator(interp, interp.core.enumerator, EnumeratorData { receiver: recv, method, args: args.to_vec(), stages: Vec::new(), cursor: Cursor::default() })
+}
+
+fn new_enumerator(interp: &mut Interpreter, class: ObjRef, data: EnumeratorData) -> Value {
+    Value::Object(interp.alloc(class, ObjectKind::Enumerator(data)))
+}
+
+fn data_of(interp: &mut Interpreter, v: Value) -> Result<EnumeratorData, Unwind> {
+    match v.as_object().map(|r| &interp.heap.get(r).kind) {
+        Some(ObjectKind::Enumerator(data)) => Ok(data.clone()),
+        _ => Err(conversion_error(interp, v, "Enumerator"))
+    }
+}
+
+// Values the native blocks keep between calls go in a heap array, where the collector sees them.
+fn push(interp: &mut Interpreter, acc: Value, v: Value) -> Result<(), Unwind> {
+    array::update(interp, acc, |items| items.push(v))
+}
+
+fn slot(interp: &Interpreter, acc: Value, i: usize) -> Value {
+    interp.array_items(acc).and_then(|items| items.get(i).copied()).unwrap_or(Value::Nil)
+}
+
+fn set_slot(interp: &mut Interpreter, acc: Value, i: usize, v: Value) -> Result<(), Unwind> {
+    array::update(interp, acc, |items| items[i] = v)
+}
+
+// Every value `recv.each` yields, rooted.
+pub fn values_of(interp: &mut Interpreter, recv: Value) -> Result<Vec<Value>, Unwind> {
+    let acc = to_a(interp, recv)?;
+    array::items_of(interp, acc)
+}
+
+fn to_a(interp: &mut Interpreter, recv: Value) -> EvalResult {
+    let acc = interp.new_array(Vec::new());
+    each_value(interp, recv, move |interp, v| {
+        push(interp, acc, v)?;
+        Ok(true)
+    })?;
+    Ok(acc)
+}
+
+// -- Enumerable --
+
+fn map(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "map", args))
+    };
+    let acc = interp.new_array(Vec::new());
+    each_value(interp, recv, move |interp, v| {
+        let mapped = interp.call_proc(block, &[v], None)?;
+        push(interp, acc, mapped)?;
+        Ok(true)
+    })?;
+    Ok(acc)
+}
+
+fn flat_map(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "flat_map", args))
+    };
+    let acc = interp.new_array(Vec::new());
+    each_value(interp, recv, move |interp, v| {
+        let mapped = interp.call_proc(block, &[v], None)?;
+        match interp.array_items(mapped).cloned() {
+            Some(items) => array::update(interp, acc, |out| out.extend(items))?,
+            None => push(interp, acc, mapped)?
+        }
+        Ok(true)
+    })?;
+    Ok(acc)
+}
+
+// `select` keeps the values the block accepts, `reject` the ones it doesn't.
+fn filter(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>, name: &str, keep: bool) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, name, args))
+    };
+    let acc = interp.new_array(Vec::new());
+    each_value(interp, recv, move |interp, v| {
+        if interp.call_proc(block, &[v], None)?.is_truthy() == keep {
+            push(interp, acc, v)?;
+        }
+        Ok(true)
+    })?;
+    Ok(acc)
+}
+
+fn filter_map(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "filter_map", args))
+    };
+    let acc = interp.new_array(Vec::new());
+    each_value(interp, recv, move |interp, v| {
+        let mapped = interp.call_proc(block, &[v], None)?;
+        if mapped.is_truthy() {
+            push(interp, acc, mapped)?;
+        }
+        Ok(true)
+    })?;
+    Ok(acc)
+}
+
+fn find(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "find", args))
+    };
+    let acc = interp.new_array(vec![Value::Nil]);
+    each_value(interp, recv, move |interp, v| {
+        if interp.call_proc(block, &[v], None)?.is_truthy() {
+            set_slot(interp, acc, 0, v)?;
+            return Ok(false);
+        }
+        Ok(true)
+    })?;
+    Ok(slot(interp, acc, 0))
+}
+
+// `find_index(value)`, or the index of the first value the block accepts.
+fn find_index(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let target = args.first().copied();
+    if target.is_none() && block.is_none() {
+        return Ok(enumerator(interp, recv, "find_index", args));
+    }
+    let index = Rc::new(Cell::new(0));
+    let found = Rc::new(Cell::new(false));
+    let (i, done) = (index.clone(), found.clone());
+    each_value(interp, recv, move |interp, v| {
+        let matched = match (target, block) {
+            (Some(target), _) => interp.values_equal(v, target)?,
+            (None, Some(block)) => interp.call_proc(block, &[v], None)?.is_truthy(),
+            (None, None) => false
+        };
+        if matched {
+            done.set(true);
+            return Ok(false);
+        }
+        i.set(i.get() + 1);
+        Ok(true)
+    })?;
+    Ok(if found.get() { Value::Integer(index.get()) } else { Value::Nil })
+}
+
+fn each_with_index(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "each_with_index", args))
+    };
+    let index = Rc::new(Cell::new(0));
+    each_value(interp, recv, move |interp, v| {
+        interp.call_proc_for_effect(block, &[v, Value::Integer(index.get())])?;
+        index.set(index.get() + 1);
+        Ok(true)
+    })?;
+    Ok(recv)
+}
+
+fn each_with_object(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "each_with_object", args))
+    };
+    let memo = args[0];
+    each_value(interp, recv, move |interp, v| {
+        interp.call_proc_for_effect(block, &[v, memo])?;
+        Ok(true)
+    })?;
+    Ok(memo)
+}
+
+fn slice_size(interp: &mut Interpreter, args: &[Value]) -> Result<usize, Unwind> {
+    interp.check_args(args, 1, Some(1))?;
+    match expect_integer(interp, args[0])? {
+        n if n > 0 => Ok(n as usize),
+        _ => Err(interp.argument_error(String::from("invalid size")))
+    }
+}
+
+// Yields consecutive groups of `n` values; the last group may be shorter.
+fn each_slice(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let n = slice_size(interp, args)?;
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "each_slice", args))
+    };
+    let acc = interp.new_array(Vec::new());
+    each_value(interp, recv, move |interp, v| {
+        push(interp, acc, v)?;
+        if interp.array_items(acc).map_or(0, Vec::len) == n {
+            let group = array::update(interp, acc, std::mem::take)?;
+            let group = interp.new_array(group);
+            interp.call_proc_for_effect(block, &[group])?;
+        }
+        Ok(true)
+    })?;
+    let rest = array::update(interp, acc, std::mem::take)?;
+    if !rest.is_empty() {
+        let group = interp.new_array(rest);
+        interp.call_proc(block, &[group], None)?;
+    }
+    Ok(recv)
+}
+
+// Yields every run of `n` consecutive values.
+fn each_cons(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let n = slice_size(interp, args)?;
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "each_cons", args))
+    };
+    let acc = interp.new_array(Vec::new());
+    each_value(interp, recv, move |interp, v| {
+        let window = array::update(interp, acc, |items| {
+            items.push(v);
+            if items.len() > n {
+                items.remove(0);
+            }
+            items.clone()
+        })?;
+        if window.len() == n {
+            let window = interp.new_array(window);
+            interp.call_proc_for_effect(block, &[window])?;
+        }
+        Ok(true)
+    })?;
+    Ok(recv)
+}
+
+fn reverse_each(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "reverse_each", args))
+    };
+    for v in values_of(interp, recv)?.into_iter().rev() {
+        interp.call_proc_for_effect(block, &[v])?;
+    }
+    Ok(recv)
+}
+
+// reduce { |memo, v| ... }
+// reduce(initial) { |memo, v| ... }
+// reduce(:op)
+// reduce(initial, :op)
+fn reduce(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(2))?;
+    let (initial, op): (Option<Value>, Option<Sym>) = match (args, block) {
+        ([], Some(_)) => (None, None),
+        ([initial], Some(_)) => (Some(*initial), None),
+        ([op], None) => (None, Some(expect_name(interp, *op)?)),
+        ([initial, op], _) => (Some(*initial), Some(expect_name(interp, *op)?)),
+        _ => return Err(interp.argument_error(String::from("wrong number of arguments (given 0, expected 1..2)")))
+    };
+    let acc = interp.new_array(vec![initial.unwrap_or(Value::Nil)]);
+    let started = Rc::new(Cell::new(initial.is_some()));
+    each_value(interp, recv, move |interp, v| {
+        if !started.get() {
+            started.set(true);
+            set_slot(interp, acc, 0, v)?;
+            return Ok(true);
+        }
+        let memo = slot(interp, acc, 0);
+        let memo = match op {
+            Some(op) => interp.send(memo, op, &[v], None)?,
+            None => interp.call_proc(block.expect("reduce without an operator has a block"), &[memo, v], None)?
+        };
+        set_slot(interp, acc, 0, memo)?;
+        Ok(true)
+    })?;
+    Ok(slot(interp, acc, 0))
+}
+
+fn sum(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let acc = interp.new_array(vec![args.first().copied().unwrap_or(Value::Integer(0))]);
+    each_value(interp, recv, move |interp, v| {
+        let v = match block {
+            Some(block) => interp.call_proc(block, &[v], None)?,
+            None => v
+        };
+        let memo = slot(interp, acc, 0);
+        let total = interp.call(memo, "+", &[v])?;
+        set_slot(interp, acc, 0, total)?;
+        Ok(true)
+    })?;
+    Ok(slot(interp, acc, 0))
+}
+
+// `count`, `count(value)` or `count { |v| ... }`.
+fn count(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let target = args.first().copied();
+    let total = Rc::new(Cell::new(0));
+    let counter = total.clone();
+    each_value(interp, recv, move |interp, v| {
+        let counted = match (target, block) {
+            (Some(target), _) => interp.values_equal(v, target)?,
+            (None, Some(block)) => interp.call_proc(block, &[v], None)?.is_truthy(),
+            (None, None) => true
+        };
+        if counted {
+            counter.set(counter.get() + 1);
+        }
+        Ok(true)
+    })?;
+    Ok(Value::Integer(total.get()))
+}
+
+fn take_size(interp: &mut Interpreter, v: Value) -> Result<usize, Unwind> {
+    match expect_integer(interp, v)? {
+        n if n < 0 => Err(interp.argument_error(String::from("attempt to take negative size"))),
+        n => Ok(n as usize)
+    }
+}
+
+// `first` is the first value or nil; `first(n)` an array of up to `n` values. Both stop `each`
+// once they have what they need, so they work on endless sequences.
+fn first(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let n = match args.first() {
+        Some(v) => take_size(interp, *v)?,
+        None => {
+            let acc = interp.new_array(vec![Value::Nil]);
+            each_value(interp, recv, move |interp, v| {
+                set_slot(interp, acc, 0, v)?;
+                Ok(false)
+            })?;
+            return Ok(slot(interp, acc, 0));
+        }
+    };
+    let acc = interp.new_array(Vec::new());
+    if n == 0 {
+        return Ok(acc);
+    }
+    each_value(interp, recv, move |interp, v| {
+        push(interp, acc, v)?;
+        Ok(interp.array_items(acc).map_or(0, Vec::len) < n)
+    })?;
+    Ok(acc)
+}
+
+fn drop(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let n = match expect_integer(interp, args[0])? {
+        n if n < 0 => return Err(interp.argument_error(String::from("attempt to drop negative size"))),
+        n => n
+    };
+    let acc = interp.new_array(Vec::new());
+    let skipped = Rc::new(Cell::new(0));
+    each_value(interp, recv, move |interp, v| {
+        if skipped.get() < n {
+            skipped.set(skipped.get() + 1);
+        } else {
+            push(interp, acc, v)?;
+        }
+        Ok(true)
+    })?;
+    Ok(acc)
+}
+
+fn take_while(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "take_while", args))
+    };
+    let acc = interp.new_array(Vec::new());
+    each_value(interp, recv, move |interp, v| {
+        if !interp.call_proc(block, &[v], None)?.is_truthy() {
+            return Ok(false);
+        }
+        push(interp, acc, v)?;
+        Ok(true)
+    })?;
+    Ok(acc)
+}
+
+fn drop_while(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "drop_while", args))
+    };
+    let acc = interp.new_array(Vec::new());
+    let dropping = Rc::new(Cell::new(true));
+    each_value(interp, recv, move |interp, v| {
+        if dropping.get() && interp.call_proc(block, &[v], None)?.is_truthy() {
+            return Ok(true);
+        }
+        dropping.set(false);
+        push(interp, acc, v)?;
+        Ok(true)
+    })?;
+    Ok(acc)
+}
+
+fn include(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let target = args[0];
+    let found = Rc::new(Cell::new(false));
+    let done = found.clone();
+    each_value(interp, recv, move |interp, v| {
+        if interp.values_equal(v, target)? {
+            done.set(true);
+            return Ok(false);
+        }
+        Ok(true)
+    })?;
+    Ok(Value::from_bool(found.get()))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Quantifier {
+    All,
+    Any,
+    None,
+    One
+}
+
+// `all?`, `any?`, `none?` and `one?` test each value with a pattern's `===`, the block, or its
+// own truthiness, stopping as soon as the answer is known.
+fn quantify(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>, quantifier: Quantifier) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let pattern = args.first().copied();
+    let matches = Rc::new(Cell::new(0));
+    let failed = Rc::new(Cell::new(false));
+    let (seen, fail) = (matches.clone(), failed.clone());
+    each_value(interp, recv, move |interp, v| {
+        let matched = match (pattern, block) {
+            (Some(pattern), _) => interp.case_equal(pattern, v)?,
+            (None, Some(block)) => interp.call_proc(block, &[v], None)?.is_truthy(),
+            (None, None) => v.is_truthy()
+        };
+        if matched {
+            seen.set(seen.get() + 1);
+        }
+        let decided = match quantifier {
+            Quantifier::All => !matched,
+            Quantifier::Any | Quantifier::None => matched,
+            Quantifier::One => seen.get() > 1
+        };
+        fail.set(decided);
+        Ok(!decided)
+    })?;
+    Ok(Value::from_bool(match quantifier {
+        Quantifier::All => !failed.get(),
+        Quantifier::Any => failed.get(),
+        Quantifier::None => !failed.get(),
+        Quantifier::One => matches.get() == 1
+    }))
+}
+
+// `a <=> b` from the block when there is one.
+fn block_compare(interp: &mut Interpreter, block: Option<Value>, a: Value, b: Value) -> Result<Ordering, Unwind> {
+    match block {
+        Some(block) => match interp.call_proc(block, &[a, b], None)? {
+            Value::Integer(n) => Ok(n.cmp(&0)),
+            v => {
+                let shown = type_name(interp, v);
+                Err(interp.argument_error(format!("comparison of {} with 0 failed", shown)))
+            }
+        },
+        None => compare(interp, a, b)
+    }
+}
+
+// The smallest value for `Ordering::Less`, the largest for `Ordering::Greater`; the first wins ties.
+fn extreme(interp: &mut Interpreter, recv: Value, block: Option<Value>, wanted: Ordering) -> EvalResult {
+    let mut best: Option<Value> = None;
+    for v in values_of(interp, recv)? {
+        best = match best {
+            Some(b) if block_compare(interp, block, v, b)? != wanted => Some(b),
+            _ => Some(v)
+        };
+    }
+    Ok(best.unwrap_or(Value::Nil))
+}
+
+fn extreme_by(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>, name: &str, wanted: Ordering) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, name, args))
+    };
+    let mut best: Option<(Value, Value)> = None;
+    for v in values_of(interp, recv)? {
+        let key = interp.call_proc(block, &[v], None)?;
+        best = match best {
+            Some((b, best_key)) if compare(interp, key, best_key)? != wanted => Some((b, best_key)),
+            _ => Some((v, key))
+        };
+    }
+    Ok(best.map_or(Value::Nil, |(v, _)| v))
+}
+
+fn sort_by(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "sort_by", args))
+    };
+    let values = values_of(interp, recv)?;
+    let mut keys = Vec::with_capacity(values.len());
+    for v in values.iter() {
+        keys.push(interp.call_proc(block, &[*v], None)?);
+    }
+    // Sorts positions by their keys; the merge sort keeps equal keys in their original order.
+    let positions = (0..values.len() as i64).map(Value::Integer).collect();
+    let position = |v: Value| match v {
+        Value::Integer(i) => i as usize,
+        _ => 0
+    };
+    let sorted = super::sort_values(interp, positions, &mut |interp, a, b| compare(interp, keys[position(a)], keys[position(b)]))?;
+    let sorted = sorted.into_iter().map(|p| values[position(p)]).collect();
+    Ok(interp.new_array(sorted))
+}
+
+// Appends `v` to the array stored under `key`, creating it the first time.
+fn append_to(interp: &mut Interpreter, groups: Value, key: Value, v: Value) -> Result<(), Unwind> {
+    match hash::lookup(interp, groups, key)? {
+        Some(group) => push(interp, group, v),
+        None => {
+            let group = interp.new_array(vec![v]);
+            hash::insert(interp, groups, key, group)
+        }
+    }
+}
+
+fn group_by(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "group_by", args))
+    };
+    let groups = interp.new_hash(HashTable::create());
+    each_value(interp, recv, move |interp, v| {
+        let key = interp.call_proc(block, &[v], None)?;
+        append_to(interp, groups, key, v)?;
+        Ok(true)
+    })?;
+    Ok(groups)
+}
+
+fn partition(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "partition", args))
+    };
+    let (accepted, rejected) = (interp.new_array(Vec::new()), interp.new_array(Vec::new()));
+    each_value(interp, recv, move |interp, v| {
+        let side = if interp.call_proc(block, &[v], None)?.is_truthy() { accepted } else { rejected };
+        push(interp, side, v)?;
+        Ok(true)
+    })?;
+    Ok(interp.new_array(vec![accepted, rejected]))
+}
+
+fn tally(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let counts = interp.new_hash(HashTable::create());
+    each_value(interp, recv, move |interp, v| {
+        let n = match hash::lookup(interp, counts, v)? {
+            Some(Value::Integer(n)) => n + 1,
+            _ => 1
+        };
+        hash::insert(interp, counts, v, Value::Integer(n))?;
+        Ok(true)
+    })?;
+    Ok(counts)
+}
+
+// Values compare as hash keys do, by the block's result when there is one.
+fn uniq(interp: &mut Interpreter, recv: Value, _args: &[Value], block: Option<Value>) -> EvalResult {
+    let mut seen = HashSet::new();
+    let mut kept = Vec::new();
+    for v in values_of(interp, recv)? {
+        let key = match block {
+            Some(block) => interp.call_proc(block, &[v], None)?,
+            None => v
+        };
+        if seen.insert(interp.hash_key(key)) {
+            kept.push(v);
+        }
+    }
+    Ok(interp.new_array(kept))
+}
+
+// Builds a hash from `[key, value]` pairs, or from the pairs the block returns.
+fn to_h(interp: &mut Interpreter, recv: Value, _args: &[Value], block: Option<Value>) -> EvalResult {
+    let result = interp.new_hash(HashTable::create());
+    each_value(interp, recv, move |interp, v| {
+        let pair = match block {
+            Some(block) => interp.call_proc(block, &[v], None)?,
+            None => v
+        };
+        match interp.array_items(pair).cloned() {
+            Some(items) if items.len() == 2 => hash::insert(interp, result, items[0], items[1])?,
+            Some(items) => return Err(interp.argument_error(format!("element has wrong array length (expected 2, was {})", items.len()))),
+            None => {
+                let shown = type_name(interp, pair);
+                return Err(interp.type_error(format!("wrong element type {} (expected array)", shown)));
+            }
+        }
+        Ok(true)
+    })?;
+    Ok(result)
+}
+
+// Pairs each value with the values at the same position in `others`, padding with nil.
+fn zip(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let mut others = Vec::with_capacity(args.len());
+    for other in args.iter() {
+        let items = match interp.array_items(*other).cloned() {
+            Some(items) => items,
+            None => values_of(interp, *other)?
+        };
+        others.push(items);
+    }
+    let mut rows = Vec::new();
+    for (i, v) in values_of(interp, recv)?.into_iter().enumerate() {
+        let mut row = vec![v];
+        row.extend(others.iter().map(|items| items.get(i).copied().unwrap_or(Value::Nil)));
+        let row = interp.new_array(row);
+        match block {
+            Some(block) => interp.call_proc_for_effect(block, &[row])?,
+            None => rows.push(row)
+        }
+    }
+    match block {
+        Some(_) => Ok(Value::Nil),
+        None => Ok(interp.new_array(rows))
+    }
+}
+
+// -- Enumerator --
+
+fn each(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    if block.is_none() && args.is_empty() {
+        return Ok(recv);
+    }
+    let data = data_of(interp, recv)?;
+    let mut all_args = data.args.clone();
+    all_args.extend_from_slice(args);
+    interp.send(data.receiver, data.method, &all_args, block)
+}
+
+// -- external iteration --
+
+// The value `next` returns next, moving past it when `advance` is set. With no fibers to suspend
+// `each` part way, more values are fetched by iterating again from the start, twice as many each
+// time, so endless sources work too; the iteration's side effects happen again on each refill.
+fn external_value(interp: &mut Interpreter, recv: Value, advance: bool) -> EvalResult {
+    let mut cursor = data_of(interp, recv)?.cursor;
+    let fetched = cursor.fetched.and_then(|acc| interp.array_items(acc)).map_or(0, Vec::len);
+    if cursor.position >= fetched && !cursor.complete {
+        let limit = (fetched * 2).max(16);
+        let acc = interp.new_array(Vec::new());
+        each_value(interp, recv, move |interp, v| {
+            push(interp, acc, v)?;
+            Ok(interp.array_items(acc).map_or(0, Vec::len) < limit)
+        })?;
+        cursor.complete = interp.array_items(acc).map_or(0, Vec::len) < limit;
+        cursor.fetched = Some(acc);
+    }
+    let value = match cursor.fetched {
+        Some(acc) if cursor.position < interp.array_items(acc).map_or(0, Vec::len) => slot(interp, acc, cursor.position),
+        _ => {
+            set_cursor(interp, recv, cursor);
+            return Err(interp.error(interp.core.stop_iteration, String::from("iteration reached an end")));
+        }
+    };
+    if advance {
+        cursor.position += 1;
+    }
+    set_cursor(interp, recv, cursor);
+    Ok(value)
+}
+
+fn set_cursor(interp: &mut Interpreter, recv: Value, cursor: Cursor) {
+    if let Some(ObjectKind::Enumerator(data)) = recv.as_object().map(|r| &mut interp.heap.get_mut(r).kind) {
+        data.cursor = cursor;
+    }
+}
+
+// Runs the underlying method, passing each value on with its index; the block's result goes back
+// to the method, so `map.with_index { |v, i| ... }` maps.
+fn with_index(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "with_index", args))
+    };
+    let offset = match args.first() {
+        Some(Value::Nil) | None => 0,
+        Some(v) => expect_integer(interp, *v)?
+    };
+    let data = data_of(interp, recv)?;
+    let index = Rc::new(Cell::new(offset));
+    let native = native_block(interp, move |interp, _, args| {
+        let v = packed(interp, args);
+        let i = index.get();
+        index.set(i + 1);
+        interp.call_proc(block, &[v, Value::Integer(i)], None)
+    });
+    interp.send(data.receiver, data.method, &data.args, Some(native))
+}
+
+fn with_object(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerator(interp, recv, "with_object", args))
+    };
+    let memo = args[0];
+    let data = data_of(interp, recv)?;
+    let native = native_block(interp, move |interp, _, args| {
+        let v = packed(interp, args);
+        interp.call_proc(block, &[v, memo], None)
+    });
+    interp.send(data.receiver, data.method, &data.args, Some(native))?;
+    Ok(memo)
+}
+
+// The receiver's size when the method visits each value once, otherwise nil.
+fn size(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let data = data_of(interp, recv)?;
+    let name = interp.sym_name(data.method).to_string();
+    let sized = ["each", "map", "collect", "flat_map", "select", "filter", "reject", "filter_map", "each_with_index",
+        "with_index", "find_index", "sort_by", "group_by", "partition", "min_by", "max_by", "each_with_object", "reverse_each"];
+    if !sized.contains(&name.as_str()) {
+        return Ok(Value::Nil);
+    }
+    let size = interp.sym("size");
+    if !interp.call(data.receiver, "respond_to?", &[Value::Symbol(size)])?.is_truthy() {
+        return Ok(Value::Nil);
+    }
+    interp.send(data.receiver, size, &[], None)
+}
+
+fn describe_call(interp: &mut Interpreter, method: Sym, args: &[Value]) -> Result<String, Unwind> {
+    let mut shown = format!(":{}", interp.sym_name(method));
+    if !args.is_empty() {
+        let mut parts = Vec::with_capacity(args.len());
+        for arg in args.iter() {
+            parts.push(interp.inspect(*arg)?);
+        }
+        shown.push_str(&format!("({})", parts.join(", ")));
+    }
+    Ok(shown)
+}
+
+// `#<Enumerator: [1, 2]:each_slice(2)>`; a lazy enumerator nests one level per stage.
+fn inspect(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let data = data_of(interp, recv)?;
+    let class = interp.module_name(interp.real_class(recv));
+    let receiver = interp.inspect(data.receiver)?;
+    let call = if interp.is_a(recv, interp.core.lazy) && data.method == interp.sym("each") && data.args.is_empty() {
+        String::new()
+    } else {
+        describe_call(interp, data.method, &data.args)?
+    };
+    let mut shown = format!("#<{}: {}{}>", class, receiver, call);
+    for (stage, v) in data.stages.iter() {
+        let name = match stage {
+            LazyStage::Map => "map",
+            LazyStage::Select => "select",
+            LazyStage::Reject => "reject",
+            LazyStage::FilterMap => "filter_map",
+            LazyStage::FlatMap => "flat_map",
+            LazyStage::TakeWhile => "take_while",
+            LazyStage::DropWhile => "drop_while",
+            LazyStage::Take => "take",
+            LazyStage::Drop => "drop"
+        };
+        let name = interp.sym(name);
+        let args = if matches!(stage, LazyStage::Take | LazyStage::Drop) { vec![*v] } else { Vec::new() };
+        let call = describe_call(interp, name, &args)?;
+        shown = format!("#<{}: {}{}>", class, shown, call);
+    }
+    Ok(interp.new_string(shown))
+}
+
+// -- Enumerator::Lazy --
+
+// A new lazy enumerator with one more stage; the stage's block, or count, runs only as values
+// are pulled through.
+fn add_stage(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>, stage: LazyStage) -> EvalResult {
+    let v = match stage {
+        LazyStage::Take | LazyStage::Drop => {
+            interp.check_args(args, 1, Some(1))?;
+            match expect_integer(interp, args[0])? {
+                n if n < 0 => {
+                    let action = if stage == LazyStage::Take { "take" } else { "drop" };
+                    return Err(interp.argument_error(format!("attempt to {} negative size", action)));
+                },
+                n => Value::Integer(n)
+            }
+        },
+        _ => {
+            interp.check_args(args, 0, Some(0))?;
+            match block {
+                Some(b) => b,
+                None => return Err(interp.argument_error(String::from("tried to call lazy method without a block")))
+            }
+        }
+    };
+    let mut data = data_of(interp, recv)?;
+    data.stages.push((stage, v));
+    data.cursor = Cursor::default();
+    Ok(new_enumerator(interp, interp.core.lazy, data))
+}
+
+// Passes `v` through the stages from `i` on and then to `block`. False once a `take` has all it
+// needs, or a `take_while` has failed, so the source can stop.
+fn feed(interp: &mut Interpreter, stages: &[(LazyStage, Value)], counters: &[Cell<i64>], i: usize, v: Value, block: Value) -> Result<bool, Unwind> {
+    let (stage, arg) = match stages.get(i) {
+        Some(stage) => *stage,
+        None => {
+            interp.call_proc(block, &[v], None)?;
+            return Ok(true);
+        }
+    };
+    match stage {
+        LazyStage::Map => {
+            let mapped = interp.call_proc(arg, &[v], None)?;
+            feed(interp, stages, counters, i + 1, mapped, block)
+        },
+        LazyStage::Select | LazyStage::Reject => {
+            let accepted = interp.call_proc(arg, &[v], None)?.is_truthy();
+            if accepted == (stage == LazyStage::Select) { feed(interp, stages, counters, i + 1, v, block) } else { Ok(true) }
+        },
+        LazyStage::FilterMap => {
+            let mapped = interp.call_proc(arg, &[v], None)?;
+            if mapped.is_truthy() { feed(interp, stages, counters, i + 1, mapped, block) } else { Ok(true) }
+        },
+        LazyStage::FlatMap => {
+            let mapped = interp.call_proc(arg, &[v], None)?;
+            match interp.array_items(mapped).cloned() {
+                Some(items) => {
+                    for item in items {
+                        if !feed(interp, stages, counters, i + 1, item, block)? {
+                            return Ok(false);
+                        }
+                    }
+                    Ok(true)
+                },
+                None => feed(interp, stages, counters, i + 1, mapped, block)
+            }
+        },
+        LazyStage::TakeWhile => {
+            if interp.call_proc(arg, &[v], None)?.is_truthy() { feed(interp, stages, counters, i + 1, v, block) } else { Ok(false) }
+        },
+        // The counter is 0 while still dropping.
+        LazyStage::DropWhile => {
+            if counters[i].get() == 0 && interp.call_proc(arg, &[v], None)?.is_truthy() {
+                return Ok(true);
+            }
+            counters[i].set(1);
+            feed(interp, stages, counters, i + 1, v, block)
+        },
+        // The counters hold how many values are still to take or drop.
+        LazyStage::Take => {
+            let remaining = counters[i].get() - 1;
+            counters[i].set(remaining);
+            let more = feed(interp, stages, counters, i + 1, v, block)?;
+            Ok(more && remaining > 0)
+        },
+        LazyStage::Drop => {
+            if counters[i].get() > 0 {
+                counters[i].set(counters[i].get() - 1);
+                return Ok(true);
+            }
+            feed(interp, stages, counters, i + 1, v, block)
+        }
+    }
+}
+
+fn lazy_each(interp: &mut Interpreter, recv: Value, _args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(recv)
+    };
+    let data = data_of(interp, recv)?;
+    if data.stages.iter().any(|(stage, n)| *stage == LazyStage::Take && matches!(n, Value::Integer(0))) {
+        return Ok(recv);
+    }
+    let counters: Vec<Cell<i64>> = data.stages.iter().map(|(_, n)| Cell::new(match n {
+        Value::Integer(n) => *n,
+        _ => 0
+    })).collect();
+    let stages = data.stages.clone();
+    let native = native_block(interp, move |interp, tag, args| {
+        let v = packed(interp, args);
+        if feed(interp, &stages, &counters, 0, v, block)? { Ok(Value::Nil) } else { Err(Unwind::Break(Value::Nil, tag)) }
+    });
+    let result = interp.send(data.receiver, data.method, &data.args, Some(native));
+    interp.catch_break(Some(native), result)?;
+    Ok(recv)
+}
@@ -0,0 +1,116 @@
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::runtime::heap::{ExceptionData, ObjectKind};
+use crate::interpreter::runtime::value::Value;
+
+pub fn install(interp: &mut Interpreter) {
+    let exception = interp.core.exception;
+    let singleton = interp.singleton_class(Value::Object(exception)).expect("classes have singleton classes");
+    interp.define_builtin(singleton, "exception", |interp, recv, args, block| {
+        let new = interp.sym("new");
+        interp.send(recv, new, args, block)
+    });
+    interp.define_builtin(exception, "initialize", initialize);
+    interp.define_builtin(exception, "exception", exception_with_message);
+    interp.define_builtin(exception, "message", |interp, recv, _, _| interp.call(recv, "to_s", &[]));
+    interp.define_builtin(exception, "to_s", to_s);
+    interp.define_builtin(exception, "inspect", inspect);
+    interp.define_builtin(exception, "backtrace", backtrace);
+    interp.define_builtin(exception, "full_message", full_message);
+    interp.define_builtin(exception, "==", equal);
+
+    let system_exit = interp.core.system_exit;
+    interp.define_builtin(system_exit, "status", |interp, recv, _, _| {
+        let status = interp.sym("@status");
+        Ok(match interp.get_ivar(recv, status) {
+            Value::Nil => Value::Integer(0),
+            v => v
+        })
+    });
+    interp.define_builtin(system_exit, "success?", |interp, recv, _, _| {
+        let status = interp.sym("@status");
+        Ok(Value::from_bool(matches!(interp.get_ivar(recv, status), Value::Nil | Value::Integer(0))))
+    });
+}
+
+fn data(interp: &mut Interpreter, v: Value) -> Result<ExceptionData, Unwind> {
+    match v.as_object().map(|r| &interp.heap.get(r).kind) {
+        Some(ObjectKind::Exception(data)) => Ok(data.clone()),
+        _ => Err(interp.type_error(String::from("exception object expected")))
+    }
+}
+
+fn initialize(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let message = args.first().copied().unwrap_or(Value::Nil);
+    if let Some(ObjectKind::Exception(data)) = recv.as_object().map(|r| &mut interp.heap.get_mut(r).kind) {
+        data.message = message;
+    }
+    Ok(Value::Nil)
+}
+
+// `exc.exception("new message")` copies the exception with a different message.
+fn exception_with_message(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let message = match args.first() {
+        None => return Ok(recv),
+        Some(message) => *message
+    };
+    let mut copy = data(interp, recv)?;
+    copy.message = message;
+    let class = interp.real_class(recv);
+    Ok(Value::Object(interp.alloc(class, ObjectKind::Exception(copy))))
+}
+
+// The message, or the class name when there isn't one.
+fn to_s(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    match data(interp, recv)?.message {
+        Value::Nil => {
+            let name = interp.module_name(interp.real_class(recv));
+            Ok(interp.new_string(name))
+        },
+        message => {
+            let s = interp.to_s(message)?;
+            Ok(interp.new_string(s))
+        }
+    }
+}
+
+fn inspect(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let class_name = interp.module_name(interp.real_class(recv));
+    let message = interp.exception_message(recv);
+    let shown = if message.is_empty() || message == class_name { class_name } else { format!("#<{}: {}>", class_name, message) };
+    Ok(interp.new_string(shown))
+}
+
+fn backtrace(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    match data(interp, recv)?.backtrace {
+        Some(lines) => {
+            let lines = lines.into_iter().map(|l| interp.new_string(l)).collect();
+            Ok(interp.new_array(lines))
+        },
+        None => Ok(Value::Nil)
+    }
+}
+
+fn full_message(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let class_name = interp.module_name(interp.real_class(recv));
+    let message = interp.exception_message(recv);
+    let mut lines = data(interp, recv)?.backtrace.unwrap_or_default().into_iter();
+    let mut out = match lines.next() {
+        Some(first) => format!("{}: {} ({})", first, message, class_name),
+        None => format!("{} ({})", message, class_name)
+    };
+    for line in lines {
+        out.push_str(&format!("\n\tfrom {}", line));
+    }
+    Ok(interp.new_string(out))
+}
+
+fn equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    if interp.real_class(recv) != interp.real_class(args[0]) {
+        return Ok(Value::False);
+    }
+    let (a, b) = (interp.exception_message(recv), interp.exception_message(args[0]));
+    Ok(Value::from_bool(a == b))
+}
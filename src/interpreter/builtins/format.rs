@@ -0,0 +1,300 @@
+use std::convert::TryFrom;
+
+use crate::interpreter::interpreter::{Interpreter, Unwind};
+use crate::interpreter::runtime::value::Value;
+use super::{kernel, numeric};
+
+// A `%` directive's flags, width and precision.
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    zero: bool,
+    alternate: bool,
+    width: Option<usize>,
+    precision: Option<usize>
+}
+
+// The largest precision the float formatting below can take.
+static MAX_PRECISION: usize = u16::MAX as usize;
+// The largest width, as in Ruby, whose widths are C ints.
+static MAX_WIDTH: usize = i32::MAX as usize;
+
+// `format`'s directives: `%d %i %u %f %e %E %g %G %s %p %x %X %o %b %B %c %%`, with the flags
+// `-+ 0#`, a width and a precision, either of which may be `*`. `%<name>d` and `%{name}` take
+// their values from a hash argument.
+pub fn format(interp: &mut Interpreter, template: &str, args: &[Value]) -> Result<String, Unwind> {
+    let chars: Vec<char> = template.chars().collect();
+    let mut out = String::new();
+    let mut next_arg = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '%' {
+            out.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        let start = i;
+        i += 1;
+        let mut spec = Spec::default();
+        let mut named: Option<Value> = None;
+        let mut substituted = false;
+        loop {
+            match chars.get(i) {
+                Some('-') => spec.left = true,
+                Some('+') => spec.plus = true,
+                Some(' ') => spec.space = true,
+                Some('0') => spec.zero = true,
+                Some('#') => spec.alternate = true,
+                Some('<') | Some('{') => {
+                    let close = if chars[i] == '<' { '>' } else { '}' };
+                    let end = match chars[i..].iter().position(|c| *c == close) {
+                        Some(n) => i + n,
+                        None => return Err(malformed(interp, &chars[start..]))
+                    };
+                    let name: String = chars[i + 1..end].iter().collect();
+                    let value = hash_value(interp, args, &name)?;
+                    i = end + 1;
+                    // `%{name}` substitutes the value as it is; `%<name>` still needs a conversion.
+                    if close == '}' {
+                        let shown = interp.to_s(value)?;
+                        pad(&mut out, &shown, &spec);
+                        substituted = true;
+                        break;
+                    }
+                    named = Some(value);
+                    continue;
+                },
+                _ => break
+            }
+            i += 1;
+        }
+        if substituted {
+            continue;
+        }
+        let mut take = |interp: &mut Interpreter| -> Result<Value, Unwind> {
+            if let Some(v) = named.take() {
+                return Ok(v);
+            }
+            match args.get(next_arg) {
+                Some(v) => {
+                    next_arg += 1;
+                    Ok(*v)
+                },
+                None => Err(interp.argument_error(String::from("too few arguments")))
+            }
+        };
+        if chars.get(i) == Some(&'*') {
+            i += 1;
+            let n = take(interp)?;
+            let n = super::expect_integer(interp, n)?;
+            spec.left |= n < 0;
+            spec.width = Some(n.unsigned_abs() as usize);
+        } else {
+            spec.width = digits(&chars, &mut i);
+        }
+        if spec.width.is_some_and(|w| w > MAX_WIDTH) {
+            return Err(interp.argument_error(String::from("width too big")));
+        }
+        if chars.get(i) == Some(&'.') {
+            i += 1;
+            if chars.get(i) == Some(&'*') {
+                i += 1;
+                let n = take(interp)?;
+                spec.precision = Some(super::expect_integer(interp, n)?.max(0) as usize);
+            } else {
+                spec.precision = Some(digits(&chars, &mut i).unwrap_or(0));
+            }
+            if spec.precision.is_some_and(|p| p > MAX_PRECISION) {
+                return Err(interp.argument_error(String::from("precision too big")));
+            }
+        }
+        let conversion = match chars.get(i) {
+            Some(c) => *c,
+            None => return Err(interp.argument_error(String::from("incomplete format specifier; use %% (double %) instead")))
+        };
+        i += 1;
+        let text = match conversion {
+            '%' => String::from("%"),
+            'd' | 'i' | 'u' => {
+                let v = take(interp)?;
+                let n = integer_text(interp, v, 10)?;
+                signed(&n, &spec, "")
+            },
+            'x' | 'X' | 'o' | 'b' | 'B' => {
+                let v = take(interp)?;
+                let radix = match conversion { 'x' | 'X' => 16, 'o' => 8, _ => 2 };
+                let n = integer_text(interp, v, radix)?;
+                let n = if conversion == 'X' { n.to_uppercase() } else { n };
+                let prefix = match conversion {
+                    _ if !spec.alternate || n == "0" => "",
+                    'x' => "0x",
+                    'X' => "0X",
+                    'o' => "0",
+                    'b' => "0b",
+                    _ => "0B"
+                };
+                signed(&n, &spec, prefix)
+            },
+            'f' | 'e' | 'E' | 'g' | 'G' => {
+                let v = take(interp)?;
+                let v = if interp.str_ref(v).is_some() { kernel::float(interp, Value::Nil, &[v], None)? } else { v };
+                let f = numeric::to_f64(interp, v)?;
+                float_text(f, conversion, &spec)
+            },
+            's' | 'p' => {
+                let v = take(interp)?;
+                let s = if conversion == 's' { interp.to_s(v)? } else { interp.inspect(v)? };
+                match spec.precision {
+                    Some(p) => s.chars().take(p).collect(),
+                    None => s
+                }
+            },
+            'c' => {
+                let v = take(interp)?;
+                match interp.string_value(v) {
+                    Some(s) => s.chars().take(1).collect(),
+                    None => {
+                        let n = super::expect_integer(interp, v)?;
+                        match u32::try_from(n).ok().and_then(char::from_u32) {
+                            Some(c) => c.to_string(),
+                            None => return Err(interp.error(interp.core.range_error, format!("{} out of char range", n)))
+                        }
+                    }
+                }
+            },
+            _ => return Err(malformed(interp, &chars[start..i]))
+        };
+        pad(&mut out, &text, &spec);
+    }
+    Ok(out)
+}
+
+fn malformed(interp: &mut Interpreter, directive: &[char]) -> Unwind {
+    let directive: String = directive.iter().collect();
+    interp.argument_error(format!("malformed format string - {}", directive))
+}
+
+// A run of digits, or `usize::MAX` for one too long to fit.
+fn digits(chars: &[char], i: &mut usize) -> Option<usize> {
+    let start = *i;
+    while chars.get(*i).is_some_and(|c| c.is_ascii_digit()) {
+        *i += 1;
+    }
+    if start == *i {
+        return None;
+    }
+    Some(chars[start..*i].iter().collect::<String>().parse().unwrap_or(usize::MAX))
+}
+
+fn hash_value(interp: &mut Interpreter, args: &[Value], name: &str) -> Result<Value, Unwind> {
+    let hash = match args {
+        [hash] if interp.is_a(*hash, interp.core.hash) => *hash,
+        _ => return Err(interp.argument_error(String::from("one hash required")))
+    };
+    let key = Value::Symbol(interp.sym(name));
+    let found = interp.call(hash, "key?", &[key])?;
+    if !found.is_truthy() {
+        return Err(interp.error(interp.core.key_error, format!("key<{}> not found", name)));
+    }
+    interp.call(hash, "[]", &[key])
+}
+
+// The digits of an Integer argument in `radix`, with a leading `-` when negative.
+fn integer_text(interp: &mut Interpreter, v: Value, radix: u32) -> Result<String, Unwind> {
+    let n = kernel::integer(interp, Value::Nil, &[v], None)?;
+    Ok(match n {
+        Value::Integer(n) if radix == 10 => n.to_string(),
+        Value::Integer(n) => {
+            let digits = match radix {
+                16 => format!("{:x}", n.unsigned_abs()),
+                8 => format!("{:o}", n.unsigned_abs()),
+                _ => format!("{:b}", n.unsigned_abs())
+            };
+            if n < 0 { format!("-{}", digits) } else { digits }
+        },
+        n => interp.big_int(n).map_or_else(String::new, |b| b.to_string_radix(radix))
+    })
+}
+
+// Applies the sign flags, precision and zero padding to a number's digits.
+fn signed(n: &str, spec: &Spec, prefix: &str) -> String {
+    let (negative, digits) = match n.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, n)
+    };
+    let sign = if negative { "-" } else if spec.plus { "+" } else if spec.space { " " } else { "" };
+    let mut digits = digits.to_string();
+    if let Some(p) = spec.precision {
+        while digits.len() < p {
+            digits.insert(0, '0');
+        }
+    } else if spec.zero && !spec.left {
+        let used = sign.len() + prefix.len();
+        while used + digits.len() < spec.width.unwrap_or(0) {
+            digits.insert(0, '0');
+        }
+    }
+    format!("{}{}{}", sign, prefix, digits)
+}
+
+fn float_text(f: f64, conversion: char, spec: &Spec) -> String {
+    let sign = if f.is_sign_negative() && !f.is_nan() { "-" } else if spec.plus { "+" } else if spec.space { " " } else { "" };
+    let magnitude = f.abs();
+    if !magnitude.is_finite() {
+        return format!("{}{}", sign, if f.is_nan() { "NaN" } else { "Inf" });
+    }
+    let precision = spec.precision.unwrap_or(6);
+    let body = match conversion {
+        'f' => format!("{:.*}", precision, magnitude),
+        'e' | 'E' => exponent_form(magnitude, precision),
+        _ => {
+            // `%g` picks fixed or exponent form by the exponent, then drops trailing zeros.
+            let precision = precision.max(1);
+            let exponent = if magnitude == 0.0 { 0 } else { exponent_form(magnitude, precision - 1).rsplit('e').next().and_then(|e| e.parse::<i32>().ok()).unwrap_or(0) };
+            let body = if exponent < -4 || exponent >= precision as i32 {
+                exponent_form(magnitude, precision - 1)
+            } else {
+                format!("{:.*}", (precision as i32 - 1 - exponent).max(0) as usize, magnitude)
+            };
+            if spec.alternate { body } else { trim_zeros(&body) }
+        }
+    };
+    let body = if conversion.is_uppercase() { body.to_uppercase() } else { body };
+    let mut text = format!("{}{}", sign, body);
+    if spec.zero && !spec.left {
+        while text.chars().count() < spec.width.unwrap_or(0) {
+            text.insert(sign.len(), '0');
+        }
+    }
+    text
+}
+
+// `1.500000e+02`: a two-digit exponent with its sign, as C prints it.
+fn exponent_form(f: f64, precision: usize) -> String {
+    let formatted = format!("{:.*e}", precision, f);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    format!("{}e{}{:02}", mantissa, if exponent < 0 { '-' } else { '+' }, exponent.abs())
+}
+
+fn trim_zeros(body: &str) -> String {
+    let (mantissa, exponent) = match body.split_once('e') {
+        Some((m, e)) => (m, format!("e{}", e)),
+        None => (body, String::new())
+    };
+    let mantissa = if mantissa.contains('.') { mantissa.trim_end_matches('0').trim_end_matches('.') } else { mantissa };
+    format!("{}{}", mantissa, exponent)
+}
+
+fn pad(out: &mut String, text: &str, spec: &Spec) {
+    let fill = spec.width.unwrap_or(0).saturating_sub(text.chars().count());
+    if !spec.left {
+        out.extend(std::iter::repeat_n(' ', fill));
+    }
+    out.push_str(text);
+    if spec.left {
+        out.extend(std::iter::repeat_n(' ', fill));
+    }
+}
@@ -0,0 +1,50 @@
+use crate::interpreter::interpreter::{EvalResult, Interpreter};
+use crate::interpreter::runtime::value::Value;
+
+// `GC.start` collects now; `GC.stat` reports on the collector; `GC.stress = true` collects
+// before every allocation.
+pub fn install(interp: &mut Interpreter) {
+    let gc = interp.new_module(None, false, None);
+    let name = interp.sym("GC");
+    let object = interp.core.object;
+    interp.set_constant(object, name, Value::Object(gc));
+    interp.define_singleton_builtin(gc, "start", |interp, _, args, _| {
+        interp.check_args(args, 0, Some(0))?;
+        interp.collect_garbage(None);
+        Ok(Value::Nil)
+    });
+    interp.define_singleton_builtin(gc, "count", |interp, _, _, _| Ok(Value::Integer(interp.gc().get_count() as i64)));
+    interp.define_singleton_builtin(gc, "stat", stat);
+    interp.define_singleton_builtin(gc, "stress", |interp, _, _, _| Ok(Value::from_bool(interp.gc().is_stress())));
+    interp.define_singleton_builtin(gc, "stress=", |interp, _, args, _| {
+        interp.check_args(args, 1, Some(1))?;
+        interp.set_gc_stress(args[0].is_truthy());
+        Ok(args[0])
+    });
+}
+
+// The counts as a hash, or just the one named by a symbol argument.
+fn stat(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let gc = interp.gc();
+    let counts = [
+        ("count", gc.get_count()),
+        ("heap_live_slots", interp.heap.live_count() as u64),
+        ("heap_free_slots", (interp.heap.slot_count() - interp.heap.live_count()) as u64),
+        ("total_allocated_objects", gc.get_allocated()),
+        ("total_freed_objects", gc.get_freed())
+    ];
+    let mut pairs = Vec::with_capacity(counts.len() * 2);
+    for (name, count) in counts.iter() {
+        pairs.push(Value::Symbol(interp.sym(name)));
+        pairs.push(Value::Integer(*count as i64));
+    }
+    match args.first() {
+        Some(Value::Symbol(key)) => Ok(pairs.chunks(2).find(|pair| pair[0] == Value::Symbol(*key)).map_or(Value::Nil, |pair| pair[1])),
+        Some(other) => {
+            let shown = interp.inspect(*other)?;
+            Err(interp.argument_error(format!("unknown key: {}", shown)))
+        },
+        None => Ok(interp.hash_from_pairs(&pairs))
+    }
+}
@@ -0,0 +1,260 @@
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::runtime::heap::{HashTable, ObjectKind};
+use crate::interpreter::runtime::value::Value;
+use super::{conversion_error, enumerable};
+
+pub fn install(interp: &mut Interpreter) {
+    let hash = interp.core.hash;
+    interp.define_builtin(hash, "initialize", initialize);
+    interp.define_builtin(hash, "[]", get);
+    interp.define_builtin(hash, "[]=", set);
+    interp.define_builtin(hash, "store", set);
+    interp.define_builtin(hash, "fetch", fetch);
+    interp.define_builtin(hash, "key?", has_key);
+    interp.define_builtin(hash, "has_key?", has_key);
+    interp.define_builtin(hash, "include?", has_key);
+    interp.define_builtin(hash, "member?", has_key);
+    interp.define_builtin(hash, "value?", has_value);
+    interp.define_builtin(hash, "has_value?", has_value);
+    interp.define_builtin(hash, "key", key);
+    interp.define_builtin(hash, "delete", delete);
+    interp.define_builtin(hash, "keys", |interp, recv, _, _| {
+        let keys = entries_of(interp, recv)?.into_iter().map(|(k, _)| k).collect();
+        Ok(interp.new_array(keys))
+    });
+    interp.define_builtin(hash, "values", |interp, recv, _, _| {
+        let values = entries_of(interp, recv)?.into_iter().map(|(_, v)| v).collect();
+        Ok(interp.new_array(values))
+    });
+    interp.define_builtin(hash, "length", length);
+    interp.define_builtin(hash, "size", length);
+    interp.define_builtin(hash, "empty?", |interp, recv, _, _| Ok(Value::from_bool(entries_of(interp, recv)?.is_empty())));
+    interp.define_builtin(hash, "each", each);
+    interp.define_builtin(hash, "each_pair", each);
+    interp.define_builtin(hash, "to_a", to_a);
+    interp.define_builtin(hash, "select", |interp, recv, args, block| filter(interp, recv, args, block, "select", true));
+    interp.define_builtin(hash, "filter", |interp, recv, args, block| filter(interp, recv, args, block, "filter", true));
+    interp.define_builtin(hash, "reject", |interp, recv, args, block| filter(interp, recv, args, block, "reject", false));
+    interp.define_builtin(hash, "to_h", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(hash, "merge", merge);
+    interp.define_builtin(hash, "update", |interp, recv, args, block| {
+        let merged = merge(interp, recv, args, block)?;
+        let table = table_of(interp, merged)?;
+        update(interp, recv, |t| *t = table)?;
+        Ok(recv)
+    });
+    interp.define_builtin(hash, "clear", |interp, recv, _, _| {
+        update(interp, recv, HashTable::clear)?;
+        Ok(recv)
+    });
+    interp.define_builtin(hash, "default", |interp, recv, _, _| Ok(table_of(interp, recv)?.default.unwrap_or(Value::Nil)));
+    interp.define_builtin(hash, "default=", |interp, recv, args, _| {
+        interp.check_args(args, 1, Some(1))?;
+        let v = args[0];
+        update(interp, recv, |t| t.default = Some(v))?;
+        Ok(v)
+    });
+    interp.define_builtin(hash, "==", equal);
+    interp.define_builtin(hash, "inspect", inspect);
+    interp.define_builtin(hash, "to_s", inspect);
+}
+
+pub fn table_of(interp: &mut Interpreter, v: Value) -> Result<HashTable, Unwind> {
+    match v.as_object().map(|r| &interp.heap.get(r).kind) {
+        Some(ObjectKind::Hash(table)) => Ok(table.clone()),
+        _ => Err(conversion_error(interp, v, "Hash"))
+    }
+}
+
+// The entries are rooted: the caller may run code that takes them out of the hash.
+pub fn entries_of(interp: &mut Interpreter, v: Value) -> Result<Vec<(Value, Value)>, Unwind> {
+    let entries = table_of(interp, v)?.entries().to_vec();
+    for (k, v) in entries.iter() {
+        interp.root(*k);
+        interp.root(*v);
+    }
+    Ok(entries)
+}
+
+pub fn update<F, R>(interp: &mut Interpreter, v: Value, f: F) -> Result<R, Unwind>
+where
+    F: FnOnce(&mut HashTable) -> R
+{
+    interp.check_frozen(v)?;
+    match v.as_object().map(|r| &mut interp.heap.get_mut(r).kind) {
+        Some(ObjectKind::Hash(table)) => Ok(f(table)),
+        _ => Err(conversion_error(interp, v, "Hash"))
+    }
+}
+
+pub fn lookup(interp: &mut Interpreter, recv: Value, k: Value) -> Result<Option<Value>, Unwind> {
+    let key = interp.hash_key(k);
+    match recv.as_object().map(|r| &interp.heap.get(r).kind) {
+        Some(ObjectKind::Hash(table)) => Ok(table.get(&key)),
+        _ => Err(conversion_error(interp, recv, "Hash"))
+    }
+}
+
+pub fn insert(interp: &mut Interpreter, recv: Value, k: Value, v: Value) -> Result<(), Unwind> {
+    let key = interp.hash_key(k);
+    let k = interp.hash_key_value(k);
+    update(interp, recv, |table| table.insert(key, k, v))
+}
+
+fn initialize(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let default = args.first().copied();
+    update(interp, recv, |table| table.default = default)?;
+    Ok(Value::Nil)
+}
+
+fn get(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    match lookup(interp, recv, args[0])? {
+        Some(v) => Ok(v),
+        None => Ok(table_of(interp, recv)?.default.unwrap_or(Value::Nil))
+    }
+}
+
+fn set(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 2, Some(2))?;
+    insert(interp, recv, args[0], args[1])?;
+    Ok(args[1])
+}
+
+fn fetch(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    if let Some(v) = lookup(interp, recv, args[0])? {
+        return Ok(v);
+    }
+    match (block, args.get(1)) {
+        (Some(block), _) => interp.call_proc(block, &[args[0]], None),
+        (None, Some(default)) => Ok(*default),
+        (None, None) => {
+            let message = format!("key not found: {}", interp.inspect(args[0])?);
+            Err(interp.error(interp.core.key_error, message))
+        }
+    }
+}
+
+fn has_key(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    Ok(Value::from_bool(lookup(interp, recv, args[0])?.is_some()))
+}
+
+fn has_value(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    for (_, v) in entries_of(interp, recv)? {
+        if interp.values_equal(v, args[0])? {
+            return Ok(Value::True);
+        }
+    }
+    Ok(Value::False)
+}
+
+fn key(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    for (k, v) in entries_of(interp, recv)? {
+        if interp.values_equal(v, args[0])? {
+            return Ok(k);
+        }
+    }
+    Ok(Value::Nil)
+}
+
+fn delete(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let key = interp.hash_key(args[0]);
+    match update(interp, recv, |table| table.remove(&key))? {
+        Some(v) => Ok(v),
+        None => match block {
+            Some(block) => interp.call_proc(block, &[args[0]], None),
+            None => Ok(Value::Nil)
+        }
+    }
+}
+
+fn length(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    Ok(Value::Integer(table_of(interp, recv)?.len() as i64))
+}
+
+// Blocks get `[key, value]` pairs, which `|k, v|` destructures.
+fn each(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerable::enumerator(interp, recv, "each", args))
+    };
+    for (k, v) in entries_of(interp, recv)? {
+        let mark = interp.root_mark();
+        let pair = interp.new_array(vec![k, v]);
+        interp.call_proc(block, &[pair], None)?;
+        interp.release_roots(mark);
+    }
+    Ok(recv)
+}
+
+// Unlike `Enumerable#select`, the Hash versions return a hash.
+fn filter(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>, name: &str, keep: bool) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerable::enumerator(interp, recv, name, args))
+    };
+    let mut table = HashTable::create();
+    for (k, v) in entries_of(interp, recv)? {
+        if interp.call_proc(block, &[k, v], None)?.is_truthy() == keep {
+            let key = interp.hash_key(k);
+            table.insert(key, k, v);
+        }
+    }
+    Ok(interp.new_hash(table))
+}
+
+fn to_a(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let pairs = entries_of(interp, recv)?.into_iter().map(|(k, v)| interp.new_array(vec![k, v])).collect();
+    Ok(interp.new_array(pairs))
+}
+
+// With a block, `merge` resolves keys present in both: `{ |key, old, new| ... }`.
+fn merge(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let mut table = table_of(interp, recv)?;
+    for other in args.iter() {
+        for (k, v) in entries_of(interp, *other)? {
+            let key = interp.hash_key(k);
+            let v = match (table.get(&key), block) {
+                (Some(old), Some(block)) => interp.call_proc(block, &[k, old, v], None)?,
+                _ => v
+            };
+            table.insert(key, k, v);
+        }
+    }
+    Ok(interp.new_hash(table))
+}
+
+fn equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let a = table_of(interp, recv)?;
+    let b = match args[0].as_object().map(|r| &interp.heap.get(r).kind) {
+        Some(ObjectKind::Hash(b)) => b.clone(),
+        _ => return Ok(Value::False)
+    };
+    if a.len() != b.len() {
+        return Ok(Value::False);
+    }
+    for (k, v) in a.entries().iter() {
+        match b.get(&interp.hash_key(*k)) {
+            Some(other) if interp.values_equal(*v, other)? => continue,
+            _ => return Ok(Value::False)
+        }
+    }
+    Ok(Value::True)
+}
+
+fn inspect(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let mut parts = Vec::new();
+    for (k, v) in entries_of(interp, recv)? {
+        let k = if k == recv { String::from("{...}") } else { interp.inspect(k)? };
+        let v = if v == recv { String::from("{...}") } else { interp.inspect(v)? };
+        parts.push(format!("{}=>{}", k, v));
+    }
+    Ok(interp.new_string(format!("{{{}}}", parts.join(", "))))
+}
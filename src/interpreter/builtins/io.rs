@@ -0,0 +1,95 @@
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::runtime::heap::{MethodEntry, ObjectKind};
+use crate::interpreter::runtime::value::Value;
+use super::kernel;
+
+// `$stdin`, `$stdout` and `$stderr` (also `STDIN`, `STDOUT` and `STDERR`) are the only IOs: they
+// read and write the interpreter's streams, picked by their file descriptor number.
+pub fn install(interp: &mut Interpreter) {
+    let object = interp.core.object;
+    let io = interp.new_module(None, true, Some(object));
+    let name = interp.sym("IO");
+    interp.set_constant(object, name, Value::Object(io));
+    let singleton = interp.singleton_class(Value::Object(io)).expect("classes have singleton classes");
+    let new = interp.sym("new");
+    interp.define_method(singleton, new, MethodEntry::Undefined);
+    interp.define_builtin(io, "fileno", |interp, recv, _, _| Ok(Value::Integer(fileno(interp, recv))));
+    interp.define_builtin(io, "puts", puts);
+    interp.define_builtin(io, "print", print);
+    interp.define_builtin(io, "write", write);
+    interp.define_builtin(io, "<<", |interp, recv, args, _| {
+        interp.check_args(args, 1, Some(1))?;
+        let s = interp.to_s(args[0])?;
+        write_to(interp, recv, &s)?;
+        Ok(recv)
+    });
+    interp.define_builtin(io, "gets", |interp, recv, args, block| {
+        if fileno(interp, recv) != 0 {
+            return Err(interp.error(interp.core.io_error, String::from("not opened for reading")));
+        }
+        kernel::gets(interp, recv, args, block)
+    });
+    // Every write is flushed already.
+    interp.define_builtin(io, "flush", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(io, "sync", |_, _, _, _| Ok(Value::True));
+    interp.define_builtin(io, "sync=", |interp, _, args, _| {
+        interp.check_args(args, 1, Some(1))?;
+        Ok(args[0])
+    });
+    interp.define_builtin(io, "inspect", |interp, recv, _, _| {
+        let name = ["STDIN", "STDOUT", "STDERR"].get(fileno(interp, recv) as usize).copied().unwrap_or("?");
+        Ok(interp.new_string(format!("#<IO:<{}>>", name)))
+    });
+
+    for (fd, constant, global) in [(0, "STDIN", "$stdin"), (1, "STDOUT", "$stdout"), (2, "STDERR", "$stderr")] {
+        let stream = Value::Object(interp.alloc(io, ObjectKind::Plain));
+        let ivar = interp.sym("@fileno");
+        interp.set_ivar(stream, ivar, Value::Integer(fd)).expect("fresh objects aren't frozen");
+        let name = interp.sym(constant);
+        interp.set_constant(object, name, stream);
+        interp.set_global(global, stream);
+    }
+}
+
+fn fileno(interp: &mut Interpreter, recv: Value) -> i64 {
+    let ivar = interp.sym("@fileno");
+    match interp.get_ivar(recv, ivar) {
+        Value::Integer(fd) => fd,
+        _ => -1
+    }
+}
+
+fn write_to(interp: &mut Interpreter, recv: Value, s: &str) -> Result<(), Unwind> {
+    match fileno(interp, recv) {
+        1 => interp.write_out(s),
+        2 => interp.write_err(s),
+        _ => return Err(interp.error(interp.core.io_error, String::from("not opened for writing")))
+    }
+    Ok(())
+}
+
+fn puts(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    let mut out = String::new();
+    if args.is_empty() {
+        out.push('\n');
+    }
+    for arg in args.iter() {
+        kernel::puts_lines(interp, *arg, &mut out)?;
+    }
+    write_to(interp, recv, &out)?;
+    Ok(Value::Nil)
+}
+
+fn print(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    write(interp, recv, args, block).map(|_| Value::Nil)
+}
+
+// Like `print`, but returns the number of bytes written.
+fn write(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    let mut out = String::new();
+    for arg in args.iter() {
+        out.push_str(&interp.to_s(*arg)?);
+    }
+    write_to(interp, recv, &out)?;
+    Ok(Value::Integer(out.len() as i64))
+}
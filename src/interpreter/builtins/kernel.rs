@@ -0,0 +1,280 @@
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::runtime::heap::ObjectKind;
+use crate::interpreter::runtime::value::Value;
+use super::{expect_string, format, numeric, require_block};
+
+pub fn install(interp: &mut Interpreter) {
+    let kernel = interp.core.kernel;
+    interp.define_builtin(kernel, "puts", puts);
+    interp.define_builtin(kernel, "print", print);
+    interp.define_builtin(kernel, "p", p);
+    interp.define_builtin(kernel, "pp", p);
+    interp.define_builtin(kernel, "warn", warn);
+    interp.define_builtin(kernel, "gets", gets);
+    interp.define_builtin(kernel, "format", sprintf);
+    interp.define_builtin(kernel, "sprintf", sprintf);
+    interp.define_builtin(kernel, "printf", |interp, recv, args, block| {
+        if args.is_empty() {
+            return Ok(Value::Nil);
+        }
+        let formatted = sprintf(interp, recv, args, block)?;
+        let s = interp.to_s(formatted)?;
+        interp.write_out(&s);
+        Ok(Value::Nil)
+    });
+    interp.define_builtin(kernel, "require", require);
+    interp.define_builtin(kernel, "require_relative", require_relative);
+    interp.define_builtin(kernel, "raise", raise);
+    interp.define_builtin(kernel, "fail", raise);
+    interp.define_builtin(kernel, "loop", loop_);
+    interp.define_builtin(kernel, "lambda", lambda);
+    interp.define_builtin(kernel, "proc", proc);
+    interp.define_builtin(kernel, "block_given?", block_given);
+    interp.define_builtin(kernel, "exit", exit);
+    interp.define_builtin(kernel, "at_exit", |interp, _, _, block| {
+        let handler = require_block(interp, block)?;
+        interp.at_exit(handler);
+        Ok(handler)
+    });
+    interp.define_builtin(kernel, "Integer", integer);
+    interp.define_builtin(kernel, "Float", float);
+    interp.define_builtin(kernel, "String", string);
+    interp.define_builtin(kernel, "Array", array);
+    interp.define_builtin(kernel, "__method__", method_name);
+}
+
+// `puts` writes each argument on its own line, flattening arrays.
+pub fn puts_lines(interp: &mut Interpreter, v: Value, out: &mut String) -> Result<(), Unwind> {
+    if let Some(items) = interp.array_items(v).cloned() {
+        // An empty array is written like `puts` with no arguments: as an empty line.
+        if items.is_empty() {
+            out.push('\n');
+        }
+        for item in items {
+            puts_lines(interp, item, out)?;
+        }
+        return Ok(());
+    }
+    let s = interp.to_s(v)?;
+    out.push_str(&s);
+    if !s.ends_with('\n') {
+        out.push('\n');
+    }
+    Ok(())
+}
+
+fn puts(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    let mut out = String::new();
+    if args.is_empty() {
+        out.push('\n');
+    }
+    for arg in args.iter() {
+        puts_lines(interp, *arg, &mut out)?;
+    }
+    interp.write_out(&out);
+    Ok(Value::Nil)
+}
+
+// Like `puts`, but to the error stream, and nothing at all without arguments.
+fn warn(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    let mut out = String::new();
+    for arg in args.iter() {
+        puts_lines(interp, *arg, &mut out)?;
+    }
+    interp.write_err(&out);
+    Ok(Value::Nil)
+}
+
+// The next line of input, also left in `$_`; nil at the end of input. `gets(chomp: true)`
+// drops the line break.
+pub fn gets(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let chomp = match args.first() {
+        Some(options) if interp.is_a(*options, interp.core.hash) => {
+            let key = Value::Symbol(interp.sym("chomp"));
+            interp.call(*options, "[]", &[key])?.is_truthy()
+        },
+        Some(v) => return Err(super::conversion_error(interp, *v, "Hash")),
+        None => false
+    };
+    let line = match interp.read_line() {
+        Ok(Some(line)) if chomp => Some(line.trim_end_matches(['\r', '\n']).to_string()),
+        Ok(line) => line,
+        Err(e) => return Err(interp.error(interp.core.io_error, e.to_string()))
+    };
+    let line = match line {
+        Some(line) => interp.new_string(line),
+        None => Value::Nil
+    };
+    interp.set_global("$_", line);
+    Ok(line)
+}
+
+// `format(template, *args)`; see `builtins::format` for the directives.
+fn sprintf(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, None)?;
+    let template = expect_string(interp, args[0])?;
+    let formatted = format::format(interp, &template, &args[1..])?;
+    Ok(interp.new_string(formatted))
+}
+
+fn print(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    let mut out = String::new();
+    for arg in args.iter() {
+        out.push_str(&interp.to_s(*arg)?);
+    }
+    interp.write_out(&out);
+    Ok(Value::Nil)
+}
+
+fn p(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    let mut out = String::new();
+    for arg in args.iter() {
+        out.push_str(&interp.inspect(*arg)?);
+        out.push('\n');
+    }
+    interp.write_out(&out);
+    Ok(match args.len() {
+        0 => Value::Nil,
+        1 => args[0],
+        _ => interp.new_array(args.to_vec())
+    })
+}
+
+fn require(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let name = expect_string(interp, args[0])?;
+    interp.require(&name, false)
+}
+
+fn require_relative(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let name = expect_string(interp, args[0])?;
+    interp.require(&name, true)
+}
+
+// raise
+// raise "message"
+// raise ErrorClass[, "message"]
+// raise exception
+fn raise(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(2))?;
+    let exc = match args {
+        [] => match interp.global("$!") {
+            Value::Nil => interp.new_exception(interp.core.runtime_error, "unhandled exception"),
+            current => current
+        },
+        [message] if interp.str_ref(*message).is_some() => {
+            let exc = interp.new_exception(interp.core.runtime_error, "");
+            set_message(interp, exc, *message);
+            exc
+        },
+        [class_or_exc, rest @ ..] => interp.call(*class_or_exc, "exception", rest)?
+    };
+    if !interp.is_a(exc, interp.core.exception) {
+        return Err(interp.type_error(String::from("exception class/object expected")));
+    }
+    interp.fill_backtrace(exc);
+    Err(Unwind::Raise(exc))
+}
+
+fn set_message(interp: &mut Interpreter, exc: Value, message: Value) {
+    if let Some(ObjectKind::Exception(data)) = exc.as_object().map(|r| &mut interp.heap.get_mut(r).kind) {
+        data.message = message;
+    }
+}
+
+// Repeats the block until it breaks; `StopIteration` ends the loop quietly.
+fn loop_(interp: &mut Interpreter, _recv: Value, _args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = require_block(interp, block)?;
+    loop {
+        match interp.call_proc_for_effect(block, &[]) {
+            Ok(()) => {},
+            Err(Unwind::Raise(exc)) if interp.is_a(exc, interp.core.stop_iteration) => return Ok(Value::Nil),
+            Err(u) => return Err(u)
+        }
+    }
+}
+
+fn lambda(interp: &mut Interpreter, _recv: Value, _args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = require_block(interp, block)?;
+    if let Some(ObjectKind::Proc(data)) = block.as_object().map(|r| &mut interp.heap.get_mut(r).kind) {
+        data.is_lambda = true;
+    }
+    Ok(block)
+}
+
+fn proc(interp: &mut Interpreter, _recv: Value, _args: &[Value], block: Option<Value>) -> EvalResult {
+    require_block(interp, block)
+}
+
+fn block_given(interp: &mut Interpreter, _recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    Ok(Value::from_bool(interp.frame().context.block.is_some()))
+}
+
+// `exit` unwinds like any other exception so `ensure` blocks still run.
+fn exit(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let status = match args.first() {
+        None | Some(Value::True) => 0,
+        Some(Value::False) => 1,
+        Some(v) => super::expect_integer(interp, *v)?
+    };
+    let exc = interp.new_exception(interp.core.system_exit, "exit");
+    let ivar = interp.sym("@status");
+    interp.set_ivar(exc, ivar, Value::Integer(status))?;
+    interp.fill_backtrace(exc);
+    Err(Unwind::Raise(exc))
+}
+
+pub fn integer(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    match args[0] {
+        Value::Integer(n) => Ok(Value::Integer(n)),
+        Value::Float(f) if f.is_finite() => Ok(numeric::float_to_integer(interp, f)),
+        v if numeric::is_integer(interp, v) => Ok(v),
+        v => match interp.string_value(v) {
+            Some(s) => match numeric::parse_integer(interp, &s.trim().replace('_', "")) {
+                Some(n) => Ok(n),
+                None => Err(interp.argument_error(format!("invalid value for Integer(): {:?}", s)))
+            },
+            None => Err(super::conversion_error(interp, v, "Integer"))
+        }
+    }
+}
+
+pub fn float(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    match args[0] {
+        Value::Integer(n) => Ok(Value::Float(n as f64)),
+        Value::Float(f) => Ok(Value::Float(f)),
+        v => match interp.string_value(v) {
+            Some(s) => match numeric::parse_float(s.trim()) {
+                Some(f) => Ok(Value::Float(f)),
+                None => Err(interp.argument_error(format!("invalid value for Float(): {:?}", s)))
+            },
+            None => Err(super::conversion_error(interp, v, "Float"))
+        }
+    }
+}
+
+fn string(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let s = interp.to_s(args[0])?;
+    Ok(interp.new_string(s))
+}
+
+fn array(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let to_a = interp.sym("to_a");
+    match args[0] {
+        Value::Nil => Ok(interp.new_array(Vec::new())),
+        v if interp.array_items(v).is_some() => Ok(v),
+        v if interp.responds_to(v, to_a) => interp.call(v, "to_a", &[]),
+        v => Ok(interp.new_array(vec![v]))
+    }
+}
+
+fn method_name(interp: &mut Interpreter, _recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    Ok(interp.frame().context.method.as_ref().map_or(Value::Nil, |m| Value::Symbol(m.name)))
+}
@@ -0,0 +1,365 @@
+pub mod array;
+pub mod comparable;
+pub mod complex;
+pub mod encoding;
+pub mod enumerable;
+pub mod exception;
+pub mod format;
+pub mod gc;
+pub mod hash;
+pub mod io;
+pub mod kernel;
+pub mod module;
+pub mod numeric;
+pub mod object;
+pub mod proc;
+pub mod range;
+pub mod rational;
+pub mod regexp;
+pub mod string;
+pub mod symbol;
+
+use std::cmp::Ordering;
+
+use super::interpreter::{Interpreter, Unwind};
+use super::runtime::heap::{Heap, ModuleData, Object, ObjectKind};
+use super::runtime::symbols::{Sym, SymbolTable};
+use super::runtime::value::{ObjRef, Value};
+
+// The classes and modules the interpreter itself refers to.
+pub struct CoreClasses {
+    pub basic_object: ObjRef,
+    pub object: ObjRef,
+    pub module: ObjRef,
+    pub class: ObjRef,
+    pub kernel: ObjRef,
+    pub comparable: ObjRef,
+    pub enumerable: ObjRef,
+    pub nil_class: ObjRef,
+    pub true_class: ObjRef,
+    pub false_class: ObjRef,
+    pub numeric: ObjRef,
+    pub integer: ObjRef,
+    pub float: ObjRef,
+    pub rational: ObjRef,
+    pub complex: ObjRef,
+    pub string: ObjRef,
+    pub symbol: ObjRef,
+    pub array: ObjRef,
+    pub hash: ObjRef,
+    pub range: ObjRef,
+    pub regexp: ObjRef,
+    pub match_data: ObjRef,
+    pub enumerator: ObjRef,
+    pub lazy: ObjRef,
+    pub proc_class: ObjRef,
+    pub encoding: ObjRef,
+    pub exception: ObjRef,
+    pub script_error: ObjRef,
+    pub load_error: ObjRef,
+    pub syntax_error: ObjRef,
+    pub not_implemented_error: ObjRef,
+    pub standard_error: ObjRef,
+    pub runtime_error: ObjRef,
+    pub argument_error: ObjRef,
+    pub type_error: ObjRef,
+    pub name_error: ObjRef,
+    pub no_method_error: ObjRef,
+    pub zero_division_error: ObjRef,
+    pub float_domain_error: ObjRef,
+    pub index_error: ObjRef,
+    pub key_error: ObjRef,
+    pub stop_iteration: ObjRef,
+    pub range_error: ObjRef,
+    pub regexp_error: ObjRef,
+    pub io_error: ObjRef,
+    pub frozen_error: ObjRef,
+    pub local_jump_error: ObjRef,
+    pub system_exit: ObjRef,
+    pub system_stack_error: ObjRef
+}
+
+struct Bootstrap<'h> {
+    heap: &'h mut Heap,
+    symbols: &'h mut SymbolTable,
+    object: ObjRef,
+    class: ObjRef
+}
+
+impl<'h> Bootstrap<'h> {
+    fn define(&mut self, name: &str, is_class: bool, superclass: Option<ObjRef>) -> ObjRef {
+        let data = ModuleData::create(Some(name.to_string()), is_class, superclass);
+        let r = self.heap.alloc(Object::create(self.class, ObjectKind::Module(data)));
+        self.register(name, r);
+        r
+    }
+    // A class named inside another, like `Enumerator::Lazy`.
+    fn define_in(&mut self, outer: ObjRef, name: &str, superclass: ObjRef) -> ObjRef {
+        let full_name = match &self.heap.get(outer).kind {
+            ObjectKind::Module(data) => format!("{}::{}", data.name.as_deref().unwrap_or_default(), name),
+            _ => name.to_string()
+        };
+        let data = ModuleData::create(Some(full_name), true, Some(superclass));
+        let r = self.heap.alloc(Object::create(self.class, ObjectKind::Module(data)));
+        let sym = self.symbols.intern(name);
+        if let ObjectKind::Module(data) = &mut self.heap.get_mut(outer).kind {
+            data.constants.insert(sym, Value::Object(r));
+        }
+        r
+    }
+    fn register(&mut self, name: &str, r: ObjRef) {
+        let sym = self.symbols.intern(name);
+        if let ObjectKind::Module(data) = &mut self.heap.get_mut(self.object).kind {
+            data.constants.insert(sym, Value::Object(r));
+        }
+    }
+    fn include(&mut self, class: ObjRef, module: ObjRef) {
+        if let ObjectKind::Module(data) = &mut self.heap.get_mut(class).kind {
+            data.includes.push(module);
+        }
+    }
+}
+
+// Builds the class hierarchy. `BasicObject`, `Object`, `Module` and `Class` refer to each other,
+// so they're allocated first and their classes patched afterwards.
+pub fn bootstrap(heap: &mut Heap, symbols: &mut SymbolTable) -> CoreClasses {
+    let placeholder = ObjRef(0);
+    let mut raw = |name: &str, superclass: Option<ObjRef>| -> ObjRef {
+        heap.alloc(Object::create(placeholder, ObjectKind::Module(ModuleData::create(Some(name.to_string()), true, superclass))))
+    };
+    let basic_object = raw("BasicObject", None);
+    let object = raw("Object", Some(basic_object));
+    let module = raw("Module", Some(object));
+    let class = raw("Class", Some(module));
+    for r in [basic_object, object, module, class] {
+        heap.get_mut(r).class = class;
+    }
+    let mut b = Bootstrap { heap, symbols, object, class };
+    for (name, r) in [("BasicObject", basic_object), ("Object", object), ("Module", module), ("Class", class)] {
+        b.register(name, r);
+    }
+    let kernel = b.define("Kernel", false, None);
+    b.heap.get_mut(kernel).class = module;
+    b.include(object, kernel);
+    let comparable = b.define("Comparable", false, None);
+    b.heap.get_mut(comparable).class = module;
+    let enumerable = b.define("Enumerable", false, None);
+    b.heap.get_mut(enumerable).class = module;
+    let numeric = b.define("Numeric", true, Some(object));
+    let string = b.define("String", true, Some(object));
+    for r in [numeric, string] {
+        b.include(r, comparable);
+    }
+    let array = b.define("Array", true, Some(object));
+    let hash = b.define("Hash", true, Some(object));
+    let range = b.define("Range", true, Some(object));
+    let enumerator = b.define("Enumerator", true, Some(object));
+    for r in [array, hash, range, enumerator] {
+        b.include(r, enumerable);
+    }
+    let lazy = b.define_in(enumerator, "Lazy", enumerator);
+    let exception = b.define("Exception", true, Some(object));
+    let script_error = b.define("ScriptError", true, Some(exception));
+    let standard_error = b.define("StandardError", true, Some(exception));
+    let argument_error = b.define("ArgumentError", true, Some(standard_error));
+    let name_error = b.define("NameError", true, Some(standard_error));
+    let index_error = b.define("IndexError", true, Some(standard_error));
+    let range_error = b.define("RangeError", true, Some(standard_error));
+    let runtime_error = b.define("RuntimeError", true, Some(standard_error));
+    CoreClasses {
+        basic_object,
+        object,
+        module,
+        class,
+        kernel,
+        comparable,
+        enumerable,
+        nil_class: b.define("NilClass", true, Some(object)),
+        true_class: b.define("TrueClass", true, Some(object)),
+        false_class: b.define("FalseClass", true, Some(object)),
+        numeric,
+        integer: b.define("Integer", true, Some(numeric)),
+        float: b.define("Float", true, Some(numeric)),
+        rational: b.define("Rational", true, Some(numeric)),
+        complex: b.define("Complex", true, Some(numeric)),
+        string,
+        symbol: b.define("Symbol", true, Some(object)),
+        array,
+        hash,
+        range,
+        regexp: b.define("Regexp", true, Some(object)),
+        match_data: b.define("MatchData", true, Some(object)),
+        enumerator,
+        lazy,
+        proc_class: b.define("Proc", true, Some(object)),
+        encoding: b.define("Encoding", true, Some(object)),
+        exception,
+        script_error,
+        load_error: b.define("LoadError", true, Some(script_error)),
+        syntax_error: b.define("SyntaxError", true, Some(script_error)),
+        not_implemented_error: b.define("NotImplementedError", true, Some(script_error)),
+        standard_error,
+        runtime_error,
+        argument_error,
+        type_error: b.define("TypeError", true, Some(standard_error)),
+        name_error,
+        no_method_error: b.define("NoMethodError", true, Some(name_error)),
+        zero_division_error: b.define("ZeroDivisionError", true, Some(standard_error)),
+        float_domain_error: b.define("FloatDomainError", true, Some(range_error)),
+        index_error,
+        key_error: b.define("KeyError", true, Some(index_error)),
+        stop_iteration: b.define("StopIteration", true, Some(index_error)),
+        range_error,
+        regexp_error: b.define("RegexpError", true, Some(standard_error)),
+        io_error: b.define("IOError", true, Some(standard_error)),
+        frozen_error: b.define("FrozenError", true, Some(runtime_error)),
+        local_jump_error: b.define("LocalJumpError", true, Some(standard_error)),
+        system_exit: b.define("SystemExit", true, Some(exception)),
+        system_stack_error: b.define("SystemStackError", true, Some(exception))
+    }
+}
+
+pub fn install(interp: &mut Interpreter) {
+    object::install(interp);
+    comparable::install(interp);
+    enumerable::install(interp);
+    kernel::install(interp);
+    module::install(interp);
+    numeric::install(interp);
+    rational::install(interp);
+    complex::install(interp);
+    string::install(interp);
+    regexp::install(interp);
+    symbol::install(interp);
+    array::install(interp);
+    hash::install(interp);
+    range::install(interp);
+    proc::install(interp);
+    exception::install(interp);
+    encoding::install(interp);
+    gc::install(interp);
+    io::install(interp);
+}
+
+// -- argument helpers shared by the builtin classes --
+
+pub fn require_block(interp: &mut Interpreter, block: Option<Value>) -> Result<Value, Unwind> {
+    match block {
+        Some(b) => Ok(b),
+        None => Err(interp.error(interp.core.local_jump_error, String::from("no block given (yield)")))
+    }
+}
+
+// How a value is named in conversion errors: `nil`, `true`, `false` or its class.
+pub fn type_name(interp: &Interpreter, v: Value) -> String {
+    match v {
+        Value::Nil => String::from("nil"),
+        Value::True => String::from("true"),
+        Value::False => String::from("false"),
+        v => interp.module_name(interp.real_class(v))
+    }
+}
+
+pub fn conversion_error(interp: &mut Interpreter, v: Value, target: &str) -> Unwind {
+    let message = match v {
+        Value::Nil => format!("no implicit conversion from nil to {}", target.to_lowercase()),
+        v => format!("no implicit conversion of {} into {}", type_name(interp, v), target)
+    };
+    interp.type_error(message)
+}
+
+pub fn expect_integer(interp: &mut Interpreter, v: Value) -> Result<i64, Unwind> {
+    match v {
+        Value::Integer(n) => Ok(n),
+        Value::Float(f) if f.is_finite() => Ok(f as i64),
+        v if interp.big_int(v).is_some() => Err(interp.error(interp.core.range_error, String::from("bignum too big to convert into `long'"))),
+        v => Err(conversion_error(interp, v, "Integer"))
+    }
+}
+
+// A count of array elements, as in `Array.new(n)` and `first(n)`, which can't be negative.
+pub fn array_size(interp: &mut Interpreter, v: Value) -> Result<usize, Unwind> {
+    match expect_integer(interp, v)? {
+        n if n < 0 => Err(interp.argument_error(String::from("negative array size"))),
+        n => Ok(n as usize)
+    }
+}
+
+pub fn expect_string(interp: &mut Interpreter, v: Value) -> Result<String, Unwind> {
+    match interp.string_value(v) {
+        Some(s) => Ok(s),
+        None => Err(conversion_error(interp, v, "String"))
+    }
+}
+
+pub fn expect_name(interp: &mut Interpreter, v: Value) -> Result<Sym, Unwind> {
+    match v {
+        Value::Symbol(sym) => Ok(sym),
+        v => match interp.string_value(v) {
+            Some(s) => Ok(interp.sym(&s)),
+            None => {
+                let shown = interp.inspect(v)?;
+                Err(interp.type_error(format!("{} is not a symbol nor a string", shown)))
+            }
+        }
+    }
+}
+
+// `a <=> b` as an ordering, with the common cases done directly.
+pub fn compare(interp: &mut Interpreter, a: Value, b: Value) -> Result<Ordering, Unwind> {
+    let ordering = match (a, b) {
+        (Value::Integer(x), Value::Integer(y)) => Some(x.cmp(&y)),
+        (Value::Integer(x), Value::Float(y)) => (x as f64).partial_cmp(&y),
+        (Value::Float(x), Value::Integer(y)) => x.partial_cmp(&(y as f64)),
+        (Value::Float(x), Value::Float(y)) => x.partial_cmp(&y),
+        _ => match (interp.str_ref(a), interp.str_ref(b)) {
+            (Some(x), Some(y)) => Some(x.cmp(y)),
+            _ => match interp.call(a, "<=>", &[b])? {
+                Value::Integer(n) => Some(n.cmp(&0)),
+                _ => None
+            }
+        }
+    };
+    match ordering {
+        Some(ordering) => Ok(ordering),
+        None => {
+            let message = format!("comparison of {} with {} failed", type_name(interp, a), describe_operand(interp, b)?);
+            Err(interp.argument_error(message))
+        }
+    }
+}
+
+fn describe_operand(interp: &mut Interpreter, v: Value) -> Result<String, Unwind> {
+    match v {
+        Value::Integer(_) | Value::Float(_) | Value::Nil => interp.inspect(v),
+        v => Ok(type_name(interp, v))
+    }
+}
+
+// Merge sort over a fallible comparison; a comparison that raises stops the sort.
+pub fn sort_values<F>(interp: &mut Interpreter, items: Vec<Value>, cmp: &mut F) -> Result<Vec<Value>, Unwind>
+where
+    F: FnMut(&mut Interpreter, Value, Value) -> Result<Ordering, Unwind>
+{
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+    let mut right = items;
+    let left = right.drain(..right.len() / 2).collect();
+    let left = sort_values(interp, left, cmp)?;
+    let right = sort_values(interp, right, cmp)?;
+    let mut out = Vec::with_capacity(left.len() + right.len());
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        if cmp(interp, left[i], right[j])? == Ordering::Greater {
+            out.push(right[j]);
+            j += 1;
+        } else {
+            out.push(left[i]);
+            i += 1;
+        }
+    }
+    out.extend_from_slice(&left[i..]);
+    out.extend_from_slice(&right[j..]);
+    Ok(out)
+}
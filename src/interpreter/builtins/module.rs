@@ -0,0 +1,333 @@
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::runtime::heap::{ExceptionData, HashTable, MethodEntry, ObjectKind};
+use crate::interpreter::runtime::value::{ObjRef, Value};
+use super::expect_name;
+
+pub fn install(interp: &mut Interpreter) {
+    let module = interp.core.module;
+    interp.define_builtin(module, "name", name);
+    interp.define_builtin(module, "to_s", to_s);
+    interp.define_builtin(module, "inspect", to_s);
+    interp.define_builtin(module, "===", case_equal);
+    interp.define_builtin(module, "<", is_descendant);
+    interp.define_builtin(module, "<=", is_descendant_or_self);
+    interp.define_builtin(module, "ancestors", ancestors);
+    interp.define_builtin(module, "include", include);
+    interp.define_builtin(module, "include?", includes);
+    interp.define_builtin(module, "instance_methods", instance_methods);
+    interp.define_builtin(module, "method_defined?", method_defined);
+    interp.define_builtin(module, "attr_reader", attr_reader);
+    interp.define_builtin(module, "attr_writer", attr_writer);
+    interp.define_builtin(module, "attr_accessor", attr_accessor);
+    interp.define_builtin(module, "define_method", define_method);
+    interp.define_builtin(module, "alias_method", alias_method);
+    interp.define_builtin(module, "module_function", module_function);
+    interp.define_builtin(module, "const_get", const_get);
+    interp.define_builtin(module, "const_set", const_set);
+    interp.define_builtin(module, "const_defined?", const_defined);
+    interp.define_builtin(module, "constants", constants);
+    interp.define_builtin(module, "class_eval", module_eval);
+    interp.define_builtin(module, "module_eval", module_eval);
+    // Visibility isn't enforced; these exist so code that declares it runs.
+    for name in ["private", "public", "protected", "private_constant", "private_class_method", "public_class_method"] {
+        interp.define_builtin(module, name, |_, _, args, _| Ok(args.first().copied().unwrap_or(Value::Nil)));
+    }
+
+    let class = interp.core.class;
+    interp.define_builtin(class, "new", new);
+    interp.define_builtin(class, "allocate", allocate);
+    interp.define_builtin(class, "superclass", superclass);
+}
+
+fn module_ref(interp: &mut Interpreter, v: Value) -> Result<ObjRef, Unwind> {
+    match v.as_object() {
+        Some(r) if interp.module_data(r).is_some() => Ok(r),
+        _ => {
+            let shown = interp.inspect(v)?;
+            Err(interp.type_error(format!("{} is not a class/module", shown)))
+        }
+    }
+}
+
+pub fn include_modules(interp: &mut Interpreter, target: ObjRef, modules: &[Value]) -> Result<(), Unwind> {
+    for m in modules.iter() {
+        let r = module_ref(interp, *m)?;
+        if interp.module_data(r).is_some_and(|d| d.is_class) {
+            return Err(interp.type_error(String::from("wrong argument type Class (expected Module)")));
+        }
+        if interp.ancestors(target).contains(&r) {
+            continue;
+        }
+        if let ObjectKind::Module(data) = &mut interp.heap.get_mut(target).kind {
+            data.includes.push(r);
+        }
+        interp.method_table_changed(target);
+    }
+    Ok(())
+}
+
+fn name(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let r = module_ref(interp, recv)?;
+    match interp.module_data(r).and_then(|d| d.name.clone()) {
+        Some(name) => Ok(interp.new_string(name)),
+        None => Ok(Value::Nil)
+    }
+}
+
+fn to_s(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let r = module_ref(interp, recv)?;
+    let name = interp.module_name(r);
+    Ok(interp.new_string(name))
+}
+
+fn case_equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let r = module_ref(interp, recv)?;
+    Ok(Value::from_bool(interp.is_a(args[0], r)))
+}
+
+fn is_descendant(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let (r, other) = (module_ref(interp, recv)?, module_ref(interp, args[0])?);
+    if r == other {
+        return Ok(Value::False);
+    }
+    is_descendant_or_self(interp, recv, args, None)
+}
+
+fn is_descendant_or_self(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let (r, other) = (module_ref(interp, recv)?, module_ref(interp, args[0])?);
+    if interp.is_subclass(r, other) {
+        Ok(Value::True)
+    } else if interp.is_subclass(other, r) {
+        Ok(Value::False)
+    } else {
+        Ok(Value::Nil)
+    }
+}
+
+fn ancestors(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let r = module_ref(interp, recv)?;
+    let list = interp.ancestors(r).into_iter().map(Value::Object).collect();
+    Ok(interp.new_array(list))
+}
+
+fn include(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, None)?;
+    let r = module_ref(interp, recv)?;
+    // Included last means looked up first, so `include A, B` searches A before B.
+    let reversed: Vec<Value> = args.iter().rev().copied().collect();
+    include_modules(interp, r, &reversed)?;
+    Ok(recv)
+}
+
+fn includes(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let (r, other) = (module_ref(interp, recv)?, module_ref(interp, args[0])?);
+    Ok(Value::from_bool(r != other && interp.module_data(other).is_some_and(|d| !d.is_class) && interp.is_subclass(r, other)))
+}
+
+fn instance_methods(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    let r = module_ref(interp, recv)?;
+    let inherit = args.first().is_none_or(|v| v.is_truthy());
+    let modules = if inherit { interp.ancestors(r) } else { vec![r] };
+    let mut names: Vec<Value> = Vec::new();
+    for m in modules {
+        if let Some(data) = interp.module_data(m) {
+            let mut own: Vec<_> = data.methods.iter().filter(|(_, e)| !matches!(e, MethodEntry::Undefined)).map(|(k, _)| *k).collect();
+            own.sort();
+            for name in own {
+                if !names.contains(&Value::Symbol(name)) {
+                    names.push(Value::Symbol(name));
+                }
+            }
+        }
+    }
+    Ok(interp.new_array(names))
+}
+
+fn method_defined(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let r = module_ref(interp, recv)?;
+    let name = expect_name(interp, args[0])?;
+    Ok(Value::from_bool(interp.find_method(r, name).is_some()))
+}
+
+fn define_attrs(interp: &mut Interpreter, recv: Value, args: &[Value], reader: bool, writer: bool) -> EvalResult {
+    let r = module_ref(interp, recv)?;
+    let mut defined = Vec::new();
+    for arg in args.iter() {
+        let name = expect_name(interp, *arg)?;
+        let name = interp.sym_name(name).to_string();
+        let ivar = interp.sym(&format!("@{}", name));
+        if reader {
+            let sym = interp.sym(&name);
+            interp.define_method(r, sym, MethodEntry::AttrReader(ivar));
+            defined.push(Value::Symbol(sym));
+        }
+        if writer {
+            let sym = interp.sym(&format!("{}=", name));
+            interp.define_method(r, sym, MethodEntry::AttrWriter(ivar));
+            defined.push(Value::Symbol(sym));
+        }
+    }
+    Ok(interp.new_array(defined))
+}
+
+fn attr_reader(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    define_attrs(interp, recv, args, true, false)
+}
+
+fn attr_writer(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    define_attrs(interp, recv, args, false, true)
+}
+
+fn attr_accessor(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    define_attrs(interp, recv, args, true, true)
+}
+
+fn define_method(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let r = module_ref(interp, recv)?;
+    let name = expect_name(interp, args[0])?;
+    let body = match args.get(1).copied().or(block) {
+        Some(body) => body,
+        None => return Err(interp.argument_error(String::from("tried to create Proc object without a block")))
+    };
+    interp.define_method(r, name, MethodEntry::Proc(body));
+    Ok(Value::Symbol(name))
+}
+
+fn alias_method(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 2, Some(2))?;
+    let r = module_ref(interp, recv)?;
+    let (new_name, old_name) = (expect_name(interp, args[0])?, expect_name(interp, args[1])?);
+    match interp.find_method(r, old_name) {
+        Some((entry, _)) => {
+            interp.define_method(r, new_name, entry);
+            Ok(Value::Symbol(new_name))
+        },
+        None => {
+            let message = format!("undefined method `{}' for class `{}'", interp.sym_name(old_name), interp.module_name(r));
+            Err(interp.error(interp.core.name_error, message))
+        }
+    }
+}
+
+// `module_function :a, :b` copies the named methods onto the module itself.
+fn module_function(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    let r = module_ref(interp, recv)?;
+    let singleton = interp.singleton_class(recv)?;
+    for arg in args.iter() {
+        let name = expect_name(interp, *arg)?;
+        if let Some((entry, _)) = interp.find_method(r, name) {
+            interp.define_method(singleton, name, entry);
+        }
+    }
+    Ok(Value::Nil)
+}
+
+fn const_get(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let r = module_ref(interp, recv)?;
+    let name = expect_name(interp, args[0])?;
+    match interp.lookup_constant_in(r, name).or_else(|| interp.lookup_constant_in(interp.core.object, name)) {
+        Some(v) => Ok(v),
+        None => {
+            let message = format!("uninitialized constant {}", interp.sym_name(name));
+            Err(interp.error(interp.core.name_error, message))
+        }
+    }
+}
+
+fn const_set(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 2, Some(2))?;
+    let r = module_ref(interp, recv)?;
+    let name = expect_name(interp, args[0])?;
+    interp.set_constant(r, name, args[1]);
+    Ok(args[1])
+}
+
+fn const_defined(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let r = module_ref(interp, recv)?;
+    let name = expect_name(interp, args[0])?;
+    Ok(Value::from_bool(interp.lookup_constant_in(r, name).is_some()))
+}
+
+fn constants(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let r = module_ref(interp, recv)?;
+    let mut names: Vec<_> = interp.module_data(r).map_or(Vec::new(), |d| d.constants.keys().copied().collect());
+    names.sort();
+    let names = names.into_iter().map(Value::Symbol).collect();
+    Ok(interp.new_array(names))
+}
+
+fn module_eval(interp: &mut Interpreter, recv: Value, _args: &[Value], block: Option<Value>) -> EvalResult {
+    let r = module_ref(interp, recv)?;
+    let block = super::require_block(interp, block)?;
+    interp.call_proc_as(block, Some((recv, r)), &[recv], None, false)
+}
+
+// `Class.new(superclass) { body }`, `Module.new { body }` and `Foo.new(args)`.
+fn new(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let r = module_ref(interp, recv)?;
+    if r == interp.core.class || r == interp.core.module {
+        let is_class = r == interp.core.class;
+        let superclass = match args.first() {
+            Some(v) if is_class => {
+                let s = module_ref(interp, *v)?;
+                if !interp.module_data(s).is_some_and(|d| d.is_class) {
+                    return Err(interp.type_error(String::from("superclass must be a Class")));
+                }
+                Some(s)
+            },
+            _ if is_class => Some(interp.core.object),
+            _ => None
+        };
+        let created = interp.new_module(None, is_class, superclass);
+        if let Some(block) = block {
+            interp.call_proc_as(block, Some((Value::Object(created), created)), &[Value::Object(created)], None, false)?;
+        }
+        return Ok(Value::Object(created));
+    }
+    let instance = allocate(interp, recv, &[], None)?;
+    let initialize = interp.sym("initialize");
+    interp.send(instance, initialize, args, block)?;
+    Ok(instance)
+}
+
+fn allocate(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let r = module_ref(interp, recv)?;
+    if interp.module_data(r).is_some_and(|d| d.attached.is_some()) {
+        return Err(interp.type_error(String::from("can't create instance of singleton class")));
+    }
+    // Subclasses of the built-in classes keep the built-in representation.
+    let core = &interp.core;
+    let kind = if interp.is_subclass(r, core.exception) {
+        ObjectKind::Exception(ExceptionData { message: Value::Nil, backtrace: None, span: None })
+    } else if interp.is_subclass(r, core.string) {
+        ObjectKind::Str(String::new())
+    } else if interp.is_subclass(r, core.array) {
+        ObjectKind::Array(Vec::new())
+    } else if interp.is_subclass(r, core.hash) {
+        ObjectKind::Hash(HashTable::create())
+    } else {
+        ObjectKind::Plain
+    };
+    Ok(Value::Object(interp.alloc(r, kind)))
+}
+
+fn superclass(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let r = module_ref(interp, recv)?;
+    // Singleton classes and included modules aren't reported as superclasses.
+    let mut current = interp.module_data(r).and_then(|d| d.superclass);
+    while let Some(c) = current {
+        if interp.module_data(c).is_some_and(|d| d.attached.is_none()) {
+            return Ok(Value::Object(c));
+        }
+        current = interp.module_data(c).and_then(|d| d.superclass);
+    }
+    Ok(Value::Nil)
+}
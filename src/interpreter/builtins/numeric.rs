@@ -0,0 +1,704 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::parser::lexicon::OperatorSymbol;
+use crate::interpreter::runtime::bigint::BigInt;
+use crate::interpreter::runtime::rational::Rational;
+use crate::interpreter::runtime::value::Value;
+use super::{compare, expect_integer, rational, require_block, type_name};
+
+// Decimal digits that always tell doubles apart (Ruby's `DBL_DIG + 2`).
+static FLOAT_DIGITS: i64 = 17;
+// The largest power of ten a double can hold.
+static MAX_FLOAT_EXPONENT: i64 = 308;
+
+pub fn install(interp: &mut Interpreter) {
+    let numeric = interp.core.numeric;
+    interp.define_builtin(numeric, "+", add);
+    interp.define_builtin(numeric, "-", sub);
+    interp.define_builtin(numeric, "*", mul);
+    interp.define_builtin(numeric, "/", div);
+    interp.define_builtin(numeric, "%", modulo);
+    interp.define_builtin(numeric, "modulo", modulo);
+    interp.define_builtin(numeric, "**", pow);
+    interp.define_builtin(numeric, "-@", negate);
+    interp.define_builtin(numeric, "+@", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(numeric, "==", equal);
+    interp.define_builtin(numeric, "===", equal);
+    interp.define_builtin(numeric, "<=>", cmp);
+    interp.define_builtin(numeric, "<", |interp, recv, args, _| ordered(interp, recv, args, |o| o == Ordering::Less));
+    interp.define_builtin(numeric, "<=", |interp, recv, args, _| ordered(interp, recv, args, |o| o != Ordering::Greater));
+    interp.define_builtin(numeric, ">", |interp, recv, args, _| ordered(interp, recv, args, |o| o == Ordering::Greater));
+    interp.define_builtin(numeric, ">=", |interp, recv, args, _| ordered(interp, recv, args, |o| o != Ordering::Less));
+    interp.define_builtin(numeric, "between?", between);
+    interp.define_builtin(numeric, "divmod", divmod);
+    interp.define_builtin(numeric, "fdiv", |interp, recv, args, _| {
+        interp.check_args(args, 1, Some(1))?;
+        Ok(Value::Float(to_f64(interp, recv)? / to_f64(interp, args[0])?))
+    });
+    interp.define_builtin(numeric, "abs", abs);
+    interp.define_builtin(numeric, "zero?", |interp, recv, _, _| Ok(Value::from_bool(to_f64(interp, recv)? == 0.0)));
+    interp.define_builtin(numeric, "positive?", |interp, recv, _, _| Ok(Value::from_bool(to_f64(interp, recv)? > 0.0)));
+    interp.define_builtin(numeric, "negative?", |interp, recv, _, _| Ok(Value::from_bool(to_f64(interp, recv)? < 0.0)));
+    interp.define_builtin(numeric, "to_i", to_i);
+    interp.define_builtin(numeric, "to_int", to_i);
+    interp.define_builtin(numeric, "to_f", |interp, recv, _, _| Ok(Value::Float(to_f64(interp, recv)?)));
+    interp.define_builtin(numeric, "to_s", to_s);
+    interp.define_builtin(numeric, "inspect", to_s);
+    interp.define_builtin(numeric, "integer?", |interp, recv, _, _| Ok(Value::from_bool(is_integer(interp, recv))));
+    interp.define_builtin(numeric, "step", step);
+
+    let integer = interp.core.integer;
+    interp.define_builtin(integer, "~", |interp, recv, _, _| match interp.big_int(recv).map(BigInt::not) {
+        Some(n) => Ok(interp.new_integer(n)),
+        None => Ok(Value::Integer(!int(interp, recv)?))
+    });
+    interp.define_builtin(integer, "&", |interp, recv, args, _| bitwise(interp, recv, args, |a, b| a & b, |a, b| a & b));
+    interp.define_builtin(integer, "|", |interp, recv, args, _| bitwise(interp, recv, args, |a, b| a | b, |a, b| a | b));
+    interp.define_builtin(integer, "^", |interp, recv, args, _| bitwise(interp, recv, args, |a, b| a ^ b, |a, b| a ^ b));
+    interp.define_builtin(integer, "<<", |interp, recv, args, _| shift(interp, recv, args, false));
+    interp.define_builtin(integer, ">>", |interp, recv, args, _| shift(interp, recv, args, true));
+    interp.define_builtin(integer, "times", times);
+    interp.define_builtin(integer, "upto", upto);
+    interp.define_builtin(integer, "downto", downto);
+    interp.define_builtin(integer, "succ", |interp, recv, _, _| add(interp, recv, &[Value::Integer(1)], None));
+    interp.define_builtin(integer, "next", |interp, recv, _, _| add(interp, recv, &[Value::Integer(1)], None));
+    interp.define_builtin(integer, "pred", |interp, recv, _, _| sub(interp, recv, &[Value::Integer(1)], None));
+    interp.define_builtin(integer, "even?", |interp, recv, _, _| Ok(Value::from_bool(is_even(interp, recv)?)));
+    interp.define_builtin(integer, "odd?", |interp, recv, _, _| Ok(Value::from_bool(!is_even(interp, recv)?)));
+    interp.define_builtin(integer, "chr", chr);
+    interp.define_builtin(integer, "gcd", |interp, recv, args, _| {
+        interp.check_args(args, 1, Some(1))?;
+        Ok(Value::Integer(gcd(int(interp, recv)?, expect_integer(interp, args[0])?)))
+    });
+    interp.define_builtin(integer, "floor", |interp, recv, args, _| rational::rounded(interp, recv, args, Rational::floor));
+    interp.define_builtin(integer, "ceil", |interp, recv, args, _| rational::rounded(interp, recv, args, Rational::ceil));
+    interp.define_builtin(integer, "round", |interp, recv, args, _| rational::rounded(interp, recv, args, Rational::round));
+    interp.define_builtin(integer, "truncate", |interp, recv, args, _| rational::rounded(interp, recv, args, Rational::truncate));
+
+    let float = interp.core.float;
+    interp.define_builtin(float, "nan?", |interp, recv, _, _| Ok(Value::from_bool(to_f64(interp, recv)?.is_nan())));
+    interp.define_builtin(float, "infinite?", |interp, recv, _, _| {
+        let f = to_f64(interp, recv)?;
+        Ok(if f.is_infinite() { Value::Integer(f.signum() as i64) } else { Value::Nil })
+    });
+    interp.define_builtin(float, "finite?", |interp, recv, _, _| Ok(Value::from_bool(to_f64(interp, recv)?.is_finite())));
+    interp.define_builtin(float, "floor", |interp, recv, args, _| rounded(interp, recv, args, f64::floor));
+    interp.define_builtin(float, "ceil", |interp, recv, args, _| rounded(interp, recv, args, f64::ceil));
+    interp.define_builtin(float, "round", |interp, recv, args, _| rounded(interp, recv, args, f64::round));
+    interp.define_builtin(float, "truncate", |interp, recv, args, _| rounded(interp, recv, args, f64::trunc));
+
+    let infinity = interp.sym("INFINITY");
+    interp.set_constant(float, infinity, Value::Float(f64::INFINITY));
+    let nan = interp.sym("NAN");
+    interp.set_constant(float, nan, Value::Float(f64::NAN));
+    let epsilon = interp.sym("EPSILON");
+    interp.set_constant(float, epsilon, Value::Float(f64::EPSILON));
+}
+
+// Floats print the way Ruby prints them: always with a fractional part, switching to exponent
+// form for very large and very small magnitudes.
+pub fn format_float(f: f64) -> String {
+    if f.is_nan() {
+        return String::from("NaN");
+    }
+    if f.is_infinite() {
+        return String::from(if f > 0.0 { "Infinity" } else { "-Infinity" });
+    }
+    let magnitude = f.abs();
+    if magnitude != 0.0 && !(1e-4..1e16).contains(&magnitude) {
+        let formatted = format!("{:e}", f);
+        let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+        let mantissa = if mantissa.contains('.') { mantissa.to_string() } else { format!("{}.0", mantissa) };
+        let exponent: i32 = exponent.parse().unwrap_or(0);
+        return format!("{}e{}{:02}", mantissa, if exponent < 0 { '-' } else { '+' }, exponent.abs());
+    }
+    let formatted = format!("{}", f);
+    if formatted.contains('.') { formatted } else { format!("{}.0", formatted) }
+}
+
+pub fn parse_float(s: &str) -> Option<f64> {
+    let cleaned = s.replace('_', "");
+    if cleaned.is_empty() || cleaned.starts_with('.') || cleaned.ends_with('.') {
+        return None;
+    }
+    cleaned.parse::<f64>().ok().filter(|_| !cleaned.chars().any(|c| c.is_alphabetic() && c != 'e' && c != 'E'))
+}
+
+// Decimal digits with an optional sign, as an Integer of whatever size they need.
+pub fn parse_integer(interp: &mut Interpreter, digits: &str) -> Option<Value> {
+    match digits.parse::<i64>() {
+        Ok(n) => Some(Value::Integer(n)),
+        Err(_) => BigInt::parse(digits).map(|n| interp.new_integer(n))
+    }
+}
+
+pub fn to_f64(interp: &mut Interpreter, v: Value) -> Result<f64, Unwind> {
+    match v {
+        Value::Integer(n) => Ok(n as f64),
+        Value::Float(f) => Ok(f),
+        v if interp.big_int(v).is_some() => Ok(interp.big_int(v).map_or(0.0, BigInt::to_f64)),
+        v if interp.rational(v).is_some() => Ok(interp.rational(v).map_or(0.0, Rational::to_f64)),
+        v => {
+            let message = format!("{} can't be coerced into Float", type_name(interp, v));
+            Err(interp.type_error(message))
+        }
+    }
+}
+
+fn int(interp: &mut Interpreter, v: Value) -> Result<i64, Unwind> {
+    expect_integer(interp, v)
+}
+
+pub fn is_integer(interp: &Interpreter, v: Value) -> bool {
+    matches!(v, Value::Integer(_)) || interp.big_int(v).is_some()
+}
+
+fn is_even(interp: &mut Interpreter, v: Value) -> Result<bool, Unwind> {
+    match interp.big_int(v) {
+        Some(n) => Ok(n.is_even()),
+        None => Ok(int(interp, v)? % 2 == 0)
+    }
+}
+
+// An operand of numeric arithmetic, with big integers copied out of the heap.
+enum Num {
+    Int(i64),
+    Big(BigInt),
+    Float(f64)
+}
+
+impl Num {
+    fn to_big(&self) -> Option<BigInt> {
+        match self {
+            Num::Int(n) => Some(BigInt::from(*n)),
+            Num::Big(n) => Some(n.clone()),
+            Num::Float(_) => None
+        }
+    }
+    fn to_float(&self) -> f64 {
+        match self {
+            Num::Int(n) => *n as f64,
+            Num::Big(n) => n.to_f64(),
+            Num::Float(f) => *f
+        }
+    }
+}
+
+fn num(interp: &Interpreter, v: Value) -> Option<Num> {
+    match v {
+        Value::Integer(n) => Some(Num::Int(n)),
+        Value::Float(f) => Some(Num::Float(f)),
+        v => interp.big_int(v).map(|n| Num::Big(n.clone()))
+    }
+}
+
+pub fn coerce_error(interp: &mut Interpreter, recv: Value, other: Value) -> Unwind {
+    let message = format!("{} can't be coerced into {}", type_name(interp, other), type_name(interp, recv));
+    interp.type_error(message)
+}
+
+pub fn zero_division(interp: &mut Interpreter) -> Unwind {
+    interp.error(interp.core.zero_division_error, String::from("divided by 0"))
+}
+
+// Applies the operator `name` to two numbers: `ints` when both are machine integers, `bigs`
+// when either is big or `ints` overflowed, and `floats` when either is a float. The integer
+// operations return `None` only when dividing by zero. Other operands are coerced.
+fn arithmetic(
+    interp: &mut Interpreter,
+    name: &str,
+    recv: Value,
+    args: &[Value],
+    ints: fn(i64, i64) -> Option<i64>,
+    bigs: fn(&BigInt, &BigInt) -> Option<BigInt>,
+    floats: fn(f64, f64) -> f64
+) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let (a, b) = match (num(interp, recv), num(interp, args[0])) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return coerced(interp, name, recv, args[0])
+    };
+    if let (Num::Int(x), Num::Int(y)) = (&a, &b) {
+        if let Some(n) = ints(*x, *y) {
+            return Ok(Value::Integer(n));
+        }
+    }
+    match (a.to_big(), b.to_big()) {
+        (Some(x), Some(y)) => match bigs(&x, &y) {
+            Some(n) => Ok(interp.new_integer(n)),
+            None => Err(zero_division(interp))
+        },
+        _ => Ok(Value::Float(floats(a.to_float(), b.to_float())))
+    }
+}
+
+// `recv name other` done in the common type `other.coerce(recv)` returns, which is how Integer
+// and Float work with Rational, Complex and user-defined numbers.
+pub fn coerced(interp: &mut Interpreter, name: &str, recv: Value, other: Value) -> EvalResult {
+    match coerce_pair(interp, recv, other)? {
+        Some((a, b)) => interp.call(a, name, &[b]),
+        None => Err(coerce_error(interp, recv, other))
+    }
+}
+
+// `None` when `other` has no `coerce`.
+pub fn coerce_pair(interp: &mut Interpreter, recv: Value, other: Value) -> Result<Option<(Value, Value)>, Unwind> {
+    let coerce = interp.sym("coerce");
+    if !interp.responds_to(other, coerce) {
+        return Ok(None);
+    }
+    let pair = interp.call(other, "coerce", &[recv])?;
+    match interp.array_items(pair).map(Vec::as_slice) {
+        Some(&[a, b]) => Ok(Some((a, b))),
+        _ => Err(interp.type_error(String::from("coerce must return [x, y]")))
+    }
+}
+
+fn add(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    arithmetic(interp, "+", recv, args, i64::checked_add, |a, b| Some(a.add(b)), |a, b| a + b)
+}
+
+fn sub(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    arithmetic(interp, "-", recv, args, i64::checked_sub, |a, b| Some(a.sub(b)), |a, b| a - b)
+}
+
+fn mul(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    arithmetic(interp, "*", recv, args, i64::checked_mul, |a, b| Some(a.mul(b)), |a, b| a * b)
+}
+
+// Integer and Float arithmetic and comparisons computed without a method call. `None` leaves
+// the operation to the method, which also covers every case that raises.
+pub fn fast_binary_op(op: OperatorSymbol, l: Value, r: Value) -> Option<Value> {
+    use OperatorSymbol::*;
+    match (l, r) {
+        (Value::Integer(a), Value::Integer(b)) => match op {
+            PLUS     => a.checked_add(b).map(Value::Integer),
+            MINUS    => a.checked_sub(b).map(Value::Integer),
+            ASTERISK => a.checked_mul(b).map(Value::Integer),
+            SLASH    => floor_div(a, b).map(Value::Integer),
+            MODULO   => floor_mod(a, b).map(Value::Integer),
+            LT       => Some(Value::from_bool(a < b)),
+            LTE      => Some(Value::from_bool(a <= b)),
+            GT       => Some(Value::from_bool(a > b)),
+            GTE      => Some(Value::from_bool(a >= b)),
+            EQ       => Some(Value::from_bool(a == b)),
+            NOT_EQ   => Some(Value::from_bool(a != b)),
+            COMP     => Some(Value::Integer(a.cmp(&b) as i64)),
+            _        => None
+        },
+        (Value::Float(_), Value::Float(_) | Value::Integer(_)) | (Value::Integer(_), Value::Float(_)) => {
+            let (a, b) = (as_float(l)?, as_float(r)?);
+            match op {
+                PLUS     => Some(Value::Float(a + b)),
+                MINUS    => Some(Value::Float(a - b)),
+                ASTERISK => Some(Value::Float(a * b)),
+                SLASH    => Some(Value::Float(a / b)),
+                LT       => Some(Value::from_bool(a < b)),
+                LTE      => Some(Value::from_bool(a <= b)),
+                GT       => Some(Value::from_bool(a > b)),
+                GTE      => Some(Value::from_bool(a >= b)),
+                EQ       => Some(Value::from_bool(a == b)),
+                NOT_EQ   => Some(Value::from_bool(a != b)),
+                _        => None
+            }
+        },
+        _ => None
+    }
+}
+
+fn as_float(v: Value) -> Option<f64> {
+    match v {
+        Value::Integer(n) => Some(n as f64),
+        Value::Float(f) => Some(f),
+        _ => None
+    }
+}
+
+// Integer division floors, so `-7 / 2` is `-4`.
+pub fn floor_div(a: i64, b: i64) -> Option<i64> {
+    let q = a.checked_div(b)?;
+    Some(if (a % b != 0) && ((a < 0) != (b < 0)) { q - 1 } else { q })
+}
+
+// The remainder takes the sign of the divisor, so `-7 % 2` is `1`.
+pub fn floor_mod(a: i64, b: i64) -> Option<i64> {
+    let r = a.checked_rem(b)?;
+    Some(if r != 0 && ((r < 0) != (b < 0)) { r + b } else { r })
+}
+
+fn float_mod(a: f64, b: f64) -> f64 {
+    let r = a % b;
+    if r != 0.0 && ((r < 0.0) != (b < 0.0)) { r + b } else { r }
+}
+
+fn div(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    arithmetic(interp, "/", recv, args, floor_div, |a, b| a.div_mod_floor(b).map(|(q, _)| q), |a, b| a / b)
+}
+
+fn modulo(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    arithmetic(interp, "%", recv, args, floor_mod, |a, b| a.div_mod_floor(b).map(|(_, r)| r), float_mod)
+}
+
+fn pow(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let base = match num(interp, recv) {
+        Some(base) => base,
+        None => return Err(coerce_error(interp, recv, args[0]))
+    };
+    let exponent = match (base.to_big(), num(interp, args[0])) {
+        (Some(_), Some(Num::Int(b))) if b >= 0 => b as u64,
+        // Too big to reach unless the base is 0, 1 or -1, where only its parity matters.
+        (Some(_), Some(Num::Big(b))) if !b.is_negative() => if b.is_even() { u64::MAX - 1 } else { u64::MAX },
+        (Some(a), Some(Num::Int(b))) => return match Rational::from_integer(a).pow(b) {
+            Some(q) => Ok(interp.new_rational(q)),
+            None => Err(zero_division(interp))
+        },
+        (Some(_), Some(b)) => return Ok(Value::Float(base.to_float().powf(b.to_float()))),
+        _ => return arithmetic(interp, "**", recv, args, |_, _| None, |_, _| None, f64::powf)
+    };
+    if let Num::Int(a) = base {
+        if let Some(n) = u32::try_from(exponent).ok().and_then(|b| a.checked_pow(b)) {
+            return Ok(Value::Integer(n));
+        }
+    }
+    let base = base.to_big().unwrap_or_else(BigInt::zero);
+    if base.bit_length() <= 1 {
+        return Ok(interp.new_integer(base.pow(2 - exponent % 2)));
+    }
+    if exponent > MAX_BIG_BITS as u64 / base.bit_length() as u64 {
+        return Err(interp.argument_error(String::from("exponent is too large")));
+    }
+    Ok(interp.new_integer(base.pow(exponent)))
+}
+
+// The largest integer `**` and `<<` will build, in bits.
+pub const MAX_BIG_BITS: usize = 32 * 1024 * 1024;
+
+fn negate(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    match num(interp, recv) {
+        Some(Num::Int(n)) => match n.checked_neg() {
+            Some(n) => Ok(Value::Integer(n)),
+            None => Ok(interp.new_integer(BigInt::from(n).neg()))
+        },
+        Some(Num::Big(n)) => Ok(interp.new_integer(n.neg())),
+        Some(Num::Float(f)) => Ok(Value::Float(-f)),
+        None => Err(coerce_error(interp, recv, recv))
+    }
+}
+
+fn numeric_cmp(interp: &Interpreter, recv: Value, other: Value) -> Option<Ordering> {
+    match (num(interp, recv)?, num(interp, other)?) {
+        (Num::Int(a), Num::Int(b)) => Some(a.cmp(&b)),
+        (Num::Int(a), Num::Float(b)) => (a as f64).partial_cmp(&b),
+        (Num::Float(a), Num::Int(b)) => a.partial_cmp(&(b as f64)),
+        (Num::Float(a), Num::Float(b)) => a.partial_cmp(&b),
+        (Num::Big(a), Num::Float(b)) => big_float_cmp(&a, b),
+        (Num::Float(a), Num::Big(b)) => big_float_cmp(&b, a).map(Ordering::reverse),
+        (a, b) => Some(a.to_big()?.cmp(&b.to_big()?))
+    }
+}
+
+// Exact, where converting the big integer to a float would round.
+fn big_float_cmp(a: &BigInt, b: f64) -> Option<Ordering> {
+    if b.is_nan() {
+        return None;
+    }
+    if b.is_infinite() {
+        return Some(if b > 0.0 { Ordering::Less } else { Ordering::Greater });
+    }
+    match a.cmp(&BigInt::from_f64(b)) {
+        Ordering::Equal => 0.0.partial_cmp(&b.fract()),
+        o => Some(o)
+    }
+}
+
+fn equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    match numeric_cmp(interp, recv, args[0]) {
+        Some(o) => Ok(Value::from_bool(o == Ordering::Equal)),
+        // Other kinds of number know how to compare themselves with this one.
+        None if num(interp, args[0]).is_none() && interp.is_a(args[0], interp.core.numeric) => {
+            Ok(Value::from_bool(interp.call(args[0], "==", &[recv])?.is_truthy()))
+        },
+        None => Ok(Value::False)
+    }
+}
+
+fn cmp(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    if let Some(o) = numeric_cmp(interp, recv, args[0]) {
+        return Ok(Value::Integer(o as i64));
+    }
+    match num(interp, args[0]) {
+        Some(_) => Ok(Value::Nil),
+        None => match coerce_pair(interp, recv, args[0])? {
+            Some((a, b)) => interp.call(a, "<=>", &[b]),
+            None => Ok(Value::Nil)
+        }
+    }
+}
+
+fn ordered(interp: &mut Interpreter, recv: Value, args: &[Value], test: fn(Ordering) -> bool) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    match numeric_cmp(interp, recv, args[0]) {
+        Some(o) => Ok(Value::from_bool(test(o))),
+        // NaN compares false with everything.
+        None if num(interp, recv).is_some() && num(interp, args[0]).is_some() => Ok(Value::False),
+        None => Ok(Value::from_bool(test(compare(interp, recv, args[0])?)))
+    }
+}
+
+fn between(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 2, Some(2))?;
+    let above = compare(interp, recv, args[0])? != Ordering::Less;
+    let below = compare(interp, recv, args[1])? != Ordering::Greater;
+    Ok(Value::from_bool(above && below))
+}
+
+fn divmod(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    let q = div(interp, recv, args, None)?;
+    let q = match q {
+        Value::Float(f) => Value::Float(f.floor()),
+        q => q
+    };
+    let r = modulo(interp, recv, args, None)?;
+    Ok(interp.new_array(vec![q, r]))
+}
+
+fn abs(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    match num(interp, recv) {
+        Some(Num::Int(n)) => match n.checked_abs() {
+            Some(n) => Ok(Value::Integer(n)),
+            None => Ok(interp.new_integer(BigInt::from(n).abs()))
+        },
+        Some(Num::Big(n)) => Ok(interp.new_integer(n.abs())),
+        Some(Num::Float(f)) => Ok(Value::Float(f.abs())),
+        None => Err(coerce_error(interp, recv, recv))
+    }
+}
+
+// The integer part of a finite float, big if it needs to be.
+pub fn float_to_integer(interp: &mut Interpreter, f: f64) -> Value {
+    if f.abs() < 9.2e18 {
+        Value::Integer(f as i64)
+    } else {
+        interp.new_integer(BigInt::from_f64(f))
+    }
+}
+
+fn to_i(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(0))?;
+    match recv {
+        Value::Float(f) if !f.is_finite() => {
+            let shown = format_float(f);
+            Err(interp.error(interp.core.float_domain_error, shown))
+        },
+        Value::Float(f) => Ok(float_to_integer(interp, f)),
+        v => Ok(v)
+    }
+}
+
+fn to_s(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    let radix = match args.first() {
+        Some(base) => {
+            let base = expect_integer(interp, *base)?;
+            if !(2..=36).contains(&base) {
+                return Err(interp.argument_error(format!("invalid radix {}", base)));
+            }
+            base as u32
+        },
+        None => 10
+    };
+    let s = match num(interp, recv) {
+        Some(Num::Int(n)) => to_radix(n, radix),
+        Some(Num::Big(n)) => n.to_string_radix(radix),
+        Some(Num::Float(f)) => format_float(f),
+        None => super::object::default_to_s(interp, recv)
+    };
+    Ok(interp.new_string(s))
+}
+
+fn to_radix(n: i64, base: u32) -> String {
+    let mut digits = Vec::new();
+    let mut m = n.unsigned_abs();
+    loop {
+        digits.push(std::char::from_digit((m % base as u64) as u32, base).unwrap());
+        m /= base as u64;
+        if m == 0 { break; }
+    }
+    if n < 0 { digits.push('-'); }
+    digits.iter().rev().collect()
+}
+
+fn rounded(interp: &mut Interpreter, recv: Value, args: &[Value], op: fn(f64) -> f64) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let f = to_f64(interp, recv)?;
+    match args.first() {
+        Some(digits) => {
+            let digits = expect_integer(interp, *digits)?;
+            if digits > 0 {
+                // Past the digits a double holds, which are more for small numbers, rounding
+                // leaves it as it is.
+                let binexp = if f == 0.0 || !f.is_finite() { 0 } else { f.abs().log2().floor() as i64 + 1 };
+                let precise = FLOAT_DIGITS - if binexp > 0 { binexp / 4 } else { binexp / 3 - 1 };
+                let scale = 10f64.powi(digits.min(precise) as i32);
+                if digits >= precise || !(f * scale).is_finite() {
+                    return Ok(Value::Float(f));
+                }
+                return Ok(Value::Float(op(f * scale) / scale));
+            }
+            if !f.is_finite() {
+                return Err(interp.error(interp.core.float_domain_error, format_float(f)));
+            }
+            // Every double is below 10**309, so rounding to more places than that leaves 0.
+            let places = digits.checked_neg().unwrap_or(i64::MAX);
+            if places > MAX_FLOAT_EXPONENT {
+                return Ok(Value::Integer(0));
+            }
+            let scale = 10f64.powi(places as i32);
+            Ok(float_to_integer(interp, op(f / scale) * scale))
+        },
+        None if !f.is_finite() => Err(interp.error(interp.core.float_domain_error, format_float(f))),
+        None => Ok(float_to_integer(interp, op(f)))
+    }
+}
+
+// `ints` for machine integers, `limbs` a limb at a time when either operand is big.
+fn bitwise(interp: &mut Interpreter, recv: Value, args: &[Value], ints: fn(i64, i64) -> i64, limbs: fn(u32, u32) -> u32) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let (a, b) = match (num(interp, recv), num(interp, args[0])) {
+        (Some(Num::Int(a)), Some(Num::Int(b))) => return Ok(Value::Integer(ints(a, b))),
+        (Some(a), Some(b)) => (a.to_big(), b.to_big()),
+        _ => (None, None)
+    };
+    match (a, b) {
+        (Some(a), Some(b)) => Ok(interp.new_integer(a.bitwise(&b, limbs))),
+        _ => {
+            let (a, b) = (int(interp, recv)?, expect_integer(interp, args[0])?);
+            Ok(Value::Integer(ints(a, b)))
+        }
+    }
+}
+
+fn shift(interp: &mut Interpreter, recv: Value, args: &[Value], right: bool) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    // A bignum width shifts everything out one way and can't be represented the other.
+    if let Some(Num::Big(width)) = num(interp, args[0]) {
+        let negative = match num(interp, recv) {
+            Some(Num::Int(a)) => a < 0,
+            Some(Num::Big(a)) => a.is_negative(),
+            _ => return Err(coerce_error(interp, recv, args[0]))
+        };
+        if right != width.is_negative() {
+            return Ok(Value::Integer(if negative { -1 } else { 0 }));
+        }
+        if recv == Value::Integer(0) {
+            return Ok(recv);
+        }
+        return Err(interp.error(interp.core.range_error, String::from("shift width too big")));
+    }
+    let b = expect_integer(interp, args[0])?;
+    let b = if right { b.saturating_neg() } else { b };
+    let a = match num(interp, recv) {
+        Some(Num::Int(a)) => {
+            if b >= 0 {
+                let shifted = u32::try_from(b).ok().and_then(|b| a.checked_shl(b)).filter(|r| r >> b == a);
+                if let Some(n) = shifted {
+                    return Ok(Value::Integer(n));
+                }
+            } else {
+                return Ok(Value::Integer(if b <= -64 { if a < 0 { -1 } else { 0 } } else { a >> -b }));
+            }
+            BigInt::from(a)
+        },
+        Some(Num::Big(a)) => a,
+        _ => return Err(coerce_error(interp, recv, args[0]))
+    };
+    if b >= 0 {
+        if b as u64 + a.bit_length() as u64 > MAX_BIG_BITS as u64 {
+            return Err(interp.argument_error(String::from("shift width too big")));
+        }
+        return Ok(interp.new_integer(a.shl(b as usize)));
+    }
+    Ok(interp.new_integer(a.shr(b.unsigned_abs())))
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn chr(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let n = int(interp, recv)?;
+    match u32::try_from(n).ok().and_then(char::from_u32) {
+        Some(c) => Ok(interp.new_string(c.to_string())),
+        None => Err(interp.error(interp.core.range_error, format!("{} out of char range", n)))
+    }
+}
+
+fn times(interp: &mut Interpreter, recv: Value, _args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = require_block(interp, block)?;
+    let n = int(interp, recv)?;
+    for i in 0..n {
+        interp.call_proc_for_effect(block, &[Value::Integer(i)])?;
+    }
+    Ok(recv)
+}
+
+fn upto(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let block = require_block(interp, block)?;
+    let (from, to) = (int(interp, recv)?, expect_integer(interp, args[0])?);
+    for i in from..=to {
+        interp.call_proc_for_effect(block, &[Value::Integer(i)])?;
+    }
+    Ok(recv)
+}
+
+fn downto(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let block = require_block(interp, block)?;
+    let (from, to) = (int(interp, recv)?, expect_integer(interp, args[0])?);
+    for i in (to..=from).rev() {
+        interp.call_proc_for_effect(block, &[Value::Integer(i)])?;
+    }
+    Ok(recv)
+}
+
+// `1.step(10, 3) { }` and `1.0.step(2.0, 0.5) { }`.
+fn step(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let block = require_block(interp, block)?;
+    let by = args.get(1).copied().unwrap_or(Value::Integer(1));
+    if let (Value::Integer(from), Value::Integer(to), Value::Integer(by)) = (recv, args[0], by) {
+        if by == 0 {
+            return Err(interp.argument_error(String::from("step can't be 0")));
+        }
+        let mut i = from;
+        while (by > 0 && i <= to) || (by < 0 && i >= to) {
+            interp.call_proc_for_effect(block, &[Value::Integer(i)])?;
+            i = match i.checked_add(by) {
+                Some(i) => i,
+                None => break
+            };
+        }
+        return Ok(recv);
+    }
+    let (from, to, by) = (to_f64(interp, recv)?, to_f64(interp, args[0])?, to_f64(interp, by)?);
+    if by == 0.0 {
+        return Err(interp.argument_error(String::from("step can't be 0")));
+    }
+    // Counting steps rather than accumulating avoids drift.
+    let n = ((to - from) / by + 1e-9).floor();
+    let mut i = 0.0;
+    while i <= n {
+        interp.call_proc_for_effect(block, &[Value::Float(from + i * by)])?;
+        i += 1.0;
+    }
+    Ok(recv)
+}
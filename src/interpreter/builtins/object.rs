@@ -0,0 +1,315 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::runtime::heap::{MethodEntry, Object, ObjectKind, ProcBody};
+use crate::interpreter::runtime::symbols::Sym;
+use crate::interpreter::runtime::value::Value;
+use super::{expect_name, require_block};
+
+pub fn install(interp: &mut Interpreter) {
+    let basic = interp.core.basic_object;
+    interp.define_builtin(basic, "initialize", initialize);
+    interp.define_builtin(basic, "==", equal);
+    interp.define_builtin(basic, "equal?", equal);
+    interp.define_builtin(basic, "!", not);
+    interp.define_builtin(basic, "!=", not_equal);
+    interp.define_builtin(basic, "__send__", send);
+    interp.define_builtin(basic, "instance_eval", instance_eval);
+    interp.define_builtin(basic, "instance_exec", instance_exec);
+
+    let object = interp.core.object;
+    interp.define_builtin(object, "eql?", eql);
+    interp.define_builtin(object, "===", case_equal);
+    interp.define_builtin(object, "=~", |_, _, _, _| Ok(Value::Nil));
+    interp.define_builtin(object, "hash", hash);
+    interp.define_builtin(object, "object_id", object_id);
+    interp.define_builtin(object, "class", class);
+    interp.define_builtin(object, "singleton_class", singleton_class);
+    interp.define_builtin(object, "inspect", inspect);
+    interp.define_builtin(object, "to_s", to_s);
+    interp.define_builtin(object, "nil?", |_, _, _, _| Ok(Value::False));
+    interp.define_builtin(object, "is_a?", is_a);
+    interp.define_builtin(object, "kind_of?", is_a);
+    interp.define_builtin(object, "instance_of?", instance_of);
+    interp.define_builtin(object, "respond_to?", respond_to);
+    interp.define_builtin(object, "send", send);
+    interp.define_builtin(object, "public_send", send);
+    interp.define_builtin(object, "method", method);
+    interp.define_builtin(object, "methods", methods);
+    interp.define_builtin(object, "instance_variable_get", instance_variable_get);
+    interp.define_builtin(object, "instance_variable_set", instance_variable_set);
+    interp.define_builtin(object, "instance_variable_defined?", instance_variable_defined);
+    interp.define_builtin(object, "instance_variables", instance_variables);
+    interp.define_builtin(object, "freeze", freeze);
+    interp.define_builtin(object, "frozen?", |interp, recv, _, _| Ok(Value::from_bool(interp.is_frozen(recv))));
+    interp.define_builtin(object, "dup", dup);
+    interp.define_builtin(object, "clone", dup);
+    interp.define_builtin(object, "itself", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(object, "tap", tap);
+    interp.define_builtin(object, "then", then);
+    interp.define_builtin(object, "yield_self", then);
+    interp.define_builtin(object, "extend", extend);
+    interp.define_builtin(object, "define_singleton_method", define_singleton_method);
+
+    let nil = interp.core.nil_class;
+    interp.define_builtin(nil, "nil?", |_, _, _, _| Ok(Value::True));
+    interp.define_builtin(nil, "to_s", |interp, _, _, _| Ok(interp.new_string(String::new())));
+    interp.define_builtin(nil, "to_a", |interp, _, _, _| Ok(interp.new_array(Vec::new())));
+    interp.define_builtin(nil, "to_i", |_, _, _, _| Ok(Value::Integer(0)));
+    interp.define_builtin(nil, "to_f", |_, _, _, _| Ok(Value::Float(0.0)));
+    interp.define_builtin(nil, "inspect", |interp, _, _, _| Ok(interp.new_string(String::from("nil"))));
+    interp.define_builtin(nil, "&", |_, _, _, _| Ok(Value::False));
+    interp.define_builtin(nil, "|", |_, _, args, _| Ok(Value::from_bool(args.first().is_some_and(|v| v.is_truthy()))));
+    for class in [interp.core.true_class, interp.core.false_class] {
+        interp.define_builtin(class, "to_s", |interp, recv, _, _| Ok(interp.new_string(recv.is_truthy().to_string())));
+        interp.define_builtin(class, "inspect", |interp, recv, _, _| Ok(interp.new_string(recv.is_truthy().to_string())));
+        interp.define_builtin(class, "&", |_, recv, args, _| Ok(Value::from_bool(recv.is_truthy() && args.first().is_some_and(|v| v.is_truthy()))));
+        interp.define_builtin(class, "|", |_, recv, args, _| Ok(Value::from_bool(recv.is_truthy() || args.first().is_some_and(|v| v.is_truthy()))));
+        interp.define_builtin(class, "^", |_, recv, args, _| Ok(Value::from_bool(recv.is_truthy() != args.first().is_some_and(|v| v.is_truthy()))));
+    }
+    for class in [nil, interp.core.true_class, interp.core.false_class, interp.core.integer, interp.core.float, interp.core.rational, interp.core.complex, interp.core.symbol] {
+        let singleton = interp.singleton_class(Value::Object(class)).expect("classes have singleton classes");
+        let new = interp.sym("new");
+        interp.define_method(singleton, new, MethodEntry::Undefined);
+    }
+}
+
+// The `#<Foo>` form used by objects without their own `to_s`.
+pub fn default_to_s(interp: &Interpreter, v: Value) -> String {
+    format!("#<{}>", interp.module_name(interp.real_class(v)))
+}
+
+fn initialize(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(0))?;
+    Ok(Value::Nil)
+}
+
+fn equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    Ok(Value::from_bool(recv.is_identical(args[0])))
+}
+
+fn not(_interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    Ok(Value::from_bool(!recv.is_truthy()))
+}
+
+fn not_equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    Ok(Value::from_bool(!interp.values_equal(recv, args[0])?))
+}
+
+fn eql(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    Ok(Value::from_bool(interp.hash_key(recv) == interp.hash_key(args[0])))
+}
+
+fn case_equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    Ok(Value::from_bool(interp.values_equal(recv, args[0])?))
+}
+
+fn hash(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let mut hasher = DefaultHasher::new();
+    interp.hash_key(recv).hash(&mut hasher);
+    Ok(Value::Integer(hasher.finish() as i64 >> 2))
+}
+
+fn object_id(_interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    Ok(Value::Integer(match recv {
+        Value::Nil => 8,
+        Value::True => 20,
+        Value::False => 0,
+        Value::Integer(n) => n.wrapping_mul(2).wrapping_add(1),
+        Value::Float(f) => f.to_bits() as i64 | 2,
+        Value::Symbol(s) => (s.index() as i64) * 8 + 12,
+        Value::Object(r) => (r.index() as i64 + 1) * 8 + 16
+    }))
+}
+
+fn class(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    Ok(Value::Object(interp.real_class(recv)))
+}
+
+fn singleton_class(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    Ok(Value::Object(interp.singleton_class(recv)?))
+}
+
+fn inspect(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let ivars: Vec<_> = match recv {
+        Value::Object(r) => interp.heap.get(r).ivars.iter().map(|(k, v)| (*k, *v)).collect(),
+        _ => Vec::new()
+    };
+    let mut out = format!("#<{}", interp.module_name(interp.real_class(recv)));
+    let mut ivars = ivars;
+    ivars.sort_by_key(|(k, _)| *k);
+    for (i, (name, v)) in ivars.into_iter().enumerate() {
+        let shown = interp.inspect(v)?;
+        out.push_str(&format!("{} {}={}", if i == 0 { "" } else { "," }, interp.sym_name(name), shown));
+    }
+    out.push('>');
+    Ok(interp.new_string(out))
+}
+
+fn to_s(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let s = if recv == interp.main { String::from("main") } else { default_to_s(interp, recv) };
+    Ok(interp.new_string(s))
+}
+
+fn is_a(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    match args[0].as_object() {
+        Some(module) if interp.is_module(args[0]) => Ok(Value::from_bool(interp.is_a(recv, module))),
+        _ => Err(interp.type_error(String::from("class or module required")))
+    }
+}
+
+fn instance_of(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    Ok(Value::from_bool(args[0] == Value::Object(interp.real_class(recv))))
+}
+
+fn respond_to(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let name = expect_name(interp, args[0])?;
+    Ok(Value::from_bool(interp.responds_to(recv, name)))
+}
+
+fn send(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, None)?;
+    let name = expect_name(interp, args[0])?;
+    interp.send(recv, name, &args[1..], block)
+}
+
+fn method(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let name = expect_name(interp, args[0])?;
+    if !interp.responds_to(recv, name) {
+        let message = format!("undefined method `{}' for {}", interp.sym_name(name), super::type_name(interp, recv));
+        return Err(interp.error(interp.core.name_error, message));
+    }
+    Ok(interp.new_proc(ProcBody::Method { receiver: recv, name }, true))
+}
+
+fn methods(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let mut names = Vec::new();
+    for m in interp.ancestors(interp.dispatch_class(recv)) {
+        if let Some(data) = interp.module_data(m) {
+            for (name, entry) in data.methods.iter() {
+                if !matches!(entry, MethodEntry::Undefined) && !names.contains(&Value::Symbol(*name)) {
+                    names.push(Value::Symbol(*name));
+                }
+            }
+        }
+    }
+    Ok(interp.new_array(names))
+}
+
+fn ivar_name(interp: &mut Interpreter, v: Value) -> Result<Sym, Unwind> {
+    let name = expect_name(interp, v)?;
+    if !interp.sym_name(name).starts_with('@') {
+        let message = format!("'{}' is not allowed as an instance variable name", interp.sym_name(name));
+        return Err(interp.error(interp.core.name_error, message));
+    }
+    Ok(name)
+}
+
+fn instance_variable_get(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let name = ivar_name(interp, args[0])?;
+    Ok(interp.get_ivar(recv, name))
+}
+
+fn instance_variable_set(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 2, Some(2))?;
+    let name = ivar_name(interp, args[0])?;
+    interp.set_ivar(recv, name, args[1])?;
+    Ok(args[1])
+}
+
+fn instance_variable_defined(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let name = ivar_name(interp, args[0])?;
+    Ok(Value::from_bool(recv.as_object().is_some_and(|r| interp.heap.get(r).ivars.contains_key(&name))))
+}
+
+fn instance_variables(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let mut names: Vec<_> = match recv {
+        Value::Object(r) => interp.heap.get(r).ivars.keys().copied().collect(),
+        _ => Vec::new()
+    };
+    names.sort();
+    let names = names.into_iter().map(Value::Symbol).collect();
+    Ok(interp.new_array(names))
+}
+
+fn freeze(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    if let Value::Object(r) = recv {
+        interp.heap.get_mut(r).frozen = true;
+    }
+    Ok(recv)
+}
+
+// A shallow copy: same class, copied instance variables and contents, not frozen.
+fn dup(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let r = match recv {
+        Value::Object(r) => r,
+        v => return Ok(v)
+    };
+    let original = interp.heap.get(r);
+    let kind = match &original.kind {
+        ObjectKind::Plain => ObjectKind::Plain,
+        ObjectKind::Str(s) => ObjectKind::Str(s.clone()),
+        ObjectKind::Array(items) => ObjectKind::Array(items.clone()),
+        ObjectKind::Hash(table) => ObjectKind::Hash(table.clone()),
+        ObjectKind::Range { start, end, exclusive } => ObjectKind::Range { start: *start, end: *end, exclusive: *exclusive },
+        ObjectKind::Exception(data) => ObjectKind::Exception(data.clone()),
+        ObjectKind::Enumerator(data) => ObjectKind::Enumerator(data.clone()),
+        _ => return Ok(recv)
+    };
+    let mut copy = Object::create(original.class, kind);
+    copy.ivars = original.ivars.clone();
+    Ok(Value::Object(interp.alloc_object(copy)))
+}
+
+fn tap(interp: &mut Interpreter, recv: Value, _args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = require_block(interp, block)?;
+    interp.call_proc(block, &[recv], None)?;
+    Ok(recv)
+}
+
+fn then(interp: &mut Interpreter, recv: Value, _args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = require_block(interp, block)?;
+    interp.call_proc(block, &[recv], None)
+}
+
+fn extend(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, None)?;
+    let singleton = interp.singleton_class(recv)?;
+    super::module::include_modules(interp, singleton, args)?;
+    Ok(recv)
+}
+
+fn define_singleton_method(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let name = expect_name(interp, args[0])?;
+    let body = match args.get(1).copied().or(block) {
+        Some(body) => body,
+        None => return Err(interp.argument_error(String::from("tried to create Proc object without a block")))
+    };
+    let singleton = interp.singleton_class(recv)?;
+    interp.define_method(singleton, name, MethodEntry::Proc(body));
+    Ok(Value::Symbol(name))
+}
+
+fn instance_eval(interp: &mut Interpreter, recv: Value, _args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = require_block(interp, block)?;
+    let target = interp.singleton_class(recv).unwrap_or(interp.real_class(recv));
+    interp.call_proc_as(block, Some((recv, target)), &[recv], None, false)
+}
+
+fn instance_exec(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = require_block(interp, block)?;
+    let target = interp.singleton_class(recv).unwrap_or(interp.real_class(recv));
+    interp.call_proc_as(block, Some((recv, target)), args, None, false)
+}
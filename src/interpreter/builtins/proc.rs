@@ -0,0 +1,56 @@
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::runtime::heap::{ObjectKind, ProcBody};
+use crate::interpreter::runtime::value::Value;
+use super::require_block;
+
+pub fn install(interp: &mut Interpreter) {
+    let proc_class = interp.core.proc_class;
+    let singleton = interp.singleton_class(Value::Object(proc_class)).expect("classes have singleton classes");
+    interp.define_builtin(singleton, "new", |interp, _, _, block| require_block(interp, block));
+    interp.define_builtin(proc_class, "call", call);
+    interp.define_builtin(proc_class, "()", call);
+    interp.define_builtin(proc_class, "yield", call);
+    interp.define_builtin(proc_class, "[]", call);
+    interp.define_builtin(proc_class, "===", call);
+    interp.define_builtin(proc_class, "to_proc", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(proc_class, "lambda?", |interp, recv, _, _| Ok(Value::from_bool(is_lambda(interp, recv)?)));
+    interp.define_builtin(proc_class, "arity", arity);
+    interp.define_builtin(proc_class, "inspect", inspect);
+    interp.define_builtin(proc_class, "to_s", inspect);
+}
+
+fn is_lambda(interp: &mut Interpreter, v: Value) -> Result<bool, Unwind> {
+    match v.as_object().map(|r| &interp.heap.get(r).kind) {
+        Some(ObjectKind::Proc(data)) => Ok(data.is_lambda),
+        _ => Err(super::conversion_error(interp, v, "Proc"))
+    }
+}
+
+fn call(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.call_proc(recv, args, block)
+}
+
+// Required parameter count, or `-(required + 1)` when more arguments are accepted.
+fn arity(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    is_lambda(interp, recv)?;
+    let r = recv.as_object().unwrap();
+    let arity = match &interp.heap.get(r).kind {
+        ObjectKind::Proc(data) => match &data.body {
+            ProcBody::Block { block, .. } => {
+                let params = &block.params;
+                let required = (params.required.len() + params.post.len()) as i64;
+                if params.rest.is_some() || !params.optional.is_empty() { -required - 1 } else { required }
+            },
+            ProcBody::Compiled { chunk, .. } => chunk.params.arity(),
+            ProcBody::Symbol(_) => -2,
+            ProcBody::Method { .. } | ProcBody::Native(_) => -1
+        },
+        _ => 0
+    };
+    Ok(Value::Integer(arity))
+}
+
+fn inspect(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let text = if is_lambda(interp, recv)? { "#<Proc (lambda)>" } else { "#<Proc>" };
+    Ok(interp.new_string(text.to_string()))
+}
@@ -0,0 +1,224 @@
+use std::cmp::Ordering;
+
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::runtime::heap::ObjectKind;
+use crate::interpreter::runtime::value::Value;
+use super::{array_size, compare, enumerable, expect_integer, require_block, type_name};
+
+pub fn install(interp: &mut Interpreter) {
+    let range = interp.core.range;
+    let singleton = interp.singleton_class(Value::Object(range)).expect("classes have singleton classes");
+    interp.define_builtin(singleton, "new", new);
+    interp.define_builtin(range, "first", first);
+    interp.define_builtin(range, "begin", |interp, recv, _, _| Ok(bounds(interp, recv)?.0));
+    interp.define_builtin(range, "last", last);
+    interp.define_builtin(range, "end", |interp, recv, _, _| Ok(bounds(interp, recv)?.1));
+    interp.define_builtin(range, "exclude_end?", |interp, recv, _, _| Ok(Value::from_bool(bounds(interp, recv)?.2)));
+    interp.define_builtin(range, "each", each);
+    interp.define_builtin(range, "to_a", to_a);
+    interp.define_builtin(range, "to_ary", to_a);
+    interp.define_builtin(range, "entries", to_a);
+    interp.define_builtin(range, "size", size);
+    interp.define_builtin(range, "include?", cover);
+    interp.define_builtin(range, "member?", cover);
+    interp.define_builtin(range, "cover?", cover);
+    interp.define_builtin(range, "===", cover);
+    interp.define_builtin(range, "step", step);
+    interp.define_builtin(range, "==", equal);
+    interp.define_builtin(range, "inspect", |interp, recv, _, _| describe(interp, recv, true));
+    interp.define_builtin(range, "to_s", |interp, recv, _, _| describe(interp, recv, false));
+}
+
+pub fn bounds(interp: &mut Interpreter, v: Value) -> Result<(Value, Value, bool), Unwind> {
+    match v.as_object().map(|r| &interp.heap.get(r).kind) {
+        Some(ObjectKind::Range { start, end, exclusive }) => Ok((*start, *end, *exclusive)),
+        _ => Err(super::conversion_error(interp, v, "Range"))
+    }
+}
+
+// The integers an integer range covers, as `(first, last)` inclusive; `None` when the range
+// isn't over integers.
+pub fn int_bounds(interp: &mut Interpreter, v: Value) -> Result<Option<(i64, i64)>, Unwind> {
+    match bounds(interp, v)? {
+        (Value::Integer(a), Value::Integer(b), exclusive) => Ok(Some((a, if exclusive { b - 1 } else { b }))),
+        (Value::Integer(a), Value::Nil, _) => Ok(Some((a, i64::MAX))),
+        (Value::Integer(a), Value::Float(b), _) if b == f64::INFINITY => Ok(Some((a, i64::MAX))),
+        _ => Ok(None)
+    }
+}
+
+fn new(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 2, Some(3))?;
+    let exclusive = args.get(2).is_some_and(|v| v.is_truthy());
+    Ok(interp.new_range(args[0], args[1], exclusive))
+}
+
+fn cannot_iterate(interp: &mut Interpreter, v: Value) -> Unwind {
+    let message = format!("can't iterate from {}", type_name(interp, v));
+    interp.type_error(message)
+}
+
+fn each(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerable::enumerator(interp, recv, "each", args))
+    };
+    match int_bounds(interp, recv)? {
+        Some((from, to)) => {
+            let mut i = from;
+            while i <= to {
+                interp.call_proc_for_effect(block, &[Value::Integer(i)])?;
+                if i == i64::MAX { break; }
+                i += 1;
+            }
+            Ok(recv)
+        },
+        None => {
+            let start = bounds(interp, recv)?.0;
+            Err(cannot_iterate(interp, start))
+        }
+    }
+}
+
+fn to_a(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    match bounds(interp, recv)? {
+        (_, Value::Nil, _) => Err(interp.error(interp.core.range_error, String::from("cannot convert endless range to an array"))),
+        _ => match int_bounds(interp, recv)? {
+            Some((from, to)) => {
+                let items = (from..=to).map(Value::Integer).collect();
+                Ok(interp.new_array(items))
+            },
+            None => {
+                let start = bounds(interp, recv)?.0;
+                Err(cannot_iterate(interp, start))
+            }
+        }
+    }
+}
+
+fn size(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    match bounds(interp, recv)? {
+        (Value::Integer(_), Value::Nil, _) => Ok(Value::Float(f64::INFINITY)),
+        _ => match int_bounds(interp, recv)? {
+            Some((from, to)) => Ok(Value::Integer((to - from + 1).max(0))),
+            None => Ok(Value::Nil)
+        }
+    }
+}
+
+fn first(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let (start, _, _) = bounds(interp, recv)?;
+    match args.first() {
+        None => Ok(start),
+        Some(n) => {
+            let n = array_size(interp, *n)?;
+            match int_bounds(interp, recv)? {
+                Some((from, to)) => {
+                    let items = (from..=to).take(n).map(Value::Integer).collect();
+                    Ok(interp.new_array(items))
+                },
+                None => Err(cannot_iterate(interp, start))
+            }
+        }
+    }
+}
+
+fn last(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let (_, end, _) = bounds(interp, recv)?;
+    match args.first() {
+        None => Ok(end),
+        Some(n) => {
+            let n = array_size(interp, *n)?;
+            match int_bounds(interp, recv)? {
+                Some((from, to)) => {
+                    let mut items: Vec<Value> = (from..=to).rev().take(n).map(Value::Integer).collect();
+                    items.reverse();
+                    Ok(interp.new_array(items))
+                },
+                None => Err(cannot_iterate(interp, end))
+            }
+        }
+    }
+}
+
+fn cover(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let (start, end, exclusive) = bounds(interp, recv)?;
+    let v = args[0];
+    let above = match start {
+        Value::Nil => true,
+        start => matches!(compare_or_none(interp, start, v)?, Some(Ordering::Less | Ordering::Equal))
+    };
+    let below = match end {
+        Value::Nil => true,
+        end => match compare_or_none(interp, v, end)? {
+            Some(Ordering::Less) => true,
+            Some(Ordering::Equal) => !exclusive,
+            _ => false
+        }
+    };
+    Ok(Value::from_bool(above && below))
+}
+
+// Values that can't be compared with the bounds just aren't in the range.
+fn compare_or_none(interp: &mut Interpreter, a: Value, b: Value) -> Result<Option<Ordering>, Unwind> {
+    match compare(interp, a, b) {
+        Ok(o) => Ok(Some(o)),
+        Err(Unwind::Raise(exc)) if interp.is_a(exc, interp.core.argument_error) => Ok(None),
+        Err(u) => Err(u)
+    }
+}
+
+fn step(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let block = require_block(interp, block)?;
+    let by = expect_integer(interp, args[0])?;
+    if by == 0 {
+        return Err(interp.argument_error(String::from("step can't be 0")));
+    }
+    if by < 0 {
+        return Err(interp.argument_error(String::from("step can't be negative")));
+    }
+    match int_bounds(interp, recv)? {
+        Some((from, to)) => {
+            let mut i = from;
+            while i <= to {
+                interp.call_proc_for_effect(block, &[Value::Integer(i)])?;
+                i = match i.checked_add(by) {
+                    Some(i) => i,
+                    None => break
+                };
+            }
+            Ok(recv)
+        },
+        None => {
+            let start = bounds(interp, recv)?.0;
+            Err(cannot_iterate(interp, start))
+        }
+    }
+}
+
+fn equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let (a_start, a_end, a_excl) = bounds(interp, recv)?;
+    let (b_start, b_end, b_excl) = match args[0].as_object().map(|r| &interp.heap.get(r).kind) {
+        Some(ObjectKind::Range { start, end, exclusive }) => (*start, *end, *exclusive),
+        _ => return Ok(Value::False)
+    };
+    Ok(Value::from_bool(a_excl == b_excl && interp.values_equal(a_start, b_start)? && interp.values_equal(a_end, b_end)?))
+}
+
+fn describe(interp: &mut Interpreter, recv: Value, inspect: bool) -> EvalResult {
+    let (start, end, exclusive) = bounds(interp, recv)?;
+    let show = |interp: &mut Interpreter, v: Value| -> Result<String, Unwind> {
+        match v {
+            Value::Nil => Ok(String::new()),
+            v if inspect => interp.inspect(v),
+            v => interp.to_s(v)
+        }
+    };
+    let (start, end) = (show(interp, start)?, show(interp, end)?);
+    Ok(interp.new_string(format!("{}{}{}", start, if exclusive { "..." } else { ".." }, end)))
+}
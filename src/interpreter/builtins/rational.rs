@@ -0,0 +1,282 @@
+use std::cmp::Ordering;
+
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::runtime::bigint::BigInt;
+use crate::interpreter::runtime::rational::Rational;
+use crate::interpreter::runtime::value::Value;
+use super::numeric::{self, coerce_error, zero_division, MAX_BIG_BITS};
+use super::type_name;
+
+pub fn install(interp: &mut Interpreter) {
+    let rational = interp.core.rational;
+    interp.define_builtin(rational, "+", |interp, recv, args, _| arithmetic(interp, "+", recv, args, |a, b| Some(a.add(b))));
+    interp.define_builtin(rational, "-", |interp, recv, args, _| arithmetic(interp, "-", recv, args, |a, b| Some(a.sub(b))));
+    interp.define_builtin(rational, "*", |interp, recv, args, _| arithmetic(interp, "*", recv, args, |a, b| Some(a.mul(b))));
+    interp.define_builtin(rational, "/", |interp, recv, args, _| arithmetic(interp, "/", recv, args, Rational::div));
+    interp.define_builtin(rational, "quo", |interp, recv, args, _| arithmetic(interp, "/", recv, args, Rational::div));
+    interp.define_builtin(rational, "%", |interp, recv, args, _| arithmetic(interp, "%", recv, args, modulo));
+    interp.define_builtin(rational, "modulo", |interp, recv, args, _| arithmetic(interp, "%", recv, args, modulo));
+    interp.define_builtin(rational, "divmod", divmod);
+    interp.define_builtin(rational, "**", pow);
+    interp.define_builtin(rational, "-@", |interp, recv, _, _| {
+        let q = rational_of(interp, recv)?.neg();
+        Ok(interp.new_rational(q))
+    });
+    interp.define_builtin(rational, "abs", |interp, recv, _, _| {
+        let q = rational_of(interp, recv)?.abs();
+        Ok(interp.new_rational(q))
+    });
+    interp.define_builtin(rational, "==", equal);
+    interp.define_builtin(rational, "<=>", cmp);
+    interp.define_builtin(rational, "coerce", coerce);
+    interp.define_builtin(rational, "numerator", |interp, recv, _, _| {
+        let n = rational_of(interp, recv)?.get_numerator().clone();
+        Ok(interp.new_integer(n))
+    });
+    interp.define_builtin(rational, "denominator", |interp, recv, _, _| {
+        let n = rational_of(interp, recv)?.get_denominator().clone();
+        Ok(interp.new_integer(n))
+    });
+    interp.define_builtin(rational, "floor", |interp, recv, args, _| rounded(interp, recv, args, Rational::floor));
+    interp.define_builtin(rational, "ceil", |interp, recv, args, _| rounded(interp, recv, args, Rational::ceil));
+    interp.define_builtin(rational, "round", |interp, recv, args, _| rounded(interp, recv, args, Rational::round));
+    interp.define_builtin(rational, "truncate", |interp, recv, args, _| rounded(interp, recv, args, Rational::truncate));
+    interp.define_builtin(rational, "to_i", |interp, recv, args, _| {
+        interp.check_args(args, 0, Some(0))?;
+        rounded(interp, recv, args, Rational::truncate)
+    });
+    interp.define_builtin(rational, "to_f", |interp, recv, _, _| Ok(Value::Float(rational_of(interp, recv)?.to_f64())));
+    interp.define_builtin(rational, "to_r", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(rational, "to_s", |interp, recv, _, _| {
+        let s = rational_of(interp, recv)?.to_string();
+        Ok(interp.new_string(s))
+    });
+    interp.define_builtin(rational, "inspect", |interp, recv, _, _| {
+        let s = format!("({})", rational_of(interp, recv)?);
+        Ok(interp.new_string(s))
+    });
+
+    let numeric = interp.core.numeric;
+    interp.define_builtin(numeric, "quo", quo);
+    let integer = interp.core.integer;
+    interp.define_builtin(integer, "to_r", |interp, recv, _, _| to_r(interp, recv));
+    interp.define_builtin(integer, "numerator", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(integer, "denominator", |_, _, _, _| Ok(Value::Integer(1)));
+    let float = interp.core.float;
+    interp.define_builtin(float, "to_r", |interp, recv, _, _| to_r(interp, recv));
+
+    let kernel = interp.core.kernel;
+    interp.define_builtin(kernel, "Rational", kernel_rational);
+}
+
+// The exact value of an Integer or Rational.
+pub fn exact(interp: &Interpreter, v: Value) -> Option<Rational> {
+    match v {
+        Value::Integer(n) => Some(Rational::from_integer(BigInt::from(n))),
+        v => match interp.big_int(v) {
+            Some(n) => Some(Rational::from_integer(n.clone())),
+            None => interp.rational(v).cloned()
+        }
+    }
+}
+
+fn rational_of(interp: &mut Interpreter, v: Value) -> Result<Rational, Unwind> {
+    match interp.rational(v) {
+        Some(q) => Ok(q.clone()),
+        None => Err(super::conversion_error(interp, v, "Rational"))
+    }
+}
+
+// Exact with Integer and Rational operands, done in floats with a Float, and coerced otherwise.
+// `op` returns `None` when dividing by zero.
+fn arithmetic(interp: &mut Interpreter, name: &str, recv: Value, args: &[Value], op: fn(&Rational, &Rational) -> Option<Rational>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let a = rational_of(interp, recv)?;
+    match (exact(interp, args[0]), args[0]) {
+        (Some(b), _) => match op(&a, &b) {
+            Some(q) => Ok(interp.new_rational(q)),
+            None => Err(zero_division(interp))
+        },
+        (None, Value::Float(_)) => interp.call(Value::Float(a.to_f64()), name, args),
+        (None, other) => numeric::coerced(interp, name, recv, other)
+    }
+}
+
+// The remainder takes the divisor's sign, as with integers.
+fn modulo(a: &Rational, b: &Rational) -> Option<Rational> {
+    let q = Rational::from_integer(a.div(b)?.floor());
+    Some(a.sub(&b.mul(&q)))
+}
+
+fn divmod(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let a = rational_of(interp, recv)?;
+    match exact(interp, args[0]) {
+        Some(b) => {
+            let (q, r) = match (a.div(&b), modulo(&a, &b)) {
+                (Some(q), Some(r)) => (q.floor(), r),
+                _ => return Err(zero_division(interp))
+            };
+            let q = interp.new_integer(q);
+            let r = interp.new_rational(r);
+            Ok(interp.new_array(vec![q, r]))
+        },
+        None if matches!(args[0], Value::Float(_)) => interp.call(Value::Float(a.to_f64()), "divmod", args),
+        None => numeric::coerced(interp, "divmod", recv, args[0])
+    }
+}
+
+// Integer powers stay exact; fractional ones can't, so they give a Float.
+fn pow(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let a = rational_of(interp, recv)?;
+    let exponent = match exact(interp, args[0]) {
+        Some(b) if b.is_integer() => b.get_numerator().to_i64(),
+        Some(b) => return Ok(Value::Float(a.to_f64().powf(b.to_f64()))),
+        None if matches!(args[0], Value::Float(_)) => return interp.call(Value::Float(a.to_f64()), "**", args),
+        None => return numeric::coerced(interp, "**", recv, args[0])
+    };
+    let bits = a.get_numerator().bit_length().max(a.get_denominator().bit_length()) as u64;
+    let exponent = match exponent {
+        Some(n) if bits <= 1 || n.unsigned_abs() <= MAX_BIG_BITS as u64 / bits => n,
+        _ => return Err(interp.argument_error(String::from("exponent is too large")))
+    };
+    match a.pow(exponent) {
+        Some(q) => Ok(interp.new_rational(q)),
+        None => Err(zero_division(interp))
+    }
+}
+
+fn equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let a = rational_of(interp, recv)?;
+    match (exact(interp, args[0]), args[0]) {
+        (Some(b), _) => Ok(Value::from_bool(a == b)),
+        (None, Value::Float(f)) => Ok(Value::from_bool(a.to_f64() == f)),
+        (None, other) if interp.is_a(other, interp.core.numeric) => Ok(Value::from_bool(interp.call(other, "==", &[recv])?.is_truthy())),
+        _ => Ok(Value::False)
+    }
+}
+
+fn cmp(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let a = rational_of(interp, recv)?;
+    let ordering = match (exact(interp, args[0]), args[0]) {
+        (Some(b), _) => Some(a.cmp(&b)),
+        (None, Value::Float(f)) => a.to_f64().partial_cmp(&f),
+        (None, other) => return match numeric::coerce_pair(interp, recv, other)? {
+            Some((x, y)) => interp.call(x, "<=>", &[y]),
+            None => Ok(Value::Nil)
+        }
+    };
+    Ok(ordering.map_or(Value::Nil, |o: Ordering| Value::Integer(o as i64)))
+}
+
+// `[other, self]` in a type both can work in: Rational for integers, Float for floats.
+fn coerce(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let a = rational_of(interp, recv)?;
+    let pair = match (exact(interp, args[0]), args[0]) {
+        (Some(b), _) => vec![interp.new_rational(b), recv],
+        (None, Value::Float(f)) => vec![Value::Float(f), Value::Float(a.to_f64())],
+        (None, other) => return Err(coerce_error(interp, recv, other))
+    };
+    Ok(interp.new_array(pair))
+}
+
+// `floor`, `ceil`, `round` and `truncate` for Integers and Rationals, to an optional number of
+// decimal digits. Positive digits keep a Rational a Rational (and leave an Integer alone);
+// otherwise the result is an Integer, a multiple of `10 ** -digits`.
+pub fn rounded(interp: &mut Interpreter, recv: Value, args: &[Value], op: fn(&Rational) -> BigInt) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    let q = match exact(interp, recv) {
+        Some(q) => q,
+        None => return Err(super::conversion_error(interp, recv, "Rational"))
+    };
+    let digits = match args.first() {
+        Some(digits) => super::expect_integer(interp, *digits)?,
+        None => 0
+    };
+    if digits == 0 {
+        return Ok(interp.new_integer(op(&q)));
+    }
+    if digits > 0 && interp.rational(recv).is_none() {
+        return Ok(recv);
+    }
+    // A power of ten takes more than three bits a digit.
+    if digits.unsigned_abs() > MAX_BIG_BITS as u64 / 4 {
+        return Err(interp.argument_error(String::from("too many digits")));
+    }
+    let scale = BigInt::from(10).pow(digits.unsigned_abs());
+    let scaled = Rational::from_integer(scale.clone());
+    if digits > 0 {
+        let n = op(&q.mul(&scaled));
+        let q = Rational::create(n, scale).expect("a power of ten isn't zero");
+        return Ok(interp.new_rational(q));
+    }
+    let n = op(&q.div(&scaled).expect("a power of ten isn't zero"));
+    Ok(interp.new_integer(n.mul(&scale)))
+}
+
+// Division that stays exact for integers: `1.quo(3)` is `(1/3)`.
+fn quo(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    match exact(interp, recv) {
+        Some(a) => {
+            let a = interp.new_rational(a);
+            interp.call(a, "/", args)
+        },
+        None => interp.call(recv, "/", args)
+    }
+}
+
+fn to_r(interp: &mut Interpreter, recv: Value) -> EvalResult {
+    match (exact(interp, recv), recv) {
+        (Some(q), _) => Ok(interp.new_rational(q)),
+        (None, Value::Float(f)) => match Rational::from_f64(f) {
+            Some(q) => Ok(interp.new_rational(q)),
+            None => Err(interp.error(interp.core.float_domain_error, numeric::format_float(f)))
+        },
+        (None, v) => Err(super::conversion_error(interp, v, "Rational"))
+    }
+}
+
+// `Rational(1, 3)`, `Rational(0.5)` and `Rational("2/3")`.
+fn kernel_rational(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let mut parts = Vec::new();
+    for v in args.iter() {
+        let q = match (exact(interp, *v), *v) {
+            (Some(q), _) => Some(q),
+            (None, Value::Float(f)) => Rational::from_f64(f),
+            (None, v) => match interp.string_value(v) {
+                Some(s) => match parse(s.trim()) {
+                    Some(q) => Some(q),
+                    None => return Err(interp.argument_error(format!("invalid value for convert(): {:?}", s)))
+                },
+                None => {
+                    let message = format!("can't convert {} into Rational", type_name(interp, v));
+                    return Err(interp.type_error(message));
+                }
+            }
+        };
+        match q {
+            Some(q) => parts.push(q),
+            None => return Err(interp.error(interp.core.float_domain_error, String::from("non-finite value")))
+        }
+    }
+    let denominator = parts.get(1).cloned().unwrap_or_else(|| Rational::from_integer(BigInt::from(1)));
+    match parts[0].div(&denominator) {
+        Some(q) => Ok(interp.new_rational(q)),
+        None => Err(zero_division(interp))
+    }
+}
+
+// `3`, `-0.75` or `2/3`.
+fn parse(s: &str) -> Option<Rational> {
+    let s = s.replace('_', "");
+    match s.split_once('/') {
+        Some((n, d)) => Rational::parse(n.trim())?.div(&Rational::from_integer(BigInt::parse(d.trim())?)),
+        None => Rational::parse(&s)
+    }
+}
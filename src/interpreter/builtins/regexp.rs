@@ -0,0 +1,434 @@
+use std::rc::Rc;
+
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::runtime::heap::{MatchData, MethodEntry, ObjectKind};
+use crate::interpreter::runtime::regex::{self, Captures, Regex};
+use crate::interpreter::runtime::value::Value;
+use super::{expect_integer, expect_string, type_name};
+
+pub fn install(interp: &mut Interpreter) {
+    let regexp = interp.core.regexp;
+    let singleton = interp.singleton_class(Value::Object(regexp)).expect("classes have singleton classes");
+    interp.define_builtin(singleton, "new", new);
+    interp.define_builtin(singleton, "compile", new);
+    interp.define_builtin(singleton, "escape", escape);
+    interp.define_builtin(singleton, "quote", escape);
+    interp.define_builtin(singleton, "union", union);
+    interp.define_builtin(singleton, "last_match", |interp, _, args, _| {
+        interp.check_args(args, 0, Some(1))?;
+        let last = interp.global("$~");
+        match args.first() {
+            Some(n) if !last.is_nil() => interp.call(last, "[]", &[*n]),
+            _ => Ok(last)
+        }
+    });
+    for (name, bits) in [("IGNORECASE", regex::IGNORECASE), ("EXTENDED", regex::EXTENDED), ("MULTILINE", regex::MULTILINE)] {
+        let constant = interp.sym(name);
+        interp.set_constant(regexp, constant, Value::Integer(bits as i64));
+    }
+    interp.define_builtin(regexp, "source", |interp, recv, _, _| {
+        let source = regex_of(interp, recv)?.get_source().to_string();
+        Ok(interp.new_string(source))
+    });
+    interp.define_builtin(regexp, "options", |interp, recv, _, _| Ok(Value::Integer(regex_of(interp, recv)?.get_options() as i64)));
+    interp.define_builtin(regexp, "casefold?", |interp, recv, _, _| Ok(Value::from_bool(regex_of(interp, recv)?.get_options() & regex::IGNORECASE != 0)));
+    interp.define_builtin(regexp, "names", |interp, recv, _, _| {
+        let names: Vec<String> = regex_of(interp, recv)?.get_names().iter().map(|(name, _)| name.clone()).collect();
+        let names = names.into_iter().map(|name| interp.new_string(name)).collect();
+        Ok(interp.new_array(names))
+    });
+    interp.define_builtin(regexp, "inspect", inspect);
+    interp.define_builtin(regexp, "to_s", to_s);
+    interp.define_builtin(regexp, "==", equal);
+    interp.define_builtin(regexp, "eql?", equal);
+    interp.define_builtin(regexp, "=~", match_index);
+    interp.define_builtin(regexp, "match", match_data);
+    interp.define_builtin(regexp, "match?", is_match);
+    interp.define_builtin(regexp, "===", case_equal);
+
+    let match_data_class = interp.core.match_data;
+    let singleton = interp.singleton_class(Value::Object(match_data_class)).expect("classes have singleton classes");
+    let new = interp.sym("new");
+    interp.define_method(singleton, new, MethodEntry::Undefined);
+    interp.define_builtin(match_data_class, "[]", group);
+    interp.define_builtin(match_data_class, "captures", |interp, recv, _, _| {
+        let groups = groups(interp, recv)?;
+        Ok(interp.new_array(groups[1..].to_vec()))
+    });
+    interp.define_builtin(match_data_class, "to_a", |interp, recv, _, _| {
+        let groups = groups(interp, recv)?;
+        Ok(interp.new_array(groups))
+    });
+    interp.define_builtin(match_data_class, "named_captures", named_captures);
+    interp.define_builtin(match_data_class, "names", |interp, recv, _, _| {
+        let data = fields(interp, recv)?;
+        let names = data.regex.get_names().iter().map(|(name, _)| interp.new_string(name.clone())).collect();
+        Ok(interp.new_array(names))
+    });
+    interp.define_builtin(match_data_class, "pre_match", |interp, recv, _, _| {
+        let data = fields(interp, recv)?;
+        let (start, _) = data.captures[0].unwrap_or((0, 0));
+        Ok(interp.new_string(data.text[..start].iter().collect()))
+    });
+    interp.define_builtin(match_data_class, "post_match", |interp, recv, _, _| {
+        let data = fields(interp, recv)?;
+        let (_, end) = data.captures[0].unwrap_or((0, 0));
+        Ok(interp.new_string(data.text[end..].iter().collect()))
+    });
+    interp.define_builtin(match_data_class, "to_s", |interp, recv, _, _| {
+        let data = fields(interp, recv)?;
+        Ok(substring(interp, &data.text, data.captures[0]))
+    });
+    interp.define_builtin(match_data_class, "begin", |interp, recv, args, _| offset(interp, recv, args, |(start, _)| start));
+    interp.define_builtin(match_data_class, "end", |interp, recv, args, _| offset(interp, recv, args, |(_, end)| end));
+    interp.define_builtin(match_data_class, "size", |interp, recv, _, _| Ok(Value::Integer(fields(interp, recv)?.captures.len() as i64)));
+    interp.define_builtin(match_data_class, "length", |interp, recv, _, _| Ok(Value::Integer(fields(interp, recv)?.captures.len() as i64)));
+    interp.define_builtin(match_data_class, "string", |interp, recv, _, _| {
+        let data = fields(interp, recv)?;
+        Ok(interp.frozen_string(data.text.iter().collect()))
+    });
+    interp.define_builtin(match_data_class, "regexp", |interp, recv, _, _| {
+        let data = fields(interp, recv)?;
+        Ok(interp.new_regexp((*data.regex).clone()))
+    });
+    interp.define_builtin(match_data_class, "values_at", |interp, recv, args, _| {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args.iter() {
+            values.push(group(interp, recv, &[*arg], None)?);
+        }
+        Ok(interp.new_array(values))
+    });
+    interp.define_builtin(match_data_class, "inspect", inspect_match);
+}
+
+// Option bits from literal flags such as `im`.
+pub fn parse_options(flags: &str) -> u32 {
+    flags.chars().fold(0, |options, c| options | match c {
+        'i' => regex::IGNORECASE,
+        'x' => regex::EXTENDED,
+        'm' => regex::MULTILINE,
+        _ => 0
+    })
+}
+
+pub fn regex_of(interp: &mut Interpreter, v: Value) -> Result<Rc<Regex>, Unwind> {
+    match interp.regex(v) {
+        Some(regex) => Ok(regex.clone()),
+        None => Err(wrong_type(interp, v))
+    }
+}
+
+fn wrong_type(interp: &mut Interpreter, v: Value) -> Unwind {
+    let message = format!("wrong argument type {} (expected Regexp)", type_name(interp, v));
+    interp.type_error(message)
+}
+
+// What `sub`, `split` and friends search for: a Regexp, or a String matched literally.
+pub fn pattern(interp: &mut Interpreter, v: Value) -> Result<Rc<Regex>, Unwind> {
+    if let Some(regex) = interp.regex(v) {
+        return Ok(regex.clone());
+    }
+    match interp.string_value(v) {
+        Some(s) => Ok(Rc::new(Regex::create(&Regex::escape(&s), 0).expect("escaped patterns are valid"))),
+        None => Err(wrong_type(interp, v))
+    }
+}
+
+// Records a search's outcome in `$~` and `$1` to `$9`, returning the MatchData or nil.
+pub fn last_match(interp: &mut Interpreter, regex: &Rc<Regex>, text: &Rc<[char]>, captures: Option<Captures>) -> Value {
+    let data = match captures {
+        Some(captures) => {
+            let groups: Vec<Option<(usize, usize)>> = captures.to_vec();
+            let data = MatchData { regex: regex.clone(), text: text.clone(), captures };
+            let md = Value::Object(interp.alloc(interp.core.match_data, ObjectKind::MatchData(data)));
+            interp.set_global("$~", md);
+            for n in 1..=9 {
+                let v = match groups.get(n).copied().flatten() {
+                    Some(span) => substring(interp, text, Some(span)),
+                    None => Value::Nil
+                };
+                interp.set_global(&format!("${}", n), v);
+            }
+            return md;
+        },
+        None => Value::Nil
+    };
+    interp.set_global("$~", data);
+    for n in 1..=9 {
+        interp.set_global(&format!("${}", n), Value::Nil);
+    }
+    data
+}
+
+pub fn substring(interp: &mut Interpreter, text: &[char], span: Option<(usize, usize)>) -> Value {
+    match span {
+        Some((start, end)) => interp.new_string(text[start..end].iter().collect()),
+        None => Value::Nil
+    }
+}
+
+fn fields(interp: &mut Interpreter, v: Value) -> Result<MatchData, Unwind> {
+    match v.as_object().map(|r| &interp.heap.get(r).kind) {
+        Some(ObjectKind::MatchData(data)) => Ok(MatchData { regex: data.regex.clone(), text: data.text.clone(), captures: data.captures.clone() }),
+        _ => Err(super::conversion_error(interp, v, "MatchData"))
+    }
+}
+
+fn groups(interp: &mut Interpreter, v: Value) -> Result<Vec<Value>, Unwind> {
+    let data = fields(interp, v)?;
+    Ok(data.captures.iter().map(|span| substring(interp, &data.text, *span)).collect())
+}
+
+// The group an index or name refers to; `None` for an index past the last group.
+fn group_index(interp: &mut Interpreter, data: &MatchData, v: Value) -> Result<Option<usize>, Unwind> {
+    let name = match v {
+        Value::Symbol(sym) => Some(interp.sym_name(sym).to_string()),
+        v => interp.string_value(v)
+    };
+    if let Some(name) = name {
+        return match data.regex.group_index(&name) {
+            Some(i) => Ok(Some(i)),
+            None => Err(interp.error(interp.core.index_error, format!("undefined group name reference: {}", name)))
+        };
+    }
+    let n = expect_integer(interp, v)?;
+    let len = data.captures.len() as i64;
+    let n = if n < 0 { n + len } else { n };
+    Ok(if (0..len).contains(&n) { Some(n as usize) } else { None })
+}
+
+fn new(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    if let Some(regex) = interp.regex(args[0]) {
+        let regex = (**regex).clone();
+        return Ok(interp.new_regexp(regex));
+    }
+    let source = expect_string(interp, args[0])?;
+    let options = match args.get(1).copied() {
+        None | Some(Value::Nil) | Some(Value::False) => 0,
+        Some(Value::Integer(n)) => n as u32 & (regex::IGNORECASE | regex::EXTENDED | regex::MULTILINE),
+        Some(v) => match interp.string_value(v) {
+            Some(flags) => {
+                if let Some(c) = flags.chars().find(|c| !"imx".contains(*c)) {
+                    return Err(interp.argument_error(format!("unknown regexp option: {}", c)));
+                }
+                parse_options(&flags)
+            },
+            None => regex::IGNORECASE
+        }
+    };
+    match Regex::create(&source, options) {
+        Ok(regex) => Ok(interp.new_regexp(regex)),
+        Err(message) => Err(interp.error(interp.core.regexp_error, format!("{}: /{}/", message, source)))
+    }
+}
+
+fn escape(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let text = match args[0] {
+        Value::Symbol(sym) => interp.sym_name(sym).to_string(),
+        v => expect_string(interp, v)?
+    };
+    Ok(interp.new_string(Regex::escape(&text)))
+}
+
+// A Regexp matching any of the patterns; strings are matched literally.
+fn union(interp: &mut Interpreter, _recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    let patterns = match args {
+        [single] => interp.array_items(*single).cloned().unwrap_or_else(|| vec![*single]),
+        _ => args.to_vec()
+    };
+    let mut parts = Vec::with_capacity(patterns.len());
+    for p in patterns {
+        parts.push(match interp.regex(p) {
+            Some(_) => {
+                let shown = to_s(interp, p, &[], None)?;
+                expect_string(interp, shown)?
+            },
+            None => Regex::escape(&expect_string(interp, p)?)
+        });
+    }
+    let source = if parts.is_empty() { String::from("(?!)") } else { parts.join("|") };
+    match Regex::create(&source, 0) {
+        Ok(regex) => Ok(interp.new_regexp(regex)),
+        Err(message) => Err(interp.error(interp.core.regexp_error, message))
+    }
+}
+
+fn option_letters(options: u32) -> (String, String) {
+    let mut on = String::new();
+    let mut off = String::new();
+    for (bit, letter) in [(regex::MULTILINE, 'm'), (regex::IGNORECASE, 'i'), (regex::EXTENDED, 'x')] {
+        if options & bit != 0 { on.push(letter) } else { off.push(letter) }
+    }
+    (on, off)
+}
+
+// `/source/flags`, with bare slashes in the source escaped.
+fn inspect(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let regex = regex_of(interp, recv)?;
+    let mut shown = String::from("/");
+    let mut escaped = false;
+    for c in regex.get_source().chars() {
+        if c == '/' && !escaped {
+            shown.push('\\');
+        }
+        escaped = c == '\\' && !escaped;
+        shown.push(c);
+    }
+    shown.push('/');
+    shown.push_str(&option_letters(regex.get_options()).0);
+    Ok(interp.new_string(shown))
+}
+
+// `(?i-mx:source)`, which embeds in another pattern with the same meaning.
+fn to_s(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let regex = regex_of(interp, recv)?;
+    let (on, off) = option_letters(regex.get_options());
+    let off = if off.is_empty() { off } else { format!("-{}", off) };
+    Ok(interp.new_string(format!("(?{}{}:{})", on, off, regex.get_source())))
+}
+
+fn equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    Ok(Value::from_bool(match (interp.regex(recv), interp.regex(args[0])) {
+        (Some(a), Some(b)) => a.get_source() == b.get_source() && a.get_options() == b.get_options(),
+        _ => false
+    }))
+}
+
+// Searches `args[0]` from the optional position in `args[1]`, setting `$~`.
+fn search(interp: &mut Interpreter, recv: Value, args: &[Value]) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let regex = regex_of(interp, recv)?;
+    let text = match args[0] {
+        Value::Nil => return Ok(last_match(interp, &regex, &Rc::from(Vec::new()), None)),
+        Value::Symbol(sym) => interp.sym_name(sym).to_string(),
+        v => expect_string(interp, v)?
+    };
+    let text: Rc<[char]> = text.chars().collect();
+    let start = match args.get(1) {
+        Some(pos) => {
+            let pos = expect_integer(interp, *pos)?;
+            let pos = if pos < 0 { pos + text.len() as i64 } else { pos };
+            if pos < 0 || pos > text.len() as i64 {
+                return Ok(last_match(interp, &regex, &text, None));
+            }
+            pos as usize
+        },
+        None => 0
+    };
+    let captures = regex.search(&text, start);
+    Ok(last_match(interp, &regex, &text, captures))
+}
+
+fn match_start(interp: &mut Interpreter, md: Value) -> Value {
+    match md.as_object().map(|r| &interp.heap.get(r).kind) {
+        Some(ObjectKind::MatchData(data)) => data.captures[0].map_or(Value::Nil, |(start, _)| Value::Integer(start as i64)),
+        _ => Value::Nil
+    }
+}
+
+fn match_index(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    let md = search(interp, recv, &args[..args.len().min(1)])?;
+    Ok(match_start(interp, md))
+}
+
+fn match_data(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let md = search(interp, recv, args)?;
+    match block {
+        Some(block) if !md.is_nil() => interp.call_proc(block, &[md], None),
+        _ => Ok(md)
+    }
+}
+
+// Like `match`, but leaves `$~` alone.
+fn is_match(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    if args[0].is_nil() {
+        return Ok(Value::False);
+    }
+    let regex = regex_of(interp, recv)?;
+    let text = match args[0] {
+        Value::Symbol(sym) => interp.sym_name(sym).to_string(),
+        v => expect_string(interp, v)?
+    };
+    let text: Vec<char> = text.chars().collect();
+    let start = match args.get(1) {
+        Some(pos) => {
+            let pos = expect_integer(interp, *pos)?;
+            let pos = if pos < 0 { pos + text.len() as i64 } else { pos };
+            if pos < 0 || pos > text.len() as i64 { return Ok(Value::False); }
+            pos as usize
+        },
+        None => 0
+    };
+    Ok(Value::from_bool(regex.search(&text, start).is_some()))
+}
+
+// `when /pattern/` tests strings and symbols, and anything else simply doesn't match.
+fn case_equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    if !matches!(args[0], Value::Symbol(_)) && interp.str_ref(args[0]).is_none() {
+        return Ok(Value::False);
+    }
+    Ok(Value::from_bool(!search(interp, recv, args)?.is_nil()))
+}
+
+// `md[n]`, `md[name]`, and `md[start, length]` or `md[range]` over the groups.
+fn group(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let data = fields(interp, recv)?;
+    let simple = args.len() == 1 && !matches!(args[0].as_object().map(|r| &interp.heap.get(r).kind), Some(ObjectKind::Range { .. }));
+    if !simple {
+        let groups = groups(interp, recv)?;
+        let groups = interp.new_array(groups);
+        return interp.call(groups, "[]", args);
+    }
+    match group_index(interp, &data, args[0])? {
+        Some(i) => Ok(substring(interp, &data.text, data.captures[i])),
+        None => Ok(Value::Nil)
+    }
+}
+
+fn named_captures(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let data = fields(interp, recv)?;
+    let hash = interp.new_hash(Default::default());
+    for (name, index) in data.regex.get_names().iter() {
+        let key = interp.new_string(name.clone());
+        let value = substring(interp, &data.text, data.captures[*index]);
+        interp.call(hash, "[]=", &[key, value])?;
+    }
+    Ok(hash)
+}
+
+fn offset(interp: &mut Interpreter, recv: Value, args: &[Value], part: fn((usize, usize)) -> usize) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let data = fields(interp, recv)?;
+    match group_index(interp, &data, args[0])? {
+        Some(i) => Ok(data.captures[i].map_or(Value::Nil, |span| Value::Integer(part(span) as i64))),
+        None => {
+            let shown = interp.inspect(args[0])?;
+            Err(interp.error(interp.core.index_error, format!("index {} out of matches", shown)))
+        }
+    }
+}
+
+// `#<MatchData "2024-06" year:"2024" 2:"06">`.
+fn inspect_match(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let data = fields(interp, recv)?;
+    let shown = |span: Option<(usize, usize)>| match span {
+        Some((start, end)) => super::string::inspect_str(&data.text[start..end].iter().collect::<String>()),
+        None => String::from("nil")
+    };
+    let mut out = format!("#<MatchData {}", shown(data.captures[0]));
+    for (i, span) in data.captures.iter().enumerate().skip(1) {
+        let label = match data.regex.get_names().iter().find(|(_, index)| *index == i) {
+            Some((name, _)) => name.clone(),
+            None => i.to_string()
+        };
+        out.push_str(&format!(" {}:{}", label, shown(*span)));
+    }
+    out.push('>');
+    Ok(interp.new_string(out))
+}
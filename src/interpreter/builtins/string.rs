@@ -0,0 +1,755 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::runtime::heap::ObjectKind;
+use crate::interpreter::runtime::regex::{Captures, Regex};
+use crate::interpreter::runtime::value::Value;
+use super::{array, compare, conversion_error, enumerable, expect_integer, expect_string, format, numeric, regexp};
+
+pub fn install(interp: &mut Interpreter) {
+    let string = interp.core.string;
+    interp.define_builtin(string, "initialize", initialize);
+    interp.define_builtin(string, "+", add);
+    interp.define_builtin(string, "*", repeat);
+    interp.define_builtin(string, "==", equal);
+    interp.define_builtin(string, "===", equal);
+    interp.define_builtin(string, "eql?", equal);
+    interp.define_builtin(string, "<=>", cmp);
+    interp.define_builtin(string, "<", |interp, recv, args, _| ordered(interp, recv, args, |o| o == Ordering::Less));
+    interp.define_builtin(string, "<=", |interp, recv, args, _| ordered(interp, recv, args, |o| o != Ordering::Greater));
+    interp.define_builtin(string, ">", |interp, recv, args, _| ordered(interp, recv, args, |o| o == Ordering::Greater));
+    interp.define_builtin(string, ">=", |interp, recv, args, _| ordered(interp, recv, args, |o| o != Ordering::Less));
+    interp.define_builtin(string, "length", length);
+    interp.define_builtin(string, "size", length);
+    interp.define_builtin(string, "empty?", |interp, recv, _, _| Ok(Value::from_bool(str_of(interp, recv)?.is_empty())));
+    interp.define_builtin(string, "to_s", to_s);
+    interp.define_builtin(string, "to_str", to_s);
+    interp.define_builtin(string, "inspect", |interp, recv, _, _| {
+        let shown = inspect_str(&str_of(interp, recv)?);
+        Ok(interp.new_string(shown))
+    });
+    interp.define_builtin(string, "to_sym", to_sym);
+    interp.define_builtin(string, "intern", to_sym);
+    interp.define_builtin(string, "to_i", to_i);
+    interp.define_builtin(string, "to_f", to_f);
+    interp.define_builtin(string, "<<", append);
+    interp.define_builtin(string, "concat", append);
+    interp.define_builtin(string, "[]", index);
+    interp.define_builtin(string, "slice", index);
+    interp.define_builtin(string, "%", |interp, recv, args, _| {
+        interp.check_args(args, 1, Some(1))?;
+        let template = str_of(interp, recv)?;
+        let values = interp.array_items(args[0]).cloned().unwrap_or_else(|| vec![args[0]]);
+        let formatted = format::format(interp, &template, &values)?;
+        Ok(interp.new_string(formatted))
+    });
+    interp.define_builtin(string, "+@", |interp, recv, _, _| {
+        if !interp.is_frozen(recv) {
+            return Ok(recv);
+        }
+        let s = str_of(interp, recv)?;
+        Ok(interp.new_string(s))
+    });
+    interp.define_builtin(string, "-@", dedup);
+    interp.define_builtin(string, "dedup", dedup);
+    interp.define_builtin(string, "bytesize", |interp, recv, _, _| Ok(Value::Integer(str_of(interp, recv)?.len() as i64)));
+    interp.define_builtin(string, "bytes", |interp, recv, _, _| {
+        let bytes = str_of(interp, recv)?.bytes().map(|b| Value::Integer(b as i64)).collect();
+        Ok(interp.new_array(bytes))
+    });
+    interp.define_builtin(string, "ascii_only?", |interp, recv, _, _| Ok(Value::from_bool(str_of(interp, recv)?.is_ascii())));
+    interp.define_builtin(string, "valid_encoding?", |_, _, _, _| Ok(Value::True));
+    interp.define_builtin(string, "chars", |interp, recv, _, _| {
+        let chars = str_of(interp, recv)?.chars().map(|c| interp.new_string(c.to_string())).collect();
+        Ok(interp.new_array(chars))
+    });
+    interp.define_builtin(string, "each_char", each_char);
+    interp.define_builtin(string, "lines", |interp, recv, _, _| {
+        let lines = lines_of(&str_of(interp, recv)?).into_iter().map(|l| interp.new_string(l)).collect();
+        Ok(interp.new_array(lines))
+    });
+    interp.define_builtin(string, "each_line", each_line);
+    interp.define_builtin(string, "ord", |interp, recv, _, _| match str_of(interp, recv)?.chars().next() {
+        Some(c) => Ok(Value::Integer(c as i64)),
+        None => Err(interp.argument_error(String::from("empty string")))
+    });
+    interp.define_builtin(string, "reverse", |interp, recv, _, _| {
+        let reversed = str_of(interp, recv)?.chars().rev().collect();
+        Ok(interp.new_string(reversed))
+    });
+    interp.define_builtin(string, "upcase", |interp, recv, _, _| converted(interp, recv, upcase));
+    interp.define_builtin(string, "downcase", |interp, recv, _, _| converted(interp, recv, downcase));
+    interp.define_builtin(string, "capitalize", |interp, recv, _, _| converted(interp, recv, capitalize));
+    interp.define_builtin(string, "swapcase", |interp, recv, _, _| converted(interp, recv, swapcase));
+    interp.define_builtin(string, "strip", |interp, recv, _, _| converted(interp, recv, strip));
+    interp.define_builtin(string, "lstrip", |interp, recv, _, _| converted(interp, recv, lstrip));
+    interp.define_builtin(string, "rstrip", |interp, recv, _, _| converted(interp, recv, rstrip));
+    interp.define_builtin(string, "chop", |interp, recv, _, _| converted(interp, recv, chop));
+    interp.define_builtin(string, "upcase!", |interp, recv, _, _| converted_in_place(interp, recv, upcase));
+    interp.define_builtin(string, "downcase!", |interp, recv, _, _| converted_in_place(interp, recv, downcase));
+    interp.define_builtin(string, "capitalize!", |interp, recv, _, _| converted_in_place(interp, recv, capitalize));
+    interp.define_builtin(string, "swapcase!", |interp, recv, _, _| converted_in_place(interp, recv, swapcase));
+    interp.define_builtin(string, "strip!", |interp, recv, _, _| converted_in_place(interp, recv, strip));
+    interp.define_builtin(string, "lstrip!", |interp, recv, _, _| converted_in_place(interp, recv, lstrip));
+    interp.define_builtin(string, "rstrip!", |interp, recv, _, _| converted_in_place(interp, recv, rstrip));
+    interp.define_builtin(string, "chomp", |interp, recv, args, _| {
+        let chomped = chomp(interp, recv, args)?;
+        Ok(interp.new_string(chomped))
+    });
+    interp.define_builtin(string, "chomp!", |interp, recv, args, _| {
+        let chomped = chomp(interp, recv, args)?;
+        in_place(interp, recv, chomped)
+    });
+    interp.define_builtin(string, "center", |interp, recv, args, _| justify(interp, recv, args, 0.5));
+    interp.define_builtin(string, "ljust", |interp, recv, args, _| justify(interp, recv, args, 0.0));
+    interp.define_builtin(string, "rjust", |interp, recv, args, _| justify(interp, recv, args, 1.0));
+    interp.define_builtin(string, "include?", |interp, recv, args, _| {
+        interp.check_args(args, 1, Some(1))?;
+        let other = str_of(interp, args[0])?;
+        Ok(Value::from_bool(str_of(interp, recv)?.contains(&other)))
+    });
+    interp.define_builtin(string, "start_with?", start_with);
+    interp.define_builtin(string, "end_with?", |interp, recv, args, _| {
+        let s = str_of(interp, recv)?;
+        for suffix in args.iter() {
+            if s.ends_with(&str_of(interp, *suffix)?) {
+                return Ok(Value::True);
+            }
+        }
+        Ok(Value::False)
+    });
+    interp.define_builtin(string, "index", find_index);
+    interp.define_builtin(string, "rindex", find_rindex);
+    interp.define_builtin(string, "=~", match_index);
+    interp.define_builtin(string, "match", |interp, recv, args, block| {
+        interp.check_args(args, 1, Some(2))?;
+        let regexp = to_regexp(interp, args[0])?;
+        let mut rest = vec![recv];
+        rest.extend_from_slice(&args[1..]);
+        let name = interp.sym("match");
+        interp.send(regexp, name, &rest, block)
+    });
+    interp.define_builtin(string, "match?", |interp, recv, args, _| {
+        interp.check_args(args, 1, Some(2))?;
+        let regexp = to_regexp(interp, args[0])?;
+        let mut rest = vec![recv];
+        rest.extend_from_slice(&args[1..]);
+        interp.call(regexp, "match?", &rest)
+    });
+    interp.define_builtin(string, "sub", |interp, recv, args, block| {
+        let (replaced, _) = substitute(interp, recv, args, block, false)?;
+        Ok(interp.new_string(replaced))
+    });
+    interp.define_builtin(string, "gsub", |interp, recv, args, block| {
+        let (replaced, _) = substitute(interp, recv, args, block, true)?;
+        Ok(interp.new_string(replaced))
+    });
+    interp.define_builtin(string, "sub!", |interp, recv, args, block| {
+        let (replaced, matched) = substitute(interp, recv, args, block, false)?;
+        if !matched { return Ok(Value::Nil); }
+        replace_contents(interp, recv, replaced)?;
+        Ok(recv)
+    });
+    interp.define_builtin(string, "gsub!", |interp, recv, args, block| {
+        let (replaced, matched) = substitute(interp, recv, args, block, true)?;
+        if !matched { return Ok(Value::Nil); }
+        replace_contents(interp, recv, replaced)?;
+        Ok(recv)
+    });
+    interp.define_builtin(string, "scan", scan);
+    interp.define_builtin(string, "split", split);
+}
+
+pub fn str_of(interp: &mut Interpreter, v: Value) -> Result<String, Unwind> {
+    expect_string(interp, v)
+}
+
+// The quoted, escaped form `inspect` and `p` show.
+pub fn inspect_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\x1b' => out.push_str("\\e"),
+            '\0' => out.push_str("\\0"),
+            c if c.is_control() => out.push_str(&format!("\\x{:02X}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Replaces a string's contents in place, checking it isn't frozen.
+pub fn replace_contents(interp: &mut Interpreter, v: Value, contents: String) -> Result<(), Unwind> {
+    interp.check_frozen(v)?;
+    if let Some(ObjectKind::Str(s)) = v.as_object().map(|r| &mut interp.heap.get_mut(r).kind) {
+        *s = contents;
+    }
+    Ok(())
+}
+
+fn initialize(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(1))?;
+    if let Some(v) = args.first() {
+        let s = str_of(interp, *v)?;
+        replace_contents(interp, recv, s)?;
+    }
+    Ok(Value::Nil)
+}
+
+fn add(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let mut s = str_of(interp, recv)?;
+    match interp.str_ref(args[0]) {
+        Some(other) => s.push_str(other),
+        None => return Err(conversion_error(interp, args[0], "String"))
+    }
+    Ok(interp.new_string(s))
+}
+
+fn repeat(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let s = str_of(interp, recv)?;
+    let n = expect_integer(interp, args[0])?;
+    if n < 0 {
+        return Err(interp.argument_error(String::from("negative argument")));
+    }
+    Ok(interp.new_string(s.repeat(n as usize)))
+}
+
+fn equal(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    Ok(Value::from_bool(interp.str_ref(recv).is_some() && interp.str_ref(recv) == interp.str_ref(args[0])))
+}
+
+fn cmp(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    match (interp.str_ref(recv), interp.str_ref(args[0])) {
+        (Some(a), Some(b)) => Ok(Value::Integer(a.cmp(b) as i64)),
+        _ => Ok(Value::Nil)
+    }
+}
+
+fn ordered(interp: &mut Interpreter, recv: Value, args: &[Value], test: fn(Ordering) -> bool) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    if interp.str_ref(args[0]).is_none() {
+        let message = format!("comparison of String with {} failed", interp.inspect(args[0])?);
+        return Err(interp.argument_error(message));
+    }
+    Ok(Value::from_bool(test(compare(interp, recv, args[0])?)))
+}
+
+fn length(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    Ok(Value::Integer(str_of(interp, recv)?.chars().count() as i64))
+}
+
+fn to_s(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    // Subclass instances convert to plain strings.
+    if interp.real_class(recv) == interp.core.string {
+        return Ok(recv);
+    }
+    let s = str_of(interp, recv)?;
+    Ok(interp.new_string(s))
+}
+
+fn to_sym(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let s = str_of(interp, recv)?;
+    Ok(Value::Symbol(interp.sym(&s)))
+}
+
+// Leading integer, ignoring the rest: `"12abc".to_i` is 12, `"abc".to_i` is 0.
+fn to_i(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let s = str_of(interp, recv)?;
+    let trimmed = s.trim_start();
+    let mut end = 0;
+    for (i, c) in trimmed.char_indices() {
+        if c.is_ascii_digit() || c == '_' || (i == 0 && (c == '-' || c == '+')) {
+            end = i + c.len_utf8();
+        } else {
+            break;
+        }
+    }
+    Ok(numeric::parse_integer(interp, &trimmed[..end].replace('_', "")).unwrap_or(Value::Integer(0)))
+}
+
+fn to_f(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let s = str_of(interp, recv)?;
+    let trimmed = s.trim_start();
+    // The longest prefix that parses as a float.
+    let f = (1..=trimmed.len()).rev()
+        .filter(|i| trimmed.is_char_boundary(*i))
+        .find_map(|i| numeric::parse_float(&trimmed[..i]))
+        .unwrap_or(0.0);
+    Ok(Value::Float(f))
+}
+
+fn append(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let mut s = str_of(interp, recv)?;
+    match args[0] {
+        Value::Integer(n) => match u32::try_from(n).ok().and_then(char::from_u32) {
+            Some(c) => s.push(c),
+            None => return Err(interp.error(interp.core.range_error, format!("{} out of char range", n)))
+        },
+        v => s.push_str(&str_of(interp, v)?)
+    }
+    replace_contents(interp, recv, s)?;
+    Ok(recv)
+}
+
+// Characters of a string, for indexing by character rather than by byte.
+fn chars_of(interp: &mut Interpreter, v: Value) -> Result<Rc<[char]>, Unwind> {
+    Ok(str_of(interp, v)?.chars().collect())
+}
+
+fn slice_of(interp: &mut Interpreter, chars: &[char], start: usize, count: usize) -> Value {
+    let end = (start + count).min(chars.len());
+    interp.new_string(chars[start..end].iter().collect())
+}
+
+// `s[i]`, `s[start, length]`, `s[range]`, `s[substring]` and `s[regexp, group]`, counting
+// characters; out of range gives nil.
+fn index(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let chars = chars_of(interp, recv)?;
+    let len = chars.len();
+    if interp.regex(args[0]).is_some() {
+        let md = interp.call(args[0], "match", &[recv])?;
+        return match (md, args.get(1)) {
+            (Value::Nil, _) => Ok(Value::Nil),
+            (md, group) => interp.call(md, "[]", &[group.copied().unwrap_or(Value::Integer(0))])
+        };
+    }
+    if let Some(needle) = interp.string_value(args[0]) {
+        let s: String = chars.iter().collect();
+        return Ok(if s.contains(&needle) { interp.new_string(needle) } else { Value::Nil });
+    }
+    let range = match args[0].as_object().map(|r| &interp.heap.get(r).kind) {
+        Some(ObjectKind::Range { start, end, exclusive }) => Some((*start, *end, *exclusive)),
+        _ => None
+    };
+    let (start, count) = match (range, args.get(1)) {
+        (Some((start, end, exclusive)), _) => match array::range_span(interp, start, end, exclusive, len)? {
+            Some(span) => span,
+            None => return Ok(Value::Nil)
+        },
+        (None, Some(count)) => {
+            let start = expect_integer(interp, args[0])?;
+            let start = if start < 0 { start + len as i64 } else { start };
+            let count = expect_integer(interp, *count)?;
+            if start < 0 || count < 0 {
+                return Ok(Value::Nil);
+            }
+            (start as usize, count as usize)
+        },
+        (None, None) => {
+            let i = expect_integer(interp, args[0])?;
+            let i = if i < 0 { i + len as i64 } else { i };
+            if i < 0 || i >= len as i64 {
+                return Ok(Value::Nil);
+            }
+            (i as usize, 1)
+        }
+    };
+    if start > len {
+        return Ok(Value::Nil);
+    }
+    Ok(slice_of(interp, &chars, start, count))
+}
+
+// `-str`: the string itself when already frozen, otherwise a frozen copy.
+fn dedup(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    if interp.is_frozen(recv) {
+        return Ok(recv);
+    }
+    let s = str_of(interp, recv)?;
+    Ok(interp.frozen_string(s))
+}
+
+fn each_char(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerable::enumerator(interp, recv, "each_char", args))
+    };
+    for c in str_of(interp, recv)?.chars() {
+        let c = interp.new_string(c.to_string());
+        interp.call_proc_for_effect(block, &[c])?;
+    }
+    Ok(recv)
+}
+
+// Lines with their line breaks kept.
+fn lines_of(s: &str) -> Vec<String> {
+    s.split_inclusive('\n').map(str::to_string).collect()
+}
+
+fn each_line(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    let block = match block {
+        Some(b) => b,
+        None => return Ok(enumerable::enumerator(interp, recv, "each_line", args))
+    };
+    for line in lines_of(&str_of(interp, recv)?) {
+        let line = interp.new_string(line);
+        interp.call_proc_for_effect(block, &[line])?;
+    }
+    Ok(recv)
+}
+
+fn upcase(s: &str) -> String { s.to_uppercase() }
+fn downcase(s: &str) -> String { s.to_lowercase() }
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+        None => String::new()
+    }
+}
+
+fn swapcase(s: &str) -> String {
+    s.chars().flat_map(|c| -> Vec<char> {
+        if c.is_uppercase() { c.to_lowercase().collect() } else { c.to_uppercase().collect() }
+    }).collect()
+}
+
+// Ruby strips ASCII whitespace, and trailing NULs as well.
+fn is_space(c: char) -> bool { matches!(c, ' ' | '\t' | '\n' | '\x0b' | '\x0c' | '\r') }
+fn strip(s: &str) -> String { s.trim_start_matches(is_space).trim_end_matches(|c| is_space(c) || c == '\0').to_string() }
+fn lstrip(s: &str) -> String { s.trim_start_matches(is_space).to_string() }
+fn rstrip(s: &str) -> String { s.trim_end_matches(|c| is_space(c) || c == '\0').to_string() }
+
+fn chop(s: &str) -> String {
+    match s.strip_suffix("\r\n") {
+        Some(rest) => rest.to_string(),
+        None => {
+            let mut chars = s.chars();
+            chars.next_back();
+            chars.as_str().to_string()
+        }
+    }
+}
+
+fn converted(interp: &mut Interpreter, recv: Value, f: fn(&str) -> String) -> EvalResult {
+    let s = str_of(interp, recv)?;
+    Ok(interp.new_string(f(&s)))
+}
+
+// The bang forms change the receiver, returning nil when nothing changed.
+fn in_place(interp: &mut Interpreter, recv: Value, changed: String) -> EvalResult {
+    if interp.str_ref(recv) == Some(changed.as_str()) {
+        interp.check_frozen(recv)?;
+        return Ok(Value::Nil);
+    }
+    replace_contents(interp, recv, changed)?;
+    Ok(recv)
+}
+
+fn converted_in_place(interp: &mut Interpreter, recv: Value, f: fn(&str) -> String) -> EvalResult {
+    let s = str_of(interp, recv)?;
+    in_place(interp, recv, f(&s))
+}
+
+// Without an argument, removes one trailing line break of any style; `chomp("")` removes
+// every trailing line break.
+fn chomp(interp: &mut Interpreter, recv: Value, args: &[Value]) -> Result<String, Unwind> {
+    interp.check_args(args, 0, Some(1))?;
+    let s = str_of(interp, recv)?;
+    let suffix = match args.first() {
+        Some(v) => Some(str_of(interp, *v)?),
+        None => None
+    };
+    Ok(match suffix.as_deref() {
+        None => s.strip_suffix("\r\n").or_else(|| s.strip_suffix('\n')).or_else(|| s.strip_suffix('\r')).unwrap_or(&s).to_string(),
+        Some("") => {
+            let mut rest = s.as_str();
+            while let Some(shorter) = rest.strip_suffix("\r\n").or_else(|| rest.strip_suffix('\n')) {
+                rest = shorter;
+            }
+            rest.to_string()
+        },
+        Some(suffix) => s.strip_suffix(suffix).unwrap_or(&s).to_string()
+    })
+}
+
+// Pads to `width` characters, putting `left` of the padding before the text.
+fn justify(interp: &mut Interpreter, recv: Value, args: &[Value], left: f64) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let s = str_of(interp, recv)?;
+    let width = expect_integer(interp, args[0])?;
+    let pad: Vec<char> = match args.get(1) {
+        Some(v) => str_of(interp, *v)?.chars().collect(),
+        None => vec![' ']
+    };
+    if pad.is_empty() {
+        return Err(interp.argument_error(String::from("zero width padding")));
+    }
+    let fill = (width.max(0) as usize).saturating_sub(s.chars().count());
+    let before = (fill as f64 * left).floor() as usize;
+    let padding = |n: usize| pad.iter().cycle().take(n).collect::<String>();
+    Ok(interp.new_string(format!("{}{}{}", padding(before), s, padding(fill - before))))
+}
+
+fn start_with(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    let s = str_of(interp, recv)?;
+    for prefix in args.iter() {
+        let found = match interp.regex(*prefix).cloned() {
+            Some(regex) => {
+                let chars: Rc<[char]> = s.chars().collect();
+                let captures = regex.search(&chars, 0).filter(|c| c[0].is_some_and(|(start, _)| start == 0));
+                let found = captures.is_some();
+                regexp::last_match(interp, &regex, &chars, captures);
+                found
+            },
+            None => s.starts_with(&str_of(interp, *prefix)?)
+        };
+        if found {
+            return Ok(Value::True);
+        }
+    }
+    Ok(Value::False)
+}
+
+// Where `pattern` first occurs at or after the character offset `args[1]`.
+fn find_index(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let chars = chars_of(interp, recv)?;
+    let start = match args.get(1) {
+        Some(v) => expect_integer(interp, *v)?,
+        None => 0
+    };
+    let start = if start < 0 { start + chars.len() as i64 } else { start };
+    if start < 0 || start > chars.len() as i64 {
+        return Ok(Value::Nil);
+    }
+    let regex = regexp::pattern(interp, args[0])?;
+    let captures = regex.search(&chars, start as usize);
+    let found = captures.as_ref().and_then(|c| c[0]).map_or(Value::Nil, |(s, _)| Value::Integer(s as i64));
+    if interp.regex(args[0]).is_some() {
+        regexp::last_match(interp, &regex, &chars, captures);
+    }
+    Ok(found)
+}
+
+// Where `pattern` last begins at or before the character offset `args[1]`.
+fn find_rindex(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(2))?;
+    let chars = chars_of(interp, recv)?;
+    let start = match args.get(1) {
+        Some(v) => expect_integer(interp, *v)?,
+        None => chars.len() as i64
+    };
+    let start = if start < 0 { start + chars.len() as i64 } else { start.min(chars.len() as i64) };
+    if start < 0 {
+        return Ok(Value::Nil);
+    }
+    let regex = regexp::pattern(interp, args[0])?;
+    for pos in (0..=start as usize).rev() {
+        if let Some(captures) = regex.search(&chars, pos).filter(|c| c[0].is_some_and(|(s, _)| s == pos)) {
+            if interp.regex(args[0]).is_some() {
+                regexp::last_match(interp, &regex, &chars, Some(captures));
+            }
+            return Ok(Value::Integer(pos as i64));
+        }
+    }
+    Ok(Value::Nil)
+}
+
+fn match_index(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    if interp.str_ref(args[0]).is_some() {
+        return Err(interp.type_error(String::from("wrong argument type String (expected Regexp)")));
+    }
+    interp.call(args[0], "=~", &[recv])
+}
+
+// `str.match(pattern)` compiles a string pattern rather than matching it literally.
+fn to_regexp(interp: &mut Interpreter, v: Value) -> EvalResult {
+    if interp.regex(v).is_some() {
+        return Ok(v);
+    }
+    let regexp = Value::Object(interp.core.regexp);
+    interp.call(regexp, "new", &[v])
+}
+
+enum Replacement {
+    Template(String),
+    Hash(Value),
+    Block(Value)
+}
+
+// Expands `\0`, `\&`, `\1`..`\9`, `\k<name>`, `` \` ``, `\'` and `\\` in a replacement string.
+fn expand(template: &str, regex: &Regex, text: &[char], captures: &Captures) -> String {
+    let group = |span: Option<(usize, usize)>| span.map_or_else(String::new, |(s, e)| text[s..e].iter().collect());
+    let (start, end) = captures[0].unwrap_or((0, 0));
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('&') => out.push_str(&group(captures[0])),
+            Some('`') => out.extend(&text[..start]),
+            Some('\'') => out.extend(&text[end..]),
+            Some('\\') => out.push('\\'),
+            Some(d) if d.is_ascii_digit() => out.push_str(&group(captures.get(d as usize - '0' as usize).copied().flatten())),
+            Some('k') if chars.peek() == Some(&'<') => {
+                chars.next();
+                let name: String = chars.by_ref().take_while(|c| *c != '>').collect();
+                out.push_str(&group(regex.group_index(&name).and_then(|i| captures[i])));
+            },
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            },
+            None => out.push('\\')
+        }
+    }
+    out
+}
+
+// Replaces the first match of `args[0]`, or every match when `global`, with `args[1]` or what
+// the block returns. Returns the new text and whether anything matched.
+fn substitute(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>, global: bool) -> Result<(String, bool), Unwind> {
+    interp.check_args(args, 1, Some(2))?;
+    let replacement = match (args.get(1), block) {
+        (Some(v), _) if interp.is_a(*v, interp.core.hash) => Replacement::Hash(*v),
+        (Some(v), _) => Replacement::Template(str_of(interp, *v)?),
+        (None, Some(block)) => Replacement::Block(block),
+        (None, None) => return Err(interp.argument_error(String::from("wrong number of arguments (given 1, expected 2)")))
+    };
+    let regex = regexp::pattern(interp, args[0])?;
+    let text = chars_of(interp, recv)?;
+    let mut out = String::new();
+    let mut last: Option<Captures> = None;
+    let mut copied = 0;
+    let mut pos = 0;
+    while let Some(captures) = regex.search(&text, pos) {
+        let (start, end) = captures[0].expect("a match spans group 0");
+        out.extend(&text[copied..start]);
+        let piece = match &replacement {
+            Replacement::Template(template) => expand(template, &regex, &text, &captures),
+            Replacement::Hash(hash) => {
+                let key = interp.new_string(text[start..end].iter().collect());
+                let v = interp.call(*hash, "[]", &[key])?;
+                interp.to_s(v)?
+            },
+            Replacement::Block(block) => {
+                regexp::last_match(interp, &regex, &text, Some(captures.clone()));
+                let matched = interp.new_string(text[start..end].iter().collect());
+                let v = interp.call_proc(*block, &[matched], None)?;
+                interp.to_s(v)?
+            }
+        };
+        out.push_str(&piece);
+        copied = end;
+        last = Some(captures);
+        // After an empty match, the next search starts a character later.
+        pos = if end == start { end + 1 } else { end };
+        if !global || pos > text.len() {
+            break;
+        }
+    }
+    out.extend(&text[copied..]);
+    let matched = last.is_some();
+    regexp::last_match(interp, &regex, &text, last);
+    Ok((out, matched))
+}
+
+// Each match of `args[0]`: the matched text, or an array of the groups when there are any.
+fn scan(interp: &mut Interpreter, recv: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    let regex = regexp::pattern(interp, args[0])?;
+    let text = chars_of(interp, recv)?;
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(captures) = regex.search(&text, pos) {
+        let (start, end) = captures[0].expect("a match spans group 0");
+        let item = if captures.len() == 1 {
+            regexp::substring(interp, &text, captures[0])
+        } else {
+            let groups = captures[1..].iter().map(|span| regexp::substring(interp, &text, *span)).collect();
+            interp.new_array(groups)
+        };
+        pos = if end == start { end + 1 } else { end };
+        match block {
+            Some(block) => {
+                regexp::last_match(interp, &regex, &text, Some(captures));
+                interp.call_proc_for_effect(block, &[item])?;
+            },
+            None => found.push(item)
+        }
+        if pos > text.len() {
+            break;
+        }
+    }
+    match block {
+        Some(_) => Ok(recv),
+        None => Ok(interp.new_array(found))
+    }
+}
+
+// Splits on a string, a regexp (whose groups are kept in the result) or, by default, runs of
+// whitespace. A positive limit caps the number of fields; zero drops trailing empty fields.
+fn split(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 0, Some(2))?;
+    let s = str_of(interp, recv)?;
+    let limit = match args.get(1) {
+        Some(v) => expect_integer(interp, *v)?,
+        None => 0
+    };
+    let awk = match args.first() {
+        None | Some(Value::Nil) => true,
+        Some(v) => interp.str_ref(*v) == Some(" ")
+    };
+    let (text, regex): (Rc<[char]>, Rc<Regex>) = if awk {
+        let trimmed = if limit == 1 { s.as_str() } else { s.trim_start_matches(is_space) };
+        (trimmed.chars().collect(), Rc::new(Regex::create("\\s+", 0).expect("the whitespace pattern is valid")))
+    } else {
+        (s.chars().collect(), regexp::pattern(interp, args[0])?)
+    };
+    let mut fields: Vec<Value> = Vec::new();
+    if text.is_empty() {
+        return Ok(interp.new_array(fields));
+    }
+    let mut field_start = 0;
+    let mut pos = 0;
+    let mut count = 0;
+    while limit <= 0 || count + 1 < limit {
+        let captures = match regex.search(&text, pos) {
+            Some(captures) => captures,
+            None => break
+        };
+        let (start, end) = captures[0].expect("a match spans group 0");
+        if end == start {
+            // An empty match splits between characters, never before the first one.
+            if start >= text.len() { break; }
+            if start == field_start {
+                pos = start + 1;
+                continue;
+            }
+        }
+        fields.push(interp.new_string(text[field_start..start].iter().collect()));
+        for span in captures[1..].iter().filter(|span| span.is_some()) {
+            fields.push(regexp::substring(interp, &text, *span));
+        }
+        count += 1;
+        field_start = end;
+        pos = if end == start { end + 1 } else { end };
+    }
+    fields.push(interp.new_string(text[field_start..].iter().collect()));
+    if limit == 0 {
+        while fields.last().is_some_and(|f| interp.str_ref(*f) == Some("")) {
+            fields.pop();
+        }
+    }
+    Ok(interp.new_array(fields))
+}
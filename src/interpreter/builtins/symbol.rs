@@ -0,0 +1,62 @@
+use crate::interpreter::interpreter::{EvalResult, Interpreter};
+use crate::interpreter::runtime::heap::ProcBody;
+use crate::interpreter::runtime::value::Value;
+use super::string::inspect_str;
+
+pub fn install(interp: &mut Interpreter) {
+    let symbol = interp.core.symbol;
+    interp.define_builtin(symbol, "to_s", to_s);
+    interp.define_builtin(symbol, "id2name", to_s);
+    interp.define_builtin(symbol, "name", to_s);
+    interp.define_builtin(symbol, "to_sym", |_, recv, _, _| Ok(recv));
+    interp.define_builtin(symbol, "inspect", inspect);
+    interp.define_builtin(symbol, "to_proc", to_proc);
+    interp.define_builtin(symbol, "<=>", cmp);
+    interp.define_builtin(symbol, "length", |interp, recv, _, _| Ok(Value::Integer(name(interp, recv).chars().count() as i64)));
+    interp.define_builtin(symbol, "size", |interp, recv, _, _| Ok(Value::Integer(name(interp, recv).chars().count() as i64)));
+}
+
+fn name(interp: &Interpreter, v: Value) -> String {
+    match v {
+        Value::Symbol(sym) => interp.sym_name(sym).to_string(),
+        _ => String::new()
+    }
+}
+
+// Whether `:name` can be written without quotes.
+fn is_plain_symbol(s: &str) -> bool {
+    let operators = ["+", "-", "*", "/", "%", "**", "==", "!=", "<", "<=", ">", ">=", "<=>", "===", "=~", "!", "[]", "[]=", "<<", ">>", "&", "|", "^", "~", "+@", "-@"];
+    if operators.contains(&s) {
+        return true;
+    }
+    let body = s.trim_start_matches("@@").trim_start_matches(['@', '$']);
+    let body = body.strip_suffix(['?', '!', '=']).unwrap_or(body);
+    let mut chars = body.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_') && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn to_s(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let s = name(interp, recv);
+    Ok(interp.new_string(s))
+}
+
+fn inspect(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    let s = name(interp, recv);
+    let shown = if is_plain_symbol(&s) { format!(":{}", s) } else { format!(":{}", inspect_str(&s)) };
+    Ok(interp.new_string(shown))
+}
+
+fn to_proc(interp: &mut Interpreter, recv: Value, _args: &[Value], _block: Option<Value>) -> EvalResult {
+    match recv {
+        Value::Symbol(sym) => Ok(interp.new_proc(ProcBody::Symbol(sym), true)),
+        v => Ok(v)
+    }
+}
+
+fn cmp(interp: &mut Interpreter, recv: Value, args: &[Value], _block: Option<Value>) -> EvalResult {
+    interp.check_args(args, 1, Some(1))?;
+    match args[0] {
+        Value::Symbol(_) => Ok(Value::Integer(name(interp, recv).cmp(&name(interp, args[0])) as i64)),
+        _ => Ok(Value::Nil)
+    }
+}
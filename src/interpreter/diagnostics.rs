@@ -0,0 +1,242 @@
+use std::fmt;
+
+use super::source_map::{FileId, SourceMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note
+}
+
+impl Severity {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Severity::Error   => "error",
+            Severity::Warning => "warning",
+            Severity::Note    => "note"
+        }
+    }
+    fn colour(self) -> &'static str {
+        match self {
+            Severity::Error   => "\x1b[1;31m",
+            Severity::Warning => "\x1b[1;33m",
+            Severity::Note    => "\x1b[1;36m"
+        }
+    }
+}
+
+// A run of `len` characters starting at a 1-based line and column of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub file: FileId,
+    pub line: u32,
+    pub col: u32,
+    pub len: u32
+}
+
+impl Span {
+    pub fn create(file: FileId, line: u32, col: u32, len: u32) -> Span {
+        Span { file, line, col, len: len.max(1) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    // The primary label marks the problem itself (`^^^`); secondary ones add context (`---`).
+    pub primary: bool
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    severity: Severity,
+    message: String,
+    labels: Vec<Label>,
+    notes: Vec<String>,
+    help: Vec<String>
+}
+
+impl Diagnostic {
+    pub fn create(severity: Severity, message: String) -> Diagnostic {
+        Diagnostic { severity, message, labels: Vec::new(), notes: Vec::new(), help: Vec::new() }
+    }
+    pub fn error(message: String) -> Diagnostic { Diagnostic::create(Severity::Error, message) }
+    pub fn warning(message: String) -> Diagnostic { Diagnostic::create(Severity::Warning, message) }
+    pub fn note(message: String) -> Diagnostic { Diagnostic::create(Severity::Note, message) }
+
+    pub fn with_label(mut self, span: Span, message: &str) -> Diagnostic {
+        self.labels.push(Label { span, message: message.to_string(), primary: true });
+        self
+    }
+    pub fn with_secondary_label(mut self, span: Span, message: &str) -> Diagnostic {
+        self.labels.push(Label { span, message: message.to_string(), primary: false });
+        self
+    }
+    pub fn with_note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(note.to_string());
+        self
+    }
+    pub fn with_help(mut self, help: &str) -> Diagnostic {
+        self.help.push(help.to_string());
+        self
+    }
+
+    pub fn get_severity(&self) -> Severity { self.severity }
+    pub fn get_message(&self) -> &str { &self.message }
+    pub fn get_labels(&self) -> &[Label] { &self.labels }
+    pub fn get_notes(&self) -> &[String] { &self.notes }
+    pub fn get_help(&self) -> &[String] { &self.help }
+    pub fn primary_span(&self) -> Option<Span> {
+        self.labels.iter().find(|l| l.primary).or_else(|| self.labels.first()).map(|l| l.span)
+    }
+}
+
+// The one-line form, `error: message at [l: x, c: y]`, for logs and tests.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity.to_str(), self.message)?;
+        if let Some(span) = self.primary_span() {
+            write!(f, " at [l: {}, c: {}]", span.line, span.col)?;
+        }
+        Ok(())
+    }
+}
+
+static TAB_WIDTH: usize = 4;
+static RESET: &str = "\x1b[0m";
+static BOLD: &str = "\x1b[1m";
+static GUTTER: &str = "\x1b[1;34m";
+
+// Renders diagnostics against the source they refer to:
+//
+//     error: unexpected '*', expected an expression
+//      --> script.lang:3:11
+//       |
+//     3 |   y = a * * b
+//       |           ^ expected an expression
+//       |
+//       = help: ...
+pub struct Renderer {
+    colour: bool
+}
+
+impl Renderer {
+    pub fn create(colour: bool) -> Renderer {
+        Renderer { colour }
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic, sources: &SourceMap) -> String {
+        let mut labels: Vec<&Label> = diagnostic.labels.iter().collect();
+        // The primary label's file comes first, then the others in order of appearance.
+        let primary_file = diagnostic.primary_span().map(|s| s.file);
+        labels.sort_by_key(|l| (Some(l.span.file) != primary_file, l.span.file, l.span.line, l.span.col, !l.primary));
+        let width = labels.iter().map(|l| l.span.line.to_string().len()).max().unwrap_or(1);
+        let pad = " ".repeat(width);
+
+        let mut out = String::new();
+        let severity = diagnostic.severity;
+        out.push_str(&format!("{}{}", self.paint(severity.colour(), severity.to_str()), self.paint(BOLD, &format!(": {}", diagnostic.message))));
+        out.push('\n');
+        let mut last: Option<(FileId, u32)> = None;
+        for label in labels.iter() {
+            let span = label.span;
+            if last.map(|(file, _)| file) != Some(span.file) {
+                let arrow = if last.is_none() { "-->" } else { ":::" };
+                if last.is_some() {
+                    out.push_str(&format!("{} {}\n", pad, self.paint(GUTTER, "|")));
+                }
+                // The header points at the primary label even when a secondary one comes first.
+                let at = diagnostic.primary_span().filter(|p| p.file == span.file).unwrap_or(span);
+                out.push_str(&format!("{}{} {}:{}:{}\n", pad, self.paint(GUTTER, arrow), sources.get_name(at.file), at.line, at.col));
+                out.push_str(&format!("{} {}\n", pad, self.paint(GUTTER, "|")));
+                last = None;
+            }
+            let text = sources.get(span.file).and_then(|f| f.get_line(span.line)).unwrap_or("");
+            if last != Some((span.file, span.line)) {
+                if last.is_some_and(|(_, l)| span.line > l + 1) {
+                    out.push_str(&format!("{}\n", self.paint(GUTTER, "...")));
+                }
+                out.push_str(&format!("{} {} {}\n", self.paint(GUTTER, &format!("{:>w$}", span.line, w = width)), self.paint(GUTTER, "|"), expand_tabs(text)));
+                last = Some((span.file, span.line));
+            }
+            let (offset, len) = underline_extent(text, span);
+            let (mark, colour) = if label.primary { ('^', severity.colour()) } else { ('-', GUTTER) };
+            let mut underline: String = std::iter::repeat_n(mark, len).collect();
+            if !label.message.is_empty() {
+                underline.push(' ');
+                underline.push_str(&label.message);
+            }
+            out.push_str(&format!("{} {} {}{}\n", pad, self.paint(GUTTER, "|"), " ".repeat(offset), self.paint(colour, &underline)));
+        }
+        if !diagnostic.notes.is_empty() || !diagnostic.help.is_empty() {
+            out.push_str(&format!("{} {}\n", pad, self.paint(GUTTER, "|")));
+        }
+        for note in diagnostic.notes.iter() {
+            out.push_str(&format!("{} {} {} {}\n", pad, self.paint(GUTTER, "="), self.paint(BOLD, "note:"), note));
+        }
+        for help in diagnostic.help.iter() {
+            out.push_str(&format!("{} {} {} {}\n", pad, self.paint(GUTTER, "="), self.paint(BOLD, "help:"), help));
+        }
+        out
+    }
+
+    fn paint(&self, colour: &str, text: &str) -> String {
+        if self.colour {
+            format!("{}{}{}", colour, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+// Display offset and width of a span's underline, counting tabs as they're expanded and clipping
+// spans that run past the end of the line.
+fn underline_extent(text: &str, span: Span) -> (usize, usize) {
+    let width = |c: char| if c == '\t' { TAB_WIDTH } else { 1 };
+    let chars: Vec<char> = text.chars().collect();
+    let start = (span.col as usize).saturating_sub(1);
+    let offset: usize = chars.iter().take(start).map(|c| width(*c)).sum::<usize>() + start.saturating_sub(chars.len());
+    let len: usize = chars.iter().skip(start).take(span.len as usize).map(|c| width(*c)).sum();
+    (offset, len.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_snippet_with_caret_under_the_span() {
+        let mut sources = SourceMap::create();
+        let file = sources.add_string("test.lang", "x = 1\ny = a * * b\n");
+        let diagnostic = Diagnostic::error(String::from("unexpected '*'"))
+            .with_label(Span::create(file, 2, 9, 1), "expected an expression")
+            .with_secondary_label(Span::create(file, 2, 7, 1), "operator starts here")
+            .with_help("remove one of the operators");
+        let expected = "\
+error: unexpected '*'
+ --> test.lang:2:9
+  |
+2 | y = a * * b
+  |       - operator starts here
+  |         ^ expected an expression
+  |
+  = help: remove one of the operators
+";
+        assert_eq!(Renderer::create(false).render(&diagnostic, &sources), expected);
+    }
+
+    #[test]
+    fn underlines_follow_tab_expansion() {
+        let mut sources = SourceMap::create();
+        let file = sources.add_string("t", "\tfoo(bar)");
+        let diagnostic = Diagnostic::warning(String::from("unused")).with_label(Span::create(file, 1, 6, 3), "");
+        let rendered = Renderer::create(false).render(&diagnostic, &sources);
+        assert!(rendered.contains("1 |     foo(bar)\n  |         ^^^\n"), "{}", rendered);
+    }
+}
@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+
+use super::super::parser::input_stream::InputStream;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repetition {
+    ZeroOrMore,
+    OneOrMore,
+    Optional
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Terminal(String),
+    NonTerminal(String),
+    Sequence(Vec<Expr>),
+    Choice(Vec<Expr>),
+    Repeat(Box<Expr>, Repetition)
+}
+
+impl Expr {
+    // Every nonterminal referenced anywhere inside the expression, in order of appearance.
+    pub fn references(&self) -> Vec<&str> {
+        let mut out: Vec<&str> = Vec::new();
+        self.collect_references(&mut out);
+        out
+    }
+    fn collect_references<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            Expr::Terminal(_) => {},
+            Expr::NonTerminal(name) => out.push(name),
+            Expr::Sequence(items) | Expr::Choice(items) => items.iter().for_each(|e| e.collect_references(out)),
+            Expr::Repeat(inner, _) => inner.collect_references(out)
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Terminal(t) => write!(f, "'{}'", t.escape_default()),
+            Expr::NonTerminal(n) => write!(f, "<{}>", n),
+            Expr::Sequence(items) => {
+                let parts: Vec<String> = items.iter().map(|e| match e {
+                    Expr::Choice(_) => format!("({})", e),
+                    _ => e.to_string()
+                }).collect();
+                write!(f, "{}", parts.join(" "))
+            },
+            Expr::Choice(items) => {
+                let parts: Vec<String> = items.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", parts.join(" | "))
+            },
+            Expr::Repeat(inner, rep) => {
+                let suffix = match rep {
+                    Repetition::ZeroOrMore => "*",
+                    Repetition::OneOrMore => "+",
+                    Repetition::Optional => "?"
+                };
+                match **inner {
+                    Expr::Terminal(_) | Expr::NonTerminal(_) => write!(f, "{}{}", inner, suffix),
+                    _ => write!(f, "({}){}", inner, suffix)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    name: String,
+    expr: Expr,
+    line: u32
+}
+
+impl Rule {
+    pub fn get_name(&self) -> &str { &self.name }
+    pub fn get_expr(&self) -> &Expr { &self.expr }
+    pub fn get_line(&self) -> u32 { self.line }
+    // A rule made only of terminals describes the spelling of a single token (`<lit>`, `<eq>`)
+    // and is matched against token text character by character rather than token by token.
+    pub fn is_lexical(&self) -> bool { self.expr.references().is_empty() }
+}
+
+#[derive(Debug)]
+pub struct GrammarError {
+    message: String,
+    line: u32,
+    col: u32
+}
+
+impl GrammarError {
+    pub fn get_message(&self) -> &str { &self.message }
+    pub fn get_line(&self) -> u32 { self.line }
+    pub fn get_col(&self) -> u32 { self.col }
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at [l: {}, c: {}]", self.message, self.line, self.col)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Grammar {
+    rules: Vec<Rule>,
+    index: HashMap<String, usize>
+}
+
+impl Grammar {
+    pub fn from_string(src: &str) -> Result<Grammar, GrammarError> {
+        let mut is = InputStream::from_string(src);
+        let tokens = BnfLexer { input_stream: &mut is, last_line: 0 }.read_all()?;
+        BnfParser { tokens, pos: 0 }.read_grammar()
+    }
+    pub fn from_file(path: &str) -> Result<Grammar, GrammarError> {
+        match fs::read_to_string(path) {
+            Ok(src) => Grammar::from_string(&src),
+            Err(e) => Err(GrammarError { message: format!("can't read {}: {}", path, e), line: 0, col: 0 })
+        }
+    }
+
+    pub fn rules(&self) -> &[Rule] { &self.rules }
+    // The first rule in the file is the start symbol, `<program>` in `test.bnf`.
+    pub fn start_rule(&self) -> Option<&Rule> { self.rules.first() }
+    pub fn get_rule(&self, name: &str) -> Option<&Rule> { self.index.get(name).map(|i| &self.rules[*i]) }
+    pub fn rule_index(&self, name: &str) -> Option<usize> { self.index.get(name).copied() }
+
+    // Adds the rules of `other` that this grammar refers to without defining, and the ones those
+    // refer to in turn, after its own.
+    pub fn define_missing(&mut self, other: &Grammar) {
+        let mut i = 0;
+        while i < self.rules.len() {
+            let missing: Vec<String> = self.rules[i].expr.references().into_iter()
+                .filter(|name| !self.index.contains_key(*name))
+                .map(String::from)
+                .collect();
+            for name in missing {
+                if let Some(rule) = other.get_rule(&name).filter(|_| !self.index.contains_key(&name)) {
+                    self.index.insert(name, self.rules.len());
+                    self.rules.push(rule.clone());
+                }
+            }
+            i += 1;
+        }
+    }
+
+    // Adds the rules of `other`, where a rule both grammars define also matches the
+    // alternatives `other` gives it.
+    pub fn extend(&mut self, other: &Grammar) {
+        for rule in other.rules.iter() {
+            if let Some(i) = self.index.get(&rule.name) {
+                let own = &mut self.rules[*i].expr;
+                let mut alternatives = match std::mem::replace(own, Expr::Choice(Vec::new())) {
+                    Expr::Choice(items) => items,
+                    expr => vec![expr]
+                };
+                alternatives.push(rule.expr.clone());
+                *own = Expr::Choice(alternatives);
+            } else {
+                self.index.insert(rule.name.clone(), self.rules.len());
+                self.rules.push(rule.clone());
+            }
+        }
+    }
+}
+
+impl fmt::Display for Grammar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for rule in self.rules.iter() {
+            writeln!(f, "<{}> ::= {}", rule.name, rule.expr)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum BnfSymbol {
+    NonTerminal(String),
+    Terminal(String),
+    Define,
+    Bar,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Star,
+    Plus,
+    Question
+}
+
+#[derive(Debug, Clone)]
+struct BnfToken {
+    symbol: BnfSymbol,
+    line: u32,
+    col: u32
+}
+
+struct BnfLexer<'a, 'b> {
+    input_stream: &'b mut InputStream<'a>,
+    // The line of the last token read; a `#` that starts a line begins a comment.
+    last_line: u32
+}
+
+impl<'a, 'b> BnfLexer<'a, 'b> {
+    fn error(&self, message: String) -> GrammarError {
+        GrammarError { message, line: self.input_stream.get_line(), col: self.input_stream.get_col() }
+    }
+
+    fn read_all(&mut self) -> Result<Vec<BnfToken>, GrammarError> {
+        let mut out: Vec<BnfToken> = Vec::new();
+        while let Some(tok) = self.read_next()? {
+            out.push(tok);
+        }
+        Ok(out)
+    }
+
+    fn read_next(&mut self) -> Result<Option<BnfToken>, GrammarError> {
+        while let Some(c) = self.input_stream.peek().copied() {
+            if c == '#' && self.input_stream.get_line() != self.last_line {
+                while self.input_stream.peek().is_some_and(|c| *c != '\n') { self.input_stream.next(); }
+                continue;
+            }
+            if !c.is_whitespace() { break; }
+            self.input_stream.next();
+        }
+        let (line, col) = (self.input_stream.get_line(), self.input_stream.get_col());
+        self.last_line = line;
+        let c = match self.input_stream.peek() {
+            Some(c) => *c,
+            None => return Ok(None)
+        };
+        let symbol = match c {
+            '<' if self.input_stream.peek_nth(1).is_some_and(|n| n.is_alphanumeric() || n == '_') => {
+                self.input_stream.next();
+                let mut name = String::new();
+                loop {
+                    match self.input_stream.next() {
+                        Some('>') => break,
+                        Some(ch) if ch.is_alphanumeric() || ch == '_' || ch == '-' => name.push(ch),
+                        _ => return Err(self.error(format!("unterminated nonterminal <{}", name)))
+                    }
+                }
+                BnfSymbol::NonTerminal(name)
+            },
+            ':' if self.input_stream.peek_nth(1) == Some(':') && self.input_stream.peek_nth(2) == Some('=') => {
+                for _ in 0..3 { self.input_stream.next(); }
+                BnfSymbol::Define
+            },
+            '\'' | '"' => BnfSymbol::Terminal(self.read_quoted()?),
+            '|' | '(' | ')' | '[' | ']' | '*' | '+' | '?' => {
+                self.input_stream.next();
+                match c {
+                    '|' => BnfSymbol::Bar,
+                    '(' => BnfSymbol::LParen,
+                    ')' => BnfSymbol::RParen,
+                    '[' => BnfSymbol::LBracket,
+                    ']' => BnfSymbol::RBracket,
+                    '*' => BnfSymbol::Star,
+                    '+' => BnfSymbol::Plus,
+                    _ => BnfSymbol::Question
+                }
+            },
+            _ => {
+                // Bare terminals such as the digits in `( 0 | 1 | 2 )*`.
+                let mut text = String::new();
+                while let Some(ch) = self.input_stream.peek() {
+                    if ch.is_whitespace() || "|()[]*+?'\"".contains(*ch) { break; }
+                    text.push(self.input_stream.next().unwrap());
+                }
+                BnfSymbol::Terminal(text)
+            }
+        };
+        Ok(Some(BnfToken { symbol, line, col }))
+    }
+
+    fn read_quoted(&mut self) -> Result<String, GrammarError> {
+        let quote = self.input_stream.next().unwrap();
+        let mut text = String::new();
+        loop {
+            match self.input_stream.next() {
+                Some(ch) if ch == quote => break,
+                Some('\\') => match self.input_stream.next() {
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some('r') => text.push('\r'),
+                    Some(ch) => text.push(ch),
+                    None => return Err(self.error(String::from("unterminated terminal")))
+                },
+                Some('\n') | None => return Err(self.error(String::from("unterminated terminal"))),
+                Some(ch) => text.push(ch)
+            }
+        }
+        if text.is_empty() {
+            return Err(self.error(String::from("empty terminal")));
+        }
+        Ok(text)
+    }
+}
+
+struct BnfParser {
+    tokens: Vec<BnfToken>,
+    pos: usize
+}
+
+impl BnfParser {
+    fn peek(&self) -> Option<&BnfSymbol> { self.tokens.get(self.pos).map(|t| &t.symbol) }
+    fn error(&self, message: String) -> GrammarError {
+        match self.tokens.get(self.pos).or_else(|| self.tokens.last()) {
+            Some(tok) => GrammarError { message, line: tok.line, col: tok.col },
+            None => GrammarError { message, line: 1, col: 1 }
+        }
+    }
+    // A nonterminal followed by `::=` starts the next rule rather than continuing this one.
+    fn at_rule_start(&self) -> bool {
+        matches!(self.peek(), Some(BnfSymbol::NonTerminal(_)))
+            && matches!(self.tokens.get(self.pos + 1).map(|t| &t.symbol), Some(BnfSymbol::Define))
+    }
+
+    fn read_grammar(&mut self) -> Result<Grammar, GrammarError> {
+        let mut rules: Vec<Rule> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        while self.peek().is_some() {
+            let line = self.tokens[self.pos].line;
+            let name = match self.peek() {
+                Some(BnfSymbol::NonTerminal(name)) if self.at_rule_start() => name.clone(),
+                _ => return Err(self.error(String::from("expected a rule of the form <name> ::= ...")))
+            };
+            self.pos += 2;
+            let expr = self.read_choice()?;
+            if self.peek().is_some() && !self.at_rule_start() {
+                return Err(self.error(format!("unexpected {:?} in rule <{}>", self.peek().unwrap(), name)));
+            }
+            if index.contains_key(&name) {
+                return Err(GrammarError { message: format!("rule <{}> is defined twice", name), line, col: 1 });
+            }
+            index.insert(name.clone(), rules.len());
+            rules.push(Rule { name, expr, line });
+        }
+        if rules.is_empty() {
+            return Err(self.error(String::from("grammar has no rules")));
+        }
+        Ok(Grammar { rules, index })
+    }
+
+    fn read_choice(&mut self) -> Result<Expr, GrammarError> {
+        let mut alternatives: Vec<Expr> = vec![self.read_sequence()?];
+        while self.peek() == Some(&BnfSymbol::Bar) {
+            self.pos += 1;
+            alternatives.push(self.read_sequence()?);
+        }
+        Ok(if alternatives.len() == 1 { alternatives.pop().unwrap() } else { Expr::Choice(alternatives) })
+    }
+
+    fn read_sequence(&mut self) -> Result<Expr, GrammarError> {
+        let mut items: Vec<Expr> = Vec::new();
+        loop {
+            if self.at_rule_start() { break; }
+            let atom = match self.peek() {
+                Some(BnfSymbol::NonTerminal(name)) => { let e = Expr::NonTerminal(name.clone()); self.pos += 1; e },
+                Some(BnfSymbol::Terminal(text)) => { let e = Expr::Terminal(text.clone()); self.pos += 1; e },
+                Some(BnfSymbol::LParen) => {
+                    self.pos += 1;
+                    let inner = self.read_choice()?;
+                    self.expect(BnfSymbol::RParen)?;
+                    inner
+                },
+                Some(BnfSymbol::LBracket) => {
+                    self.pos += 1;
+                    let inner = self.read_choice()?;
+                    self.expect(BnfSymbol::RBracket)?;
+                    Expr::Repeat(Box::new(inner), Repetition::Optional)
+                },
+                _ => break
+            };
+            items.push(self.read_postfix(atom));
+        }
+        match items.len() {
+            0 => Err(self.error(String::from("expected a terminal, nonterminal or group"))),
+            1 => Ok(items.pop().unwrap()),
+            _ => Ok(Expr::Sequence(items))
+        }
+    }
+
+    fn read_postfix(&mut self, mut atom: Expr) -> Expr {
+        loop {
+            let rep = match self.peek() {
+                Some(BnfSymbol::Star) => Repetition::ZeroOrMore,
+                Some(BnfSymbol::Plus) => Repetition::OneOrMore,
+                Some(BnfSymbol::Question) => Repetition::Optional,
+                _ => return atom
+            };
+            self.pos += 1;
+            atom = Expr::Repeat(Box::new(atom), rep);
+        }
+    }
+
+    fn expect(&mut self, symbol: BnfSymbol) -> Result<(), GrammarError> {
+        if self.peek() == Some(&symbol) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {:?}", symbol)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_rules_with_groups_repetition_and_quoted_terminals() {
+        let grammar = Grammar::from_string("<stmt> ::= <lit> ('+' | \"-\") <lit> [';']\n<lit> ::= ( 0 | 1 )+").unwrap();
+        assert_eq!(grammar.rules().len(), 2);
+        assert_eq!(grammar.start_rule().map(Rule::get_name), Some("stmt"));
+        assert_eq!(grammar.to_string(), "<stmt> ::= <lit> ('+' | '-') <lit> ';'?\n<lit> ::= ('0' | '1')+\n");
+        assert!(grammar.get_rule("lit").unwrap().is_lexical());
+        assert!(!grammar.get_rule("stmt").unwrap().is_lexical());
+        assert_eq!(grammar.get_rule("stmt").unwrap().get_expr().references(), vec!["lit", "lit"]);
+        assert_eq!(grammar.get_rule("lit").unwrap().get_line(), 2);
+    }
+
+    #[test]
+    fn escapes_in_terminals() {
+        let grammar = Grammar::from_string("<lf> ::= '\\n' | '\\''").unwrap();
+        assert_eq!(grammar.rules()[0].get_expr(), &Expr::Choice(vec![Expr::Terminal(String::from("\n")), Expr::Terminal(String::from("'"))]));
+    }
+
+    #[test]
+    fn comments_start_a_line() {
+        let grammar = Grammar::from_string("# the start rule\n<a> ::= '#' <b>\n  # and its operand\n<b> ::= ( # | 1 )").unwrap();
+        assert_eq!(grammar.to_string(), "<a> ::= '#' <b>\n<b> ::= '#' | '1'\n");
+    }
+
+    #[test]
+    fn extends_rules_and_fills_in_undefined_ones() {
+        let reference = Grammar::from_string("<a> ::= <b> ';'").unwrap();
+        let allowed = Grammar::from_string("<a> ::= <c>\n<b> ::= 'x'\n<c> ::= 'y'").unwrap();
+        let mut filled = reference.clone();
+        filled.define_missing(&allowed);
+        assert_eq!(filled.to_string(), "<a> ::= <b> ';'\n<b> ::= 'x'\n");
+        let mut extended = reference.clone();
+        extended.extend(&allowed);
+        assert_eq!(extended.to_string(), "<a> ::= <b> ';' | <c>\n<b> ::= 'x'\n<c> ::= 'y'\n");
+    }
+
+    #[test]
+    fn reports_malformed_grammars_with_positions() {
+        let error = |src: &str| Grammar::from_string(src).unwrap_err();
+        assert_eq!(error("").get_message(), "grammar has no rules");
+        assert_eq!(error("<a> ::= 'x'\n<a> ::= 'y'").get_message(), "rule <a> is defined twice");
+        assert_eq!(error("<a> ::= 'x").get_message(), "unterminated terminal");
+        assert_eq!(error("<a> ::= ''").get_message(), "empty terminal");
+        let unclosed = error("<a> ::= ('x'\n<b> ::= 'y'");
+        assert_eq!((unclosed.get_message(), unclosed.get_line(), unclosed.get_col()), ("expected RParen", 2, 1));
+        assert_eq!(error("'x' ::= 'y'").to_string(), "expected a rule of the form <name> ::= ... at [l: 1, c: 1]");
+    }
+}
@@ -0,0 +1,374 @@
+use std::collections::{HashMap, HashSet};
+
+use super::bnf::{Expr, Grammar, Repetition};
+use super::generic_parser::GenericParser;
+use super::validation::{self, GrammarIssue};
+use super::super::parser::input_stream::InputStream;
+use super::super::parser::lexicon::KeywordSymbol;
+use super::super::parser::parser::Parser;
+use super::super::parser::token_stream::TokenStream;
+
+// xorshift64*, so a given seed always checks the same sentences.
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn create(seed: u64) -> Rng {
+        Rng { state: seed.max(1) }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n.max(1) as u64) as usize
+    }
+}
+
+pub struct SentenceGenerator<'g> {
+    grammar: &'g Grammar,
+    rng: Rng,
+    max_depth: usize,
+    min_depth: HashMap<String, usize>
+}
+
+impl<'g> SentenceGenerator<'g> {
+    pub fn create(grammar: &'g Grammar, seed: u64, max_depth: usize) -> SentenceGenerator<'g> {
+        let min_depth = minimum_depths(grammar);
+        SentenceGenerator { grammar, rng: Rng::create(seed), max_depth, min_depth }
+    }
+
+    // A random sentence of the start rule as a list of token texts, or `None` when the
+    // grammar can't derive one (e.g. every alternative needs an undefined nonterminal).
+    pub fn generate(&mut self) -> Option<Vec<String>> {
+        let start = self.grammar.start_rule()?.get_name().to_string();
+        let mut out: Vec<String> = Vec::new();
+        if self.generate_expr(&Expr::NonTerminal(start), 0, &mut out) { Some(out) } else { None }
+    }
+
+    fn expr_depth(&self, expr: &Expr) -> Option<usize> {
+        expr_min_depth(expr, &self.min_depth)
+    }
+
+    fn generate_expr(&mut self, expr: &Expr, depth: usize, out: &mut Vec<String>) -> bool {
+        match expr {
+            Expr::Terminal(text) => { out.push(text.clone()); true },
+            Expr::NonTerminal(name) => {
+                // Rules without a finite derivation, like `<a> ::= <a> 'x'`, would expand forever.
+                let rule = match self.grammar.get_rule(name) {
+                    Some(rule) if self.min_depth.contains_key(name) => rule,
+                    _ => return false
+                };
+                if rule.is_lexical() {
+                    return match self.generate_spelling(rule.get_expr(), depth) {
+                        Some(text) => { out.push(text); true },
+                        None => false
+                    };
+                }
+                self.generate_expr(rule.get_expr(), depth + 1, out)
+            },
+            Expr::Sequence(items) => items.iter().all(|item| self.generate_expr(item, depth, out)),
+            Expr::Choice(items) => {
+                let viable: Vec<(&Expr, usize)> = items.iter()
+                    .filter_map(|item| self.expr_depth(item).map(|d| (item, d)))
+                    .collect();
+                if viable.is_empty() { return false; }
+                let pick = if depth >= self.max_depth {
+                    viable.iter().min_by_key(|(_, d)| *d).unwrap().0
+                } else {
+                    viable[self.rng.below(viable.len())].0
+                };
+                self.generate_expr(pick, depth, out)
+            },
+            Expr::Repeat(inner, rep) => {
+                let (min, max) = match rep {
+                    Repetition::ZeroOrMore => (0, 3),
+                    Repetition::OneOrMore => (1, 3),
+                    Repetition::Optional => (0, 1)
+                };
+                let count = if depth >= self.max_depth || self.expr_depth(inner).is_none() { min } else { min + self.rng.below(max - min + 1) };
+                (0..count).all(|_| self.generate_expr(inner, depth, out))
+            }
+        }
+    }
+
+    // Lexical rules spell one token. An empty spelling can't be a token and a reserved word
+    // lexes as a keyword, so retry, and past the depth limit take the shortest spelling there is.
+    fn generate_spelling(&mut self, expr: &Expr, depth: usize) -> Option<String> {
+        if depth < self.max_depth {
+            for _ in 0..16 {
+                let mut parts: Vec<String> = Vec::new();
+                if self.generate_expr(expr, depth, &mut parts) && is_spelling(&parts.concat()) {
+                    return Some(parts.concat());
+                }
+            }
+        }
+        shortest_spelling(expr, true).filter(|text| is_spelling(text))
+    }
+}
+
+fn is_spelling(text: &str) -> bool {
+    !text.is_empty() && KeywordSymbol::from_string(text) == KeywordSymbol::ILLEGAL
+}
+
+// The shortest text a lexical expression spells, optionally excluding the empty one.
+fn shortest_spelling(expr: &Expr, non_empty: bool) -> Option<String> {
+    match expr {
+        Expr::Terminal(text) => Some(text.clone()),
+        Expr::NonTerminal(_) => None,
+        Expr::Choice(items) => items.iter().filter_map(|item| shortest_spelling(item, non_empty)).min_by_key(String::len),
+        Expr::Repeat(inner, rep) if non_empty || *rep == Repetition::OneOrMore => shortest_spelling(inner, non_empty),
+        Expr::Repeat(_, _) => Some(String::new()),
+        Expr::Sequence(items) => {
+            let parts: Vec<String> = items.iter().map(|item| shortest_spelling(item, false)).collect::<Option<_>>()?;
+            if !non_empty || parts.iter().any(|p| !p.is_empty()) {
+                return Some(parts.concat());
+            }
+            // Every part can be empty: make the one with the shortest non-empty spelling non-empty.
+            items.iter().filter_map(|item| shortest_spelling(item, true)).min_by_key(String::len)
+        }
+    }
+}
+
+fn expr_min_depth(expr: &Expr, depths: &HashMap<String, usize>) -> Option<usize> {
+    match expr {
+        Expr::Terminal(_) => Some(0),
+        Expr::NonTerminal(name) => depths.get(name).map(|d| d + 1),
+        Expr::Sequence(items) => items.iter().try_fold(0, |acc, item| expr_min_depth(item, depths).map(|d| acc.max(d))),
+        Expr::Choice(items) => items.iter().filter_map(|item| expr_min_depth(item, depths)).min(),
+        Expr::Repeat(inner, Repetition::OneOrMore) => expr_min_depth(inner, depths),
+        Expr::Repeat(_, _) => Some(0)
+    }
+}
+
+// Shallowest derivation depth of every rule that can derive a sentence at all.
+fn minimum_depths(grammar: &Grammar) -> HashMap<String, usize> {
+    let mut depths: HashMap<String, usize> = HashMap::new();
+    loop {
+        let mut changed = false;
+        for rule in grammar.rules() {
+            if let Some(d) = expr_min_depth(rule.get_expr(), &depths) {
+                if depths.get(rule.get_name()).is_none_or(|old| d < *old) {
+                    depths.insert(rule.get_name().to_string(), d);
+                    changed = true;
+                }
+            }
+        }
+        if !changed { return depths; }
+    }
+}
+
+pub fn render(tokens: &[String]) -> String {
+    let mut out = String::new();
+    for tok in tokens {
+        if !out.is_empty() && !out.ends_with('\n') && tok != "\n" { out.push(' '); }
+        out.push_str(tok);
+    }
+    out
+}
+
+pub fn grammar_accepts(grammar: &Grammar, src: &str) -> Result<(), String> {
+    let mut is = InputStream::from_string(src);
+    let mut ts = TokenStream::create(&mut is);
+    GenericParser::create(grammar).parse(&mut ts).map(|_| ()).map_err(|e| e.to_string())
+}
+
+pub fn implementation_accepts(src: &str) -> Result<(), String> {
+    let mut is = InputStream::from_string(src);
+    let mut ts = TokenStream::create(&mut is);
+    Parser::create(&mut ts).parse_program().map(|_| ()).map_err(|e| e.to_string())
+}
+
+pub struct Divergence {
+    pub sentence: String,
+    pub reason: String
+}
+
+pub struct ConformanceReport {
+    pub issues: Vec<GrammarIssue>,
+    pub checked: usize,
+    pub ungenerated: usize,
+    // Sentences the grammar accepts but the parser rejects, and the other way round.
+    pub grammar_only: Vec<Divergence>,
+    pub implementation_only: Vec<Divergence>,
+    // Sentences only the parser accepts, but which the allowed extensions describe.
+    pub allowed: usize
+}
+
+impl ConformanceReport {
+    pub fn is_conformant(&self) -> bool {
+        self.issues.is_empty() && self.grammar_only.is_empty() && self.implementation_only.is_empty()
+    }
+}
+
+// Generates `samples` sentences from the grammar plus one random single-token mutation of each,
+// and runs every distinct sentence through both the grammar and the hand-written parser.
+// `allowed` lists what the parser accepts beyond the grammar (see `test.allow.bnf`): its rules
+// fill in nonterminals the grammar leaves undefined, and the alternatives it adds to the
+// grammar's own rules excuse sentences only the parser accepts. Sentences are generated from
+// the grammar alone.
+pub fn check(reference: &Grammar, allowed: Option<&Grammar>, samples: usize, seed: u64) -> ConformanceReport {
+    let mut grammar = reference.clone();
+    let mut extended = reference.clone();
+    if let Some(allowed) = allowed {
+        grammar.define_missing(allowed);
+        extended.extend(allowed);
+    }
+    let grammar = &grammar;
+    let mut generator = SentenceGenerator::create(grammar, seed, 8);
+    let mut rng = Rng::create(seed.wrapping_mul(31).wrapping_add(7));
+    let pool = token_pool(grammar, &mut generator);
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut report = ConformanceReport {
+        issues: validation::validate(grammar),
+        checked: 0,
+        ungenerated: 0,
+        grammar_only: Vec::new(),
+        implementation_only: Vec::new(),
+        allowed: 0
+    };
+    for _ in 0..samples {
+        let tokens = match generator.generate() {
+            Some(tokens) => tokens,
+            None => { report.ungenerated += 1; continue; }
+        };
+        let mutated = mutate(&tokens, &pool, &mut rng);
+        for sentence in [render(&tokens), render(&mutated)] {
+            if !seen.insert(sentence.clone()) { continue; }
+            report.checked += 1;
+            match (grammar_accepts(grammar, &sentence), implementation_accepts(&sentence)) {
+                (Ok(()), Err(reason)) => report.grammar_only.push(Divergence { sentence, reason }),
+                (Err(_), Ok(())) if allowed.is_some() && grammar_accepts(&extended, &sentence).is_ok() => report.allowed += 1,
+                (Err(reason), Ok(())) => report.implementation_only.push(Divergence { sentence, reason }),
+                _ => {}
+            }
+        }
+    }
+    report
+}
+
+fn token_pool(grammar: &Grammar, generator: &mut SentenceGenerator) -> Vec<String> {
+    let mut pool: Vec<String> = Vec::new();
+    for rule in grammar.rules() {
+        let mut out: Vec<String> = Vec::new();
+        if rule.is_lexical() && generator.generate_expr(&Expr::NonTerminal(rule.get_name().to_string()), 0, &mut out) {
+            pool.extend(out);
+        }
+        collect_terminals(rule.get_expr(), rule.is_lexical(), &mut pool);
+    }
+    pool.sort();
+    pool.dedup();
+    pool
+}
+
+fn collect_terminals(expr: &Expr, lexical: bool, out: &mut Vec<String>) {
+    match expr {
+        Expr::Terminal(text) if !lexical => out.push(text.clone()),
+        Expr::Terminal(_) | Expr::NonTerminal(_) => {},
+        Expr::Sequence(items) | Expr::Choice(items) => items.iter().for_each(|e| collect_terminals(e, lexical, out)),
+        Expr::Repeat(inner, _) => collect_terminals(inner, lexical, out)
+    }
+}
+
+fn mutate(tokens: &[String], pool: &[String], rng: &mut Rng) -> Vec<String> {
+    let mut out: Vec<String> = tokens.to_vec();
+    let at = rng.below(out.len() + 1);
+    match rng.below(4) {
+        0 if at < out.len() => { out.remove(at); },
+        1 if at < out.len() => { let tok = out[at].clone(); out.insert(at, tok); },
+        2 if at + 1 < out.len() => out.swap(at, at + 1),
+        _ if !pool.is_empty() => out.insert(at, pool[rng.below(pool.len())].clone()),
+        _ => {}
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grammar(src: &str) -> Grammar {
+        Grammar::from_string(src).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    #[test]
+    fn generated_sentences_are_in_the_grammar() {
+        let g = grammar("<program> ::= (<stmt> <lf>)*\n<stmt> ::= <lit> ('+' <lit>)*\n<lit> ::= (0 | 1 | 2)*\n<lf> ::= '\\n'");
+        let mut generator = SentenceGenerator::create(&g, 3, 4);
+        for _ in 0..50 {
+            let sentence = render(&generator.generate().expect("a sentence"));
+            assert_eq!(grammar_accepts(&g, &sentence), Ok(()), "{:?}", sentence);
+        }
+        let report = check(&g, None, 100, 3);
+        assert_eq!(report.ungenerated, 0);
+        assert!(report.issues.is_empty());
+    }
+
+    #[test]
+    fn identifiers_are_never_reserved_words() {
+        let g = grammar("<program> ::= <name>\n<name> ::= (o | r | i | f | n | d)+");
+        let mut generator = SentenceGenerator::create(&g, 1, 8);
+        for _ in 0..200 {
+            let name = generator.generate().expect("a sentence").concat();
+            assert_eq!(KeywordSymbol::from_string(&name), KeywordSymbol::ILLEGAL, "{}", name);
+        }
+    }
+
+    #[test]
+    fn reports_sentences_only_one_side_accepts() {
+        // Two literals in a row are in this grammar but not in the language.
+        let g = grammar("<program> ::= <lit> <lit> <lf>\n<lit> ::= (1 | 2)+\n<lf> ::= '\\n'");
+        let report = check(&g, None, 20, 1);
+        assert!(!report.is_conformant());
+        assert!(report.grammar_only.iter().any(|d| d.sentence.ends_with('\n') && d.sentence.split(' ').count() == 2));
+        assert!(report.implementation_only.iter().all(|d| grammar_accepts(&g, &d.sentence).is_err()));
+    }
+
+    #[test]
+    fn allowed_extensions_excuse_what_only_the_parser_accepts() {
+        let g = grammar("<program> ::= (<stmt> <lf>)*\n<stmt> ::= <lit> '+' <lit>\n<lit> ::= (1 | 2)+\n<lf> ::= '\\n'");
+        // Unary plus, any number of operands, and a last line without a line break.
+        let allowed = grammar("<program> ::= (<stmt> <lf>)* <stmt>\n<stmt> ::= '+'* <lit> ('+' '+'* <lit>)*");
+        let strict = check(&g, None, 50, 2);
+        assert!(!strict.implementation_only.is_empty());
+        let report = check(&g, Some(&allowed), 50, 2);
+        assert!(report.is_conformant());
+        assert_eq!(report.allowed, strict.implementation_only.len());
+    }
+
+    // test.allow.bnf lists the parser's deliberate extensions. What the check still reports are
+    // assignments used as values inside an operand or argument (`1 + x = 2`, `f x = 1`), which
+    // the parser takes as Ruby does and test.bnf doesn't.
+    #[test]
+    fn the_parser_conforms_to_test_bnf() {
+        let root = env!("CARGO_MANIFEST_DIR");
+        let reference = Grammar::from_file(&format!("{}/test.bnf", root)).unwrap();
+        let allowed = Grammar::from_file(&format!("{}/test.allow.bnf", root)).unwrap();
+        let mut reported = 0;
+        for seed in 1..4 {
+            let report = check(&reference, Some(&allowed), 500, seed);
+            let grammar_only: Vec<&str> = report.grammar_only.iter().map(|d| d.sentence.as_str()).collect();
+            assert!(report.issues.is_empty() && grammar_only.is_empty(), "{:?} {:?}", report.issues, grammar_only);
+            assert_eq!(report.ungenerated, 0);
+            let unexplained: Vec<&str> = report.implementation_only.iter().map(|d| d.sentence.as_str()).filter(|s| !s.contains('=')).collect();
+            assert!(unexplained.is_empty(), "{:?}", unexplained);
+            reported += report.implementation_only.len();
+        }
+        assert!(reported > 0);
+    }
+
+    #[test]
+    fn rules_without_a_finite_derivation_are_errors() {
+        let g = grammar("<program> ::= <a> | <b>\n<a> ::= <a> 'x'\n<b> ::= 'y' <b>");
+        let mut generator = SentenceGenerator::create(&g, 1, 8);
+        assert_eq!(generator.generate(), None);
+        let report = check(&g, None, 10, 1);
+        assert_eq!(report.ungenerated, 10);
+        assert!(report.issues.contains(&GrammarIssue::NonTerminating { rule: String::from("a"), line: 2 }));
+        assert!(report.issues.contains(&GrammarIssue::NonTerminating { rule: String::from("b"), line: 3 }));
+        assert!(report.issues.iter().any(|i| matches!(i, GrammarIssue::LeftRecursion { .. })));
+    }
+}
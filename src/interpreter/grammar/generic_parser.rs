@@ -0,0 +1,337 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+use super::bnf::{Expr, Grammar, Repetition};
+use super::super::parser::lexicon::{IdentifierSymbol, TokenKind};
+use super::super::parser::token_stream::TokenStream;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseTree {
+    Node { rule: String, children: Vec<ParseTree> },
+    Leaf { value: String, line: u32, col: u32 }
+}
+
+impl ParseTree {
+    pub fn get_rule(&self) -> Option<&str> {
+        match self {
+            ParseTree::Node { rule, .. } => Some(rule),
+            ParseTree::Leaf { .. } => None
+        }
+    }
+    pub fn get_children(&self) -> &[ParseTree] {
+        match self {
+            ParseTree::Node { children, .. } => children,
+            ParseTree::Leaf { .. } => &[]
+        }
+    }
+    fn write_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        match self {
+            ParseTree::Node { rule, children } => {
+                writeln!(f, "{}<{}>", "  ".repeat(depth), rule)?;
+                children.iter().try_for_each(|c| c.write_indented(f, depth + 1))
+            },
+            ParseTree::Leaf { value, .. } => writeln!(f, "{}'{}'", "  ".repeat(depth), value.escape_default())
+        }
+    }
+}
+
+impl fmt::Display for ParseTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_indented(f, 0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseFailure {
+    line: u32,
+    col: u32,
+    found: Option<String>,
+    expected: Vec<String>
+}
+
+impl ParseFailure {
+    pub fn get_line(&self) -> u32 { self.line }
+    pub fn get_col(&self) -> u32 { self.col }
+    pub fn get_found(&self) -> Option<&str> { self.found.as_deref() }
+    pub fn get_expected(&self) -> &[String] { &self.expected }
+}
+
+impl fmt::Display for ParseFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.found {
+            Some(found) => write!(f, "unexpected '{}'", found.escape_default())?,
+            None => write!(f, "unexpected end of input")?
+        }
+        if !self.expected.is_empty() {
+            write!(f, ", expected one of {}", self.expected.join(", "))?;
+        }
+        write!(f, " at [l: {}, c: {}]", self.line, self.col)
+    }
+}
+
+struct Lexeme {
+    value: String,
+    line: u32,
+    col: u32
+}
+
+// All the ways an expression can match from one position, one derivation per end position.
+type Matches = Vec<(usize, Vec<ParseTree>)>;
+
+// Parses a token stream against any `Grammar`. Alternatives are explored in parallel rather than
+// by ordered choice, so the parser accepts exactly the language the BNF describes; grammars must
+// be free of left recursion (see `validation::validate`).
+pub struct GenericParser<'g> {
+    grammar: &'g Grammar
+}
+
+impl<'g> GenericParser<'g> {
+    pub fn create(grammar: &'g Grammar) -> GenericParser<'g> {
+        GenericParser { grammar }
+    }
+
+    pub fn parse(&self, ts: &mut TokenStream) -> Result<ParseTree, ParseFailure> {
+        let mut tokens: Vec<Lexeme> = Vec::new();
+        while let Some(tok) = ts.read_next() {
+            if tok.get_data().get_kind() == TokenKind::Identifier(IdentifierSymbol::COMMENT) { continue; }
+            tokens.push(Lexeme { value: tok.get_data().get_value().clone(), line: tok.get_line(), col: tok.get_col() });
+        }
+        let start = match self.grammar.start_rule() {
+            Some(rule) => rule.get_name().to_string(),
+            None => return Err(ParseFailure { line: 1, col: 1, found: None, expected: Vec::new() })
+        };
+        let mut run = Run {
+            grammar: self.grammar,
+            tokens: &tokens,
+            memo: HashMap::new(),
+            active: HashSet::new(),
+            furthest: 0,
+            expected: BTreeSet::new()
+        };
+        let matches = run.match_expr(&Expr::NonTerminal(start), 0);
+        if let Some((_, mut trees)) = matches.iter().find(|(end, _)| *end == tokens.len()).cloned() {
+            return Ok(trees.pop().unwrap());
+        }
+        let (line, col, found) = match tokens.get(run.furthest) {
+            Some(tok) => (tok.line, tok.col, Some(tok.value.clone())),
+            None => tokens.last().map_or((1, 1, None), |tok| (tok.line, tok.col + tok.value.chars().count() as u32, None))
+        };
+        Err(ParseFailure { line, col, found, expected: run.expected.into_iter().collect() })
+    }
+}
+
+struct Run<'g, 't> {
+    grammar: &'g Grammar,
+    tokens: &'t [Lexeme],
+    memo: HashMap<(usize, usize), Rc<Matches>>,
+    active: HashSet<(usize, usize)>,
+    furthest: usize,
+    expected: BTreeSet<String>
+}
+
+impl<'g, 't> Run<'g, 't> {
+    fn fail(&mut self, pos: usize, expected: String) {
+        if pos > self.furthest {
+            self.furthest = pos;
+            self.expected.clear();
+        }
+        if pos == self.furthest {
+            self.expected.insert(expected);
+        }
+    }
+
+    fn match_expr(&mut self, expr: &Expr, pos: usize) -> Matches {
+        match expr {
+            Expr::Terminal(text) => match self.tokens.get(pos) {
+                Some(tok) if tok.value == *text => {
+                    vec![(pos + 1, vec![ParseTree::Leaf { value: tok.value.clone(), line: tok.line, col: tok.col }])]
+                },
+                _ => {
+                    self.fail(pos, format!("'{}'", text.escape_default()));
+                    Vec::new()
+                }
+            },
+            Expr::NonTerminal(name) => self.match_rule(name, pos),
+            Expr::Sequence(items) => {
+                let mut current: Matches = vec![(pos, Vec::new())];
+                for item in items {
+                    let mut next: Matches = Vec::new();
+                    for (at, trees) in current {
+                        for (end, more) in self.match_expr(item, at) {
+                            if next.iter().all(|(e, _)| *e != end) {
+                                let mut joined = trees.clone();
+                                joined.extend(more);
+                                next.push((end, joined));
+                            }
+                        }
+                    }
+                    if next.is_empty() { return next; }
+                    current = next;
+                }
+                current
+            },
+            Expr::Choice(items) => {
+                let mut out: Matches = Vec::new();
+                for item in items {
+                    for (end, trees) in self.match_expr(item, pos) {
+                        if out.iter().all(|(e, _)| *e != end) { out.push((end, trees)); }
+                    }
+                }
+                out
+            },
+            Expr::Repeat(inner, rep) => {
+                let mut out: Matches = Vec::new();
+                if *rep != Repetition::OneOrMore { out.push((pos, Vec::new())); }
+                let mut frontier: Matches = vec![(pos, Vec::new())];
+                let mut first = true;
+                while !frontier.is_empty() {
+                    let mut next: Matches = Vec::new();
+                    for (at, trees) in frontier {
+                        for (end, more) in self.match_expr(inner, at) {
+                            if end == at || out.iter().any(|(e, _)| *e == end) || next.iter().any(|(e, _)| *e == end) { continue; }
+                            let mut joined = trees.clone();
+                            joined.extend(more);
+                            next.push((end, joined));
+                        }
+                    }
+                    out.extend(next.iter().cloned());
+                    if *rep == Repetition::Optional || (first && next.is_empty()) { break; }
+                    first = false;
+                    frontier = next;
+                }
+                out
+            }
+        }
+    }
+
+    fn match_rule(&mut self, name: &str, pos: usize) -> Matches {
+        let index = match self.grammar.rule_index(name) {
+            Some(index) => index,
+            None => return Vec::new()
+        };
+        if let Some(found) = self.memo.get(&(index, pos)) {
+            return (**found).clone();
+        }
+        // Left recursion would loop forever; treat the re-entry as a failed branch.
+        if !self.active.insert((index, pos)) { return Vec::new(); }
+        let rule = &self.grammar.rules()[index];
+        let matches: Matches = if rule.is_lexical() {
+            match self.tokens.get(pos) {
+                Some(tok) if matches_text(rule.get_expr(), &tok.value.chars().collect::<Vec<char>>()) => {
+                    let leaf = ParseTree::Leaf { value: tok.value.clone(), line: tok.line, col: tok.col };
+                    vec![(pos + 1, vec![ParseTree::Node { rule: name.to_string(), children: vec![leaf] }])]
+                },
+                _ => {
+                    self.fail(pos, format!("<{}>", name));
+                    Vec::new()
+                }
+            }
+        } else {
+            self.match_expr(rule.get_expr(), pos).into_iter()
+                .map(|(end, children)| (end, vec![ParseTree::Node { rule: name.to_string(), children }]))
+                .collect()
+        };
+        self.active.remove(&(index, pos));
+        self.memo.insert((index, pos), Rc::new(matches.clone()));
+        matches
+    }
+}
+
+// Whether a lexical rule spells out exactly the given token text.
+fn matches_text(expr: &Expr, text: &[char]) -> bool {
+    !text.is_empty() && char_matches(expr, text, 0).contains(&text.len())
+}
+
+fn char_matches(expr: &Expr, text: &[char], pos: usize) -> Vec<usize> {
+    let mut out: Vec<usize> = Vec::new();
+    match expr {
+        Expr::Terminal(t) => {
+            let t: Vec<char> = t.chars().collect();
+            if text[pos..].starts_with(&t) { push_unique(&mut out, pos + t.len()); }
+        },
+        Expr::NonTerminal(_) => {},
+        Expr::Sequence(items) => {
+            let mut current: Vec<usize> = vec![pos];
+            for item in items {
+                let mut next: Vec<usize> = Vec::new();
+                for at in current {
+                    for end in char_matches(item, text, at) { push_unique(&mut next, end); }
+                }
+                current = next;
+            }
+            out = current;
+        },
+        Expr::Choice(items) => {
+            for item in items {
+                for end in char_matches(item, text, pos) { push_unique(&mut out, end); }
+            }
+        },
+        Expr::Repeat(inner, rep) => {
+            if *rep != Repetition::OneOrMore { push_unique(&mut out, pos); }
+            let mut frontier: Vec<usize> = vec![pos];
+            while !frontier.is_empty() {
+                let mut next: Vec<usize> = Vec::new();
+                for at in frontier {
+                    for end in char_matches(inner, text, at) {
+                        if end != at && !out.contains(&end) && !next.contains(&end) { next.push(end); }
+                    }
+                }
+                out.extend(next.iter().copied());
+                if *rep == Repetition::Optional { break; }
+                frontier = next;
+            }
+        }
+    }
+    out
+}
+
+fn push_unique(out: &mut Vec<usize>, end: usize) {
+    if !out.contains(&end) { out.push(end); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::parser::input_stream::InputStream;
+
+    fn parse(grammar: &str, src: &str) -> Result<ParseTree, ParseFailure> {
+        let grammar = Grammar::from_string(grammar).unwrap();
+        let mut is = InputStream::from_string(src);
+        let mut ts = TokenStream::create(&mut is);
+        GenericParser::create(&grammar).parse(&mut ts)
+    }
+
+    static ARITH: &str = "<stmt> ::= <lit> (<op> <lit>)* [';']\n<op> ::= '+' | '*'\n<lit> ::= (0 | 1 | 2 | 3)+";
+
+    #[test]
+    fn builds_a_tree_of_rules_and_tokens() {
+        let tree = parse(ARITH, "12 + 3;").unwrap();
+        assert_eq!(tree.get_rule(), Some("stmt"));
+        let rules: Vec<Option<&str>> = tree.get_children().iter().map(ParseTree::get_rule).collect();
+        assert_eq!(rules, vec![Some("lit"), Some("op"), Some("lit"), None]);
+        assert_eq!(tree.to_string(), "<stmt>\n  <lit>\n    '12'\n  <op>\n    '+'\n  <lit>\n    '3'\n  ';'\n");
+    }
+
+    #[test]
+    fn lexical_rules_match_whole_tokens() {
+        assert!(parse(ARITH, "4").is_err());
+        assert!(parse(ARITH, "1 + 2 * 3 # comments are skipped").is_ok());
+    }
+
+    #[test]
+    fn failures_point_at_the_furthest_token_reached() {
+        let failure = parse(ARITH, "1 + + 2").unwrap_err();
+        assert_eq!((failure.get_line(), failure.get_col(), failure.get_found()), (1, 5, Some("+")));
+        assert_eq!(failure.get_expected(), ["<lit>"]);
+        let failure = parse(ARITH, "1 +").unwrap_err();
+        assert_eq!(failure.to_string(), "unexpected end of input, expected one of <lit> at [l: 1, c: 4]");
+    }
+
+    #[test]
+    fn explores_alternatives_without_ordered_choice() {
+        // Ordered choice would commit to the shorter `'1'` and then fail on the `'2'`.
+        assert!(parse("<s> ::= <a> ';'\n<a> ::= <one> | <one> <two>\n<one> ::= 1\n<two> ::= 2", "1 2;").is_ok());
+        assert!(parse("<s> ::= <one>* <one>\n<one> ::= 1", "1 1 1").is_ok());
+    }
+}
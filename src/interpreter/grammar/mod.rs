@@ -0,0 +1,4 @@
+pub mod bnf;
+pub mod conformance;
+pub mod generic_parser;
+pub mod validation;
@@ -0,0 +1,245 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use super::bnf::{Expr, Grammar, Repetition};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GrammarIssue {
+    UndefinedNonTerminal { rule: String, name: String, line: u32 },
+    LeftRecursion { cycle: Vec<String>, line: u32 },
+    UnreachableRule { rule: String, line: u32 },
+    // Every expansion of the rule needs the rule itself again, so it derives no finite sentence.
+    NonTerminating { rule: String, line: u32 }
+}
+
+impl GrammarIssue {
+    pub fn get_line(&self) -> u32 {
+        match self {
+            GrammarIssue::UndefinedNonTerminal { line, .. }
+            | GrammarIssue::LeftRecursion { line, .. }
+            | GrammarIssue::UnreachableRule { line, .. }
+            | GrammarIssue::NonTerminating { line, .. } => *line
+        }
+    }
+}
+
+impl fmt::Display for GrammarIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrammarIssue::UndefinedNonTerminal { rule, name, line } =>
+                write!(f, "<{}> refers to undefined <{}> at [l: {}]", rule, name, line),
+            GrammarIssue::LeftRecursion { cycle, line } => {
+                let path: Vec<String> = cycle.iter().map(|n| format!("<{}>", n)).collect();
+                write!(f, "left recursion {} at [l: {}]", path.join(" -> "), line)
+            },
+            GrammarIssue::UnreachableRule { rule, line } =>
+                write!(f, "<{}> is unreachable from the start rule at [l: {}]", rule, line),
+            GrammarIssue::NonTerminating { rule, line } =>
+                write!(f, "<{}> never derives a finite sentence at [l: {}]", rule, line)
+        }
+    }
+}
+
+pub fn validate(grammar: &Grammar) -> Vec<GrammarIssue> {
+    let mut issues: Vec<GrammarIssue> = Vec::new();
+    issues.extend(undefined_nonterminals(grammar));
+    issues.extend(left_recursion(grammar));
+    issues.extend(unreachable_rules(grammar));
+    issues.extend(non_terminating_rules(grammar));
+    issues
+}
+
+fn undefined_nonterminals(grammar: &Grammar) -> Vec<GrammarIssue> {
+    let mut issues: Vec<GrammarIssue> = Vec::new();
+    for rule in grammar.rules() {
+        let mut seen: HashSet<&str> = HashSet::new();
+        for name in rule.get_expr().references() {
+            if grammar.get_rule(name).is_none() && seen.insert(name) {
+                issues.push(GrammarIssue::UndefinedNonTerminal {
+                    rule: rule.get_name().to_string(),
+                    name: name.to_string(),
+                    line: rule.get_line()
+                });
+            }
+        }
+    }
+    issues
+}
+
+fn unreachable_rules(grammar: &Grammar) -> Vec<GrammarIssue> {
+    let start = match grammar.start_rule() {
+        Some(rule) => rule.get_name(),
+        None => return Vec::new()
+    };
+    let mut reached: HashSet<&str> = HashSet::new();
+    let mut pending: Vec<&str> = vec![start];
+    while let Some(name) = pending.pop() {
+        if !reached.insert(name) { continue; }
+        if let Some(rule) = grammar.get_rule(name) {
+            pending.extend(rule.get_expr().references());
+        }
+    }
+    grammar.rules().iter()
+        .filter(|rule| !reached.contains(rule.get_name()))
+        .map(|rule| GrammarIssue::UnreachableRule { rule: rule.get_name().to_string(), line: rule.get_line() })
+        .collect()
+}
+
+// Rules referring to undefined nonterminals are already reported as such, and aren't repeated here.
+fn non_terminating_rules(grammar: &Grammar) -> Vec<GrammarIssue> {
+    let productive = productive_rules(grammar);
+    grammar.rules().iter()
+        .filter(|rule| !productive.contains(rule.get_name()))
+        .filter(|rule| rule.get_expr().references().iter().all(|name| grammar.get_rule(name).is_some()))
+        .map(|rule| GrammarIssue::NonTerminating { rule: rule.get_name().to_string(), line: rule.get_line() })
+        .collect()
+}
+
+// Rules that can derive some finite sentence, found by iterating to a fixed point.
+pub fn productive_rules(grammar: &Grammar) -> HashSet<String> {
+    let mut productive: HashSet<String> = HashSet::new();
+    loop {
+        let before = productive.len();
+        for rule in grammar.rules() {
+            if is_productive(rule.get_expr(), &productive) {
+                productive.insert(rule.get_name().to_string());
+            }
+        }
+        if productive.len() == before { return productive; }
+    }
+}
+
+fn is_productive(expr: &Expr, productive: &HashSet<String>) -> bool {
+    match expr {
+        Expr::Terminal(_) => true,
+        Expr::NonTerminal(name) => productive.contains(name),
+        Expr::Sequence(items) => items.iter().all(|e| is_productive(e, productive)),
+        Expr::Choice(items) => items.iter().any(|e| is_productive(e, productive)),
+        Expr::Repeat(inner, Repetition::OneOrMore) => is_productive(inner, productive),
+        Expr::Repeat(_, _) => true
+    }
+}
+
+// Rules that can derive the empty string, found by iterating to a fixed point.
+pub fn nullable_rules(grammar: &Grammar) -> HashSet<String> {
+    let mut nullable: HashSet<String> = HashSet::new();
+    loop {
+        let before = nullable.len();
+        for rule in grammar.rules() {
+            if is_nullable(rule.get_expr(), &nullable) {
+                nullable.insert(rule.get_name().to_string());
+            }
+        }
+        if nullable.len() == before { return nullable; }
+    }
+}
+
+fn is_nullable(expr: &Expr, nullable: &HashSet<String>) -> bool {
+    match expr {
+        Expr::Terminal(_) => false,
+        Expr::NonTerminal(name) => nullable.contains(name),
+        Expr::Sequence(items) => items.iter().all(|e| is_nullable(e, nullable)),
+        Expr::Choice(items) => items.iter().any(|e| is_nullable(e, nullable)),
+        Expr::Repeat(inner, Repetition::OneOrMore) => is_nullable(inner, nullable),
+        Expr::Repeat(_, _) => true
+    }
+}
+
+// Nonterminals that can be expanded without consuming any input first.
+fn left_corners<'a>(expr: &'a Expr, nullable: &HashSet<String>, out: &mut Vec<&'a str>) {
+    match expr {
+        Expr::Terminal(_) => {},
+        Expr::NonTerminal(name) => out.push(name),
+        Expr::Sequence(items) => {
+            for item in items {
+                left_corners(item, nullable, out);
+                if !is_nullable(item, nullable) { break; }
+            }
+        },
+        Expr::Choice(items) => items.iter().for_each(|e| left_corners(e, nullable, out)),
+        Expr::Repeat(inner, _) => left_corners(inner, nullable, out)
+    }
+}
+
+fn left_recursion(grammar: &Grammar) -> Vec<GrammarIssue> {
+    let nullable = nullable_rules(grammar);
+    let edges: Vec<Vec<usize>> = grammar.rules().iter().map(|rule| {
+        let mut corners: Vec<&str> = Vec::new();
+        left_corners(rule.get_expr(), &nullable, &mut corners);
+        corners.iter().filter_map(|name| grammar.rule_index(name)).collect()
+    }).collect();
+
+    let mut issues: Vec<GrammarIssue> = Vec::new();
+    let mut reported: HashSet<usize> = HashSet::new();
+    for start in 0..edges.len() {
+        if reported.contains(&start) { continue; }
+        if let Some(cycle) = find_cycle(start, &edges) {
+            reported.extend(cycle.iter().copied());
+            let rules = grammar.rules();
+            let mut names: Vec<String> = cycle.iter().map(|i| rules[*i].get_name().to_string()).collect();
+            names.push(rules[start].get_name().to_string());
+            issues.push(GrammarIssue::LeftRecursion { cycle: names, line: rules[start].get_line() });
+        }
+    }
+    issues
+}
+
+// Depth-first search for a path of left corners leading from `start` back to itself.
+fn find_cycle(start: usize, edges: &[Vec<usize>]) -> Option<Vec<usize>> {
+    let mut visited: HashSet<usize> = HashSet::new();
+    let mut path: Vec<usize> = vec![start];
+    let mut stack: Vec<(usize, usize)> = vec![(start, 0)];
+    while let Some((node, next_edge)) = stack.pop() {
+        if next_edge < edges[node].len() {
+            stack.push((node, next_edge + 1));
+            let target = edges[node][next_edge];
+            if target == start { return Some(path); }
+            if visited.insert(target) {
+                path.push(target);
+                stack.push((target, 0));
+            }
+        } else {
+            path.pop();
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues(src: &str) -> Vec<String> {
+        validate(&Grammar::from_string(src).unwrap()).iter().map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn well_formed_grammars_have_no_issues() {
+        assert!(issues("<s> ::= <e> ';'\n<e> ::= <t> ('+' <t>)*\n<t> ::= 1 | '(' <e> ')'").is_empty());
+    }
+
+    #[test]
+    fn reports_undefined_and_unreachable_rules() {
+        assert_eq!(issues("<s> ::= <a> <missing> <missing>\n<a> ::= 'x'\n<orphan> ::= 'y'"), vec![
+            "<s> refers to undefined <missing> at [l: 1]",
+            "<orphan> is unreachable from the start rule at [l: 3]"
+        ]);
+    }
+
+    #[test]
+    fn finds_direct_and_indirect_left_recursion() {
+        assert_eq!(issues("<e> ::= <e> '+' 1 | 1"), vec!["left recursion <e> -> <e> at [l: 1]"]);
+        assert_eq!(issues("<a> ::= <b> 'x' | 'x'\n<b> ::= <c> 'y'\n<c> ::= <a> 'z'"), vec!["left recursion <a> -> <b> -> <c> -> <a> at [l: 1]"]);
+        // Only a nullable prefix keeps the recursion on the left.
+        assert_eq!(issues("<a> ::= [';'] <a> 'x' | 'x'"), vec!["left recursion <a> -> <a> at [l: 1]"]);
+        assert!(issues("<a> ::= ';' <a> | 'x'").is_empty());
+    }
+
+    #[test]
+    fn nullable_rules_reach_a_fixed_point() {
+        let grammar = Grammar::from_string("<a> ::= <b> <c>\n<b> ::= 'x'*\n<c> ::= [<b>]\n<d> ::= <b> 'y'").unwrap();
+        let nullable = nullable_rules(&grammar);
+        assert!(nullable.contains("a") && nullable.contains("b") && nullable.contains("c"));
+        assert!(!nullable.contains("d"));
+    }
+}
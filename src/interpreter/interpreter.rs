@@ -0,0 +1,2747 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::builtins::{self, CoreClasses};
+use super::diagnostics::{Diagnostic, Span};
+use super::parser::ast::{Block, Body, Expr, ExprKind, Params, Program, RescueClause};
+use super::parser::input_stream::InputStream;
+use super::parser::lexicon::OperatorSymbol;
+use super::parser::parser::Parser;
+use super::parser::token_stream::TokenStream;
+use super::runtime::heap::{
+    Builtin, EnvData, ExceptionData, HashKey, HashTable, Heap, MethodBody, MethodEntry, ModuleData, Object, ObjectKind,
+    ProcBody, ProcData, UserMethod
+};
+use super::runtime::bigint::BigInt;
+use super::runtime::rational::Rational;
+use super::runtime::regex::Regex;
+use super::runtime::gc::{Collector, Roots};
+use super::runtime::io::Streams;
+use super::runtime::method_cache::MethodCache;
+use super::runtime::symbols::{Sym, SymbolTable};
+use super::runtime::value::{ObjRef, Value};
+use super::source_map::{FileId, SourceMap};
+use super::vm::bytecode::Chunk;
+use super::vm::inline_cache::InlineCache;
+
+pub type EvalResult = Result<Value, Unwind>;
+
+static DEFAULT_MAX_DEPTH: usize = 10_000;
+// Tree-walked calls, and blocks called from builtins, recurse on the Rust stack. Until told
+// otherwise the interpreter assumes it's on a thread as small as the ones `std::thread::spawn`
+// makes by default, so a runaway recursion raises `SystemStackError` instead of overflowing it.
+static DEFAULT_STACK_SIZE: usize = 2 << 20;
+// The part of the thread stack kept free: frames stop being pushed once the rest is used, so
+// what runs inside the last frame (builtins, nested expressions) still has room.
+static STACK_RESERVE: usize = 1 << 24;
+static SOURCE_EXTENSION: &str = "lang";
+// Longer backtraces are reported by their first and last this many frames, like Ruby does.
+static BACKTRACE_EDGE: usize = 8;
+
+// Non-local exits propagate through the evaluator as the error side of `EvalResult`.
+#[derive(Debug)]
+pub enum Unwind {
+    Raise(Value),
+    // Carries the tag of the loop or block call that the `break` leaves.
+    Break(Value, usize),
+    Next(Value),
+    Redo,
+    Retry,
+    // Carries the id of the frame the `return` returns from.
+    Return(Value, u64),
+    // A `return` ending in a call to a user method, which takes over the returning method's frame.
+    TailCall(Box<TailCall>)
+}
+
+pub struct TailCall {
+    pub method: Rc<UserMethod>,
+    pub owner: ObjRef,
+    pub recv: Value,
+    pub name: Sym,
+    pub args: Vec<Value>,
+    pub block: Option<Value>
+}
+
+impl fmt::Debug for TailCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TailCall({:?})", self.name)
+    }
+}
+
+impl Unwind {
+    // The value an exit carries out with it: the exception, or the `break`, `next` or `return` value.
+    pub fn get_value(&self) -> Option<Value> {
+        match self {
+            Unwind::Raise(v) | Unwind::Break(v, _) | Unwind::Next(v) | Unwind::Return(v, _) => Some(*v),
+            Unwind::Redo | Unwind::Retry | Unwind::TailCall(_) => None
+        }
+    }
+}
+
+pub struct MethodContext {
+    pub name: Sym,
+    pub owner: ObjRef,
+    pub args: Vec<Value>
+}
+
+// What a block captures from the code around it, and what a method or class body sets up.
+#[derive(Clone)]
+pub struct Context {
+    pub self_value: Value,
+    // Where `def` puts methods.
+    pub def_target: ObjRef,
+    // Lexically enclosing modules, outermost first, for constant lookup.
+    pub cref: Rc<Vec<ObjRef>>,
+    pub method: Option<Rc<MethodContext>>,
+    pub block: Option<Value>,
+    pub return_target: u64,
+    pub label: Rc<str>
+}
+
+// The code of a block proc, as written or compiled.
+enum BlockCode {
+    Tree(Rc<Block>),
+    Compiled(Rc<Chunk>)
+}
+
+pub struct Frame {
+    pub id: u64,
+    pub context: Context,
+    pub env: ObjRef,
+    pub file: FileId,
+    pub line: u32,
+    pub col: u32,
+    pub break_tag: Option<usize>,
+    // Whether a `return` ending in a call may hand this frame to the callee: only for method
+    // bodies walked as trees, and not while a `rescue` or `ensure` in it is waiting.
+    pub tail_calls: bool,
+    pub handlers: u32
+}
+
+pub struct Interpreter {
+    pub heap: Heap,
+    pub symbols: SymbolTable,
+    pub sources: SourceMap,
+    pub core: CoreClasses,
+    pub globals: HashMap<Sym, Value>,
+    pub main: Value,
+    // Syntax errors from files that failed to load, for the caller to render.
+    pub syntax_diagnostics: Vec<Diagnostic>,
+    // `at_exit` and `END` handlers, in registration order; they run last to first.
+    exit_handlers: Vec<Value>,
+    // The `END` blocks already registered, by position, so a loop registers each only once.
+    end_blocks: HashSet<(FileId, u32, u32)>,
+    // Whether Integer and Float operators are still the builtins, so `binary_op` can compute
+    // them without a method call. Cleared for good once a numeric class's methods change.
+    fast_numeric_ops: bool,
+    pub(crate) method_cache: MethodCache,
+    // Operator method names, interned on first use and indexed by operator.
+    operator_syms: Vec<Option<Sym>>,
+    // Whether programs are compiled to bytecode and run by the VM rather than walked as trees.
+    vm: bool,
+    frames: Vec<Frame>,
+    // How many frames deep calls may go before raising SystemStackError.
+    max_depth: usize,
+    // Where the Rust stack started and how much of it frames may use, once its size is known.
+    stack_base: usize,
+    stack_budget: usize,
+    // Intermediate values (receivers, evaluated arguments) kept reachable while more code runs.
+    // The VM also uses it as its operand stack.
+    pub(crate) stack: Vec<Value>,
+    gc: Collector,
+    // Values only Rust code holds: each object allocated and each value evaluated since the
+    // innermost statement (or VM instruction) began. Collections treat them as roots.
+    pub(crate) roots: Vec<Value>,
+    streams: Streams,
+    next_tag: usize,
+    next_frame_id: u64
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::create()
+    }
+}
+
+impl Interpreter {
+    pub fn create() -> Interpreter {
+        let mut heap = Heap::create();
+        let mut symbols = SymbolTable::create();
+        let core = builtins::bootstrap(&mut heap, &mut symbols);
+        let main = heap.alloc(Object::create(core.object, ObjectKind::Plain));
+        // The core classes and `main` are allocated before there's a collector to count them.
+        let mut gc = Collector::create();
+        gc.note_allocs(heap.live_count());
+        let mut interp = Interpreter {
+            heap,
+            symbols,
+            sources: SourceMap::create(),
+            core,
+            globals: HashMap::new(),
+            main: Value::Object(main),
+            syntax_diagnostics: Vec::new(),
+            exit_handlers: Vec::new(),
+            end_blocks: HashSet::new(),
+            fast_numeric_ops: false,
+            method_cache: MethodCache::create(),
+            operator_syms: vec![None; OperatorSymbol::ILLEGAL as usize + 1],
+            vm: true,
+            frames: Vec::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            stack_base: stack_address(),
+            stack_budget: stack_budget(DEFAULT_STACK_SIZE),
+            stack: Vec::new(),
+            gc,
+            roots: Vec::new(),
+            streams: Streams::create(),
+            next_tag: 0,
+            next_frame_id: 0
+        };
+        builtins::install(&mut interp);
+        interp.fast_numeric_ops = true;
+        let load_path = interp.new_array(Vec::new());
+        interp.set_global("$LOAD_PATH", load_path);
+        interp.set_global("$:", load_path);
+        let features = interp.new_array(Vec::new());
+        interp.set_global("$LOADED_FEATURES", features);
+        interp.set_global("$\"", features);
+        interp
+    }
+
+    // -- entry points --
+
+    // Method caches are on by default; turning them off is for measuring what they save.
+    pub fn set_method_caches(&mut self, enabled: bool) {
+        self.method_cache.set_enabled(enabled);
+    }
+
+    // The VM runs compiled calls without using the Rust stack, so it can go far deeper than the
+    // default. The tree walker recurses for each call, so how deep it gets is also bounded by
+    // the stack; see `set_stack_size`.
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    // Tells the interpreter the size of the thread stack it's running on, measured from here,
+    // in place of the small default. Calls raise `SystemStackError` when the stack is nearly used
+    // up, however deep `set_max_depth` allows, instead of overflowing it.
+    pub fn set_stack_size(&mut self, size: usize) {
+        self.stack_base = stack_address();
+        self.stack_budget = stack_budget(size);
+    }
+
+    // Programs are compiled and run on the VM, whose calls use the interpreter's own activation
+    // stack. Turning it off walks the tree instead, where each call recurses on the Rust stack.
+    pub fn set_vm(&mut self, enabled: bool) {
+        self.vm = enabled;
+    }
+
+    // Collects before every allocation: slow, but a value something forgot to root is freed
+    // while it's still in use instead of only once the heap happens to fill up.
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.gc.set_stress(stress);
+    }
+
+    // Output from `puts`, `print`, `p` and the like goes to `stdout`, `warn`'s to `stderr`, and
+    // `gets` reads from `stdin`, so embedders can capture or supply them.
+    pub fn set_stdout(&mut self, out: Box<dyn Write>) {
+        self.streams.stdout = out;
+    }
+    pub fn set_stderr(&mut self, err: Box<dyn Write>) {
+        self.streams.stderr = err;
+    }
+    pub fn set_stdin(&mut self, input: Box<dyn BufRead>) {
+        self.streams.stdin = input;
+    }
+
+    // Writes are flushed straight away so output interleaves with the other stream's.
+    pub fn write_out(&mut self, s: &str) {
+        let _ = self.streams.stdout.write_all(s.as_bytes());
+        let _ = self.streams.stdout.flush();
+    }
+    pub fn write_err(&mut self, s: &str) {
+        let _ = self.streams.stderr.write_all(s.as_bytes());
+        let _ = self.streams.stderr.flush();
+    }
+    // The next line of input with its line break, or None at the end of input.
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        match self.streams.stdin.read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line))
+        }
+    }
+
+    pub fn add_load_path(&mut self, dir: &Path) {
+        let entry = self.new_string(dir.to_string_lossy().into_owned());
+        if let Some(ObjectKind::Array(items)) = self.global("$LOAD_PATH").as_object().map(|r| &mut self.heap.get_mut(r).kind) {
+            items.push(entry);
+        }
+    }
+
+    pub fn eval_file(&mut self, path: &Path) -> EvalResult {
+        let file = match self.sources.load(path) {
+            Ok(file) => file,
+            Err(e) => return Err(self.error(self.core.load_error, format!("cannot load such file -- {} ({})", path.display(), e)))
+        };
+        let name = self.new_string(path.to_string_lossy().into_owned());
+        self.set_global("$0", name);
+        self.eval_loaded(file, "<main>")
+    }
+
+    pub fn eval_source(&mut self, name: &str, text: &str) -> EvalResult {
+        let file = self.sources.add_string(name, text);
+        self.eval_loaded(file, "<main>")
+    }
+
+    fn eval_loaded(&mut self, file: FileId, label: &str) -> EvalResult {
+        if self.vm {
+            let chunk = self.compile_file(file, label)?;
+            return self.run_program(chunk, file, label);
+        }
+        let program = self.parse_file(file)?;
+        self.eval_program(&program, file, label)
+    }
+
+    pub(crate) fn parse_file(&mut self, file: FileId) -> Result<Program, Unwind> {
+        let text = self.sources.get(file).map(|f| f.get_text().to_string()).unwrap_or_default();
+        let mut is = InputStream::for_file(file, &text);
+        let mut ts = TokenStream::create(&mut is);
+        let output = Parser::create(&mut ts).parse();
+        if !output.has_errors() {
+            return Ok(output.program);
+        }
+        let diagnostics = output.diagnostics();
+        let first = diagnostics.first().map(|d| d.to_string()).unwrap_or_default();
+        let message = format!("{}: {}", self.sources.get_name(file), first);
+        let span = diagnostics.first().and_then(|d| d.primary_span());
+        self.syntax_diagnostics.extend(diagnostics);
+        let exc = self.new_exception(self.core.syntax_error, &message);
+        if let Some(ObjectKind::Exception(data)) = exc.as_object().map(|r| &mut self.heap.get_mut(r).kind) {
+            data.span = span;
+        }
+        Err(Unwind::Raise(exc))
+    }
+
+    fn eval_program(&mut self, program: &Program, file: FileId, label: &str) -> EvalResult {
+        let env = self.new_env(None);
+        let id = self.push_program_frame(env, file, label)?;
+        let mut result = Ok(Value::Nil);
+        for statement in program.statements.iter() {
+            if let ExprKind::BeginBlock(body) = &statement.kind {
+                result = self.eval_body(body);
+                if result.is_err() { break; }
+            }
+        }
+        if result.is_ok() {
+            result = self.eval_body(&program.statements);
+        }
+        self.frames.pop();
+        match result {
+            Err(Unwind::Return(v, target)) if target == id => Ok(v),
+            other => other
+        }
+    }
+
+    // The frame a file's top level runs in; returns its id, which a top-level `return` targets.
+    pub(crate) fn push_program_frame(&mut self, env: ObjRef, file: FileId, label: &str) -> Result<u64, Unwind> {
+        let id = self.new_frame_id();
+        let context = Context {
+            self_value: self.main,
+            def_target: self.core.object,
+            cref: Rc::new(Vec::new()),
+            method: None,
+            block: None,
+            return_target: id,
+            label: Rc::from(label)
+        };
+        self.push_frame(id, context, env, file)?;
+        Ok(id)
+    }
+
+    pub fn at_exit(&mut self, handler: Value) {
+        self.exit_handlers.push(handler);
+    }
+
+    // Runs the exit handlers, last registered first, once the program has finished with
+    // `result`. Each handler runs even if an earlier one raised; the last exception raised
+    // replaces the program's result.
+    pub fn run_exit_handlers(&mut self, mut result: EvalResult) -> EvalResult {
+        while let Some(handler) = self.exit_handlers.pop() {
+            if let Err(Unwind::Raise(exc)) = result {
+                self.set_global("$!", exc);
+            }
+            if let Err(Unwind::Raise(exc)) = self.call_proc(handler, &[], None) {
+                result = Err(Unwind::Raise(exc));
+            }
+        }
+        result
+    }
+
+    // Describes an exception that escaped the program, pointing at where it was raised.
+    pub fn exception_diagnostic(&mut self, exc: Value) -> Diagnostic {
+        let class_name = self.module_name(self.real_class(exc));
+        let message = self.exception_message(exc);
+        let mut diagnostic = Diagnostic::error(format!("{} ({})", message, class_name));
+        if let Some(ObjectKind::Exception(data)) = exc.as_object().map(|r| &self.heap.get(r).kind) {
+            if let Some(span) = data.span {
+                diagnostic = diagnostic.with_label(span, "");
+            }
+            let frames: &[String] = data.backtrace.as_deref().map_or(&[], |b| b.get(1..).unwrap_or(&[]));
+            let elided = frames.len().saturating_sub(2 * BACKTRACE_EDGE);
+            for (i, line) in frames.iter().enumerate() {
+                if elided > 0 && i == BACKTRACE_EDGE {
+                    diagnostic = diagnostic.with_note(&format!("... {} levels...", elided));
+                }
+                if elided == 0 || i < BACKTRACE_EDGE || i >= BACKTRACE_EDGE + elided {
+                    diagnostic = diagnostic.with_note(&format!("from {}", line));
+                }
+            }
+        }
+        diagnostic
+    }
+
+    pub fn exception_message(&mut self, exc: Value) -> String {
+        match self.call(exc, "message", &[]) {
+            Ok(v) => self.string_value(v).unwrap_or_default(),
+            Err(_) => String::from("exception")
+        }
+    }
+
+    // -- frames --
+
+    pub(crate) fn new_frame_id(&mut self) -> u64 {
+        self.next_frame_id += 1;
+        self.next_frame_id
+    }
+    pub fn new_tag(&mut self) -> usize {
+        self.next_tag += 1;
+        self.next_tag
+    }
+
+    pub(crate) fn push_frame(&mut self, id: u64, context: Context, env: ObjRef, file: FileId) -> Result<(), Unwind> {
+        let stack_used = stack_address().abs_diff(self.stack_base);
+        if self.frames.len() >= self.max_depth || stack_used > self.stack_budget {
+            return Err(self.error(self.core.system_stack_error, String::from("stack level too deep")));
+        }
+        let (line, col) = self.frames.last().map_or((1, 1), |f| (f.line, f.col));
+        self.frames.push(Frame { id, context, env, file, line, col, break_tag: None, tail_calls: false, handlers: 0 });
+        Ok(())
+    }
+
+    pub fn frame(&self) -> &Frame {
+        self.frames.last().expect("no active frame")
+    }
+    pub(crate) fn frame_mut(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("no active frame")
+    }
+    pub(crate) fn pop_frame(&mut self) {
+        self.frames.pop();
+    }
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+    pub fn value_stack(&self) -> &[Value] {
+        &self.stack
+    }
+
+    fn set_position(&mut self, e: &Expr) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.line = e.line;
+            frame.col = e.col;
+        }
+    }
+
+    pub fn current_span(&self) -> Option<Span> {
+        self.frames.last().map(|f| Span::create(f.file, f.line, f.col, 1))
+    }
+
+    pub fn backtrace(&self) -> Vec<String> {
+        self.frames.iter().rev().map(|f| format!("{}:{}:in `{}'", self.sources.get_name(f.file), f.line, f.context.label)).collect()
+    }
+
+    // -- values --
+
+    pub fn sym(&mut self, name: &str) -> Sym {
+        self.symbols.intern(name)
+    }
+    pub fn sym_name(&self, sym: Sym) -> &str {
+        self.symbols.name(sym)
+    }
+
+    pub fn alloc(&mut self, class: ObjRef, kind: ObjectKind) -> ObjRef {
+        self.alloc_object(Object::create(class, kind))
+    }
+    // Collects first when it's due, keeping whatever the new object refers to.
+    pub fn alloc_object(&mut self, object: Object) -> ObjRef {
+        if self.gc.is_due(self.heap.live_count()) {
+            self.collect_garbage(Some(&object));
+        }
+        self.gc.note_alloc();
+        let r = self.heap.alloc(object);
+        self.roots.push(Value::Object(r));
+        r
+    }
+    pub fn new_string(&mut self, s: String) -> Value {
+        Value::Object(self.alloc(self.core.string, ObjectKind::Str(s)))
+    }
+    pub fn new_array(&mut self, items: Vec<Value>) -> Value {
+        Value::Object(self.alloc(self.core.array, ObjectKind::Array(items)))
+    }
+    pub fn new_hash(&mut self, table: HashTable) -> Value {
+        Value::Object(self.alloc(self.core.hash, ObjectKind::Hash(table)))
+    }
+    pub fn new_range(&mut self, start: Value, end: Value, exclusive: bool) -> Value {
+        Value::Object(self.alloc(self.core.range, ObjectKind::Range { start, end, exclusive }))
+    }
+    // An Integer, boxed only when it doesn't fit an `i64`.
+    pub fn new_integer(&mut self, n: BigInt) -> Value {
+        if let Some(small) = n.to_i64() {
+            return Value::Integer(small);
+        }
+        let r = self.alloc(self.core.integer, ObjectKind::BigInt(n));
+        self.heap.get_mut(r).frozen = true;
+        Value::Object(r)
+    }
+    pub fn new_rational(&mut self, q: Rational) -> Value {
+        let r = self.alloc(self.core.rational, ObjectKind::Rational(q));
+        self.heap.get_mut(r).frozen = true;
+        Value::Object(r)
+    }
+    pub fn new_complex(&mut self, real: Value, imaginary: Value) -> Value {
+        let r = self.alloc(self.core.complex, ObjectKind::Complex { real, imaginary });
+        self.heap.get_mut(r).frozen = true;
+        Value::Object(r)
+    }
+    pub fn new_regexp(&mut self, regex: Regex) -> Value {
+        Value::Object(self.alloc(self.core.regexp, ObjectKind::Regexp(Rc::new(regex))))
+    }
+    fn new_env(&mut self, parent: Option<ObjRef>) -> ObjRef {
+        self.alloc(self.core.object, ObjectKind::Env(EnvData { vars: HashMap::new(), slots: Vec::new(), parent }))
+    }
+    // An environment for compiled code, with `size` slots of nil.
+    pub(crate) fn new_slot_env(&mut self, parent: Option<ObjRef>, size: usize) -> ObjRef {
+        self.alloc(self.core.object, ObjectKind::Env(EnvData { vars: HashMap::new(), slots: vec![Value::Nil; size], parent }))
+    }
+    pub fn new_proc(&mut self, body: ProcBody, is_lambda: bool) -> Value {
+        let break_tag = self.new_tag();
+        Value::Object(self.alloc(self.core.proc_class, ObjectKind::Proc(ProcData { body, is_lambda, break_tag })))
+    }
+    fn new_block_proc(&mut self, block: &Rc<Block>, is_lambda: bool) -> Value {
+        let (env, context, file) = self.block_capture();
+        self.new_proc(ProcBody::Block { block: block.clone(), env, context, file }, is_lambda)
+    }
+    // What a block created in the current frame closes over.
+    pub(crate) fn block_capture(&self) -> (ObjRef, Context, FileId) {
+        let frame = self.frame();
+        let mut context = frame.context.clone();
+        if !context.label.starts_with("block in ") {
+            context.label = Rc::from(format!("block in {}", context.label));
+        }
+        (frame.env, context, frame.file)
+    }
+    pub fn new_module(&mut self, name: Option<String>, is_class: bool, superclass: Option<ObjRef>) -> ObjRef {
+        let class = if is_class { self.core.class } else { self.core.module };
+        self.alloc(class, ObjectKind::Module(ModuleData::create(name, is_class, superclass)))
+    }
+
+    pub fn string_value(&self, v: Value) -> Option<String> {
+        self.str_ref(v).map(str::to_string)
+    }
+    pub fn str_ref(&self, v: Value) -> Option<&str> {
+        match v.as_object().map(|r| &self.heap.get(r).kind) {
+            Some(ObjectKind::Str(s)) => Some(s),
+            _ => None
+        }
+    }
+    pub fn big_int(&self, v: Value) -> Option<&BigInt> {
+        match v.as_object().map(|r| &self.heap.get(r).kind) {
+            Some(ObjectKind::BigInt(n)) => Some(n),
+            _ => None
+        }
+    }
+    pub fn rational(&self, v: Value) -> Option<&Rational> {
+        match v.as_object().map(|r| &self.heap.get(r).kind) {
+            Some(ObjectKind::Rational(q)) => Some(q),
+            _ => None
+        }
+    }
+    // The real and imaginary parts of a Complex.
+    pub fn complex_parts(&self, v: Value) -> Option<(Value, Value)> {
+        match v.as_object().map(|r| &self.heap.get(r).kind) {
+            Some(ObjectKind::Complex { real, imaginary }) => Some((*real, *imaginary)),
+            _ => None
+        }
+    }
+    pub fn regex(&self, v: Value) -> Option<&Rc<Regex>> {
+        match v.as_object().map(|r| &self.heap.get(r).kind) {
+            Some(ObjectKind::Regexp(regex)) => Some(regex),
+            _ => None
+        }
+    }
+    pub fn array_items(&self, v: Value) -> Option<&Vec<Value>> {
+        match v.as_object().map(|r| &self.heap.get(r).kind) {
+            Some(ObjectKind::Array(items)) => Some(items),
+            _ => None
+        }
+    }
+    pub fn module_data(&self, r: ObjRef) -> Option<&ModuleData> {
+        match &self.heap.get(r).kind {
+            ObjectKind::Module(data) => Some(data),
+            _ => None
+        }
+    }
+    fn module_data_mut(&mut self, r: ObjRef) -> Option<&mut ModuleData> {
+        match &mut self.heap.get_mut(r).kind {
+            ObjectKind::Module(data) => Some(data),
+            _ => None
+        }
+    }
+    pub fn is_module(&self, v: Value) -> bool {
+        v.as_object().is_some_and(|r| self.module_data(r).is_some())
+    }
+
+    pub fn global(&mut self, name: &str) -> Value {
+        let sym = self.sym(name);
+        self.globals.get(&sym).copied().unwrap_or(Value::Nil)
+    }
+    pub fn set_global(&mut self, name: &str, v: Value) {
+        let sym = self.sym(name);
+        self.globals.insert(sym, v);
+    }
+
+    pub fn is_frozen(&self, v: Value) -> bool {
+        match v {
+            Value::Object(r) => self.heap.get(r).frozen,
+            _ => true
+        }
+    }
+    pub fn check_frozen(&mut self, v: Value) -> Result<(), Unwind> {
+        if let Value::Object(r) = v {
+            if self.heap.get(r).frozen {
+                let class = self.module_name(self.real_class(v));
+                let shown = self.inspect(v)?;
+                return Err(self.error(self.core.frozen_error, format!("can't modify frozen {}: {}", class, shown)));
+            }
+        }
+        Ok(())
+    }
+
+    // -- classes and modules --
+
+    // The class an object actually belongs to, skipping any singleton class.
+    pub fn real_class(&self, v: Value) -> ObjRef {
+        match v {
+            Value::Nil => self.core.nil_class,
+            Value::True => self.core.true_class,
+            Value::False => self.core.false_class,
+            Value::Integer(_) => self.core.integer,
+            Value::Float(_) => self.core.float,
+            Value::Symbol(_) => self.core.symbol,
+            Value::Object(r) => self.heap.get(r).class
+        }
+    }
+    // Where method lookup for `v` starts.
+    pub fn dispatch_class(&self, v: Value) -> ObjRef {
+        let r = match v {
+            Value::Object(r) => r,
+            _ => return self.real_class(v)
+        };
+        if let Some(singleton) = self.heap.get(r).singleton {
+            return singleton;
+        }
+        // A class without its own singleton class still inherits its superclasses' class methods.
+        if let Some(data) = self.module_data(r).filter(|d| d.is_class && d.attached.is_none()) {
+            let mut current = data.superclass;
+            while let Some(c) = current {
+                if let Some(singleton) = self.heap.get(c).singleton {
+                    return singleton;
+                }
+                current = self.module_data(c).and_then(|d| d.superclass);
+            }
+        }
+        self.heap.get(r).class
+    }
+
+    pub fn singleton_class(&mut self, v: Value) -> Result<ObjRef, Unwind> {
+        let r = match v {
+            Value::Object(r) => r,
+            Value::Nil | Value::True | Value::False => return Ok(self.real_class(v)),
+            _ => return Err(self.error(self.core.type_error, String::from("can't define singleton")))
+        };
+        if let Some(singleton) = self.heap.get(r).singleton {
+            return Ok(singleton);
+        }
+        // A class's singleton inherits from its superclass's singleton, so class methods are
+        // inherited too.
+        let superclass = match self.module_data(r) {
+            Some(data) if data.is_class => match data.superclass {
+                Some(sup) => self.singleton_class(Value::Object(sup))?,
+                None => self.core.class
+            },
+            _ => self.heap.get(r).class
+        };
+        let singleton = self.new_module(None, true, Some(superclass));
+        if let Some(data) = self.module_data_mut(singleton) {
+            data.attached = Some(v);
+        }
+        self.heap.get_mut(r).singleton = Some(singleton);
+        Ok(singleton)
+    }
+
+    // Method resolution order: the module, its included modules (latest first), then the same
+    // for each superclass.
+    pub fn ancestors(&self, module: ObjRef) -> Vec<ObjRef> {
+        let mut out: Vec<ObjRef> = Vec::new();
+        let mut current = Some(module);
+        while let Some(m) = current {
+            self.push_with_includes(m, &mut out);
+            current = self.module_data(m).and_then(|d| d.superclass);
+        }
+        out
+    }
+    fn push_with_includes(&self, m: ObjRef, out: &mut Vec<ObjRef>) {
+        if out.contains(&m) { return; }
+        out.push(m);
+        if let Some(data) = self.module_data(m) {
+            for included in data.includes.iter().rev() {
+                self.push_with_includes(*included, out);
+            }
+        }
+    }
+
+    pub fn find_method(&self, class: ObjRef, name: Sym) -> Option<(MethodEntry, ObjRef)> {
+        for m in self.ancestors(class) {
+            if let Some(entry) = self.module_data(m).and_then(|d| d.methods.get(&name)) {
+                return match entry {
+                    MethodEntry::Undefined => None,
+                    entry => Some((entry.clone(), m))
+                };
+            }
+        }
+        None
+    }
+    // `find_method` through the global method cache.
+    pub fn lookup_method(&mut self, class: ObjRef, name: Sym) -> Option<(MethodEntry, ObjRef)> {
+        if let Some(found) = self.method_cache.get(class, name) {
+            return found.clone();
+        }
+        let found = self.find_method(class, name);
+        self.method_cache.insert(class, name, found.clone());
+        found
+    }
+    pub fn responds_to(&self, v: Value, name: Sym) -> bool {
+        self.find_method(self.dispatch_class(v), name).is_some()
+    }
+
+    pub fn define_method(&mut self, module: ObjRef, name: Sym, entry: MethodEntry) {
+        if let Some(data) = self.module_data_mut(module) {
+            data.methods.insert(name, entry);
+        }
+        self.method_table_changed(module);
+    }
+    // Called whenever a module gains, loses or replaces a method, or includes another module.
+    pub fn method_table_changed(&mut self, module: ObjRef) {
+        self.method_cache.invalidate();
+        if [self.core.integer, self.core.float, self.core.numeric].contains(&module) {
+            self.fast_numeric_ops = false;
+        }
+    }
+    pub fn define_builtin(&mut self, module: ObjRef, name: &str, f: Builtin) {
+        let sym = self.sym(name);
+        self.define_method(module, sym, MethodEntry::Builtin(f));
+    }
+    pub fn define_singleton_builtin(&mut self, module: ObjRef, name: &str, f: Builtin) {
+        let singleton = self.singleton_class(Value::Object(module)).expect("modules have singleton classes");
+        self.define_builtin(singleton, name, f);
+    }
+
+    pub fn is_a(&self, v: Value, module: ObjRef) -> bool {
+        self.ancestors(self.dispatch_class(v)).contains(&module)
+    }
+    pub fn is_subclass(&self, class: ObjRef, ancestor: ObjRef) -> bool {
+        self.ancestors(class).contains(&ancestor)
+    }
+
+    pub fn module_name(&self, module: ObjRef) -> String {
+        match self.module_data(module) {
+            Some(ModuleData { name: Some(name), .. }) => name.clone(),
+            Some(ModuleData { attached: Some(v), .. }) => match v.as_object().and_then(|r| self.module_data(r)) {
+                Some(_) => format!("#<Class:{}>", self.module_name(v.as_object().unwrap())),
+                None => String::from("#<Class:#<Object>>")
+            },
+            Some(data) if data.is_class => String::from("#<Class>"),
+            _ => String::from("#<Module>")
+        }
+    }
+
+    pub fn set_constant(&mut self, module: ObjRef, name: Sym, v: Value) {
+        // Anonymous classes and modules take the name of the first constant they're assigned to.
+        if let Some(r) = v.as_object() {
+            let qualified = self.qualified_name(module, name);
+            if let Some(data) = self.module_data_mut(r) {
+                if data.name.is_none() && data.attached.is_none() {
+                    data.name = Some(qualified);
+                }
+            }
+        }
+        if let Some(data) = self.module_data_mut(module) {
+            data.constants.insert(name, v);
+        }
+    }
+    fn qualified_name(&self, container: ObjRef, name: Sym) -> String {
+        if container == self.core.object {
+            self.sym_name(name).to_string()
+        } else {
+            format!("{}::{}", self.module_name(container), self.sym_name(name))
+        }
+    }
+
+    pub fn lookup_constant_in(&self, module: ObjRef, name: Sym) -> Option<Value> {
+        self.ancestors(module).into_iter().find_map(|m| self.module_data(m).and_then(|d| d.constants.get(&name).copied()))
+    }
+
+    pub(crate) fn lookup_constant(&mut self, name: Sym) -> EvalResult {
+        let cref = self.frame().context.cref.clone();
+        for m in cref.iter().rev() {
+            if let Some(v) = self.module_data(*m).and_then(|d| d.constants.get(&name)) {
+                return Ok(*v);
+            }
+        }
+        let start = cref.last().copied().unwrap_or(self.core.object);
+        if let Some(v) = self.lookup_constant_in(start, name) {
+            return Ok(v);
+        }
+        if let Some(v) = self.lookup_constant_in(self.core.object, name) {
+            return Ok(v);
+        }
+        let message = format!("uninitialized constant {}", self.sym_name(name));
+        Err(self.error(self.core.name_error, message))
+    }
+
+    // -- errors --
+
+    pub fn new_exception(&mut self, class: ObjRef, message: &str) -> Value {
+        let message = self.new_string(message.to_string());
+        let data = ExceptionData { message, backtrace: None, span: None };
+        Value::Object(self.alloc(class, ObjectKind::Exception(data)))
+    }
+
+    // Records where an exception was raised, unless it already carries a location.
+    pub fn fill_backtrace(&mut self, exc: Value) {
+        let backtrace = self.backtrace();
+        let span = self.current_span();
+        if let Some(ObjectKind::Exception(data)) = exc.as_object().map(|r| &mut self.heap.get_mut(r).kind) {
+            if data.backtrace.is_none() {
+                data.backtrace = Some(backtrace);
+                data.span = data.span.or(span);
+            }
+        }
+    }
+
+    pub fn error(&mut self, class: ObjRef, message: String) -> Unwind {
+        let exc = self.new_exception(class, &message);
+        self.fill_backtrace(exc);
+        Unwind::Raise(exc)
+    }
+    pub fn type_error(&mut self, message: String) -> Unwind {
+        self.error(self.core.type_error, message)
+    }
+    pub fn argument_error(&mut self, message: String) -> Unwind {
+        self.error(self.core.argument_error, message)
+    }
+
+    pub fn check_args(&mut self, args: &[Value], min: usize, max: Option<usize>) -> Result<(), Unwind> {
+        let n = args.len();
+        if n >= min && max.is_none_or(|max| n <= max) {
+            return Ok(());
+        }
+        let expected = match max {
+            Some(max) if max == min => min.to_string(),
+            Some(max) => format!("{}..{}", min, max),
+            None => format!("{}+", min)
+        };
+        Err(self.argument_error(format!("wrong number of arguments (given {}, expected {})", n, expected)))
+    }
+
+    // -- calls --
+
+    pub fn call(&mut self, recv: Value, name: &str, args: &[Value]) -> EvalResult {
+        let sym = self.sym(name);
+        self.send(recv, sym, args, None)
+    }
+    pub fn call_with_block(&mut self, recv: Value, name: &str, args: &[Value], block: Value) -> EvalResult {
+        let sym = self.sym(name);
+        self.send(recv, sym, args, Some(block))
+    }
+
+    pub fn send(&mut self, recv: Value, name: Sym, args: &[Value], block: Option<Value>) -> EvalResult {
+        let class = self.dispatch_class(recv);
+        match self.lookup_method(class, name) {
+            Some((entry, owner)) => self.call_method(entry, owner, recv, name, args, block),
+            None => self.method_missing(recv, name, args, block, false)
+        }
+    }
+
+    pub(crate) fn method_missing(&mut self, recv: Value, name: Sym, args: &[Value], block: Option<Value>, vcall: bool) -> EvalResult {
+        let mm = self.sym("method_missing");
+        let class = self.dispatch_class(recv);
+        if let Some((entry @ MethodEntry::User(_), owner)) = self.lookup_method(class, mm) {
+            let mut full: Vec<Value> = vec![Value::Symbol(name)];
+            full.extend_from_slice(args);
+            return self.call_method(entry, owner, recv, mm, &full, block);
+        }
+        let description = self.describe_receiver(recv);
+        let name = self.sym_name(name).to_string();
+        if vcall {
+            Err(self.error(self.core.name_error, format!("undefined local variable or method `{}' for {}", name, description)))
+        } else {
+            Err(self.error(self.core.no_method_error, format!("undefined method `{}' for {}", name, description)))
+        }
+    }
+
+    fn describe_receiver(&mut self, recv: Value) -> String {
+        match recv {
+            Value::Nil => String::from("nil:NilClass"),
+            Value::True => String::from("true:TrueClass"),
+            Value::False => String::from("false:FalseClass"),
+            v if v == self.main => String::from("main:Object"),
+            v if self.is_module(v) => {
+                let kind = if self.module_data(v.as_object().unwrap()).is_some_and(|d| d.is_class) { "Class" } else { "Module" };
+                format!("{}:{}", self.module_name(v.as_object().unwrap()), kind)
+            },
+            v => format!("an instance of {}", self.module_name(self.real_class(v)))
+        }
+    }
+
+    pub fn call_method(&mut self, entry: MethodEntry, owner: ObjRef, recv: Value, name: Sym, args: &[Value], block: Option<Value>) -> EvalResult {
+        let result = self.dispatch(entry, owner, recv, name, args, block);
+        // A builtin may return something it just took out of the heap, like `pop` does.
+        self.keep(&result);
+        result
+    }
+
+    fn dispatch(&mut self, entry: MethodEntry, owner: ObjRef, recv: Value, name: Sym, args: &[Value], block: Option<Value>) -> EvalResult {
+        match entry {
+            MethodEntry::Builtin(f) => f(self, recv, args, block),
+            MethodEntry::AttrReader(ivar) => {
+                self.check_args(args, 0, Some(0))?;
+                Ok(self.get_ivar(recv, ivar))
+            },
+            MethodEntry::AttrWriter(ivar) => {
+                self.check_args(args, 1, Some(1))?;
+                self.set_ivar(recv, ivar, args[0])?;
+                Ok(args[0])
+            },
+            MethodEntry::User(method) => self.invoke_user(&method, owner, recv, name, args, block),
+            MethodEntry::Proc(body) => self.call_proc_as(body, Some((recv, owner)), args, block, true),
+            MethodEntry::Undefined => self.method_missing(recv, name, args, block, false)
+        }
+    }
+
+    fn invoke_user(&mut self, method: &Rc<UserMethod>, owner: ObjRef, recv: Value, name: Sym, args: &[Value], block: Option<Value>) -> EvalResult {
+        let mut result = self.run_user(method, owner, recv, name, args, block);
+        // Tail calls run here, after the method making them has returned, so a chain of them
+        // doesn't go any deeper.
+        let mark = self.root_mark();
+        while let Err(Unwind::TailCall(call)) = result {
+            self.release_roots(mark);
+            let TailCall { method, owner, recv, name, args, block } = *call;
+            self.root(recv);
+            self.roots.extend_from_slice(&args);
+            self.roots.extend(block);
+            result = self.run_user(&method, owner, recv, name, &args, block);
+        }
+        result
+    }
+
+    fn run_user(&mut self, method: &Rc<UserMethod>, owner: ObjRef, recv: Value, name: Sym, args: &[Value], block: Option<Value>) -> EvalResult {
+        let id = self.enter_method(method, owner, recv, name, args, block)?;
+        let result = match &method.body {
+            MethodBody::Tree { params, body } => {
+                self.frame_mut().tail_calls = true;
+                self.bind_params(params, args, block, true).and_then(|_| self.eval(body))
+            },
+            MethodBody::Compiled(chunk) => self.bind_chunk_params(chunk, args, block, true).and_then(|start| self.run_chunk(chunk, start))
+        };
+        self.frames.pop();
+        match result {
+            Err(Unwind::Return(v, target)) if target == id => Ok(v),
+            other => other
+        }
+    }
+
+    // Pushes a method's frame and returns its id, for its arguments to be bound in.
+    pub(crate) fn enter_method(&mut self, method: &UserMethod, owner: ObjRef, recv: Value, name: Sym, args: &[Value], block: Option<Value>) -> Result<u64, Unwind> {
+        let env = match &method.body {
+            MethodBody::Tree { .. } => self.new_env(None),
+            MethodBody::Compiled(chunk) => self.new_slot_env(None, chunk.locals.len())
+        };
+        let id = self.new_frame_id();
+        let context = Context {
+            self_value: recv,
+            def_target: owner,
+            cref: method.cref.clone(),
+            method: Some(Rc::new(MethodContext { name, owner, args: args.to_vec() })),
+            block,
+            return_target: id,
+            label: Rc::from(self.sym_name(name))
+        };
+        self.push_frame(id, context, env, method.file)?;
+        Ok(id)
+    }
+
+    // Binds arguments to parameters in the current frame's environment. Methods and lambdas are
+    // `strict` about arity; blocks pad with nil, drop extras and splat a lone array argument.
+    fn bind_params(&mut self, params: &Params, args: &[Value], block: Option<Value>, strict: bool) -> Result<(), Unwind> {
+        let required = params.required.len() + params.post.len();
+        let optional = params.optional.len();
+        let spread: Vec<Value>;
+        let args: &[Value] = if !strict && args.len() == 1 && (required + optional > 1 || (params.rest.is_some() && required > 0)) {
+            match self.array_items(args[0]) {
+                Some(items) => {
+                    spread = items.clone();
+                    &spread
+                },
+                None => args
+            }
+        } else {
+            args
+        };
+        if strict {
+            let max = if params.rest.is_some() { None } else { Some(required + optional) };
+            self.check_args(args, required, max)?;
+        }
+        let n = args.len();
+        let arg = |i: usize| args.get(i).copied().unwrap_or(Value::Nil);
+        for (i, name) in params.required.iter().enumerate() {
+            self.declare_local(name, arg(i));
+        }
+        let available_optional = n.saturating_sub(required).min(optional);
+        let mut next = params.required.len();
+        for (i, (name, default)) in params.optional.iter().enumerate() {
+            if i < available_optional {
+                self.declare_local(name, arg(next));
+                next += 1;
+            } else {
+                let v = self.eval(default)?;
+                self.declare_local(name, v);
+            }
+        }
+        let post_start = n.saturating_sub(params.post.len()).max(next);
+        if let Some(rest) = &params.rest {
+            let rest_items: Vec<Value> = if post_start > next { args[next..post_start].to_vec() } else { Vec::new() };
+            let v = self.new_array(rest_items);
+            self.declare_local(rest, v);
+        }
+        for (i, name) in params.post.iter().enumerate() {
+            self.declare_local(name, arg(post_start + i));
+        }
+        if let Some(name) = &params.block {
+            self.declare_local(name, block.unwrap_or(Value::Nil));
+        }
+        Ok(())
+    }
+
+    pub fn call_proc(&mut self, proc_value: Value, args: &[Value], block: Option<Value>) -> EvalResult {
+        self.call_proc_as(proc_value, None, args, block, false)
+    }
+
+    // Calls a proc, optionally with a different `self` and `def` target (for `instance_eval`,
+    // `class_eval` and `define_method`), and optionally with lambda semantics.
+    pub fn call_proc_as(&mut self, proc_value: Value, rebind: Option<(Value, ObjRef)>, args: &[Value], block: Option<Value>, as_lambda: bool) -> EvalResult {
+        let r = match proc_value.as_object() {
+            Some(r) => r,
+            None => return Err(self.type_error(String::from("not a proc")))
+        };
+        let (body, is_lambda, break_tag) = match &self.heap.get(r).kind {
+            ObjectKind::Proc(ProcData { body: ProcBody::Native(f), break_tag, .. }) => {
+                let (f, break_tag) = (f.clone(), *break_tag);
+                return f(self, break_tag, args);
+            },
+            ObjectKind::Proc(data) => match &data.body {
+                ProcBody::Block { block, env, context, file } => (Ok((BlockCode::Tree(block.clone()), *env, context.clone(), *file)), data.is_lambda, data.break_tag),
+                ProcBody::Compiled { chunk, env, context, file } => (Ok((BlockCode::Compiled(chunk.clone()), *env, context.clone(), *file)), data.is_lambda, data.break_tag),
+                ProcBody::Symbol(sym) => (Err((None, *sym)), data.is_lambda, data.break_tag),
+                ProcBody::Method { receiver, name } => (Err((Some(*receiver), *name)), data.is_lambda, data.break_tag),
+                ProcBody::Native(_) => unreachable!("native blocks are called above")
+            },
+            _ => return Err(self.type_error(String::from("not a proc")))
+        };
+        let (code, env, mut context, file) = match body {
+            Ok(parts) => parts,
+            Err((Some(receiver), name)) => return self.send(receiver, name, args, block),
+            Err((None, name)) => {
+                if args.is_empty() {
+                    return Err(self.argument_error(String::from("no receiver given")));
+                }
+                return self.send(args[0], name, &args[1..], block);
+            }
+        };
+        let is_lambda = is_lambda || as_lambda;
+        let id = self.new_frame_id();
+        if is_lambda {
+            context.return_target = id;
+        }
+        if let Some((self_value, def_target)) = rebind {
+            context.self_value = self_value;
+            context.def_target = def_target;
+        }
+        let env = match &code {
+            BlockCode::Tree(_) => self.new_env(Some(env)),
+            BlockCode::Compiled(chunk) => self.new_slot_env(Some(env), chunk.locals.len())
+        };
+        self.push_frame(id, context, env, file)?;
+        self.frame_mut().break_tag = Some(break_tag);
+        let result = match &code {
+            BlockCode::Tree(block_ast) => {
+                self.frame_mut().line = block_ast.line;
+                self.bind_params(&block_ast.params, args, block, is_lambda).and_then(|_| loop {
+                    match self.eval_body(&block_ast.body) {
+                        Err(Unwind::Redo) => continue,
+                        other => break other
+                    }
+                })
+            },
+            BlockCode::Compiled(chunk) => {
+                self.frame_mut().line = chunk.line;
+                self.bind_chunk_params(chunk, args, block, is_lambda).and_then(|start| {
+                    let mut result = self.run_chunk(chunk, start);
+                    while let Err(Unwind::Redo) = result {
+                        result = self.run_chunk(chunk, chunk.params.body_start());
+                    }
+                    result
+                })
+            }
+        };
+        self.frames.pop();
+        match result {
+            Err(Unwind::Next(v)) => Ok(v),
+            Err(Unwind::Return(v, target)) if is_lambda && target == id => Ok(v),
+            Err(Unwind::Break(v, tag)) if is_lambda && tag == break_tag => Ok(v),
+            other => other
+        }
+    }
+
+    // Calls a proc for what it does rather than what it returns, so loops like `each` don't keep
+    // every iteration's result rooted until they finish.
+    pub fn call_proc_for_effect(&mut self, proc_value: Value, args: &[Value]) -> Result<(), Unwind> {
+        let mark = self.root_mark();
+        let result = self.call_proc(proc_value, args, None);
+        self.release_roots(mark);
+        if result.is_err() {
+            self.keep(&result);
+        }
+        result.map(|_| ())
+    }
+
+    pub fn yield_block(&mut self, block: Option<Value>, args: &[Value]) -> EvalResult {
+        match block {
+            Some(b) => self.call_proc(b, args, None),
+            None => Err(self.error(self.core.local_jump_error, String::from("no block given (yield)")))
+        }
+    }
+
+    // Runs `f` with a block, turning a `break` out of that block into the call's result.
+    pub fn catch_break(&mut self, block: Option<Value>, result: EvalResult) -> EvalResult {
+        let tag = block.and_then(|b| b.as_object()).and_then(|r| match &self.heap.get(r).kind {
+            ObjectKind::Proc(data) => Some(data.break_tag),
+            _ => None
+        });
+        match result {
+            Err(Unwind::Break(v, t)) if Some(t) == tag => Ok(v),
+            other => other
+        }
+    }
+
+    // -- variables --
+
+    fn lookup_env(&self, name: Sym) -> Option<(ObjRef, Value)> {
+        let mut env = Some(self.frame().env);
+        while let Some(r) = env {
+            match &self.heap.get(r).kind {
+                ObjectKind::Env(data) => {
+                    if let Some(v) = data.vars.get(&name) {
+                        return Some((r, *v));
+                    }
+                    env = data.parent;
+                },
+                _ => break
+            }
+        }
+        None
+    }
+    pub fn get_local(&mut self, name: &str) -> Option<Value> {
+        let sym = self.sym(name);
+        self.lookup_env(sym).map(|(_, v)| v)
+    }
+    fn declare_local(&mut self, name: &str, v: Value) {
+        let sym = self.sym(name);
+        let env = self.frame().env;
+        if let ObjectKind::Env(data) = &mut self.heap.get_mut(env).kind {
+            data.vars.insert(sym, v);
+        }
+    }
+    pub fn set_local(&mut self, name: &str, v: Value) {
+        let sym = self.sym(name);
+        let env = self.lookup_env(sym).map_or(self.frame().env, |(r, _)| r);
+        if let ObjectKind::Env(data) = &mut self.heap.get_mut(env).kind {
+            data.vars.insert(sym, v);
+        }
+    }
+
+    pub fn get_ivar(&self, recv: Value, name: Sym) -> Value {
+        match recv {
+            Value::Object(r) => self.heap.get(r).ivars.get(&name).copied().unwrap_or(Value::Nil),
+            _ => Value::Nil
+        }
+    }
+    pub fn set_ivar(&mut self, recv: Value, name: Sym, v: Value) -> Result<(), Unwind> {
+        self.check_frozen(recv)?;
+        if let Value::Object(r) = recv {
+            self.heap.get_mut(r).ivars.insert(name, v);
+        }
+        Ok(())
+    }
+
+    // -- garbage collection --
+
+    pub fn collect_garbage(&mut self, allocating: Option<&Object>) -> usize {
+        let mut roots = Roots::default();
+        roots.add_object(self.core.object);
+        roots.add_value(self.main);
+        for v in self.globals.values().chain(self.exit_handlers.iter()).chain(self.stack.iter()).chain(self.roots.iter()) {
+            roots.add_value(*v);
+        }
+        for frame in self.frames.iter() {
+            roots.add_object(frame.env);
+            roots.add_context(&frame.context);
+        }
+        if let Some(object) = allocating {
+            roots.add_children(object);
+        }
+        let sweep = self.gc.collect(&mut self.heap, roots);
+        if sweep.modules_freed {
+            self.method_cache.invalidate();
+        }
+        sweep.freed
+    }
+    pub fn gc(&self) -> &Collector {
+        &self.gc
+    }
+
+    pub fn root_mark(&self) -> usize {
+        self.roots.len()
+    }
+    // Lets go of what was rooted since `mark`, once nothing Rust holds refers to it.
+    pub fn release_roots(&mut self, mark: usize) {
+        self.roots.truncate(mark);
+    }
+    pub fn root(&mut self, v: Value) {
+        if let Value::Object(_) = v {
+            self.roots.push(v);
+        }
+    }
+    // Roots the value a result carries, whether returned or unwinding.
+    pub fn keep(&mut self, result: &EvalResult) {
+        match result {
+            Ok(v) => self.root(*v),
+            Err(Unwind::TailCall(call)) => {
+                self.root(call.recv);
+                self.roots.extend_from_slice(&call.args);
+                self.roots.extend(call.block);
+            },
+            Err(u) => if let Some(v) = u.get_value() { self.root(v) }
+        }
+    }
+
+    // -- evaluation --
+
+    // What each statement left rooted is let go once it's done; only the last value stays.
+    pub fn eval_body(&mut self, body: &[Expr]) -> EvalResult {
+        let mark = self.root_mark();
+        let mut last = Value::Nil;
+        for e in body.iter() {
+            let result = self.eval(e);
+            self.release_roots(mark);
+            self.keep(&result);
+            last = result?;
+        }
+        Ok(last)
+    }
+
+    pub fn eval(&mut self, e: &Expr) -> EvalResult {
+        let result = self.eval_expr(e);
+        self.keep(&result);
+        result
+    }
+
+    // Only dispatches: anything needing locals of its own is in a method of its own, since a
+    // frame of this function sits on the Rust stack for every nested expression being evaluated.
+    fn eval_expr(&mut self, e: &Expr) -> EvalResult {
+        self.set_position(e);
+        match &e.kind {
+            ExprKind::Nil => Ok(Value::Nil),
+            ExprKind::True => Ok(Value::True),
+            ExprKind::False => Ok(Value::False),
+            ExprKind::SelfRef => Ok(self.frame().context.self_value),
+            ExprKind::File => self.file_name(),
+            ExprKind::Line => Ok(Value::Integer(i64::from(e.line))),
+            ExprKind::Encoding => Ok(builtins::encoding::utf_8(self)),
+            ExprKind::Integer(digits) => self.integer_literal(digits),
+            ExprKind::Float(digits) => Ok(Value::Float(digits.parse::<f64>().unwrap_or(f64::NAN))),
+            ExprKind::Rational(digits) => self.rational_literal(digits),
+            ExprKind::Imaginary(scaled) => self.eval_imaginary(scaled),
+            ExprKind::Str(s) => Ok(self.new_string(s.clone())),
+            ExprKind::FrozenStr(s) => Ok(self.frozen_string(s.clone())),
+            ExprKind::Regexp { source, flags } => self.regexp_literal(source, flags),
+            ExprKind::Symbol(s) => Ok(Value::Symbol(self.sym(s))),
+            ExprKind::Array(items) => self.eval_array(items),
+            ExprKind::Hash(pairs) => self.eval_hash(pairs),
+            ExprKind::LocalVar(name) => Ok(self.get_local(name).unwrap_or(Value::Nil)),
+            ExprKind::InstanceVar(name) => self.eval_ivar(name),
+            ExprKind::GlobalVar(name) => Ok(self.global(name)),
+            ExprKind::Constant { scope: None, name } => self.eval_constant(name),
+            ExprKind::Constant { scope: Some(scope), name } => self.eval_scoped_constant(scope, name),
+            ExprKind::Assign { target, value } => self.eval_assign(target, value),
+            ExprKind::OpAssign { target, op, value } => self.eval_op_assign(target, *op, value),
+            ExprKind::Binary { op, lhs, rhs, cache } => self.eval_binary(e, *op, lhs, rhs, cache),
+            ExprKind::Unary { op, operand } => self.eval_unary(e, *op, operand),
+            ExprKind::And { lhs, rhs } => self.eval_logical(lhs, rhs, true),
+            ExprKind::Or { lhs, rhs } => self.eval_logical(lhs, rhs, false),
+            ExprKind::Not(operand) => self.eval_not(operand),
+            ExprKind::Defined(operand) => self.eval_defined(operand),
+            ExprKind::Call { receiver, name, args, block, safe_nav, cache } => self.eval_call(e, receiver.as_deref(), name, args, block.as_ref(), *safe_nav, cache, false),
+            ExprKind::Splat(inner) | ExprKind::BlockPass(inner) => self.eval(inner),
+            ExprKind::Yield(args) => self.eval_yield(e, args),
+            ExprKind::Super { args, block } => self.eval_super(e, args.as_ref(), block.as_ref()),
+            ExprKind::Return(value) => self.eval_return(value.as_deref()),
+            ExprKind::Break(value) => self.eval_break(value.as_deref()),
+            ExprKind::Next(value) => self.eval_next(value.as_deref()),
+            ExprKind::Redo => Err(Unwind::Redo),
+            ExprKind::Retry => Err(Unwind::Retry),
+            ExprKind::If { cond, then_body, else_body } => self.eval_if(cond, then_body, else_body.as_ref()),
+            ExprKind::While { cond, body, until } => self.eval_while(cond, body, *until),
+            ExprKind::For { var, iter, body } => self.eval_for(var, iter, body),
+            ExprKind::Case { subject, whens, else_body } => self.eval_case(subject.as_deref(), whens, else_body.as_ref()),
+            ExprKind::Begin { body, rescues, else_body, ensure_body } => self.eval_begin(body, rescues, else_body.as_ref(), ensure_body.as_ref()),
+            ExprKind::Sequence(body) => self.eval_body(body),
+            ExprKind::Def { singleton, name, params, body } => self.eval_def(singleton.as_deref(), name, params, body),
+            ExprKind::Class { path, superclass, body } => self.eval_class(path, superclass.as_deref(), body),
+            ExprKind::Module { path, body } => self.eval_module(path, body),
+            ExprKind::Lambda(block) => Ok(self.new_block_proc(block, true)),
+            // Run ahead of the rest of the program by `eval_program`.
+            ExprKind::BeginBlock(_) => Ok(Value::Nil),
+            ExprKind::EndBlock(block) => self.eval_end_block(block),
+            ExprKind::Alias { new_name, old_name } => self.eval_alias(new_name, old_name),
+            ExprKind::Undef(names) => self.eval_undef(names),
+            ExprKind::Error => Err(self.error(self.core.syntax_error, String::from("invalid syntax")))
+        }
+    }
+
+    fn file_name(&mut self) -> EvalResult {
+        let name = self.sources.get_name(self.frame().file).to_string();
+        Ok(self.new_string(name))
+    }
+
+    fn eval_imaginary(&mut self, scaled: &Expr) -> EvalResult {
+        let v = self.eval(scaled)?;
+        Ok(self.new_complex(Value::Integer(0), v))
+    }
+
+    fn eval_array(&mut self, items: &[Expr]) -> EvalResult {
+        let base = self.stack.len();
+        let result = self.eval_args(items);
+        let values = self.stack.split_off(base);
+        result?;
+        Ok(self.new_array(values))
+    }
+
+    fn eval_ivar(&mut self, name: &str) -> EvalResult {
+        let sym = self.sym(name);
+        Ok(self.get_ivar(self.frame().context.self_value, sym))
+    }
+
+    fn eval_constant(&mut self, name: &str) -> EvalResult {
+        let sym = self.sym(name);
+        self.lookup_constant(sym)
+    }
+
+    fn eval_scoped_constant(&mut self, scope: &Expr, name: &str) -> EvalResult {
+        let module = self.eval_module_ref(scope)?;
+        let sym = self.sym(name);
+        match self.lookup_constant_in(module, sym) {
+            Some(v) => Ok(v),
+            None => {
+                let message = format!("uninitialized constant {}::{}", self.module_name(module), name);
+                Err(self.error(self.core.name_error, message))
+            }
+        }
+    }
+
+    fn eval_assign(&mut self, target: &Expr, value: &Expr) -> EvalResult {
+        let v = self.eval(value)?;
+        self.assign(target, v)
+    }
+
+    fn eval_binary(&mut self, e: &Expr, op: OperatorSymbol, lhs: &Expr, rhs: &Expr, cache: &InlineCache) -> EvalResult {
+        let l = self.eval(lhs)?;
+        self.stack.push(l);
+        let r = self.eval(rhs);
+        self.stack.pop();
+        let r = r?;
+        self.set_position(e);
+        if let Some(v) = self.fast_binary_op(op, l, r) {
+            return Ok(v);
+        }
+        if matches!(op, OperatorSymbol::RANGE | OperatorSymbol::EXCL_RANGE | OperatorSymbol::NOT_MATCH) {
+            return self.binary_op(op, l, r);
+        }
+        // Otherwise a send of the operator method, found through this node's cache as a call's is.
+        let name = self.operator_sym(op);
+        match self.lookup_at_site(cache, l, name) {
+            Some((entry, owner)) => self.call_method(entry, owner, l, name, &[r], None),
+            None => self.method_missing(l, name, &[r], None, false)
+        }
+    }
+
+    fn eval_unary(&mut self, e: &Expr, op: OperatorSymbol, operand: &Expr) -> EvalResult {
+        let v = self.eval(operand)?;
+        self.set_position(e);
+        self.unary_op(op, v)
+    }
+
+    // `&&` when `and` is set, `||` otherwise.
+    fn eval_logical(&mut self, lhs: &Expr, rhs: &Expr, and: bool) -> EvalResult {
+        let l = self.eval(lhs)?;
+        if l.is_truthy() == and { self.eval(rhs) } else { Ok(l) }
+    }
+
+    fn eval_not(&mut self, operand: &Expr) -> EvalResult {
+        match self.eval(operand)? {
+            v @ Value::Object(_) => self.call(v, "!", &[]),
+            v => Ok(Value::from_bool(!v.is_truthy()))
+        }
+    }
+
+    fn eval_defined(&mut self, operand: &Expr) -> EvalResult {
+        match self.defined(operand)? {
+            Some(description) => Ok(self.new_string(description.to_string())),
+            None => Ok(Value::Nil)
+        }
+    }
+
+    fn eval_yield(&mut self, e: &Expr, args: &[Expr]) -> EvalResult {
+        let base = self.stack.len();
+        let result = self.eval_args(args);
+        let values = self.stack.split_off(base);
+        result?;
+        self.set_position(e);
+        let block = self.frame().context.block;
+        self.yield_block(block, &values)
+    }
+
+    fn eval_return(&mut self, value: Option<&Expr>) -> EvalResult {
+        let frame = self.frame();
+        let v = match value {
+            Some(call @ Expr { kind: ExprKind::Call { receiver, name, args, block: None, safe_nav, cache }, .. }) if frame.tail_calls && frame.handlers == 0 => {
+                self.eval_call(call, receiver.as_deref(), name, args, None, *safe_nav, cache, true)?
+            },
+            value => self.eval_optional(value)?
+        };
+        Err(Unwind::Return(v, self.frame().context.return_target))
+    }
+
+    fn eval_break(&mut self, value: Option<&Expr>) -> EvalResult {
+        let v = self.eval_optional(value)?;
+        match self.frame().break_tag {
+            Some(tag) => Err(Unwind::Break(v, tag)),
+            None => Err(self.error(self.core.local_jump_error, String::from("break from proc-closure")))
+        }
+    }
+
+    fn eval_next(&mut self, value: Option<&Expr>) -> EvalResult {
+        Err(Unwind::Next(self.eval_optional(value)?))
+    }
+
+    fn eval_if(&mut self, cond: &Expr, then_body: &Body, else_body: Option<&Body>) -> EvalResult {
+        if self.eval(cond)?.is_truthy() {
+            self.eval_body(then_body)
+        } else {
+            match else_body {
+                Some(body) => self.eval_body(body),
+                None => Ok(Value::Nil)
+            }
+        }
+    }
+
+    fn eval_def(&mut self, singleton: Option<&Expr>, name: &str, params: &Params, body: &Rc<Expr>) -> EvalResult {
+        let target = match singleton {
+            Some(on) => {
+                let v = self.eval(on)?;
+                self.singleton_class(v)?
+            },
+            None => self.frame().context.def_target
+        };
+        let sym = self.sym(name);
+        let method = UserMethod {
+            name: sym,
+            body: MethodBody::Tree { params: params.clone(), body: body.clone() },
+            owner: target,
+            file: self.frame().file,
+            cref: self.frame().context.cref.clone()
+        };
+        self.define_method(target, sym, MethodEntry::User(Rc::new(method)));
+        Ok(Value::Symbol(sym))
+    }
+
+    fn eval_end_block(&mut self, block: &Rc<Block>) -> EvalResult {
+        if self.first_reached(block.line, block.col) {
+            let handler = self.new_block_proc(block, false);
+            self.at_exit(handler);
+        }
+        Ok(Value::Nil)
+    }
+
+    fn eval_alias(&mut self, new_name: &str, old_name: &str) -> EvalResult {
+        let (new_sym, old_sym) = (self.sym(new_name), self.sym(old_name));
+        self.alias_method(new_sym, old_sym)?;
+        Ok(Value::Nil)
+    }
+
+    fn eval_undef(&mut self, names: &[String]) -> EvalResult {
+        for name in names.iter() {
+            let sym = self.sym(name);
+            self.undef_method(sym);
+        }
+        Ok(Value::Nil)
+    }
+
+    // Whether this is the first time the `END` block at `line` and `col` of the current file is
+    // reached; each registers its handler only once.
+    pub(crate) fn first_reached(&mut self, line: u32, col: u32) -> bool {
+        let file = self.frame().file;
+        self.end_blocks.insert((file, line, col))
+    }
+
+    pub(crate) fn alias_method(&mut self, new_name: Sym, old_name: Sym) -> Result<(), Unwind> {
+        let target = self.frame().context.def_target;
+        match self.find_method(target, old_name) {
+            Some((entry, _)) => {
+                self.define_method(target, new_name, entry);
+                Ok(())
+            },
+            None => {
+                let message = format!("undefined method `{}' for class `{}'", self.sym_name(old_name), self.module_name(target));
+                Err(self.error(self.core.name_error, message))
+            }
+        }
+    }
+
+    pub(crate) fn undef_method(&mut self, name: Sym) {
+        let target = self.frame().context.def_target;
+        self.define_method(target, name, MethodEntry::Undefined);
+    }
+
+    fn eval_optional(&mut self, e: Option<&Expr>) -> EvalResult {
+        match e {
+            Some(e) => self.eval(e),
+            None => Ok(Value::Nil)
+        }
+    }
+
+    pub(crate) fn integer_literal(&mut self, digits: &str) -> EvalResult {
+        match builtins::numeric::parse_integer(self, digits) {
+            Some(n) => Ok(n),
+            None => Err(self.error(self.core.syntax_error, format!("invalid integer literal {}", digits)))
+        }
+    }
+
+    pub(crate) fn rational_literal(&mut self, digits: &str) -> EvalResult {
+        match Rational::parse(digits) {
+            Some(q) => Ok(self.new_rational(q)),
+            None => Err(self.error(self.core.syntax_error, format!("invalid rational literal {}r", digits)))
+        }
+    }
+
+    // `/source/flags`; literals are frozen.
+    pub(crate) fn regexp_literal(&mut self, source: &str, flags: &str) -> EvalResult {
+        match Regex::create(source, builtins::regexp::parse_options(flags)) {
+            Ok(regex) => {
+                let r = self.alloc(self.core.regexp, ObjectKind::Regexp(Rc::new(regex)));
+                self.heap.get_mut(r).frozen = true;
+                Ok(Value::Object(r))
+            },
+            Err(message) => Err(self.error(self.core.syntax_error, format!("{}: /{}/", message, source)))
+        }
+    }
+
+    // A string literal in a file with `# frozen_string_literal: true`.
+    pub(crate) fn frozen_string(&mut self, s: String) -> Value {
+        let r = self.alloc(self.core.string, ObjectKind::Str(s));
+        self.heap.get_mut(r).frozen = true;
+        Value::Object(r)
+    }
+
+    // Evaluates arguments onto the value stack, expanding splats. Returns the `&block` argument.
+    fn eval_args(&mut self, args: &[Expr]) -> Result<Option<Value>, Unwind> {
+        let mut block: Option<Value> = None;
+        for arg in args.iter() {
+            match &arg.kind {
+                ExprKind::Splat(inner) => {
+                    let v = self.eval(inner)?;
+                    match self.array_items(v) {
+                        Some(items) => {
+                            let items = items.clone();
+                            self.stack.extend(items);
+                        },
+                        None if v.is_nil() => {},
+                        None => self.stack.push(v)
+                    }
+                },
+                ExprKind::BlockPass(inner) => {
+                    let v = self.eval(inner)?;
+                    block = match v {
+                        Value::Nil => None,
+                        Value::Symbol(sym) => Some(self.new_proc(ProcBody::Symbol(sym), false)),
+                        v if self.is_proc(v) => Some(v),
+                        v => Some(self.call(v, "to_proc", &[])?)
+                    };
+                    if let Some(b) = block {
+                        self.stack.push(b);
+                    }
+                },
+                _ => {
+                    let v = self.eval(arg)?;
+                    self.stack.push(v);
+                }
+            }
+        }
+        Ok(block)
+    }
+
+    pub fn is_proc(&self, v: Value) -> bool {
+        matches!(v.as_object().map(|r| &self.heap.get(r).kind), Some(ObjectKind::Proc(_)))
+    }
+
+    fn eval_hash(&mut self, pairs: &[(Expr, Expr)]) -> EvalResult {
+        let base = self.stack.len();
+        let result = (|| -> Result<(), Unwind> {
+            for (k, v) in pairs.iter() {
+                let k = self.eval(k)?;
+                self.stack.push(k);
+                let v = self.eval(v)?;
+                self.stack.push(v);
+            }
+            Ok(())
+        })();
+        let values = self.stack.split_off(base);
+        result?;
+        Ok(self.hash_from_pairs(&values))
+    }
+
+    // A hash from alternating keys and values.
+    pub(crate) fn hash_from_pairs(&mut self, values: &[Value]) -> Value {
+        let mut table = HashTable::create();
+        for pair in values.chunks(2) {
+            let key = self.hash_key(pair[0]);
+            let k = self.hash_key_value(pair[0]);
+            table.insert(key, k, pair[1]);
+        }
+        self.new_hash(table)
+    }
+
+    // String keys are copied and frozen so later changes to the original don't move the entry.
+    pub fn hash_key_value(&mut self, k: Value) -> Value {
+        match self.str_ref(k) {
+            Some(s) if !self.is_frozen(k) => {
+                let copy = self.new_string(s.to_string());
+                self.heap.get_mut(copy.as_object().unwrap()).frozen = true;
+                copy
+            },
+            _ => k
+        }
+    }
+
+    pub fn hash_key(&self, v: Value) -> HashKey {
+        match v {
+            Value::Nil => HashKey::Nil,
+            Value::True => HashKey::True,
+            Value::False => HashKey::False,
+            Value::Integer(n) => HashKey::Int(n),
+            Value::Float(f) => HashKey::Float(f.to_bits()),
+            Value::Symbol(s) => HashKey::Sym(s),
+            Value::Object(r) => match &self.heap.get(r).kind {
+                ObjectKind::Str(s) => HashKey::Str(s.clone()),
+                ObjectKind::BigInt(n) => HashKey::BigInt(n.clone()),
+                ObjectKind::Rational(q) => HashKey::Rational(q.clone()),
+                ObjectKind::Regexp(regex) => HashKey::Regexp(regex.get_source().to_string(), regex.get_options()),
+                ObjectKind::Complex { real, imaginary } => HashKey::Complex(Box::new(self.hash_key(*real)), Box::new(self.hash_key(*imaginary))),
+                ObjectKind::Array(items) => HashKey::Array(items.iter().map(|i| self.hash_key(*i)).collect()),
+                _ => HashKey::Object(r)
+            }
+        }
+    }
+
+    // A `tail` call to a user method isn't made here: it's returned as `Unwind::TailCall` for the
+    // method returning its result to make once it has left its frame.
+    #[allow(clippy::too_many_arguments)]
+    fn eval_call(&mut self, e: &Expr, receiver: Option<&Expr>, name: &str, args: &[Expr], block: Option<&Rc<Block>>, safe_nav: bool, cache: &InlineCache, tail: bool) -> EvalResult {
+        let recv = match receiver {
+            Some(r) => self.eval(r)?,
+            None => self.frame().context.self_value
+        };
+        if safe_nav && recv.is_nil() {
+            return Ok(Value::Nil);
+        }
+        let base = self.stack.len();
+        self.stack.push(recv);
+        let block_pass = match self.eval_args(args) {
+            Ok(b) => b,
+            Err(u) => {
+                self.stack.truncate(base);
+                return Err(u);
+            }
+        };
+        let arg_end = if block_pass.is_some() { self.stack.len() - 1 } else { self.stack.len() };
+        let values: Vec<Value> = self.stack[base + 1..arg_end].to_vec();
+        let block_value = match block {
+            Some(b) => {
+                let p = self.new_block_proc(b, false);
+                self.stack.push(p);
+                Some(p)
+            },
+            None => block_pass
+        };
+        self.set_position(e);
+        let sym = cache.name(|| self.symbols.intern(name));
+        let result = match self.lookup_at_site(cache, recv, sym) {
+            Some((MethodEntry::User(method), owner)) if tail && block.is_none() => {
+                let call = TailCall { method, owner, recv, name: sym, args: values, block: block_value };
+                Err(Unwind::TailCall(Box::new(call)))
+            },
+            Some((entry, owner)) => self.call_method(entry, owner, recv, sym, &values, block_value),
+            None => {
+                let vcall = receiver.is_none() && args.is_empty() && block.is_none();
+                self.method_missing(recv, sym, &values, block_value, vcall)
+            }
+        };
+        self.stack.truncate(base);
+        if block.is_some() {
+            self.catch_break(block_value, result)
+        } else {
+            result
+        }
+    }
+
+    // Finds a method through the call's inline cache, falling back to the global method cache.
+    fn lookup_at_site(&mut self, cache: &InlineCache, recv: Value, name: Sym) -> Option<(MethodEntry, ObjRef)> {
+        let class = self.dispatch_class(recv);
+        let serial = self.method_cache.get_serial();
+        if let Some(hit) = cache.lookup(class, serial) {
+            return Some(hit);
+        }
+        let found = self.lookup_method(class, name);
+        if let Some((method, owner)) = found.as_ref().filter(|_| self.method_cache.is_enabled()) {
+            cache.record(class, serial, method, *owner);
+        }
+        found
+    }
+
+    // The method `super` would call from `method`, looking past its owner in `recv`'s ancestors.
+    pub(crate) fn find_super_method(&self, recv: Value, method: &MethodContext) -> Option<(MethodEntry, ObjRef)> {
+        let ancestors = self.ancestors(self.dispatch_class(recv));
+        let start = ancestors.iter().position(|m| *m == method.owner).map_or(ancestors.len(), |i| i + 1);
+        ancestors[start..].iter().find_map(|m| {
+            self.module_data(*m).and_then(|d| d.methods.get(&method.name)).map(|entry| (entry.clone(), *m))
+        })
+    }
+
+    // What `defined?(e)` describes `e` as, or `None` when it isn't defined. Nothing is evaluated
+    // except the receivers and scopes that have to exist for `e` to be defined, and exceptions
+    // they raise just mean `None`.
+    fn defined(&mut self, e: &Expr) -> Result<Option<&'static str>, Unwind> {
+        let base = self.stack.len();
+        let result = self.defined_inner(e);
+        self.stack.truncate(base);
+        match result {
+            Err(Unwind::Raise(_)) => Ok(None),
+            other => other
+        }
+    }
+    fn defined_inner(&mut self, e: &Expr) -> Result<Option<&'static str>, Unwind> {
+        Ok(match &e.kind {
+            ExprKind::SelfRef => Some("self"),
+            ExprKind::LocalVar(_) => Some("local-variable"),
+            ExprKind::InstanceVar(name) => {
+                let sym = self.sym(name);
+                let self_value = self.frame().context.self_value;
+                let has_ivar = self_value.as_object().is_some_and(|r| self.heap.get(r).ivars.contains_key(&sym));
+                if has_ivar { Some("instance-variable") } else { None }
+            },
+            ExprKind::GlobalVar(name) => {
+                let sym = self.sym(name);
+                if self.globals.contains_key(&sym) { Some("global-variable") } else { None }
+            },
+            ExprKind::Constant { scope: None, name } => {
+                let sym = self.sym(name);
+                self.lookup_constant(sym)?;
+                Some("constant")
+            },
+            ExprKind::Constant { scope: Some(scope), name } => {
+                if self.defined_inner(scope)?.is_none() { return Ok(None); }
+                let module = self.eval(scope)?;
+                let sym = self.sym(name);
+                match module.as_object().filter(|_| self.is_module(module)) {
+                    Some(r) if self.lookup_constant_in(r, sym).is_some() => Some("constant"),
+                    _ => None
+                }
+            },
+            ExprKind::Assign { .. } | ExprKind::OpAssign { .. } => Some("assignment"),
+            ExprKind::Call { receiver, name, args, .. } => {
+                if !self.all_defined(args)? { return Ok(None); }
+                let sym = self.sym(name);
+                let recv = match receiver {
+                    None => self.frame().context.self_value,
+                    Some(receiver) => {
+                        if self.defined_inner(receiver)?.is_none() { return Ok(None); }
+                        let recv = self.eval(receiver)?;
+                        self.stack.push(recv);
+                        recv
+                    }
+                };
+                if self.responds_to(recv, sym) { Some("method") } else { None }
+            },
+            ExprKind::Binary { op, lhs, rhs, .. } => {
+                if !self.all_defined(std::slice::from_ref(lhs))? || !self.all_defined(std::slice::from_ref(rhs))? { return Ok(None); }
+                match op {
+                    OperatorSymbol::RANGE | OperatorSymbol::EXCL_RANGE => Some("expression"),
+                    OperatorSymbol::NOT_EQ | OperatorSymbol::NOT_MATCH => Some("method"),
+                    op => {
+                        let recv = self.eval(lhs)?;
+                        let sym = self.sym(op.to_str());
+                        if self.responds_to(recv, sym) { Some("method") } else { None }
+                    }
+                }
+            },
+            ExprKind::Unary { operand, .. } => {
+                if self.defined_inner(operand)?.is_some() { Some("method") } else { None }
+            },
+            ExprKind::Yield(_) => {
+                if self.frame().context.block.is_some() { Some("yield") } else { None }
+            },
+            ExprKind::Super { .. } => {
+                let recv = self.frame().context.self_value;
+                match self.frame().context.method.clone() {
+                    Some(method) if self.find_super_method(recv, &method).is_some_and(|(entry, _)| !matches!(entry, MethodEntry::Undefined)) => Some("super"),
+                    _ => None
+                }
+            },
+            _ => Some("expression")
+        })
+    }
+    fn all_defined(&mut self, exprs: &[Expr]) -> Result<bool, Unwind> {
+        for e in exprs.iter() {
+            let inner = match &e.kind {
+                ExprKind::Splat(inner) | ExprKind::BlockPass(inner) => inner,
+                _ => e
+            };
+            if self.defined_inner(inner)?.is_none() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn eval_super(&mut self, e: &Expr, args: Option<&Vec<Expr>>, block: Option<&Rc<Block>>) -> EvalResult {
+        let method = match self.frame().context.method.clone() {
+            Some(m) => m,
+            None => return Err(self.error(self.core.runtime_error, String::from("super called outside of method")))
+        };
+        let base = self.stack.len();
+        let (values, block_pass) = match args {
+            Some(args) => {
+                let block_pass = match self.eval_args(args) {
+                    Ok(b) => b,
+                    Err(u) => {
+                        self.stack.truncate(base);
+                        return Err(u);
+                    }
+                };
+                let end = if block_pass.is_some() { self.stack.len() - 1 } else { self.stack.len() };
+                (self.stack[base..end].to_vec(), block_pass)
+            },
+            None => (method.args.clone(), None)
+        };
+        let block_value = match block {
+            Some(b) => {
+                let p = self.new_block_proc(b, false);
+                self.stack.push(p);
+                Some(p)
+            },
+            None => block_pass.or(self.frame().context.block)
+        };
+        self.set_position(e);
+        let result = self.call_super(&method, &values, block_value);
+        self.stack.truncate(base);
+        if block.is_some() { self.catch_break(block_value, result) } else { result }
+    }
+
+    pub(crate) fn call_super(&mut self, method: &MethodContext, args: &[Value], block: Option<Value>) -> EvalResult {
+        let recv = self.frame().context.self_value;
+        match self.find_super_method(recv, method) {
+            Some((MethodEntry::Undefined, _)) | None => {
+                let message = format!("super: no superclass method `{}'", self.sym_name(method.name));
+                Err(self.error(self.core.no_method_error, message))
+            },
+            Some((entry, owner)) => self.call_method(entry, owner, recv, method.name, args, block)
+        }
+    }
+
+    fn assign(&mut self, target: &Expr, v: Value) -> EvalResult {
+        match &target.kind {
+            ExprKind::LocalVar(name) => self.set_local(name, v),
+            ExprKind::InstanceVar(name) => {
+                let sym = self.sym(name);
+                let recv = self.frame().context.self_value;
+                self.set_ivar(recv, sym, v)?;
+            },
+            ExprKind::GlobalVar(name) => self.set_global(name, v),
+            ExprKind::Constant { scope, name } => {
+                let module = match scope {
+                    Some(scope) => self.eval_module_ref(scope)?,
+                    None => self.frame().context.cref.last().copied().unwrap_or(self.core.object)
+                };
+                let sym = self.sym(name);
+                self.set_constant(module, sym, v);
+            },
+            ExprKind::Call { receiver: Some(receiver), name, args, .. } => {
+                let recv = self.eval(receiver)?;
+                let base = self.stack.len();
+                self.stack.push(recv);
+                self.stack.push(v);
+                let result = self.eval_args(args);
+                let mut values = self.stack.split_off(base + 2);
+                self.stack.truncate(base);
+                result?;
+                values.push(v);
+                let setter = if name == "[]" { String::from("[]=") } else { format!("{}=", name) };
+                self.set_position(target);
+                self.call(recv, &setter, &values)?;
+            },
+            _ => return Err(self.error(self.core.syntax_error, String::from("invalid assignment target")))
+        }
+        Ok(v)
+    }
+
+    // `a op= b` reads the target once, so `x[i()] += 1` calls `i` once.
+    fn eval_op_assign(&mut self, target: &Expr, op: OperatorSymbol, value: &Expr) -> EvalResult {
+        if let ExprKind::Call { receiver: Some(receiver), name, args, .. } = &target.kind {
+            let recv = self.eval(receiver)?;
+            let base = self.stack.len();
+            self.stack.push(recv);
+            if let Err(u) = self.eval_args(args) {
+                self.stack.truncate(base);
+                return Err(u);
+            }
+            let index: Vec<Value> = self.stack[base + 1..].to_vec();
+            let result = (|| -> EvalResult {
+                let current = self.call(recv, name, &index)?;
+                let updated = match self.short_circuit(op, current) {
+                    Some(v) => return Ok(v),
+                    None => {
+                        self.stack.push(current);
+                        let rhs = self.eval(value)?;
+                        if op == OperatorSymbol::AND || op == OperatorSymbol::OR { rhs } else { self.binary_op(op, current, rhs)? }
+                    }
+                };
+                let mut setter_args = index.clone();
+                setter_args.push(updated);
+                let setter = if name == "[]" { String::from("[]=") } else { format!("{}=", name) };
+                self.call(recv, &setter, &setter_args)?;
+                Ok(updated)
+            })();
+            self.stack.truncate(base);
+            return result;
+        }
+        let current = match &target.kind {
+            ExprKind::Constant { .. } => self.eval(target).unwrap_or(Value::Nil),
+            _ => self.eval(target)?
+        };
+        if let Some(v) = self.short_circuit(op, current) {
+            return Ok(v);
+        }
+        self.stack.push(current);
+        let rhs = self.eval(value);
+        self.stack.pop();
+        let rhs = rhs?;
+        let updated = if op == OperatorSymbol::AND || op == OperatorSymbol::OR { rhs } else { self.binary_op(op, current, rhs)? };
+        self.assign(target, updated)
+    }
+
+    // `a ||= b` keeps a truthy `a`, `a &&= b` keeps a falsy one.
+    fn short_circuit(&self, op: OperatorSymbol, current: Value) -> Option<Value> {
+        match op {
+            OperatorSymbol::OR if current.is_truthy() => Some(current),
+            OperatorSymbol::AND if !current.is_truthy() => Some(current),
+            _ => None
+        }
+    }
+
+    fn eval_while(&mut self, cond: &Expr, body: &Body, until: bool) -> EvalResult {
+        let tag = self.new_tag();
+        let saved = self.frame().break_tag;
+        self.frame_mut().break_tag = Some(tag);
+        let mark = self.root_mark();
+        let result = loop {
+            self.release_roots(mark);
+            let c = match self.eval(cond) {
+                Ok(c) => c,
+                Err(u) => break Err(u)
+            };
+            if c.is_truthy() == until {
+                break Ok(Value::Nil);
+            }
+            match self.eval_body(body) {
+                Ok(_) | Err(Unwind::Next(_)) => continue,
+                Err(Unwind::Break(v, t)) if t == tag => break Ok(v),
+                Err(u) => break Err(u)
+            }
+        };
+        self.frame_mut().break_tag = saved;
+        result
+    }
+
+    fn eval_for(&mut self, var: &str, iter: &Expr, body: &Body) -> EvalResult {
+        let collection = self.eval(iter)?;
+        self.stack.push(collection);
+        let items = self.call(collection, "to_a", &[]);
+        self.stack.pop();
+        let items = items?;
+        let items = match self.array_items(items) {
+            Some(items) => items.clone(),
+            None => return Err(self.type_error(String::from("can't iterate")))
+        };
+        self.stack.push(Value::Nil);
+        let tag = self.new_tag();
+        let saved = self.frame().break_tag;
+        self.frame_mut().break_tag = Some(tag);
+        self.roots.extend_from_slice(&items);
+        let mark = self.root_mark();
+        let mut result = Ok(collection);
+        for item in items {
+            self.release_roots(mark);
+            self.set_local(var, item);
+            match self.eval_body(body) {
+                Ok(_) | Err(Unwind::Next(_)) => {},
+                Err(Unwind::Break(v, t)) if t == tag => {
+                    result = Ok(v);
+                    break;
+                },
+                Err(u) => {
+                    result = Err(u);
+                    break;
+                }
+            }
+        }
+        self.frame_mut().break_tag = saved;
+        self.stack.pop();
+        result
+    }
+
+    fn eval_case(&mut self, subject: Option<&Expr>, whens: &[(Vec<Expr>, Body)], else_body: Option<&Body>) -> EvalResult {
+        let value = match subject {
+            Some(s) => Some(self.eval(s)?),
+            None => None
+        };
+        if let Some(v) = value {
+            self.stack.push(v);
+        }
+        let result = (|| -> EvalResult {
+            for (tests, body) in whens.iter() {
+                for test in tests.iter() {
+                    let patterns: Vec<Value> = match &test.kind {
+                        ExprKind::Splat(inner) => {
+                            let v = self.eval(inner)?;
+                            self.array_items(v).cloned().unwrap_or_else(|| vec![v])
+                        },
+                        _ => vec![self.eval(test)?]
+                    };
+                    for pattern in patterns {
+                        let matched = match value {
+                            Some(v) => self.case_equal(pattern, v)?,
+                            None => pattern.is_truthy()
+                        };
+                        if matched {
+                            return self.eval_body(body);
+                        }
+                    }
+                }
+            }
+            match else_body {
+                Some(body) => self.eval_body(body),
+                None => Ok(Value::Nil)
+            }
+        })();
+        if value.is_some() {
+            self.stack.pop();
+        }
+        result
+    }
+
+    pub fn case_equal(&mut self, pattern: Value, v: Value) -> Result<bool, Unwind> {
+        Ok(self.call(pattern, "===", &[v])?.is_truthy())
+    }
+
+    fn eval_begin(&mut self, body: &Body, rescues: &[RescueClause], else_body: Option<&Body>, ensure_body: Option<&Body>) -> EvalResult {
+        let handles = !rescues.is_empty() || ensure_body.is_some();
+        if handles {
+            self.frame_mut().handlers += 1;
+        }
+        let result = self.run_begin(body, rescues, else_body, ensure_body);
+        if handles {
+            self.frame_mut().handlers -= 1;
+        }
+        result
+    }
+
+    fn run_begin(&mut self, body: &Body, rescues: &[RescueClause], else_body: Option<&Body>, ensure_body: Option<&Body>) -> EvalResult {
+        loop {
+            let mut result = self.eval_body(body);
+            if let Err(Unwind::Raise(exc)) = result {
+                if !rescues.is_empty() {
+                    self.stack.push(exc);
+                    result = match self.find_rescue(rescues, exc) {
+                        Ok(Some(clause)) => {
+                            if let Some(var) = &clause.var {
+                                self.set_local(var, exc);
+                            }
+                            let saved = self.global("$!");
+                            self.set_global("$!", exc);
+                            let r = self.eval_body(&clause.body);
+                            self.set_global("$!", saved);
+                            if let Err(Unwind::Retry) = r {
+                                self.stack.pop();
+                                continue;
+                            }
+                            r
+                        },
+                        Ok(None) => Err(Unwind::Raise(exc)),
+                        Err(u) => Err(u)
+                    };
+                    self.stack.pop();
+                }
+            } else if let (Ok(_), Some(else_body)) = (&result, else_body) {
+                result = self.eval_body(else_body);
+            }
+            if let Some(ensure_body) = ensure_body {
+                self.eval_body(ensure_body)?;
+            }
+            return result;
+        }
+    }
+
+    fn find_rescue<'r>(&mut self, rescues: &'r [RescueClause], exc: Value) -> Result<Option<&'r RescueClause>, Unwind> {
+        for clause in rescues.iter() {
+            if clause.classes.is_empty() {
+                if self.is_a(exc, self.core.standard_error) {
+                    return Ok(Some(clause));
+                }
+                continue;
+            }
+            for class_expr in clause.classes.iter() {
+                let classes: Vec<Value> = match &class_expr.kind {
+                    ExprKind::Splat(inner) => {
+                        let v = self.eval(inner)?;
+                        self.array_items(v).cloned().unwrap_or_else(|| vec![v])
+                    },
+                    _ => vec![self.eval(class_expr)?]
+                };
+                for class in classes {
+                    match class.as_object() {
+                        Some(r) if self.module_data(r).is_some() => {
+                            if self.is_a(exc, r) {
+                                return Ok(Some(clause));
+                            }
+                        },
+                        _ => return Err(self.type_error(String::from("class or module required for rescue clause")))
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn eval_module_ref(&mut self, e: &Expr) -> Result<ObjRef, Unwind> {
+        let v = self.eval(e)?;
+        self.expect_module(v)
+    }
+
+    pub(crate) fn expect_module(&mut self, v: Value) -> Result<ObjRef, Unwind> {
+        match v.as_object() {
+            Some(r) if self.module_data(r).is_some() => Ok(r),
+            _ => {
+                let shown = self.inspect(v)?;
+                Err(self.type_error(format!("{} is not a class/module", shown)))
+            }
+        }
+    }
+
+    // The module a `class`/`module` definition goes into, and the constant it's named by.
+    fn definition_target(&mut self, path: &Expr) -> Result<(ObjRef, Sym), Unwind> {
+        match &path.kind {
+            ExprKind::Constant { scope: Some(scope), name } => {
+                let container = self.eval_module_ref(scope)?;
+                Ok((container, self.sym(name)))
+            },
+            ExprKind::Constant { scope: None, name } => {
+                let container = self.frame().context.cref.last().copied().unwrap_or(self.core.object);
+                Ok((container, self.sym(name)))
+            },
+            _ => Err(self.type_error(String::from("class/module name must be a constant")))
+        }
+    }
+
+    fn eval_class(&mut self, path: &Expr, superclass: Option<&Expr>, body: &Body) -> EvalResult {
+        let (container, name) = self.definition_target(path)?;
+        let superclass = match superclass {
+            Some(e) => Some(self.eval(e)?),
+            None => None
+        };
+        let class = self.open_class(container, name, superclass)?;
+        self.eval_module_body(class, body, "class")
+    }
+
+    // The class `class name < superclass` in `container` reopens, or a new one.
+    pub(crate) fn open_class(&mut self, container: ObjRef, name: Sym, superclass: Option<Value>) -> Result<ObjRef, Unwind> {
+        let superclass = match superclass {
+            Some(v) => match v.as_object() {
+                Some(r) if self.module_data(r).is_some_and(|d| d.is_class) => Some(r),
+                _ => return Err(self.type_error(String::from("superclass must be a Class")))
+            },
+            None => None
+        };
+        let existing = self.module_data(container).and_then(|d| d.constants.get(&name).copied());
+        let class = match existing {
+            Some(v) => {
+                let r = match v.as_object() {
+                    Some(r) if self.module_data(r).is_some_and(|d| d.is_class) => r,
+                    _ => return Err(self.type_error(format!("{} is not a class", self.sym_name(name))))
+                };
+                if superclass.is_some() && self.module_data(r).and_then(|d| d.superclass) != superclass {
+                    return Err(self.type_error(format!("superclass mismatch for class {}", self.sym_name(name))));
+                }
+                r
+            },
+            None => {
+                let superclass = superclass.unwrap_or(self.core.object);
+                let class = self.new_module(None, true, Some(superclass));
+                self.set_constant(container, name, Value::Object(class));
+                class
+            }
+        };
+        Ok(class)
+    }
+
+    fn eval_module(&mut self, path: &Expr, body: &Body) -> EvalResult {
+        let (container, name) = self.definition_target(path)?;
+        let module = self.open_module(container, name)?;
+        self.eval_module_body(module, body, "module")
+    }
+
+    pub(crate) fn open_module(&mut self, container: ObjRef, name: Sym) -> Result<ObjRef, Unwind> {
+        let existing = self.module_data(container).and_then(|d| d.constants.get(&name).copied());
+        let module = match existing {
+            Some(v) => match v.as_object() {
+                Some(r) if self.module_data(r).is_some_and(|d| !d.is_class) => r,
+                _ => return Err(self.type_error(format!("{} is not a module", self.sym_name(name))))
+            },
+            None => {
+                let module = self.new_module(None, false, None);
+                self.set_constant(container, name, Value::Object(module));
+                module
+            }
+        };
+        Ok(module)
+    }
+
+    fn eval_module_body(&mut self, module: ObjRef, body: &Body, kind: &str) -> EvalResult {
+        let env = self.new_env(None);
+        self.push_module_frame(module, env, kind)?;
+        let result = self.eval_body(body);
+        self.frames.pop();
+        result
+    }
+
+    // The frame a class or module body runs in, with the module as `self` and innermost `cref`.
+    pub(crate) fn push_module_frame(&mut self, module: ObjRef, env: ObjRef, kind: &str) -> Result<(), Unwind> {
+        let mut cref = (*self.frame().context.cref).clone();
+        cref.push(module);
+        let id = self.new_frame_id();
+        let context = Context {
+            self_value: Value::Object(module),
+            def_target: module,
+            cref: Rc::new(cref),
+            method: None,
+            block: None,
+            return_target: id,
+            label: Rc::from(format!("<{}:{}>", kind, self.module_name(module)))
+        };
+        let file = self.frame().file;
+        self.push_frame(id, context, env, file)
+    }
+
+    // -- operators --
+
+    // Operators are method calls on the left operand, except ranges and `!~`, with Integer and
+    // Float operands short-circuited while their operators haven't been redefined.
+    pub(crate) fn binary_op(&mut self, op: OperatorSymbol, l: Value, r: Value) -> EvalResult {
+        if let Some(v) = self.fast_binary_op(op, l, r) {
+            return Ok(v);
+        }
+        match op {
+            OperatorSymbol::RANGE => return Ok(self.new_range(l, r, false)),
+            OperatorSymbol::EXCL_RANGE => return Ok(self.new_range(l, r, true)),
+            OperatorSymbol::NOT_MATCH => {
+                let matched = self.call(l, "=~", &[r])?;
+                return Ok(Value::from_bool(!matched.is_truthy()));
+            },
+            _ => {}
+        }
+        let name = self.operator_sym(op);
+        self.send(l, name, &[r], None)
+    }
+
+    pub(crate) fn operator_sym(&mut self, op: OperatorSymbol) -> Sym {
+        let index = op as usize;
+        if let Some(sym) = self.operator_syms[index] {
+            return sym;
+        }
+        let sym = self.symbols.intern(op.to_str());
+        self.operator_syms[index] = Some(sym);
+        sym
+    }
+
+    pub(crate) fn fast_binary_op(&self, op: OperatorSymbol, l: Value, r: Value) -> Option<Value> {
+        if self.fast_numeric_ops { builtins::numeric::fast_binary_op(op, l, r) } else { None }
+    }
+
+    pub(crate) fn unary_op(&mut self, op: OperatorSymbol, v: Value) -> EvalResult {
+        match (op, v) {
+            (OperatorSymbol::MINUS, Value::Integer(n)) if self.fast_numeric_ops && n != i64::MIN => return Ok(Value::Integer(-n)),
+            (OperatorSymbol::MINUS, Value::Float(f)) if self.fast_numeric_ops => return Ok(Value::Float(-f)),
+            _ => {}
+        }
+        match op {
+            OperatorSymbol::MINUS => self.call(v, "-@", &[]),
+            OperatorSymbol::PLUS => self.call(v, "+@", &[]),
+            OperatorSymbol::BIT_NOT => self.call(v, "~", &[]),
+            _ => self.call(v, op.to_str(), &[])
+        }
+    }
+
+    // `==` as jasper code sees it, including user-defined equality.
+    pub fn values_equal(&mut self, a: Value, b: Value) -> Result<bool, Unwind> {
+        if a.is_identical(b) {
+            return Ok(true);
+        }
+        Ok(self.call(a, "==", &[b])?.is_truthy())
+    }
+
+    pub fn inspect(&mut self, v: Value) -> Result<String, Unwind> {
+        let s = self.call(v, "inspect", &[])?;
+        Ok(self.string_value(s).unwrap_or_default())
+    }
+    pub fn to_s(&mut self, v: Value) -> Result<String, Unwind> {
+        if let Some(s) = self.str_ref(v) {
+            return Ok(s.to_string());
+        }
+        let s = self.call(v, "to_s", &[])?;
+        match self.string_value(s) {
+            Some(s) => Ok(s),
+            None => Ok(builtins::object::default_to_s(self, v))
+        }
+    }
+
+    // -- require --
+
+    pub fn require(&mut self, name: &str, relative: bool) -> EvalResult {
+        let path = match self.resolve_feature(name, relative) {
+            Some(path) => path,
+            None => return Err(self.error(self.core.load_error, format!("cannot load such file -- {}", name)))
+        };
+        let absolute = std::fs::canonicalize(&path).unwrap_or(path.clone());
+        let absolute_name = absolute.to_string_lossy().into_owned();
+        let features = self.global("$LOADED_FEATURES");
+        let loaded = self.array_items(features).is_some_and(|items| items.iter().any(|i| self.str_ref(*i) == Some(absolute_name.as_str())));
+        if loaded {
+            return Ok(Value::False);
+        }
+        // Recorded before evaluation so a file that requires itself, directly or not, loads once.
+        let entry = self.new_string(absolute_name);
+        if let Some(r) = features.as_object() {
+            if let ObjectKind::Array(items) = &mut self.heap.get_mut(r).kind {
+                items.push(entry);
+            }
+        }
+        let file = match self.sources.find_path(&absolute) {
+            Some(file) => file,
+            None => match self.sources.load(&path) {
+                Ok(file) => file,
+                Err(e) => return Err(self.error(self.core.load_error, format!("cannot load such file -- {} ({})", name, e)))
+            }
+        };
+        self.eval_loaded(file, "<top (required)>")?;
+        Ok(Value::True)
+    }
+
+    fn resolve_feature(&mut self, name: &str, relative: bool) -> Option<PathBuf> {
+        let with_extension = |p: PathBuf| -> PathBuf {
+            if p.extension().is_some() { p } else { p.with_extension(SOURCE_EXTENSION) }
+        };
+        let requested = Path::new(name);
+        if relative {
+            let current = self.frame().file;
+            let base = self.sources.get(current).and_then(|f| f.get_path()).and_then(Path::parent).map(Path::to_path_buf);
+            let base = base.unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+            // Resolved like the paths in $LOADED_FEATURES, so a file reached through a symlink or
+            // `..` from one side and directly from the other still loads once.
+            let base = std::fs::canonicalize(&base).unwrap_or(base);
+            let candidate = with_extension(base.join(requested));
+            return if candidate.is_file() { Some(candidate) } else { None };
+        }
+        if requested.is_absolute() || name.starts_with("./") || name.starts_with("../") {
+            let candidate = with_extension(requested.to_path_buf());
+            return if candidate.is_file() { Some(candidate) } else { None };
+        }
+        let load_path = self.global("$LOAD_PATH");
+        let dirs: Vec<String> = self.array_items(load_path).map_or(Vec::new(), |items| {
+            items.iter().filter_map(|i| self.str_ref(*i).map(str::to_string)).collect()
+        });
+        dirs.into_iter().map(|dir| with_extension(Path::new(&dir).join(requested))).find(|c| c.is_file())
+    }
+}
+
+// Roughly where the Rust stack is now: the address of a local in a call that isn't inlined.
+#[inline(never)]
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+// How much of a stack this big calls may use: small stacks keep a quarter of themselves free
+// rather than the whole reserve.
+fn stack_budget(size: usize) -> usize {
+    size - STACK_RESERVE.min(size / 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::interpreter::runtime::io::Capture;
+
+    // Evaluates `source` on the VM, checking the tree walker agrees.
+    fn eval_to_s(source: &str) -> String {
+        let results: Vec<String> = [false, true].iter().map(|vm| {
+            let mut interp = Interpreter::create();
+            interp.set_vm(*vm);
+            let v = interp.eval_source("test", source).unwrap_or_else(|_| panic!("{} raised", source));
+            interp.inspect(v).unwrap()
+        }).collect();
+        assert_eq!(results[0], results[1], "{}", source);
+        results[1].clone()
+    }
+
+    #[test]
+    fn evaluates_methods_blocks_and_classes() {
+        assert_eq!(eval_to_s("def fact(n)\n  if n <= 1 then 1 else n * fact(n - 1) end\nend\nfact(10)"), "3628800");
+        assert_eq!(eval_to_s("out = []\n[1, 2, 3].each { |x| out << x * 2 }\nout"), "[2, 4, 6]");
+        assert_eq!(eval_to_s("class A\n  def initialize(x)\n    @x = x\n  end\n  def x; @x; end\nend\nclass B < A; end\nB.new(5).x"), "5");
+        assert_eq!(eval_to_s("[1, 2, 3].each { |x| break x * 10 if x == 2 }"), "20");
+    }
+
+    #[test]
+    fn exceptions_carry_class_and_message() {
+        assert_eq!(eval_to_s("begin\n  raise ArgumentError, \"bad\"\nrescue => e\n  [e.class, e.message]\nend"), "[ArgumentError, \"bad\"]");
+        let mut interp = Interpreter::create();
+        let exc = match interp.eval_source("test", "1 / 0") {
+            Err(Unwind::Raise(exc)) => exc,
+            _ => panic!("expected an exception")
+        };
+        assert!(interp.is_a(exc, interp.core.zero_division_error));
+        assert_eq!(interp.exception_message(exc), "divided by 0");
+    }
+
+    #[test]
+    fn bare_rescue_handles_standard_errors_on_its_own_line() {
+        assert_eq!(eval_to_s("out = []\nbegin\n  raise \"boom\"\nrescue\n  out << $!.message\nend\nout"), "[\"boom\"]");
+        assert_eq!(eval_to_s("tries = 0\nbegin\n  tries += 1\n  raise \"boom\" if tries < 3\n  tries\nrescue\n  retry\nend"), "3");
+        assert_eq!(eval_to_s("begin\n  1\nrescue\n  2\nelse\n  -3\nensure\n  4\nend"), "-3");
+    }
+
+    #[test]
+    fn long_backtraces_are_elided_in_the_middle() {
+        let mut interp = Interpreter::create();
+        let exc = match interp.eval_source("deep.lang", "def down(n)\n  raise \"bottom\" if n == 0\n  down(n - 1)\nend\ndown(40)") {
+            Err(Unwind::Raise(exc)) => exc,
+            _ => panic!("expected an exception")
+        };
+        let notes = interp.exception_diagnostic(exc).get_notes().to_vec();
+        assert_eq!(notes.len(), 2 * BACKTRACE_EDGE + 1);
+        assert_eq!(notes[BACKTRACE_EDGE], "... 25 levels...");
+        assert_eq!(notes[0], "from deep.lang:3:in `down'");
+        assert_eq!(notes.last().unwrap(), "from deep.lang:5:in `<main>'");
+    }
+
+    #[test]
+    fn returned_calls_reuse_the_frame() {
+        let mut interp = Interpreter::create();
+        interp.set_max_depth(20);
+        let v = interp.eval_source("test", "def count(n, acc)\n  return acc if n == 0\n  return count(n - 1, acc + 1)\nend\ncount(50_000, 0)").unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "50000");
+        // Not a tail call while an `ensure` still has to run.
+        let source = "def guarded(n)\n  return 0 if n == 0\n  begin\n    return guarded(n - 1)\n  ensure\n    n\n  end\nend\n\
+            begin\n  guarded(1_000)\nrescue SystemStackError => e\n  e.message\nend";
+        let v = interp.eval_source("test", source).unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "\"stack level too deep\"");
+    }
+
+    #[test]
+    fn deep_recursion_stops_short_of_the_thread_stack() {
+        let size = 1 << 26;
+        let runner = std::thread::Builder::new().stack_size(size).spawn(move || {
+            let mut interp = Interpreter::create();
+            interp.set_vm(false);
+            interp.set_stack_size(size);
+            interp.set_max_depth(usize::MAX);
+            let source = "def deep(n)\n  if n == 0 then 0 else 1 + deep(n - 1) end\nend\n\
+                begin\n  deep(10_000_000)\nrescue SystemStackError => e\n  [e.message, deep(100)]\nend";
+            let v = interp.eval_source("test", source).unwrap();
+            interp.inspect(v).unwrap()
+        });
+        assert_eq!(runner.unwrap().join().unwrap(), "[\"stack level too deep\", 100]");
+    }
+
+    #[test]
+    fn deep_recursion_is_caught_on_a_default_thread() {
+        let runner = std::thread::spawn(|| {
+            let source = "def deep(n)\n  if n == 0 then 0 else 1 + deep(n - 1) end\nend\n\
+                def through_block(n)\n  if n == 0 then 0 else [n].each { through_block(n - 1) } end\nend\n\
+                [deep(20), through_block(20)]\n\
+                begin\n  deep(100_000)\nrescue SystemStackError => e\n  e.message\nend";
+            [false, true].map(|vm| {
+                let mut interp = Interpreter::create();
+                interp.set_vm(vm);
+                interp.set_max_depth(usize::MAX);
+                let v = match interp.eval_source("test", source) { Ok(v) => v, Err(Unwind::Raise(e)) => panic!("{:?}", interp.exception_diagnostic(e)), _ => panic!() };
+                let deep = interp.inspect(v).unwrap();
+                let v = interp.eval_source("test", "begin\n  through_block(100_000)\nrescue SystemStackError => e\n  e.message\nend").unwrap();
+                (deep, interp.inspect(v).unwrap())
+            })
+        });
+        // Compiled calls don't recurse on the Rust stack, so only the block calls run out of it.
+        let too_deep = String::from("\"stack level too deep\"");
+        assert_eq!(runner.join().unwrap(), [(too_deep.clone(), too_deep.clone()), (String::from("100000"), too_deep)]);
+    }
+
+    #[test]
+    fn pseudo_keywords_describe_the_source_position() {
+        let mut interp = Interpreter::create();
+        let v = interp.eval_source("pos.lang", "x = 1\n[__FILE__,\n  __LINE__, __ENCODING__.name]").unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "[\"pos.lang\", 3, \"UTF-8\"]");
+    }
+
+    #[test]
+    fn begin_blocks_run_first_and_exit_handlers_run_in_reverse() {
+        let mut interp = Interpreter::create();
+        let source = "$log << :main\nat_exit { $log << :first }\n2.times { END { $log << :end } }\nBEGIN { $log = [:begin] }\nraise \"boom\"";
+        let result = interp.eval_source("test", source);
+        assert!(matches!(interp.run_exit_handlers(result), Err(Unwind::Raise(_))));
+        let log = interp.global("$log");
+        assert_eq!(interp.inspect(log).unwrap(), "[:begin, :main, :end, :first]");
+    }
+
+    #[test]
+    fn defined_describes_without_evaluating() {
+        assert_eq!(eval_to_s("x = 1\n[defined?(x), defined?(y), defined?(raise), defined?(String), defined?(@a), defined?(Nope::X)]"),
+            "[\"local-variable\", nil, \"method\", \"constant\", nil, nil]");
+        assert_eq!(eval_to_s("x = 1\ndefined?(x = 2)\nx"), "1");
+        assert_eq!(eval_to_s("def m\n  defined?(yield)\nend\n[m, m { }]"), "[nil, \"yield\"]");
+    }
+
+    #[test]
+    fn operators_dispatch_to_user_methods() {
+        let version = "class V\n  include Comparable\n  attr_reader :n\n  def initialize(n); @n = n; end\n  def <=>(o); n <=> o.n; end\nend\n";
+        assert_eq!(eval_to_s(&format!("{}[V.new(1) < V.new(2), V.new(2) >= V.new(3), V.new(1) == V.new(1), V.new(4).clamp(V.new(1), V.new(2)).n]", version)),
+            "[true, false, true, 2]");
+        assert_eq!(eval_to_s("class P\n  def +(o); :plus; end\n  def [](i); i * 2; end\n  def -@; :neg; end\nend\n[P.new + 1, P.new[4], -P.new]"), "[:plus, 8, :neg]");
+        assert_eq!(eval_to_s("class Integer\n  def +(o); 42; end\nend\n[1 + 1, 3 - 1]"), "[42, 2]");
+    }
+
+    #[test]
+    fn integers_promote_past_64_bits_and_back() {
+        assert_eq!(eval_to_s("[9223372036854775807 + 1, 2 ** 100, 123456789012345678901234567890]"), "[9223372036854775808, 1267650600228229401496703205376, 123456789012345678901234567890]");
+        assert_eq!(eval_to_s("big = 2 ** 70
+[big / 2 ** 69, (big - big).class, big * big / big == big, big > 2.5]"), "[2, Integer, true, true]");
+        assert_eq!(eval_to_s("n = -(2 ** 70) - 3
+[n / 7, n % 7, n.divmod(-2 ** 65)]"), "[-168655945816773043347, 2, [32, -3]]");
+        assert_eq!(eval_to_s("[\"99999999999999999999\".to_i + 1, Integer(\"-10000000000000000000\"), { 2 ** 64 => 1 }[2 ** 64]]"), "[100000000000000000000, -10000000000000000000, 1]");
+        assert_eq!(eval_to_s("[10 ** 20 / 10 ** 19, (2 ** 70 + 5) & 7, -(2 ** 70) ^ 3, ~(2 ** 64), 5 | 2 ** 64]"), "[10, 5, -1180591620717411303421, -18446744073709551617, 18446744073709551621]");
+        assert_eq!(eval_to_s("[1 >> 2 ** 70, -1 >> 2 ** 70, 2 ** 70 >> 68, 1 << -(2 ** 70), 0 << 2 ** 70]"), "[0, -1, 4, 0, 0]");
+    }
+
+    #[test]
+    fn floats_round_to_any_number_of_digits() {
+        assert_eq!(eval_to_s("[3.14159.round(2), 3.14159.floor(3), -3.14159.ceil(1), 1234.5.round(-2), -1234.5.floor(-2), 1234.5.truncate(-1)]"), "[3.14, 3.141, -3.1, 1200, -1300, 1230]");
+        assert_eq!(eval_to_s("[5.0.round(400), 0.1.round(17), (10.0 ** -300).round(310), 5.0.round(9223372036854775807)]"), "[5.0, 0.1, 1.0e-300, 5.0]");
+        assert_eq!(eval_to_s("[1234.5.round(-4294967295), 5.0.round(-9223372036854775807 - 1), (10.0 ** 308).round(-309), -5.0.floor(-400)]"), "[0, 0, 0, 0]");
+    }
+
+    #[test]
+    fn rational_and_complex_literals_mix_with_other_numbers() {
+        assert_eq!(eval_to_s("[1/3r + 1/6r, 3 * (1/3r), 1.5r - 1, 0.5 + 1/4r, 2 ** -2, 1 == 1r, 3/2r > 1]"), "[(1/2), (1/1), (1/2), 0.75, (1/4), true, true]");
+        assert_eq!(eval_to_s("[Rational(\"2/4\"), 0.75.to_r, (-7/2r).floor, (-7/2r).round, 1.quo(3)]"), "[(1/2), (3/4), -4, -4, (1/3)]");
+        assert_eq!(eval_to_s("[(1/3r).round(2), (-1/3r).ceil(1), (12345/7r).round(-2), 1234.round(-2), (-1250).round(-2), (-1234).floor(-2), 5.round(2)]"), "[(33/100), (-3/10), 1800, 1200, -1300, -1300, 5]");
+        assert_eq!(eval_to_s("begin\n  (7/2r).to_i(1)\nrescue ArgumentError => e\n  e.message\nend"), "\"wrong number of arguments (given 1, expected 0)\"");
+        assert_eq!(eval_to_s("[1 + 2i, (1 + 2i) * (3 + 4i), (1 + 2i) / (3 + 4i), 1i ** 2, (3 + 4i).abs, Complex(3) == 3]"), "[(1+2i), (-5+10i), ((11/25)+(2/25)*i), (-1+0i), 5.0, true]");
+    }
+
+    #[test]
+    fn strings_index_by_character_and_match_regexps() {
+        assert_eq!(eval_to_s("s = \"h\u{e9}llo\"\n[s.length, s.bytesize, s[1], s[1, 3], s[-2..-1], s[/l+/], s.upcase, s.reverse]"),
+            "[5, 6, \"\u{e9}\", \"\u{e9}ll\", \"lo\", \"ll\", \"H\u{c9}LLO\", \"oll\u{e9}h\"]");
+        assert_eq!(eval_to_s("[\" a,b,,c,, \".strip.split(\",\"), \"a  b\".split, \"a1b22\".split(/\\d/), \"ab\" * 2, \"%03d|%-3s|\" % [7, \"x\"]]"),
+            "[[\"a\", \"b\", \"\", \"c\"], [\"a\", \"b\"], [\"a\", \"b\"], \"abab\", \"007|x  |\"]");
+        assert_eq!(eval_to_s("[\"hello\".sub(/l/, \"L\"), \"hello\".gsub(/(l)/) { $1.upcase }, \"ab cd\".gsub(/(\\w)(\\w)/, \"\\\\2\\\\1\"), \"hello\" =~ /ll/, $~[0]]"),
+            "[\"heLlo\", \"heLLo\", \"ba dc\", 2, \"ll\"]");
+        assert_eq!(eval_to_s("[\"hello\".start_with?(\"he\"), \"hello\".include?(\"lx\"), \"ab\".chars, \"hi\".center(6, \"*\")]"),
+            "[true, false, [\"a\", \"b\"], \"**hi**\"]");
+        assert_eq!(eval_to_s("[\"ab\".each_char.to_a, \"ab\".each_char.with_index.map { |c, i| c * (i + 1) }, \"a\\nb\".each_line.to_a]"),
+            "[[\"a\", \"b\"], [\"a\", \"bb\"], [\"a\\n\", \"b\"]]");
+        assert_eq!(eval_to_s("[\"\\u00e9\", \"\\u{e9}\", \"\\u{48 49}!\", \"\\x41\\x7e\", \"\\u00e9\".size]"),
+            "[\"\u{e9}\", \"\u{e9}\", \"HI!\", \"A~\", 1]");
+    }
+
+    #[test]
+    fn format_rejects_precisions_and_widths_too_big_to_print() {
+        let errors = ["\"%.70000f\" % 1", "\"%.70000e\" % 1", "\"%.99999999999999999999f\" % 1", "\"%.*f\" % [70000, 1]", "\"%99999999999999999999d\" % 1"]
+            .map(|source| eval_to_s(&format!("begin\n  {}\nrescue ArgumentError => e\n  e.message\nend", source)));
+        assert_eq!(errors.join(", "), "\"precision too big\", \"precision too big\", \"precision too big\", \"precision too big\", \"width too big\"");
+        assert_eq!(eval_to_s("(\"%.65535f\" % 1).size"), "65537");
+    }
+
+    #[test]
+    fn frozen_string_literal_comment_freezes_literals() {
+        assert_eq!(eval_to_s("# frozen_string_literal: true\ns = \"a\"\nbegin\n  s << \"b\"\nrescue FrozenError\n  [s.frozen?, (+s).frozen?, (+s) << \"b\"]\nend"),
+            "[true, false, \"ab\"]");
+        assert_eq!(eval_to_s("\"a\".frozen?"), "false");
+    }
+
+    #[test]
+    fn enumerable_builds_on_each() {
+        let numbers = "class Numbers\n  include Enumerable\n  def initialize(*items); @items = items; end\n  def each\n    @items.each { |x| yield x }\n  end\nend\nn = Numbers.new(3, 1, 2)\n";
+        assert_eq!(eval_to_s(&format!("{}[n.map {{ |x| x * 2 }}, n.select(&:odd?), n.reduce(:+), n.sort_by {{ |x| -x }}, n.min_by {{ |x| x }}, n.sum, n.to_a]", numbers)),
+            "[[6, 2, 4], [3, 1], 6, [3, 2, 1], 1, 6, [3, 1, 2]]");
+        assert_eq!(eval_to_s(&format!("{}[n.group_by(&:odd?), n.zip([4]), n.take_while {{ |x| x > 2 }}, n.each_slice(2).to_a, n.first]", numbers)),
+            "[{true=>[3, 1], false=>[2]}, [[3, 4], [1, nil], [2, nil]], [3], [[3, 1], [2]], 3]");
+        assert_eq!(eval_to_s("[{ a: 1, b: 2 }.map { |k, v| v }, { a: 1, b: 2 }.select { |k, v| v > 1 }, (1..4).reduce(:*), [1, 2].map.with_index(1) { |x, i| x * i }]"),
+            "[[1, 2], {:b=>2}, 24, [1, 4]]");
+    }
+
+    #[test]
+    fn counts_and_steps_are_checked() {
+        let messages: Vec<String> = ["(1..10).step(0) { }", "(1..10).step(-1) { }", "[1, 2, 3].first(-1)", "[1, 2, 3].last(-1)", "(1..3).first(-1)", "(1..3).last(-1)", "Array.new(-1)"].iter()
+            .map(|source| eval_to_s(&format!("begin\n  {}\nrescue ArgumentError => e\n  e.message\nend", source)))
+            .collect();
+        assert_eq!(messages, ["\"step can't be 0\"", "\"step can't be negative\"", "\"negative array size\"", "\"negative array size\"", "\"negative array size\"", "\"negative array size\"", "\"negative array size\""]);
+        assert_eq!(eval_to_s("[[1, 2, 3].first(0), [1, 2, 3].last(2), (1..3).last(5), (1..3).last(0), (1..10).first(2)]"), "[[], [2, 3], [1, 2, 3], [], [1, 2]]");
+    }
+
+    #[test]
+    fn lazy_enumerators_pull_only_what_they_need() {
+        assert_eq!(eval_to_s("(1..Float::INFINITY).lazy.map { |x| x * 2 }.select { |x| x % 3 == 0 }.first(3)"), "[6, 12, 18]");
+        assert_eq!(eval_to_s("$seen = []\n(1..10).lazy.map { |x| $seen << x; x }.take(2).to_a\n$seen"), "[1, 2]");
+        assert_eq!(eval_to_s("(1..3).lazy.map { |x| x }.inspect"), "\"#<Enumerator::Lazy: #<Enumerator::Lazy: 1..3>:map>\"");
+    }
+
+    #[test]
+    fn enumerators_iterate_externally() {
+        assert_eq!(eval_to_s("e = [1, 2].each\n[e.next, e.peek, e.next, (e.next rescue $!.class), e.rewind.next]"), "[1, 2, 2, StopIteration, 1]");
+        assert_eq!(eval_to_s("e = [1, 2, 3].map\nout = []\nloop { out << e.next }\nout"), "[1, 2, 3]");
+        assert_eq!(eval_to_s("e = (1..Float::INFINITY).lazy.map { |x| x * 2 }\n40.times { e.next }\n[e.next, e.peek]"), "[82, 84]");
+    }
+
+    #[test]
+    fn kernel_io_uses_the_interpreter_streams() {
+        let (out, err) = (Capture::create(), Capture::create());
+        let mut interp = Interpreter::create();
+        interp.set_stdout(Box::new(out.clone()));
+        interp.set_stderr(Box::new(err.clone()));
+        interp.set_stdin(Box::new(io::Cursor::new(b"first\nsecond\n".to_vec())));
+        let source = "line = gets\nputs line, [1, [2]]\nprint \"a\", :b\np \"q\", nil\nwarn \"careful\"\nputs format(\"%03d|%-3s|%.2f\", 7, \"x\", 1.005)\n[gets(chomp: true), gets, $_]";
+        let v = interp.eval_source("test", source).unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "[\"second\", nil, nil]");
+        assert_eq!(out.get_text(), "first\n1\n2\nab\"q\"\nnil\n007|x  |1.00\n");
+        assert_eq!(err.get_text(), "careful\n");
+    }
+
+    #[test]
+    fn puts_writes_an_empty_line_for_an_empty_array() {
+        let out = Capture::create();
+        let mut interp = Interpreter::create();
+        interp.set_stdout(Box::new(out.clone()));
+        interp.eval_source("test", "puts []\nputs 1, [], [[]], 2\nputs").unwrap();
+        assert_eq!(out.get_text(), "\n1\n\n\n2\n\n");
+    }
+
+    #[test]
+    fn standard_stream_globals_write_to_their_own_streams() {
+        let (out, err) = (Capture::create(), Capture::create());
+        let mut interp = Interpreter::create();
+        interp.set_stdout(Box::new(out.clone()));
+        interp.set_stderr(Box::new(err.clone()));
+        interp.set_stdin(Box::new(io::Cursor::new(b"in\n".to_vec())));
+        let source = "$stderr.print \"e\", 1\nSTDERR.puts\nn = $stdout.write(\"ab\", :c)\n$stdout << \"d\" << 2\n\
+            [n, $stdin.gets, $stdout.fileno, ($stdin.print(\"x\") rescue $!.class), $stderr.equal?(STDERR)]";
+        let v = interp.eval_source("test", source).unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "[3, \"in\\n\", 1, IOError, true]");
+        assert_eq!(out.get_text(), "abcd2");
+        assert_eq!(err.get_text(), "e1\n");
+    }
+
+    #[test]
+    fn require_loads_each_file_once() {
+        let dir = std::env::temp_dir().join(format!("jasper-require-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("counter.lang"), "$loads = ($loads || 0) + 1\n").unwrap();
+        let mut interp = Interpreter::create();
+        interp.add_load_path(&dir);
+        let v = interp.eval_source("test", "[require(\"counter\"), require(\"counter\"), $loads]").unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "[true, false, 1]");
+        let v = interp.eval_source("test", "begin\n  require \"missing\"\nrescue LoadError => e\n  e.message\nend").unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "\"cannot load such file -- missing\"");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn require_relative_resolves_the_requiring_file() {
+        let dir = std::env::temp_dir().join(format!("jasper-require-relative-{}", std::process::id()));
+        fs::create_dir_all(dir.join("real/lib")).unwrap();
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+        fs::write(dir.join("real/lib/helper.lang"), "$loads = ($loads || 0) + 1\n").unwrap();
+        fs::write(dir.join("real/main.lang"), "[require_relative(\"lib/../lib/helper\"), require(\"helper\"), require_relative(\"../link/lib/helper\"), $loads]\n").unwrap();
+        let mut interp = Interpreter::create();
+        interp.add_load_path(&dir.join("link/lib"));
+        let v = interp.eval_file(&dir.join("link/main.lang")).unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "[true, false, false, 1]");
+        let v = interp.eval_source("test", &format!("require_relative \"{}\"", dir.join("link/lib/helper").display())).unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "false");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
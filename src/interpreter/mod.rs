@@ -1 +1,9 @@
-pub mod parser;
\ No newline at end of file
+pub mod builtins;
+pub mod diagnostics;
+pub mod grammar;
+#[allow(clippy::module_inception)]
+pub mod interpreter;
+pub mod parser;
+pub mod runtime;
+pub mod source_map;
+pub mod vm;
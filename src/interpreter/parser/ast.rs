@@ -0,0 +1,281 @@
+use std::fmt;
+use std::rc::Rc;
+
+use super::lexicon::OperatorSymbol;
+use crate::interpreter::vm::inline_cache::InlineCache;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub line: u32,
+    pub col: u32
+}
+
+impl Expr {
+    pub fn create(kind: ExprKind, line: u32, col: u32) -> Expr {
+        Expr { kind, line, col }
+    }
+}
+
+pub type Body = Vec<Expr>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Nil,
+    True,
+    False,
+    SelfRef,
+    // `__FILE__`, `__LINE__` and `__ENCODING__`; the line is the expression's own.
+    File,
+    Line,
+    Encoding,
+    Integer(String),
+    Float(String),
+    // `3r` and `1.5r`, holding the digits.
+    Rational(String),
+    // `2i`, holding the literal it scales.
+    Imaginary(Box<Expr>),
+    Str(String),
+    // A string literal under `# frozen_string_literal: true`.
+    FrozenStr(String),
+    // `/source/flags`, with the source exactly as written between the slashes.
+    Regexp { source: String, flags: String },
+    Symbol(String),
+    Array(Vec<Expr>),
+    Hash(Vec<(Expr, Expr)>),
+    LocalVar(String),
+    InstanceVar(String),
+    GlobalVar(String),
+    Constant { scope: Option<Box<Expr>>, name: String },
+    // `target` is a variable, constant, index or attribute call; `a[i] = v` and `a.b = v`
+    // become calls to `[]=` and `b=` when evaluated.
+    Assign { target: Box<Expr>, value: Box<Expr> },
+    // `a += 1`, `a ||= b`; `op` is the binary operator being applied.
+    OpAssign { target: Box<Expr>, op: OperatorSymbol, value: Box<Expr> },
+    // `cache` remembers the operator methods the tree walker sent here, for operands the
+    // numeric fast paths don't handle.
+    Binary { op: OperatorSymbol, lhs: Box<Expr>, rhs: Box<Expr>, cache: InlineCache },
+    Unary { op: OperatorSymbol, operand: Box<Expr> },
+    And { lhs: Box<Expr>, rhs: Box<Expr> },
+    Or { lhs: Box<Expr>, rhs: Box<Expr> },
+    Not(Box<Expr>),
+    Defined(Box<Expr>),
+    // `cache` remembers the methods the tree walker called here.
+    Call { receiver: Option<Box<Expr>>, name: String, args: Vec<Expr>, block: Option<Rc<Block>>, safe_nav: bool, cache: InlineCache },
+    Splat(Box<Expr>),
+    BlockPass(Box<Expr>),
+    Yield(Vec<Expr>),
+    // `args` is `None` for a bare `super`, which passes the current method's arguments along.
+    Super { args: Option<Vec<Expr>>, block: Option<Rc<Block>> },
+    Return(Option<Box<Expr>>),
+    Break(Option<Box<Expr>>),
+    Next(Option<Box<Expr>>),
+    Redo,
+    Retry,
+    If { cond: Box<Expr>, then_body: Body, else_body: Option<Body> },
+    While { cond: Box<Expr>, body: Body, until: bool },
+    For { var: String, iter: Box<Expr>, body: Body },
+    Case { subject: Option<Box<Expr>>, whens: Vec<(Vec<Expr>, Body)>, else_body: Option<Body> },
+    Begin { body: Body, rescues: Vec<RescueClause>, else_body: Option<Body>, ensure_body: Option<Body> },
+    Sequence(Body),
+    Def { singleton: Option<Box<Expr>>, name: String, params: Params, body: Rc<Expr> },
+    Class { path: Box<Expr>, superclass: Option<Box<Expr>>, body: Body },
+    Module { path: Box<Expr>, body: Body },
+    Lambda(Rc<Block>),
+    // `BEGIN { ... }` runs before the rest of its file; `END { ... }` registers an exit handler
+    // the first time it's reached.
+    BeginBlock(Body),
+    EndBlock(Rc<Block>),
+    Alias { new_name: String, old_name: String },
+    Undef(Vec<String>),
+    // A statement that failed to parse; the error itself is reported alongside the tree.
+    Error
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Params {
+    pub required: Vec<String>,
+    pub optional: Vec<(String, Expr)>,
+    pub rest: Option<String>,
+    pub post: Vec<String>,
+    pub block: Option<String>
+}
+
+impl Params {
+    pub fn names(&self) -> Vec<&str> {
+        let mut out: Vec<&str> = self.required.iter().map(String::as_str).collect();
+        out.extend(self.optional.iter().map(|(n, _)| n.as_str()));
+        out.extend(self.rest.iter().map(String::as_str));
+        out.extend(self.post.iter().map(String::as_str));
+        out.extend(self.block.iter().map(String::as_str));
+        out
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub params: Params,
+    pub body: Body,
+    pub line: u32,
+    pub col: u32
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RescueClause {
+    pub classes: Vec<Expr>,
+    pub var: Option<String>,
+    pub body: Body
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub statements: Body
+}
+
+// Expressions print as S-expressions, `(+ 1 (* 2 3))`, which makes grouping visible.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ExprKind::Nil => write!(f, "nil"),
+            ExprKind::True => write!(f, "true"),
+            ExprKind::False => write!(f, "false"),
+            ExprKind::SelfRef => write!(f, "self"),
+            ExprKind::File => write!(f, "__FILE__"),
+            ExprKind::Line => write!(f, "__LINE__"),
+            ExprKind::Encoding => write!(f, "__ENCODING__"),
+            ExprKind::Integer(v) | ExprKind::Float(v) => write!(f, "{}", v),
+            ExprKind::Rational(v) => write!(f, "{}r", v),
+            ExprKind::Imaginary(v) => write!(f, "{}i", v),
+            ExprKind::Str(v) | ExprKind::FrozenStr(v) => write!(f, "{:?}", v),
+            ExprKind::Regexp { source, flags } => write!(f, "/{}/{}", source, flags),
+            ExprKind::Symbol(v) => write!(f, ":{}", v),
+            ExprKind::Array(items) => write!(f, "[{}]", join(items)),
+            ExprKind::Hash(pairs) => {
+                let pairs: Vec<String> = pairs.iter().map(|(k, v)| format!("{} => {}", k, v)).collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            },
+            ExprKind::LocalVar(name) | ExprKind::InstanceVar(name) | ExprKind::GlobalVar(name) => write!(f, "{}", name),
+            ExprKind::Constant { scope: Some(scope), name } => write!(f, "{}::{}", scope, name),
+            ExprKind::Constant { scope: None, name } => write!(f, "{}", name),
+            ExprKind::Assign { target, value } => write!(f, "(= {} {})", target, value),
+            ExprKind::OpAssign { target, op, value } => write!(f, "({}= {} {})", op.to_str(), target, value),
+            ExprKind::Binary { op, lhs, rhs, .. } => write!(f, "({} {} {})", op.to_str(), lhs, rhs),
+            ExprKind::Unary { op, operand } => write!(f, "({} {})", op.to_str(), operand),
+            ExprKind::And { lhs, rhs } => write!(f, "(and {} {})", lhs, rhs),
+            ExprKind::Or { lhs, rhs } => write!(f, "(or {} {})", lhs, rhs),
+            ExprKind::Not(operand) => write!(f, "(not {})", operand),
+            ExprKind::Defined(operand) => write!(f, "(defined? {})", operand),
+            ExprKind::Call { receiver, name, args, block, safe_nav, .. } => {
+                write!(f, "(")?;
+                if let Some(receiver) = receiver {
+                    write!(f, "{}{}", receiver, if *safe_nav { "&." } else { "." })?;
+                }
+                write!(f, "{}", name)?;
+                for arg in args.iter() {
+                    write!(f, " {}", arg)?;
+                }
+                if let Some(block) = block {
+                    write!(f, " {}", block)?;
+                }
+                write!(f, ")")
+            },
+            ExprKind::Splat(inner) => write!(f, "*{}", inner),
+            ExprKind::BlockPass(inner) => write!(f, "&{}", inner),
+            ExprKind::Yield(args) => write!(f, "(yield{})", prefixed(args)),
+            ExprKind::Super { args: None, block } => write!(f, "(zsuper{})", optional(block)),
+            ExprKind::Super { args: Some(args), block } => write!(f, "(super{}{})", prefixed(args), optional(block)),
+            ExprKind::Return(value) => write!(f, "(return{})", optional(value)),
+            ExprKind::Break(value) => write!(f, "(break{})", optional(value)),
+            ExprKind::Next(value) => write!(f, "(next{})", optional(value)),
+            ExprKind::Redo => write!(f, "(redo)"),
+            ExprKind::Retry => write!(f, "(retry)"),
+            ExprKind::If { cond, then_body, else_body } => {
+                write!(f, "(if {} ({})", cond, join(then_body))?;
+                if let Some(else_body) = else_body {
+                    write!(f, " ({})", join(else_body))?;
+                }
+                write!(f, ")")
+            },
+            ExprKind::While { cond, body, until } => write!(f, "({} {} ({}))", if *until { "until" } else { "while" }, cond, join(body)),
+            ExprKind::For { var, iter, body } => write!(f, "(for {} {} ({}))", var, iter, join(body)),
+            ExprKind::Case { subject, whens, else_body } => {
+                write!(f, "(case{}", optional(subject))?;
+                for (tests, body) in whens.iter() {
+                    write!(f, " (when ({}) ({}))", join(tests), join(body))?;
+                }
+                if let Some(else_body) = else_body {
+                    write!(f, " (else {})", join(else_body))?;
+                }
+                write!(f, ")")
+            },
+            ExprKind::Begin { body, rescues, else_body, ensure_body } => {
+                write!(f, "(begin ({})", join(body))?;
+                for rescue in rescues.iter() {
+                    write!(f, " (rescue ({})", join(&rescue.classes))?;
+                    if let Some(var) = &rescue.var {
+                        write!(f, " => {}", var)?;
+                    }
+                    write!(f, " ({}))", join(&rescue.body))?;
+                }
+                if let Some(else_body) = else_body {
+                    write!(f, " (else {})", join(else_body))?;
+                }
+                if let Some(ensure_body) = ensure_body {
+                    write!(f, " (ensure {})", join(ensure_body))?;
+                }
+                write!(f, ")")
+            },
+            ExprKind::Sequence(body) => write!(f, "({})", join(body)),
+            ExprKind::Def { singleton, name, params, body } => match singleton {
+                Some(on) => write!(f, "(def {}.{} ({}) {})", on, name, params, body),
+                None => write!(f, "(def {} ({}) {})", name, params, body)
+            },
+            ExprKind::Class { path, superclass, body } => write!(f, "(class {}{} ({}))", path, optional(superclass), join(body)),
+            ExprKind::Module { path, body } => write!(f, "(module {} ({}))", path, join(body)),
+            ExprKind::Lambda(block) => write!(f, "(lambda {})", block),
+            ExprKind::BeginBlock(body) => write!(f, "(BEGIN ({}))", join(body)),
+            ExprKind::EndBlock(block) => write!(f, "(END {})", block),
+            ExprKind::Alias { new_name, old_name } => write!(f, "(alias {} {})", new_name, old_name),
+            ExprKind::Undef(names) => write!(f, "(undef {})", names.join(" ")),
+            ExprKind::Error => write!(f, "(error)")
+        }
+    }
+}
+
+impl fmt::Display for Params {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts: Vec<String> = self.required.clone();
+        parts.extend(self.optional.iter().map(|(name, default)| format!("{}={}", name, default)));
+        parts.extend(self.rest.iter().map(|name| format!("*{}", name)));
+        parts.extend(self.post.iter().cloned());
+        parts.extend(self.block.iter().map(|name| format!("&{}", name)));
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{|{}| {}}}", self.params, join(&self.body))
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for statement in self.statements.iter() {
+            writeln!(f, "{}", statement)?;
+        }
+        Ok(())
+    }
+}
+
+fn join(exprs: &[Expr]) -> String {
+    exprs.iter().map(Expr::to_string).collect::<Vec<String>>().join(" ")
+}
+
+fn prefixed(exprs: &[Expr]) -> String {
+    exprs.iter().map(|e| format!(" {}", e)).collect()
+}
+
+fn optional<E: fmt::Display>(expr: &Option<E>) -> String {
+    expr.as_ref().map_or(String::new(), |e| format!(" {}", e))
+}
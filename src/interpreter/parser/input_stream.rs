@@ -1,40 +1,64 @@
-use std::iter::Iterator;
-use std::iter::Peekable;
-use std::str::Chars;
-
-pub struct InputStream<'a> {
-    line: u32,
-    col: u32,
-    data: Peekable<Chars<'a>>
-}
-
-impl<'a> InputStream<'a> {
-    pub fn from_string(d: &'a String) -> InputStream<'a> {
-        let test = d.chars().into_iter().peekable().clone();
-        InputStream { line: 1, col: 1, data: test }
-    }
-    pub fn get_line(&self) -> u32 { self.line }
-    pub fn get_col(&self) -> u32 { self.col }
-    pub fn next(&mut self) -> Option<char> {
-        match self.data.next() {
-            Some(ch) => {
-                if ch == '\n' {
-                    self.line += 1;
-                    self.col = 1;
-                } else {
-                    self.col += 1;
-                }
-                Some(ch)
-            },
-            None => {
-                None
-            }
-        }
-    }
-    pub fn peek(&mut self) -> Option<&char> {
-        self.data.peek()
-    }
-    pub fn is_eof(&mut self) -> bool {
-        self.data.peek().is_none()
-    }
-}
\ No newline at end of file
+use std::collections::VecDeque;
+use std::str::Chars;
+use crate::interpreter::source_map::FileId;
+
+pub struct InputStream<'a> {
+    file: FileId,
+    line: u32,
+    col: u32,
+    data: Chars<'a>,
+    lookahead: VecDeque<char>
+}
+
+impl<'a> InputStream<'a> {
+    pub fn from_string(d: &'a str) -> InputStream<'a> {
+        InputStream::for_file(FileId::ANONYMOUS, d)
+    }
+    pub fn for_file(file: FileId, d: &'a str) -> InputStream<'a> {
+        InputStream { file, line: 1, col: 1, data: d.chars(), lookahead: VecDeque::new() }
+    }
+    pub fn get_file(&self) -> FileId { self.file }
+    pub fn get_line(&self) -> u32 { self.line }
+    pub fn get_col(&self) -> u32 { self.col }
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<char> {
+        let next = match self.lookahead.pop_front() {
+            Some(ch) => Some(ch),
+            None => self.data.next()
+        };
+        match next {
+            Some(ch) => {
+                if ch == '\n' {
+                    self.line += 1;
+                    self.col = 1;
+                } else {
+                    self.col += 1;
+                }
+                Some(ch)
+            },
+            None => {
+                None
+            }
+        }
+    }
+    pub fn peek(&mut self) -> Option<&char> {
+        self.fill_lookahead(1);
+        self.lookahead.front()
+    }
+    pub fn peek_nth(&mut self, n: usize) -> Option<char> {
+        self.fill_lookahead(n + 1);
+        self.lookahead.get(n).copied()
+    }
+    pub fn is_eof(&mut self) -> bool {
+        self.peek().is_none()
+    }
+
+    fn fill_lookahead(&mut self, n: usize) {
+        while self.lookahead.len() < n {
+            match self.data.next() {
+                Some(ch) => self.lookahead.push_back(ch),
+                None => break
+            }
+        }
+    }
+}
@@ -1,3 +1,7 @@
-pub mod input_stream;
-pub mod lexicon;
-pub mod token_stream;
\ No newline at end of file
+pub mod ast;
+pub mod input_stream;
+pub mod lexicon;
+#[allow(clippy::module_inception)]
+pub mod parser;
+pub mod precedence;
+pub mod token_stream;
@@ -0,0 +1,1652 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::interpreter::diagnostics::{Diagnostic, Severity, Span};
+use crate::interpreter::source_map::FileId;
+use crate::interpreter::vm::inline_cache::InlineCache;
+use super::ast::{Block, Body, Expr, ExprKind, Params, Program, RescueClause};
+use super::lexicon::{IdentifierSymbol, KeywordSymbol, OperatorSymbol, SeparatorSymbol, TokenKind};
+use super::precedence::{self, Associativity, Fixity, OperatorSpec, Precedence, PrecedenceTable};
+use super::token_stream::{LexMode, TokenStream};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    message: String,
+    file: FileId,
+    line: u32,
+    col: u32,
+    len: u32,
+    expected: Vec<String>
+}
+
+impl ParseError {
+    pub fn create(message: String, line: u32, col: u32) -> ParseError {
+        ParseError { message, file: FileId::ANONYMOUS, line, col, len: 1, expected: Vec::new() }
+    }
+    pub fn get_message(&self) -> &str { &self.message }
+    pub fn get_file(&self) -> FileId { self.file }
+    pub fn get_line(&self) -> u32 { self.line }
+    pub fn get_col(&self) -> u32 { self.col }
+    // What the parser would have accepted at this position, when the error is an unexpected token.
+    pub fn get_expected(&self) -> &[String] { &self.expected }
+
+    fn describe_expected(&self) -> Option<String> {
+        match self.expected.as_slice() {
+            [] => None,
+            [one] => Some(format!("expected {}", one)),
+            many => Some(format!("expected one of {}", many.join(", ")))
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let label = self.describe_expected().unwrap_or_default();
+        Diagnostic::error(self.message.clone()).with_label(Span::create(self.file, self.line, self.col, self.len), &label)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(expected) = self.describe_expected() {
+            write!(f, ", {}", expected)?;
+        }
+        write!(f, " at [l: {}, c: {}]", self.line, self.col)
+    }
+}
+
+type ParseResult<T> = Result<T, ParseError>;
+
+// The result of parsing a whole file: every syntax error found, and the tree built around them.
+// Statements that failed to parse appear in the tree as `ExprKind::Error` nodes.
+#[derive(Debug)]
+pub struct ParseOutput {
+    pub program: Program,
+    pub errors: Vec<ParseError>,
+    pub lexer_diagnostics: Vec<Diagnostic>
+}
+
+impl ParseOutput {
+    // Lexer and parser problems together, in source order.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut all: Vec<Diagnostic> = self.lexer_diagnostics.clone();
+        all.extend(self.errors.iter().map(ParseError::to_diagnostic));
+        all.sort_by_key(|d| d.primary_span().map(|s| (s.line, s.col)));
+        all
+    }
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty() || self.lexer_diagnostics.iter().any(|d| d.get_severity() == Severity::Error)
+    }
+}
+
+// A token as the parser sees it: owned, with a note of whether whitespace preceded it, which
+// separates `foo -1` (a call with a negative argument) from `foo - 1` and `foo[1]` from `foo [1]`.
+#[derive(Debug, Clone)]
+struct Lexeme {
+    kind: TokenKind,
+    value: String,
+    line: u32,
+    col: u32,
+    space_before: bool,
+    // Brackets the lexer had open before this token.
+    depth: usize
+}
+
+impl Lexeme {
+    // Columns the token spans in the source, including quotes and sigils the value omits.
+    fn source_len(&self) -> u32 {
+        let len = self.value.chars().count() as u32;
+        match self.kind {
+            TokenKind::Identifier(IdentifierSymbol::STRING) => len + 2,
+            TokenKind::Identifier(IdentifierSymbol::SYMBOL) => len + 1,
+            _ => len
+        }
+    }
+}
+
+struct Scope {
+    locals: HashSet<String>,
+    // Blocks see the locals of the scope they're written in; `def`, `class` and `module` don't.
+    inherits: bool
+}
+
+pub struct Parser<'a, 'b> {
+    token_stream: &'b mut TokenStream<'a>,
+    lookahead: VecDeque<Lexeme>,
+    last_read: Option<(u32, u32)>,
+    last_pos: (u32, u32),
+    table: PrecedenceTable,
+    scopes: Vec<Scope>,
+    errors: Vec<ParseError>,
+    lexer_diagnostics: Vec<Diagnostic>,
+    // Inside a `while` condition or the arguments of a command call, `do` belongs to the
+    // enclosing construct rather than to the innermost call.
+    no_do: usize,
+    // Inside an operand of an operator, a call takes no arguments without parentheses: `1 + f 2`
+    // is an error, as in Ruby, rather than `1 + f(2)`.
+    no_command: usize,
+    // Comments before the first token may hold magic comments such as
+    // `# frozen_string_literal: true`, which makes every string literal in the file frozen.
+    in_header: bool,
+    frozen_strings: bool
+}
+
+impl<'a, 'b> Parser<'a, 'b> {
+    pub fn create(ts: &'b mut TokenStream<'a>) -> Parser<'a, 'b> {
+        Parser::with_table(ts, PrecedenceTable::default())
+    }
+    pub fn with_table(ts: &'b mut TokenStream<'a>, table: PrecedenceTable) -> Parser<'a, 'b> {
+        Parser {
+            token_stream: ts,
+            lookahead: VecDeque::new(),
+            last_read: None,
+            last_pos: (1, 1),
+            table,
+            scopes: vec![Scope { locals: HashSet::new(), inherits: false }],
+            errors: Vec::new(),
+            lexer_diagnostics: Vec::new(),
+            no_do: 0,
+            no_command: 0,
+            in_header: true,
+            frozen_strings: false
+        }
+    }
+
+    // -- token access --
+
+    fn fill(&mut self, n: usize) {
+        while self.lookahead.len() < n {
+            let depth = self.token_stream.get_bracket_depth();
+            let tok = match self.token_stream.read_next() {
+                Some(tok) => tok,
+                None => {
+                    self.lexer_diagnostics.extend(self.token_stream.take_diagnostics());
+                    break;
+                }
+            };
+            self.lexer_diagnostics.extend(self.token_stream.take_diagnostics());
+            let kind = tok.get_data().get_kind();
+            if kind == TokenKind::Identifier(IdentifierSymbol::COMMENT) {
+                if self.in_header {
+                    self.read_magic_comment(tok.get_data().get_value());
+                }
+                self.last_read = None;
+                continue;
+            }
+            self.in_header = false;
+            if kind == TokenKind::Identifier(IdentifierSymbol::VARIABLE) && self.is_local(tok.get_data().get_value()) {
+                self.token_stream.mark_local_variable();
+            }
+            let mut lexeme = Lexeme {
+                kind,
+                value: tok.get_data().get_value().clone(),
+                line: tok.get_line(),
+                col: tok.get_col(),
+                space_before: true,
+                depth
+            };
+            if let Some((line, end)) = self.last_read {
+                lexeme.space_before = line != lexeme.line || end < lexeme.col;
+            }
+            self.last_read = Some((lexeme.line, lexeme.col + lexeme.source_len()));
+            self.lookahead.push_back(lexeme);
+        }
+    }
+    fn read_magic_comment(&mut self, comment: &str) {
+        let text = comment.trim_start_matches('#').trim().trim_start_matches("-*-").trim_end_matches("-*-").trim();
+        if let Some((key, value)) = text.split_once(':') {
+            if key.trim().replace('-', "_").eq_ignore_ascii_case("frozen_string_literal") {
+                self.frozen_strings = value.trim().eq_ignore_ascii_case("true");
+            }
+        }
+    }
+    fn peek(&mut self) -> Option<&Lexeme> {
+        self.fill(1);
+        self.lookahead.front()
+    }
+    fn peek_nth(&mut self, n: usize) -> Option<&Lexeme> {
+        self.fill(n + 1);
+        self.lookahead.get(n)
+    }
+    // Switches the lexer's mode for the tokens after the ones already read, so only the current
+    // token may have been looked at; the parser peeks no further ahead of a `|` or a block.
+    fn push_lex_mode(&mut self, mode: LexMode) {
+        debug_assert!(self.lookahead.is_empty(), "tokens were read before a lexer mode switch");
+        self.token_stream.push_mode(mode);
+    }
+    fn peek_kind(&mut self) -> Option<TokenKind> {
+        self.peek().map(|t| t.kind)
+    }
+    fn peek_is(&mut self, kind: TokenKind) -> bool {
+        self.peek_kind() == Some(kind)
+    }
+    fn next(&mut self) -> Option<Lexeme> {
+        self.fill(1);
+        let tok = self.lookahead.pop_front();
+        if let Some(t) = &tok {
+            self.last_pos = (t.line, t.col + t.source_len());
+        }
+        tok
+    }
+    fn accept(&mut self, kind: TokenKind) -> bool {
+        if self.peek_is(kind) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+    fn expect(&mut self, kind: TokenKind, what: &str) -> ParseResult<Lexeme> {
+        if self.peek_is(kind) {
+            Ok(self.next().unwrap())
+        } else {
+            Err(self.unexpected(&[what]))
+        }
+    }
+    fn error_here(&mut self, message: String) -> ParseError {
+        let (line, col, len) = match self.peek() {
+            Some(tok) => (tok.line, tok.col, tok.source_len()),
+            None => (self.last_pos.0, self.last_pos.1, 1)
+        };
+        let mut error = ParseError::create(message, line, col);
+        error.len = len;
+        error
+    }
+    fn unexpected(&mut self, expected: &[&str]) -> ParseError {
+        let found = match self.peek() {
+            Some(tok) if tok.kind == TokenKind::Separator(SeparatorSymbol::NEWLINE) => String::from("unexpected end of line"),
+            Some(tok) => format!("unexpected '{}'", tok.value.escape_default()),
+            None => String::from("unexpected end of input")
+        };
+        let mut error = self.error_here(found);
+        error.expected = expected.iter().map(|e| e.to_string()).collect();
+        error
+    }
+
+    fn at_terminator(&mut self) -> bool {
+        matches!(self.peek_kind(), Some(TokenKind::Separator(SeparatorSymbol::NEWLINE)) | Some(TokenKind::Separator(SeparatorSymbol::SEMICOLON)))
+    }
+    fn skip_terminators(&mut self) {
+        while self.at_terminator() { self.next(); }
+    }
+
+    // -- local variable scopes --
+
+    fn push_scope(&mut self, inherits: bool) {
+        self.scopes.push(Scope { locals: HashSet::new(), inherits });
+    }
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+    fn declare(&mut self, name: &str) {
+        self.scopes.last_mut().unwrap().locals.insert(name.to_string());
+    }
+    fn is_local(&self, name: &str) -> bool {
+        for scope in self.scopes.iter().rev() {
+            if scope.locals.contains(name) { return true; }
+            if !scope.inherits { return false; }
+        }
+        false
+    }
+
+    // -- statements --
+
+    // Parses the whole input, recovering from syntax errors so that all of them are reported.
+    pub fn parse(&mut self) -> ParseOutput {
+        let statements = self.parse_statements(&[]);
+        let file = self.token_stream.get_file();
+        let mut errors = std::mem::take(&mut self.errors);
+        for error in errors.iter_mut() {
+            error.file = file;
+        }
+        ParseOutput {
+            program: Program { statements },
+            errors,
+            lexer_diagnostics: std::mem::take(&mut self.lexer_diagnostics)
+        }
+    }
+
+    // Parses the whole input, failing with the first syntax error.
+    pub fn parse_program(&mut self) -> ParseResult<Program> {
+        let output = self.parse();
+        let lexer_errors = output.lexer_diagnostics.iter().filter(|d| d.get_severity() == Severity::Error).map(|d| {
+            let span = d.primary_span().unwrap_or(Span::create(FileId::ANONYMOUS, 1, 1, 1));
+            let mut error = ParseError::create(d.get_message().to_string(), span.line, span.col);
+            error.file = span.file;
+            error
+        });
+        let mut errors: Vec<ParseError> = lexer_errors.chain(output.errors).collect();
+        errors.sort_by_key(|e| (e.line, e.col));
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(output.program)
+        }
+    }
+
+    // Statements up to (not including) one of the `until` tokens or the end of input. A statement
+    // that fails to parse is recorded, replaced by an error node, and skipped.
+    fn parse_statements(&mut self, until: &[TokenKind]) -> Body {
+        let saved_no_command = std::mem::take(&mut self.no_command);
+        let body = self.parse_statements_inner(until);
+        self.no_command = saved_no_command;
+        body
+    }
+    fn parse_statements_inner(&mut self, until: &[TokenKind]) -> Body {
+        let mut body: Body = Vec::new();
+        loop {
+            self.skip_terminators();
+            match self.peek_kind() {
+                None => break,
+                Some(kind) if until.contains(&kind) => break,
+                _ => {}
+            }
+            let (line, col, depth) = self.peek().map(|t| (t.line, t.col, t.depth)).unwrap();
+            match self.parse_statement() {
+                Ok(statement) => body.push(statement),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize(until, depth);
+                    body.push(Expr::create(ExprKind::Error, line, col));
+                    continue;
+                }
+            }
+            match self.peek_kind() {
+                None => break,
+                Some(kind) if until.contains(&kind) => break,
+                _ if self.at_terminator() => {},
+                _ => {
+                    let mut expected = vec![String::from("a newline"), String::from("';'")];
+                    expected.extend(until.iter().map(|kind| kind.describe()));
+                    let expected: Vec<&str> = expected.iter().map(String::as_str).collect();
+                    let error = self.unexpected(&expected);
+                    self.errors.push(error);
+                    self.synchronize(until, depth);
+                }
+            }
+        }
+        body
+    }
+
+    // Panic-mode recovery: skips to the end of the broken statement, which is the next newline or
+    // `;`, a token that closes the enclosing construct, or an `end` that most likely closes a
+    // construct the error interrupted. Brackets the statement left open are closed first, or the
+    // lexer would go on swallowing the line breaks that end it and the statements after it; a
+    // token already read inside them that starts a line is taken to start the next statement.
+    fn synchronize(&mut self, until: &[TokenKind], depth: usize) {
+        self.token_stream.close_brackets(depth);
+        while let Some((kind, line, inside)) = self.peek().map(|t| (t.kind, t.line, t.depth > depth)) {
+            if until.contains(&kind) || (inside && line > self.last_pos.0) { return; }
+            self.next();
+            match kind {
+                TokenKind::Separator(SeparatorSymbol::NEWLINE)
+                | TokenKind::Separator(SeparatorSymbol::SEMICOLON)
+                | TokenKind::Keyword(KeywordSymbol::END) => return,
+                _ => {}
+            }
+        }
+    }
+
+    fn parse_statement(&mut self) -> ParseResult<Expr> {
+        self.parse_expr(Precedence::Lowest.binding_power())
+    }
+
+    // -- Pratt core --
+
+    fn parse_expr(&mut self, min_bp: u8) -> ParseResult<Expr> {
+        let mut lhs = self.parse_prefix()?;
+        // A variable directly followed by `=` is assigned to whatever binds around it, as in
+        // `1 + x = 2`.
+        if let Some(kind) = self.peek_kind().filter(|kind| is_assignment(*kind) && is_assignable(&lhs)) {
+            if let Some(spec) = self.table.get(kind, Fixity::Infix) {
+                lhs = self.parse_infix(lhs, kind, spec)?;
+            }
+        }
+        while let Some((kind, space_before)) = self.peek().map(|tok| (tok.kind, tok.space_before)) {
+            if let Some(spec) = self.table.get(kind, Fixity::Postfix) {
+                // `foo [1]` passes an array to `foo`; only `foo[1]` indexes.
+                let indexing = kind == TokenKind::Separator(SeparatorSymbol::L_BRACKET);
+                if spec.precedence.binding_power() < min_bp || (indexing && space_before && !is_variable(&lhs)) { break; }
+                lhs = self.parse_postfix(lhs, kind)?;
+                continue;
+            }
+            if let Some(spec) = self.table.get(kind, Fixity::Infix) {
+                if spec.precedence.binding_power() < min_bp { break; }
+                lhs = self.parse_infix(lhs, kind, spec)?;
+                if spec.associativity == Associativity::NonAssoc {
+                    if let Some(next) = self.peek_kind().and_then(|k| self.table.get(k, Fixity::Infix)) {
+                        if next.precedence == spec.precedence {
+                            return Err(self.error_here(String::from("equality and range operators don't chain")));
+                        }
+                    }
+                }
+                continue;
+            }
+            break;
+        }
+        Ok(lhs)
+    }
+
+    fn parse_prefix(&mut self) -> ParseResult<Expr> {
+        let tok = match self.peek() {
+            Some(tok) => tok.clone(),
+            None => return Err(self.unexpected(&["an expression"]))
+        };
+        // `-2.abs` is `(-2).abs`: a minus written against a number makes a negative literal,
+        // except before `**`, so that `-2 ** 2` is still `-(2 ** 2)`.
+        if tok.kind == TokenKind::Operator(OperatorSymbol::MINUS) {
+            let literal = match self.peek_nth(1) {
+                Some(t) if !t.space_before => match t.kind {
+                    TokenKind::Identifier(sym @ (IdentifierSymbol::INT | IdentifierSymbol::FLOAT | IdentifierSymbol::RATIONAL | IdentifierSymbol::IMAGINARY)) => Some(sym),
+                    _ => None
+                },
+                _ => None
+            };
+            if let Some(sym) = literal {
+                if !matches!(self.peek_nth(2), Some(t) if t.kind == TokenKind::Operator(OperatorSymbol::POW)) {
+                    self.next();
+                    let operand = self.parse_identifier(sym)?;
+                    return Ok(negative(operand, tok.line, tok.col));
+                }
+            }
+        }
+        // `defined?(x)` is a primary, like a call with parentheses, so `defined?(x).nil?` and
+        // `defined?(x) && x` apply to its result; only `defined? x` takes the rest of the expression.
+        if tok.kind == TokenKind::Keyword(KeywordSymbol::DEFINED_P)
+            && matches!(self.peek_nth(1), Some(t) if t.kind == TokenKind::Separator(SeparatorSymbol::L_PAREN) && !t.space_before) {
+            self.next();
+            let operand = self.parse_prefix()?;
+            return Ok(Expr::create(ExprKind::Defined(Box::new(operand)), tok.line, tok.col));
+        }
+        if let Some(spec) = self.table.get(tok.kind, Fixity::Prefix) {
+            self.next();
+            let operand = match tok.kind {
+                TokenKind::Operator(OperatorSymbol::BANG) => self.parse_expr(spec.precedence.binding_power())?,
+                kind => self.parse_operand(spec.precedence.binding_power(), kind)?
+            };
+            return Ok(match tok.kind {
+                TokenKind::Keyword(KeywordSymbol::NOT) | TokenKind::Operator(OperatorSymbol::BANG) => {
+                    Expr::create(ExprKind::Not(Box::new(operand)), tok.line, tok.col)
+                },
+                TokenKind::Keyword(KeywordSymbol::DEFINED_P) => Expr::create(ExprKind::Defined(Box::new(operand)), tok.line, tok.col),
+                TokenKind::Operator(OperatorSymbol::MINUS) => negative(operand, tok.line, tok.col),
+                TokenKind::Operator(op) => Expr::create(ExprKind::Unary { op, operand: Box::new(operand) }, tok.line, tok.col),
+                _ => return Err(ParseError::create(format!("'{}' can't be used as a prefix operator", tok.value), tok.line, tok.col))
+            });
+        }
+        match tok.kind {
+            TokenKind::Identifier(sym) => self.parse_identifier(sym),
+            TokenKind::Keyword(kw) => self.parse_keyword(kw),
+            TokenKind::Separator(SeparatorSymbol::L_PAREN) => {
+                self.next();
+                let body = self.parse_statements(&[TokenKind::Separator(SeparatorSymbol::R_PAREN)]);
+                self.expect(TokenKind::Separator(SeparatorSymbol::R_PAREN), "')'")?;
+                Ok(match body.len() {
+                    0 => Expr::create(ExprKind::Nil, tok.line, tok.col),
+                    1 => body.into_iter().next().unwrap(),
+                    _ => Expr::create(ExprKind::Sequence(body), tok.line, tok.col)
+                })
+            },
+            TokenKind::Separator(SeparatorSymbol::L_BRACKET) => {
+                self.next();
+                let items = self.parse_args_until(TokenKind::Separator(SeparatorSymbol::R_BRACKET))?;
+                Ok(Expr::create(ExprKind::Array(items), tok.line, tok.col))
+            },
+            TokenKind::Separator(SeparatorSymbol::L_BRACE) => self.parse_hash(),
+            // `|a, b| do ... end` is shorthand for a lambda. The opening bar was lexed as an
+            // operator, and `||` is an empty parameter list.
+            TokenKind::Operator(OperatorSymbol::BIT_OR) | TokenKind::Operator(OperatorSymbol::OR) => {
+                self.next();
+                self.push_scope(true);
+                let params = if tok.kind == TokenKind::Operator(OperatorSymbol::OR) {
+                    Ok(Params::default())
+                } else {
+                    self.push_lex_mode(LexMode::BlockParams);
+                    self.parse_block_params()
+                };
+                let block = params.and_then(|params| self.parse_block_body(params, tok.line, tok.col));
+                self.pop_scope();
+                Ok(Expr::create(ExprKind::Lambda(Rc::new(block?)), tok.line, tok.col))
+            },
+            TokenKind::Operator(OperatorSymbol::ARROW) => self.parse_lambda(),
+            TokenKind::Operator(OperatorSymbol::RESOLUTION) => {
+                self.next();
+                let name = self.expect_constant_name()?;
+                Ok(Expr::create(ExprKind::Constant { scope: None, name }, tok.line, tok.col))
+            },
+            _ => Err(self.unexpected(&["an expression"]))
+        }
+    }
+
+    fn parse_infix(&mut self, lhs: Expr, kind: TokenKind, spec: OperatorSpec) -> ParseResult<Expr> {
+        let tok = self.next().unwrap();
+        let (line, col) = (tok.line, tok.col);
+        match kind {
+            TokenKind::Keyword(KeywordSymbol::IF) | TokenKind::Keyword(KeywordSymbol::UNLESS) => {
+                let cond = self.parse_expr(spec.right_binding_power())?;
+                let cond = if kind == TokenKind::Keyword(KeywordSymbol::UNLESS) { negate(cond) } else { cond };
+                Ok(Expr::create(ExprKind::If { cond: Box::new(cond), then_body: vec![lhs], else_body: None }, line, col))
+            },
+            TokenKind::Keyword(KeywordSymbol::WHILE) | TokenKind::Keyword(KeywordSymbol::UNTIL) => {
+                let cond = self.parse_expr(spec.right_binding_power())?;
+                let until = kind == TokenKind::Keyword(KeywordSymbol::UNTIL);
+                Ok(Expr::create(ExprKind::While { cond: Box::new(cond), body: vec![lhs], until }, line, col))
+            },
+            TokenKind::Keyword(KeywordSymbol::RESCUE) => {
+                let fallback = self.parse_expr(spec.right_binding_power())?;
+                let rescue = RescueClause { classes: Vec::new(), var: None, body: vec![fallback] };
+                Ok(Expr::create(ExprKind::Begin { body: vec![lhs], rescues: vec![rescue], else_body: None, ensure_body: None }, line, col))
+            },
+            TokenKind::Keyword(KeywordSymbol::AND) | TokenKind::Operator(OperatorSymbol::AND) => {
+                let rhs = self.parse_operand(spec.right_binding_power(), kind)?;
+                Ok(Expr::create(ExprKind::And { lhs: Box::new(lhs), rhs: Box::new(rhs) }, line, col))
+            },
+            TokenKind::Keyword(KeywordSymbol::OR) | TokenKind::Operator(OperatorSymbol::OR) => {
+                let rhs = self.parse_operand(spec.right_binding_power(), kind)?;
+                Ok(Expr::create(ExprKind::Or { lhs: Box::new(lhs), rhs: Box::new(rhs) }, line, col))
+            },
+            TokenKind::Operator(OperatorSymbol::ASSIGN) => {
+                let target = self.assignment_target(lhs)?;
+                let value = self.parse_assigned_value(spec)?;
+                Ok(Expr::create(ExprKind::Assign { target: Box::new(target), value: Box::new(value) }, line, col))
+            },
+            TokenKind::Operator(op) if precedence::compound_base(op).is_some() => {
+                let target = self.assignment_target(lhs)?;
+                let value = self.parse_assigned_value(spec)?;
+                let op = precedence::compound_base(op).unwrap();
+                Ok(Expr::create(ExprKind::OpAssign { target: Box::new(target), op, value: Box::new(value) }, line, col))
+            },
+            TokenKind::Operator(op) => {
+                let rhs = self.parse_operand(spec.right_binding_power(), kind)?;
+                Ok(Expr::create(ExprKind::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs), cache: InlineCache::default() }, line, col))
+            },
+            _ => Err(ParseError::create(format!("'{}' can't be used as an infix operator", tok.value), line, col))
+        }
+    }
+
+    // The right operand of `kind`. After an operator, unlike a keyword such as `and`, it can't
+    // be a command call.
+    fn parse_operand(&mut self, min_bp: u8, kind: TokenKind) -> ParseResult<Expr> {
+        let operator = matches!(kind, TokenKind::Operator(_));
+        self.no_command += operator as usize;
+        let operand = self.parse_expr(min_bp);
+        self.no_command -= operator as usize;
+        operand
+    }
+
+    // The right side of `=` may be a bare list, `a = 1, 2`, which builds an array.
+    fn parse_assigned_value(&mut self, spec: OperatorSpec) -> ParseResult<Expr> {
+        let first = self.parse_expr(spec.right_binding_power())?;
+        if !self.peek_is(TokenKind::Separator(SeparatorSymbol::COMMA)) || self.no_do > 0 {
+            return Ok(first);
+        }
+        let (line, col) = (first.line, first.col);
+        let mut items = vec![first];
+        while self.accept(TokenKind::Separator(SeparatorSymbol::COMMA)) {
+            items.push(self.parse_arg()?);
+        }
+        Ok(Expr::create(ExprKind::Array(items), line, col))
+    }
+
+    fn assignment_target(&mut self, lhs: Expr) -> ParseResult<Expr> {
+        let (line, col) = (lhs.line, lhs.col);
+        match lhs.kind {
+            ExprKind::LocalVar(ref name) => { let name = name.clone(); self.declare(&name); Ok(lhs) },
+            ExprKind::Call { receiver: None, ref name, ref args, block: None, .. } if args.is_empty() && is_local_name(name) => {
+                let name = name.clone();
+                self.declare(&name);
+                Ok(Expr::create(ExprKind::LocalVar(name), line, col))
+            },
+            ExprKind::InstanceVar(_) | ExprKind::GlobalVar(_) | ExprKind::Constant { .. } => Ok(lhs),
+            ExprKind::Call { receiver: Some(_), block: None, .. } => Ok(lhs),
+            _ => Err(ParseError::create(String::from("invalid assignment target"), line, col))
+        }
+    }
+
+    fn parse_postfix(&mut self, lhs: Expr, kind: TokenKind) -> ParseResult<Expr> {
+        let tok = self.next().unwrap();
+        match kind {
+            TokenKind::Separator(SeparatorSymbol::L_BRACKET) => {
+                let args = self.parse_args_until(TokenKind::Separator(SeparatorSymbol::R_BRACKET))?;
+                Ok(Expr::create(ExprKind::Call { receiver: Some(Box::new(lhs)), name: String::from("[]"), args, block: None, safe_nav: false, cache: InlineCache::default() }, tok.line, tok.col))
+            },
+            TokenKind::Operator(OperatorSymbol::RESOLUTION) => {
+                let is_constant = matches!(self.peek(), Some(t) if t.kind == TokenKind::Identifier(IdentifierSymbol::VARIABLE) && is_constant_name(&t.value));
+                let adjacent_paren = matches!(self.peek_nth(1), Some(t) if t.kind == TokenKind::Separator(SeparatorSymbol::L_PAREN) && !t.space_before);
+                if is_constant && !adjacent_paren {
+                    let name = self.next().unwrap().value;
+                    return Ok(Expr::create(ExprKind::Constant { scope: Some(Box::new(lhs)), name }, tok.line, tok.col));
+                }
+                let name = self.parse_method_name()?;
+                self.parse_call_rest(Some(lhs), name, tok.line, tok.col, false)
+            },
+            _ => {
+                let safe_nav = kind == TokenKind::Operator(OperatorSymbol::SAFE_NAV);
+                // `recv.()` calls a proc.
+                if self.peek_is(TokenKind::Separator(SeparatorSymbol::L_PAREN)) {
+                    return self.parse_call_rest(Some(lhs), String::from("call"), tok.line, tok.col, safe_nav);
+                }
+                let name = self.parse_method_name()?;
+                self.parse_call_rest(Some(lhs), name, tok.line, tok.col, safe_nav)
+            }
+        }
+    }
+
+    fn parse_method_name(&mut self) -> ParseResult<String> {
+        match self.peek_kind() {
+            Some(TokenKind::Identifier(IdentifierSymbol::VARIABLE)) | Some(TokenKind::Keyword(_)) => Ok(self.next().unwrap().value),
+            Some(TokenKind::Operator(_)) => Ok(self.next().unwrap().value),
+            _ => Err(self.unexpected(&["a method name"]))
+        }
+    }
+
+    // -- primaries --
+
+    fn parse_identifier(&mut self, sym: IdentifierSymbol) -> ParseResult<Expr> {
+        let tok = self.next().unwrap();
+        let (line, col) = (tok.line, tok.col);
+        let kind = match sym {
+            IdentifierSymbol::INT => ExprKind::Integer(tok.value.replace('_', "")),
+            IdentifierSymbol::FLOAT => ExprKind::Float(tok.value.replace('_', "")),
+            IdentifierSymbol::RATIONAL => ExprKind::Rational(tok.value.replace('_', "").trim_end_matches('r').to_string()),
+            IdentifierSymbol::IMAGINARY => {
+                let digits = tok.value.replace('_', "");
+                let digits = digits.trim_end_matches('i');
+                let kind = match digits.strip_suffix('r') {
+                    Some(rational) => ExprKind::Rational(rational.to_string()),
+                    None if digits.contains('.') => ExprKind::Float(digits.to_string()),
+                    None => ExprKind::Integer(digits.to_string())
+                };
+                ExprKind::Imaginary(Box::new(Expr::create(kind, line, col)))
+            },
+            IdentifierSymbol::STRING if self.frozen_strings => ExprKind::FrozenStr(unescape(&tok.value)),
+            IdentifierSymbol::STRING => ExprKind::Str(unescape(&tok.value)),
+            IdentifierSymbol::REGEXP => {
+                let (source, flags) = tok.value[1..].rsplit_once('/').unwrap_or((&tok.value[1..], ""));
+                ExprKind::Regexp { source: source.to_string(), flags: flags.to_string() }
+            },
+            IdentifierSymbol::SYMBOL => ExprKind::Symbol(tok.value),
+            IdentifierSymbol::INSTANCE_VARIABLE => ExprKind::InstanceVar(tok.value),
+            IdentifierSymbol::GLOBAL_VARIABLE => ExprKind::GlobalVar(tok.value),
+            IdentifierSymbol::COMMENT => unreachable!(),
+            IdentifierSymbol::VARIABLE => {
+                let adjacent_paren = matches!(self.peek(), Some(t) if t.kind == TokenKind::Separator(SeparatorSymbol::L_PAREN) && !t.space_before);
+                if is_constant_name(&tok.value) && !adjacent_paren {
+                    ExprKind::Constant { scope: None, name: tok.value }
+                } else if self.is_local(&tok.value) && !adjacent_paren {
+                    ExprKind::LocalVar(tok.value)
+                } else {
+                    return self.parse_call_rest(None, tok.value, line, col, false);
+                }
+            }
+        };
+        Ok(Expr::create(kind, line, col))
+    }
+
+    // Arguments and block of a call whose receiver and name have been read.
+    fn parse_call_rest(&mut self, receiver: Option<Expr>, name: String, line: u32, col: u32, safe_nav: bool) -> ParseResult<Expr> {
+        let args = self.parse_call_args()?;
+        let block = self.parse_block_if_present()?;
+        Ok(Expr::create(ExprKind::Call { receiver: receiver.map(Box::new), name, args, block, safe_nav, cache: InlineCache::default() }, line, col))
+    }
+
+    fn parse_call_args(&mut self) -> ParseResult<Vec<Expr>> {
+        if let Some(t) = self.peek() {
+            if t.kind == TokenKind::Separator(SeparatorSymbol::L_PAREN) && !t.space_before {
+                self.next();
+                return self.parse_args_until(TokenKind::Separator(SeparatorSymbol::R_PAREN));
+            }
+        }
+        if self.no_command == 0 && self.can_start_command_arg() {
+            self.no_do += 1;
+            let args = self.parse_command_args();
+            self.no_do -= 1;
+            return args;
+        }
+        Ok(Vec::new())
+    }
+
+    fn parse_block_if_present(&mut self) -> ParseResult<Option<Rc<Block>>> {
+        match self.peek_kind() {
+            Some(TokenKind::Separator(SeparatorSymbol::L_BRACE)) => Ok(Some(Rc::new(self.parse_block()?))),
+            Some(TokenKind::Keyword(KeywordSymbol::DO)) if self.no_do == 0 => Ok(Some(Rc::new(self.parse_block()?))),
+            _ => Ok(None)
+        }
+    }
+
+    // Whether the token after a method name begins an argument of a parenthesis-free call such
+    // as `puts x` or `foo -1`, rather than continuing an expression as in `foo - 1`.
+    fn can_start_command_arg(&mut self) -> bool {
+        let tok = match self.peek().cloned() {
+            Some(tok) => tok,
+            None => return false
+        };
+        if !tok.space_before { return false; }
+        // Only looks past the token when it has to: a `{` or `do` here may open a block whose
+        // `|` must be lexed after the parser has switched modes.
+        let tight_after = |parser: &mut Self| parser.peek_nth(1).is_some_and(|a| !a.space_before);
+        match tok.kind {
+            TokenKind::Identifier(_) => true,
+            TokenKind::Keyword(kw) => matches!(kw,
+                KeywordSymbol::NIL | KeywordSymbol::TRUE | KeywordSymbol::FALSE | KeywordSymbol::SELF
+                | KeywordSymbol::NOT | KeywordSymbol::DEFINED_P | KeywordSymbol::SUPER | KeywordSymbol::YIELD
+                | KeywordSymbol::__FILE__ | KeywordSymbol::__LINE__ | KeywordSymbol::__ENCODING__),
+            TokenKind::Operator(OperatorSymbol::MINUS)
+            | TokenKind::Operator(OperatorSymbol::ASTERISK)
+            | TokenKind::Operator(OperatorSymbol::BIT_AND)
+            | TokenKind::Operator(OperatorSymbol::RESOLUTION) => tight_after(self),
+            TokenKind::Operator(OperatorSymbol::BANG)
+            | TokenKind::Operator(OperatorSymbol::BIT_NOT)
+            | TokenKind::Operator(OperatorSymbol::ARROW) => true,
+            TokenKind::Separator(SeparatorSymbol::L_BRACKET)
+            | TokenKind::Separator(SeparatorSymbol::L_PAREN) => true,
+            _ => false
+        }
+    }
+
+    fn parse_command_args(&mut self) -> ParseResult<Vec<Expr>> {
+        let mut args: Vec<Expr> = Vec::new();
+        let mut pairs: Vec<(Expr, Expr)> = Vec::new();
+        loop {
+            self.parse_arg_into(&mut args, &mut pairs)?;
+            if !self.accept(TokenKind::Separator(SeparatorSymbol::COMMA)) { break; }
+        }
+        if !pairs.is_empty() {
+            let (line, col) = (pairs[0].0.line, pairs[0].0.col);
+            args.push(Expr::create(ExprKind::Hash(pairs), line, col));
+        }
+        Ok(args)
+    }
+
+    // Comma-separated arguments up to a closing `)` or `]`, which is consumed.
+    fn parse_args_until(&mut self, close: TokenKind) -> ParseResult<Vec<Expr>> {
+        let saved = (self.no_do, self.no_command);
+        (self.no_do, self.no_command) = (0, 0);
+        let result = self.parse_args_until_inner(close);
+        (self.no_do, self.no_command) = saved;
+        result
+    }
+    fn parse_args_until_inner(&mut self, close: TokenKind) -> ParseResult<Vec<Expr>> {
+        let mut args: Vec<Expr> = Vec::new();
+        let mut pairs: Vec<(Expr, Expr)> = Vec::new();
+        self.skip_newlines();
+        while !self.peek_is(close) {
+            self.parse_arg_into(&mut args, &mut pairs)?;
+            self.skip_newlines();
+            if !self.accept(TokenKind::Separator(SeparatorSymbol::COMMA)) { break; }
+            self.skip_newlines();
+        }
+        let what = if close == TokenKind::Separator(SeparatorSymbol::R_PAREN) { "')'" } else { "']'" };
+        if !self.peek_is(close) {
+            return Err(self.unexpected(&["','", what]));
+        }
+        self.next();
+        if !pairs.is_empty() {
+            let (line, col) = (pairs[0].0.line, pairs[0].0.col);
+            args.push(Expr::create(ExprKind::Hash(pairs), line, col));
+        }
+        Ok(args)
+    }
+    fn skip_newlines(&mut self) {
+        while self.accept(TokenKind::Separator(SeparatorSymbol::NEWLINE)) {}
+    }
+
+    // One argument: a plain expression, `*splat`, `&block`, or a trailing `key: value` /
+    // `key => value` pair collected into an implicit hash.
+    fn parse_arg_into(&mut self, args: &mut Vec<Expr>, pairs: &mut Vec<(Expr, Expr)>) -> ParseResult<()> {
+        if let Some(key) = self.parse_label()? {
+            let value = self.parse_arg()?;
+            pairs.push((key, value));
+            return Ok(());
+        }
+        let arg = self.parse_arg()?;
+        if self.accept(TokenKind::Operator(OperatorSymbol::HASH_ROCKET)) {
+            let value = self.parse_arg()?;
+            pairs.push((arg, value));
+        } else {
+            args.push(arg);
+        }
+        Ok(())
+    }
+
+    fn parse_arg(&mut self) -> ParseResult<Expr> {
+        let tok = match self.peek() {
+            Some(tok) => tok.clone(),
+            None => return Err(self.unexpected(&["an argument"]))
+        };
+        match tok.kind {
+            TokenKind::Operator(OperatorSymbol::ASTERISK) => {
+                self.next();
+                let inner = self.parse_expr(Precedence::Assignment.binding_power())?;
+                Ok(Expr::create(ExprKind::Splat(Box::new(inner)), tok.line, tok.col))
+            },
+            TokenKind::Operator(OperatorSymbol::BIT_AND) => {
+                self.next();
+                let inner = self.parse_expr(Precedence::Assignment.binding_power())?;
+                Ok(Expr::create(ExprKind::BlockPass(Box::new(inner)), tok.line, tok.col))
+            },
+            _ => self.parse_expr(Precedence::Assignment.binding_power())
+        }
+    }
+
+    // `name:` in an argument list or hash literal is a symbol key.
+    fn parse_label(&mut self) -> ParseResult<Option<Expr>> {
+        let is_label = match (self.peek().cloned(), self.peek_nth(1).cloned()) {
+            (Some(t), Some(colon)) => matches!(t.kind, TokenKind::Identifier(IdentifierSymbol::VARIABLE) | TokenKind::Identifier(IdentifierSymbol::STRING))
+                && colon.kind == TokenKind::Separator(SeparatorSymbol::COLON) && !colon.space_before,
+            _ => false
+        };
+        if !is_label { return Ok(None); }
+        let tok = self.next().unwrap();
+        self.next();
+        Ok(Some(Expr::create(ExprKind::Symbol(tok.value), tok.line, tok.col)))
+    }
+
+    fn parse_hash(&mut self) -> ParseResult<Expr> {
+        let open = self.next().unwrap();
+        let mut pairs: Vec<(Expr, Expr)> = Vec::new();
+        self.skip_newlines();
+        while !self.peek_is(TokenKind::Separator(SeparatorSymbol::R_BRACE)) {
+            let key = match self.parse_label()? {
+                Some(key) => key,
+                None => {
+                    let key = self.parse_arg()?;
+                    self.expect(TokenKind::Operator(OperatorSymbol::HASH_ROCKET), "'=>'")?;
+                    key
+                }
+            };
+            self.skip_newlines();
+            let value = self.parse_arg()?;
+            pairs.push((key, value));
+            self.skip_newlines();
+            if !self.accept(TokenKind::Separator(SeparatorSymbol::COMMA)) { break; }
+            self.skip_newlines();
+        }
+        self.expect(TokenKind::Separator(SeparatorSymbol::R_BRACE), "'}'")?;
+        Ok(Expr::create(ExprKind::Hash(pairs), open.line, open.col))
+    }
+
+    // -- blocks and parameters --
+
+    fn parse_block(&mut self) -> ParseResult<Block> {
+        let open = self.peek().unwrap().clone();
+        self.push_scope(true);
+        let block = self.parse_block_inner(open.line, open.col);
+        self.pop_scope();
+        block
+    }
+    fn parse_block_inner(&mut self, line: u32, col: u32) -> ParseResult<Block> {
+        let brace = self.peek_is(TokenKind::Separator(SeparatorSymbol::L_BRACE));
+        self.next();
+        self.push_lex_mode(LexMode::BlockStart);
+        let params = if self.peek_is(TokenKind::Separator(SeparatorSymbol::BAR)) {
+            self.next();
+            self.parse_block_params()?
+        } else {
+            Params::default()
+        };
+        let close = if brace { TokenKind::Separator(SeparatorSymbol::R_BRACE) } else { TokenKind::Keyword(KeywordSymbol::END) };
+        let saved_no_do = self.no_do;
+        self.no_do = 0;
+        let body = self.parse_statements(&[close]);
+        self.no_do = saved_no_do;
+        self.expect(close, if brace { "'}'" } else { "'end'" })?;
+        Ok(Block { params, body, line, col })
+    }
+
+    // Parameters after an opening `|`, through the closing `|`.
+    fn parse_block_params(&mut self) -> ParseResult<Params> {
+        let params = self.parse_param_list(&[TokenKind::Separator(SeparatorSymbol::BAR)])?;
+        self.expect(TokenKind::Separator(SeparatorSymbol::BAR), "'|'")?;
+        Ok(params)
+    }
+
+    // After `|params|` in the `|a| do ... end` lambda shorthand.
+    fn parse_block_body(&mut self, params: Params, line: u32, col: u32) -> ParseResult<Block> {
+        match self.peek_kind() {
+            Some(TokenKind::Separator(SeparatorSymbol::L_BRACE)) | Some(TokenKind::Keyword(KeywordSymbol::DO)) => {
+                let mut block = self.parse_block_inner(line, col)?;
+                block.params = params;
+                Ok(block)
+            },
+            _ => Err(self.unexpected(&["'do'", "'{'"]))
+        }
+    }
+
+    fn parse_lambda(&mut self) -> ParseResult<Expr> {
+        let arrow = self.next().unwrap();
+        self.push_scope(true);
+        let result = self.parse_lambda_inner(arrow.line, arrow.col);
+        self.pop_scope();
+        Ok(Expr::create(ExprKind::Lambda(Rc::new(result?)), arrow.line, arrow.col))
+    }
+    fn parse_lambda_inner(&mut self, line: u32, col: u32) -> ParseResult<Block> {
+        let params = if self.accept(TokenKind::Separator(SeparatorSymbol::L_PAREN)) {
+            let params = self.parse_param_list(&[TokenKind::Separator(SeparatorSymbol::R_PAREN)])?;
+            self.expect(TokenKind::Separator(SeparatorSymbol::R_PAREN), "')'")?;
+            params
+        } else if self.peek_is(TokenKind::Identifier(IdentifierSymbol::VARIABLE)) {
+            self.parse_param_list(&[TokenKind::Separator(SeparatorSymbol::L_BRACE), TokenKind::Keyword(KeywordSymbol::DO)])?
+        } else {
+            Params::default()
+        };
+        self.parse_block_body(params, line, col)
+    }
+
+    fn parse_param_list(&mut self, close: &[TokenKind]) -> ParseResult<Params> {
+        let mut params = Params::default();
+        self.skip_newlines();
+        loop {
+            match self.peek_kind() {
+                Some(kind) if close.contains(&kind) => break,
+                Some(TokenKind::Separator(SeparatorSymbol::NEWLINE)) | Some(TokenKind::Separator(SeparatorSymbol::SEMICOLON)) => break,
+                _ => {}
+            }
+            if self.accept(TokenKind::Operator(OperatorSymbol::ASTERISK)) {
+                let name = self.expect_param_name()?;
+                params.rest = Some(name);
+            } else if self.accept(TokenKind::Operator(OperatorSymbol::BIT_AND)) {
+                let name = self.expect_param_name()?;
+                params.block = Some(name);
+            } else {
+                let name = self.expect_param_name()?;
+                if self.accept(TokenKind::Operator(OperatorSymbol::ASSIGN)) {
+                    let default = self.parse_expr(Precedence::Ternary.binding_power())?;
+                    params.optional.push((name, default));
+                } else if params.rest.is_some() || !params.optional.is_empty() {
+                    params.post.push(name);
+                } else {
+                    params.required.push(name);
+                }
+            }
+            self.skip_newlines();
+            if !self.accept(TokenKind::Separator(SeparatorSymbol::COMMA)) { break; }
+            self.skip_newlines();
+        }
+        Ok(params)
+    }
+
+    fn expect_param_name(&mut self) -> ParseResult<String> {
+        match self.peek() {
+            Some(t) if t.kind == TokenKind::Identifier(IdentifierSymbol::VARIABLE) && is_local_name(&t.value) => {
+                let name = self.next().unwrap().value;
+                self.declare(&name);
+                Ok(name)
+            },
+            _ => Err(self.unexpected(&["a parameter name"]))
+        }
+    }
+
+    // -- keyword constructs --
+
+    fn parse_keyword(&mut self, kw: KeywordSymbol) -> ParseResult<Expr> {
+        let tok = self.peek().unwrap().clone();
+        let (line, col) = (tok.line, tok.col);
+        let simple = |kind: ExprKind| Ok(Expr::create(kind, line, col));
+        match kw {
+            KeywordSymbol::NIL => { self.next(); simple(ExprKind::Nil) },
+            KeywordSymbol::TRUE => { self.next(); simple(ExprKind::True) },
+            KeywordSymbol::FALSE => { self.next(); simple(ExprKind::False) },
+            KeywordSymbol::SELF => { self.next(); simple(ExprKind::SelfRef) },
+            KeywordSymbol::__FILE__ => { self.next(); simple(ExprKind::File) },
+            KeywordSymbol::__LINE__ => { self.next(); simple(ExprKind::Line) },
+            KeywordSymbol::__ENCODING__ => { self.next(); simple(ExprKind::Encoding) },
+            KeywordSymbol::REDO => { self.next(); simple(ExprKind::Redo) },
+            KeywordSymbol::RETRY => { self.next(); simple(ExprKind::Retry) },
+            KeywordSymbol::IF | KeywordSymbol::UNLESS => self.parse_if(),
+            KeywordSymbol::WHILE | KeywordSymbol::UNTIL => self.parse_while(),
+            KeywordSymbol::FOR => self.parse_for(),
+            KeywordSymbol::CASE => self.parse_case(),
+            KeywordSymbol::BEGIN => {
+                self.next();
+                let body = self.parse_body_with_rescue(line, col)?;
+                self.expect(TokenKind::Keyword(KeywordSymbol::END), "'end'")?;
+                Ok(body)
+            },
+            KeywordSymbol::LBEGIN => {
+                if self.scopes.len() > 1 {
+                    return Err(self.error_here(String::from("BEGIN is permitted only at toplevel")));
+                }
+                self.next();
+                self.expect(TokenKind::Separator(SeparatorSymbol::L_BRACE), "'{'")?;
+                let body = self.parse_statements(&[TokenKind::Separator(SeparatorSymbol::R_BRACE)]);
+                self.expect(TokenKind::Separator(SeparatorSymbol::R_BRACE), "'}'")?;
+                simple(ExprKind::BeginBlock(body))
+            },
+            KeywordSymbol::LEND => {
+                self.next();
+                if !self.peek_is(TokenKind::Separator(SeparatorSymbol::L_BRACE)) {
+                    return Err(self.unexpected(&["'{'"]));
+                }
+                let block = self.parse_block()?;
+                simple(ExprKind::EndBlock(Rc::new(block)))
+            },
+            KeywordSymbol::DEF => self.parse_def(),
+            KeywordSymbol::CLASS => self.parse_class(),
+            KeywordSymbol::MODULE => self.parse_module(),
+            KeywordSymbol::RETURN | KeywordSymbol::BREAK | KeywordSymbol::NEXT => {
+                self.next();
+                let value = self.parse_jump_value()?.map(Box::new);
+                simple(match kw {
+                    KeywordSymbol::RETURN => ExprKind::Return(value),
+                    KeywordSymbol::BREAK => ExprKind::Break(value),
+                    _ => ExprKind::Next(value)
+                })
+            },
+            KeywordSymbol::YIELD => {
+                self.next();
+                let args = self.parse_call_args()?;
+                simple(ExprKind::Yield(args))
+            },
+            KeywordSymbol::SUPER => {
+                self.next();
+                let explicit = self.peek().is_some_and(|t| t.kind == TokenKind::Separator(SeparatorSymbol::L_PAREN) && !t.space_before)
+                    || self.can_start_command_arg();
+                let args = if explicit { Some(self.parse_call_args()?) } else { None };
+                let block = self.parse_block_if_present()?;
+                simple(ExprKind::Super { args, block })
+            },
+            KeywordSymbol::ALIAS => {
+                self.next();
+                let new_name = self.parse_method_ref()?;
+                let old_name = self.parse_method_ref()?;
+                simple(ExprKind::Alias { new_name, old_name })
+            },
+            KeywordSymbol::UNDEF => {
+                self.next();
+                let mut names = vec![self.parse_method_ref()?];
+                while self.accept(TokenKind::Separator(SeparatorSymbol::COMMA)) {
+                    names.push(self.parse_method_ref()?);
+                }
+                simple(ExprKind::Undef(names))
+            },
+            _ => Err(self.unexpected(&["an expression"]))
+        }
+    }
+
+    // The optional value of `return`, `break` and `next`; several values make an array.
+    fn parse_jump_value(&mut self) -> ParseResult<Option<Expr>> {
+        let starts_value = match self.peek_kind() {
+            None => false,
+            Some(TokenKind::Separator(SeparatorSymbol::NEWLINE))
+            | Some(TokenKind::Separator(SeparatorSymbol::SEMICOLON))
+            | Some(TokenKind::Separator(SeparatorSymbol::R_BRACE))
+            | Some(TokenKind::Separator(SeparatorSymbol::R_PAREN)) => false,
+            Some(TokenKind::Keyword(kw)) => !matches!(kw, KeywordSymbol::IF | KeywordSymbol::UNLESS | KeywordSymbol::WHILE
+                | KeywordSymbol::UNTIL | KeywordSymbol::END | KeywordSymbol::RESCUE | KeywordSymbol::AND | KeywordSymbol::OR
+                | KeywordSymbol::ELSE | KeywordSymbol::ELSIF | KeywordSymbol::ENSURE | KeywordSymbol::WHEN | KeywordSymbol::THEN),
+            _ => true
+        };
+        if !starts_value { return Ok(None); }
+        let first = self.parse_arg()?;
+        if !self.peek_is(TokenKind::Separator(SeparatorSymbol::COMMA)) { return Ok(Some(first)); }
+        let (line, col) = (first.line, first.col);
+        let mut items = vec![first];
+        while self.accept(TokenKind::Separator(SeparatorSymbol::COMMA)) {
+            items.push(self.parse_arg()?);
+        }
+        Ok(Some(Expr::create(ExprKind::Array(items), line, col)))
+    }
+
+    // A method name in `alias` and `undef`: a bare name or a symbol.
+    fn parse_method_ref(&mut self) -> ParseResult<String> {
+        match self.peek_kind() {
+            Some(TokenKind::Identifier(IdentifierSymbol::SYMBOL)) | Some(TokenKind::Identifier(IdentifierSymbol::VARIABLE)) => Ok(self.next().unwrap().value),
+            Some(TokenKind::Identifier(IdentifierSymbol::GLOBAL_VARIABLE)) => Ok(self.next().unwrap().value),
+            _ => Err(self.unexpected(&["a method name"]))
+        }
+    }
+
+    fn parse_condition(&mut self) -> ParseResult<Expr> {
+        self.no_do += 1;
+        let cond = self.parse_expr(Precedence::Modifier.binding_power() + 1);
+        self.no_do -= 1;
+        cond
+    }
+
+    fn parse_if(&mut self) -> ParseResult<Expr> {
+        let tok = self.next().unwrap();
+        let negated = tok.kind == TokenKind::Keyword(KeywordSymbol::UNLESS);
+        let cond = self.parse_condition()?;
+        let cond = if negated { negate(cond) } else { cond };
+        self.accept_then();
+        let then_body = self.parse_statements(&[
+            TokenKind::Keyword(KeywordSymbol::ELSIF), TokenKind::Keyword(KeywordSymbol::ELSE), TokenKind::Keyword(KeywordSymbol::END)
+        ]);
+        let else_body = match self.peek_kind() {
+            Some(TokenKind::Keyword(KeywordSymbol::ELSIF)) if !negated => {
+                // `elsif` reads as a nested `if` sharing this one's `end`.
+                let nested = self.parse_if()?;
+                return Ok(Expr::create(ExprKind::If { cond: Box::new(cond), then_body, else_body: Some(vec![nested]) }, tok.line, tok.col));
+            },
+            Some(TokenKind::Keyword(KeywordSymbol::ELSE)) => {
+                self.next();
+                Some(self.parse_statements(&[TokenKind::Keyword(KeywordSymbol::END)]))
+            },
+            Some(TokenKind::Keyword(KeywordSymbol::END)) => None,
+            _ if negated => return Err(self.unexpected(&["'else'", "'end'"])),
+            _ => return Err(self.unexpected(&["'elsif'", "'else'", "'end'"]))
+        };
+        self.expect(TokenKind::Keyword(KeywordSymbol::END), "'end'")?;
+        Ok(Expr::create(ExprKind::If { cond: Box::new(cond), then_body, else_body }, tok.line, tok.col))
+    }
+
+    fn accept_then(&mut self) {
+        self.skip_terminators();
+        self.accept(TokenKind::Keyword(KeywordSymbol::THEN));
+    }
+
+    fn parse_while(&mut self) -> ParseResult<Expr> {
+        let tok = self.next().unwrap();
+        let until = tok.kind == TokenKind::Keyword(KeywordSymbol::UNTIL);
+        let cond = self.parse_condition()?;
+        self.accept(TokenKind::Keyword(KeywordSymbol::DO));
+        let body = self.parse_statements(&[TokenKind::Keyword(KeywordSymbol::END)]);
+        self.expect(TokenKind::Keyword(KeywordSymbol::END), "'end'")?;
+        Ok(Expr::create(ExprKind::While { cond: Box::new(cond), body, until }, tok.line, tok.col))
+    }
+
+    fn parse_for(&mut self) -> ParseResult<Expr> {
+        let tok = self.next().unwrap();
+        let var = self.expect_param_name()?;
+        self.expect(TokenKind::Keyword(KeywordSymbol::IN), "'in'")?;
+        let iter = self.parse_condition()?;
+        self.accept(TokenKind::Keyword(KeywordSymbol::DO));
+        let body = self.parse_statements(&[TokenKind::Keyword(KeywordSymbol::END)]);
+        self.expect(TokenKind::Keyword(KeywordSymbol::END), "'end'")?;
+        Ok(Expr::create(ExprKind::For { var, iter: Box::new(iter), body }, tok.line, tok.col))
+    }
+
+    fn parse_case(&mut self) -> ParseResult<Expr> {
+        let tok = self.next().unwrap();
+        let subject = if self.at_terminator() { None } else { Some(Box::new(self.parse_statement()?)) };
+        self.skip_terminators();
+        let mut whens: Vec<(Vec<Expr>, Body)> = Vec::new();
+        while self.accept(TokenKind::Keyword(KeywordSymbol::WHEN)) {
+            let mut tests = vec![self.parse_arg()?];
+            while self.accept(TokenKind::Separator(SeparatorSymbol::COMMA)) {
+                self.skip_newlines();
+                tests.push(self.parse_arg()?);
+            }
+            self.accept_then();
+            let body = self.parse_statements(&[
+                TokenKind::Keyword(KeywordSymbol::WHEN), TokenKind::Keyword(KeywordSymbol::ELSE), TokenKind::Keyword(KeywordSymbol::END)
+            ]);
+            whens.push((tests, body));
+        }
+        if whens.is_empty() {
+            return Err(self.unexpected(&["'when'"]));
+        }
+        let else_body = if self.accept(TokenKind::Keyword(KeywordSymbol::ELSE)) {
+            Some(self.parse_statements(&[TokenKind::Keyword(KeywordSymbol::END)]))
+        } else {
+            None
+        };
+        self.expect(TokenKind::Keyword(KeywordSymbol::END), "'end'")?;
+        Ok(Expr::create(ExprKind::Case { subject, whens, else_body }, tok.line, tok.col))
+    }
+
+    // The body of `begin`, `def`, `class` and `module`, with optional `rescue`, `else` and
+    // `ensure` clauses, up to but not including the closing `end`.
+    fn parse_body_with_rescue(&mut self, line: u32, col: u32) -> ParseResult<Expr> {
+        let stops = [
+            TokenKind::Keyword(KeywordSymbol::RESCUE), TokenKind::Keyword(KeywordSymbol::ELSE),
+            TokenKind::Keyword(KeywordSymbol::ENSURE), TokenKind::Keyword(KeywordSymbol::END)
+        ];
+        let body = self.parse_statements(&stops);
+        let mut rescues: Vec<RescueClause> = Vec::new();
+        while self.peek_is(TokenKind::Keyword(KeywordSymbol::RESCUE)) {
+            let rescue_line = self.next().unwrap().line;
+            let mut classes: Vec<Expr> = Vec::new();
+            let mut var: Option<String> = None;
+            // A bare `rescue`: the class list, if any, is on the same line as the keyword.
+            let same_line = self.peek().is_some_and(|t| t.line == rescue_line);
+            if same_line && !self.at_terminator() && !self.peek_is(TokenKind::Keyword(KeywordSymbol::THEN))
+                && !self.peek_is(TokenKind::Operator(OperatorSymbol::HASH_ROCKET)) {
+                classes.push(self.parse_arg()?);
+                while self.accept(TokenKind::Separator(SeparatorSymbol::COMMA)) {
+                    classes.push(self.parse_arg()?);
+                }
+            }
+            if self.accept(TokenKind::Operator(OperatorSymbol::HASH_ROCKET)) {
+                var = Some(self.expect_param_name()?);
+            }
+            self.accept_then();
+            let body = self.parse_statements(&stops);
+            rescues.push(RescueClause { classes, var, body });
+        }
+        let else_body = if self.accept(TokenKind::Keyword(KeywordSymbol::ELSE)) {
+            Some(self.parse_statements(&[TokenKind::Keyword(KeywordSymbol::ENSURE), TokenKind::Keyword(KeywordSymbol::END)]))
+        } else {
+            None
+        };
+        let ensure_body = if self.accept(TokenKind::Keyword(KeywordSymbol::ENSURE)) {
+            Some(self.parse_statements(&[TokenKind::Keyword(KeywordSymbol::END)]))
+        } else {
+            None
+        };
+        Ok(Expr::create(ExprKind::Begin { body, rescues, else_body, ensure_body }, line, col))
+    }
+
+    fn parse_def(&mut self) -> ParseResult<Expr> {
+        let tok = self.next().unwrap();
+        let mut singleton: Option<Box<Expr>> = None;
+        if self.peek_is(TokenKind::Keyword(KeywordSymbol::SELF))
+            && self.peek_nth(1).is_some_and(|t| t.kind == TokenKind::Operator(OperatorSymbol::DOT)) {
+            let this = self.next().unwrap();
+            self.next();
+            singleton = Some(Box::new(Expr::create(ExprKind::SelfRef, this.line, this.col)));
+        }
+        let name = self.parse_def_name()?;
+        self.push_scope(false);
+        let result = self.parse_def_rest(tok.line, tok.col);
+        self.pop_scope();
+        let (params, body) = result?;
+        Ok(Expr::create(ExprKind::Def { singleton, name, params, body: Rc::new(body) }, tok.line, tok.col))
+    }
+    // A plain name, a setter `name=`, `[]`, `[]=`, or an operator such as `+`, `<=>` or `-@`.
+    fn parse_def_name(&mut self) -> ParseResult<String> {
+        let tok = match self.peek() {
+            Some(tok) => tok.clone(),
+            None => return Err(self.unexpected(&["a method name"]))
+        };
+        let mut name = match tok.kind {
+            TokenKind::Identifier(IdentifierSymbol::VARIABLE) | TokenKind::Keyword(_) => {
+                self.next();
+                let setter = !tok.value.ends_with(['?', '!']) && self.peek_adjacent(TokenKind::Operator(OperatorSymbol::ASSIGN));
+                if !setter { return Ok(tok.value); }
+                self.next();
+                return Ok(format!("{}=", tok.value));
+            },
+            TokenKind::Separator(SeparatorSymbol::L_BRACKET) => {
+                self.next();
+                if !self.peek_adjacent(TokenKind::Separator(SeparatorSymbol::R_BRACKET)) {
+                    return Err(self.unexpected(&["']'"]));
+                }
+                self.next();
+                if self.peek_adjacent(TokenKind::Operator(OperatorSymbol::ASSIGN)) {
+                    self.next();
+                    return Ok(String::from("[]="));
+                }
+                return Ok(String::from("[]"));
+            },
+            TokenKind::Operator(op) if is_definable_operator(op) => {
+                self.next();
+                op.to_str().to_string()
+            },
+            _ => return Err(self.unexpected(&["a method name"]))
+        };
+        // Unary `-@`, `+@`, `!@` and `~@`.
+        let unary = matches!(tok.kind, TokenKind::Operator(OperatorSymbol::MINUS | OperatorSymbol::PLUS | OperatorSymbol::BANG | OperatorSymbol::BIT_NOT));
+        if unary && self.peek().is_some_and(|t| t.kind == TokenKind::Identifier(IdentifierSymbol::INSTANCE_VARIABLE) && t.value == "@" && !t.space_before) {
+            self.next();
+            if matches!(tok.kind, TokenKind::Operator(OperatorSymbol::MINUS | OperatorSymbol::PLUS)) {
+                name.push('@');
+            }
+        }
+        Ok(name)
+    }
+    // Whether the next token is `kind` with no space before it.
+    fn peek_adjacent(&mut self, kind: TokenKind) -> bool {
+        self.peek().is_some_and(|t| t.kind == kind && !t.space_before)
+    }
+
+    fn parse_def_rest(&mut self, line: u32, col: u32) -> ParseResult<(Params, Expr)> {
+        let params = if self.accept(TokenKind::Separator(SeparatorSymbol::L_PAREN)) {
+            let params = self.parse_param_list(&[TokenKind::Separator(SeparatorSymbol::R_PAREN)])?;
+            self.expect(TokenKind::Separator(SeparatorSymbol::R_PAREN), "')'")?;
+            params
+        } else if self.at_terminator() {
+            Params::default()
+        } else {
+            self.parse_param_list(&[])?
+        };
+        let body = self.parse_body_with_rescue(line, col)?;
+        self.expect(TokenKind::Keyword(KeywordSymbol::END), "'end'")?;
+        Ok((params, body))
+    }
+
+    fn expect_constant_name(&mut self) -> ParseResult<String> {
+        match self.peek() {
+            Some(t) if t.kind == TokenKind::Identifier(IdentifierSymbol::VARIABLE) && is_constant_name(&t.value) => Ok(self.next().unwrap().value),
+            _ => Err(self.unexpected(&["a constant name"]))
+        }
+    }
+
+    fn parse_constant_path(&mut self) -> ParseResult<Expr> {
+        let first = self.peek().cloned();
+        let name = self.expect_constant_name()?;
+        let first = first.unwrap();
+        let mut path = Expr::create(ExprKind::Constant { scope: None, name }, first.line, first.col);
+        while self.peek_is(TokenKind::Operator(OperatorSymbol::RESOLUTION)) {
+            let sep = self.next().unwrap();
+            let name = self.expect_constant_name()?;
+            path = Expr::create(ExprKind::Constant { scope: Some(Box::new(path)), name }, sep.line, sep.col);
+        }
+        Ok(path)
+    }
+
+    fn parse_class(&mut self) -> ParseResult<Expr> {
+        let tok = self.next().unwrap();
+        let path = self.parse_constant_path()?;
+        let superclass = if self.accept(TokenKind::Operator(OperatorSymbol::LT)) {
+            Some(Box::new(self.parse_expr(Precedence::Call.binding_power())?))
+        } else {
+            None
+        };
+        self.push_scope(false);
+        let body = self.parse_statements(&[TokenKind::Keyword(KeywordSymbol::END)]);
+        self.pop_scope();
+        self.expect(TokenKind::Keyword(KeywordSymbol::END), "'end'")?;
+        Ok(Expr::create(ExprKind::Class { path: Box::new(path), superclass, body }, tok.line, tok.col))
+    }
+
+    fn parse_module(&mut self) -> ParseResult<Expr> {
+        let tok = self.next().unwrap();
+        let path = self.parse_constant_path()?;
+        self.push_scope(false);
+        let body = self.parse_statements(&[TokenKind::Keyword(KeywordSymbol::END)]);
+        self.pop_scope();
+        self.expect(TokenKind::Keyword(KeywordSymbol::END), "'end'")?;
+        Ok(Expr::create(ExprKind::Module { path: Box::new(path), body }, tok.line, tok.col))
+    }
+}
+
+fn negate(cond: Expr) -> Expr {
+    let (line, col) = (cond.line, cond.col);
+    Expr::create(ExprKind::Not(Box::new(cond)), line, col)
+}
+
+// `-operand`, folded into the literal when the operand is a number.
+fn negative(operand: Expr, line: u32, col: u32) -> Expr {
+    let kind = match operand.kind {
+        ExprKind::Integer(v) if !v.starts_with('-') => ExprKind::Integer(format!("-{}", v)),
+        ExprKind::Float(v) if !v.starts_with('-') => ExprKind::Float(format!("-{}", v)),
+        ExprKind::Rational(v) if !v.starts_with('-') => ExprKind::Rational(format!("-{}", v)),
+        kind => ExprKind::Unary { op: OperatorSymbol::MINUS, operand: Box::new(Expr::create(kind, operand.line, operand.col)) }
+    };
+    Expr::create(kind, line, col)
+}
+
+// Operators a class can define methods for.
+fn is_definable_operator(op: OperatorSymbol) -> bool {
+    use OperatorSymbol::*;
+    matches!(op, PLUS | MINUS | ASTERISK | SLASH | MODULO | POW | EQ | NOT_EQ | CASE_EQ | MATCH | NOT_MATCH | COMP
+        | LT | LTE | GT | GTE | L_SHIFT | R_SHIFT | BIT_AND | BIT_OR | BIT_XOR | BIT_NOT | BANG)
+}
+
+fn is_constant_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+}
+
+fn is_local_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+}
+
+fn is_assignment(kind: TokenKind) -> bool {
+    match kind {
+        TokenKind::Operator(OperatorSymbol::ASSIGN) => true,
+        TokenKind::Operator(op) => precedence::compound_base(op).is_some(),
+        _ => false
+    }
+}
+
+fn is_assignable(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::LocalVar(_) | ExprKind::InstanceVar(_) | ExprKind::GlobalVar(_) | ExprKind::Constant { .. } => true,
+        ExprKind::Call { receiver: None, name, args, block: None, .. } => args.is_empty() && is_local_name(name),
+        _ => false
+    }
+}
+
+fn is_variable(expr: &Expr) -> bool {
+    !matches!(expr.kind, ExprKind::Call { receiver: None, .. })
+}
+
+// Resolves the backslash escapes a string token keeps from the source.
+fn unescape(raw: &str) -> String {
+    let mut out = String::new();
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('0') => out.push('\0'),
+            Some('s') => out.push(' '),
+            Some('e') => out.push('\u{1b}'),
+            Some('a') => out.push('\u{7}'),
+            Some('b') => out.push('\u{8}'),
+            Some('f') => out.push('\u{c}'),
+            Some('v') => out.push('\u{b}'),
+            Some('x') => match hex_digits(&mut chars, 2) {
+                Some(code) => out.extend(char::from_u32(code)),
+                None => out.push('x')
+            },
+            // `\u00e9`, or `\u{e9}` and `\u{48 49}` with one or more code points in braces.
+            Some('u') if chars.peek() == Some(&'{') => {
+                chars.next();
+                loop {
+                    while chars.peek() == Some(&' ') {
+                        chars.next();
+                    }
+                    match hex_digits(&mut chars, 6) {
+                        Some(code) => out.extend(char::from_u32(code)),
+                        None => break
+                    }
+                }
+                chars.next_if_eq(&'}');
+            },
+            Some('u') => match hex_digits(&mut chars, 4) {
+                Some(code) => out.extend(char::from_u32(code)),
+                None => out.push('u')
+            },
+            Some(other) => out.push(other),
+            None => out.push('\\')
+        }
+    }
+    out
+}
+
+// Reads up to `max` hex digits as a number, or none when there are none.
+fn hex_digits(chars: &mut std::iter::Peekable<std::str::Chars>, max: usize) -> Option<u32> {
+    let mut code = None;
+    for _ in 0..max {
+        match chars.peek().and_then(|c| c.to_digit(16)) {
+            Some(digit) => {
+                chars.next();
+                code = Some(code.unwrap_or(0) * 16 + digit);
+            },
+            None => break
+        }
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::parser::input_stream::InputStream;
+
+    fn parse(source: &str) -> String {
+        let mut is = InputStream::from_string(source);
+        let mut ts = TokenStream::create(&mut is);
+        let program = Parser::create(&mut ts).parse_program().unwrap_or_else(|e| panic!("{}: {}", source, e));
+        program.to_string().trim_end().to_string()
+    }
+
+    #[test]
+    fn def_accepts_setter_and_operator_names() {
+        assert_eq!(parse("def name=(v)\n  @name = v\nend"), "(def name= (v) (begin ((= @name v))))");
+        assert_eq!(parse("def +(o); end"), "(def + (o) (begin ()))");
+        assert_eq!(parse("def [](i); end"), "(def [] (i) (begin ()))");
+        assert_eq!(parse("def []=(i, v); end"), "(def []= (i v) (begin ()))");
+        assert_eq!(parse("def -@; end"), "(def -@ () (begin ()))");
+        assert_eq!(parse("def self.<=>(o); end"), "(def self.<=> (o) (begin ()))");
+        assert_eq!(parse("def empty?; end"), "(def empty? () (begin ()))");
+    }
+
+    #[test]
+    fn a_bare_rescue_ends_at_the_line_break() {
+        assert_eq!(parse("begin\n  f\nrescue\n  puts 1\nend"), "(begin ((f)) (rescue () ((puts 1))))");
+        assert_eq!(parse("begin\n  f\nrescue\n  retry\nend"), "(begin ((f)) (rescue () ((retry))))");
+        assert_eq!(parse("begin\n  f\nrescue A, B => e\n  g\nelse\n  -1\nensure\n  h\nend"), "(begin ((f)) (rescue (A B) => e ((g))) (else -1) (ensure (h)))");
+    }
+
+    #[test]
+    fn binary_operators_follow_the_precedence_table() {
+        assert_eq!(parse("1 + 2 * 3"), "(+ 1 (* 2 3))");
+        assert_eq!(parse("1 - 2 - 3"), "(- (- 1 2) 3)");
+        assert_eq!(parse("a = 1 < 2 == true"), "(= a (== (< 1 2) true))");
+        assert_eq!(parse("1 | 2 & 3 << 4"), "(| 1 (& 2 (<< 3 4)))");
+    }
+
+    #[test]
+    fn power_is_right_associative_and_binds_tighter_than_unary_minus() {
+        assert_eq!(parse("2 ** 3 ** 2"), "(** 2 (** 3 2))");
+        assert_eq!(parse("-2 ** 2"), "(- (** 2 2))");
+        assert_eq!(parse("-2 * 2"), "(* -2 2)");
+    }
+
+    #[test]
+    fn unary_operators() {
+        assert_eq!(parse("!a.b"), "(not ((a).b))");
+        assert_eq!(parse("!a == b"), "(== (not (a)) (b))");
+        assert_eq!(parse("not a == b"), "(not (== (a) (b)))");
+        assert_eq!(parse("-x + ~y"), "(+ (- (x)) (~ (y)))");
+        assert_eq!(parse("-10.abs"), "(-10.abs)");
+        assert_eq!(parse("-2.5.floor"), "(-2.5.floor)");
+        assert_eq!(parse("-2 ** 2"), "(- (** 2 2))");
+        assert_eq!(parse("- 2.abs"), "(- (2.abs))");
+    }
+
+    #[test]
+    fn defined_with_parentheses_is_a_primary() {
+        assert_eq!(parse("defined?(a) && b"), "(and (defined? (a)) (b))");
+        assert_eq!(parse("defined?(a).nil?"), "((defined? (a)).nil?)");
+        assert_eq!(parse("defined? a && b"), "(defined? (and (a) (b)))");
+    }
+
+    #[test]
+    fn keyword_logic_binds_looser_than_symbolic_logic() {
+        assert_eq!(parse("a or b && c"), "(or (a) (and (b) (c)))");
+        assert_eq!(parse("a || b and c"), "(and (or (a) (b)) (c))");
+        assert_eq!(parse("x = a and b"), "(and (= x (a)) (b))");
+        assert_eq!(parse("x = a && b"), "(= x (and (a) (b)))");
+    }
+
+    #[test]
+    fn method_calls_chain() {
+        assert_eq!(parse("a.b.c(1).d"), "((((a).b).c 1).d)");
+        assert_eq!(parse("x = []; x.first&.size"), "(= x [])\n((x.first)&.size)");
+        assert_eq!(parse("a.each { |q| q * 2 }"), "((a).each {|q| (* q 2)})");
+        assert_eq!(parse("Foo::Bar.new(1)"), "(Foo::Bar.new 1)");
+    }
+
+    #[test]
+    fn bars_open_block_parameters_only_where_a_block_begins() {
+        assert_eq!(parse("a.each { || b || c }"), "((a).each {|| (or (b) (c))})");
+        assert_eq!(parse("a.each do |x| x | 1 end"), "((a).each {|x| (| x 1)})");
+        assert_eq!(parse("a.each { b | c }"), "((a).each {|| (| (b) (c))})");
+        assert_eq!(parse("f = |x, y| do x end"), "(= f (lambda {|x y| x}))");
+    }
+
+    #[test]
+    fn slashes_after_a_local_variable_divide() {
+        assert_eq!(parse("x = 10\nx /5/ 2"), "(= x 10)\n(/ (/ x 5) 2)");
+        assert!(parse("[1].each { |y| y /1/ 1 }").contains("(/ (/ y 1) 1)"));
+        assert_eq!(parse("x /5/"), "(x /5/)");
+    }
+
+    #[test]
+    fn command_calls_take_unparenthesised_arguments() {
+        assert_eq!(parse("puts 1, 2"), "(puts 1 2)");
+        assert_eq!(parse("foo -1"), "(foo -1)");
+        assert_eq!(parse("foo - 1"), "(- (foo) 1)");
+        assert_eq!(parse("list.each do |x| puts x end"), "((list).each {|x| (puts x)})");
+    }
+
+    #[test]
+    fn operands_of_operators_are_not_command_calls() {
+        assert_eq!(parse("x = f 1"), "(= x (f 1))");
+        assert_eq!(parse("puts f 1"), "(puts (f 1))");
+        assert_eq!(parse("a and f 1"), "(and (a) (f 1))");
+        assert_eq!(parse("1 + (f 2)"), "(+ 1 (f 2))");
+        assert_eq!(parse("1 + f(g 2)"), "(+ 1 (f (g 2)))");
+        for source in ["1 + f 2", "a && f 1", "-f 1"] {
+            assert!(parse_with_errors(source).has_errors(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn assignments_declare_locals() {
+        assert_eq!(parse("a = 1\na [0]"), "(= a 1)\n(a.[] 0)");
+        assert_eq!(parse("b [0]"), "(b [0])");
+        assert_eq!(parse("a = b = 2"), "(= a (= b 2))");
+        assert_eq!(parse("a = 1\na += 2 * 3"), "(= a 1)\n(+= a (* 2 3))");
+    }
+
+    #[test]
+    fn modifiers_wrap_the_whole_statement() {
+        assert_eq!(parse("x = 1 if y"), "(if (y) ((= x 1)))");
+        assert_eq!(parse("return 1 unless a and b"), "(if (not (and (a) (b))) ((return 1)))");
+    }
+
+    #[test]
+    fn equality_operators_do_not_chain() {
+        let mut is = InputStream::from_string("1 == 2 == 3");
+        let mut ts = TokenStream::create(&mut is);
+        assert!(Parser::create(&mut ts).parse_program().is_err());
+    }
+
+    #[test]
+    fn custom_tables_change_grouping() {
+        let mut table = PrecedenceTable::default();
+        table.set(TokenKind::Operator(OperatorSymbol::PLUS), Fixity::Infix, OperatorSpec::create(Precedence::Power, Associativity::Right));
+        let mut is = InputStream::from_string("1 * 2 + 3 + 4");
+        let mut ts = TokenStream::create(&mut is);
+        let program = Parser::with_table(&mut ts, table).parse_program().unwrap();
+        assert_eq!(program.to_string().trim_end(), "(* 1 (+ 2 (+ 3 4)))");
+    }
+
+    fn parse_with_errors(source: &str) -> ParseOutput {
+        let mut is = InputStream::from_string(source);
+        let mut ts = TokenStream::create(&mut is);
+        Parser::create(&mut ts).parse()
+    }
+
+    #[test]
+    fn recovery_reports_every_broken_statement() {
+        let output = parse_with_errors("a = 1 +\nb = * 2\nputs a\nc = (1 2)\nputs c; d = ]\n");
+        let positions: Vec<(u32, u32)> = output.errors.iter().map(|e| (e.get_line(), e.get_col())).collect();
+        assert_eq!(positions, vec![(2, 5), (4, 8), (5, 13)]);
+        assert_eq!(output.program.to_string(), "(error)\n(puts a)\n(= c 1)\n(puts c)\n(error)\n");
+    }
+
+    #[test]
+    fn recovery_closes_brackets_the_broken_statement_left_open() {
+        let output = parse_with_errors("z = [1, 2\nclass A\n  1 +* 2\n  def b; end\nend\ndef foo(a,\nputs(\nq = 1 +* 2\nr = 3\n");
+        let positions: Vec<(u32, u32)> = output.errors.iter().map(|e| (e.get_line(), e.get_col())).collect();
+        assert_eq!(positions, vec![(2, 1), (3, 6), (7, 5), (8, 8)]);
+        assert_eq!(output.program.to_string(), "(error)\n(class A ((error) (def b () (begin ()))))\n(error)\n(error)\n(= r 3)\n");
+    }
+
+    #[test]
+    fn recovery_stays_inside_the_enclosing_construct() {
+        let output = parse_with_errors("def f(x)\n  x + * 1\n  x\nend\nf(2)\n");
+        assert_eq!(output.errors.len(), 1);
+        assert_eq!(output.program.to_string(), "(def f (x) (begin ((error) x)))\n(f 2)\n");
+    }
+
+    #[test]
+    fn errors_list_the_expected_tokens() {
+        let output = parse_with_errors("if x\n  y\n");
+        assert_eq!(output.errors.len(), 1);
+        assert_eq!(output.errors[0].get_expected(), ["'elsif'", "'else'", "'end'"]);
+        let output = parse_with_errors("foo(1 2)");
+        assert_eq!(output.errors[0].get_expected(), ["','", "')'"]);
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use super::lexicon::{KeywordSymbol, OperatorSymbol, SeparatorSymbol, TokenKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+    NonAssoc
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fixity {
+    Prefix,
+    Infix,
+    Postfix
+}
+
+// Binding levels from loosest to tightest; `Ord` follows declaration order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum Precedence {
+    Lowest,
+    Modifier,
+    KeywordLogic,
+    KeywordNot,
+    Defined,
+    Assignment,
+    Ternary,
+    Range,
+    LogicalOr,
+    LogicalAnd,
+    Equality,
+    Comparison,
+    BitwiseOr,
+    BitwiseAnd,
+    Shift,
+    Additive,
+    Multiplicative,
+    UnaryMinus,
+    Power,
+    Unary,
+    Call
+}
+
+impl Precedence {
+    // Pratt binding power; infix operators bind their right operand at one of these.
+    pub fn binding_power(self) -> u8 { self as u8 * 2 }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperatorSpec {
+    pub precedence: Precedence,
+    pub associativity: Associativity
+}
+
+impl OperatorSpec {
+    pub fn create(precedence: Precedence, associativity: Associativity) -> OperatorSpec {
+        OperatorSpec { precedence, associativity }
+    }
+    // Minimum binding power for the right operand: left-associative and non-associative
+    // operators only accept tighter operators there, right-associative ones also their own level.
+    pub fn right_binding_power(&self) -> u8 {
+        match self.associativity {
+            Associativity::Right => self.precedence.binding_power(),
+            _ => self.precedence.binding_power() + 1
+        }
+    }
+}
+
+// Drives the Pratt parser: which tokens act as prefix, infix and postfix operators and how
+// tightly they bind. Keys are `TokenKind`s so keyword operators (`and`, `not`, modifier `if`)
+// sit in the same table as `OperatorSymbol`s.
+#[derive(Debug, Clone)]
+pub struct PrecedenceTable {
+    entries: HashMap<(TokenKind, Fixity), OperatorSpec>
+}
+
+impl PrecedenceTable {
+    pub fn empty() -> PrecedenceTable {
+        PrecedenceTable { entries: HashMap::new() }
+    }
+
+    pub fn set(&mut self, kind: TokenKind, fixity: Fixity, spec: OperatorSpec) {
+        self.entries.insert((kind, fixity), spec);
+    }
+    pub fn remove(&mut self, kind: TokenKind, fixity: Fixity) {
+        self.entries.remove(&(kind, fixity));
+    }
+    pub fn get(&self, kind: TokenKind, fixity: Fixity) -> Option<OperatorSpec> {
+        self.entries.get(&(kind, fixity)).copied()
+    }
+
+    fn set_operators(&mut self, ops: &[OperatorSymbol], fixity: Fixity, precedence: Precedence, associativity: Associativity) {
+        for op in ops {
+            self.set(TokenKind::Operator(*op), fixity, OperatorSpec::create(precedence, associativity));
+        }
+    }
+    fn set_keywords(&mut self, kws: &[KeywordSymbol], fixity: Fixity, precedence: Precedence, associativity: Associativity) {
+        for kw in kws {
+            self.set(TokenKind::Keyword(*kw), fixity, OperatorSpec::create(precedence, associativity));
+        }
+    }
+}
+
+impl Default for PrecedenceTable {
+    // Ruby's operator precedence.
+    fn default() -> PrecedenceTable {
+        use Associativity::*;
+        use Fixity::*;
+        use OperatorSymbol::*;
+        let mut table = PrecedenceTable::empty();
+        table.set_keywords(&[KeywordSymbol::IF, KeywordSymbol::UNLESS, KeywordSymbol::WHILE, KeywordSymbol::UNTIL, KeywordSymbol::RESCUE], Infix, Precedence::Modifier, Left);
+        table.set_keywords(&[KeywordSymbol::AND, KeywordSymbol::OR], Infix, Precedence::KeywordLogic, Left);
+        table.set_keywords(&[KeywordSymbol::NOT], Prefix, Precedence::KeywordNot, Right);
+        table.set_keywords(&[KeywordSymbol::DEFINED_P], Prefix, Precedence::Defined, Right);
+        table.set_operators(&[ASSIGN, PLUS_EQ, MINUS_EQ, ASTERISK_EQ, POW_EQ, SLASH_EQ, MODULO_EQ, AND_EQ, OR_EQ,
+            BIT_AND_EQ, BIT_OR_EQ, BIT_XOR_EQ, L_SHIFT_EQ, R_SHIFT_EQ], Infix, Precedence::Assignment, Right);
+        table.set_operators(&[RANGE, EXCL_RANGE], Infix, Precedence::Range, NonAssoc);
+        table.set_operators(&[OR], Infix, Precedence::LogicalOr, Left);
+        table.set_operators(&[AND], Infix, Precedence::LogicalAnd, Left);
+        table.set_operators(&[COMP, EQ, CASE_EQ, NOT_EQ, MATCH, NOT_MATCH], Infix, Precedence::Equality, NonAssoc);
+        table.set_operators(&[LT, LTE, GT, GTE], Infix, Precedence::Comparison, Left);
+        table.set_operators(&[BIT_OR, BIT_XOR], Infix, Precedence::BitwiseOr, Left);
+        table.set_operators(&[BIT_AND], Infix, Precedence::BitwiseAnd, Left);
+        table.set_operators(&[L_SHIFT, R_SHIFT], Infix, Precedence::Shift, Left);
+        table.set_operators(&[PLUS, MINUS], Infix, Precedence::Additive, Left);
+        table.set_operators(&[ASTERISK, SLASH, MODULO], Infix, Precedence::Multiplicative, Left);
+        table.set_operators(&[MINUS], Prefix, Precedence::UnaryMinus, Right);
+        table.set_operators(&[POW], Infix, Precedence::Power, Right);
+        table.set_operators(&[BANG, BIT_NOT, PLUS], Prefix, Precedence::Unary, Right);
+        table.set_operators(&[DOT, SAFE_NAV, RESOLUTION], Postfix, Precedence::Call, Left);
+        table.set(TokenKind::Separator(SeparatorSymbol::L_BRACKET), Postfix, OperatorSpec::create(Precedence::Call, Left));
+        table
+    }
+}
+
+// The binary operator a compound assignment applies, e.g. `<<=` applies `<<`.
+pub fn compound_base(op: OperatorSymbol) -> Option<OperatorSymbol> {
+    match op {
+        OperatorSymbol::PLUS_EQ     => Some(OperatorSymbol::PLUS),
+        OperatorSymbol::MINUS_EQ    => Some(OperatorSymbol::MINUS),
+        OperatorSymbol::ASTERISK_EQ => Some(OperatorSymbol::ASTERISK),
+        OperatorSymbol::POW_EQ      => Some(OperatorSymbol::POW),
+        OperatorSymbol::SLASH_EQ    => Some(OperatorSymbol::SLASH),
+        OperatorSymbol::MODULO_EQ   => Some(OperatorSymbol::MODULO),
+        OperatorSymbol::AND_EQ      => Some(OperatorSymbol::AND),
+        OperatorSymbol::OR_EQ       => Some(OperatorSymbol::OR),
+        OperatorSymbol::BIT_AND_EQ  => Some(OperatorSymbol::BIT_AND),
+        OperatorSymbol::BIT_OR_EQ   => Some(OperatorSymbol::BIT_OR),
+        OperatorSymbol::BIT_XOR_EQ  => Some(OperatorSymbol::BIT_XOR),
+        OperatorSymbol::L_SHIFT_EQ  => Some(OperatorSymbol::L_SHIFT),
+        OperatorSymbol::R_SHIFT_EQ  => Some(OperatorSymbol::R_SHIFT),
+        _                           => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infix(table: &PrecedenceTable, op: OperatorSymbol) -> OperatorSpec {
+        table.get(TokenKind::Operator(op), Fixity::Infix).unwrap_or_else(|| panic!("{:?} is not infix", op))
+    }
+
+    #[test]
+    fn default_table_follows_ruby_precedence() {
+        let table = PrecedenceTable::default();
+        let order = [OperatorSymbol::ASSIGN, OperatorSymbol::RANGE, OperatorSymbol::OR, OperatorSymbol::AND, OperatorSymbol::EQ,
+            OperatorSymbol::LT, OperatorSymbol::BIT_OR, OperatorSymbol::BIT_AND, OperatorSymbol::L_SHIFT, OperatorSymbol::PLUS,
+            OperatorSymbol::ASTERISK, OperatorSymbol::POW];
+        for pair in order.windows(2) {
+            assert!(infix(&table, pair[0]).precedence < infix(&table, pair[1]).precedence, "{:?} < {:?}", pair[0], pair[1]);
+        }
+        let and = table.get(TokenKind::Keyword(KeywordSymbol::AND), Fixity::Infix).unwrap();
+        assert!(and.precedence < infix(&table, OperatorSymbol::ASSIGN).precedence);
+        assert!(table.get(TokenKind::Operator(OperatorSymbol::MINUS), Fixity::Prefix).is_some());
+        assert!(table.get(TokenKind::Operator(OperatorSymbol::BANG), Fixity::Infix).is_none());
+    }
+
+    #[test]
+    fn right_associative_operators_accept_their_own_level() {
+        let table = PrecedenceTable::default();
+        let pow = infix(&table, OperatorSymbol::POW);
+        assert_eq!(pow.right_binding_power(), pow.precedence.binding_power());
+        let minus = infix(&table, OperatorSymbol::MINUS);
+        assert_eq!(minus.right_binding_power(), minus.precedence.binding_power() + 1);
+        assert_eq!(compound_base(OperatorSymbol::L_SHIFT_EQ), Some(OperatorSymbol::L_SHIFT));
+        assert_eq!(compound_base(OperatorSymbol::EQ), None);
+    }
+}
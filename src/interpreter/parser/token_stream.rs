@@ -1,137 +1,705 @@
-use super::input_stream::InputStream;
-use super::lexicon::{
-    IntoToken,
-    Identifier,
-    IdentifierSymbol,
-    Keyword,
-    KeywordSymbol,
-    Operator,
-    OperatorSymbol,
-    Separator,
-    SeparatorSymbol
-};
-
-#[derive(Debug)]
-pub struct Token<T: ?Sized + IntoToken> {
-    line: u32,
-    col: u32,
-    data: Box<T>
-}
-
-static WHITESPACE_CHARS: [char; 4] = [' ', '\t', '\n', '\r'];
-static COMMENT_START_CHAR: char = '#';
-static STRING_START_CHAR: char = '\"';
-static DIGIT_CHARS: [char; 10] = ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
-static LETTER_CHARS: [char; 52] = ['A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z'];
-static OPERATION_CHARS: [char; 12] = ['.', '+', '-', '*', '/', '%', '=', '&', '|', '<', '>', '!'];
-static SEPARATOR_CHARS: [char; 9] = [',', ';', '(', ')', '{', '}', '[', ']', '|'];
-static IDENTIFIER_SPECIAL_CHARS: [char; 3] = ['?', '!', '_'];
-
-pub struct TokenStream<'a> {
-    input_stream: &'a mut InputStream<'a>
-}
-
-impl<'a> TokenStream<'a> {
-    pub fn create(is: &'a mut InputStream<'a>) -> TokenStream<'a> {
-        TokenStream { input_stream: is }
-    }
-
-    fn is_comment_start(c: char) -> bool { c == COMMENT_START_CHAR }
-    fn is_string_start(c: char) -> bool { c == STRING_START_CHAR }
-    fn is_number_start(c: char) -> bool { DIGIT_CHARS.contains(&c) }
-    fn is_identifier_start(c: char) -> bool { LETTER_CHARS.contains(&c) }
-    fn is_operator_start(c: char) -> bool { OPERATION_CHARS.contains(&c) }
-    fn is_separator_start(c: char) -> bool { SEPARATOR_CHARS.contains(&c) }
-
-    fn is_whitespace(c: char) -> bool { WHITESPACE_CHARS.contains(&c) }
-    fn is_comment(c: char) -> bool { c != '\n' && c != '\r' }
-    fn is_number(c: char) -> bool { DIGIT_CHARS.contains(&c) || c == '.' }
-    fn is_identifier(c: char) -> bool { LETTER_CHARS.contains(&c) || DIGIT_CHARS.contains(&c) || IDENTIFIER_SPECIAL_CHARS.contains(&c) }
-    fn is_operator(c: char) -> bool { OPERATION_CHARS.contains(&c) }
-
-    fn read_comment(&mut self) -> Token<dyn IntoToken> {
-        let v = self.read_while(TokenStream::is_comment);
-        Token { line: self.input_stream.get_line(), col: self.input_stream.get_col(), data: Box::new(Identifier::create(IdentifierSymbol::COMMENT, v)) }
-    }
-    fn read_string(&mut self) -> Token<dyn IntoToken> {
-        let pos: (u32, u32) = (self.input_stream.get_line(), self.input_stream.get_col());
-        let start_char: char = self.input_stream.next().unwrap();
-        let v = self.read_while_string(start_char);
-        self.input_stream.next();
-        Token { line: pos.0, col: pos.1, data: Box::new(Identifier::create(IdentifierSymbol::STRING, v)) }
-    }
-    fn read_number(&mut self) -> Token<dyn IntoToken> {
-        let v = self.read_while(TokenStream::is_number);
-        if v.contains('.') {
-            Token { line: self.input_stream.get_line(), col: self.input_stream.get_col(), data: Box::new(Identifier::create(IdentifierSymbol::FLOAT, v)) }
-        } else {
-            Token { line: self.input_stream.get_line(), col: self.input_stream.get_col(), data: Box::new(Identifier::create(IdentifierSymbol::INT, v)) }
-        }
-    }
-    fn read_identifier(&mut self) -> Token<dyn IntoToken> {
-        let pos: (u32, u32) = (self.input_stream.get_line(), self.input_stream.get_col());
-        let v: String = self.read_while(TokenStream::is_identifier);
-        match KeywordSymbol::from_string(&v) {
-            KeywordSymbol::ILLEGAL => Token { line: pos.0, col: pos.1, data: Box::new(Identifier::create(IdentifierSymbol::VARIABLE, v)) },
-            _ => Token { line: pos.0, col: pos.1, data: Box::new(Keyword::create(KeywordSymbol::from_string(&v), v)) }
-        }
-    }
-    fn read_operator(&mut self) -> Token<dyn IntoToken> {
-        let v = self.read_while(TokenStream::is_operator);
-        match OperatorSymbol::from_string(&v) {
-            OperatorSymbol::ILLEGAL => {
-                if v.len() == 1 {
-                    Token { line: self.input_stream.get_line(), col: self.input_stream.get_col(), data: Box::new(Separator::create(SeparatorSymbol::from_string(&v), v)) }
-                } else {
-                    Token { line: self.input_stream.get_line(), col: self.input_stream.get_col(), data: Box::new(Operator::create(OperatorSymbol::from_string(&v), v)) }
-                }
-            },
-            _ => Token { line: self.input_stream.get_line(), col: self.input_stream.get_col(), data: Box::new(Operator::create(OperatorSymbol::from_string(&v), v)) }
-        }
-    }
-    fn read_separator(&mut self) -> Token<dyn IntoToken> {
-        let v = self.input_stream.next().unwrap().to_string();
-        Token { line: self.input_stream.get_line(), col: self.input_stream.get_col(), data: Box::new(Separator::create(SeparatorSymbol::from_string(&v), v)) }
-    }
-
-    fn read_while(&mut self, func: fn(char) -> bool) -> String {
-        let mut out: String = String::new();
-        let mut unboxed_next: Option<&char> = self.input_stream.peek();
-        while unboxed_next.is_some() && func(*unboxed_next.unwrap()) {
-            out.push(self.input_stream.next().unwrap());
-            unboxed_next = self.input_stream.peek();
-        }
-        out
-    }
-    fn read_while_string(&mut self, end: char) -> String {
-        let mut escaped = false;
-        let mut out: String = String::new();
-        while let Some(next_c) = self.input_stream.peek() {
-            if escaped {
-                out.push(self.input_stream.next().unwrap());
-                escaped = false;
-            } else if *next_c == '\\' {
-                out.push(self.input_stream.next().unwrap());
-                escaped = true;
-            } else if end == *next_c {
-                break;
-            } else {
-                out.push(self.input_stream.next().unwrap());
-            }
-        }
-        out
-    }
-
-    pub fn read_next(&mut self) -> Option<Token<dyn IntoToken>> {
-        self.read_while(TokenStream::is_whitespace);
-        if self.input_stream.is_eof() { return None; }
-        let c: &char = self.input_stream.peek().unwrap();
-        if TokenStream::is_comment_start(*c) { return Some(self.read_comment()); }
-        if TokenStream::is_string_start(*c) { return Some(self.read_string()); }
-        if TokenStream::is_number_start(*c) { return Some(self.read_number()); }
-        if TokenStream::is_identifier_start(*c) { return Some(self.read_identifier()); }
-        if TokenStream::is_operator_start(*c) { return Some(self.read_operator()); }
-        if TokenStream::is_separator_start(*c) { return Some(self.read_separator()); }
-        panic!("can't process character <{}> at [l: {}, c: {}]", *c as u32, self.input_stream.get_line(), self.input_stream.get_col())
-    }
-}
\ No newline at end of file
+use crate::interpreter::diagnostics::{Diagnostic, Span};
+use crate::interpreter::source_map::FileId;
+use super::input_stream::InputStream;
+use super::lexicon::{
+    IntoToken,
+    Identifier,
+    IdentifierSymbol,
+    Keyword,
+    KeywordSymbol,
+    Operator,
+    OperatorSymbol,
+    Separator,
+    SeparatorSymbol,
+    TokenKind
+};
+
+#[derive(Debug)]
+pub struct Token<T: ?Sized + IntoToken> {
+    file: FileId,
+    line: u32,
+    col: u32,
+    data: Box<T>
+}
+
+impl<T: ?Sized + IntoToken> Token<T> {
+    pub fn get_file(&self) -> FileId { self.file }
+    pub fn get_line(&self) -> u32 { self.line }
+    pub fn get_col(&self) -> u32 { self.col }
+    pub fn get_data(&self) -> &T { &self.data }
+}
+
+static WHITESPACE_CHARS: [char; 4] = [' ', '\t', '\n', '\r'];
+static COMMENT_START_CHAR: char = '#';
+static STRING_START_CHAR: char = '\"';
+static DIGIT_CHARS: [char; 10] = ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
+static LETTER_CHARS: [char; 52] = ['A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z'];
+static OPERATION_CHARS: [char; 15] = ['.', '+', '-', '*', '/', '%', '=', '&', '|', '^', '~', '<', '>', '!', ':'];
+static SEPARATOR_CHARS: [char; 9] = [',', ';', '(', ')', '{', '}', '[', ']', '|'];
+static MAX_OPERATOR_LEN: usize = 3;
+static SIGIL_CHARS: [char; 2] = ['@', '$'];
+static SYMBOL_START_CHAR: char = ':';
+// `?` and `!` may end a method name, but never appear inside one.
+static METHOD_SUFFIX_CHARS: [char; 2] = ['?', '!'];
+// Punctuation globals such as `$!` (the current exception) and `$:` (the load path).
+static SPECIAL_GLOBAL_CHARS: [char; 12] = ['!', '@', ':', '"', '~', ';', ',', '/', '\\', '&', '<', '>'];
+
+// Whether the next token begins an expression (`Begin`) or follows a complete value (`End`).
+// A line break at the beginning of an expression continues the statement. `Mid` follows
+// `return`, `break` and `next`, which may take a value but also end a statement on their own,
+// and `rescue`, `else` and `ensure`, whose bodies may start on the next line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LexState {
+    Begin,
+    Mid,
+    End
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LexMode {
+    Normal,
+    // Just after `do` or `{`, where `|` opens block parameters; over after one token.
+    BlockStart,
+    // Inside block parameters, where the next `|` closes them.
+    BlockParams
+}
+
+pub struct TokenStream<'a> {
+    input_stream: &'a mut InputStream<'a>,
+    state: LexState,
+    modes: Vec<LexMode>,
+    brackets: Vec<SeparatorSymbol>,
+    // Just after `def` (or `def self.`), where operators such as `+` and `[]` name the method
+    // being defined rather than starting an expression.
+    method_name_next: bool,
+    // Just after a name that may be a method, where `foo /x/` passes a regexp argument.
+    command_name: bool,
+    // The name was written without a receiver, so it may also be a local variable.
+    bare_name: bool,
+    // Just after `.` or `&.`, where a keyword such as `class` is a method name.
+    after_dot: bool,
+    diagnostics: Vec<Diagnostic>
+}
+
+impl<'a> TokenStream<'a> {
+    pub fn create(is: &'a mut InputStream<'a>) -> TokenStream<'a> {
+        TokenStream { input_stream: is, state: LexState::Begin, modes: vec![LexMode::Normal], brackets: Vec::new(), method_name_next: false, command_name: false, bare_name: false, after_dot: false, diagnostics: Vec::new() }
+    }
+
+    pub fn get_file(&self) -> FileId { self.input_stream.get_file() }
+    pub fn get_state(&self) -> LexState { self.state }
+    // Problems found while lexing; the offending input is skipped and lexing carries on.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> { std::mem::take(&mut self.diagnostics) }
+    // `(`, `[` and `{` not closed yet; a line break inside `(` or `[` doesn't end a statement.
+    pub fn get_bracket_depth(&self) -> usize { self.brackets.len() }
+    // Forgets brackets opened past `depth`, for the parser to call when it skips a broken
+    // statement that left some open. The statement is over, so the next line break ends it even
+    // straight after an opening bracket.
+    pub fn close_brackets(&mut self, depth: usize) {
+        if self.brackets.len() > depth {
+            self.brackets.truncate(depth);
+            self.state = LexState::End;
+        }
+    }
+    // The parser knows which names are local variables; after one, `x /2/ 1` divides rather
+    // than passing a regexp to a method `x`. Must be called before the next token is read.
+    pub fn mark_local_variable(&mut self) {
+        if self.bare_name { self.command_name = false; }
+    }
+
+    // Only the parser knows where a block begins, so it pushes `BlockStart` after reading `do`
+    // or `{` (or `BlockParams` after the `|` of a lambda); the lexer leaves those modes itself.
+    // Tokens read before a push were lexed in the old mode.
+    pub fn get_mode(&self) -> LexMode { *self.modes.last().unwrap() }
+    pub fn push_mode(&mut self, mode: LexMode) { self.modes.push(mode); }
+    pub fn pop_mode(&mut self) {
+        if self.modes.len() > 1 { self.modes.pop(); }
+    }
+
+    fn is_comment_start(c: char) -> bool { c == COMMENT_START_CHAR }
+    fn is_string_start(c: char) -> bool { c == STRING_START_CHAR }
+    fn is_number_start(c: char) -> bool { DIGIT_CHARS.contains(&c) }
+    fn is_identifier_start(c: char) -> bool { LETTER_CHARS.contains(&c) || c == '_' }
+    fn is_operator_start(c: char) -> bool { OPERATION_CHARS.contains(&c) }
+    fn is_separator_start(c: char) -> bool { SEPARATOR_CHARS.contains(&c) }
+
+    fn is_whitespace(c: char) -> bool { WHITESPACE_CHARS.contains(&c) }
+    fn is_comment(c: char) -> bool { c != '\n' && c != '\r' }
+    fn is_sigil_start(c: char) -> bool { SIGIL_CHARS.contains(&c) }
+    fn is_number(c: char) -> bool { DIGIT_CHARS.contains(&c) || c == '_' }
+    fn is_identifier(c: char) -> bool { LETTER_CHARS.contains(&c) || DIGIT_CHARS.contains(&c) || c == '_' }
+    fn is_operator(c: char) -> bool { OPERATION_CHARS.contains(&c) }
+
+    // A `/` begins a regexp where an expression begins, or as the first argument of a command
+    // when written `foo /x/`: a space before the slash but not after it, and a closing slash
+    // on the same line. After a local variable (see `mark_local_variable`) it always divides.
+    fn is_regexp_start(&mut self, spaced: bool) -> bool {
+        if self.method_name_next { return false; }
+        match self.state {
+            LexState::Begin | LexState::Mid => true,
+            LexState::End if !spaced || !self.command_name => false,
+            LexState::End => {
+                if matches!(self.input_stream.peek_nth(1), None | Some(' ') | Some('\t') | Some('\n') | Some('=')) {
+                    return false;
+                }
+                let mut n = 1;
+                let mut escaped = false;
+                while let Some(c) = self.input_stream.peek_nth(n) {
+                    match c {
+                        '\n' => return false,
+                        '/' if !escaped => return true,
+                        _ => {}
+                    }
+                    escaped = c == '\\' && !escaped;
+                    n += 1;
+                }
+                false
+            }
+        }
+    }
+
+    fn read_comment(&mut self) -> Token<dyn IntoToken> {
+        let pos: (u32, u32) = (self.input_stream.get_line(), self.input_stream.get_col());
+        let v = self.read_while(TokenStream::is_comment);
+        Token { file: self.input_stream.get_file(), line: pos.0, col: pos.1, data: Box::new(Identifier::create(IdentifierSymbol::COMMENT, v)) }
+    }
+    fn read_string(&mut self) -> Token<dyn IntoToken> {
+        let pos: (u32, u32) = (self.input_stream.get_line(), self.input_stream.get_col());
+        let start_char: char = self.input_stream.next().unwrap();
+        let v = self.read_while_string(start_char);
+        if self.input_stream.next().is_none() {
+            self.diagnostics.push(Diagnostic::error(String::from("unterminated string"))
+                .with_label(Span::create(self.input_stream.get_file(), pos.0, pos.1, 1), "string starts here")
+                .with_help(&format!("add a closing `{}`", start_char)));
+        }
+        Token { file: self.input_stream.get_file(), line: pos.0, col: pos.1, data: Box::new(Identifier::create(IdentifierSymbol::STRING, v)) }
+    }
+    // `/pattern/flags`; the value keeps the slashes and flags for the parser to split.
+    fn read_regexp(&mut self) -> Token<dyn IntoToken> {
+        let pos: (u32, u32) = (self.input_stream.get_line(), self.input_stream.get_col());
+        let mut v: String = self.input_stream.next().unwrap().to_string();
+        v.push_str(&self.read_while_string('/'));
+        if self.input_stream.next().is_none() {
+            self.diagnostics.push(Diagnostic::error(String::from("unterminated regexp"))
+                .with_label(Span::create(self.input_stream.get_file(), pos.0, pos.1, 1), "regexp starts here")
+                .with_help("add a closing `/`"));
+        }
+        v.push('/');
+        v.push_str(&self.read_while(|c| matches!(c, 'i' | 'm' | 'x' | 'o')));
+        Token { file: self.input_stream.get_file(), line: pos.0, col: pos.1, data: Box::new(Identifier::create(IdentifierSymbol::REGEXP, v)) }
+    }
+    fn read_number(&mut self) -> Token<dyn IntoToken> {
+        let pos: (u32, u32) = (self.input_stream.get_line(), self.input_stream.get_col());
+        let mut v = self.read_while(TokenStream::is_number);
+        // Only a `.` followed by a digit continues the number, so `1..3` and `1.to_s` stay apart.
+        if self.input_stream.peek() == Some(&'.') && self.input_stream.peek_nth(1).is_some_and(|c| DIGIT_CHARS.contains(&c)) {
+            v.push(self.input_stream.next().unwrap());
+            v.push_str(&self.read_while(TokenStream::is_number));
+        }
+        let mut sym = if v.contains('.') { IdentifierSymbol::FLOAT } else { IdentifierSymbol::INT };
+        // `3r`, `2i` and `1.5ri`; a suffix that runs on into a name, as in `3if`, isn't one.
+        if self.read_number_suffix('r', &mut v) {
+            sym = IdentifierSymbol::RATIONAL;
+        }
+        if self.read_number_suffix('i', &mut v) {
+            sym = IdentifierSymbol::IMAGINARY;
+        }
+        Token { file: self.input_stream.get_file(), line: pos.0, col: pos.1, data: Box::new(Identifier::create(sym, v)) }
+    }
+    fn read_number_suffix(&mut self, suffix: char, v: &mut String) -> bool {
+        let ends = |c: Option<char>| !c.is_some_and(TokenStream::is_identifier);
+        let taken = self.input_stream.peek() == Some(&suffix)
+            && (ends(self.input_stream.peek_nth(1)) || (suffix == 'r' && self.input_stream.peek_nth(1) == Some('i') && ends(self.input_stream.peek_nth(2))));
+        if taken {
+            v.push(self.input_stream.next().unwrap());
+        }
+        taken
+    }
+    // A name with an optional trailing `?` or `!`. The suffix isn't taken when it starts an
+    // operator such as `!=`, or when more name characters follow it, so `x!=y` is `x != y`.
+    fn read_name(&mut self) -> String {
+        let mut v: String = self.read_while(TokenStream::is_identifier);
+        if let Some(suffix) = self.input_stream.peek().copied().filter(|c| METHOD_SUFFIX_CHARS.contains(c)) {
+            let after = self.input_stream.peek_nth(1);
+            let starts_operator = after == Some('=') && !matches!(self.input_stream.peek_nth(2), Some('=') | Some('~'));
+            if !starts_operator && !after.is_some_and(TokenStream::is_identifier) {
+                self.input_stream.next();
+                v.push(suffix);
+            }
+        }
+        v
+    }
+    fn read_identifier(&mut self) -> Token<dyn IntoToken> {
+        let pos: (u32, u32) = (self.input_stream.get_line(), self.input_stream.get_col());
+        let v: String = self.read_name();
+        match KeywordSymbol::from_string(&v) {
+            KeywordSymbol::ILLEGAL => Token { file: self.input_stream.get_file(), line: pos.0, col: pos.1, data: Box::new(Identifier::create(IdentifierSymbol::VARIABLE, v)) },
+            _ => Token { file: self.input_stream.get_file(), line: pos.0, col: pos.1, data: Box::new(Keyword::create(KeywordSymbol::from_string(&v), v)) }
+        }
+    }
+    fn read_sigil_variable(&mut self) -> Token<dyn IntoToken> {
+        let pos: (u32, u32) = (self.input_stream.get_line(), self.input_stream.get_col());
+        let sigil: char = self.input_stream.next().unwrap();
+        let mut v: String = sigil.to_string();
+        match self.input_stream.peek().copied() {
+            Some(c) if sigil == '$' && SPECIAL_GLOBAL_CHARS.contains(&c) => v.push(self.input_stream.next().unwrap()),
+            _ => v.push_str(&self.read_while(TokenStream::is_identifier))
+        }
+        let sym = if sigil == '@' { IdentifierSymbol::INSTANCE_VARIABLE } else { IdentifierSymbol::GLOBAL_VARIABLE };
+        Token { file: self.input_stream.get_file(), line: pos.0, col: pos.1, data: Box::new(Identifier::create(sym, v)) }
+    }
+    // `:name`, `:[]`, or an operator such as `:+` outside of a binary position. The value
+    // excludes the leading colon.
+    fn read_symbol(&mut self) -> Option<Token<dyn IntoToken>> {
+        let pos: (u32, u32) = (self.input_stream.get_line(), self.input_stream.get_col());
+        let next = self.input_stream.peek_nth(1)?;
+        let v: String = if TokenStream::is_identifier_start(next) {
+            self.input_stream.next();
+            let mut v = self.read_name();
+            // `:name=` names a setter, but `:a=>1` is a hash pair and `:a==b` a comparison.
+            if !v.ends_with(|c: char| METHOD_SUFFIX_CHARS.contains(&c)) && self.input_stream.peek() == Some(&'=')
+                && !matches!(self.input_stream.peek_nth(1), Some('=') | Some('~') | Some('>')) {
+                v.push(self.input_stream.next().unwrap());
+            }
+            v
+        } else if next == '[' && self.input_stream.peek_nth(2) == Some(']') {
+            self.input_stream.next();
+            let mut v: String = String::new();
+            v.push(self.input_stream.next().unwrap());
+            v.push(self.input_stream.next().unwrap());
+            if self.input_stream.peek() == Some(&'=') { v.push(self.input_stream.next().unwrap()); }
+            v
+        } else if TokenStream::is_operator(next) && next != SYMBOL_START_CHAR && self.state != LexState::End {
+            self.input_stream.next();
+            let len = self.longest_operator_len();
+            if len == 0 { return None; }
+            (0..len).map(|_| self.input_stream.next().unwrap()).collect()
+        } else {
+            return None;
+        };
+        Some(Token { file: self.input_stream.get_file(), line: pos.0, col: pos.1, data: Box::new(Identifier::create(IdentifierSymbol::SYMBOL, v)) })
+    }
+    fn read_operator(&mut self) -> Token<dyn IntoToken> {
+        let pos: (u32, u32) = (self.input_stream.get_line(), self.input_stream.get_col());
+        if self.input_stream.peek() == Some(&SYMBOL_START_CHAR) && self.input_stream.peek_nth(1) != Some(SYMBOL_START_CHAR) {
+            if let Some(symbol) = self.read_symbol() { return symbol; }
+        }
+        if self.input_stream.peek() == Some(&'|') {
+            if let Some(bar) = self.read_block_param_bar() { return bar; }
+        }
+        let len = self.longest_operator_len();
+        let mut v: String = String::new();
+        for _ in 0..len.max(1) {
+            v.push(self.input_stream.next().unwrap());
+        }
+        match OperatorSymbol::from_string(&v) {
+            OperatorSymbol::ILLEGAL => Token { file: self.input_stream.get_file(), line: pos.0, col: pos.1, data: Box::new(Separator::create(SeparatorSymbol::from_string(&v), v)) },
+            sym => Token { file: self.input_stream.get_file(), line: pos.0, col: pos.1, data: Box::new(Operator::create(sym, v)) }
+        }
+    }
+    // `|` delimits block parameters when it opens them right after `do` or `{` (`do |a|`,
+    // `{ || ... }`) or closes an open parameter list; otherwise it's `|`, `||`, `|=` or `||=`.
+    fn read_block_param_bar(&mut self) -> Option<Token<dyn IntoToken>> {
+        let pos: (u32, u32) = (self.input_stream.get_line(), self.input_stream.get_col());
+        match self.get_mode() {
+            LexMode::BlockStart => {
+                self.pop_mode();
+                self.push_mode(LexMode::BlockParams);
+            },
+            LexMode::BlockParams => self.pop_mode(),
+            LexMode::Normal => return None
+        }
+        let v = self.input_stream.next().unwrap().to_string();
+        Some(Token { file: self.input_stream.get_file(), line: pos.0, col: pos.1, data: Box::new(Separator::create(SeparatorSymbol::BAR, v)) })
+    }
+    // Length of the longest prefix of the upcoming operator characters that names an operator,
+    // so `+-1` lexes as `+` followed by `-` rather than one illegal `+-`.
+    fn longest_operator_len(&mut self) -> usize {
+        let mut candidate: String = String::new();
+        let mut longest: usize = 0;
+        while let Some(c) = self.input_stream.peek_nth(candidate.chars().count()) {
+            if !TokenStream::is_operator(c) || candidate.len() == MAX_OPERATOR_LEN { break; }
+            candidate.push(c);
+            if OperatorSymbol::from_string(&candidate) != OperatorSymbol::ILLEGAL {
+                longest = candidate.len();
+            }
+        }
+        longest
+    }
+    fn read_separator(&mut self) -> Token<dyn IntoToken> {
+        let pos: (u32, u32) = (self.input_stream.get_line(), self.input_stream.get_col());
+        let v = self.input_stream.next().unwrap().to_string();
+        Token { file: self.input_stream.get_file(), line: pos.0, col: pos.1, data: Box::new(Separator::create(SeparatorSymbol::from_string(&v), v)) }
+    }
+    fn read_newline(&mut self) -> Token<dyn IntoToken> {
+        let pos: (u32, u32) = (self.input_stream.get_line(), self.input_stream.get_col());
+        let v = self.input_stream.next().unwrap().to_string();
+        Token { file: self.input_stream.get_file(), line: pos.0, col: pos.1, data: Box::new(Separator::create(SeparatorSymbol::NEWLINE, v)) }
+    }
+
+    // A line break ends a statement only after a complete value, outside `(...)` and `[...]`,
+    // and when the next line doesn't continue a method chain with a leading `.` or `&.`.
+    fn is_terminating_newline(&mut self) -> bool {
+        if self.state == LexState::Begin { return false; }
+        match self.brackets.last() {
+            Some(SeparatorSymbol::L_PAREN) | Some(SeparatorSymbol::L_BRACKET) => return false,
+            _ => {}
+        }
+        let mut n = 1;
+        while let Some(c) = self.input_stream.peek_nth(n) {
+            if !TokenStream::is_whitespace(c) { break; }
+            n += 1;
+        }
+        match (self.input_stream.peek_nth(n), self.input_stream.peek_nth(n + 1)) {
+            (Some('.'), Some(next)) => next == '.',
+            (Some('&'), Some('.')) => false,
+            _ => true
+        }
+    }
+    fn skip_whitespace(&mut self) -> Option<Token<dyn IntoToken>> {
+        while let Some(c) = self.input_stream.peek().copied() {
+            if c == '\n' {
+                if self.is_terminating_newline() { return Some(self.read_newline()); }
+                self.input_stream.next();
+            } else if c == '\\' && self.input_stream.peek_nth(1) == Some('\n') {
+                self.input_stream.next();
+                self.input_stream.next();
+            } else if TokenStream::is_whitespace(c) {
+                self.input_stream.next();
+            } else {
+                break;
+            }
+        }
+        None
+    }
+
+    fn read_while(&mut self, func: fn(char) -> bool) -> String {
+        let mut out: String = String::new();
+        let mut unboxed_next: Option<&char> = self.input_stream.peek();
+        while unboxed_next.is_some() && func(*unboxed_next.unwrap()) {
+            out.push(self.input_stream.next().unwrap());
+            unboxed_next = self.input_stream.peek();
+        }
+        out
+    }
+    fn read_while_string(&mut self, end: char) -> String {
+        let mut escaped = false;
+        let mut out: String = String::new();
+        while let Some(next_c) = self.input_stream.peek() {
+            if escaped {
+                out.push(self.input_stream.next().unwrap());
+                escaped = false;
+            } else if *next_c == '\\' {
+                out.push(self.input_stream.next().unwrap());
+                escaped = true;
+            } else if end == *next_c {
+                break;
+            } else {
+                out.push(self.input_stream.next().unwrap());
+            }
+        }
+        out
+    }
+
+    fn update_state(&mut self, kind: TokenKind) {
+        self.state = match kind {
+            TokenKind::Identifier(IdentifierSymbol::COMMENT) => self.state,
+            TokenKind::Identifier(_) => LexState::End,
+            TokenKind::Keyword(KeywordSymbol::END)
+            | TokenKind::Keyword(KeywordSymbol::SELF)
+            | TokenKind::Keyword(KeywordSymbol::NIL)
+            | TokenKind::Keyword(KeywordSymbol::TRUE)
+            | TokenKind::Keyword(KeywordSymbol::FALSE)
+            | TokenKind::Keyword(KeywordSymbol::YIELD)
+            | TokenKind::Keyword(KeywordSymbol::SUPER)
+            | TokenKind::Keyword(KeywordSymbol::REDO)
+            | TokenKind::Keyword(KeywordSymbol::RETRY)
+            | TokenKind::Keyword(KeywordSymbol::__LINE__)
+            | TokenKind::Keyword(KeywordSymbol::__FILE__)
+            | TokenKind::Keyword(KeywordSymbol::__ENCODING__) => LexState::End,
+            TokenKind::Keyword(KeywordSymbol::RETURN)
+            | TokenKind::Keyword(KeywordSymbol::BREAK)
+            | TokenKind::Keyword(KeywordSymbol::NEXT)
+            | TokenKind::Keyword(KeywordSymbol::RESCUE)
+            | TokenKind::Keyword(KeywordSymbol::ELSE)
+            | TokenKind::Keyword(KeywordSymbol::ENSURE) => LexState::Mid,
+            TokenKind::Keyword(_) => LexState::Begin,
+            TokenKind::Operator(_) => LexState::Begin,
+            TokenKind::Separator(SeparatorSymbol::R_PAREN)
+            | TokenKind::Separator(SeparatorSymbol::R_BRACKET)
+            | TokenKind::Separator(SeparatorSymbol::R_BRACE) => LexState::End,
+            TokenKind::Separator(_) => LexState::Begin
+        };
+    }
+
+    fn update_brackets(&mut self, kind: TokenKind) {
+        match kind {
+            TokenKind::Separator(sym @ SeparatorSymbol::L_PAREN)
+            | TokenKind::Separator(sym @ SeparatorSymbol::L_BRACKET)
+            | TokenKind::Separator(sym @ SeparatorSymbol::L_BRACE) => self.brackets.push(sym),
+            TokenKind::Separator(SeparatorSymbol::R_PAREN)
+            | TokenKind::Separator(SeparatorSymbol::R_BRACKET)
+            | TokenKind::Separator(SeparatorSymbol::R_BRACE) => { self.brackets.pop(); },
+            _ => {}
+        }
+    }
+
+    pub fn read_next(&mut self) -> Option<Token<dyn IntoToken>> {
+        let block_start = self.get_mode() == LexMode::BlockStart;
+        let tok = self.read_token()?;
+        if block_start && self.get_mode() == LexMode::BlockStart {
+            self.pop_mode();
+        }
+        let kind = tok.get_data().get_kind();
+        let has_receiver = self.after_dot;
+        self.update_state(kind);
+        self.update_brackets(kind);
+        if self.after_dot && matches!(kind, TokenKind::Keyword(_)) {
+            self.state = LexState::End;
+        }
+        self.after_dot = matches!(kind, TokenKind::Operator(OperatorSymbol::DOT) | TokenKind::Operator(OperatorSymbol::SAFE_NAV));
+        if self.method_name_next && matches!(kind, TokenKind::Operator(op) if op != OperatorSymbol::DOT) {
+            self.state = LexState::End;
+        }
+        self.command_name = kind == TokenKind::Identifier(IdentifierSymbol::VARIABLE) && !self.method_name_next;
+        self.bare_name = self.command_name && !has_receiver;
+        self.method_name_next = match kind {
+            TokenKind::Keyword(KeywordSymbol::DEF) => true,
+            TokenKind::Keyword(KeywordSymbol::SELF) | TokenKind::Operator(OperatorSymbol::DOT) => self.method_name_next,
+            _ => false
+        };
+        Some(tok)
+    }
+    fn read_token(&mut self) -> Option<Token<dyn IntoToken>> {
+        let start = (self.input_stream.get_line(), self.input_stream.get_col());
+        if let Some(newline) = self.skip_whitespace() { return Some(newline); }
+        let spaced = start != (self.input_stream.get_line(), self.input_stream.get_col());
+        if self.input_stream.is_eof() { return None; }
+        if self.input_stream.peek() == Some(&'/') && self.is_regexp_start(spaced) { return Some(self.read_regexp()); }
+        let c: &char = self.input_stream.peek().unwrap();
+        if TokenStream::is_comment_start(*c) { return Some(self.read_comment()); }
+        if TokenStream::is_string_start(*c) { return Some(self.read_string()); }
+        if TokenStream::is_number_start(*c) { return Some(self.read_number()); }
+        if TokenStream::is_identifier_start(*c) { return Some(self.read_identifier()); }
+        if TokenStream::is_sigil_start(*c) { return Some(self.read_sigil_variable()); }
+        if TokenStream::is_operator_start(*c) { return Some(self.read_operator()); }
+        if TokenStream::is_separator_start(*c) { return Some(self.read_separator()); }
+        let c: char = *c;
+        let span = Span::create(self.input_stream.get_file(), self.input_stream.get_line(), self.input_stream.get_col(), 1);
+        self.diagnostics.push(Diagnostic::error(format!("unexpected character {:?}", c))
+            .with_label(span, "not valid in jasper source")
+            .with_note(&format!("the character is U+{:04X}", c as u32)));
+        self.input_stream.next();
+        self.read_token()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static OPERATORS: [&str; 46] = [
+        "=", "+", "+=", "-", "-=", "!", "*", "*=", "**", "**=", "/", "/=", ".", "&.", "&&", "&&=",
+        "||", "||=", "&", "&=", "|", "|=", "^", "^=", "~", "<<", "<<=", ">>", ">>=", "%", "%=",
+        "=~", "!~", "<", "<=", ">", ">=", "<=>", "==", "===", "!=", "..", "...", "::", "->", "=>"
+    ];
+
+    fn lex(src: &str) -> Vec<String> {
+        let mut is = InputStream::from_string(src);
+        let mut ts = TokenStream::create(&mut is);
+        let mut out = Vec::new();
+        while let Some(tok) = ts.read_next() {
+            out.push(tok.get_data().get_value().clone());
+        }
+        out
+    }
+
+    fn lex_symbols(src: &str) -> Vec<String> {
+        let mut is = InputStream::from_string(src);
+        let mut ts = TokenStream::create(&mut is);
+        let mut out = Vec::new();
+        while let Some(tok) = ts.read_next() {
+            out.push(tok.get_data().get_symbol());
+        }
+        out
+    }
+
+    // Pushes `mode` after the token at `at`, as the parser does.
+    fn lex_symbols_in_mode(src: &str, at: usize, mode: LexMode) -> Vec<String> {
+        let mut is = InputStream::from_string(src);
+        let mut ts = TokenStream::create(&mut is);
+        let mut out = Vec::new();
+        while let Some(tok) = ts.read_next() {
+            out.push(tok.get_data().get_symbol());
+            if out.len() == at + 1 {
+                ts.push_mode(mode);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn every_operator_lexes_to_itself() {
+        for op in OPERATORS.iter() {
+            assert_ne!(OperatorSymbol::from_string(op), OperatorSymbol::ILLEGAL, "{}", op);
+            assert_eq!(lex(&format!("a {} b", op)), vec!["a", op, "b"], "{}", op);
+        }
+    }
+
+    #[test]
+    fn adjacent_operators_split_on_longest_match() {
+        for first in OPERATORS.iter() {
+            for second in OPERATORS.iter() {
+                // A slash right after an operator starts a regexp literal instead.
+                if second.starts_with('/') {
+                    continue;
+                }
+                let joined = format!("{}{}", first, second);
+                let tokens = lex(&format!("a {} b", joined));
+                assert_eq!(tokens.first().map(String::as_str), Some("a"), "{}", joined);
+                assert_eq!(tokens.last().map(String::as_str), Some("b"), "{}", joined);
+                let ops = &tokens[1..tokens.len() - 1];
+                assert_eq!(ops.concat(), joined);
+                let mut rest: &str = &joined;
+                for op in ops.iter() {
+                    let longest = (1..=rest.len().min(MAX_OPERATOR_LEN)).rev()
+                        .find(|n| OperatorSymbol::from_string(&rest[..*n]) != OperatorSymbol::ILLEGAL)
+                        .unwrap_or(1);
+                    assert_eq!(op, &rest[..longest], "{}", joined);
+                    rest = &rest[longest..];
+                }
+                let spaced = lex(&format!("a {} {} b", first, second));
+                assert_eq!(spaced, vec!["a", first, second, "b"], "{} {}", first, second);
+            }
+        }
+    }
+
+    #[test]
+    fn operators_lex_by_longest_match() {
+        assert_eq!(lex("a**=b"), vec!["a", "**=", "b"]);
+        assert_eq!(lex("a<=>b"), vec!["a", "<=>", "b"]);
+        assert_eq!(lex("a===b"), vec!["a", "===", "b"]);
+        assert_eq!(lex("a...b"), vec!["a", "...", "b"]);
+        assert_eq!(lex("1...2"), vec!["1", "...", "2"]);
+        assert_eq!(lex("a !~b"), vec!["a", "!~", "b"]);
+        assert_eq!(lex("a&.b"), vec!["a", "&.", "b"]);
+        assert_eq!(lex("A::B"), vec!["A", "::", "B"]);
+        assert_eq!(lex("a^~b"), vec!["a", "^", "~", "b"]);
+    }
+
+    #[test]
+    fn unary_minus_after_operator() {
+        assert_eq!(lex("index+-1"), vec!["index", "+", "-", "1"]);
+        assert_eq!(lex("a=-1"), vec!["a", "=", "-", "1"]);
+        assert_eq!(lex("x*-y"), vec!["x", "*", "-", "y"]);
+        assert_eq!(lex("a**-b"), vec!["a", "**", "-", "b"]);
+        assert_eq!(lex("a<=>-b"), vec!["a", "<=>", "-", "b"]);
+    }
+
+    #[test]
+    fn operator_tokens_start_at_operator() {
+        let mut is = InputStream::from_string("ab += 1");
+        let mut ts = TokenStream::create(&mut is);
+        ts.read_next();
+        let op = ts.read_next().unwrap();
+        assert_eq!((op.get_line(), op.get_col()), (1, 4));
+        assert_eq!(lex_symbols("a::b")[1], "operator::::");
+    }
+
+    #[test]
+    fn bars_follow_the_parsers_mode() {
+        assert_eq!(lex_symbols("a | b")[1], "operator::|");
+        assert_eq!(lex_symbols("a || b")[1], "operator::||");
+        assert_eq!(lex_symbols("a ||= b")[1], "operator::||=");
+        assert_eq!(lex_symbols("a |= b")[1], "operator::|=");
+        // Left to itself, the lexer reads every bar as an operator.
+        assert_eq!(lex_symbols("x.each do |a| a end"),
+            vec!["identifier::variable", "operator::.", "identifier::variable", "keyword::do",
+                 "operator::|", "identifier::variable", "operator::|", "identifier::variable", "keyword::end"]);
+        assert_eq!(lex_symbols("f { || a || b }"),
+            vec!["identifier::variable", "separator::{", "operator::||", "identifier::variable", "operator::||",
+                 "identifier::variable", "separator::}"]);
+        assert_eq!(lex_symbols("clos = |a,b| a | b"),
+            vec!["identifier::variable", "operator::=", "operator::|", "identifier::variable", "separator::,",
+                 "identifier::variable", "operator::|", "identifier::variable", "operator::|", "identifier::variable"]);
+        // Driven as the parser drives it: `BlockStart` after `do` or `{`, `BlockParams` after the
+        // opening bar of a lambda.
+        assert_eq!(lex_symbols_in_mode("x.each do |a| a end", 3, LexMode::BlockStart),
+            vec!["identifier::variable", "operator::.", "identifier::variable", "keyword::do",
+                 "separator::|", "identifier::variable", "separator::|", "identifier::variable", "keyword::end"]);
+        assert_eq!(lex_symbols_in_mode("f { || a || b }", 1, LexMode::BlockStart),
+            vec!["identifier::variable", "separator::{", "separator::|", "separator::|",
+                 "identifier::variable", "operator::||", "identifier::variable", "separator::}"]);
+        assert_eq!(lex_symbols_in_mode("f { a | b }", 1, LexMode::BlockStart),
+            vec!["identifier::variable", "separator::{", "identifier::variable", "operator::|", "identifier::variable", "separator::}"]);
+        assert_eq!(lex_symbols_in_mode("clos = |a,b| a | b", 2, LexMode::BlockParams),
+            vec!["identifier::variable", "operator::=", "operator::|", "identifier::variable", "separator::,",
+                 "identifier::variable", "separator::|", "identifier::variable", "operator::|", "identifier::variable"]);
+    }
+
+    #[test]
+    fn question_and_bang_only_end_names() {
+        assert_eq!(lex("empty? save! x!=y a==b?"), vec!["empty?", "save!", "x", "!=", "y", "a", "==", "b?"]);
+        assert_eq!(lex("a?b"), vec!["a", "b"]);
+        assert_eq!(lex(":ok? :go! :name= {:a=>1}"), vec!["ok?", "go!", "name=", "{", "a", "=>", "1", "}"]);
+        assert_eq!(lex("$! $: $stdout"), vec!["$!", "$:", "$stdout"]);
+    }
+
+    #[test]
+    fn numbers_take_rational_and_imaginary_suffixes() {
+        assert_eq!(lex("3r 1.5r 2i 1/3ri 3if x"), vec!["3r", "1.5r", "2i", "1", "/", "3ri", "3", "if", "x"]);
+        assert_eq!(lex_symbols("3r 2i 3ri"), vec!["identifier::rational", "identifier::imaginary", "identifier::imaginary"]);
+    }
+
+    #[test]
+    fn operators_after_def_name_the_method() {
+        assert_eq!(lex("def +\nend"), vec!["def", "+", "\n", "end"]);
+        assert_eq!(lex("def |(o)"), vec!["def", "|", "(", "o", ")"]);
+        assert_eq!(lex_symbols("def self.|(o)")[3], "operator::|");
+    }
+
+    #[test]
+    fn newlines_terminate_complete_statements() {
+        assert_eq!(lex("a = 1\nb = 2\n"), vec!["a", "=", "1", "\n", "b", "=", "2", "\n"]);
+        assert_eq!(lex("\n\na = 1\n\n\nb"), vec!["a", "=", "1", "\n", "b"]);
+        assert_eq!(lex("a = 1; b"), vec!["a", "=", "1", ";", "b"]);
+        assert_eq!(lex("x # note\ny"), vec!["x", "# note", "\n", "y"]);
+        assert_eq!(lex("return\nx"), vec!["return", "\n", "x"]);
+        assert_eq!(lex("e.class\nx"), vec!["e", ".", "class", "\n", "x"]);
+    }
+
+    #[test]
+    fn slashes_start_regexps_where_a_value_is_expected() {
+        assert_eq!(lex("x = /a b/i"), vec!["x", "=", "/a b/i"]);
+        assert_eq!(lex("a / b / c"), vec!["a", "/", "b", "/", "c"]);
+        assert_eq!(lex("scan /o/"), vec!["scan", "/o/"]);
+        assert_eq!(lex("p x /y"), vec!["p", "x", "/", "y"]);
+    }
+
+    #[test]
+    fn newlines_are_suppressed_inside_expressions() {
+        assert_eq!(lex("a +\n b"), vec!["a", "+", "b"]);
+        assert_eq!(lex("f(a,\n b)\n"), vec!["f", "(", "a", ",", "b", ")", "\n"]);
+        assert_eq!(lex("[1\n, 2\n]"), vec!["[", "1", ",", "2", "]"]);
+        assert_eq!(lex("a\n  .b\n  &.c"), vec!["a", ".", "b", "&.", "c"]);
+        assert_eq!(lex("a \\\n + b"), vec!["a", "+", "b"]);
+        assert_eq!(lex("x.each do\n y\nend"), vec!["x", ".", "each", "do", "y", "\n", "end"]);
+        assert_eq!(lex("{ a\n}"), vec!["{", "a", "\n", "}"]);
+    }
+
+    #[test]
+    fn invalid_characters_are_reported_and_skipped() {
+        let mut is = InputStream::from_string("a \u{a7} b\n\"open");
+        let mut ts = TokenStream::create(&mut is);
+        let mut values = Vec::new();
+        while let Some(tok) = ts.read_next() {
+            values.push(tok.get_data().get_value().clone());
+        }
+        assert_eq!(values, vec!["a", "b", "\n", "open"]);
+        let messages: Vec<String> = ts.take_diagnostics().iter().map(|d| d.to_string()).collect();
+        assert_eq!(messages, vec![
+            "error: unexpected character '\u{a7}' at [l: 1, c: 3]",
+            "error: unterminated string at [l: 2, c: 1]"
+        ]);
+    }
+}
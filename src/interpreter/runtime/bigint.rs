@@ -0,0 +1,466 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
+// Integers too large for an `i64`: a sign and a magnitude in base 2^32, least significant limb
+// first, with no leading zero limbs. Zero has no limbs and is never negative.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>
+}
+
+impl BigInt {
+    pub fn zero() -> BigInt {
+        BigInt { negative: false, limbs: Vec::new() }
+    }
+    fn create(negative: bool, mut limbs: Vec<u32>) -> BigInt {
+        trim(&mut limbs);
+        BigInt { negative: negative && !limbs.is_empty(), limbs }
+    }
+
+    // Decimal digits with an optional sign; `None` if there's anything else.
+    pub fn parse(text: &str) -> Option<BigInt> {
+        let (negative, digits) = match text.as_bytes().first()? {
+            b'-' => (true, &text[1..]),
+            b'+' => (false, &text[1..]),
+            _ => (false, text)
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut limbs = Vec::new();
+        // Nine digits at a time fit a limb.
+        for chunk in digits.as_bytes().chunks(9) {
+            let scale = 10u32.pow(chunk.len() as u32);
+            let value = chunk.iter().fold(0u32, |n, b| n * 10 + (b - b'0') as u32);
+            mul_small_add(&mut limbs, scale, value);
+        }
+        Some(BigInt::create(negative, limbs))
+    }
+
+    // The integer part of a finite float.
+    pub fn from_f64(f: f64) -> BigInt {
+        let f = f.trunc();
+        if !f.is_finite() || f.abs() < 1.0 {
+            return BigInt::zero();
+        }
+        let bits = f.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64 - 1075;
+        let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+        let magnitude = BigInt::from(mantissa as i64);
+        let magnitude = if exponent >= 0 { magnitude.shl(exponent as usize) } else { BigInt::from((mantissa >> -exponent) as i64) };
+        BigInt::create(f < 0.0, magnitude.limbs)
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.limbs.len() > 2 {
+            return None;
+        }
+        let magnitude = self.limbs.iter().rev().fold(0u64, |n, l| (n << 32) | *l as u64);
+        if self.negative {
+            if magnitude <= 1 << 63 { Some((magnitude as i64).wrapping_neg()) } else { None }
+        } else {
+            i64::try_from(magnitude).ok()
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self.limbs.iter().rev().fold(0.0, |f, l| f * 4294967296.0 + *l as f64);
+        if self.negative { -magnitude } else { magnitude }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+    pub fn is_even(&self) -> bool {
+        self.limbs.first().is_none_or(|l| l % 2 == 0)
+    }
+    // Bits in the magnitude.
+    pub fn bit_length(&self) -> usize {
+        match self.limbs.last() {
+            Some(top) => self.limbs.len() * 32 - top.leading_zeros() as usize,
+            None => 0
+        }
+    }
+
+    pub fn neg(&self) -> BigInt {
+        BigInt::create(!self.negative, self.limbs.clone())
+    }
+    pub fn abs(&self) -> BigInt {
+        BigInt::create(false, self.limbs.clone())
+    }
+
+    pub fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::create(self.negative, add_magnitudes(&self.limbs, &other.limbs));
+        }
+        match compare_magnitudes(&self.limbs, &other.limbs) {
+            Ordering::Less => BigInt::create(other.negative, sub_magnitudes(&other.limbs, &self.limbs)),
+            _ => BigInt::create(self.negative, sub_magnitudes(&self.limbs, &other.limbs))
+        }
+    }
+    pub fn sub(&self, other: &BigInt) -> BigInt {
+        self.add(&other.neg())
+    }
+    pub fn mul(&self, other: &BigInt) -> BigInt {
+        BigInt::create(self.negative != other.negative, mul_magnitudes(&self.limbs, &other.limbs))
+    }
+
+    // Division rounding toward negative infinity, with the remainder taking the divisor's sign,
+    // as Integer `/` and `%` do. `None` when dividing by zero.
+    pub fn div_mod_floor(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (q, r) = divide_magnitudes(&self.limbs, &other.limbs);
+        let q = BigInt::create(self.negative != other.negative, q);
+        let r = BigInt::create(self.negative, r);
+        if !r.is_zero() && r.negative != other.negative {
+            Some((q.sub(&BigInt::from(1)), r.add(other)))
+        } else {
+            Some((q, r))
+        }
+    }
+
+    pub fn pow(&self, mut exponent: u64) -> BigInt {
+        let mut result = BigInt::from(1);
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul(&base);
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = base.mul(&base);
+            }
+        }
+        result
+    }
+
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let (mut a, mut b) = (self.abs(), other.abs());
+        while !b.is_zero() {
+            let (_, r) = divide_magnitudes(&a.limbs, &b.limbs);
+            a = b;
+            b = BigInt::create(false, r);
+        }
+        a
+    }
+
+    pub fn shl(&self, bits: usize) -> BigInt {
+        if self.is_zero() {
+            return self.clone();
+        }
+        let mut limbs = vec![0; bits / 32];
+        limbs.extend(shift_left(&self.limbs, (bits % 32) as u32));
+        BigInt::create(self.negative, limbs)
+    }
+    // Rounds towards negative infinity, so negative numbers shift down to -1 rather than 0.
+    pub fn shr(&self, bits: u64) -> BigInt {
+        if bits >= self.bit_length() as u64 {
+            return BigInt::from(if self.negative { -1 } else { 0 });
+        }
+        let divisor = BigInt::from(1).shl(bits as usize);
+        self.div_mod_floor(&divisor).map_or_else(BigInt::zero, |(q, _)| q)
+    }
+
+    // Applies a bitwise operator limb by limb to the two's complement forms, which for negative
+    // numbers go on with one bits forever, as Integer `&`, `|` and `^` do.
+    pub fn bitwise(&self, other: &BigInt, op: fn(u32, u32) -> u32) -> BigInt {
+        let width = self.limbs.len().max(other.limbs.len()) + 1;
+        let (a, b) = (self.twos_complement(width), other.twos_complement(width));
+        let limbs: Vec<u32> = a.iter().zip(b.iter()).map(|(x, y)| op(*x, *y)).collect();
+        if limbs[width - 1] >> 31 == 1 {
+            BigInt::create(true, negate_limbs(&limbs))
+        } else {
+            BigInt::create(false, limbs)
+        }
+    }
+    // `-self - 1`, which is `~self` in two's complement.
+    pub fn not(&self) -> BigInt {
+        self.neg().sub(&BigInt::from(1))
+    }
+    fn twos_complement(&self, width: usize) -> Vec<u32> {
+        let mut limbs = self.limbs.clone();
+        limbs.resize(width, 0);
+        if self.negative { negate_limbs(&limbs) } else { limbs }
+    }
+
+    pub fn to_string_radix(&self, radix: u32) -> String {
+        if self.is_zero() {
+            return String::from("0");
+        }
+        let mut digits = Vec::new();
+        let mut limbs = self.limbs.clone();
+        while !limbs.is_empty() {
+            let remainder = div_small(&mut limbs, radix);
+            digits.push(std::char::from_digit(remainder, radix).expect("a digit below the radix"));
+        }
+        if self.negative {
+            digits.push('-');
+        }
+        digits.iter().rev().collect()
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(n: i64) -> BigInt {
+        let magnitude = n.unsigned_abs();
+        BigInt::create(n < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitudes(&self.limbs, &other.limbs),
+            (true, true) => compare_magnitudes(&other.limbs, &self.limbs)
+        }
+    }
+}
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_string_radix(10))
+    }
+}
+
+// -- magnitudes --
+
+fn trim(limbs: &mut Vec<u32>) {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut out = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, l) in long.iter().enumerate() {
+        let sum = *l as u64 + short.get(i).copied().unwrap_or(0) as u64 + carry;
+        out.push(sum as u32);
+        carry = sum >> 32;
+    }
+    out.push(carry as u32);
+    out
+}
+
+// `a - b` where `a` is at least `b`.
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, l) in a.iter().enumerate() {
+        let diff = *l as i64 - b.get(i).copied().unwrap_or(0) as i64 - borrow;
+        out.push(diff as u32);
+        borrow = if diff < 0 { 1 } else { 0 };
+    }
+    out
+}
+
+fn mul_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, y) in b.iter().enumerate() {
+            let t = *x as u64 * *y as u64 + out[i + j] as u64 + carry;
+            out[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        out[i + b.len()] = carry as u32;
+    }
+    out
+}
+
+fn mul_small_add(limbs: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = addend as u64;
+    for l in limbs.iter_mut() {
+        let t = *l as u64 * factor as u64 + carry;
+        *l = t as u32;
+        carry = t >> 32;
+    }
+    if carry > 0 {
+        limbs.push(carry as u32);
+    }
+}
+
+// Divides in place and returns the remainder.
+fn div_small(limbs: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for l in limbs.iter_mut().rev() {
+        let t = (remainder << 32) | *l as u64;
+        *l = (t / divisor as u64) as u32;
+        remainder = t % divisor as u64;
+    }
+    trim(limbs);
+    remainder as u32
+}
+
+// Shifts by less than a limb. The result always has one limb more than `limbs`, even when it's
+// zero: division relies on that spare top limb.
+fn shift_left(limbs: &[u32], bits: u32) -> Vec<u32> {
+    let mut out = Vec::with_capacity(limbs.len() + 1);
+    let mut carry = 0u32;
+    for l in limbs.iter() {
+        out.push((l << bits) | carry);
+        carry = if bits == 0 { 0 } else { l >> (32 - bits) };
+    }
+    out.push(carry);
+    out
+}
+
+// Negates a fixed-width two's complement number: inverts it and adds one.
+fn negate_limbs(limbs: &[u32]) -> Vec<u32> {
+    let mut carry = 1u64;
+    limbs.iter().map(|l| {
+        let t = !l as u64 + carry;
+        carry = t >> 32;
+        t as u32
+    }).collect()
+}
+
+fn shift_right(limbs: &[u32], bits: u32) -> Vec<u32> {
+    if bits == 0 {
+        return limbs.to_vec();
+    }
+    let mut out = vec![0u32; limbs.len()];
+    for i in 0..limbs.len() {
+        let high = limbs.get(i + 1).map_or(0, |h| h << (32 - bits));
+        out[i] = (limbs[i] >> bits) | high;
+    }
+    out
+}
+
+// Truncating division of magnitudes: Knuth's algorithm D, with the divisor normalized so its top
+// bit is set, which keeps each estimated quotient limb within two of the true one.
+fn divide_magnitudes(u: &[u32], v: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if compare_magnitudes(u, v) == Ordering::Less {
+        return (Vec::new(), u.to_vec());
+    }
+    if v.len() == 1 {
+        let mut q = u.to_vec();
+        let r = div_small(&mut q, v[0]);
+        return (q, vec![r]);
+    }
+    const BASE: u64 = 1 << 32;
+    let shift = v[v.len() - 1].leading_zeros();
+    let vn = shift_left(v, shift);
+    let mut un = shift_left(u, shift);
+    let n = v.len();
+    let m = u.len() - n;
+    let mut q = vec![0u32; m + 1];
+    for j in (0..=m).rev() {
+        let top = ((un[j + n] as u64) << 32) | un[j + n - 1] as u64;
+        let mut qhat = top / vn[n - 1] as u64;
+        let mut rhat = top % vn[n - 1] as u64;
+        while qhat >= BASE || qhat * vn[n - 2] as u64 > ((rhat << 32) | un[j + n - 2] as u64) {
+            qhat -= 1;
+            rhat += vn[n - 1] as u64;
+            if rhat >= BASE {
+                break;
+            }
+        }
+        let mut borrow = 0i64;
+        let mut carry = 0u64;
+        for i in 0..n {
+            let p = qhat * vn[i] as u64 + carry;
+            carry = p >> 32;
+            let t = un[i + j] as i64 - borrow - (p & 0xffff_ffff) as i64;
+            un[i + j] = t as u32;
+            borrow = if t < 0 { 1 } else { 0 };
+        }
+        let t = un[j + n] as i64 - borrow - carry as i64;
+        un[j + n] = t as u32;
+        if t < 0 {
+            // The estimate was one too many: add the divisor back.
+            qhat -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = un[i + j] as u64 + vn[i] as u64 + carry;
+                un[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            un[j + n] = un[j + n].wrapping_add(carry as u32);
+        }
+        q[j] = qhat as u32;
+    }
+    (q, shift_right(&un[..n], shift))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(s: &str) -> BigInt {
+        BigInt::parse(s).unwrap()
+    }
+
+    #[test]
+    fn parses_prints_and_converts() {
+        assert_eq!(big("-123456789012345678901234567890").to_string(), "-123456789012345678901234567890");
+        assert_eq!(big("000").to_string(), "0");
+        assert_eq!(big("9223372036854775807").to_i64(), Some(i64::MAX));
+        assert_eq!(big("-9223372036854775808").to_i64(), Some(i64::MIN));
+        assert_eq!(big("9223372036854775808").to_i64(), None);
+        assert_eq!(big("255").to_string_radix(16), "ff");
+        assert_eq!(BigInt::from_f64(1e20).to_string(), "100000000000000000000");
+        assert!(BigInt::parse("12a").is_none() && BigInt::parse("-").is_none());
+    }
+
+    #[test]
+    fn arithmetic_matches_known_values() {
+        let a = big("340282366920938463463374607431768211456");
+        assert_eq!(big("2").pow(128), a);
+        assert_eq!(a.sub(&big("1")).to_string(), "340282366920938463463374607431768211455");
+        assert_eq!(big("-5").add(&big("3")).to_string(), "-2");
+        assert_eq!(big("123456789123456789").mul(&big("-987654321987654321")).to_string(), "-121932631356500531347203169112635269");
+        let (q, r) = a.div_mod_floor(&big("12345678901234567890123")).unwrap();
+        assert_eq!((q.to_string(), r.to_string()), (String::from("27562871968661863"), String::from("3497502651852243732307")));
+        let (q, r) = big("-7").div_mod_floor(&big("2")).unwrap();
+        assert_eq!((q.to_string(), r.to_string()), (String::from("-4"), String::from("1")));
+        let (q, r) = big("7").div_mod_floor(&big("-100000000000000000000")).unwrap();
+        assert_eq!((q.to_string(), r.to_string()), (String::from("-1"), String::from("-99999999999999999993")));
+        assert!(big("1").div_mod_floor(&BigInt::zero()).is_none());
+    }
+
+    #[test]
+    fn divides_by_divisors_whose_top_bit_is_set() {
+        // 10**19 is 0x8ac7230489e80000, so its top limb needs no normalizing shift.
+        let (q, r) = big("100000000000000000000").div_mod_floor(&big("10000000000000000000")).unwrap();
+        assert_eq!((q.to_string(), r.to_string()), (String::from("10"), String::from("0")));
+        let (q, r) = big("-340282366920938463463374607431768211455").div_mod_floor(&big("18446744073709551615")).unwrap();
+        assert_eq!((q.to_string(), r.to_string()), (String::from("-18446744073709551617"), String::from("0")));
+        assert_eq!(big("2").pow(64).gcd(&big("9223372036854775808")).to_string(), "9223372036854775808");
+    }
+
+    #[test]
+    fn bitwise_operators_act_on_twos_complement() {
+        let a = big("2").pow(70).add(&big("5"));
+        assert_eq!(a.bitwise(&big("7"), |x, y| x & y).to_string(), "5");
+        assert_eq!(a.bitwise(&big("-1"), |x, y| x & y), a);
+        assert_eq!(a.neg().bitwise(&big("255"), |x, y| x & y).to_string(), "251");
+        assert_eq!(a.bitwise(&big("2"), |x, y| x | y).to_string(), "1180591620717411303431");
+        assert_eq!(a.neg().bitwise(&big("4"), |x, y| x | y).to_string(), "-1180591620717411303425");
+        assert_eq!(a.bitwise(&a.neg(), |x, y| x ^ y).to_string(), "-2");
+        assert_eq!(a.not().to_string(), "-1180591620717411303430");
+        assert_eq!(a.shr(68).to_string(), "4");
+        assert_eq!(a.neg().shr(68).to_string(), "-5");
+        assert_eq!(a.shr(u64::MAX), BigInt::zero());
+        assert_eq!(a.neg().shr(u64::MAX).to_string(), "-1");
+    }
+}
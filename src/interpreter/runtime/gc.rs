@@ -0,0 +1,249 @@
+use crate::interpreter::interpreter::Context;
+use super::heap::{Heap, MethodEntry, Object, ObjectKind, ProcBody};
+use super::value::{ObjRef, Value};
+
+// Heaps smaller than this are never collected except on request.
+const MIN_THRESHOLD: usize = 10_000;
+
+// The objects a collection starts from: whatever the interpreter can reach without going
+// through the heap.
+#[derive(Default)]
+pub struct Roots {
+    refs: Vec<ObjRef>
+}
+
+impl Roots {
+    pub fn add_object(&mut self, r: ObjRef) {
+        self.refs.push(r);
+    }
+    pub fn add_value(&mut self, v: Value) {
+        if let Value::Object(r) = v {
+            self.refs.push(r);
+        }
+    }
+    pub fn add_context(&mut self, context: &Context) {
+        self.add_value(context.self_value);
+        self.add_object(context.def_target);
+        self.refs.extend(context.cref.iter().copied());
+        if let Some(method) = &context.method {
+            self.add_object(method.owner);
+            method.args.iter().for_each(|v| self.add_value(*v));
+        }
+        if let Some(block) = context.block {
+            self.add_value(block);
+        }
+    }
+    // Everything `object` refers to directly.
+    pub fn add_children(&mut self, object: &Object) {
+        self.add_object(object.class);
+        if let Some(singleton) = object.singleton {
+            self.add_object(singleton);
+        }
+        object.ivars.values().for_each(|v| self.add_value(*v));
+        match &object.kind {
+            ObjectKind::Plain | ObjectKind::Str(_) | ObjectKind::BigInt(_) | ObjectKind::Rational(_)
+            | ObjectKind::Regexp(_) | ObjectKind::MatchData(_) => {},
+            ObjectKind::Complex { real, imaginary } => {
+                self.add_value(*real);
+                self.add_value(*imaginary);
+            },
+            ObjectKind::Array(items) => items.iter().for_each(|v| self.add_value(*v)),
+            ObjectKind::Hash(table) => {
+                for (k, v) in table.entries() {
+                    self.add_value(*k);
+                    self.add_value(*v);
+                }
+                if let Some(default) = table.default {
+                    self.add_value(default);
+                }
+            },
+            ObjectKind::Range { start, end, .. } => {
+                self.add_value(*start);
+                self.add_value(*end);
+            },
+            ObjectKind::Proc(data) => match &data.body {
+                ProcBody::Block { env, context, .. } | ProcBody::Compiled { env, context, .. } => {
+                    self.add_object(*env);
+                    self.add_context(context);
+                },
+                ProcBody::Symbol(_) | ProcBody::Native(_) => {},
+                ProcBody::Method { receiver, .. } => self.add_value(*receiver)
+            },
+            ObjectKind::Enumerator(data) => {
+                self.add_value(data.receiver);
+                data.args.iter().for_each(|v| self.add_value(*v));
+                data.stages.iter().for_each(|(_, v)| self.add_value(*v));
+                data.cursor.fetched.iter().for_each(|v| self.add_value(*v));
+            },
+            ObjectKind::Module(data) => {
+                self.refs.extend(data.superclass);
+                self.refs.extend(data.includes.iter().copied());
+                for entry in data.methods.values() {
+                    match entry {
+                        MethodEntry::User(method) => {
+                            self.add_object(method.owner);
+                            self.refs.extend(method.cref.iter().copied());
+                        },
+                        MethodEntry::Proc(body) => self.add_value(*body),
+                        _ => {}
+                    }
+                }
+                data.constants.values().for_each(|v| self.add_value(*v));
+                if let Some(attached) = data.attached {
+                    self.add_value(attached);
+                }
+            },
+            ObjectKind::Env(data) => {
+                data.vars.values().for_each(|v| self.add_value(*v));
+                data.slots.iter().for_each(|v| self.add_value(*v));
+                self.refs.extend(data.parent);
+            },
+            ObjectKind::Exception(data) => self.add_value(data.message)
+        }
+    }
+}
+
+// What one collection did.
+pub struct Sweep {
+    pub freed: usize,
+    // Freed classes' slots can be reused, so anything cached by class is stale.
+    pub modules_freed: bool
+}
+
+// A mark-sweep collector: it decides when to run and keeps the counts `GC.stat` reports.
+pub struct Collector {
+    // Collect before every allocation, to shake out values the interpreter forgot to root.
+    stress: bool,
+    // Live objects at which the next allocation collects first.
+    threshold: usize,
+    count: u64,
+    allocated: u64,
+    freed: u64
+}
+
+impl Collector {
+    pub fn create() -> Collector {
+        Collector { stress: false, threshold: MIN_THRESHOLD, count: 0, allocated: 0, freed: 0 }
+    }
+    pub fn is_stress(&self) -> bool {
+        self.stress
+    }
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+    pub fn is_due(&self, live: usize) -> bool {
+        self.stress || live >= self.threshold
+    }
+    pub fn note_alloc(&mut self) {
+        self.allocated += 1;
+    }
+    pub fn note_allocs(&mut self, count: usize) {
+        self.allocated += count as u64;
+    }
+    pub fn get_count(&self) -> u64 {
+        self.count
+    }
+    pub fn get_allocated(&self) -> u64 {
+        self.allocated
+    }
+    pub fn get_freed(&self) -> u64 {
+        self.freed
+    }
+
+    // Marks everything reachable from `roots` and frees the rest. The heap may then grow to twice
+    // what survived before collecting again.
+    pub fn collect(&mut self, heap: &mut Heap, roots: Roots) -> Sweep {
+        let mut marked = vec![false; heap.slot_count()];
+        let mut pending = roots;
+        while let Some(r) = pending.refs.pop() {
+            if marked[r.index()] {
+                continue;
+            }
+            marked[r.index()] = true;
+            if let Some(object) = heap.slot(r.index()) {
+                pending.add_children(object);
+            }
+        }
+        let mut sweep = Sweep { freed: 0, modules_freed: false };
+        for (index, live) in marked.into_iter().enumerate() {
+            match heap.slot(index) {
+                Some(object) if !live => {
+                    sweep.modules_freed |= matches!(object.kind, ObjectKind::Module(_));
+                    heap.release(index);
+                    sweep.freed += 1;
+                },
+                _ => {}
+            }
+        }
+        self.count += 1;
+        self.freed += sweep.freed as u64;
+        self.threshold = MIN_THRESHOLD.max(heap.live_count() * 2);
+        sweep
+    }
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Collector::create()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::interpreter::Interpreter;
+
+    // Runs `source` collecting before every allocation, on both the tree walker and the VM.
+    fn eval_stressed(source: &str) -> String {
+        let mut results = Vec::new();
+        for vm in [false, true] {
+            let mut interp = Interpreter::create();
+            interp.set_vm(vm);
+            interp.set_gc_stress(true);
+            let v = interp.eval_source("test", source).unwrap_or_else(|_| panic!("{} raised", source));
+            results.push(interp.inspect(v).unwrap());
+        }
+        assert_eq!(results[0], results[1], "{}", source);
+        results.pop().unwrap()
+    }
+
+    #[test]
+    fn frees_unreachable_cycles() {
+        let mut interp = Interpreter::create();
+        interp.collect_garbage(None);
+        let before = interp.heap.live_count();
+        let source = "class Node\n  attr_accessor :peer\nend\n100.times do\n  a = Node.new\n  b = Node.new\n  a.peer = b\n  b.peer = a\n  f = lambda { a }\nend\nkept = Node.new\nkept.peer = kept\n$kept = kept\nnil";
+        interp.eval_source("test", source).unwrap_or_else(|_| panic!("raised"));
+        interp.collect_garbage(None);
+        // Only the class and the node in `$kept` stay behind.
+        assert!(interp.heap.live_count() < before + 10, "{} live after, {} before", interp.heap.live_count(), before);
+        let kept = interp.eval_source("test", "[$kept.peer.equal?($kept), GC.stat(:total_freed_objects) > 400]").unwrap();
+        assert_eq!(interp.inspect(kept).unwrap(), "[true, true]");
+    }
+
+    #[test]
+    fn values_only_rust_holds_survive_stress() {
+        assert_eq!(eval_stressed("a = [\"x\" + \"x\", \"y\" + \"y\"]\na.pop + (\"z\" + \"z\")"), "\"yyzz\"");
+        assert_eq!(eval_stressed("h = {\"a\" => [1], \"b\" => [2]}\nout = []\nh.each { |k, v| h.delete(k); out << k + v.inspect }\nout"), "[\"a[1]\", \"b[2]\"]");
+        assert_eq!(eval_stressed("a = [[1], [2], [3]]\nout = []\na.each { |i| a.clear; out << i }\nout"), "[[1]]");
+        assert_eq!(eval_stressed("def f\n  raise ArgumentError, \"no \" + \"way\"\nensure\n  \"junk\" + \"junk\"\nend\nbegin\n  f\nrescue => e\n  e.message\nend"), "\"no way\"");
+        assert_eq!(eval_stressed("out = []\n[3, 1, 2].sort { |a, b| [a] * 3; a <=> b }.each { |i| out << \"n\" + i.to_s }\nout"), "[\"n1\", \"n2\", \"n3\"]");
+        assert_eq!(eval_stressed("def sum(n, acc)\n  if n == 0 then acc else sum(n - 1, acc + [n]) end\nend\nsum(30, []).length"), "30");
+    }
+
+    #[test]
+    fn reports_and_controls_the_collector() {
+        let mut interp = Interpreter::create();
+        let v = interp.eval_source("test", "GC.start\nwas = GC.stress\nGC.stress = true\n[\"a\"] * 3\nc = GC.count\nx = [c]\n[was, GC.stress, c > 1, GC.count > c, GC.stat[:count] >= c]").unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "[false, true, true, true, true]");
+    }
+
+    #[test]
+    fn stat_counts_objects_made_while_booting() {
+        let mut interp = Interpreter::create();
+        let source = "s = GC.stat\n[s[:heap_live_slots] <= s[:total_allocated_objects] - s[:total_freed_objects], s[:total_allocated_objects] > 50]";
+        let v = interp.eval_source("test", source).unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "[true, true]");
+        let v = interp.eval_source("test", "100.times { [1] }\nGC.start\ns = GC.stat\ns[:heap_live_slots] == s[:total_allocated_objects] - s[:total_freed_objects]").unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "true");
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::interpreter::diagnostics::Span;
+use crate::interpreter::interpreter::{Context, Interpreter, Unwind};
+use crate::interpreter::parser::ast::{Block, Expr, Params};
+use crate::interpreter::source_map::FileId;
+use crate::interpreter::vm::bytecode::Chunk;
+use super::bigint::BigInt;
+use super::rational::Rational;
+use super::regex::{Captures, Regex};
+use super::symbols::Sym;
+use super::value::{ObjRef, Value};
+
+// A method implemented in Rust. It receives the receiver, the evaluated arguments and the block.
+pub type Builtin = fn(&mut Interpreter, Value, &[Value], Option<Value>) -> Result<Value, Unwind>;
+
+// A block implemented in Rust, like the ones `Enumerable` passes to `each`. It receives its own
+// break tag, to end the iteration early, and the yielded values. Any value it keeps between
+// calls must live in the heap, where the collector can see it.
+pub type NativeBlock = Rc<dyn Fn(&mut Interpreter, usize, &[Value]) -> Result<Value, Unwind>>;
+
+pub enum MethodBody {
+    Tree { params: Params, body: Rc<Expr> },
+    // Compiled to bytecode; the chunk binds its own parameters.
+    Compiled(Rc<Chunk>)
+}
+
+pub struct UserMethod {
+    pub name: Sym,
+    pub body: MethodBody,
+    pub owner: ObjRef,
+    pub file: FileId,
+    // The lexical module nesting of the `def`, for constant lookup in the body.
+    pub cref: Rc<Vec<ObjRef>>
+}
+
+#[derive(Clone)]
+pub enum MethodEntry {
+    User(Rc<UserMethod>),
+    Builtin(Builtin),
+    AttrReader(Sym),
+    AttrWriter(Sym),
+    // Defined with `define_method`: the block runs with the receiver as `self`.
+    Proc(Value),
+    // Left by `undef`: lookup stops here instead of continuing to the ancestors.
+    Undefined
+}
+
+pub struct ModuleData {
+    pub name: Option<String>,
+    pub is_class: bool,
+    pub superclass: Option<ObjRef>,
+    // Modules mixed in with `include`, most recent last.
+    pub includes: Vec<ObjRef>,
+    pub methods: HashMap<Sym, MethodEntry>,
+    pub constants: HashMap<Sym, Value>,
+    // For singleton classes, the object they belong to.
+    pub attached: Option<Value>
+}
+
+impl ModuleData {
+    pub fn create(name: Option<String>, is_class: bool, superclass: Option<ObjRef>) -> ModuleData {
+        ModuleData { name, is_class, superclass, includes: Vec::new(), methods: HashMap::new(), constants: HashMap::new(), attached: None }
+    }
+}
+
+pub enum ProcBody {
+    Block { block: Rc<Block>, env: ObjRef, context: Context, file: FileId },
+    Compiled { chunk: Rc<Chunk>, env: ObjRef, context: Context, file: FileId },
+    // `&:name`: calls `name` on the first argument.
+    Symbol(Sym),
+    // `obj.method(:name)`.
+    Method { receiver: Value, name: Sym },
+    Native(NativeBlock)
+}
+
+pub struct ProcData {
+    pub body: ProcBody,
+    pub is_lambda: bool,
+    // `break` inside the block ends the call the block was passed to; that call catches this tag.
+    pub break_tag: usize
+}
+
+// What an `Enumerator::Lazy` does to each value before passing it on. The stage's value is a
+// block, or the count for `Take` and `Drop`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum LazyStage {
+    Map,
+    Select,
+    Reject,
+    FilterMap,
+    FlatMap,
+    TakeWhile,
+    DropWhile,
+    Take,
+    Drop
+}
+
+// `receiver.method(*args)` as something to iterate. A lazy enumerator iterates `receiver` with
+// `method` and sends each value through `stages`.
+#[derive(Clone)]
+pub struct EnumeratorData {
+    pub receiver: Value,
+    pub method: Sym,
+    pub args: Vec<Value>,
+    pub stages: Vec<(LazyStage, Value)>,
+    pub cursor: Cursor
+}
+
+// How far `Enumerator#next` has got: the values fetched so far (an array), whether they're all
+// the values there are, and how many of them `next` has returned.
+#[derive(Clone, Default)]
+pub struct Cursor {
+    pub fetched: Option<Value>,
+    pub complete: bool,
+    pub position: usize
+}
+
+// Local variables of one method, class body or block invocation. Blocks see their defining
+// scope through `parent`. The tree-walker keeps locals by name, compiled code by slot.
+pub struct EnvData {
+    pub vars: HashMap<Sym, Value>,
+    pub slots: Vec<Value>,
+    pub parent: Option<ObjRef>
+}
+
+// The result of a successful match: the text searched and where each group matched in it.
+pub struct MatchData {
+    pub regex: Rc<Regex>,
+    pub text: Rc<[char]>,
+    pub captures: Captures
+}
+
+#[derive(Clone)]
+pub struct ExceptionData {
+    pub message: Value,
+    pub backtrace: Option<Vec<String>>,
+    // Where it was raised, for diagnostics.
+    pub span: Option<Span>
+}
+
+// Hash keys compare by value for immediates, strings and arrays, and by identity otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    Nil,
+    True,
+    False,
+    Int(i64),
+    BigInt(BigInt),
+    Rational(Rational),
+    Complex(Box<HashKey>, Box<HashKey>),
+    // A Regexp's source and options.
+    Regexp(String, u32),
+    Float(u64),
+    Sym(Sym),
+    Str(String),
+    Array(Vec<HashKey>),
+    Object(ObjRef)
+}
+
+#[derive(Clone, Default)]
+pub struct HashTable {
+    entries: Vec<(Value, Value)>,
+    index: HashMap<HashKey, usize>,
+    pub default: Option<Value>
+}
+
+impl HashTable {
+    pub fn create() -> HashTable {
+        HashTable { entries: Vec::new(), index: HashMap::new(), default: None }
+    }
+    pub fn get(&self, key: &HashKey) -> Option<Value> {
+        self.index.get(key).map(|i| self.entries[*i].1)
+    }
+    pub fn insert(&mut self, key: HashKey, k: Value, v: Value) {
+        match self.index.get(&key) {
+            Some(i) => self.entries[*i].1 = v,
+            None => {
+                self.index.insert(key, self.entries.len());
+                self.entries.push((k, v));
+            }
+        }
+    }
+    pub fn remove(&mut self, key: &HashKey) -> Option<Value> {
+        let i = self.index.remove(key)?;
+        let (_, v) = self.entries.remove(i);
+        for slot in self.index.values_mut() {
+            if *slot > i { *slot -= 1; }
+        }
+        Some(v)
+    }
+    pub fn contains(&self, key: &HashKey) -> bool {
+        self.index.contains_key(key)
+    }
+    pub fn entries(&self) -> &[(Value, Value)] {
+        &self.entries
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.index.clear();
+    }
+}
+
+pub enum ObjectKind {
+    Plain,
+    Str(String),
+    // An Integer outside the `i64` range; smaller ones are always `Value::Integer`.
+    BigInt(BigInt),
+    Rational(Rational),
+    // Each part is a real number: an Integer, Float or Rational.
+    Complex { real: Value, imaginary: Value },
+    Regexp(Rc<Regex>),
+    MatchData(MatchData),
+    Array(Vec<Value>),
+    Hash(HashTable),
+    Range { start: Value, end: Value, exclusive: bool },
+    Proc(ProcData),
+    Enumerator(EnumeratorData),
+    Module(ModuleData),
+    Env(EnvData),
+    Exception(ExceptionData)
+}
+
+pub struct Object {
+    pub class: ObjRef,
+    pub singleton: Option<ObjRef>,
+    pub ivars: HashMap<Sym, Value>,
+    pub frozen: bool,
+    pub kind: ObjectKind
+}
+
+impl Object {
+    pub fn create(class: ObjRef, kind: ObjectKind) -> Object {
+        Object { class, singleton: None, ivars: HashMap::new(), frozen: false, kind }
+    }
+}
+
+#[derive(Default)]
+pub struct Heap {
+    objects: Vec<Option<Object>>,
+    free: Vec<u32>
+}
+
+impl Heap {
+    pub fn create() -> Heap {
+        Heap { objects: Vec::new(), free: Vec::new() }
+    }
+    pub fn alloc(&mut self, object: Object) -> ObjRef {
+        match self.free.pop() {
+            Some(i) => {
+                self.objects[i as usize] = Some(object);
+                ObjRef(i)
+            },
+            None => {
+                self.objects.push(Some(object));
+                ObjRef(self.objects.len() as u32 - 1)
+            }
+        }
+    }
+    pub fn get(&self, r: ObjRef) -> &Object {
+        self.objects[r.index()].as_ref().expect("dangling object reference")
+    }
+    pub fn get_mut(&mut self, r: ObjRef) -> &mut Object {
+        self.objects[r.index()].as_mut().expect("dangling object reference")
+    }
+    pub fn live_count(&self) -> usize {
+        self.objects.len() - self.free.len()
+    }
+    // Live and free slots together; every reference indexes below this.
+    pub fn slot_count(&self) -> usize {
+        self.objects.len()
+    }
+    pub fn slot(&self, index: usize) -> Option<&Object> {
+        self.objects[index].as_ref()
+    }
+    // Drops the object in a slot and lets `alloc` reuse it.
+    pub fn release(&mut self, index: usize) {
+        if self.objects[index].take().is_some() {
+            self.free.push(index as u32);
+        }
+    }
+}
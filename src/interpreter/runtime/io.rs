@@ -0,0 +1,43 @@
+use std::cell::RefCell;
+use std::io::{self, BufRead, BufReader, Write};
+use std::rc::Rc;
+
+// Where `puts`, `warn` and `gets` read and write. They start as the process's streams; an
+// embedder or a test can swap in its own.
+pub struct Streams {
+    pub stdout: Box<dyn Write>,
+    pub stderr: Box<dyn Write>,
+    pub stdin: Box<dyn BufRead>
+}
+
+impl Streams {
+    pub fn create() -> Streams {
+        Streams { stdout: Box::new(io::stdout()), stderr: Box::new(io::stderr()), stdin: Box::new(BufReader::new(io::stdin())) }
+    }
+}
+
+// An output stream that keeps what is written, for reading back after the program runs. Clones
+// share the same buffer.
+#[derive(Clone, Default)]
+pub struct Capture {
+    buffer: Rc<RefCell<Vec<u8>>>
+}
+
+impl Capture {
+    pub fn create() -> Capture {
+        Capture::default()
+    }
+    pub fn get_text(&self) -> String {
+        String::from_utf8_lossy(&self.buffer.borrow()).into_owned()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
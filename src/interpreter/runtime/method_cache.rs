@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+
+use super::heap::MethodEntry;
+use super::symbols::Sym;
+use super::value::ObjRef;
+
+// What method lookup found for a class and name, from the class's ancestors.
+pub type Lookup = Option<(MethodEntry, ObjRef)>;
+
+// Remembers method lookups by receiver class and name. Any change to a method table or to the
+// modules a class includes clears it and bumps the serial, which call-site caches compare against.
+pub struct MethodCache {
+    enabled: bool,
+    serial: u64,
+    entries: HashMap<(ObjRef, Sym), Lookup>
+}
+
+impl MethodCache {
+    pub fn create() -> MethodCache {
+        MethodCache { enabled: true, serial: 0, entries: HashMap::new() }
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.invalidate();
+    }
+    pub fn get_serial(&self) -> u64 {
+        self.serial
+    }
+    pub fn get(&self, class: ObjRef, name: Sym) -> Option<&Lookup> {
+        self.entries.get(&(class, name))
+    }
+    pub fn insert(&mut self, class: ObjRef, name: Sym, lookup: Lookup) {
+        if self.enabled {
+            self.entries.insert((class, name), lookup);
+        }
+    }
+    pub fn invalidate(&mut self) {
+        self.entries.clear();
+        self.serial += 1;
+    }
+}
+
+impl Default for MethodCache {
+    fn default() -> Self {
+        MethodCache::create()
+    }
+}
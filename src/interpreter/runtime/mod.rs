@@ -0,0 +1,9 @@
+pub mod bigint;
+pub mod gc;
+pub mod heap;
+pub mod io;
+pub mod method_cache;
+pub mod rational;
+pub mod regex;
+pub mod symbols;
+pub mod value;
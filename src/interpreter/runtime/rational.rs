@@ -0,0 +1,175 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use super::bigint::BigInt;
+
+// An exact fraction in lowest terms. The denominator is always positive, so equal rationals
+// have equal fields.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numerator: BigInt,
+    denominator: BigInt
+}
+
+impl Rational {
+    // `None` for a zero denominator.
+    pub fn create(numerator: BigInt, denominator: BigInt) -> Option<Rational> {
+        if denominator.is_zero() {
+            return None;
+        }
+        let divisor = numerator.gcd(&denominator);
+        let divisor = if denominator.is_negative() { divisor.neg() } else { divisor };
+        let exact = |n: &BigInt| n.div_mod_floor(&divisor).map_or_else(BigInt::zero, |(q, _)| q);
+        Some(Rational { numerator: exact(&numerator), denominator: exact(&denominator) })
+    }
+    pub fn from_integer(n: BigInt) -> Rational {
+        Rational { numerator: n, denominator: BigInt::from(1) }
+    }
+
+    // Decimal digits with an optional sign and fraction, so `1.5` is exactly `3/2`.
+    pub fn parse(text: &str) -> Option<Rational> {
+        let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+        if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let numerator = BigInt::parse(&format!("{}{}", whole, fraction))?;
+        Rational::create(numerator, BigInt::from(10).pow(fraction.len() as u64))
+    }
+
+    // The float's exact value, which is always a dyadic fraction.
+    pub fn from_f64(f: f64) -> Option<Rational> {
+        if !f.is_finite() {
+            return None;
+        }
+        let bits = f.to_bits();
+        let biased = ((bits >> 52) & 0x7ff) as i64;
+        let fraction = (bits & ((1 << 52) - 1)) as i64;
+        // Subnormals have no implicit leading bit.
+        let (mantissa, exponent) = if biased == 0 { (fraction, -1074) } else { (fraction | (1 << 52), biased - 1075) };
+        let mantissa = BigInt::from(if f < 0.0 { -mantissa } else { mantissa });
+        if exponent >= 0 {
+            return Some(Rational::from_integer(mantissa.shl(exponent as usize)));
+        }
+        Rational::create(mantissa, BigInt::from(1).shl(-exponent as usize))
+    }
+
+    pub fn get_numerator(&self) -> &BigInt {
+        &self.numerator
+    }
+    pub fn get_denominator(&self) -> &BigInt {
+        &self.denominator
+    }
+    pub fn is_integer(&self) -> bool {
+        self.denominator == BigInt::from(1)
+    }
+    pub fn is_zero(&self) -> bool {
+        self.numerator.is_zero()
+    }
+    pub fn is_negative(&self) -> bool {
+        self.numerator.is_negative()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        // Past a float's range the parts are scaled down together first.
+        let excess = self.numerator.bit_length().max(self.denominator.bit_length()).saturating_sub(1000);
+        if excess == 0 {
+            return self.numerator.to_f64() / self.denominator.to_f64();
+        }
+        let scale = BigInt::from(1).shl(excess);
+        let shrink = |n: &BigInt| n.div_mod_floor(&scale).map_or(0.0, |(q, _)| q.to_f64());
+        shrink(&self.numerator) / shrink(&self.denominator)
+    }
+
+    pub fn neg(&self) -> Rational {
+        Rational { numerator: self.numerator.neg(), denominator: self.denominator.clone() }
+    }
+    pub fn abs(&self) -> Rational {
+        Rational { numerator: self.numerator.abs(), denominator: self.denominator.clone() }
+    }
+    pub fn add(&self, other: &Rational) -> Rational {
+        let numerator = self.numerator.mul(&other.denominator).add(&other.numerator.mul(&self.denominator));
+        Rational::reduced(numerator, self.denominator.mul(&other.denominator))
+    }
+    pub fn sub(&self, other: &Rational) -> Rational {
+        self.add(&other.neg())
+    }
+    pub fn mul(&self, other: &Rational) -> Rational {
+        Rational::reduced(self.numerator.mul(&other.numerator), self.denominator.mul(&other.denominator))
+    }
+    // `None` when dividing by zero.
+    pub fn div(&self, other: &Rational) -> Option<Rational> {
+        Rational::create(self.numerator.mul(&other.denominator), self.denominator.mul(&other.numerator))
+    }
+    // `None` for zero to a negative power.
+    pub fn pow(&self, exponent: i64) -> Option<Rational> {
+        let magnitude = exponent.unsigned_abs();
+        let raised = Rational { numerator: self.numerator.pow(magnitude), denominator: self.denominator.pow(magnitude) };
+        if exponent >= 0 { Some(raised) } else { Rational::from_integer(BigInt::from(1)).div(&raised) }
+    }
+
+    pub fn floor(&self) -> BigInt {
+        self.numerator.div_mod_floor(&self.denominator).map_or_else(BigInt::zero, |(q, _)| q)
+    }
+    pub fn ceil(&self) -> BigInt {
+        self.neg().floor().neg()
+    }
+    pub fn truncate(&self) -> BigInt {
+        if self.is_negative() { self.ceil() } else { self.floor() }
+    }
+    // Halves round away from zero.
+    pub fn round(&self) -> BigInt {
+        let half = Rational { numerator: BigInt::from(1), denominator: BigInt::from(2) };
+        if self.is_negative() { self.neg().add(&half).floor().neg() } else { self.add(&half).floor() }
+    }
+
+    fn reduced(numerator: BigInt, denominator: BigInt) -> Rational {
+        Rational::create(numerator, denominator).unwrap_or_else(|| Rational::from_integer(BigInt::zero()))
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Rational) -> Ordering {
+        self.numerator.mul(&other.denominator).cmp(&other.numerator.mul(&self.denominator))
+    }
+}
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Rational) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn r(n: i64, d: i64) -> Rational {
+        Rational::create(BigInt::from(n), BigInt::from(d)).unwrap()
+    }
+
+    #[test]
+    fn normalizes_and_computes_exactly() {
+        assert_eq!(r(6, -4).to_string(), "-3/2");
+        assert_eq!(Rational::parse("-1.25"), Some(r(-5, 4)));
+        assert_eq!(Rational::from_f64(0.1).unwrap().to_string(), "3602879701896397/36028797018963968");
+        assert_eq!(r(1, 3).add(&r(1, 6)), r(1, 2));
+        assert_eq!(r(1, 3).sub(&r(1, 2)).mul(&r(3, 1)), r(-1, 2));
+        assert_eq!(r(2, 3).div(&r(4, 9)), Some(r(3, 2)));
+        assert_eq!(r(2, 3).pow(-2), Some(r(9, 4)));
+        assert!(r(0, 1).pow(-1).is_none() && Rational::create(BigInt::from(1), BigInt::zero()).is_none());
+        assert!(r(1, 3) < r(1, 2) && r(-1, 2) < r(-1, 3));
+    }
+
+    #[test]
+    fn rounds_like_integers_do() {
+        let parts = |q: Rational| [q.floor(), q.ceil(), q.truncate(), q.round()].iter().map(|n| n.to_i64().unwrap()).collect::<Vec<_>>();
+        assert_eq!(parts(r(7, 2)), vec![3, 4, 3, 4]);
+        assert_eq!(parts(r(-7, 2)), vec![-4, -3, -3, -4]);
+        assert_eq!(parts(r(-5, 3)), vec![-2, -1, -1, -2]);
+    }
+}
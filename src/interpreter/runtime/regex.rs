@@ -0,0 +1,806 @@
+// A backtracking regular expression engine covering the Ruby syntax scripts commonly use:
+// classes, anchors, greedy and lazy quantifiers, capturing and named groups, backreferences,
+// lookaround and the `i`, `m` and `x` options. Matching works on characters, so offsets are
+// character offsets rather than byte offsets.
+
+// Option bits, with Ruby's values so `Regexp#options` reports the same numbers.
+pub const IGNORECASE: u32 = 1;
+pub const EXTENDED: u32 = 2;
+pub const MULTILINE: u32 = 4;
+
+// Where each group matched, as character offsets; group 0 is the whole match.
+pub type Captures = Vec<Option<(usize, usize)>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClassKind {
+    Digit,
+    Word,
+    Space,
+    Hex,
+    Alpha,
+    Alnum,
+    Upper,
+    Lower,
+    Punct,
+    Cntrl,
+    Print,
+    Graph,
+    Blank
+}
+
+impl ClassKind {
+    fn from_posix(name: &str) -> Option<ClassKind> {
+        Some(match name {
+            "digit"  => ClassKind::Digit,
+            "word"   => ClassKind::Word,
+            "space"  => ClassKind::Space,
+            "xdigit" => ClassKind::Hex,
+            "alpha"  => ClassKind::Alpha,
+            "alnum"  => ClassKind::Alnum,
+            "upper"  => ClassKind::Upper,
+            "lower"  => ClassKind::Lower,
+            "punct"  => ClassKind::Punct,
+            "cntrl"  => ClassKind::Cntrl,
+            "print"  => ClassKind::Print,
+            "graph"  => ClassKind::Graph,
+            "blank"  => ClassKind::Blank,
+            _        => return None
+        })
+    }
+    fn matches(self, c: char) -> bool {
+        match self {
+            ClassKind::Digit => c.is_ascii_digit(),
+            ClassKind::Word  => is_word(c),
+            ClassKind::Space => matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0b' | '\x0c'),
+            ClassKind::Hex   => c.is_ascii_hexdigit(),
+            ClassKind::Alpha => c.is_alphabetic(),
+            ClassKind::Alnum => c.is_alphanumeric(),
+            ClassKind::Upper => c.is_uppercase(),
+            ClassKind::Lower => c.is_lowercase(),
+            ClassKind::Punct => c.is_ascii_punctuation(),
+            ClassKind::Cntrl => c.is_control(),
+            ClassKind::Print => !c.is_control(),
+            ClassKind::Graph => !c.is_control() && !c.is_whitespace(),
+            ClassKind::Blank => c == ' ' || c == '\t'
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SetItem {
+    Range(char, char),
+    Class(ClassKind, bool)
+}
+
+#[derive(Debug, Clone)]
+struct Set {
+    negated: bool,
+    items: Vec<SetItem>
+}
+
+impl Set {
+    fn single(kind: ClassKind, negated: bool) -> Set {
+        Set { negated: false, items: vec![SetItem::Class(kind, negated)] }
+    }
+    fn contains(&self, c: char, ignore_case: bool) -> bool {
+        let test = |c: char| self.items.iter().any(|item| match *item {
+            SetItem::Range(lo, hi) => lo <= c && c <= hi,
+            SetItem::Class(kind, negated) => kind.matches(c) != negated
+        });
+        let found = test(c) || (ignore_case && (test(fold(c)) || test(upper(c))));
+        found != self.negated
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    // The flag is whether case is ignored.
+    Char(char, bool),
+    // `.`; the flag is whether it also matches a newline.
+    Any(bool),
+    Set(Set, bool),
+    LineStart,
+    LineEnd,
+    TextStart,
+    TextEnd,
+    // `\Z`: the end, or just before a final newline.
+    TextEndNewline,
+    // `\b` when true, `\B` when false.
+    WordBoundary(bool),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat { node: Box<Node>, min: usize, max: Option<usize>, greedy: bool },
+    Backref(usize, bool),
+    Look { node: Box<Node>, ahead: bool, negated: bool }
+}
+
+impl Node {
+    // Nodes that always consume exactly one character, which repeat without recursing.
+    fn is_single(&self) -> bool {
+        matches!(self, Node::Char(..) | Node::Any(_) | Node::Set(..))
+    }
+    fn matches_char(&self, c: char) -> bool {
+        match self {
+            Node::Char(expected, ignore_case) => *expected == c || (*ignore_case && fold(*expected) == fold(c)),
+            Node::Any(dot_all) => *dot_all || c != '\n',
+            Node::Set(set, ignore_case) => set.contains(c, *ignore_case),
+            _ => false
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    source: String,
+    options: u32,
+    root: Node,
+    groups: usize,
+    names: Vec<(String, usize)>,
+    // Runs of characters every match contains, so text without them is rejected early.
+    required: Vec<Vec<char>>
+}
+
+impl Regex {
+    pub fn create(source: &str, options: u32) -> Result<Regex, String> {
+        let mut parser = Parser { chars: source.chars().collect(), pos: 0, options, groups: 0, names: Vec::new() };
+        let root = parser.alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err(String::from("unmatched close parenthesis"));
+        }
+        let mut required = Vec::new();
+        required_literals(&root, &mut required);
+        Ok(Regex { source: source.to_string(), options, root, groups: parser.groups, names: parser.names, required })
+    }
+
+    pub fn get_source(&self) -> &str { &self.source }
+    pub fn get_options(&self) -> u32 { self.options }
+    pub fn get_group_count(&self) -> usize { self.groups }
+    // Named groups in the order they appear, with their group numbers.
+    pub fn get_names(&self) -> &[(String, usize)] { &self.names }
+    pub fn group_index(&self, name: &str) -> Option<usize> {
+        self.names.iter().find(|(n, _)| n == name).map(|(_, i)| *i)
+    }
+
+    // The leftmost match starting at or after `start`.
+    pub fn search(&self, text: &[char], start: usize) -> Option<Captures> {
+        let matcher = Matcher { text };
+        let anchored = match &self.root {
+            Node::TextStart => true,
+            Node::Concat(nodes) => matches!(nodes.first(), Some(Node::TextStart)),
+            _ => false
+        };
+        // A match holds each required literal somewhere after where it starts, so none can start
+        // past a literal's last occurrence.
+        let mut last = text.len();
+        for literal in self.required.iter() {
+            let len = literal.len();
+            let found = (start..=text.len().checked_sub(len)?).rev().find(|i| text[*i..*i + len] == literal[..])?;
+            last = last.min(found);
+        }
+        for from in start..=last {
+            let mut captures = vec![None; self.groups + 1];
+            let mut end = from;
+            if matcher.node(&self.root, from, &mut captures, &mut |e, _| { end = e; true }) {
+                captures[0] = Some((from, end));
+                return Some(captures);
+            }
+            if anchored { break; }
+        }
+        None
+    }
+
+    // The pattern that matches `text` literally.
+    pub fn escape(text: &str) -> String {
+        let mut out = String::new();
+        for c in text.chars() {
+            match c {
+                '\n' => out.push_str("\\n"),
+                '\t' => out.push_str("\\t"),
+                '\r' => out.push_str("\\r"),
+                '\x0c' => out.push_str("\\f"),
+                '\x0b' => out.push_str("\\v"),
+                '.' | '*' | '?' | '+' | '^' | '$' | '|' | '(' | ')' | '[' | ']' | '{' | '}' | '\\' | '-' | '#' | ' ' => {
+                    out.push('\\');
+                    out.push(c);
+                },
+                c => out.push(c)
+            }
+        }
+        out
+    }
+}
+
+// Runs of characters that every match of `node` contains, taken from the parts it can't skip.
+// Lookaround and case-insensitive characters contribute nothing.
+fn required_literals(node: &Node, literals: &mut Vec<Vec<char>>) {
+    match node {
+        Node::Char(c, false) => literals.push(vec![*c]),
+        Node::Group(inner, _) => required_literals(inner, literals),
+        Node::Repeat { node, min, .. } if *min > 0 => required_literals(node, literals),
+        Node::Concat(nodes) => {
+            let mut run = Vec::new();
+            for node in nodes.iter() {
+                match node {
+                    Node::Char(c, false) => run.push(*c),
+                    node => {
+                        if !run.is_empty() {
+                            literals.push(std::mem::take(&mut run));
+                        }
+                        required_literals(node, literals);
+                    }
+                }
+            }
+            if !run.is_empty() {
+                literals.push(run);
+            }
+        },
+        _ => {}
+    }
+}
+
+pub fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn fold(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c
+    }
+}
+
+fn upper(c: char) -> char {
+    let mut upper = c.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(u), None) => u,
+        _ => c
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    options: u32,
+    groups: usize,
+    names: Vec<(String, usize)>
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> { self.chars.get(self.pos).copied() }
+    fn peek_nth(&self, n: usize) -> Option<char> { self.chars.get(self.pos + n).copied() }
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+    fn ignore_case(&self) -> bool { self.options & IGNORECASE != 0 }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.sequence()?];
+        while self.eat('|') {
+            branches.push(self.sequence()?);
+        }
+        Ok(if branches.len() == 1 { branches.pop().unwrap() } else { Node::Alternate(branches) })
+    }
+
+    fn sequence(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' { break; }
+            if self.options & EXTENDED != 0 {
+                if c.is_whitespace() {
+                    self.pos += 1;
+                    continue;
+                }
+                if c == '#' {
+                    while !matches!(self.peek(), None | Some('\n')) { self.pos += 1; }
+                    continue;
+                }
+            }
+            let atom = match self.atom()? {
+                Some(atom) => atom,
+                None => continue
+            };
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(if nodes.len() == 1 { nodes.pop().unwrap() } else if nodes.is_empty() { Node::Empty } else { Node::Concat(nodes) })
+    }
+
+    fn quantified(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => { self.pos += 1; (0, None) },
+            Some('+') => { self.pos += 1; (1, None) },
+            Some('?') => { self.pos += 1; (0, Some(1)) },
+            Some('{') => match self.bounds() {
+                Some(bounds) => bounds,
+                None => return Ok(atom)
+            },
+            _ => return Ok(atom)
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err(String::from("upper bound must be greater than lower bound"));
+        }
+        let greedy = !self.eat('?');
+        Ok(Node::Repeat { node: Box::new(atom), min, max, greedy })
+    }
+
+    // `{n}`, `{n,}`, `{,m}` or `{n,m}`; anything else leaves the `{` to be read literally.
+    fn bounds(&mut self) -> Option<(usize, Option<usize>)> {
+        let close = (self.pos..self.chars.len()).find(|i| self.chars[*i] == '}')?;
+        let inside: String = self.chars[self.pos + 1..close].iter().collect();
+        if inside == "," { return None; }
+        let number = |s: &str| if s.is_empty() { Some(None) } else { s.parse::<usize>().ok().map(Some) };
+        let bounds = match inside.split_once(',') {
+            Some((lo, hi)) => (number(lo)?.unwrap_or(0), number(hi)?),
+            None => {
+                let n = inside.parse::<usize>().ok()?;
+                (n, Some(n))
+            }
+        };
+        self.pos = close + 1;
+        Some(bounds)
+    }
+
+    // `None` for constructs that match nothing themselves, such as `(?i)`.
+    fn atom(&mut self) -> Result<Option<Node>, String> {
+        let c = self.peek().unwrap();
+        self.pos += 1;
+        Ok(Some(match c {
+            '(' => return self.group(),
+            '[' => Node::Set(self.set()?, self.ignore_case()),
+            '.' => Node::Any(self.options & MULTILINE != 0),
+            '^' => Node::LineStart,
+            '$' => Node::LineEnd,
+            '\\' => self.escape()?,
+            '*' | '+' | '?' => return Err(String::from("target of repeat operator is not specified")),
+            c => Node::Char(c, self.ignore_case())
+        }))
+    }
+
+    fn group(&mut self) -> Result<Option<Node>, String> {
+        let saved = self.options;
+        let node = if self.eat('?') {
+            match self.peek() {
+                Some(':') => {
+                    self.pos += 1;
+                    Node::Group(Box::new(self.alternation()?), None)
+                },
+                Some('=') | Some('!') => {
+                    let negated = self.peek() == Some('!');
+                    self.pos += 1;
+                    Node::Look { node: Box::new(self.alternation()?), ahead: true, negated }
+                },
+                Some('<') if matches!(self.peek_nth(1), Some('=') | Some('!')) => {
+                    let negated = self.peek_nth(1) == Some('!');
+                    self.pos += 2;
+                    Node::Look { node: Box::new(self.alternation()?), ahead: false, negated }
+                },
+                Some('<') => {
+                    self.pos += 1;
+                    let mut name = String::new();
+                    while let Some(c) = self.peek() {
+                        self.pos += 1;
+                        if c == '>' { break; }
+                        if !is_word(c) { return Err(format!("invalid group name <{}{}>", name, c)); }
+                        name.push(c);
+                    }
+                    if name.is_empty() { return Err(String::from("group name is empty")); }
+                    self.groups += 1;
+                    let index = self.groups;
+                    self.names.push((name, index));
+                    Node::Group(Box::new(self.alternation()?), Some(index))
+                },
+                Some('#') => {
+                    while !matches!(self.peek(), None | Some(')')) { self.pos += 1; }
+                    if !self.eat(')') { return Err(String::from("end pattern in group")); }
+                    return Ok(None);
+                },
+                _ => {
+                    // `(?imx-imx)` changes the options for the rest of the group, `(?imx-imx:...)`
+                    // only inside it.
+                    let mut on = true;
+                    let mut options = self.options;
+                    loop {
+                        let bit = match self.peek() {
+                            Some('i') => IGNORECASE,
+                            Some('m') => MULTILINE,
+                            Some('x') => EXTENDED,
+                            Some('-') if on => { on = false; self.pos += 1; continue },
+                            Some(')') => {
+                                self.pos += 1;
+                                self.options = options;
+                                return Ok(None);
+                            },
+                            Some(':') => {
+                                self.pos += 1;
+                                break;
+                            },
+                            _ => return Err(String::from("undefined group option"))
+                        };
+                        options = if on { options | bit } else { options & !bit };
+                        self.pos += 1;
+                    }
+                    self.options = options;
+                    Node::Group(Box::new(self.alternation()?), None)
+                }
+            }
+        } else {
+            self.groups += 1;
+            let index = self.groups;
+            Node::Group(Box::new(self.alternation()?), Some(index))
+        };
+        self.options = saved;
+        if !self.eat(')') {
+            return Err(String::from("end pattern with unmatched parenthesis"));
+        }
+        Ok(Some(node))
+    }
+
+    fn escape(&mut self) -> Result<Node, String> {
+        let c = self.peek().ok_or_else(|| String::from("too short escape sequence"))?;
+        self.pos += 1;
+        let class = |kind, negated| Node::Set(Set::single(kind, negated), false);
+        Ok(match c {
+            'd' => class(ClassKind::Digit, false),
+            'D' => class(ClassKind::Digit, true),
+            'w' => class(ClassKind::Word, false),
+            'W' => class(ClassKind::Word, true),
+            's' => class(ClassKind::Space, false),
+            'S' => class(ClassKind::Space, true),
+            'h' => class(ClassKind::Hex, false),
+            'H' => class(ClassKind::Hex, true),
+            'A' => Node::TextStart,
+            'z' => Node::TextEnd,
+            'Z' => Node::TextEndNewline,
+            'b' => Node::WordBoundary(true),
+            'B' => Node::WordBoundary(false),
+            '1'..='9' => {
+                let mut n = c.to_digit(10).unwrap() as usize;
+                while let Some(d) = self.peek().and_then(|d| d.to_digit(10)) {
+                    if n * 10 + d as usize > self.groups { break; }
+                    n = n * 10 + d as usize;
+                    self.pos += 1;
+                }
+                if n > self.groups { return Err(format!("invalid backref number/name: \\{}", n)); }
+                Node::Backref(n, self.ignore_case())
+            },
+            'k' if self.peek() == Some('<') => {
+                self.pos += 1;
+                let mut name = String::new();
+                while let Some(c) = self.peek() {
+                    self.pos += 1;
+                    if c == '>' { break; }
+                    name.push(c);
+                }
+                match self.names.iter().find(|(n, _)| *n == name) {
+                    Some((_, index)) => Node::Backref(*index, self.ignore_case()),
+                    None => return Err(format!("undefined name <{}> reference", name))
+                }
+            },
+            c => Node::Char(self.escaped_char(c)?, self.ignore_case())
+        })
+    }
+
+    // The character an escape such as `\n`, `\x41` or `\.` stands for.
+    fn escaped_char(&mut self, c: char) -> Result<char, String> {
+        Ok(match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'f' => '\x0c',
+            'v' => '\x0b',
+            'e' => '\x1b',
+            'a' => '\x07',
+            '0' => '\0',
+            'x' => {
+                let digits: String = self.chars[self.pos..].iter().take(2).take_while(|c| c.is_ascii_hexdigit()).collect();
+                if digits.is_empty() { return Err(String::from("invalid hex escape")); }
+                self.pos += digits.len();
+                char::from_u32(u32::from_str_radix(&digits, 16).unwrap()).unwrap()
+            },
+            'u' => {
+                let braced = self.eat('{');
+                let digits: String = self.chars[self.pos..].iter().take(if braced { 6 } else { 4 }).take_while(|c| c.is_ascii_hexdigit()).collect();
+                self.pos += digits.len();
+                if digits.is_empty() || (!braced && digits.len() < 4) || (braced && !self.eat('}')) {
+                    return Err(String::from("invalid Unicode escape"));
+                }
+                u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32).ok_or_else(|| String::from("invalid Unicode range"))?
+            },
+            c => c
+        })
+    }
+
+    fn set(&mut self) -> Result<Set, String> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or_else(|| String::from("premature end of char-class"))?;
+            self.pos += 1;
+            if c == ']' && !first { break; }
+            first = false;
+            let lo = match c {
+                '[' if self.peek() == Some(':') => {
+                    let close = (self.pos..self.chars.len().saturating_sub(1))
+                        .find(|i| self.chars[*i] == ':' && self.chars[*i + 1] == ']')
+                        .ok_or_else(|| String::from("premature end of char-class"))?;
+                    let name: String = self.chars[self.pos + 1..close].iter().collect();
+                    let (name, negated) = match name.strip_prefix('^') {
+                        Some(name) => (name.to_string(), true),
+                        None => (name, false)
+                    };
+                    let kind = ClassKind::from_posix(&name).ok_or_else(|| String::from("invalid POSIX bracket type"))?;
+                    self.pos = close + 2;
+                    items.push(SetItem::Class(kind, negated));
+                    continue;
+                },
+                '\\' => {
+                    let e = self.peek().ok_or_else(|| String::from("premature end of char-class"))?;
+                    self.pos += 1;
+                    let kind = match e {
+                        'd' | 'D' => Some(ClassKind::Digit),
+                        'w' | 'W' => Some(ClassKind::Word),
+                        's' | 'S' => Some(ClassKind::Space),
+                        'h' | 'H' => Some(ClassKind::Hex),
+                        _ => None
+                    };
+                    if let Some(kind) = kind {
+                        items.push(SetItem::Class(kind, e.is_uppercase()));
+                        continue;
+                    }
+                    if e == 'b' { '\x08' } else { self.escaped_char(e)? }
+                },
+                c => c
+            };
+            let hi = if self.peek() == Some('-') && !matches!(self.peek_nth(1), None | Some(']')) {
+                self.pos += 1;
+                let c = self.peek().unwrap();
+                self.pos += 1;
+                if c == '\\' {
+                    let e = self.peek().ok_or_else(|| String::from("premature end of char-class"))?;
+                    self.pos += 1;
+                    self.escaped_char(e)?
+                } else {
+                    c
+                }
+            } else {
+                lo
+            };
+            if hi < lo {
+                return Err(String::from("empty range in char class"));
+            }
+            items.push(SetItem::Range(lo, hi));
+        }
+        Ok(Set { negated, items })
+    }
+}
+
+type Continuation<'k> = dyn FnMut(usize, &mut Captures) -> bool + 'k;
+
+struct Matcher<'t> {
+    text: &'t [char]
+}
+
+impl<'t> Matcher<'t> {
+    // Whether `node` matches at `pos` with the rest of the pattern, `k`, matching after it.
+    fn node(&self, node: &Node, pos: usize, captures: &mut Captures, k: &mut Continuation) -> bool {
+        let text = self.text;
+        match node {
+            Node::Empty => k(pos, captures),
+            Node::Char(..) | Node::Any(_) | Node::Set(..) => pos < text.len() && node.matches_char(text[pos]) && k(pos + 1, captures),
+            Node::LineStart => (pos == 0 || text[pos - 1] == '\n') && k(pos, captures),
+            Node::LineEnd => (pos == text.len() || text[pos] == '\n') && k(pos, captures),
+            Node::TextStart => pos == 0 && k(pos, captures),
+            Node::TextEnd => pos == text.len() && k(pos, captures),
+            Node::TextEndNewline => (pos == text.len() || (pos + 1 == text.len() && text[pos] == '\n')) && k(pos, captures),
+            Node::WordBoundary(wanted) => {
+                let before = pos > 0 && is_word(text[pos - 1]);
+                let after = pos < text.len() && is_word(text[pos]);
+                (before != after) == *wanted && k(pos, captures)
+            },
+            Node::Group(inner, None) => self.node(inner, pos, captures, k),
+            Node::Group(inner, Some(index)) => {
+                let index = *index;
+                self.node(inner, pos, captures, &mut |end, captures| {
+                    let saved = captures[index];
+                    captures[index] = Some((pos, end));
+                    if k(end, captures) { return true; }
+                    captures[index] = saved;
+                    false
+                })
+            },
+            Node::Concat(nodes) => self.sequence(nodes, pos, captures, k),
+            Node::Alternate(branches) => {
+                for branch in branches.iter() {
+                    if self.node(branch, pos, captures, k) { return true; }
+                }
+                false
+            },
+            Node::Repeat { node, min, max, greedy } if node.is_single() => {
+                let mut count = 0;
+                while max.is_none_or(|max| count < max) && pos + count < text.len() && node.matches_char(text[pos + count]) {
+                    count += 1;
+                }
+                if count < *min { return false; }
+                if *greedy {
+                    (*min..=count).rev().any(|n| k(pos + n, captures))
+                } else {
+                    (*min..=count).any(|n| k(pos + n, captures))
+                }
+            },
+            Node::Repeat { node, min, max, greedy } => self.repeat(node, *min, *max, *greedy, 0, pos, captures, k),
+            Node::Backref(index, ignore_case) => {
+                let (start, end) = match captures[*index] {
+                    Some(span) => span,
+                    None => return false
+                };
+                let len = end - start;
+                if pos + len > text.len() { return false; }
+                let same = (0..len).all(|i| {
+                    let (a, b) = (text[start + i], text[pos + i]);
+                    a == b || (*ignore_case && fold(a) == fold(b))
+                });
+                same && k(pos + len, captures)
+            },
+            Node::Look { node, ahead, negated } => {
+                let mut trial = captures.clone();
+                let found = if *ahead {
+                    self.node(node, pos, &mut trial, &mut |_, _| true)
+                } else {
+                    (0..=pos).rev().any(|start| self.node(node, start, &mut trial, &mut |end, _| end == pos))
+                };
+                if found == *negated { return false; }
+                if *negated { return k(pos, captures); }
+                // Groups inside a positive lookaround keep what they captured.
+                let saved = std::mem::replace(captures, trial);
+                if k(pos, captures) { return true; }
+                *captures = saved;
+                false
+            }
+        }
+    }
+
+    fn sequence(&self, nodes: &[Node], pos: usize, captures: &mut Captures, k: &mut Continuation) -> bool {
+        match nodes.split_first() {
+            None => k(pos, captures),
+            Some((first, rest)) => self.node(first, pos, captures, &mut |p, captures| self.sequence(rest, p, captures, k))
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn repeat(&self, node: &Node, min: usize, max: Option<usize>, greedy: bool, count: usize, pos: usize, captures: &mut Captures, k: &mut Continuation) -> bool {
+        let more = max.is_none_or(|max| count < max);
+        if !greedy && count >= min && k(pos, captures) {
+            return true;
+        }
+        // Once the minimum is met, an iteration that consumes nothing can't lead anywhere new.
+        if more && self.node(node, pos, captures, &mut |p, captures| {
+            (p != pos || count < min) && self.repeat(node, min, max, greedy, count + 1, p, captures, k)
+        }) {
+            return true;
+        }
+        greedy && count >= min && k(pos, captures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, options: u32, text: &str) -> Option<Vec<Option<String>>> {
+        let chars: Vec<char> = text.chars().collect();
+        let captures = Regex::create(pattern, options).unwrap().search(&chars, 0)?;
+        Some(captures.iter().map(|c| c.map(|(s, e)| chars[s..e].iter().collect())).collect())
+    }
+    fn whole(pattern: &str, text: &str) -> Option<String> {
+        find(pattern, 0, text).and_then(|c| c[0].clone())
+    }
+
+    #[test]
+    fn matches_common_constructs() {
+        assert_eq!(whole("b+", "abbbc").as_deref(), Some("bbb"));
+        assert_eq!(whole("b+?", "abbbc").as_deref(), Some("b"));
+        assert_eq!(whole("\\d{2,3}", "a12345").as_deref(), Some("123"));
+        assert_eq!(whole("[^a-c\\s]+", "abc dxyz").as_deref(), Some("dxyz"));
+        assert_eq!(whole("[[:upper:]]\\w*", "hello World").as_deref(), Some("World"));
+        assert_eq!(whole("^b", "a\nb").as_deref(), Some("b"));
+        assert_eq!(whole("\\Ab", "a\nb"), None);
+        assert_eq!(whole("\\bcat\\b", "concat cat").as_deref(), Some("cat"));
+        assert_eq!(whole("colou?r|grey", "the grey").as_deref(), Some("grey"));
+        assert_eq!(whole("(\\w)\\1", "abccd").as_deref(), Some("cc"));
+        assert_eq!(whole("\\d+(?=px)", "10em 20px").as_deref(), Some("20"));
+        assert_eq!(whole("(?<!\\$)\\b\\d+", "$5 7").as_deref(), Some("7"));
+        assert_eq!(whole("é.", "café!").as_deref(), Some("é!"));
+        assert_eq!(find("HELLO", IGNORECASE, "say hello").unwrap()[0].as_deref(), Some("hello"));
+        assert_eq!(find("a.b", MULTILINE, "a\nb").unwrap()[0].as_deref(), Some("a\nb"));
+        assert_eq!(find("a b # comment", EXTENDED, "ab").unwrap()[0].as_deref(), Some("ab"));
+    }
+
+    #[test]
+    fn anchors_match_positions() {
+        assert_eq!(whole("^\\w+$", "one\ntwo\n").as_deref(), Some("one"));
+        assert_eq!(whole("two$", "one\ntwo\n").as_deref(), Some("two"));
+        assert_eq!(whole("\\Aone", "one\ntwo").as_deref(), Some("one"));
+        assert_eq!(whole("two\\z", "two\n"), None);
+        assert_eq!(whole("two\\Z", "two\n").as_deref(), Some("two"));
+        assert_eq!(whole("\\Bat\\B", "at cats").as_deref(), Some("at"));
+        assert_eq!(whole("^$", "a\n\nb").as_deref(), Some(""));
+        assert_eq!(whole("\\b", "  ").as_deref(), None);
+    }
+
+    #[test]
+    fn classes_match_sets_of_characters() {
+        assert_eq!(whole("\\d+\\s\\w+", "a 42 apples").as_deref(), Some("42 apples"));
+        assert_eq!(whole("\\h+", "xyz c0ffee").as_deref(), Some("c0ffee"));
+        assert_eq!(whole("\\D\\W\\S", "1a.b").as_deref(), Some("a.b"));
+        assert_eq!(whole("[a-cx-z]+", "defbazw").as_deref(), Some("baz"));
+        assert_eq!(whole("[\\d.-]+", "v-1.25 ").as_deref(), Some("-1.25"));
+        assert_eq!(whole("[\\]\\\\]+", "a]\\]").as_deref(), Some("]\\]"));
+        assert_eq!(whole("[]a]+", "b]a]").as_deref(), Some("]a]"));
+        assert_eq!(whole("[^[:alpha:][:space:]]+", "ab 12;c").as_deref(), Some("12;"));
+        assert_eq!(whole("[\\x41-\\x43]+", "xABCD").as_deref(), Some("ABC"));
+        assert_eq!(find("[a-c]+", IGNORECASE, "xAbC").unwrap()[0].as_deref(), Some("AbC"));
+        assert_eq!(whole(".+", "ab\ncd").as_deref(), Some("ab"));
+    }
+
+    #[test]
+    fn groups_capture_and_repeat() {
+        let groups = find("((a)(b)?)+", 0, "aab").unwrap();
+        assert_eq!(groups, vec![Some(String::from("aab")), Some(String::from("ab")), Some(String::from("a")), Some(String::from("b"))]);
+        assert_eq!(find("(?:ab)+(c)", 0, "ababc").unwrap(), vec![Some(String::from("ababc")), Some(String::from("c"))]);
+        assert_eq!(find("(?<x>a)(?<y>b)\\k<x>", 0, "aba").unwrap()[0].as_deref(), Some("aba"));
+        assert_eq!(whole("a(?i)b", "aB").as_deref(), Some("aB"));
+        assert_eq!(whole("(?i:a)b", "Ab").as_deref(), Some("Ab"));
+        assert_eq!(whole("(?i:a)b", "AB"), None);
+        assert_eq!(whole("(ab){2}", "abababa").as_deref(), Some("abab"));
+        assert_eq!(whole("(a|b)*?c", "abac").as_deref(), Some("abac"));
+    }
+
+    #[test]
+    fn alternation_takes_the_leftmost_then_first_branch() {
+        assert_eq!(whole("a|ab", "ab").as_deref(), Some("a"));
+        assert_eq!(whole("ab|a", "ab").as_deref(), Some("ab"));
+        assert_eq!(whole("cat|dog", "hotdog cat").as_deref(), Some("dog"));
+        assert_eq!(whole("x(a|b|)y", "xy").as_deref(), Some("xy"));
+        assert_eq!(whole("(?:ab|cd)+", "zabcdab").as_deref(), Some("abcdab"));
+        assert_eq!(whole("^(?:a|b)$", "c\nb").as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn rejects_text_missing_a_required_literal() {
+        let required = |pattern: &str, options: u32| -> Vec<String> {
+            Regex::create(pattern, options).unwrap().required.iter().map(|l| l.iter().collect()).collect()
+        };
+        assert_eq!(required("x*y", 0), ["y"]);
+        assert_eq!(required("a(bcd)+e|f", 0), Vec::<String>::new());
+        assert_eq!(required("ab(cd)+e\\d (wide)", 0), ["ab", "cd", "e", " ", "wide"]);
+        assert_eq!(required("(wide)?ab\\d", IGNORECASE), Vec::<String>::new());
+        let text: Vec<char> = "x".repeat(200_000).chars().collect();
+        assert!(Regex::create("x*y", 0).unwrap().search(&text, 0).is_none());
+        assert!(Regex::create("(x+x+)+y", 0).unwrap().search(&text, 0).is_none());
+        assert_eq!(whole("x*y", "xxyxy").as_deref(), Some("xxy"));
+        assert_eq!(find("x*y", 0, "y").unwrap()[0].as_deref(), Some("y"));
+        assert_eq!(whole("abc", "ab"), None);
+    }
+
+    #[test]
+    fn reports_groups_and_errors() {
+        let groups = find("(?<year>\\d+)-(\\d+)(x)?", 0, "on 2024-06").unwrap();
+        assert_eq!(groups, vec![Some(String::from("2024-06")), Some(String::from("2024")), Some(String::from("06")), None]);
+        assert_eq!(Regex::create("(?<year>\\d+)", 0).unwrap().group_index("year"), Some(1));
+        assert_eq!(whole("(a|ab)(c|bcd)(d*)", "abcd").as_deref(), Some("abcd"));
+        assert_eq!(Regex::escape("1.5 (x)"), "1\\.5\\ \\(x\\)");
+        assert!(Regex::create("(ab", 0).is_err());
+        assert!(Regex::create("ab)", 0).is_err());
+        assert!(Regex::create("[b-a]", 0).is_err());
+        assert!(Regex::create("*a", 0).is_err());
+        assert!(Regex::create("a{3,2}", 0).is_err());
+    }
+}
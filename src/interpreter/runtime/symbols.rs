@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+
+// An interned name: symbols, method names, variable names and constant names all share one table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Sym(u32);
+
+impl Sym {
+    pub fn index(self) -> usize { self.0 as usize }
+}
+
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    names: Vec<String>,
+    ids: HashMap<String, Sym>
+}
+
+impl SymbolTable {
+    pub fn create() -> SymbolTable {
+        SymbolTable { names: Vec::new(), ids: HashMap::new() }
+    }
+    pub fn intern(&mut self, name: &str) -> Sym {
+        if let Some(sym) = self.ids.get(name) {
+            return *sym;
+        }
+        let sym = Sym(self.names.len() as u32);
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), sym);
+        sym
+    }
+    pub fn name(&self, sym: Sym) -> &str {
+        &self.names[sym.0 as usize]
+    }
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
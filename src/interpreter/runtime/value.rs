@@ -0,0 +1,48 @@
+use super::symbols::Sym;
+
+// A handle to an object on the runtime heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjRef(pub(crate) u32);
+
+impl ObjRef {
+    pub fn index(self) -> usize { self.0 as usize }
+}
+
+// Immediate values are stored inline; everything else lives on the heap and is passed by handle,
+// so a `Value` is always cheap to copy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Nil,
+    True,
+    False,
+    Integer(i64),
+    Float(f64),
+    Symbol(Sym),
+    Object(ObjRef)
+}
+
+impl Value {
+    pub fn from_bool(b: bool) -> Value {
+        if b { Value::True } else { Value::False }
+    }
+    // Only `nil` and `false` are falsy.
+    pub fn is_truthy(self) -> bool {
+        !matches!(self, Value::Nil | Value::False)
+    }
+    pub fn is_nil(self) -> bool {
+        self == Value::Nil
+    }
+    pub fn as_object(self) -> Option<ObjRef> {
+        match self {
+            Value::Object(r) => Some(r),
+            _ => None
+        }
+    }
+    // Identity, as `equal?` sees it. Immediates are identical when their values are.
+    pub fn is_identical(self, other: Value) -> bool {
+        match (self, other) {
+            (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+            _ => self == other
+        }
+    }
+}
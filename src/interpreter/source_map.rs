@@ -0,0 +1,70 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Identifies a loaded source file. Tokens, spans and runtime frames carry one so errors and
+// `__FILE__` name the file the code came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(u32);
+
+impl FileId {
+    // Source that didn't come from the source map, such as a string handed straight to the lexer.
+    pub const ANONYMOUS: FileId = FileId(u32::MAX);
+
+    pub fn index(self) -> usize { self.0 as usize }
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    name: String,
+    path: Option<PathBuf>,
+    text: String
+}
+
+impl SourceFile {
+    // The name the file is reported under: the path as given on the command line or found on
+    // the load path, or a label like `-e` for source without a file.
+    pub fn get_name(&self) -> &str { &self.name }
+    pub fn get_path(&self) -> Option<&Path> { self.path.as_deref() }
+    pub fn get_text(&self) -> &str { &self.text }
+    pub fn get_line(&self, line: u32) -> Option<&str> {
+        (line as usize).checked_sub(1).and_then(|i| self.text.lines().nth(i))
+    }
+}
+
+// Owns the text of every file loaded into a program.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>
+}
+
+impl SourceMap {
+    pub fn create() -> SourceMap {
+        SourceMap { files: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: &str, path: Option<PathBuf>, text: String) -> FileId {
+        self.files.push(SourceFile { name: name.to_string(), path, text });
+        FileId(self.files.len() as u32 - 1)
+    }
+    pub fn add_string(&mut self, name: &str, text: &str) -> FileId {
+        self.add(name, None, text.to_string())
+    }
+    // Reads a file from disk. The path is kept in absolute form for `require` bookkeeping; the
+    // name stays as given.
+    pub fn load(&mut self, path: &Path) -> io::Result<FileId> {
+        let text = fs::read_to_string(path)?;
+        let absolute = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        Ok(self.add(&path.to_string_lossy(), Some(absolute), text))
+    }
+
+    pub fn get(&self, id: FileId) -> Option<&SourceFile> {
+        self.files.get(id.index())
+    }
+    pub fn get_name(&self, id: FileId) -> &str {
+        self.get(id).map_or("<unknown>", SourceFile::get_name)
+    }
+    pub fn find_path(&self, path: &Path) -> Option<FileId> {
+        self.files.iter().position(|f| f.path.as_deref() == Some(path)).map(|i| FileId(i as u32))
+    }
+}
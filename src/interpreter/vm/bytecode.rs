@@ -0,0 +1,252 @@
+use std::rc::Rc;
+
+use crate::interpreter::parser::lexicon::OperatorSymbol;
+use crate::interpreter::runtime::symbols::Sym;
+use super::inline_cache::CallSites;
+
+// How a `Send`, `Super` or `Yield` finds its arguments on the stack, above the receiver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CallFlags(u8);
+
+impl CallFlags {
+    pub const NONE: CallFlags = CallFlags(0);
+    // The first argument is an array whose items are passed in its place.
+    pub const SPLAT: CallFlags = CallFlags(1);
+    // A `&block` argument (a proc or nil) follows the arguments.
+    pub const BLOCK_ARG: CallFlags = CallFlags(2);
+    // A literal block's proc follows the arguments; a `break` out of it ends the call.
+    pub const BLOCK_LITERAL: CallFlags = CallFlags(4);
+    // A bare name that could have been a variable, for the NameError message.
+    pub const VCALL: CallFlags = CallFlags(8);
+    // An attribute or index assignment: the call evaluates to its last argument.
+    pub const ASSIGN: CallFlags = CallFlags(16);
+    // The value of a `return` in a method: the callee may take over the caller's frame.
+    pub const TAIL: CallFlags = CallFlags(32);
+
+    pub fn contains(self, other: CallFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+    pub fn with(self, other: CallFlags) -> CallFlags {
+        CallFlags(self.0 | other.0)
+    }
+    pub fn has_block(self) -> bool {
+        self.0 & (CallFlags::BLOCK_ARG.0 | CallFlags::BLOCK_LITERAL.0) != 0
+    }
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+    pub fn from_bits(bits: u8) -> CallFlags {
+        CallFlags(bits)
+    }
+}
+
+// What `Defined` checks; each pushes its description, or nil.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinedCheck {
+    InstanceVar(Sym),
+    GlobalVar(Sym),
+    Constant(Sym),
+    // Pops the scope.
+    ScopedConstant(Sym),
+    // Pops the receiver.
+    Method(Sym),
+    SelfMethod(Sym),
+    Yield,
+    Super
+}
+
+// Jump targets are instruction indices in the same chunk; `u32` operands named `chunk` or
+// `constant` index the chunk's constants pool.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Nil,
+    True,
+    False,
+    SelfValue,
+    Integer(i64),
+    // An immediate from the pool, such as a float.
+    Constant(u32),
+    // A new string with the pooled text.
+    String(u32),
+    // An integer literal too wide for a machine integer, built as a big integer when reached.
+    WideInteger(u32),
+    // A rational literal from the pooled digits.
+    Rational(u32),
+    // Replaces the top value `v` with the complex number `v * i`.
+    Imaginary,
+    // A frozen string from the pooled text, for files with `# frozen_string_literal: true`.
+    FrozenString(u32),
+    // A regexp literal from its pooled source and flags.
+    Regexp { source: u32, flags: u32 },
+    Symbol(Sym),
+    File,
+    Encoding,
+
+    Pop,
+    Dup,
+    // Copies the top `n` values.
+    DupN(u32),
+    // Drops the `n` values under the top one.
+    Slide(u32),
+    // Moves the value `n` places under the top to the top.
+    Pull(u32),
+
+    // Locals live in environment slots; `depth` counts enclosing blocks outwards.
+    GetLocal { depth: u32, slot: u32 },
+    SetLocal { depth: u32, slot: u32 },
+    GetIvar(Sym),
+    SetIvar(Sym),
+    GetGlobal(Sym),
+    SetGlobal(Sym),
+    GetConstant(Sym),
+    // Pops the scope.
+    GetScopedConstant(Sym),
+    SetConstant(Sym),
+    // Pops the scope, leaving the value.
+    SetScopedConstant(Sym),
+
+    Array(u32),
+    // Appends the top value to the array under it.
+    ArrayPush,
+    // Appends the items of the top value to the array under it, as a `*splat` argument would.
+    ArraySplat,
+    // Builds a hash from `n` key/value pairs.
+    Hash(u32),
+    // Turns a `&block` argument into a proc, or nil.
+    ToBlock,
+
+    Jump(u32),
+    // Conditional jumps pop the value they test.
+    JumpIfFalse(u32),
+    JumpIfTrue(u32),
+    JumpIfNil(u32),
+
+    Binary(OperatorSymbol),
+    Unary(OperatorSymbol),
+    Not,
+    Send { name: Sym, argc: u32, flags: CallFlags },
+    // `argc` and the flags are ignored by a bare `super`, which passes the method's arguments on.
+    Super { argc: u32, flags: CallFlags, bare: bool },
+    Yield { argc: u32, flags: CallFlags },
+    Block(u32),
+    Lambda(u32),
+
+    Return,
+    Break,
+    Next,
+    Redo,
+    Retry,
+    // Ends the chunk with the top value.
+    Leave,
+
+    // Handlers guard the code up to the matching `PopHandler`. A rescue handler catches
+    // exceptions with the exception pushed; an ensure handler catches any exit and remembers it
+    // for `EndEnsure`; a guard catches exceptions with nil pushed.
+    PushRescue(u32),
+    PushEnsure(u32),
+    PushGuard(u32),
+    // Catches `break` with its value pushed, and `next`.
+    PushLoop { break_target: u32, next_target: u32 },
+    // Runs a rescue clause with `$!` set to the exception on top; `retry` jumps back to `retry_target`.
+    PushRescueBody { retry_target: u32 },
+    PopHandler,
+    // Pops a class (or, with `splat`, a list of them) and pushes whether the exception under it
+    // is one.
+    RescueMatch { splat: bool },
+    RescueStandard,
+    // Pops the exception and raises it again.
+    Reraise,
+    // Resumes whatever exit the ensure handler caught, if any.
+    EndEnsure,
+    // Pops a pattern (or list of them) and pushes whether it matches the `case` subject under it.
+    CaseMatch { splat: bool },
+    // Pops a list of `when` conditions and pushes whether any is truthy.
+    AnyTruthy,
+    // Pushes the items of the collection on top for a `for` loop.
+    ForItems,
+    // With the items and an index on top, pushes the next item or jumps when there are none.
+    ForNext(u32),
+    Defined(DefinedCheck),
+
+    DefineMethod { chunk: u32, singleton: bool },
+    DefineClass { name: Sym, chunk: u32, scoped: bool, superclass: bool },
+    DefineModule { name: Sym, chunk: u32, scoped: bool },
+    Alias { new_name: Sym, old_name: Sym },
+    Undef(Sym),
+    EndBlock(u32),
+    // Raises a SyntaxError with the pooled message.
+    Invalid(u32)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Float(f64),
+    Str(String),
+    Chunk(Rc<Chunk>)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkKind {
+    Program,
+    Method,
+    Block,
+    Module
+}
+
+// Parameters in binding order: required, optional, rest, post, block. `slots` gives each one's
+// local slot; `entries[k]` is where to start once `k` optional arguments were supplied, after
+// the defaults of the others have been computed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ParamSpec {
+    pub required: u32,
+    pub optional: u32,
+    pub rest: bool,
+    pub post: u32,
+    pub block: bool,
+    pub slots: Vec<u32>,
+    pub entries: Vec<u32>
+}
+
+impl ParamSpec {
+    // Required parameter count, or `-(required + 1)` when more arguments are accepted.
+    pub fn arity(&self) -> i64 {
+        let required = i64::from(self.required + self.post);
+        if self.rest || self.optional > 0 { -required - 1 } else { required }
+    }
+    pub fn body_start(&self) -> u32 {
+        self.entries.last().copied().unwrap_or(0)
+    }
+}
+
+// One compiled unit: a program, method body, block or class body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub kind: ChunkKind,
+    pub name: Sym,
+    pub code: Vec<Op>,
+    // The source line and column each instruction came from.
+    pub positions: Vec<(u32, u32)>,
+    pub constants: Vec<Constant>,
+    // Local variable names by slot.
+    pub locals: Vec<Sym>,
+    pub params: ParamSpec,
+    pub line: u32,
+    pub col: u32,
+    // Method caches for the chunk's `Send` and `Binary` instructions, filled as it runs.
+    pub sites: CallSites
+}
+
+impl Chunk {
+    pub fn chunk(&self, index: u32) -> &Rc<Chunk> {
+        match &self.constants[index as usize] {
+            Constant::Chunk(chunk) => chunk,
+            other => panic!("constant {} is not a chunk: {:?}", index, other)
+        }
+    }
+    pub fn text(&self, index: u32) -> &str {
+        match &self.constants[index as usize] {
+            Constant::Str(s) => s,
+            other => panic!("constant {} is not a string: {:?}", index, other)
+        }
+    }
+}
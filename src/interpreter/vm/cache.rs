@@ -0,0 +1,489 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use crate::interpreter::parser::lexicon::OperatorSymbol;
+use crate::interpreter::runtime::symbols::{Sym, SymbolTable};
+use super::bytecode::{CallFlags, Chunk, ChunkKind, Constant, DefinedCheck, Op, ParamSpec};
+use super::inline_cache::CallSites;
+
+// A `.jbc` file holds one compiled source file:
+//
+//     "JBC\0"  format version (u16)  source hash (u64)
+//     names: count, then each as a length-prefixed UTF-8 string
+//     the program chunk, which contains the rest
+//
+// Integers are little-endian; symbols are written as indices into the names. Bump the version
+// whenever the instruction set or this layout changes, so stale caches are recompiled.
+pub const FORMAT_VERSION: u16 = 4;
+const MAGIC: &[u8; 4] = b"JBC\0";
+
+// FNV-1a, which unlike the standard hasher is stable across builds.
+pub fn source_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3))
+}
+
+pub fn encode(chunk: &Chunk, hash: u64, symbols: &SymbolTable) -> Vec<u8> {
+    let mut body = Writer { out: Vec::new(), names: Vec::new(), indices: HashMap::new() };
+    body.chunk(chunk);
+    let mut out = Vec::with_capacity(body.out.len() + 64);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&hash.to_le_bytes());
+    out.extend_from_slice(&(body.names.len() as u32).to_le_bytes());
+    for sym in body.names.iter() {
+        let name = symbols.name(*sym).as_bytes();
+        out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        out.extend_from_slice(name);
+    }
+    out.extend_from_slice(&body.out);
+    out
+}
+
+// Reads back a program chunk, interning its names. Returns `None` if the data is not a cache of
+// this version for a source with `hash`, or is damaged.
+pub fn decode(data: &[u8], hash: u64, symbols: &mut SymbolTable) -> Option<Rc<Chunk>> {
+    let mut reader = Reader { data, at: 0, names: Vec::new() };
+    if reader.bytes(4)? != MAGIC || reader.u16()? != FORMAT_VERSION || reader.u64()? != hash {
+        return None;
+    }
+    let count = reader.u32()?;
+    for _ in 0..count {
+        let len = reader.u32()? as usize;
+        let name = std::str::from_utf8(reader.bytes(len)?).ok()?;
+        reader.names.push(symbols.intern(name));
+    }
+    let chunk = reader.chunk()?;
+    if reader.at != data.len() {
+        return None;
+    }
+    Some(Rc::new(chunk))
+}
+
+struct Writer {
+    out: Vec<u8>,
+    names: Vec<Sym>,
+    indices: HashMap<Sym, u32>
+}
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.out.push(v);
+    }
+    fn u32(&mut self, v: u32) {
+        self.out.extend_from_slice(&v.to_le_bytes());
+    }
+    fn i64(&mut self, v: i64) {
+        self.out.extend_from_slice(&v.to_le_bytes());
+    }
+    fn bool(&mut self, v: bool) {
+        self.u8(v as u8);
+    }
+    fn str(&mut self, s: &str) {
+        self.u32(s.len() as u32);
+        self.out.extend_from_slice(s.as_bytes());
+    }
+    fn sym(&mut self, sym: Sym) {
+        let next = self.names.len() as u32;
+        let index = *self.indices.entry(sym).or_insert(next);
+        if index == next {
+            self.names.push(sym);
+        }
+        self.u32(index);
+    }
+    fn u32s(&mut self, values: &[u32]) {
+        self.u32(values.len() as u32);
+        for v in values.iter() {
+            self.u32(*v);
+        }
+    }
+
+    fn chunk(&mut self, chunk: &Chunk) {
+        self.u8(match chunk.kind {
+            ChunkKind::Program => 0,
+            ChunkKind::Method  => 1,
+            ChunkKind::Block   => 2,
+            ChunkKind::Module  => 3
+        });
+        self.sym(chunk.name);
+        self.u32(chunk.line);
+        self.u32(chunk.col);
+        let params = &chunk.params;
+        self.u32(params.required);
+        self.u32(params.optional);
+        self.bool(params.rest);
+        self.u32(params.post);
+        self.bool(params.block);
+        self.u32s(&params.slots);
+        self.u32s(&params.entries);
+        self.u32(chunk.locals.len() as u32);
+        for local in chunk.locals.iter() {
+            self.sym(*local);
+        }
+        self.u32(chunk.constants.len() as u32);
+        for constant in chunk.constants.iter() {
+            match constant {
+                Constant::Float(f) => {
+                    self.u8(0);
+                    self.out.extend_from_slice(&f.to_bits().to_le_bytes());
+                },
+                Constant::Str(s) => {
+                    self.u8(1);
+                    self.str(s);
+                },
+                Constant::Chunk(c) => {
+                    self.u8(2);
+                    self.chunk(c);
+                }
+            }
+        }
+        self.u32(chunk.code.len() as u32);
+        for (op, (line, col)) in chunk.code.iter().zip(chunk.positions.iter()) {
+            self.op(op);
+            self.u32(*line);
+            self.u32(*col);
+        }
+    }
+
+    fn op(&mut self, op: &Op) {
+        match *op {
+            Op::Nil                        => self.u8(0),
+            Op::True                       => self.u8(1),
+            Op::False                      => self.u8(2),
+            Op::SelfValue                  => self.u8(3),
+            Op::Integer(n)                 => { self.u8(4); self.i64(n) },
+            Op::Constant(i)                => { self.u8(5); self.u32(i) },
+            Op::String(i)                  => { self.u8(6); self.u32(i) },
+            Op::WideInteger(i)             => { self.u8(7); self.u32(i) },
+            Op::Symbol(s)                  => { self.u8(8); self.sym(s) },
+            Op::File                       => self.u8(9),
+            Op::Encoding                   => self.u8(10),
+            Op::Pop                        => self.u8(11),
+            Op::Dup                        => self.u8(12),
+            Op::DupN(n)                    => { self.u8(13); self.u32(n) },
+            Op::Slide(n)                   => { self.u8(14); self.u32(n) },
+            Op::Pull(n)                    => { self.u8(15); self.u32(n) },
+            Op::GetLocal { depth, slot }   => { self.u8(16); self.u32(depth); self.u32(slot) },
+            Op::SetLocal { depth, slot }   => { self.u8(17); self.u32(depth); self.u32(slot) },
+            Op::GetIvar(s)                 => { self.u8(18); self.sym(s) },
+            Op::SetIvar(s)                 => { self.u8(19); self.sym(s) },
+            Op::GetGlobal(s)               => { self.u8(20); self.sym(s) },
+            Op::SetGlobal(s)               => { self.u8(21); self.sym(s) },
+            Op::GetConstant(s)             => { self.u8(22); self.sym(s) },
+            Op::GetScopedConstant(s)       => { self.u8(23); self.sym(s) },
+            Op::SetConstant(s)             => { self.u8(24); self.sym(s) },
+            Op::SetScopedConstant(s)       => { self.u8(25); self.sym(s) },
+            Op::Array(n)                   => { self.u8(26); self.u32(n) },
+            Op::ArrayPush                  => self.u8(27),
+            Op::ArraySplat                 => self.u8(28),
+            Op::Hash(n)                    => { self.u8(29); self.u32(n) },
+            Op::ToBlock                    => self.u8(30),
+            Op::Jump(t)                    => { self.u8(31); self.u32(t) },
+            Op::JumpIfFalse(t)             => { self.u8(32); self.u32(t) },
+            Op::JumpIfTrue(t)              => { self.u8(33); self.u32(t) },
+            Op::JumpIfNil(t)               => { self.u8(34); self.u32(t) },
+            Op::Binary(o)                  => { self.u8(35); self.str(o.to_str()) },
+            Op::Unary(o)                   => { self.u8(36); self.str(o.to_str()) },
+            Op::Not                        => self.u8(37),
+            Op::Send { name, argc, flags } => { self.u8(38); self.sym(name); self.u32(argc); self.u8(flags.bits()) },
+            Op::Super { argc, flags, bare } => { self.u8(39); self.u32(argc); self.u8(flags.bits()); self.bool(bare) },
+            Op::Yield { argc, flags }      => { self.u8(40); self.u32(argc); self.u8(flags.bits()) },
+            Op::Block(i)                   => { self.u8(41); self.u32(i) },
+            Op::Lambda(i)                  => { self.u8(42); self.u32(i) },
+            Op::Return                     => self.u8(43),
+            Op::Break                      => self.u8(44),
+            Op::Next                       => self.u8(45),
+            Op::Redo                       => self.u8(46),
+            Op::Retry                      => self.u8(47),
+            Op::Leave                      => self.u8(48),
+            Op::PushRescue(t)              => { self.u8(49); self.u32(t) },
+            Op::PushEnsure(t)              => { self.u8(50); self.u32(t) },
+            Op::PushGuard(t)               => { self.u8(51); self.u32(t) },
+            Op::PushLoop { break_target, next_target } => { self.u8(52); self.u32(break_target); self.u32(next_target) },
+            Op::PushRescueBody { retry_target } => { self.u8(53); self.u32(retry_target) },
+            Op::PopHandler                 => self.u8(54),
+            Op::RescueMatch { splat }      => { self.u8(55); self.bool(splat) },
+            Op::RescueStandard             => self.u8(56),
+            Op::Reraise                    => self.u8(57),
+            Op::EndEnsure                  => self.u8(58),
+            Op::CaseMatch { splat }        => { self.u8(59); self.bool(splat) },
+            Op::AnyTruthy                  => self.u8(60),
+            Op::ForItems                   => self.u8(61),
+            Op::ForNext(t)                 => { self.u8(62); self.u32(t) },
+            Op::Defined(check)             => { self.u8(63); self.defined_check(check) },
+            Op::DefineMethod { chunk, singleton } => { self.u8(64); self.u32(chunk); self.bool(singleton) },
+            Op::DefineClass { name, chunk, scoped, superclass } => {
+                self.u8(65);
+                self.sym(name);
+                self.u32(chunk);
+                self.bool(scoped);
+                self.bool(superclass);
+            },
+            Op::DefineModule { name, chunk, scoped } => { self.u8(66); self.sym(name); self.u32(chunk); self.bool(scoped) },
+            Op::Alias { new_name, old_name } => { self.u8(67); self.sym(new_name); self.sym(old_name) },
+            Op::Undef(s)                   => { self.u8(68); self.sym(s) },
+            Op::EndBlock(i)                => { self.u8(69); self.u32(i) },
+            Op::Invalid(i)                 => { self.u8(70); self.u32(i) },
+            Op::Rational(i)                => { self.u8(71); self.u32(i) },
+            Op::Imaginary                  => self.u8(72),
+            Op::FrozenString(i)            => { self.u8(73); self.u32(i) },
+            Op::Regexp { source, flags }   => { self.u8(74); self.u32(source); self.u32(flags) }
+        }
+    }
+
+    fn defined_check(&mut self, check: DefinedCheck) {
+        match check {
+            DefinedCheck::InstanceVar(s)    => { self.u8(0); self.sym(s) },
+            DefinedCheck::GlobalVar(s)      => { self.u8(1); self.sym(s) },
+            DefinedCheck::Constant(s)       => { self.u8(2); self.sym(s) },
+            DefinedCheck::ScopedConstant(s) => { self.u8(3); self.sym(s) },
+            DefinedCheck::Method(s)         => { self.u8(4); self.sym(s) },
+            DefinedCheck::SelfMethod(s)     => { self.u8(5); self.sym(s) },
+            DefinedCheck::Yield             => self.u8(6),
+            DefinedCheck::Super             => self.u8(7)
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    at: usize,
+    names: Vec<Sym>
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.at..self.at.checked_add(len)?)?;
+        self.at += len;
+        Some(bytes)
+    }
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|b| u16::from_le_bytes(<[u8; 2]>::try_from(b).unwrap()))
+    }
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4).map(|b| u32::from_le_bytes(<[u8; 4]>::try_from(b).unwrap()))
+    }
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes(8).map(|b| u64::from_le_bytes(<[u8; 8]>::try_from(b).unwrap()))
+    }
+    fn bool(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None
+        }
+    }
+    fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).ok()
+    }
+    fn sym(&mut self) -> Option<Sym> {
+        let index = self.u32()? as usize;
+        self.names.get(index).copied()
+    }
+    fn operator(&mut self) -> Option<OperatorSymbol> {
+        match OperatorSymbol::from_string(&self.string()?) {
+            OperatorSymbol::ILLEGAL => None,
+            op => Some(op)
+        }
+    }
+    fn flags(&mut self) -> Option<CallFlags> {
+        self.u8().map(CallFlags::from_bits)
+    }
+    // A count, checked against what is left so damaged data cannot ask for a huge allocation.
+    fn count(&mut self) -> Option<usize> {
+        let count = self.u32()? as usize;
+        if count > self.data.len() - self.at { None } else { Some(count) }
+    }
+    fn u32s(&mut self) -> Option<Vec<u32>> {
+        let count = self.count()?;
+        (0..count).map(|_| self.u32()).collect()
+    }
+
+    fn chunk(&mut self) -> Option<Chunk> {
+        let kind = match self.u8()? {
+            0 => ChunkKind::Program,
+            1 => ChunkKind::Method,
+            2 => ChunkKind::Block,
+            3 => ChunkKind::Module,
+            _ => return None
+        };
+        let name = self.sym()?;
+        let line = self.u32()?;
+        let col = self.u32()?;
+        let params = ParamSpec {
+            required: self.u32()?,
+            optional: self.u32()?,
+            rest: self.bool()?,
+            post: self.u32()?,
+            block: self.bool()?,
+            slots: self.u32s()?,
+            entries: self.u32s()?
+        };
+        let count = self.count()?;
+        let locals = (0..count).map(|_| self.sym()).collect::<Option<Vec<Sym>>>()?;
+        let count = self.count()?;
+        let mut constants = Vec::with_capacity(count);
+        for _ in 0..count {
+            constants.push(match self.u8()? {
+                0 => Constant::Float(f64::from_bits(self.u64()?)),
+                1 => Constant::Str(self.string()?),
+                2 => Constant::Chunk(Rc::new(self.chunk()?)),
+                _ => return None
+            });
+        }
+        let count = self.count()?;
+        let mut code = Vec::with_capacity(count);
+        let mut positions = Vec::with_capacity(count);
+        for _ in 0..count {
+            code.push(self.op()?);
+            positions.push((self.u32()?, self.u32()?));
+        }
+        Some(Chunk { kind, name, code, positions, constants, locals, params, line, col, sites: CallSites::default() })
+    }
+
+    fn op(&mut self) -> Option<Op> {
+        Some(match self.u8()? {
+            0  => Op::Nil,
+            1  => Op::True,
+            2  => Op::False,
+            3  => Op::SelfValue,
+            4  => Op::Integer(self.u64()? as i64),
+            5  => Op::Constant(self.u32()?),
+            6  => Op::String(self.u32()?),
+            7  => Op::WideInteger(self.u32()?),
+            8  => Op::Symbol(self.sym()?),
+            9  => Op::File,
+            10 => Op::Encoding,
+            11 => Op::Pop,
+            12 => Op::Dup,
+            13 => Op::DupN(self.u32()?),
+            14 => Op::Slide(self.u32()?),
+            15 => Op::Pull(self.u32()?),
+            16 => Op::GetLocal { depth: self.u32()?, slot: self.u32()? },
+            17 => Op::SetLocal { depth: self.u32()?, slot: self.u32()? },
+            18 => Op::GetIvar(self.sym()?),
+            19 => Op::SetIvar(self.sym()?),
+            20 => Op::GetGlobal(self.sym()?),
+            21 => Op::SetGlobal(self.sym()?),
+            22 => Op::GetConstant(self.sym()?),
+            23 => Op::GetScopedConstant(self.sym()?),
+            24 => Op::SetConstant(self.sym()?),
+            25 => Op::SetScopedConstant(self.sym()?),
+            26 => Op::Array(self.u32()?),
+            27 => Op::ArrayPush,
+            28 => Op::ArraySplat,
+            29 => Op::Hash(self.u32()?),
+            30 => Op::ToBlock,
+            31 => Op::Jump(self.u32()?),
+            32 => Op::JumpIfFalse(self.u32()?),
+            33 => Op::JumpIfTrue(self.u32()?),
+            34 => Op::JumpIfNil(self.u32()?),
+            35 => Op::Binary(self.operator()?),
+            36 => Op::Unary(self.operator()?),
+            37 => Op::Not,
+            38 => Op::Send { name: self.sym()?, argc: self.u32()?, flags: self.flags()? },
+            39 => Op::Super { argc: self.u32()?, flags: self.flags()?, bare: self.bool()? },
+            40 => Op::Yield { argc: self.u32()?, flags: self.flags()? },
+            41 => Op::Block(self.u32()?),
+            42 => Op::Lambda(self.u32()?),
+            43 => Op::Return,
+            44 => Op::Break,
+            45 => Op::Next,
+            46 => Op::Redo,
+            47 => Op::Retry,
+            48 => Op::Leave,
+            49 => Op::PushRescue(self.u32()?),
+            50 => Op::PushEnsure(self.u32()?),
+            51 => Op::PushGuard(self.u32()?),
+            52 => Op::PushLoop { break_target: self.u32()?, next_target: self.u32()? },
+            53 => Op::PushRescueBody { retry_target: self.u32()? },
+            54 => Op::PopHandler,
+            55 => Op::RescueMatch { splat: self.bool()? },
+            56 => Op::RescueStandard,
+            57 => Op::Reraise,
+            58 => Op::EndEnsure,
+            59 => Op::CaseMatch { splat: self.bool()? },
+            60 => Op::AnyTruthy,
+            61 => Op::ForItems,
+            62 => Op::ForNext(self.u32()?),
+            63 => Op::Defined(self.defined_check()?),
+            64 => Op::DefineMethod { chunk: self.u32()?, singleton: self.bool()? },
+            65 => Op::DefineClass { name: self.sym()?, chunk: self.u32()?, scoped: self.bool()?, superclass: self.bool()? },
+            66 => Op::DefineModule { name: self.sym()?, chunk: self.u32()?, scoped: self.bool()? },
+            67 => Op::Alias { new_name: self.sym()?, old_name: self.sym()? },
+            68 => Op::Undef(self.sym()?),
+            69 => Op::EndBlock(self.u32()?),
+            70 => Op::Invalid(self.u32()?),
+            71 => Op::Rational(self.u32()?),
+            72 => Op::Imaginary,
+            73 => Op::FrozenString(self.u32()?),
+            74 => Op::Regexp { source: self.u32()?, flags: self.u32()? },
+            _  => return None
+        })
+    }
+
+    fn defined_check(&mut self) -> Option<DefinedCheck> {
+        Some(match self.u8()? {
+            0 => DefinedCheck::InstanceVar(self.sym()?),
+            1 => DefinedCheck::GlobalVar(self.sym()?),
+            2 => DefinedCheck::Constant(self.sym()?),
+            3 => DefinedCheck::ScopedConstant(self.sym()?),
+            4 => DefinedCheck::Method(self.sym()?),
+            5 => DefinedCheck::SelfMethod(self.sym()?),
+            6 => DefinedCheck::Yield,
+            7 => DefinedCheck::Super,
+            _ => return None
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::parser::input_stream::InputStream;
+    use crate::interpreter::parser::parser::Parser;
+    use crate::interpreter::parser::token_stream::TokenStream;
+    use crate::interpreter::source_map::SourceMap;
+    use crate::interpreter::vm::compiler::Compiler;
+
+    fn compile(source: &str, symbols: &mut SymbolTable) -> Rc<Chunk> {
+        let mut sources = SourceMap::create();
+        let file = sources.add_string("test", source);
+        let mut is = InputStream::for_file(file, source);
+        let mut ts = TokenStream::create(&mut is);
+        let output = Parser::create(&mut ts).parse();
+        Compiler::create(symbols).compile_program(&output.program, "<main>")
+    }
+
+    #[test]
+    fn round_trips_through_a_fresh_symbol_table() {
+        let source = "class A < B\n  def m(a, b = 2.5, *r, &blk)\n    [a, \"s\", :sym, r.map { |x| x ** 2 }, defined?(@x)]\n  end\nend\nbegin\n  A.new.m(1)\nrescue => e\n  retry\nensure\n  $g = 1\nend";
+        let mut symbols = SymbolTable::create();
+        let chunk = compile(source, &mut symbols);
+        let hash = source_hash(source);
+        let data = encode(&chunk, hash, &symbols);
+
+        let mut fresh = SymbolTable::create();
+        fresh.intern("shifts every index");
+        let decoded = decode(&data, hash, &mut fresh).expect("cache should decode");
+        let again = encode(&decoded, hash, &fresh);
+        assert_eq!(data, again);
+        assert_eq!(decoded.code.len(), chunk.code.len());
+    }
+
+    #[test]
+    fn rejects_stale_or_damaged_caches() {
+        let mut symbols = SymbolTable::create();
+        let chunk = compile("x = 1\nx + 2", &mut symbols);
+        let data = encode(&chunk, 7, &symbols);
+        assert!(decode(&data, 8, &mut symbols).is_none());
+        assert!(decode(&data[..data.len() - 1], 7, &mut symbols).is_none());
+        let mut old = data.clone();
+        old[4] = old[4].wrapping_add(1);
+        assert!(decode(&old, 7, &mut symbols).is_none());
+    }
+}
@@ -0,0 +1,934 @@
+use std::rc::Rc;
+
+use crate::interpreter::parser::ast::{Block, Body, Expr, ExprKind, Params, Program, RescueClause};
+use crate::interpreter::parser::lexicon::OperatorSymbol;
+use crate::interpreter::runtime::symbols::{Sym, SymbolTable};
+use super::bytecode::{CallFlags, Chunk, ChunkKind, Constant, DefinedCheck, Op, ParamSpec};
+use super::inline_cache::CallSites;
+
+// The chunk being built for one program, method, block or class body.
+struct Scope {
+    kind: ChunkKind,
+    name: Sym,
+    code: Vec<Op>,
+    positions: Vec<(u32, u32)>,
+    constants: Vec<Constant>,
+    locals: Vec<Sym>,
+    params: ParamSpec,
+    line: u32,
+    col: u32
+}
+
+// Compiles the AST to chunks of bytecode. Local variables are resolved to slots here: blocks see
+// the locals of the scopes around them, while method, class and program bodies start afresh.
+pub struct Compiler<'a> {
+    symbols: &'a mut SymbolTable,
+    scopes: Vec<Scope>
+}
+
+impl<'a> Compiler<'a> {
+    pub fn create(symbols: &'a mut SymbolTable) -> Compiler<'a> {
+        Compiler { symbols, scopes: Vec::new() }
+    }
+
+    pub fn compile_program(&mut self, program: &Program, label: &str) -> Rc<Chunk> {
+        let name = self.symbols.intern(label);
+        self.enter(ChunkKind::Program, name, 1, 1);
+        // `BEGIN` blocks run ahead of everything else in the file.
+        for statement in program.statements.iter() {
+            if let ExprKind::BeginBlock(body) = &statement.kind {
+                self.body(body, statement);
+                self.emit(Op::Pop, statement);
+            }
+        }
+        let end = Expr::create(ExprKind::Nil, 1, 1);
+        self.body(&program.statements, program.statements.last().unwrap_or(&end));
+        self.emit(Op::Leave, program.statements.last().unwrap_or(&end));
+        Rc::new(self.leave())
+    }
+
+    fn compile_method(&mut self, name: &str, params: &Params, body: &Expr) -> Rc<Chunk> {
+        let name = self.symbols.intern(name);
+        self.enter(ChunkKind::Method, name, body.line, body.col);
+        self.params(params);
+        self.expr(body);
+        self.emit(Op::Leave, body);
+        Rc::new(self.leave())
+    }
+
+    fn compile_block(&mut self, block: &Block) -> Rc<Chunk> {
+        let name = self.symbols.intern("block");
+        self.enter(ChunkKind::Block, name, block.line, block.col);
+        let at = Expr::create(ExprKind::Nil, block.line, block.col);
+        self.params(&block.params);
+        self.body(&block.body, &at);
+        self.emit(Op::Leave, &at);
+        Rc::new(self.leave())
+    }
+
+    fn compile_module_body(&mut self, name: &str, body: &Body, at: &Expr) -> Rc<Chunk> {
+        let name = self.symbols.intern(name);
+        self.enter(ChunkKind::Module, name, at.line, at.col);
+        self.body(body, at);
+        self.emit(Op::Leave, at);
+        Rc::new(self.leave())
+    }
+
+    fn enter(&mut self, kind: ChunkKind, name: Sym, line: u32, col: u32) {
+        self.scopes.push(Scope {
+            kind,
+            name,
+            code: Vec::new(),
+            positions: Vec::new(),
+            constants: Vec::new(),
+            locals: Vec::new(),
+            params: ParamSpec::default(),
+            line,
+            col
+        });
+    }
+
+    fn leave(&mut self) -> Chunk {
+        let scope = self.scopes.pop().expect("no scope to leave");
+        Chunk {
+            kind: scope.kind,
+            name: scope.name,
+            code: scope.code,
+            positions: scope.positions,
+            constants: scope.constants,
+            locals: scope.locals,
+            params: scope.params,
+            line: scope.line,
+            col: scope.col,
+            sites: CallSites::default()
+        }
+    }
+
+    // Declares the parameters, in binding order, and compiles the optional parameters' defaults.
+    fn params(&mut self, params: &Params) {
+        let mut spec = ParamSpec {
+            required: params.required.len() as u32,
+            optional: params.optional.len() as u32,
+            rest: params.rest.is_some(),
+            post: params.post.len() as u32,
+            block: params.block.is_some(),
+            slots: Vec::new(),
+            entries: Vec::new()
+        };
+        for name in params.names() {
+            spec.slots.push(self.declare(name));
+        }
+        let first_optional = params.required.len();
+        for (i, (_, default)) in params.optional.iter().enumerate() {
+            spec.entries.push(self.here());
+            self.expr(default);
+            let slot = spec.slots[first_optional + i];
+            self.emit(Op::SetLocal { depth: 0, slot }, default);
+            self.emit(Op::Pop, default);
+        }
+        spec.entries.push(self.here());
+        self.scope().params = spec;
+    }
+
+    // -- emitting --
+
+    fn scope(&mut self) -> &mut Scope {
+        self.scopes.last_mut().expect("no scope")
+    }
+
+    fn emit(&mut self, op: Op, at: &Expr) -> usize {
+        let scope = self.scope();
+        scope.code.push(op);
+        scope.positions.push((at.line, at.col));
+        scope.code.len() - 1
+    }
+
+    fn here(&mut self) -> u32 {
+        self.scope().code.len() as u32
+    }
+
+    // Lets a method's `return f(...)` hand its frame over to the call.
+    fn mark_tail_call(&mut self) {
+        let scope = self.scope();
+        if scope.kind != ChunkKind::Method {
+            return;
+        }
+        if let Some(Op::Send { flags, .. }) = scope.code.last_mut() {
+            if !flags.contains(CallFlags::BLOCK_LITERAL) && !flags.contains(CallFlags::ASSIGN) {
+                *flags = flags.with(CallFlags::TAIL);
+            }
+        }
+    }
+
+    // Points the jump at `at` to the next instruction.
+    fn patch(&mut self, at: usize) {
+        let target = self.here();
+        let op = &mut self.scope().code[at];
+        *op = match *op {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+            Op::JumpIfTrue(_) => Op::JumpIfTrue(target),
+            Op::JumpIfNil(_) => Op::JumpIfNil(target),
+            Op::PushRescue(_) => Op::PushRescue(target),
+            Op::PushEnsure(_) => Op::PushEnsure(target),
+            Op::PushGuard(_) => Op::PushGuard(target),
+            Op::ForNext(_) => Op::ForNext(target),
+            Op::PushLoop { next_target, .. } => Op::PushLoop { break_target: target, next_target },
+            other => panic!("cannot patch {:?}", other)
+        };
+    }
+
+    fn constant(&mut self, constant: Constant) -> u32 {
+        let constants = &mut self.scope().constants;
+        if let Some(i) = constants.iter().position(|c| !matches!(c, Constant::Chunk(_)) && *c == constant) {
+            return i as u32;
+        }
+        constants.push(constant);
+        constants.len() as u32 - 1
+    }
+
+    fn string(&mut self, s: &str, at: &Expr) {
+        let index = self.constant(Constant::Str(s.to_string()));
+        self.emit(Op::String(index), at);
+    }
+
+    fn sym(&mut self, name: &str) -> Sym {
+        self.symbols.intern(name)
+    }
+
+    // -- locals --
+
+    fn declare(&mut self, name: &str) -> u32 {
+        let sym = self.sym(name);
+        let locals = &mut self.scope().locals;
+        match locals.iter().position(|l| *l == sym) {
+            Some(slot) => slot as u32,
+            None => {
+                locals.push(sym);
+                locals.len() as u32 - 1
+            }
+        }
+    }
+
+    fn resolve(&mut self, name: &str) -> Option<(u32, u32)> {
+        let sym = self.sym(name);
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if let Some(slot) = scope.locals.iter().position(|l| *l == sym) {
+                return Some((depth as u32, slot as u32));
+            }
+            if scope.kind != ChunkKind::Block {
+                break;
+            }
+        }
+        None
+    }
+
+    // A local assigned here for the first time belongs to the innermost scope.
+    fn local(&mut self, name: &str) -> (u32, u32) {
+        match self.resolve(name) {
+            Some(found) => found,
+            None => (0, self.declare(name))
+        }
+    }
+
+    // -- expressions --
+
+    fn body(&mut self, body: &[Expr], at: &Expr) {
+        if body.is_empty() {
+            self.emit(Op::Nil, at);
+            return;
+        }
+        for (i, e) in body.iter().enumerate() {
+            if i > 0 {
+                self.emit(Op::Pop, e);
+            }
+            self.expr(e);
+        }
+    }
+
+    fn optional(&mut self, e: Option<&Expr>, at: &Expr) {
+        match e {
+            Some(e) => self.expr(e),
+            None => {
+                self.emit(Op::Nil, at);
+            }
+        }
+    }
+
+    fn expr(&mut self, e: &Expr) {
+        match &e.kind {
+            ExprKind::Nil | ExprKind::BeginBlock(_) => {
+                self.emit(Op::Nil, e);
+            },
+            ExprKind::True => {
+                self.emit(Op::True, e);
+            },
+            ExprKind::False => {
+                self.emit(Op::False, e);
+            },
+            ExprKind::SelfRef => {
+                self.emit(Op::SelfValue, e);
+            },
+            ExprKind::File => {
+                self.emit(Op::File, e);
+            },
+            ExprKind::Line => {
+                self.emit(Op::Integer(i64::from(e.line)), e);
+            },
+            ExprKind::Encoding => {
+                self.emit(Op::Encoding, e);
+            },
+            ExprKind::Integer(digits) => match digits.parse::<i64>() {
+                Ok(n) => {
+                    self.emit(Op::Integer(n), e);
+                },
+                Err(_) => {
+                    let index = self.constant(Constant::Str(digits.clone()));
+                    self.emit(Op::WideInteger(index), e);
+                }
+            },
+            ExprKind::Rational(digits) => {
+                let index = self.constant(Constant::Str(digits.clone()));
+                self.emit(Op::Rational(index), e);
+            },
+            ExprKind::Imaginary(scaled) => {
+                self.expr(scaled);
+                self.emit(Op::Imaginary, e);
+            },
+            ExprKind::Float(digits) => {
+                let index = self.constant(Constant::Float(digits.parse::<f64>().unwrap_or(f64::NAN)));
+                self.emit(Op::Constant(index), e);
+            },
+            ExprKind::Str(s) => self.string(s, e),
+            ExprKind::FrozenStr(s) => {
+                let index = self.constant(Constant::Str(s.clone()));
+                self.emit(Op::FrozenString(index), e);
+            },
+            ExprKind::Regexp { source, flags } => {
+                let source = self.constant(Constant::Str(source.clone()));
+                let flags = self.constant(Constant::Str(flags.clone()));
+                self.emit(Op::Regexp { source, flags }, e);
+            },
+            ExprKind::Symbol(s) => {
+                let sym = self.sym(s);
+                self.emit(Op::Symbol(sym), e);
+            },
+            ExprKind::Array(items) => {
+                let (argc, flags) = self.args(items, e);
+                let flags = self.drop_block_arg(flags, e);
+                if !flags.contains(CallFlags::SPLAT) {
+                    self.emit(Op::Array(argc), e);
+                }
+            },
+            ExprKind::Hash(pairs) => {
+                for (k, v) in pairs.iter() {
+                    self.expr(k);
+                    self.expr(v);
+                }
+                self.emit(Op::Hash(pairs.len() as u32), e);
+            },
+            ExprKind::LocalVar(name) => {
+                let (depth, slot) = self.local(name);
+                self.emit(Op::GetLocal { depth, slot }, e);
+            },
+            ExprKind::InstanceVar(name) => {
+                let sym = self.sym(name);
+                self.emit(Op::GetIvar(sym), e);
+            },
+            ExprKind::GlobalVar(name) => {
+                let sym = self.sym(name);
+                self.emit(Op::GetGlobal(sym), e);
+            },
+            ExprKind::Constant { scope: None, name } => {
+                let sym = self.sym(name);
+                self.emit(Op::GetConstant(sym), e);
+            },
+            ExprKind::Constant { scope: Some(scope), name } => {
+                self.expr(scope);
+                let sym = self.sym(name);
+                self.emit(Op::GetScopedConstant(sym), e);
+            },
+            ExprKind::Assign { target, value } => self.assign(target, value),
+            ExprKind::OpAssign { target, op, value } => self.op_assign(target, *op, value),
+            ExprKind::Binary { op, lhs, rhs, .. } => {
+                self.expr(lhs);
+                self.expr(rhs);
+                self.emit(Op::Binary(*op), e);
+            },
+            ExprKind::Unary { op, operand } => {
+                self.expr(operand);
+                self.emit(Op::Unary(*op), e);
+            },
+            ExprKind::And { lhs, rhs } | ExprKind::Or { lhs, rhs } => {
+                self.expr(lhs);
+                self.emit(Op::Dup, e);
+                let jump = match e.kind {
+                    ExprKind::And { .. } => self.emit(Op::JumpIfFalse(0), e),
+                    _ => self.emit(Op::JumpIfTrue(0), e)
+                };
+                self.emit(Op::Pop, e);
+                self.expr(rhs);
+                self.patch(jump);
+            },
+            ExprKind::Not(operand) => {
+                self.expr(operand);
+                self.emit(Op::Not, e);
+            },
+            ExprKind::Defined(operand) => {
+                let guard = self.emit(Op::PushGuard(0), e);
+                self.defined(operand);
+                self.emit(Op::PopHandler, e);
+                self.patch(guard);
+            },
+            ExprKind::Call { receiver, name, args, block, safe_nav, .. } => self.call(e, receiver.as_deref(), name, args, block.as_ref(), *safe_nav),
+            ExprKind::Splat(inner) | ExprKind::BlockPass(inner) => self.expr(inner),
+            ExprKind::Yield(args) => {
+                let (argc, flags) = self.args(args, e);
+                self.emit(Op::Yield { argc, flags }, e);
+            },
+            ExprKind::Super { args, block } => {
+                let (argc, mut flags) = match args {
+                    Some(args) => self.args(args, e),
+                    None => (0, CallFlags::NONE)
+                };
+                if let Some(block) = block {
+                    flags = self.block_literal(block, flags, e);
+                }
+                self.emit(Op::Super { argc, flags, bare: args.is_none() }, e);
+            },
+            ExprKind::Return(value) => {
+                self.optional(value.as_deref(), e);
+                self.mark_tail_call();
+                self.emit(Op::Return, e);
+            },
+            ExprKind::Break(value) => {
+                self.optional(value.as_deref(), e);
+                self.emit(Op::Break, e);
+            },
+            ExprKind::Next(value) => {
+                self.optional(value.as_deref(), e);
+                self.emit(Op::Next, e);
+            },
+            ExprKind::Redo => {
+                self.emit(Op::Redo, e);
+            },
+            ExprKind::Retry => {
+                self.emit(Op::Retry, e);
+            },
+            ExprKind::If { cond, then_body, else_body } => {
+                self.expr(cond);
+                let to_else = self.emit(Op::JumpIfFalse(0), e);
+                self.body(then_body, e);
+                let to_end = self.emit(Op::Jump(0), e);
+                self.patch(to_else);
+                match else_body {
+                    Some(body) => self.body(body, e),
+                    None => {
+                        self.emit(Op::Nil, e);
+                    }
+                }
+                self.patch(to_end);
+            },
+            ExprKind::While { cond, body, until } => self.while_loop(e, cond, body, *until),
+            ExprKind::For { var, iter, body } => self.for_loop(e, var, iter, body),
+            ExprKind::Case { subject, whens, else_body } => self.case(e, subject.as_deref(), whens, else_body.as_ref()),
+            ExprKind::Begin { body, rescues, else_body, ensure_body } => self.begin(e, body, rescues, else_body.as_ref(), ensure_body.as_ref()),
+            ExprKind::Sequence(body) => self.body(body, e),
+            ExprKind::Def { singleton, name, params, body } => {
+                let chunk = self.compile_method(name, params, body);
+                let chunk = self.constant(Constant::Chunk(chunk));
+                if let Some(on) = singleton {
+                    self.expr(on);
+                }
+                self.emit(Op::DefineMethod { chunk, singleton: singleton.is_some() }, e);
+            },
+            ExprKind::Class { path, superclass, body } => {
+                let (name, scoped) = match self.definition_path(path) {
+                    Some(found) => found,
+                    None => return
+                };
+                if let Some(superclass) = superclass {
+                    self.expr(superclass);
+                }
+                let label = format!("<class:{}>", self.symbols.name(name));
+                let chunk = self.compile_module_body(&label, body, e);
+                let chunk = self.constant(Constant::Chunk(chunk));
+                self.emit(Op::DefineClass { name, chunk, scoped, superclass: superclass.is_some() }, e);
+            },
+            ExprKind::Module { path, body } => {
+                let (name, scoped) = match self.definition_path(path) {
+                    Some(found) => found,
+                    None => return
+                };
+                let label = format!("<module:{}>", self.symbols.name(name));
+                let chunk = self.compile_module_body(&label, body, e);
+                let chunk = self.constant(Constant::Chunk(chunk));
+                self.emit(Op::DefineModule { name, chunk, scoped }, e);
+            },
+            ExprKind::Lambda(block) => {
+                let chunk = self.compile_block(block);
+                let chunk = self.constant(Constant::Chunk(chunk));
+                self.emit(Op::Lambda(chunk), e);
+            },
+            ExprKind::EndBlock(block) => {
+                let chunk = self.compile_block(block);
+                let chunk = self.constant(Constant::Chunk(chunk));
+                self.emit(Op::EndBlock(chunk), e);
+                self.emit(Op::Nil, e);
+            },
+            ExprKind::Alias { new_name, old_name } => {
+                let (new_name, old_name) = (self.sym(new_name), self.sym(old_name));
+                self.emit(Op::Alias { new_name, old_name }, e);
+                self.emit(Op::Nil, e);
+            },
+            ExprKind::Undef(names) => {
+                for name in names.iter() {
+                    let sym = self.sym(name);
+                    self.emit(Op::Undef(sym), e);
+                }
+                self.emit(Op::Nil, e);
+            },
+            ExprKind::Error => self.invalid("invalid syntax", e)
+        }
+    }
+
+    fn invalid(&mut self, message: &str, at: &Expr) {
+        let index = self.constant(Constant::Str(message.to_string()));
+        self.emit(Op::Invalid(index), at);
+    }
+
+    // Compiles arguments onto the stack and says how the call should read them. With a splat the
+    // arguments are gathered into a single array.
+    fn args(&mut self, args: &[Expr], at: &Expr) -> (u32, CallFlags) {
+        let block_pass = args.iter().find_map(|a| match &a.kind {
+            ExprKind::BlockPass(inner) => Some(inner),
+            _ => None
+        });
+        let plain: Vec<&Expr> = args.iter().filter(|a| !matches!(a.kind, ExprKind::BlockPass(_))).collect();
+        let (argc, mut flags) = if plain.iter().any(|a| matches!(a.kind, ExprKind::Splat(_))) {
+            self.emit(Op::Array(0), at);
+            for arg in plain.iter() {
+                match &arg.kind {
+                    ExprKind::Splat(inner) => {
+                        self.expr(inner);
+                        self.emit(Op::ArraySplat, arg);
+                    },
+                    _ => {
+                        self.expr(arg);
+                        self.emit(Op::ArrayPush, arg);
+                    }
+                }
+            }
+            (1, CallFlags::SPLAT)
+        } else {
+            for arg in plain.iter() {
+                self.expr(arg);
+            }
+            (plain.len() as u32, CallFlags::NONE)
+        };
+        if let Some(inner) = block_pass {
+            self.expr(inner);
+            self.emit(Op::ToBlock, inner);
+            flags = flags.with(CallFlags::BLOCK_ARG);
+        }
+        (argc, flags)
+    }
+
+    // Discards a `&block` argument where there's no call to pass it to; it's still evaluated.
+    fn drop_block_arg(&mut self, flags: CallFlags, at: &Expr) -> CallFlags {
+        if !flags.contains(CallFlags::BLOCK_ARG) {
+            return flags;
+        }
+        self.emit(Op::Pop, at);
+        CallFlags::from_bits(flags.bits() & !CallFlags::BLOCK_ARG.bits())
+    }
+
+    // A literal block takes the place of any `&block` argument.
+    fn block_literal(&mut self, block: &Block, flags: CallFlags, at: &Expr) -> CallFlags {
+        let flags = self.drop_block_arg(flags, at);
+        let chunk = self.compile_block(block);
+        let chunk = self.constant(Constant::Chunk(chunk));
+        self.emit(Op::Block(chunk), at);
+        flags.with(CallFlags::BLOCK_LITERAL)
+    }
+
+    fn call(&mut self, e: &Expr, receiver: Option<&Expr>, name: &str, args: &[Expr], block: Option<&Rc<Block>>, safe_nav: bool) {
+        match receiver {
+            Some(receiver) => self.expr(receiver),
+            None => {
+                self.emit(Op::SelfValue, e);
+            }
+        }
+        let skip = if safe_nav {
+            self.emit(Op::Dup, e);
+            Some(self.emit(Op::JumpIfNil(0), e))
+        } else {
+            None
+        };
+        let (argc, mut flags) = self.args(args, e);
+        if let Some(block) = block {
+            flags = self.block_literal(block, flags, e);
+        }
+        if receiver.is_none() && args.is_empty() && block.is_none() {
+            flags = flags.with(CallFlags::VCALL);
+        }
+        let name = self.sym(name);
+        self.emit(Op::Send { name, argc, flags }, e);
+        if let Some(skip) = skip {
+            self.patch(skip);
+        }
+    }
+
+    fn setter_name(&mut self, name: &str) -> Sym {
+        if name == "[]" { self.sym("[]=") } else { self.sym(&format!("{}=", name)) }
+    }
+
+    fn assign(&mut self, target: &Expr, value: &Expr) {
+        self.expr(value);
+        self.store(target);
+    }
+
+    // Stores the value on top of the stack into `target`, leaving it there.
+    fn store(&mut self, target: &Expr) {
+        match &target.kind {
+            ExprKind::LocalVar(name) => {
+                let (depth, slot) = self.local(name);
+                self.emit(Op::SetLocal { depth, slot }, target);
+            },
+            ExprKind::InstanceVar(name) => {
+                let sym = self.sym(name);
+                self.emit(Op::SetIvar(sym), target);
+            },
+            ExprKind::GlobalVar(name) => {
+                let sym = self.sym(name);
+                self.emit(Op::SetGlobal(sym), target);
+            },
+            ExprKind::Constant { scope: None, name } => {
+                let sym = self.sym(name);
+                self.emit(Op::SetConstant(sym), target);
+            },
+            ExprKind::Constant { scope: Some(scope), name } => {
+                self.expr(scope);
+                let sym = self.sym(name);
+                self.emit(Op::SetScopedConstant(sym), target);
+            },
+            ExprKind::Call { receiver: Some(receiver), name, args, .. } => {
+                self.expr(receiver);
+                let (argc, flags) = self.args(args, target);
+                let flags = self.drop_block_arg(flags, target);
+                self.emit(Op::Pull(argc + 1), target);
+                let name = self.setter_name(name);
+                self.emit(Op::Send { name, argc: argc + 1, flags: flags.with(CallFlags::ASSIGN) }, target);
+            },
+            _ => {
+                self.emit(Op::Pop, target);
+                self.invalid("invalid assignment target", target);
+            }
+        }
+    }
+
+    // `a op= b` reads the target once, so `x[i()] += 1` calls `i` once.
+    fn op_assign(&mut self, target: &Expr, op: OperatorSymbol, value: &Expr) {
+        if let ExprKind::Call { receiver: Some(receiver), name, args, .. } = &target.kind {
+            self.expr(receiver);
+            let (argc, flags) = self.args(args, target);
+            let flags = self.drop_block_arg(flags, target);
+            let getter = self.sym(name);
+            self.emit(Op::DupN(argc + 1), target);
+            self.emit(Op::Send { name: getter, argc, flags }, target);
+            let keep = self.short_circuit(op, target);
+            self.expr(value);
+            if op != OperatorSymbol::AND && op != OperatorSymbol::OR {
+                self.emit(Op::Binary(op), target);
+            }
+            let setter = self.setter_name(name);
+            self.emit(Op::Send { name: setter, argc: argc + 1, flags: flags.with(CallFlags::ASSIGN) }, target);
+            if let Some(keep) = keep {
+                let to_end = self.emit(Op::Jump(0), target);
+                self.patch(keep);
+                self.emit(Op::Slide(argc + 1), target);
+                self.patch(to_end);
+            }
+            return;
+        }
+        match &target.kind {
+            // An undefined constant reads as nil.
+            ExprKind::Constant { .. } => {
+                let guard = self.emit(Op::PushGuard(0), target);
+                self.expr(target);
+                self.emit(Op::PopHandler, target);
+                self.patch(guard);
+            },
+            _ => self.expr(target)
+        }
+        let keep = self.short_circuit(op, target);
+        self.expr(value);
+        if op != OperatorSymbol::AND && op != OperatorSymbol::OR {
+            self.emit(Op::Binary(op), target);
+        }
+        self.store(target);
+        if let Some(keep) = keep {
+            self.patch(keep);
+        }
+    }
+
+    // `a ||= b` keeps a truthy `a`, `a &&= b` keeps a falsy one: jumps past the assignment with
+    // the current value on top.
+    fn short_circuit(&mut self, op: OperatorSymbol, at: &Expr) -> Option<usize> {
+        let jump = match op {
+            OperatorSymbol::OR => Op::JumpIfTrue(0),
+            OperatorSymbol::AND => Op::JumpIfFalse(0),
+            _ => return None
+        };
+        self.emit(Op::Dup, at);
+        let keep = self.emit(jump, at);
+        self.emit(Op::Pop, at);
+        Some(keep)
+    }
+
+    fn while_loop(&mut self, e: &Expr, cond: &Expr, body: &Body, until: bool) {
+        let handler = self.emit(Op::PushLoop { break_target: 0, next_target: 0 }, e);
+        let start = self.here();
+        self.scope().code[handler] = Op::PushLoop { break_target: 0, next_target: start };
+        self.expr(cond);
+        let exit = if until { self.emit(Op::JumpIfTrue(0), e) } else { self.emit(Op::JumpIfFalse(0), e) };
+        self.body(body, e);
+        self.emit(Op::Pop, e);
+        self.emit(Op::Jump(start), e);
+        self.patch(exit);
+        self.emit(Op::PopHandler, e);
+        self.emit(Op::Nil, e);
+        self.patch(handler);
+    }
+
+    // Runs with the collection, its items and the next index on the stack; evaluates to the
+    // collection.
+    fn for_loop(&mut self, e: &Expr, var: &str, iter: &Expr, body: &Body) {
+        self.expr(iter);
+        self.emit(Op::ForItems, e);
+        self.emit(Op::Integer(0), e);
+        let handler = self.emit(Op::PushLoop { break_target: 0, next_target: 0 }, e);
+        let start = self.here();
+        self.scope().code[handler] = Op::PushLoop { break_target: 0, next_target: start };
+        let exit = self.emit(Op::ForNext(0), e);
+        let (depth, slot) = self.local(var);
+        self.emit(Op::SetLocal { depth, slot }, e);
+        self.emit(Op::Pop, e);
+        self.body(body, e);
+        self.emit(Op::Pop, e);
+        self.emit(Op::Jump(start), e);
+        self.patch(exit);
+        self.emit(Op::PopHandler, e);
+        self.emit(Op::Pop, e);
+        self.emit(Op::Pop, e);
+        let to_end = self.emit(Op::Jump(0), e);
+        self.patch(handler);
+        self.emit(Op::Slide(3), e);
+        self.patch(to_end);
+    }
+
+    fn case(&mut self, e: &Expr, subject: Option<&Expr>, whens: &[(Vec<Expr>, Body)], else_body: Option<&Body>) {
+        if let Some(subject) = subject {
+            self.expr(subject);
+        }
+        let mut matched: Vec<Vec<usize>> = Vec::new();
+        for (tests, _) in whens.iter() {
+            let mut jumps = Vec::new();
+            for test in tests.iter() {
+                let (pattern, splat) = match &test.kind {
+                    ExprKind::Splat(inner) => (inner.as_ref(), true),
+                    _ => (test, false)
+                };
+                self.expr(pattern);
+                if subject.is_some() {
+                    self.emit(Op::CaseMatch { splat }, test);
+                } else if splat {
+                    self.emit(Op::AnyTruthy, test);
+                }
+                jumps.push(self.emit(Op::JumpIfTrue(0), test));
+            }
+            matched.push(jumps);
+        }
+        match else_body {
+            Some(body) => self.body(body, e),
+            None => {
+                self.emit(Op::Nil, e);
+            }
+        }
+        let mut to_end = vec![self.emit(Op::Jump(0), e)];
+        for ((_, body), jumps) in whens.iter().zip(matched) {
+            for jump in jumps {
+                self.patch(jump);
+            }
+            self.body(body, e);
+            to_end.push(self.emit(Op::Jump(0), e));
+        }
+        for jump in to_end {
+            self.patch(jump);
+        }
+        if subject.is_some() {
+            self.emit(Op::Slide(1), e);
+        }
+    }
+
+    fn begin(&mut self, e: &Expr, body: &Body, rescues: &[RescueClause], else_body: Option<&Body>, ensure_body: Option<&Body>) {
+        let ensure = ensure_body.map(|_| self.emit(Op::PushEnsure(0), e));
+        if rescues.is_empty() {
+            self.body(body, e);
+            if let Some(else_body) = else_body {
+                self.emit(Op::Pop, e);
+                self.body(else_body, e);
+            }
+        } else {
+            let retry_target = self.here();
+            let handler = self.emit(Op::PushRescue(0), e);
+            self.body(body, e);
+            self.emit(Op::PopHandler, e);
+            if let Some(else_body) = else_body {
+                self.emit(Op::Pop, e);
+                self.body(else_body, e);
+            }
+            let mut to_end = vec![self.emit(Op::Jump(0), e)];
+            // The exception is on top from here on.
+            self.patch(handler);
+            let mut matched: Vec<Vec<usize>> = Vec::new();
+            for clause in rescues.iter() {
+                let mut jumps = Vec::new();
+                if clause.classes.is_empty() {
+                    self.emit(Op::RescueStandard, e);
+                    jumps.push(self.emit(Op::JumpIfTrue(0), e));
+                }
+                for class in clause.classes.iter() {
+                    let (class_expr, splat) = match &class.kind {
+                        ExprKind::Splat(inner) => (inner.as_ref(), true),
+                        _ => (class, false)
+                    };
+                    self.expr(class_expr);
+                    self.emit(Op::RescueMatch { splat }, class);
+                    jumps.push(self.emit(Op::JumpIfTrue(0), class));
+                }
+                matched.push(jumps);
+            }
+            self.emit(Op::Reraise, e);
+            for (clause, jumps) in rescues.iter().zip(matched) {
+                for jump in jumps {
+                    self.patch(jump);
+                }
+                if let Some(var) = &clause.var {
+                    let (depth, slot) = self.local(var);
+                    self.emit(Op::SetLocal { depth, slot }, e);
+                }
+                self.emit(Op::PushRescueBody { retry_target }, e);
+                self.body(&clause.body, e);
+                self.emit(Op::PopHandler, e);
+                self.emit(Op::Slide(1), e);
+                to_end.push(self.emit(Op::Jump(0), e));
+            }
+            for jump in to_end {
+                self.patch(jump);
+            }
+        }
+        if let (Some(handler), Some(ensure_body)) = (ensure, ensure_body) {
+            self.emit(Op::PopHandler, e);
+            self.patch(handler);
+            self.body(ensure_body, e);
+            self.emit(Op::Pop, e);
+            self.emit(Op::EndEnsure, e);
+        }
+    }
+
+    // The constant a `class` or `module` definition is named by, with its scope compiled when it
+    // has one.
+    fn definition_path(&mut self, path: &Expr) -> Option<(Sym, bool)> {
+        match &path.kind {
+            ExprKind::Constant { scope: Some(scope), name } => {
+                self.expr(scope);
+                Some((self.sym(name), true))
+            },
+            ExprKind::Constant { scope: None, name } => Some((self.sym(name), false)),
+            _ => {
+                self.emit(Op::Nil, path);
+                self.invalid("class/module name must be a constant", path);
+                None
+            }
+        }
+    }
+
+    // Leaves what `defined?(e)` describes `e` as, or nil. Only the receivers and scopes that have
+    // to exist for `e` to be defined are evaluated; the caller guards against their exceptions.
+    fn defined(&mut self, e: &Expr) {
+        let check = match &e.kind {
+            ExprKind::SelfRef => return self.string("self", e),
+            ExprKind::LocalVar(_) => return self.string("local-variable", e),
+            ExprKind::Assign { .. } | ExprKind::OpAssign { .. } => return self.string("assignment", e),
+            ExprKind::InstanceVar(name) => DefinedCheck::InstanceVar(self.sym(name)),
+            ExprKind::GlobalVar(name) => DefinedCheck::GlobalVar(self.sym(name)),
+            ExprKind::Constant { scope: None, name } => DefinedCheck::Constant(self.sym(name)),
+            ExprKind::Yield(_) => DefinedCheck::Yield,
+            ExprKind::Super { .. } => DefinedCheck::Super,
+            ExprKind::Constant { scope: Some(scope), name } => {
+                let mut fail = vec![self.require_defined(scope)];
+                self.expr(scope);
+                let sym = self.sym(name);
+                return self.finish_defined(Op::Defined(DefinedCheck::ScopedConstant(sym)), &mut fail, e);
+            },
+            ExprKind::Call { receiver, name, args, .. } => {
+                let mut fail = Vec::new();
+                for arg in args.iter() {
+                    let inner = match &arg.kind {
+                        ExprKind::Splat(inner) | ExprKind::BlockPass(inner) => inner,
+                        _ => arg
+                    };
+                    fail.push(self.require_defined(inner));
+                }
+                let sym = self.sym(name);
+                let op = match receiver {
+                    Some(receiver) => {
+                        fail.push(self.require_defined(receiver));
+                        self.expr(receiver);
+                        Op::Defined(DefinedCheck::Method(sym))
+                    },
+                    None => Op::Defined(DefinedCheck::SelfMethod(sym))
+                };
+                return self.finish_defined(op, &mut fail, e);
+            },
+            ExprKind::Binary { op, lhs, rhs, .. } => {
+                let mut fail = vec![self.require_defined(lhs), self.require_defined(rhs)];
+                let description = match op {
+                    OperatorSymbol::RANGE | OperatorSymbol::EXCL_RANGE => "expression",
+                    OperatorSymbol::NOT_EQ | OperatorSymbol::NOT_MATCH => "method",
+                    op => {
+                        self.expr(lhs);
+                        let sym = self.sym(op.to_str());
+                        return self.finish_defined(Op::Defined(DefinedCheck::Method(sym)), &mut fail, e);
+                    }
+                };
+                let index = self.constant(Constant::Str(description.to_string()));
+                return self.finish_defined(Op::String(index), &mut fail, e);
+            },
+            ExprKind::Unary { operand, .. } => {
+                let mut fail = vec![self.require_defined(operand)];
+                let index = self.constant(Constant::Str(String::from("method")));
+                return self.finish_defined(Op::String(index), &mut fail, e);
+            },
+            _ => return self.string("expression", e)
+        };
+        self.emit(Op::Defined(check), e);
+    }
+
+    // Jumps out (to be patched) when `e` isn't defined.
+    fn require_defined(&mut self, e: &Expr) -> usize {
+        self.defined(e);
+        self.emit(Op::JumpIfNil(0), e)
+    }
+
+    fn finish_defined(&mut self, op: Op, fail: &mut Vec<usize>, at: &Expr) {
+        self.emit(op, at);
+        let to_end = self.emit(Op::Jump(0), at);
+        for jump in fail.drain(..) {
+            self.patch(jump);
+        }
+        self.emit(Op::Nil, at);
+        self.patch(to_end);
+    }
+}
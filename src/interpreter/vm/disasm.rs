@@ -0,0 +1,155 @@
+use std::fmt::Write;
+
+use crate::interpreter::runtime::symbols::{Sym, SymbolTable};
+use crate::interpreter::source_map::SourceFile;
+use super::bytecode::{CallFlags, Chunk, ChunkKind, Constant, DefinedCheck, Op};
+
+// Lists a chunk's instructions, then those of the chunks it contains, with each source line
+// printed above the instructions compiled from it.
+pub fn disassemble(chunk: &Chunk, symbols: &SymbolTable, source: Option<&SourceFile>) -> String {
+    let mut out = String::new();
+    let mut enclosing: Vec<&Chunk> = Vec::new();
+    listing(&mut out, chunk, &mut enclosing, symbols, source);
+    out
+}
+
+fn listing<'a>(out: &mut String, chunk: &'a Chunk, enclosing: &mut Vec<&'a Chunk>, symbols: &SymbolTable, source: Option<&SourceFile>) {
+    let kind = match chunk.kind {
+        ChunkKind::Program => "program",
+        ChunkKind::Method  => "method",
+        ChunkKind::Block   => "block",
+        ChunkKind::Module  => "module"
+    };
+    let _ = write!(out, "== {} ({}) at {}:{}", symbols.name(chunk.name), kind, chunk.line, chunk.col);
+    if !chunk.locals.is_empty() {
+        let locals: Vec<&str> = chunk.locals.iter().map(|l| symbols.name(*l)).collect();
+        let _ = write!(out, " locals: {}", locals.join(", "));
+    }
+    out.push_str(" ==\n");
+    if chunk.params.entries.len() > 1 {
+        let entries: Vec<String> = chunk.params.entries.iter().map(|e| e.to_string()).collect();
+        let _ = writeln!(out, "     entries: {}", entries.join(", "));
+    }
+    enclosing.push(chunk);
+    let mut last_line = 0;
+    for (pc, op) in chunk.code.iter().enumerate() {
+        let (line, _) = chunk.positions[pc];
+        if line != last_line {
+            if let Some(text) = source.and_then(|s| s.get_line(line)) {
+                let _ = writeln!(out, "{:>4} | {}", line, text.trim_end());
+            }
+            last_line = line;
+        }
+        let _ = writeln!(out, "     {:>5}  {}", pc, describe(op, chunk, enclosing, symbols));
+    }
+    out.push('\n');
+    for constant in chunk.constants.iter() {
+        if let Constant::Chunk(inner) = constant {
+            listing(out, inner, enclosing, symbols, source);
+        }
+    }
+    enclosing.pop();
+}
+
+fn describe(op: &Op, chunk: &Chunk, enclosing: &[&Chunk], symbols: &SymbolTable) -> String {
+    let name = |sym: &Sym| symbols.name(*sym).to_string();
+    let local = |depth: u32, slot: u32| {
+        let owner = enclosing.len().checked_sub(depth as usize + 1).map(|i| enclosing[i]);
+        match owner.and_then(|c| c.locals.get(slot as usize)) {
+            Some(sym) => format!("{} ({}, {})", symbols.name(*sym), depth, slot),
+            None => format!("({}, {})", depth, slot)
+        }
+    };
+    let constant = |index: u32| match &chunk.constants[index as usize] {
+        Constant::Float(f) => format!("{}", f),
+        Constant::Str(s) => format!("{:?}", s),
+        Constant::Chunk(c) => format!("<{}>", symbols.name(c.name))
+    };
+    match op {
+        Op::Integer(n)                 => format!("Integer {}", n),
+        Op::Constant(i)                => format!("Constant {}", constant(*i)),
+        Op::String(i)                  => format!("String {}", constant(*i)),
+        Op::WideInteger(i)             => format!("WideInteger {}", constant(*i)),
+        Op::Rational(i)                => format!("Rational {}", constant(*i)),
+        Op::FrozenString(i)            => format!("FrozenString {}", constant(*i)),
+        Op::Regexp { source, flags }   => format!("Regexp {} {}", constant(*source), constant(*flags)),
+        Op::Symbol(s)                  => format!("Symbol :{}", name(s)),
+        Op::DupN(n)                    => format!("DupN {}", n),
+        Op::Slide(n)                   => format!("Slide {}", n),
+        Op::Pull(n)                    => format!("Pull {}", n),
+        Op::GetLocal { depth, slot }   => format!("GetLocal {}", local(*depth, *slot)),
+        Op::SetLocal { depth, slot }   => format!("SetLocal {}", local(*depth, *slot)),
+        Op::GetIvar(s)                 => format!("GetIvar {}", name(s)),
+        Op::SetIvar(s)                 => format!("SetIvar {}", name(s)),
+        Op::GetGlobal(s)               => format!("GetGlobal {}", name(s)),
+        Op::SetGlobal(s)               => format!("SetGlobal {}", name(s)),
+        Op::GetConstant(s)             => format!("GetConstant {}", name(s)),
+        Op::GetScopedConstant(s)       => format!("GetScopedConstant {}", name(s)),
+        Op::SetConstant(s)             => format!("SetConstant {}", name(s)),
+        Op::SetScopedConstant(s)       => format!("SetScopedConstant {}", name(s)),
+        Op::Array(n)                   => format!("Array {}", n),
+        Op::Hash(n)                    => format!("Hash {}", n),
+        Op::Jump(t)                    => format!("Jump -> {}", t),
+        Op::JumpIfFalse(t)             => format!("JumpIfFalse -> {}", t),
+        Op::JumpIfTrue(t)              => format!("JumpIfTrue -> {}", t),
+        Op::JumpIfNil(t)               => format!("JumpIfNil -> {}", t),
+        Op::Binary(o)                  => format!("Binary {}", o.to_str()),
+        Op::Unary(o)                   => format!("Unary {}", o.to_str()),
+        Op::Send { name: n, argc, flags } => format!("Send {} {}{}", name(n), argc, call_flags(*flags)),
+        Op::Super { argc, flags, bare } => {
+            if *bare { String::from("Super (bare)") } else { format!("Super {}{}", argc, call_flags(*flags)) }
+        },
+        Op::Yield { argc, flags }      => format!("Yield {}{}", argc, call_flags(*flags)),
+        Op::Block(i)                   => format!("Block {}", constant(*i)),
+        Op::Lambda(i)                  => format!("Lambda {}", constant(*i)),
+        Op::PushRescue(t)              => format!("PushRescue -> {}", t),
+        Op::PushEnsure(t)              => format!("PushEnsure -> {}", t),
+        Op::PushGuard(t)               => format!("PushGuard -> {}", t),
+        Op::PushLoop { break_target, next_target } => format!("PushLoop break -> {} next -> {}", break_target, next_target),
+        Op::PushRescueBody { retry_target } => format!("PushRescueBody retry -> {}", retry_target),
+        Op::RescueMatch { splat }      => if *splat { String::from("RescueMatch *") } else { String::from("RescueMatch") },
+        Op::CaseMatch { splat }        => if *splat { String::from("CaseMatch *") } else { String::from("CaseMatch") },
+        Op::ForNext(t)                 => format!("ForNext exit -> {}", t),
+        Op::Defined(check)             => format!("Defined {}", defined_check(*check, symbols)),
+        Op::DefineMethod { chunk: i, singleton } => {
+            format!("DefineMethod {}{}", constant(*i), if *singleton { " (singleton)" } else { "" })
+        },
+        Op::DefineClass { name: n, chunk: i, scoped, superclass } => {
+            format!("DefineClass {} {}{}{}", name(n), constant(*i), if *scoped { " (scoped)" } else { "" }, if *superclass { " (superclass)" } else { "" })
+        },
+        Op::DefineModule { name: n, chunk: i, scoped } => {
+            format!("DefineModule {} {}{}", name(n), constant(*i), if *scoped { " (scoped)" } else { "" })
+        },
+        Op::Alias { new_name, old_name } => format!("Alias {} {}", name(new_name), name(old_name)),
+        Op::Undef(s)                   => format!("Undef {}", name(s)),
+        Op::EndBlock(i)                => format!("EndBlock {}", constant(*i)),
+        Op::Invalid(i)                 => format!("Invalid {}", constant(*i)),
+        other                          => format!("{:?}", other)
+    }
+}
+
+fn call_flags(flags: CallFlags) -> String {
+    let names = [
+        (CallFlags::SPLAT, "splat"),
+        (CallFlags::BLOCK_ARG, "block-arg"),
+        (CallFlags::BLOCK_LITERAL, "block"),
+        (CallFlags::VCALL, "vcall"),
+        (CallFlags::ASSIGN, "assign"),
+        (CallFlags::TAIL, "tail")
+    ];
+    let set: Vec<&str> = names.iter().filter(|(flag, _)| flags.contains(*flag)).map(|(_, name)| *name).collect();
+    if set.is_empty() { String::new() } else { format!(" [{}]", set.join(", ")) }
+}
+
+fn defined_check(check: DefinedCheck, symbols: &SymbolTable) -> String {
+    match check {
+        DefinedCheck::InstanceVar(s)    => format!("ivar {}", symbols.name(s)),
+        DefinedCheck::GlobalVar(s)      => format!("global {}", symbols.name(s)),
+        DefinedCheck::Constant(s)       => format!("constant {}", symbols.name(s)),
+        DefinedCheck::ScopedConstant(s) => format!("scoped-constant {}", symbols.name(s)),
+        DefinedCheck::Method(s)         => format!("method {}", symbols.name(s)),
+        DefinedCheck::SelfMethod(s)     => format!("self-method {}", symbols.name(s)),
+        DefinedCheck::Yield             => String::from("yield"),
+        DefinedCheck::Super             => String::from("super")
+    }
+}
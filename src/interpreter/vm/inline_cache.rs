@@ -0,0 +1,153 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+
+use crate::interpreter::runtime::heap::MethodEntry;
+use crate::interpreter::runtime::symbols::Sym;
+use crate::interpreter::runtime::value::ObjRef;
+
+// Receiver classes a call site remembers before giving up and using the global cache alone.
+const POLYMORPHIC_LIMIT: usize = 4;
+
+#[derive(Clone)]
+struct CacheEntry {
+    class: ObjRef,
+    method: MethodEntry,
+    owner: ObjRef
+}
+
+#[derive(Clone, Default)]
+enum Site {
+    #[default]
+    Empty,
+    Monomorphic(CacheEntry),
+    Polymorphic(Vec<CacheEntry>),
+    Megamorphic
+}
+
+impl Site {
+    fn lookup(&self, class: ObjRef) -> Option<(MethodEntry, ObjRef)> {
+        let found = match self {
+            Site::Monomorphic(entry) if entry.class == class => Some(entry),
+            Site::Polymorphic(entries) => entries.iter().find(|e| e.class == class),
+            _ => None
+        };
+        found.map(|e| (e.method.clone(), e.owner))
+    }
+
+    fn record(&mut self, entry: CacheEntry) {
+        *self = match std::mem::take(self) {
+            Site::Empty => Site::Monomorphic(entry),
+            Site::Monomorphic(first) => Site::Polymorphic(vec![first, entry]),
+            Site::Polymorphic(mut entries) if entries.len() < POLYMORPHIC_LIMIT => {
+                entries.push(entry);
+                Site::Polymorphic(entries)
+            },
+            _ => Site::Megamorphic
+        };
+    }
+}
+
+// Per-instruction caches for a chunk's call sites. Entries are only good for the method cache
+// serial they were filled under; a chunk's sites all reset together when it moves on.
+#[derive(Default)]
+pub struct CallSites {
+    state: RefCell<SiteTable>
+}
+
+#[derive(Default)]
+struct SiteTable {
+    serial: u64,
+    sites: Vec<Site>
+}
+
+impl CallSites {
+    pub fn lookup(&self, pc: usize, class: ObjRef, serial: u64) -> Option<(MethodEntry, ObjRef)> {
+        let table = self.state.borrow();
+        if table.serial != serial {
+            return None;
+        }
+        table.sites.get(pc)?.lookup(class)
+    }
+
+    pub fn record(&self, pc: usize, class: ObjRef, serial: u64, method: &MethodEntry, owner: ObjRef) {
+        let mut table = self.state.borrow_mut();
+        if table.serial != serial {
+            table.serial = serial;
+            table.sites.iter_mut().for_each(|site| *site = Site::Empty);
+        }
+        if table.sites.len() <= pc {
+            table.sites.resize(pc + 1, Site::Empty);
+        }
+        table.sites[pc].record(CacheEntry { class, method: method.clone(), owner });
+    }
+}
+
+// The cache of a single call site, which the tree walker keeps in each call's AST node. It also
+// holds the method name's symbol, which the VM gets from its constants instead.
+#[derive(Default)]
+pub struct InlineCache {
+    state: RefCell<(u64, Site)>,
+    name: Cell<Option<Sym>>
+}
+
+impl InlineCache {
+    pub fn name(&self, intern: impl FnOnce() -> Sym) -> Sym {
+        match self.name.get() {
+            Some(sym) => sym,
+            None => {
+                let sym = intern();
+                self.name.set(Some(sym));
+                sym
+            }
+        }
+    }
+
+    pub fn lookup(&self, class: ObjRef, serial: u64) -> Option<(MethodEntry, ObjRef)> {
+        let state = self.state.borrow();
+        if state.0 != serial {
+            return None;
+        }
+        state.1.lookup(class)
+    }
+
+    pub fn record(&self, class: ObjRef, serial: u64, method: &MethodEntry, owner: ObjRef) {
+        let mut state = self.state.borrow_mut();
+        if state.0 != serial {
+            *state = (serial, Site::Empty);
+        }
+        state.1.record(CacheEntry { class, method: method.clone(), owner });
+    }
+}
+
+// Caches are runtime state, not part of what was compiled: copies start empty and all compare equal.
+impl Clone for CallSites {
+    fn clone(&self) -> Self {
+        CallSites::default()
+    }
+}
+impl PartialEq for CallSites {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+impl fmt::Debug for CallSites {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CallSites")
+    }
+}
+
+impl Clone for InlineCache {
+    fn clone(&self) -> Self {
+        InlineCache::default()
+    }
+}
+impl PartialEq for InlineCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+impl fmt::Debug for InlineCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("InlineCache")
+    }
+}
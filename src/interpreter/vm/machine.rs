@@ -0,0 +1,991 @@
+use std::fs;
+use std::rc::Rc;
+
+use crate::interpreter::builtins;
+use crate::interpreter::interpreter::{EvalResult, Interpreter, Unwind};
+use crate::interpreter::parser::lexicon::OperatorSymbol;
+use crate::interpreter::runtime::heap::{MethodBody, MethodEntry, ObjectKind, ProcBody, UserMethod};
+use crate::interpreter::runtime::symbols::Sym;
+use crate::interpreter::runtime::value::{ObjRef, Value};
+use crate::interpreter::source_map::FileId;
+use super::cache;
+use super::bytecode::{CallFlags, Chunk, Constant, DefinedCheck, Op};
+use super::compiler::Compiler;
+
+#[derive(Debug, Clone, Copy)]
+enum HandlerKind {
+    Rescue,
+    Ensure,
+    Guard,
+    // The loop's `break` tag, its `next` target and the frame's break tag to restore.
+    Loop { tag: usize, next_target: u32, outer_tag: Option<usize> },
+    // The `$!` to restore once the rescue clause is done. It's nil or the exception an enclosing
+    // rescue clause is handling, which that clause keeps on the stack, so it needs no rooting.
+    RescueBody { outer_error: Value }
+}
+
+// An active `PushRescue`, `PushEnsure`, `PushGuard`, `PushLoop` or `PushRescueBody`.
+struct Handler {
+    kind: HandlerKind,
+    target: u32,
+    // The stack height to unwind to.
+    depth: usize,
+    // How many exits ensure handlers were holding when this one was pushed.
+    pending: usize
+}
+
+// The state of one chunk being run.
+struct Activation {
+    chunk: Rc<Chunk>,
+    pc: usize,
+    base: usize,
+    // Where the interpreter's roots stood on entry; each instruction's are let go before the next,
+    // since anything still in use by then is on the stack or in an environment.
+    roots: usize,
+    handlers: Vec<Handler>,
+    // Exits caught by ensure handlers, resumed by `EndEnsure`; `None` when the body finished.
+    pending: Vec<Option<Unwind>>,
+    // How to hand the result back, for a method called from another activation.
+    call: Option<CallInfo>
+}
+
+impl Activation {
+    fn create(chunk: Rc<Chunk>, start: u32, base: usize, roots: usize, call: Option<CallInfo>) -> Activation {
+        Activation { chunk, pc: start as usize, base, roots, handlers: Vec::new(), pending: Vec::new(), call }
+    }
+}
+
+// A `Send` whose method runs as a new activation of the same `run_chunk` loop.
+struct CallInfo {
+    // The method's frame, which a `return` inside it targets.
+    frame_id: u64,
+    // Where the receiver was; the caller's stack is cut back to here.
+    stack_top: usize,
+    flags: CallFlags,
+    block: Option<Value>,
+    // What an attribute or index assignment evaluates to.
+    assigned: Value
+}
+
+// Why `execute` stopped.
+enum Exit {
+    Finish(Value),
+    // A compiled method was called; run it, then resume the caller.
+    Call(Activation),
+    // The activation was replaced by a tail call.
+    Resume
+}
+
+impl Interpreter {
+    // Compiles a loaded file, or reuses the `.jbc` cache next to it when it was compiled from the
+    // same text. Writing the cache is best effort.
+    pub(crate) fn compile_file(&mut self, file: FileId, label: &str) -> Result<Rc<Chunk>, Unwind> {
+        let (text, path) = match self.sources.get(file) {
+            Some(f) => (f.get_text().to_string(), f.get_path().map(|p| p.with_extension("jbc"))),
+            None => (String::new(), None)
+        };
+        let hash = cache::source_hash(&text);
+        if let Some(cached) = path.as_ref().and_then(|p| fs::read(p).ok()) {
+            if let Some(chunk) = cache::decode(&cached, hash, &mut self.symbols).filter(|c| self.symbols.name(c.name) == label) {
+                return Ok(chunk);
+            }
+        }
+        let program = self.parse_file(file)?;
+        let chunk = Compiler::create(&mut self.symbols).compile_program(&program, label);
+        if let Some(path) = path {
+            let _ = fs::write(path, cache::encode(&chunk, hash, &self.symbols));
+        }
+        Ok(chunk)
+    }
+
+    pub(crate) fn run_program(&mut self, chunk: Rc<Chunk>, file: FileId, label: &str) -> EvalResult {
+        let env = self.new_slot_env(None, chunk.locals.len());
+        let id = self.push_program_frame(env, file, label)?;
+        let result = self.run_chunk(&chunk, 0);
+        self.pop_frame();
+        match result {
+            Err(Unwind::Return(v, target)) if target == id => Ok(v),
+            other => other
+        }
+    }
+
+    // Binds arguments to a compiled method's or block's parameter slots in the current frame,
+    // following the same rules as `bind_params`, and returns where the chunk starts.
+    pub(crate) fn bind_chunk_params(&mut self, chunk: &Chunk, args: &[Value], block: Option<Value>, strict: bool) -> Result<u32, Unwind> {
+        let params = &chunk.params;
+        let required = (params.required + params.post) as usize;
+        let optional = params.optional as usize;
+        let spread: Vec<Value>;
+        let args: &[Value] = if !strict && args.len() == 1 && (required + optional > 1 || (params.rest && required > 0)) {
+            match self.array_items(args[0]) {
+                Some(items) => {
+                    spread = items.clone();
+                    &spread
+                },
+                None => args
+            }
+        } else {
+            args
+        };
+        if strict {
+            let max = if params.rest { None } else { Some(required + optional) };
+            self.check_args(args, required, max)?;
+        }
+        let n = args.len();
+        let arg = |i: usize| args.get(i).copied().unwrap_or(Value::Nil);
+        let mut values: Vec<Value> = Vec::with_capacity(params.slots.len());
+        values.extend((0..params.required as usize).map(arg));
+        let available_optional = n.saturating_sub(required).min(optional);
+        let mut next = params.required as usize;
+        values.extend((next..next + available_optional).map(arg));
+        next += available_optional;
+        values.extend((available_optional..optional).map(|_| Value::Nil));
+        let post_start = n.saturating_sub(params.post as usize).max(next);
+        if params.rest {
+            let rest_items: Vec<Value> = if post_start > next { args[next..post_start].to_vec() } else { Vec::new() };
+            values.push(self.new_array(rest_items));
+        }
+        values.extend((0..params.post as usize).map(|i| arg(post_start + i)));
+        if params.block {
+            values.push(block.unwrap_or(Value::Nil));
+        }
+        let env = self.frame().env;
+        if let ObjectKind::Env(data) = &mut self.heap.get_mut(env).kind {
+            for (slot, v) in params.slots.iter().zip(values) {
+                data.slots[*slot as usize] = v;
+            }
+        }
+        Ok(params.entries[available_optional])
+    }
+
+    // Runs `chunk` from `start` in the current frame. Compiled methods it calls run in the same
+    // loop on a stack of activations, so jasper calls don't recurse on the Rust stack.
+    pub(crate) fn run_chunk(&mut self, chunk: &Rc<Chunk>, start: u32) -> EvalResult {
+        let mut acts = vec![Activation::create(chunk.clone(), start, self.stack.len(), self.root_mark(), None)];
+        loop {
+            let act = acts.last_mut().expect("no activation to run");
+            let mut result = match self.execute(act) {
+                Ok(Exit::Finish(v)) => Ok(v),
+                Ok(Exit::Call(callee)) => {
+                    acts.push(callee);
+                    continue;
+                },
+                Ok(Exit::Resume) => continue,
+                Err(unwind) => match self.handle(act, unwind) {
+                    Ok(()) => continue,
+                    Err(u) => {
+                        self.stack.truncate(act.base);
+                        Err(u)
+                    }
+                }
+            };
+            // Hand the result to each caller in turn until one carries on.
+            loop {
+                let call = match acts.pop().and_then(|done| done.call) {
+                    Some(call) => call,
+                    None => {
+                        self.keep(&result);
+                        return result;
+                    }
+                };
+                self.pop_frame();
+                self.stack.truncate(call.stack_top);
+                let returned = match result {
+                    Err(Unwind::Return(v, target)) if target == call.frame_id => Ok(v),
+                    other => other
+                };
+                let caller = acts.last_mut().expect("a called activation has a caller");
+                match self.finish_send(call.flags, call.block, call.assigned, returned) {
+                    Ok(v) => {
+                        self.stack.push(v);
+                        break;
+                    },
+                    Err(unwind) => match self.handle(caller, unwind) {
+                        Ok(()) => break,
+                        Err(u) => {
+                            self.stack.truncate(caller.base);
+                            result = Err(u);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Passes a non-local exit to the innermost handler that takes it, or back to the caller.
+    fn handle(&mut self, act: &mut Activation, unwind: Unwind) -> Result<(), Unwind> {
+        while let Some(handler) = act.handlers.pop() {
+            let target = match (handler.kind, &unwind) {
+                (HandlerKind::Rescue, Unwind::Raise(exc)) => {
+                    let exc = *exc;
+                    self.stack.truncate(handler.depth);
+                    self.stack.push(exc);
+                    handler.target
+                },
+                (HandlerKind::Guard, Unwind::Raise(_)) => {
+                    self.stack.truncate(handler.depth);
+                    self.stack.push(Value::Nil);
+                    handler.target
+                },
+                (HandlerKind::Ensure, _) => {
+                    // The ensure clause's slot holds what the exit carries, so it stays alive.
+                    self.stack.truncate(handler.depth);
+                    self.stack.push(unwind.get_value().unwrap_or(Value::Nil));
+                    act.pending.truncate(handler.pending);
+                    act.pending.push(Some(unwind));
+                    act.pc = handler.target as usize;
+                    return Ok(());
+                },
+                (HandlerKind::Loop { tag, outer_tag, .. }, Unwind::Break(v, t)) if *t == tag => {
+                    let v = *v;
+                    self.frame_mut().break_tag = outer_tag;
+                    self.stack.truncate(handler.depth);
+                    self.stack.push(v);
+                    handler.target
+                },
+                (HandlerKind::Loop { next_target, .. }, Unwind::Next(_)) => {
+                    self.stack.truncate(handler.depth);
+                    act.pending.truncate(handler.pending);
+                    act.handlers.push(handler);
+                    act.pc = next_target as usize;
+                    return Ok(());
+                },
+                (HandlerKind::Loop { outer_tag, .. }, _) => {
+                    self.frame_mut().break_tag = outer_tag;
+                    continue;
+                },
+                (HandlerKind::RescueBody { outer_error }, _) => {
+                    self.set_global("$!", outer_error);
+                    if let Unwind::Retry = unwind {
+                        self.stack.truncate(handler.depth);
+                        handler.target
+                    } else {
+                        continue;
+                    }
+                },
+                _ => continue
+            };
+            act.pending.truncate(handler.pending);
+            act.pc = target as usize;
+            return Ok(());
+        }
+        Err(unwind)
+    }
+
+    fn push_handler(&mut self, act: &mut Activation, kind: HandlerKind, target: u32, depth: usize) {
+        act.handlers.push(Handler { kind, target, depth, pending: act.pending.len() });
+    }
+
+    fn env_at(&self, depth: u32) -> ObjRef {
+        let mut env = self.frame().env;
+        for _ in 0..depth {
+            env = match &self.heap.get(env).kind {
+                ObjectKind::Env(data) => data.parent.expect("local resolved past the outermost scope"),
+                _ => unreachable!("frames run in environments")
+            };
+        }
+        env
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("operand stack underflow")
+    }
+    fn top(&self) -> Value {
+        *self.stack.last().expect("operand stack underflow")
+    }
+
+    // Runs instructions until the chunk finishes, calls a compiled method or something unwinds.
+    fn execute(&mut self, act: &mut Activation) -> Result<Exit, Unwind> {
+        let chunk = act.chunk.clone();
+        loop {
+            self.release_roots(act.roots);
+            let pc = act.pc;
+            act.pc += 1;
+            let (line, col) = chunk.positions[pc];
+            let frame = self.frame_mut();
+            frame.line = line;
+            frame.col = col;
+            match chunk.code[pc] {
+                Op::Nil => self.stack.push(Value::Nil),
+                Op::True => self.stack.push(Value::True),
+                Op::False => self.stack.push(Value::False),
+                Op::SelfValue => self.stack.push(self.frame().context.self_value),
+                Op::Integer(n) => self.stack.push(Value::Integer(n)),
+                Op::Constant(index) => match &chunk.constants[index as usize] {
+                    Constant::Float(f) => self.stack.push(Value::Float(*f)),
+                    other => panic!("constant {:?} is not an immediate", other)
+                },
+                Op::String(index) => {
+                    let s = self.new_string(chunk.text(index).to_string());
+                    self.stack.push(s);
+                },
+                Op::FrozenString(index) => {
+                    let s = self.frozen_string(chunk.text(index).to_string());
+                    self.stack.push(s);
+                },
+                Op::Regexp { source, flags } => {
+                    let v = self.regexp_literal(chunk.text(source), chunk.text(flags))?;
+                    self.stack.push(v);
+                },
+                Op::WideInteger(index) => {
+                    let v = self.integer_literal(chunk.text(index))?;
+                    self.stack.push(v);
+                },
+                Op::Rational(index) => {
+                    let v = self.rational_literal(chunk.text(index))?;
+                    self.stack.push(v);
+                },
+                Op::Imaginary => {
+                    let v = self.new_complex(Value::Integer(0), self.top());
+                    *self.stack.last_mut().unwrap() = v;
+                },
+                Op::Symbol(sym) => self.stack.push(Value::Symbol(sym)),
+                Op::File => {
+                    let name = self.sources.get_name(self.frame().file).to_string();
+                    let s = self.new_string(name);
+                    self.stack.push(s);
+                },
+                Op::Encoding => {
+                    let v = builtins::encoding::utf_8(self);
+                    self.stack.push(v);
+                },
+
+                Op::Pop => {
+                    self.pop();
+                },
+                Op::Dup => self.stack.push(self.top()),
+                Op::DupN(n) => {
+                    let start = self.stack.len() - n as usize;
+                    self.stack.extend_from_within(start..);
+                },
+                Op::Slide(n) => {
+                    let v = self.pop();
+                    let len = self.stack.len();
+                    self.stack.truncate(len - n as usize);
+                    self.stack.push(v);
+                },
+                Op::Pull(n) => {
+                    let i = self.stack.len() - 1 - n as usize;
+                    let v = self.stack.remove(i);
+                    self.stack.push(v);
+                },
+
+                Op::GetLocal { depth, slot } => {
+                    let env = self.env_at(depth);
+                    let v = match &self.heap.get(env).kind {
+                        ObjectKind::Env(data) => data.slots[slot as usize],
+                        _ => Value::Nil
+                    };
+                    self.stack.push(v);
+                },
+                Op::SetLocal { depth, slot } => {
+                    let env = self.env_at(depth);
+                    let v = self.top();
+                    if let ObjectKind::Env(data) = &mut self.heap.get_mut(env).kind {
+                        data.slots[slot as usize] = v;
+                    }
+                },
+                Op::GetIvar(name) => {
+                    let v = self.get_ivar(self.frame().context.self_value, name);
+                    self.stack.push(v);
+                },
+                Op::SetIvar(name) => {
+                    let recv = self.frame().context.self_value;
+                    self.set_ivar(recv, name, self.top())?;
+                },
+                Op::GetGlobal(name) => self.stack.push(self.globals.get(&name).copied().unwrap_or(Value::Nil)),
+                Op::SetGlobal(name) => {
+                    let v = self.top();
+                    self.globals.insert(name, v);
+                },
+                Op::GetConstant(name) => {
+                    let v = self.lookup_constant(name)?;
+                    self.stack.push(v);
+                },
+                Op::GetScopedConstant(name) => {
+                    let scope = self.pop();
+                    let module = self.expect_module(scope)?;
+                    match self.lookup_constant_in(module, name) {
+                        Some(v) => self.stack.push(v),
+                        None => {
+                            let message = format!("uninitialized constant {}::{}", self.module_name(module), self.sym_name(name));
+                            return Err(self.error(self.core.name_error, message));
+                        }
+                    }
+                },
+                Op::SetConstant(name) => {
+                    let module = self.frame().context.cref.last().copied().unwrap_or(self.core.object);
+                    self.set_constant(module, name, self.top());
+                },
+                Op::SetScopedConstant(name) => {
+                    let scope = self.pop();
+                    let module = self.expect_module(scope)?;
+                    self.set_constant(module, name, self.top());
+                },
+
+                Op::Array(n) => {
+                    let start = self.stack.len() - n as usize;
+                    let items = self.stack.split_off(start);
+                    let array = self.new_array(items);
+                    self.stack.push(array);
+                },
+                Op::ArrayPush => {
+                    let v = self.pop();
+                    self.array_append(self.top(), &[v]);
+                },
+                Op::ArraySplat => {
+                    let v = self.pop();
+                    match self.array_items(v) {
+                        Some(items) => {
+                            let items = items.clone();
+                            self.array_append(self.top(), &items);
+                        },
+                        None if v.is_nil() => {},
+                        None => self.array_append(self.top(), &[v])
+                    }
+                },
+                Op::Hash(n) => {
+                    // The pairs stay on the stack while string keys are copied.
+                    let start = self.stack.len() - 2 * n as usize;
+                    let pairs = self.stack[start..].to_vec();
+                    let hash = self.hash_from_pairs(&pairs);
+                    self.stack.truncate(start);
+                    self.stack.push(hash);
+                },
+                Op::ToBlock => {
+                    let v = self.top();
+                    let block = match v {
+                        Value::Nil => Value::Nil,
+                        Value::Symbol(sym) => self.new_proc(ProcBody::Symbol(sym), false),
+                        v if self.is_proc(v) => v,
+                        v => self.call(v, "to_proc", &[])?
+                    };
+                    *self.stack.last_mut().unwrap() = block;
+                },
+
+                Op::Jump(target) => act.pc = target as usize,
+                Op::JumpIfFalse(target) => {
+                    if !self.pop().is_truthy() {
+                        act.pc = target as usize;
+                    }
+                },
+                Op::JumpIfTrue(target) => {
+                    if self.pop().is_truthy() {
+                        act.pc = target as usize;
+                    }
+                },
+                Op::JumpIfNil(target) => {
+                    if self.pop().is_nil() {
+                        act.pc = target as usize;
+                    }
+                },
+
+                Op::Binary(op) => {
+                    let len = self.stack.len();
+                    let (l, r) = (self.stack[len - 2], self.stack[len - 1]);
+                    let v = match (op, self.fast_binary_op(op, l, r)) {
+                        (_, Some(v)) => v,
+                        (OperatorSymbol::RANGE, _) | (OperatorSymbol::EXCL_RANGE, _) | (OperatorSymbol::NOT_MATCH, _) => self.binary_op(op, l, r)?,
+                        _ => {
+                            // Otherwise a send of the operator method, with the operands as
+                            // receiver and argument.
+                            let name = self.operator_sym(op);
+                            if let Some(exit) = self.send_from_stack(act, &chunk, pc, name, 1, CallFlags::NONE)? {
+                                return Ok(exit);
+                            }
+                            continue;
+                        }
+                    };
+                    self.stack.truncate(len - 2);
+                    self.stack.push(v);
+                },
+                Op::Unary(op) => {
+                    let v = self.unary_op(op, self.top())?;
+                    *self.stack.last_mut().unwrap() = v;
+                },
+                Op::Not => {
+                    let v = match self.top() {
+                        v @ Value::Object(_) => self.call(v, "!", &[])?,
+                        v => Value::from_bool(!v.is_truthy())
+                    };
+                    *self.stack.last_mut().unwrap() = v;
+                },
+                Op::Send { name, argc, flags } => {
+                    if let Some(exit) = self.send_from_stack(act, &chunk, pc, name, argc, flags)? {
+                        return Ok(exit);
+                    }
+                },
+                Op::Super { argc, flags, bare } => {
+                    let v = self.super_from_stack(argc, flags, bare)?;
+                    self.stack.push(v);
+                },
+                Op::Yield { argc, flags } => {
+                    let (start, mut args, block) = self.take_args(argc, flags);
+                    if let Some(block) = block {
+                        args.push(block);
+                    }
+                    let result = self.yield_block(self.frame().context.block, &args);
+                    self.stack.truncate(start);
+                    self.stack.push(result?);
+                },
+                Op::Block(index) | Op::Lambda(index) => {
+                    let (env, context, file) = self.block_capture();
+                    let is_lambda = matches!(chunk.code[pc], Op::Lambda(_));
+                    let chunk = chunk.chunk(index).clone();
+                    let block = self.new_proc(ProcBody::Compiled { chunk, env, context, file }, is_lambda);
+                    self.stack.push(block);
+                },
+
+                Op::Return => {
+                    let v = self.pop();
+                    let (id, target) = (self.frame().id, self.frame().context.return_target);
+                    if act.handlers.is_empty() && id == target {
+                        self.stack.truncate(act.base);
+                        return Ok(Exit::Finish(v));
+                    }
+                    return Err(Unwind::Return(v, target));
+                },
+                Op::Break => {
+                    let v = self.pop();
+                    return match self.frame().break_tag {
+                        Some(tag) => Err(Unwind::Break(v, tag)),
+                        None => Err(self.error(self.core.local_jump_error, String::from("break from proc-closure")))
+                    };
+                },
+                Op::Next => {
+                    let v = self.pop();
+                    return Err(Unwind::Next(v));
+                },
+                Op::Redo => return Err(Unwind::Redo),
+                Op::Retry => return Err(Unwind::Retry),
+                Op::Leave => {
+                    let v = self.pop();
+                    self.stack.truncate(act.base);
+                    return Ok(Exit::Finish(v));
+                },
+
+                Op::PushRescue(target) => self.push_handler(act, HandlerKind::Rescue, target, self.stack.len()),
+                Op::PushEnsure(target) => self.push_handler(act, HandlerKind::Ensure, target, self.stack.len()),
+                Op::PushGuard(target) => self.push_handler(act, HandlerKind::Guard, target, self.stack.len()),
+                Op::PushLoop { break_target, next_target } => {
+                    let tag = self.new_tag();
+                    let outer_tag = self.frame().break_tag;
+                    self.frame_mut().break_tag = Some(tag);
+                    self.push_handler(act, HandlerKind::Loop { tag, next_target, outer_tag }, break_target, self.stack.len());
+                },
+                Op::PushRescueBody { retry_target } => {
+                    let outer_error = self.global("$!");
+                    self.set_global("$!", self.top());
+                    let depth = self.stack.len() - 1;
+                    self.push_handler(act, HandlerKind::RescueBody { outer_error }, retry_target, depth);
+                },
+                Op::PopHandler => {
+                    let handler = act.handlers.pop().expect("handler stack underflow");
+                    match handler.kind {
+                        HandlerKind::Ensure => act.pending.push(None),
+                        HandlerKind::Loop { outer_tag, .. } => self.frame_mut().break_tag = outer_tag,
+                        HandlerKind::RescueBody { outer_error } => self.set_global("$!", outer_error),
+                        _ => {}
+                    }
+                },
+                Op::RescueMatch { splat } => {
+                    let classes = self.pop();
+                    let exc = self.top();
+                    let classes = if splat { self.array_items(classes).cloned().unwrap_or_else(|| vec![classes]) } else { vec![classes] };
+                    let mut matched = false;
+                    for class in classes {
+                        match class.as_object() {
+                            Some(r) if self.module_data(r).is_some() => {
+                                if self.is_a(exc, r) {
+                                    matched = true;
+                                    break;
+                                }
+                            },
+                            _ => return Err(self.type_error(String::from("class or module required for rescue clause")))
+                        }
+                    }
+                    self.stack.push(Value::from_bool(matched));
+                },
+                Op::RescueStandard => {
+                    let matched = self.is_a(self.top(), self.core.standard_error);
+                    self.stack.push(Value::from_bool(matched));
+                },
+                Op::Reraise => return Err(Unwind::Raise(self.pop())),
+                Op::EndEnsure => {
+                    if let Some(Some(unwind)) = act.pending.pop() {
+                        return Err(unwind);
+                    }
+                },
+                Op::CaseMatch { splat } => {
+                    let patterns = self.pop();
+                    let subject = self.top();
+                    let patterns = if splat { self.array_items(patterns).cloned().unwrap_or_else(|| vec![patterns]) } else { vec![patterns] };
+                    let mut matched = false;
+                    for pattern in patterns {
+                        if self.case_equal(pattern, subject)? {
+                            matched = true;
+                            break;
+                        }
+                    }
+                    self.stack.push(Value::from_bool(matched));
+                },
+                Op::AnyTruthy => {
+                    let v = self.pop();
+                    let any = match self.array_items(v) {
+                        Some(items) => items.iter().any(|i| i.is_truthy()),
+                        None => v.is_truthy()
+                    };
+                    self.stack.push(Value::from_bool(any));
+                },
+                Op::ForItems => {
+                    let items = self.call(self.top(), "to_a", &[])?;
+                    let items = match self.array_items(items) {
+                        Some(items) => items.clone(),
+                        None => return Err(self.type_error(String::from("can't iterate")))
+                    };
+                    let items = self.new_array(items);
+                    self.stack.push(items);
+                },
+                Op::ForNext(exit) => {
+                    let len = self.stack.len();
+                    let (items, index) = (self.stack[len - 2], self.stack[len - 1]);
+                    let index = match index {
+                        Value::Integer(i) => i as usize,
+                        _ => unreachable!("for loop index")
+                    };
+                    match self.array_items(items).and_then(|items| items.get(index).copied()) {
+                        Some(item) => {
+                            self.stack[len - 1] = Value::Integer(index as i64 + 1);
+                            self.stack.push(item);
+                        },
+                        None => act.pc = exit as usize
+                    }
+                },
+                Op::Defined(check) => {
+                    let description = self.defined_check(check)?;
+                    let v = match description {
+                        Some(description) => self.new_string(description.to_string()),
+                        None => Value::Nil
+                    };
+                    self.stack.push(v);
+                },
+
+                Op::DefineMethod { chunk: index, singleton } => {
+                    let target = if singleton {
+                        let on = self.pop();
+                        self.singleton_class(on)?
+                    } else {
+                        self.frame().context.def_target
+                    };
+                    let body = chunk.chunk(index).clone();
+                    let name = body.name;
+                    let method = UserMethod {
+                        name,
+                        body: MethodBody::Compiled(body),
+                        owner: target,
+                        file: self.frame().file,
+                        cref: self.frame().context.cref.clone()
+                    };
+                    self.define_method(target, name, MethodEntry::User(Rc::new(method)));
+                    self.stack.push(Value::Symbol(name));
+                },
+                Op::DefineClass { name, chunk: index, scoped, superclass } => {
+                    let superclass = if superclass { Some(self.pop()) } else { None };
+                    let container = self.definition_container(scoped)?;
+                    let class = self.open_class(container, name, superclass)?;
+                    let v = self.run_module_body(class, chunk.chunk(index), "class")?;
+                    self.stack.push(v);
+                },
+                Op::DefineModule { name, chunk: index, scoped } => {
+                    let container = self.definition_container(scoped)?;
+                    let module = self.open_module(container, name)?;
+                    let v = self.run_module_body(module, chunk.chunk(index), "module")?;
+                    self.stack.push(v);
+                },
+                Op::Alias { new_name, old_name } => self.alias_method(new_name, old_name)?,
+                Op::Undef(name) => self.undef_method(name),
+                Op::EndBlock(index) => {
+                    let block = chunk.chunk(index).clone();
+                    if self.first_reached(block.line, block.col) {
+                        let (env, context, file) = self.block_capture();
+                        let handler = self.new_proc(ProcBody::Compiled { chunk: block, env, context, file }, false);
+                        self.at_exit(handler);
+                    }
+                },
+                Op::Invalid(index) => {
+                    let message = chunk.text(index).to_string();
+                    return Err(self.error(self.core.syntax_error, message));
+                }
+            }
+        }
+    }
+
+    fn array_append(&mut self, array: Value, values: &[Value]) {
+        if let Some(ObjectKind::Array(items)) = array.as_object().map(|r| &mut self.heap.get_mut(r).kind) {
+            items.extend_from_slice(values);
+        }
+    }
+
+    // Takes a call's arguments off the stack, leaving them there while the call runs. Returns
+    // where they start, the arguments with any splat expanded, and the block.
+    fn take_args(&mut self, argc: u32, flags: CallFlags) -> (usize, Vec<Value>, Option<Value>) {
+        let end = if flags.has_block() { self.stack.len() - 1 } else { self.stack.len() };
+        let start = end - argc as usize;
+        let block = if flags.has_block() { Some(self.top()).filter(|b| !b.is_nil()) } else { None };
+        let mut args: Vec<Value> = Vec::with_capacity(argc as usize);
+        for (i, v) in self.stack[start..end].iter().enumerate() {
+            match self.array_items(*v) {
+                Some(items) if i == 0 && flags.contains(CallFlags::SPLAT) => args.extend_from_slice(items),
+                _ => args.push(*v)
+            }
+        }
+        (start, args, block)
+    }
+
+    // Finds a method through the call site's inline cache, falling back to the global method cache.
+    fn cached_lookup(&mut self, chunk: &Chunk, pc: usize, recv: Value, name: Sym) -> Option<(MethodEntry, ObjRef)> {
+        let class = self.dispatch_class(recv);
+        let serial = self.method_cache.get_serial();
+        if let Some(hit) = chunk.sites.lookup(pc, class, serial) {
+            return Some(hit);
+        }
+        let found = self.lookup_method(class, name);
+        if let Some((method, owner)) = found.as_ref().filter(|_| self.method_cache.is_enabled()) {
+            chunk.sites.record(pc, class, serial, method, *owner);
+        }
+        found
+    }
+
+    // Sends to the receiver and arguments on the stack. A compiled method is returned as an
+    // activation to run, or replaces this one for a tail call; anything else is called here and
+    // its result pushed.
+    fn send_from_stack(&mut self, act: &mut Activation, chunk: &Chunk, pc: usize, name: Sym, argc: u32, flags: CallFlags) -> Result<Option<Exit>, Unwind> {
+        let (start, args, block) = self.take_args(argc, flags);
+        let recv = self.stack[start - 1];
+        let found = self.cached_lookup(chunk, pc, recv, name);
+        if let Some((MethodEntry::User(method), owner)) = &found {
+            if let MethodBody::Compiled(callee) = &method.body {
+                let callee = callee.clone();
+                if flags.contains(CallFlags::TAIL) && act.handlers.is_empty() && act.call.is_some() {
+                    // The caller's frame is finished with, so the callee takes its place. Its
+                    // arguments are only in `args` until they're bound.
+                    self.roots.extend_from_slice(&args);
+                    self.root(recv);
+                    self.roots.extend(block);
+                    self.stack.truncate(act.base);
+                    let (line, col) = (self.frame().line, self.frame().col);
+                    self.pop_frame();
+                    let id = self.enter_method(method, *owner, recv, name, &args, block)?;
+                    let frame = self.frame_mut();
+                    frame.line = line;
+                    frame.col = col;
+                    if let Some(call) = act.call.as_mut() {
+                        call.frame_id = id;
+                    }
+                    act.pc = self.bind_chunk_params(&callee, &args, block, true)? as usize;
+                    act.chunk = callee;
+                    act.pending.clear();
+                    return Ok(Some(Exit::Resume));
+                }
+                let id = self.enter_method(method, *owner, recv, name, &args, block)?;
+                let entry = match self.bind_chunk_params(&callee, &args, block, true) {
+                    Ok(entry) => entry,
+                    Err(u) => {
+                        self.pop_frame();
+                        return Err(u);
+                    }
+                };
+                let assigned = args.last().copied().unwrap_or(Value::Nil);
+                let call = CallInfo { frame_id: id, stack_top: start - 1, flags, block, assigned };
+                return Ok(Some(Exit::Call(Activation::create(callee, entry, self.stack.len(), act.roots, Some(call)))));
+            }
+        }
+        let result = match found {
+            Some((entry, owner)) => self.call_method(entry, owner, recv, name, &args, block),
+            None => self.method_missing(recv, name, &args, block, flags.contains(CallFlags::VCALL))
+        };
+        self.stack.truncate(start - 1);
+        let v = self.finish_send(flags, block, args.last().copied().unwrap_or(Value::Nil), result)?;
+        self.stack.push(v);
+        Ok(None)
+    }
+
+    // A `break` from the call's literal block ends the call; an assignment evaluates to its value.
+    fn finish_send(&mut self, flags: CallFlags, block: Option<Value>, assigned: Value, result: EvalResult) -> EvalResult {
+        let result = if flags.contains(CallFlags::BLOCK_LITERAL) { self.catch_break(block, result) } else { result };
+        if flags.contains(CallFlags::ASSIGN) {
+            result?;
+            return Ok(assigned);
+        }
+        result
+    }
+
+    fn super_from_stack(&mut self, argc: u32, flags: CallFlags, bare: bool) -> EvalResult {
+        let (start, args, block) = self.take_args(argc, flags);
+        let method = match self.frame().context.method.clone() {
+            Some(m) => m,
+            None => {
+                self.stack.truncate(start);
+                return Err(self.error(self.core.runtime_error, String::from("super called outside of method")));
+            }
+        };
+        let args = if bare { method.args.clone() } else { args };
+        let block_value = if flags.contains(CallFlags::BLOCK_LITERAL) { block } else { block.or(self.frame().context.block) };
+        let result = self.call_super(&method, &args, block_value);
+        self.stack.truncate(start);
+        if flags.contains(CallFlags::BLOCK_LITERAL) { self.catch_break(block_value, result) } else { result }
+    }
+
+    fn definition_container(&mut self, scoped: bool) -> Result<ObjRef, Unwind> {
+        if scoped {
+            let scope = self.pop();
+            self.expect_module(scope)
+        } else {
+            Ok(self.frame().context.cref.last().copied().unwrap_or(self.core.object))
+        }
+    }
+
+    fn run_module_body(&mut self, module: ObjRef, chunk: &Rc<Chunk>, kind: &str) -> EvalResult {
+        let env = self.new_slot_env(None, chunk.locals.len());
+        self.push_module_frame(module, env, kind)?;
+        let result = self.run_chunk(chunk, 0);
+        self.pop_frame();
+        result
+    }
+
+    fn defined_check(&mut self, check: DefinedCheck) -> Result<Option<&'static str>, Unwind> {
+        Ok(match check {
+            DefinedCheck::InstanceVar(name) => {
+                let self_value = self.frame().context.self_value;
+                let has_ivar = self_value.as_object().is_some_and(|r| self.heap.get(r).ivars.contains_key(&name));
+                if has_ivar { Some("instance-variable") } else { None }
+            },
+            DefinedCheck::GlobalVar(name) => {
+                if self.globals.contains_key(&name) { Some("global-variable") } else { None }
+            },
+            DefinedCheck::Constant(name) => {
+                self.lookup_constant(name)?;
+                Some("constant")
+            },
+            DefinedCheck::ScopedConstant(name) => {
+                let module = self.pop();
+                match module.as_object().filter(|_| self.is_module(module)) {
+                    Some(r) if self.lookup_constant_in(r, name).is_some() => Some("constant"),
+                    _ => None
+                }
+            },
+            DefinedCheck::Method(name) => {
+                let recv = self.pop();
+                if self.responds_to(recv, name) { Some("method") } else { None }
+            },
+            DefinedCheck::SelfMethod(name) => {
+                let recv = self.frame().context.self_value;
+                if self.responds_to(recv, name) { Some("method") } else { None }
+            },
+            DefinedCheck::Yield => {
+                if self.frame().context.block.is_some() { Some("yield") } else { None }
+            },
+            DefinedCheck::Super => {
+                let recv = self.frame().context.self_value;
+                match self.frame().context.method.clone() {
+                    Some(method) if self.find_super_method(recv, &method).is_some_and(|(entry, _)| !matches!(entry, MethodEntry::Undefined)) => Some("super"),
+                    _ => None
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs `source` on both the tree walker and the VM and returns the VM's inspected result.
+    fn eval_both(source: &str) -> String {
+        let mut results = Vec::new();
+        for vm in [false, true] {
+            let mut interp = Interpreter::create();
+            interp.set_vm(vm);
+            let v = interp.eval_source("test", source).unwrap_or_else(|_| panic!("{} raised", source));
+            results.push(interp.inspect(v).unwrap());
+        }
+        assert_eq!(results[0], results[1], "{}", source);
+        results.pop().unwrap()
+    }
+
+    #[test]
+    fn runs_methods_blocks_and_classes() {
+        assert_eq!(eval_both("def fact(n)\n  if n <= 1 then 1 else n * fact(n - 1) end\nend\nfact(10)"), "3628800");
+        assert_eq!(eval_both("def f(a, b = a + 1, *r, c)\n  [a, b, r, c]\nend\n[f(1, 2), f(1, 2, 3, 4, 5)]"), "[[1, 2, [], 2], [1, 2, [3, 4], 5]]");
+        assert_eq!(eval_both("class A\n  def initialize(x)\n    @x = x\n  end\n  def x; @x; end\nend\nclass B < A\n  def x; super * 2; end\nend\nB.new(5).x"), "10");
+        assert_eq!(eval_both("n = 0\n[1, 2, 3].each { |x| n += x }\nn"), "6");
+    }
+
+    #[test]
+    fn unwinds_through_handlers_and_loops() {
+        assert_eq!(eval_both("tries = 0\nbegin\n  tries += 1\n  raise \"no\" if tries < 3\n  tries\nrescue => e\n  retry\nend"), "3");
+        assert_eq!(eval_both("log = []\ndef m(log)\n  return 1\nensure\n  log << :ensure\nend\n[m(log), log]"), "[1, [:ensure]]");
+        assert_eq!(eval_both("i = 0\nwhile true\n  i += 1\n  next if i < 5\n  break i * 2\nend"), "10");
+        assert_eq!(eval_both("def m\n  [1, 2].each { |v| return v + 40 }\nend\nm"), "41");
+        assert_eq!(eval_both("begin\n  raise TypeError, \"t\"\nrescue ArgumentError\n  1\nrescue TypeError => e\n  e.message\nend"), "\"t\"");
+    }
+
+    #[test]
+    fn compiled_calls_do_not_recurse_on_the_rust_stack() {
+        let mut interp = Interpreter::create();
+        interp.set_vm(true);
+        interp.set_max_depth(200_000);
+        let v = interp.eval_source("test", "def deep(n)\n  if n == 0 then 0 else 1 + deep(n - 1) end\nend\ndeep(100_000)").unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "100000");
+
+        interp.set_max_depth(100);
+        let v = interp.eval_source("test", "def count(n, acc)\n  return acc if n == 0\n  return count(n - 1, acc + 1)\nend\ncount(50_000, 0)").unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "50000");
+        let v = interp.eval_source("test", "begin\n  deep(1_000)\nrescue SystemStackError => e\n  e.message\nend").unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "\"stack level too deep\"");
+    }
+
+    #[test]
+    fn call_site_caches_see_redefinitions() {
+        let source = "class A\n  def m; :a; end\nend\nclass B < A; end\nmodule M\n  def m; :m; end\nend\n\
+            def call(x); x.m; end\nlog = [call(A.new), call(B.new), (call(1.0) rescue :none)]\n\
+            class B\n  include M\nend\nlog << call(B.new)\n\
+            class A\n  def m; :a2; end\n  alias n m\nend\nlog << call(A.new) << A.new.n\n\
+            class B\n  def m; :b; end\nend\nlog << call(B.new)\nclass B\n  undef m\nend\n\
+            log << (call(B.new) rescue :undefined)\nlog";
+        assert_eq!(eval_both(source), "[:a, :a, :none, :m, :a2, :a2, :b, :undefined]");
+        let source = "class V\n  def +(o); :v; end\nend\ndef add(a, b); a + b; end\nlog = [add(V.new, 1), add([1], [2])]\n\
+            class V\n  def +(o); :v2; end\nend\nclass Array\n  def +(o); :array; end\nend\n\
+            log << add(V.new, 1) << add([1], [2]) << (add(nil, 1) rescue :none)\nlog";
+        assert_eq!(eval_both(source), "[:v, [1, 2], :v2, :array, :none]");
+    }
+
+    #[test]
+    fn reuses_the_bytecode_cache_next_to_a_source() {
+        let dir = std::env::temp_dir().join(format!("jasper-jbc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("cached.lang");
+        std::fs::write(&source, "def twice(x)\n  x * 2\nend\ntwice(21)").unwrap();
+        for _ in 0..2 {
+            let mut interp = Interpreter::create();
+            interp.set_vm(true);
+            let v = interp.eval_file(&source).unwrap();
+            assert_eq!(interp.inspect(v).unwrap(), "42");
+            assert!(dir.join("cached.jbc").exists());
+        }
+        std::fs::write(&source, "[1, 2].size").unwrap();
+        let mut interp = Interpreter::create();
+        interp.set_vm(true);
+        let v = interp.eval_file(&source).unwrap();
+        assert_eq!(interp.inspect(v).unwrap(), "2");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn matches_defined_and_operator_assignment() {
+        assert_eq!(eval_both("x = 1\n[defined?(x), defined?(y), defined?(raise), defined?(String), defined?(@a)]"), "[\"local-variable\", nil, \"method\", \"constant\", nil]");
+        assert_eq!(eval_both("@x = nil\n[defined?(@x) && @x, defined?(@y) && @y]"), "[nil, nil]");
+        assert_eq!(eval_both("x = 5\n[defined?(x).nil?, defined?(y).nil?, defined? x.nil?]"), "[false, true, \"method\"]");
+        assert_eq!(eval_both("h = {a: 1}\nh[:a] += 2\nh[:b] ||= 5\n@c ||= 3\n[h, @c]"), "[{:a=>3, :b=>5}, 3]");
+    }
+}
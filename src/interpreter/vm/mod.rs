@@ -0,0 +1,6 @@
+pub mod bytecode;
+pub mod cache;
+pub mod compiler;
+pub mod disasm;
+pub mod inline_cache;
+pub mod machine;
@@ -1,19 +1,216 @@
-mod interpreter;
-
-use std::fs;
-use interpreter::parser::input_stream::InputStream;
-use interpreter::parser::token_stream::TokenStream;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process;
+use jasper::interpreter::diagnostics::{Diagnostic, Renderer};
+use jasper::interpreter::grammar::bnf::Grammar;
+use jasper::interpreter::grammar::conformance;
+use jasper::interpreter::interpreter::{Interpreter, Unwind};
+use jasper::interpreter::parser::input_stream::InputStream;
+use jasper::interpreter::parser::parser::Parser;
+use jasper::interpreter::parser::token_stream::TokenStream;
+use jasper::interpreter::runtime::symbols::SymbolTable;
+use jasper::interpreter::source_map::SourceMap;
+use jasper::interpreter::vm::compiler::Compiler;
+use jasper::interpreter::vm::disasm;
 
 fn main() {
-    let file_name: &str = "data/my_program.lang";
-    let mut lang_file = fs::read_to_string(file_name).expect("Something went wrong reading the file");
-    let mut is: InputStream = InputStream::from_string(&mut lang_file);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let code = match args.first().map(String::as_str) {
+        Some("grammar-check") => grammar_check(&args[1..]),
+        Some("parse") => dump_ast(args.get(1).map_or("data/my_program.lang", String::as_str)),
+        Some("disasm") => disassemble(args.get(1).map_or("data/my_program.lang", String::as_str)),
+        Some("tokens") => dump_tokens(args.get(1).map_or("data/my_program.lang", String::as_str)),
+        Some(_) => run(&args),
+        None => {
+            eprintln!("usage: jasper [--tree-walk] [--gc-stress] [--max-depth N] [-I dir]... FILE | tokens FILE | parse FILE | disasm FILE | grammar-check [grammar.bnf]");
+            2
+        }
+    };
+    process::exit(code);
+}
+
+fn load(file_name: &str) -> (SourceMap, jasper::interpreter::source_map::FileId) {
+    let mut sources = SourceMap::create();
+    match sources.load(Path::new(file_name)) {
+        Ok(file) => (sources, file),
+        Err(e) => {
+            eprintln!("{}: {}", file_name, e);
+            process::exit(2);
+        }
+    }
+}
+
+fn dump_tokens(file_name: &str) -> i32 {
+    let (sources, file) = load(file_name);
+    let text = sources.get(file).map_or("", |f| f.get_text());
+    let mut is: InputStream = InputStream::for_file(file, text);
     let mut ts: TokenStream = TokenStream::create(&mut is);
     while let Some(tok) = ts.read_next() {
         println!("{:?}", tok);
     }
+    let diagnostics = ts.take_diagnostics();
+    report(&diagnostics, &sources);
+    if diagnostics.is_empty() { 0 } else { 1 }
+}
 
+fn dump_ast(file_name: &str) -> i32 {
+    let (sources, file) = load(file_name);
+    let text = sources.get(file).map_or("", |f| f.get_text());
+    let mut is: InputStream = InputStream::for_file(file, text);
+    let mut ts: TokenStream = TokenStream::create(&mut is);
+    let output = Parser::create(&mut ts).parse();
+    print!("{}", output.program);
+    report(&output.diagnostics(), &sources);
+    if output.has_errors() { 1 } else { 0 }
+}
 
-    //let kw: Keyword = Keyword::from_string(String::from("end"));
-    //let kw_s: str = kw.to_str();
-}
\ No newline at end of file
+fn disassemble(file_name: &str) -> i32 {
+    let (sources, file) = load(file_name);
+    let text = sources.get(file).map_or("", |f| f.get_text());
+    let mut is: InputStream = InputStream::for_file(file, text);
+    let mut ts: TokenStream = TokenStream::create(&mut is);
+    let output = Parser::create(&mut ts).parse();
+    if output.has_errors() {
+        report(&output.diagnostics(), &sources);
+        return 1;
+    }
+    let mut symbols = SymbolTable::create();
+    let chunk = Compiler::create(&mut symbols).compile_program(&output.program, "<main>");
+    print!("{}", disasm::disassemble(&chunk, &symbols, sources.get(file)));
+    0
+}
+
+fn report(diagnostics: &[Diagnostic], sources: &SourceMap) {
+    let colour = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let renderer = Renderer::create(colour);
+    for diagnostic in diagnostics.iter() {
+        eprintln!("{}", renderer.render(diagnostic, sources));
+    }
+}
+
+// jasper [--tree-walk] [--gc-stress] [--max-depth N] [-I dir]... FILE
+fn run(args: &[String]) -> i32 {
+    let mut load_path: Vec<String> = Vec::new();
+    let mut file_name: Option<String> = None;
+    let mut vm = true;
+    let mut gc_stress = false;
+    let mut max_depth: Option<usize> = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--tree-walk" => vm = false,
+            "--gc-stress" => gc_stress = true,
+            "--max-depth" => match iter.next().map(|n| n.parse::<usize>()) {
+                Some(Ok(n)) => max_depth = Some(n),
+                _ => {
+                    eprintln!("jasper: --max-depth needs a number of frames");
+                    return 2;
+                }
+            },
+            "-I" => load_path.extend(iter.next().cloned()),
+            dir if dir.starts_with("-I") => load_path.push(dir[2..].to_string()),
+            other => {
+                file_name = Some(other.to_string());
+                break;
+            }
+        }
+    }
+    let file_name = match file_name {
+        Some(file_name) => file_name,
+        None => {
+            eprintln!("jasper: no program file given");
+            return 2;
+        }
+    };
+    let mut interp = Interpreter::create();
+    interp.set_vm(vm);
+    interp.set_gc_stress(gc_stress);
+    if let Some(depth) = max_depth {
+        interp.set_max_depth(depth);
+    }
+    for dir in load_path.iter() {
+        interp.add_load_path(Path::new(dir));
+    }
+    let result = interp.eval_file(Path::new(&file_name));
+    let result = interp.run_exit_handlers(result);
+    exit_status(&mut interp, result)
+}
+
+// Reports an exception that ended the program and picks the exit status.
+fn exit_status(interp: &mut Interpreter, result: Result<jasper::interpreter::runtime::value::Value, Unwind>) -> i32 {
+    let exc = match result {
+        Ok(_) | Err(Unwind::Return(..)) | Err(Unwind::Next(_)) => return 0,
+        Err(Unwind::Raise(exc)) => exc,
+        Err(_) => {
+            eprintln!("jasper: break or redo outside of a loop or block");
+            return 1;
+        }
+    };
+    if interp.is_a(exc, interp.core.system_exit) {
+        return match interp.call(exc, "status", &[]) {
+            Ok(jasper::interpreter::runtime::value::Value::Integer(status)) => status as i32,
+            _ => 1
+        };
+    }
+    let diagnostics = if interp.is_a(exc, interp.core.syntax_error) && !interp.syntax_diagnostics.is_empty() {
+        std::mem::take(&mut interp.syntax_diagnostics)
+    } else {
+        vec![interp.exception_diagnostic(exc)]
+    };
+    report(&diagnostics, &interp.sources);
+    1
+}
+
+// jasper grammar-check [grammar.bnf] [--samples N] [--seed N]
+// Sentences only the parser accepts pass when `grammar.allow.bnf`, if there is one, describes them.
+fn grammar_check(args: &[String]) -> i32 {
+    let mut bnf_file: &str = "test.bnf";
+    let mut samples: usize = 500;
+    let mut seed: u64 = 1;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--samples" => samples = iter.next().and_then(|v| v.parse().ok()).unwrap_or(samples),
+            "--seed" => seed = iter.next().and_then(|v| v.parse().ok()).unwrap_or(seed),
+            other => bnf_file = other
+        }
+    }
+    let grammar = match Grammar::from_file(bnf_file) {
+        Ok(grammar) => grammar,
+        Err(e) => {
+            eprintln!("{}: {}", bnf_file, e);
+            return 2;
+        }
+    };
+    // What the parser accepts beyond the grammar is listed next to it, e.g. in `test.allow.bnf`.
+    let allow_file = Path::new(bnf_file).with_extension("allow.bnf");
+    let allowed = if allow_file.exists() {
+        match Grammar::from_file(&allow_file.to_string_lossy()) {
+            Ok(allowed) => Some(allowed),
+            Err(e) => {
+                eprintln!("{}: {}", allow_file.display(), e);
+                return 2;
+            }
+        }
+    } else {
+        None
+    };
+    let report = conformance::check(&grammar, allowed.as_ref(), samples, seed);
+    for issue in report.issues.iter() {
+        println!("{}: {}", bnf_file, issue);
+    }
+    println!("checked {} sentences from {} ({} could not be generated)", report.checked, bnf_file, report.ungenerated);
+    println!("accepted by the grammar, rejected by the parser: {}", report.grammar_only.len());
+    for d in report.grammar_only.iter() {
+        println!("  {:?}: {}", d.sentence, d.reason);
+    }
+    if allowed.is_some() {
+        println!("accepted by the parser, allowed by {}: {}", allow_file.display(), report.allowed);
+    }
+    println!("accepted by the parser, rejected by the grammar: {}", report.implementation_only.len());
+    for d in report.implementation_only.iter() {
+        println!("  {:?}: {}", d.sentence, d.reason);
+    }
+    if report.is_conformant() { 0 } else { 1 }
+}